@@ -9,10 +9,11 @@ XCODE_PATH="${XCODE_PATH:-"$PWD/Xcode_12.2.xip"}"
 # Build (only) the Liana GUI on Windows.
 docker build . -t liana_cross_win -f contrib/reproducible/docker/windows.Dockerfile
 docker run --rm -ti \
-    -v "$TARGET_DIR/gui":/liana/target \
+    -v "$TARGET_DIR/gui":/liana/gui/target \
     -v "$PWD/contrib/reproducible/docker":/liana/docker \
-    -v "$PWD/gui/src":/liana/src \
-    -v "$PWD/gui/static":/liana/static \
+    -v "$PWD/src":/liana/src \
+    -v "$PWD/gui/src":/liana/gui/src \
+    -v "$PWD/gui/static":/liana/gui/static \
     liana_cross_win
 
 # Sanity check the given MacOS SDK is the expected one.
@@ -9,12 +9,14 @@ set -xe
 # Build the GUI for Windows. The Windows Portable Execution (PE) format contains some timestamps.
 # Instruct ld to set them to 0.
 alias cargo="/liana/rust-1.65.0-x86_64-unknown-linux-gnu/cargo/bin/cargo"
+cd gui/
 RUSTFLAGS="-Clink-arg=-Wl,--no-insert-timestamp" \
     cargo rustc \
         --release \
         --target x86_64-pc-windows-gnu
+cd ..
 
 # Avoid having to get root on the host to remove the target dir.
-chmod -R a+rw target/
+chmod -R a+rw gui/target/
 
 set +xe
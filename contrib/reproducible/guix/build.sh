@@ -17,11 +17,6 @@ replace-with = "vendored_sources"
 git = "https://github.com/darosior/rust-miniscript"
 branch = "multipath_descriptors_on_9.0"
 replace-with = "vendored_sources"
-
-[source."https://github.com/wizardsardine/liana"]
-git = "https://github.com/wizardsardine/liana"
-branch = "master"
-replace-with = "vendored_sources"
 EOF
 
 # We need to set RUSTC_BOOTSTRAP=1 as a workaround to be able to use unstable
@@ -117,11 +117,15 @@ for project_folder in "" "gui"; do
     # NOTE: it looks like "--rebuild-cache" is necessary for the IS_GUI variable to
     # be taken into account when building the container (otherwise the GUI container could
     # miss some dependencies).
+    # The GUI depends on the daemon sources at the root of the repository, the container mirrors
+    # the layout of the repository for both builds.
     IS_GUI="$IS_GUI" time_machine shell --no-cwd \
-               --expose="$PROJECT_ROOT/src=/liana/src" \
-               --expose="$PWD/gui/static=/liana/static" \
-               --expose="$PROJECT_ROOT/Cargo.toml=/liana/Cargo.toml" \
-               --expose="$BUILD_ROOT/Cargo.lock=/liana/Cargo.lock" \
+               --expose="$PWD/src=/liana/src" \
+               --expose="$PWD/Cargo.toml=/liana/Cargo.toml" \
+               --expose="$PWD/gui/src=/liana/gui/src" \
+               --expose="$PWD/gui/static=/liana/gui/static" \
+               --expose="$PWD/gui/Cargo.toml=/liana/gui/Cargo.toml" \
+               --expose="$BUILD_ROOT/Cargo.lock=/liana/$project_folder/Cargo.lock" \
                --expose="$PWD/contrib/reproducible/guix/build.sh=/liana/build.sh" \
                --expose="$PROJECT_VENDOR_DIR=/vendor" \
                --share="$PROJECT_OUT_DIR=/out" \
@@ -132,7 +136,7 @@ for project_folder in "" "gui"; do
                --rebuild-cache \
                -m $PWD/contrib/reproducible/guix/manifest.scm \
                -- env CC=gcc VENDOR_DIR="$PROJECT_VENDOR_DIR" TARGET_DIR="$PROJECT_OUT_DIR" IS_GUI="$IS_GUI" JOBS="$JOBS" \
-                  /bin/sh -c "cd /liana && ./build.sh"
+                  /bin/sh -c "cd /liana/$project_folder && /liana/build.sh"
 done
 
 set +ex
@@ -0,0 +1,335 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use liana::miniscript::bitcoin::{Address, Network};
+use serde::{Deserialize, Serialize};
+
+///! AddressBook is the module to handle the recipients the user saved with a name, to pay them
+///! again without copying their address. They are stored next to the settings file.
+pub const DEFAULT_FILE_NAME: &str = "address_book.json";
+
+/// A recipient saved by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Contact {
+    pub name: String,
+    pub address: String,
+}
+
+impl Contact {
+    /// Whether the address can be paid on this network. The file may have been edited by hand or
+    /// copied from the data directory of another network.
+    pub fn is_valid_for(&self, network: Network) -> bool {
+        Address::from_str(&self.address)
+            .map(|address| address.is_valid_for_network(network))
+            .unwrap_or(false)
+    }
+}
+
+impl std::fmt::Display for Contact {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.address)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AddressBook {
+    contacts: Vec<Contact>,
+}
+
+impl AddressBook {
+    /// Read the address book of the wallet, empty if the file was never written. The contacts
+    /// whose address is not valid for the network are kept, to be flagged to the user.
+    pub fn from_file(datadir: PathBuf, network: Network) -> Result<Self, AddressBookError> {
+        let path = Self::path(datadir, network);
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(AddressBookError::ReadingFile(format!(
+                    "Reading address book file: {}",
+                    e
+                )))
+            }
+        };
+        let address_book: Self = serde_json::from_slice(&content)
+            .map_err(|e| AddressBookError::Corrupt(e.to_string()))?;
+        for contact in address_book.invalid_contacts(network) {
+            tracing::warn!(
+                "Address of contact '{}' is not valid for {}: {}",
+                contact.name,
+                network,
+                contact.address
+            );
+        }
+        Ok(address_book)
+    }
+
+    /// Read the address book like `from_file`, but start with an empty one if the file is
+    /// corrupt for the wallet to still load. The corrupt file is moved aside so it's not
+    /// overwritten when a contact is saved.
+    pub fn from_file_or_default(
+        datadir: PathBuf,
+        network: Network,
+    ) -> Result<Self, AddressBookError> {
+        match Self::from_file(datadir.clone(), network) {
+            Err(AddressBookError::Corrupt(e)) => {
+                let path = Self::path(datadir, network);
+                let backup_path = path.with_file_name(format!("{}.corrupt", DEFAULT_FILE_NAME));
+                tracing::error!(
+                    "Invalid address book file: {}. Moving it to '{}' and starting without contacts.",
+                    e,
+                    backup_path.display()
+                );
+                fs::rename(&path, &backup_path)
+                    .map_err(|e| AddressBookError::WritingFile(e.to_string()))?;
+                Ok(Self::default())
+            }
+            res => res,
+        }
+    }
+
+    /// Write the address book to a temporary file which then atomically replaces the address
+    /// book file.
+    pub fn to_file(&self, datadir: PathBuf, network: Network) -> Result<(), AddressBookError> {
+        let path = Self::path(datadir, network);
+        let content = serde_json::to_string_pretty(&self)
+            .map_err(|e| AddressBookError::WritingFile(e.to_string()))?;
+        let tmp_path = path.with_file_name(format!("{}.tmp", DEFAULT_FILE_NAME));
+        if let Err(e) = write_synced(&tmp_path, content.as_bytes()) {
+            tracing::warn!("failed to write to file: {:?}", e);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(AddressBookError::WritingFile(e.to_string()));
+        }
+        fs::rename(&tmp_path, &path).map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            AddressBookError::WritingFile(e.to_string())
+        })
+    }
+
+    fn path(datadir: PathBuf, network: Network) -> PathBuf {
+        let mut path = datadir;
+        path.push(network.to_string());
+        path.push(DEFAULT_FILE_NAME);
+        path
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// The contacts whose address can be paid on this network.
+    pub fn valid_contacts(&self, network: Network) -> impl Iterator<Item = &Contact> {
+        self.contacts
+            .iter()
+            .filter(move |contact| contact.is_valid_for(network))
+    }
+
+    /// The contacts whose address cannot be paid on this network.
+    pub fn invalid_contacts(&self, network: Network) -> impl Iterator<Item = &Contact> {
+        self.contacts
+            .iter()
+            .filter(move |contact| !contact.is_valid_for(network))
+    }
+
+    /// The contact with this address, if any.
+    pub fn contact(&self, address: &str) -> Option<&Contact> {
+        self.contacts
+            .iter()
+            .find(|contact| contact.address == address)
+    }
+
+    /// The contacts payable on this network whose name or address contains the query, ignoring
+    /// the case.
+    pub fn search<'a>(
+        &'a self,
+        query: &str,
+        network: Network,
+    ) -> impl Iterator<Item = &'a Contact> {
+        let query = query.trim().to_lowercase();
+        self.valid_contacts(network).filter(move |contact| {
+            !query.is_empty()
+                && (contact.name.to_lowercase().contains(&query)
+                    || contact.address.to_lowercase().contains(&query))
+        })
+    }
+
+    /// Add a contact, or replace the one at the given index.
+    pub fn set(&mut self, index: Option<usize>, contact: Contact) -> Result<(), AddressBookError> {
+        let contact = Contact {
+            name: contact.name.trim().to_string(),
+            address: contact.address.trim().to_string(),
+        };
+        if contact.name.is_empty() {
+            return Err(AddressBookError::InvalidContact(
+                "The name is empty".to_string(),
+            ));
+        }
+        if Address::from_str(&contact.address).is_err() {
+            return Err(AddressBookError::InvalidContact(format!(
+                "'{}' is not an address",
+                contact.address
+            )));
+        }
+        if let Some((_, other)) = self
+            .contacts
+            .iter()
+            .enumerate()
+            .find(|(i, c)| Some(*i) != index && c.address == contact.address)
+        {
+            return Err(AddressBookError::InvalidContact(format!(
+                "The address is already saved as '{}'",
+                other.name
+            )));
+        }
+        match index.and_then(|i| self.contacts.get_mut(i)) {
+            Some(existing) => *existing = contact,
+            None => self.contacts.push(contact),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.contacts.len() {
+            self.contacts.remove(index);
+        }
+    }
+}
+
+fn write_synced(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+#[derive(Debug, Clone)]
+pub enum AddressBookError {
+    ReadingFile(String),
+    Corrupt(String),
+    WritingFile(String),
+    InvalidContact(String),
+}
+
+impl std::fmt::Display for AddressBookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ReadingFile(e) => write!(f, "Error while reading file: {}", e),
+            Self::Corrupt(e) => write!(f, "Invalid address book file: {}", e),
+            Self::WritingFile(e) => write!(f, "Error while writing file: {}", e),
+            Self::InvalidContact(e) => write!(f, "Invalid contact: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAINNET_ADDRESS: &str = "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c";
+    const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+    fn contact(name: &str, address: &str) -> Contact {
+        Contact {
+            name: name.to_string(),
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_address_book_edit() {
+        let mut book = AddressBook::default();
+        book.set(None, contact(" Alice ", MAINNET_ADDRESS)).unwrap();
+        book.set(None, contact("Bob", TESTNET_ADDRESS)).unwrap();
+        assert_eq!(book.contacts()[0], contact("Alice", MAINNET_ADDRESS));
+
+        // Invalid contacts are rejected.
+        assert!(book.set(None, contact("", MAINNET_ADDRESS)).is_err());
+        assert!(book.set(None, contact("Carol", "not an address")).is_err());
+        assert!(book.set(None, contact("Carol", MAINNET_ADDRESS)).is_err());
+
+        // A contact may be renamed without changing its address.
+        book.set(Some(0), contact("Alice Smith", MAINNET_ADDRESS))
+            .unwrap();
+        assert_eq!(book.contacts().len(), 2);
+        assert_eq!(book.contact(MAINNET_ADDRESS).unwrap().name, "Alice Smith");
+
+        book.remove(1);
+        book.remove(5);
+        assert_eq!(book.contacts(), &[contact("Alice Smith", MAINNET_ADDRESS)]);
+    }
+
+    #[test]
+    fn test_address_book_network() {
+        let mut book = AddressBook::default();
+        book.set(None, contact("Alice", MAINNET_ADDRESS)).unwrap();
+        book.set(None, contact("Bob", TESTNET_ADDRESS)).unwrap();
+
+        let names = |contacts: Vec<&Contact>| -> Vec<String> {
+            contacts.into_iter().map(|c| c.name.clone()).collect()
+        };
+        assert_eq!(
+            names(book.valid_contacts(Network::Bitcoin).collect()),
+            vec!["Alice"]
+        );
+        assert_eq!(
+            names(book.invalid_contacts(Network::Bitcoin).collect()),
+            vec!["Bob"]
+        );
+        assert_eq!(
+            names(book.valid_contacts(Network::Testnet).collect()),
+            vec!["Bob"]
+        );
+
+        // Only the contacts payable on the network are suggested.
+        assert_eq!(
+            names(book.search("ALI", Network::Bitcoin).collect()),
+            vec!["Alice"]
+        );
+        assert_eq!(
+            names(book.search("bc1q34", Network::Bitcoin).collect()),
+            vec!["Alice"]
+        );
+        assert!(book.search("bob", Network::Bitcoin).next().is_none());
+        assert!(book.search(" ", Network::Bitcoin).next().is_none());
+    }
+
+    #[test]
+    fn test_address_book_file() {
+        let datadir =
+            std::env::temp_dir().join(format!("liana-gui-address-book-{}", std::process::id()));
+        fs::create_dir_all(datadir.join("bitcoin")).unwrap();
+
+        // Nothing saved yet.
+        let book = AddressBook::from_file(datadir.clone(), Network::Bitcoin).unwrap();
+        assert_eq!(book, AddressBook::default());
+
+        let mut book = AddressBook::default();
+        book.set(None, contact("Alice", MAINNET_ADDRESS)).unwrap();
+        book.set(None, contact("Bob", TESTNET_ADDRESS)).unwrap();
+        book.to_file(datadir.clone(), Network::Bitcoin).unwrap();
+
+        // The contacts invalid for the network are kept.
+        let read = AddressBook::from_file(datadir.clone(), Network::Bitcoin).unwrap();
+        assert_eq!(read, book);
+
+        let path = datadir.join("bitcoin").join(DEFAULT_FILE_NAME);
+        fs::write(&path, "{").unwrap();
+        assert!(matches!(
+            AddressBook::from_file(datadir.clone(), Network::Bitcoin),
+            Err(AddressBookError::Corrupt(_))
+        ));
+
+        // The wallet loads without contacts, and the corrupt file is kept aside.
+        let book = AddressBook::from_file_or_default(datadir.clone(), Network::Bitcoin).unwrap();
+        assert_eq!(book, AddressBook::default());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(path.with_file_name(format!("{}.corrupt", DEFAULT_FILE_NAME)))
+                .unwrap(),
+            "{"
+        );
+
+        fs::remove_dir_all(&datadir).unwrap();
+    }
+}
@@ -1,13 +1,60 @@
-use crate::daemon::model::{Coin, SpendTx};
-use liana::miniscript::bitcoin::Network;
+use crate::{
+    app::{
+        address_book::AddressBook, labels::Labels, price::Price, settings::ConfirmationTiers,
+        unit::Unit, wallet::GAP_LIMIT,
+    },
+    daemon::model::{Coin, FeeEstimates, SpendTx},
+};
+use liana::miniscript::bitcoin::{Amount, Network};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Confirmation target, in blocks, of the feerate a consolidation is suggested at. It is not
+/// urgent, but shouldn't take hours either.
+const CONSOLIDATION_CONF_TARGET: u16 = 6;
+
+/// Approximate number of blocks mined in a month.
+const BLOCKS_PER_MONTH: i32 = 144 * 30;
+
+/// Number of rescan progress samples the remaining time is estimated from. With a sample at
+/// each tick, the estimate is smoothed over the last minute.
+const RESCAN_PROGRESS_SAMPLES: usize = 12;
 
 #[derive(Debug)]
 pub struct Cache {
     pub network: Network,
     pub blockheight: i32,
+    pub sync_progress: f64,
     pub coins: Vec<Coin>,
+    /// Summary of the spent coins dropped from `coins` as too old to be displayed.
+    pub hidden_coins: HiddenCoins,
     pub spend_txs: Vec<SpendTx>,
     pub rescan_progress: Option<f64>,
+    /// Recent samples of the rescan progress, to estimate its remaining time.
+    pub rescan_samples: RescanSamples,
+    /// Minimum feerate in sats/vb accepted in the mempool of the node, refreshed at each tick.
+    pub mempool_min_feerate: u64,
+    /// Number of addresses past the last used one the daemon watches, from its configuration.
+    pub gap_limit: u32,
+    /// Feerate estimates of the node, refreshed once per new block.
+    pub fee_estimates: Option<FeeEstimates>,
+    /// Unit the amounts are displayed in, from the wallet settings.
+    pub unit: Unit,
+    /// Last price of bitcoin in the fiat currency of the wallet, if one is set and a price
+    /// source is configured.
+    pub price: Option<Price>,
+    /// Confirmations after which an amount is final, from the wallet settings.
+    pub confirmation_tiers: ConfirmationTiers,
+    /// Whether the wallet is a watch-only monitoring instance, from the wallet settings.
+    pub watch_only: bool,
+    /// Labels of the transactions and coins, from the wallet labels file.
+    pub labels: Labels,
+    /// Recipients saved by the user, from the wallet address book file.
+    pub address_book: AddressBook,
+    /// Whether the user dismissed the consolidation suggestion of the overview.
+    pub consolidation_suggestion_dismissed: bool,
 }
 
 impl std::default::Default for Cache {
@@ -15,9 +62,246 @@ impl std::default::Default for Cache {
         Self {
             network: Network::Bitcoin,
             blockheight: 0,
+            sync_progress: 0.0,
             coins: Vec::new(),
+            hidden_coins: HiddenCoins::default(),
             spend_txs: Vec::new(),
             rescan_progress: None,
+            rescan_samples: RescanSamples::default(),
+            mempool_min_feerate: 1,
+            gap_limit: GAP_LIMIT,
+            fee_estimates: None,
+            unit: Unit::default(),
+            price: None,
+            confirmation_tiers: ConfirmationTiers::default(),
+            watch_only: false,
+            labels: Labels::default(),
+            address_book: AddressBook::default(),
+            consolidation_suggestion_dismissed: false,
+        }
+    }
+}
+
+impl Cache {
+    /// Set the coins of the cache. If a number of months to display is given, the coins spent
+    /// by a transaction confirmed before are only kept as an aggregate. This only affects what
+    /// is displayed: the daemon keeps all the coins. The balance only depends on unspent coins
+    /// and is not affected.
+    pub fn set_coins(&mut self, coins: Vec<Coin>, displayed_months: Option<u32>) {
+        let (coins, hidden) = match displayed_months {
+            Some(months) => hide_old_coins(
+                coins,
+                self.blockheight - (months as i32).saturating_mul(BLOCKS_PER_MONTH),
+            ),
+            None => (coins, HiddenCoins::default()),
+        };
+        self.coins = coins;
+        self.hidden_coins = hidden;
+    }
+
+    /// The feerate to consolidate coins at, from the estimates of the node. It is never below the
+    /// minimum feerate of the mempool. None if there is no estimate yet.
+    pub fn consolidation_feerate(&self) -> Option<u64> {
+        self.fee_estimates
+            .as_ref()
+            .and_then(|estimates| estimates.feerate(CONSOLIDATION_CONF_TARGET))
+            .map(|feerate| feerate.max(self.mempool_min_feerate))
+    }
+
+    /// Set the progress of the ongoing rescan, if any, as reported at this instant.
+    pub fn set_rescan_progress(&mut self, progress: Option<f64>, now: Instant) {
+        match progress {
+            Some(p) => self.rescan_samples.push(p, now),
+            None => self.rescan_samples = RescanSamples::default(),
+        }
+        self.rescan_progress = progress;
+    }
+}
+
+/// The last samples of the progress of a rescan.
+#[derive(Debug, Default)]
+pub struct RescanSamples(VecDeque<(Instant, f64)>);
+
+impl RescanSamples {
+    fn push(&mut self, progress: f64, now: Instant) {
+        // A progress going backward is another rescan.
+        if matches!(self.0.back(), Some((_, p)) if progress < *p) {
+            self.0.clear();
+        }
+        self.0.push_back((now, progress));
+        if self.0.len() > RESCAN_PROGRESS_SAMPLES {
+            self.0.pop_front();
+        }
+    }
+
+    /// Estimate the time remaining until the rescan completes from the average pace over the
+    /// samples. None until there are two samples or while the progress stalls over all of them.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let (first_time, first_progress) = self.0.front()?;
+        let (last_time, last_progress) = self.0.back()?;
+        let progressed = last_progress - first_progress;
+        if progressed <= 0.0 {
+            return None;
         }
+        let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+        Some(Duration::from_secs_f64(
+            elapsed * (1.0 - last_progress).max(0.0) / progressed,
+        ))
+    }
+}
+
+/// Aggregate of spent coins not displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HiddenCoins {
+    pub count: usize,
+    pub amount: Amount,
+}
+
+/// Split the coins between the ones to display and the summary of the ones spent by a
+/// transaction confirmed before the given height.
+pub fn hide_old_coins(coins: Vec<Coin>, before_height: i32) -> (Vec<Coin>, HiddenCoins) {
+    let mut hidden = HiddenCoins::default();
+    let coins = coins
+        .into_iter()
+        .filter(|coin| match coin.spend_info.and_then(|info| info.height) {
+            Some(height) if height < before_height => {
+                hidden.count += 1;
+                hidden.amount += coin.amount;
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    (coins, hidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::{commands::LCSpendInfo, miniscript::bitcoin};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hide_old_coins() {
+        let txid = bitcoin::Txid::from_str(
+            "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5",
+        )
+        .unwrap();
+        let coins = vec![
+            // Unspent.
+            Coin {
+                outpoint: bitcoin::OutPoint { txid, vout: 0 },
+                amount: bitcoin::Amount::from_sat(1),
+                block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
+                spend_info: None,
+            },
+            // Spent but unconfirmed.
+            Coin {
+                outpoint: bitcoin::OutPoint { txid, vout: 1 },
+                amount: bitcoin::Amount::from_sat(10),
+                block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
+                spend_info: Some(LCSpendInfo {
+                    txid,
+                    height: None,
+                    settled: false,
+                    replaced_txid: None,
+                }),
+            },
+            // Spent a long time ago.
+            Coin {
+                outpoint: bitcoin::OutPoint { txid, vout: 2 },
+                amount: bitcoin::Amount::from_sat(100),
+                block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
+                spend_info: Some(LCSpendInfo {
+                    txid,
+                    height: Some(5),
+                    settled: true,
+                    replaced_txid: None,
+                }),
+            },
+            // Spent recently.
+            Coin {
+                outpoint: bitcoin::OutPoint { txid, vout: 3 },
+                amount: bitcoin::Amount::from_sat(1000),
+                block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
+                spend_info: Some(LCSpendInfo {
+                    txid,
+                    height: Some(50),
+                    settled: false,
+                    replaced_txid: None,
+                }),
+            },
+        ];
+
+        let (kept, hidden) = hide_old_coins(coins.clone(), 10);
+        assert_eq!(kept.len(), 3);
+        assert!(kept.iter().all(|c| c.outpoint.vout != 2));
+        assert_eq!(
+            hidden,
+            HiddenCoins {
+                count: 1,
+                amount: bitcoin::Amount::from_sat(100),
+            }
+        );
+
+        let mut cache = Cache {
+            blockheight: 100,
+            ..Default::default()
+        };
+        cache.set_coins(coins, None);
+        assert_eq!(cache.coins.len(), 4);
+        assert_eq!(cache.hidden_coins, HiddenCoins::default());
+    }
+
+    #[test]
+    fn test_rescan_remaining_time() {
+        let mut cache = Cache::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Not before two samples, nor while the progress stalls at the start.
+        cache.set_rescan_progress(Some(0.0), at(0));
+        assert_eq!(cache.rescan_samples.remaining_time(), None);
+        cache.set_rescan_progress(Some(0.0), at(5));
+        assert_eq!(cache.rescan_samples.remaining_time(), None);
+
+        // 10% in 10 seconds, so 80 more seconds for the remaining 80%.
+        cache.set_rescan_progress(Some(0.1), at(10));
+        cache.set_rescan_progress(Some(0.2), at(20));
+        assert_eq!(
+            cache
+                .rescan_samples
+                .remaining_time()
+                .map(|d| d.as_secs_f64().round() as u64),
+            Some(80)
+        );
+
+        // Then slower, 1% every 10 seconds. A jump of the progress is averaged over the
+        // samples, instead of the estimate dropping to a few seconds.
+        for i in 3..=13 {
+            cache.set_rescan_progress(Some(0.2 + (i - 2) as f64 / 100.0), at(10 * i));
+        }
+        let before_jump = cache.rescan_samples.remaining_time().unwrap();
+        cache.set_rescan_progress(Some(0.5), at(140));
+        let after_jump = cache.rescan_samples.remaining_time().unwrap();
+        assert!(after_jump < before_jump);
+        assert!(after_jump > Duration::from_secs(100));
+
+        // Another rescan starts over.
+        cache.set_rescan_progress(Some(0.1), at(150));
+        assert_eq!(cache.rescan_samples.remaining_time(), None);
+
+        // Completed.
+        cache.set_rescan_progress(None, at(160));
+        assert_eq!(cache.rescan_progress, None);
+        assert_eq!(cache.rescan_samples.remaining_time(), None);
     }
 }
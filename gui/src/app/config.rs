@@ -1,4 +1,8 @@
-use crate::hw::HardwareWalletConfig;
+use crate::{
+    app::price::PriceSource,
+    hw::HardwareWalletConfig,
+    logger::{parse_log_filter, LogRotation},
+};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tracing_subscriber::filter;
@@ -9,10 +13,21 @@ pub struct Config {
     pub daemon_config_path: Option<PathBuf>,
     /// Path to lianad_rpc socket file.
     pub daemon_rpc_path: Option<PathBuf>,
-    /// log level, can be "info", "debug", "trace".
+    /// log level, can be "error", "warn", "info", "debug", "trace".
     pub log_level: Option<String>,
+    /// Comma-separated levels of the logged events per module, overriding `log_level`, for
+    /// instance "liana::bitcoin::poller=trace,liana_gui=debug". Overridden by the
+    /// `LIANA_GUI_LOG` environment variable.
+    pub log_filter: Option<String>,
     /// Use iced debug feature if true.
     pub debug: Option<bool>,
+    /// Size in MB past which the log file is rotated, default is 10.
+    pub log_max_size_mb: Option<u64>,
+    /// Number of rotated log files kept, default is 5.
+    pub log_max_files: Option<usize>,
+    /// Source of the price of bitcoin in the fiat currency set in the wallet settings. Amounts
+    /// are only displayed in bitcoin if not set.
+    pub price_source: Option<PriceSource>,
     /// hardware wallets config.
     /// LEGACY: Use Settings module instead.
     pub hardware_wallets: Option<Vec<HardwareWalletConfig>>,
@@ -26,7 +41,11 @@ impl Config {
             daemon_config_path: Some(daemon_config_path),
             daemon_rpc_path: None,
             log_level: None,
+            log_filter: None,
             debug: None,
+            log_max_size_mb: None,
+            log_max_files: None,
+            price_source: None,
             hardware_wallets: None,
         }
     }
@@ -43,8 +62,8 @@ impl Config {
                 })
             })?;
 
-        // check if log_level field is valid
-        config.log_level()?;
+        // check if log_level and log_filter fields are valid
+        config.log_filter()?;
         Ok(config)
     }
 
@@ -52,6 +71,8 @@ impl Config {
     pub fn log_level(&self) -> Result<filter::LevelFilter, ConfigError> {
         if let Some(level) = &self.log_level {
             match level.as_ref() {
+                "error" => Ok(filter::LevelFilter::ERROR),
+                "warn" => Ok(filter::LevelFilter::WARN),
                 "info" => Ok(filter::LevelFilter::INFO),
                 "debug" => Ok(filter::LevelFilter::DEBUG),
                 "trace" => Ok(filter::LevelFilter::TRACE),
@@ -66,6 +87,40 @@ impl Config {
             Ok(filter::LevelFilter::INFO)
         }
     }
+
+    pub fn log_filter(&self) -> Result<filter::Targets, ConfigError> {
+        let log_level = self.log_level()?;
+        if let Some(directives) = &self.log_filter {
+            parse_log_filter(directives, log_level)
+                .map_err(|e| ConfigError::InvalidField("log_filter", e.to_string()))
+        } else {
+            Ok(filter::Targets::new().with_default(log_level))
+        }
+    }
+
+    /// The logging directives of the configuration, in the syntax of `log_filter`.
+    pub fn log_directives(&self) -> String {
+        match (&self.log_filter, &self.log_level) {
+            (Some(directives), _) => directives.clone(),
+            (None, Some(level)) => level.clone(),
+            (None, None) => self
+                .log_level()
+                .unwrap_or(filter::LevelFilter::INFO)
+                .to_string()
+                .to_lowercase(),
+        }
+    }
+
+    pub fn log_rotation(&self) -> LogRotation {
+        let default = LogRotation::default();
+        LogRotation {
+            max_size: self
+                .log_max_size_mb
+                .map(|mb| mb * 1024 * 1024)
+                .unwrap_or(default.max_size),
+            max_files: self.log_max_files.unwrap_or(default.max_files),
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
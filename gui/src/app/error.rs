@@ -1,10 +1,13 @@
 use std::convert::From;
 use std::io::ErrorKind;
 
-use liana::config::ConfigError;
+use liana::{config::ConfigError, miniscript::bitcoin::EcdsaSighashType, signer::SignerError};
 
 use crate::{
-    app::{settings::SettingsError, wallet::WalletError},
+    app::{
+        address_book::AddressBookError, labels::LabelsError, settings::SettingsError,
+        wallet::WalletError,
+    },
     daemon::DaemonError,
 };
 
@@ -14,7 +17,10 @@ pub enum Error {
     Wallet(WalletError),
     Daemon(DaemonError),
     Unexpected(String),
-    HardwareWallet(async_hwi::Error),
+    /// A signing device or the hot signer failed to sign, with the detailed cause.
+    Signer(SignerErrorKind, String),
+    /// The signing device cannot sign with the given sighash type.
+    UnsupportedSighash(EcdsaSighashType),
 }
 
 impl std::fmt::Display for Error {
@@ -46,7 +52,12 @@ impl std::fmt::Display for Error {
                 }
             },
             Self::Unexpected(e) => write!(f, "Unexpected error: {}", e),
-            Self::HardwareWallet(e) => write!(f, "{}", e),
+            Self::Signer(_, e) => write!(f, "{}", e),
+            Self::UnsupportedSighash(sighash) => write!(
+                f,
+                "The signing device does not support signing with {}",
+                sighash
+            ),
         }
     }
 }
@@ -69,6 +80,18 @@ impl From<SettingsError> for Error {
     }
 }
 
+impl From<LabelsError> for Error {
+    fn from(error: LabelsError) -> Self {
+        Error::Wallet(WalletError::Labels(error))
+    }
+}
+
+impl From<AddressBookError> for Error {
+    fn from(error: AddressBookError) -> Self {
+        Error::Wallet(WalletError::AddressBook(error))
+    }
+}
+
 impl From<DaemonError> for Error {
     fn from(error: DaemonError) -> Self {
         Error::Daemon(error)
@@ -77,6 +100,174 @@ impl From<DaemonError> for Error {
 
 impl From<async_hwi::Error> for Error {
     fn from(error: async_hwi::Error) -> Self {
-        Error::HardwareWallet(error)
+        let kind = match &error {
+            async_hwi::Error::DeviceNotFound | async_hwi::Error::DeviceDisconnected => {
+                SignerErrorKind::Disconnected
+            }
+            async_hwi::Error::UnimplementedMethod | async_hwi::Error::UnsupportedInput => {
+                SignerErrorKind::Unsupported
+            }
+            async_hwi::Error::DeviceDidNotSign => SignerErrorKind::WrongDevice,
+            async_hwi::Error::Device(e) => device_error_kind(e),
+            _ => SignerErrorKind::Other,
+        };
+        Error::Signer(kind, error.to_string())
+    }
+}
+
+impl From<SignerError> for Error {
+    fn from(error: SignerError) -> Self {
+        let kind = match &error {
+            SignerError::InsanePsbt | SignerError::IncompletePsbt => SignerErrorKind::InvalidPsbt,
+            _ => SignerErrorKind::Other,
+        };
+        Error::Signer(kind, error.to_string())
+    }
+}
+
+/// Category of a signing failure, telling the user what to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerErrorKind {
+    Locked,
+    Disconnected,
+    UserDeclined,
+    Unsupported,
+    /// The device does not hold any key the transaction can be signed with.
+    WrongDevice,
+    InvalidPsbt,
+    Other,
+}
+
+impl SignerErrorKind {
+    /// Whether signing again with the same device may succeed without any other change.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Locked | Self::UserDeclined)
+    }
+}
+
+impl std::fmt::Display for SignerErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Locked => write!(f, "The device is locked, unlock it and retry"),
+            Self::Disconnected => write!(
+                f,
+                "The device was disconnected, reconnect it and refresh the list of devices"
+            ),
+            Self::UserDeclined => write!(f, "The transaction was rejected on the device"),
+            Self::Unsupported => write!(f, "The device does not support this operation"),
+            Self::WrongDevice => write!(
+                f,
+                "The device has no key to sign this transaction with, check that it is the right one"
+            ),
+            Self::InvalidPsbt => write!(f, "The transaction is invalid and cannot be signed"),
+            Self::Other => write!(f, "Signing failed"),
+        }
+    }
+}
+
+/// The devices only report some failures as a message, or as the status word of the APDU
+/// for a Ledger.
+fn device_error_kind(message: &str) -> SignerErrorKind {
+    let message = message.to_lowercase();
+    if message.contains("locked") || message.contains("5515") {
+        SignerErrorKind::Locked
+    } else if message.contains("deny")
+        || message.contains("denied")
+        || message.contains("declined")
+        || message.contains("rejected")
+        || message.contains("cancel")
+        || message.contains("6985")
+    {
+        SignerErrorKind::UserDeclined
+    } else if message.contains("not supported") || message.contains("unsupported") {
+        SignerErrorKind::Unsupported
+    } else if message.contains("psbt") {
+        SignerErrorKind::InvalidPsbt
+    } else {
+        SignerErrorKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(error: Error) -> SignerErrorKind {
+        match error {
+            Error::Signer(kind, _) => kind,
+            e => panic!("not a signer error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_hardware_wallet_error_kind() {
+        assert_eq!(
+            kind(async_hwi::Error::DeviceDisconnected.into()),
+            SignerErrorKind::Disconnected
+        );
+        assert_eq!(
+            kind(async_hwi::Error::DeviceNotFound.into()),
+            SignerErrorKind::Disconnected
+        );
+        assert_eq!(
+            kind(async_hwi::Error::UnimplementedMethod.into()),
+            SignerErrorKind::Unsupported
+        );
+        assert_eq!(
+            kind(async_hwi::Error::DeviceDidNotSign.into()),
+            SignerErrorKind::WrongDevice
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("Deny".to_string()).into()),
+            SignerErrorKind::UserDeclined
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("User cancelled the operation".to_string()).into()),
+            SignerErrorKind::UserDeclined
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("Unknown(0x5515)".to_string()).into()),
+            SignerErrorKind::Locked
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("Device is locked".to_string()).into()),
+            SignerErrorKind::Locked
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("Invalid PSBT".to_string()).into()),
+            SignerErrorKind::InvalidPsbt
+        );
+        assert_eq!(
+            kind(async_hwi::Error::Device("Timeout".to_string()).into()),
+            SignerErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_hot_signer_error_kind() {
+        assert_eq!(
+            kind(SignerError::InsanePsbt.into()),
+            SignerErrorKind::InvalidPsbt
+        );
+        assert_eq!(
+            kind(SignerError::IncompletePsbt.into()),
+            SignerErrorKind::InvalidPsbt
+        );
+        assert_eq!(
+            kind(
+                SignerError::MnemonicStorage(std::io::Error::new(ErrorKind::NotFound, "missing"))
+                    .into()
+            ),
+            SignerErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_signer_error_kind_retryable() {
+        assert!(SignerErrorKind::Locked.is_retryable());
+        assert!(SignerErrorKind::UserDeclined.is_retryable());
+        assert!(!SignerErrorKind::Disconnected.is_retryable());
+        assert!(!SignerErrorKind::WrongDevice.is_retryable());
+        assert!(!SignerErrorKind::InvalidPsbt.is_retryable());
     }
 }
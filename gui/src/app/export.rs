@@ -0,0 +1,286 @@
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use liana::miniscript::bitcoin::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{
+        error::Error,
+        labels::Labels,
+        settings::{DescriptorBackup, KeySetting},
+        unit::Unit,
+        wallet::Wallet,
+    },
+    daemon::model::HistoryTransaction,
+    hw::HardwareWalletConfig,
+};
+
+///! Export is the module to write the wallet data to files for external tools.
+pub const HISTORY_CSV_HEADER: &str =
+    "date,txid,net amount (BTC),fee (BTC),confirmation height,label";
+
+/// The wallet history as CSV, one line per transaction. The date is the time of the block
+/// including the transaction in UTC, empty for an unconfirmed transaction. The fee is only
+/// known for the transactions spending coins of the wallet.
+pub fn history_csv(txs: &[HistoryTransaction], labels: &Labels) -> String {
+    let mut csv = format!("{}\n", HISTORY_CSV_HEADER);
+    for tx in txs {
+        let txid = tx.tx.txid().to_string();
+        let net = tx.net_amount().to_sat();
+        csv.push_str(&format!(
+            "{},{},{}{},{},{},{}\n",
+            tx.time
+                .map(|t| NaiveDateTime::from_timestamp(t as i64, 0)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string())
+                .unwrap_or_default(),
+            txid,
+            if net < 0 { "-" } else { "" },
+            Unit::Btc.format_value(&Amount::from_sat(net.unsigned_abs())),
+            tx.fee_amount
+                .map(|fee| Unit::Btc.format_value(&fee))
+                .unwrap_or_default(),
+            tx.height.map(|h| h.to_string()).unwrap_or_default(),
+            labels.get(&txid).map(csv_field).unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+/// Write the CSV of the wallet history to the given file.
+pub fn export_history(
+    path: &Path,
+    txs: &[HistoryTransaction],
+    labels: &Labels,
+) -> Result<(), Error> {
+    std::fs::write(path, history_csv(txs, labels)).map_err(|e| {
+        Error::Unexpected(format!(
+            "Failed to export the history to '{}': {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+/// Version of the format of the wallet backup, to be increased when it changes. A backup of a
+/// later version is rejected.
+pub const WALLET_BACKUP_VERSION: u32 = 1;
+
+/// Backup of a wallet, to restore it on another computer with its names and labels. It never
+/// contains any secret: the hot signer is restored from its mnemonic and matched with the keys
+/// of the descriptor by their fingerprint when the wallet is loaded.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WalletBackup {
+    pub version: u32,
+    pub name: String,
+    pub descriptor: String,
+    #[serde(default)]
+    pub keys: Vec<KeySetting>,
+    #[serde(default)]
+    pub hardware_wallets: Vec<HardwareWalletConfig>,
+    /// The labels in the BIP329 format, if they were exported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<String>,
+}
+
+impl WalletBackup {
+    pub fn new(wallet: &Wallet, with_labels: bool) -> Self {
+        Self {
+            version: WALLET_BACKUP_VERSION,
+            name: wallet.name.clone(),
+            descriptor: wallet.main_descriptor.to_string(),
+            keys: wallet
+                .keys_aliases
+                .iter()
+                .map(|(master_fingerprint, name)| KeySetting {
+                    name: name.clone(),
+                    master_fingerprint: *master_fingerprint,
+                })
+                .collect(),
+            hardware_wallets: wallet.hardware_wallets.clone(),
+            labels: if with_labels {
+                Some(wallet.labels.export())
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Parse a wallet backup, None if the content is not one, like a bare descriptor.
+    pub fn from_json(content: &str) -> Option<Result<Self, String>> {
+        let value: serde_json::Value = serde_json::from_str(content).ok()?;
+        value.get("version")?;
+        Some(
+            serde_json::from_value::<Self>(value)
+                .map_err(|e| format!("Invalid wallet backup: {}", e))
+                .and_then(|backup| {
+                    if backup.version > WALLET_BACKUP_VERSION {
+                        Err(format!(
+                            "The wallet backup was made by a later version of Liana (format \
+                            version {})",
+                            backup.version
+                        ))
+                    } else {
+                        Ok(backup)
+                    }
+                }),
+        )
+    }
+
+    /// Reconstruct the wallet, without any hot signer.
+    pub fn wallet(&self) -> Result<Wallet, String> {
+        let (descriptor, _) = DescriptorBackup::parse(&self.descriptor)?;
+        let mut labels = Labels::default();
+        if let Some(content) = &self.labels {
+            labels.import(content).map_err(|e| e.to_string())?;
+        }
+        Ok(Wallet::new(descriptor)
+            .with_name(self.name.clone())
+            .with_key_aliases(
+                self.keys
+                    .iter()
+                    .map(|key| (key.master_fingerprint, key.name.clone()))
+                    .collect(),
+            )
+            .with_hardware_wallets(self.hardware_wallets.clone())
+            .with_labels(labels))
+    }
+}
+
+/// Write the backup of the wallet to the given file.
+pub fn export_wallet_backup(path: &Path, wallet: &Wallet, with_labels: bool) -> Result<(), Error> {
+    serde_json::to_string_pretty(&WalletBackup::new(wallet, with_labels))
+        .map_err(|e| e.to_string())
+        .and_then(|content| std::fs::write(path, content).map_err(|e| e.to_string()))
+        .map_err(|e| {
+            Error::Unexpected(format!(
+                "Failed to back up the wallet to '{}': {}",
+                path.to_string_lossy(),
+                e
+            ))
+        })
+}
+
+/// Quote a free text field if it contains a character with a meaning in CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app::labels::LabelKind, daemon::model::Coin};
+    use liana::{
+        descriptors::MultipathDescriptor,
+        miniscript::bitcoin::{
+            util::bip32::Fingerprint, OutPoint, PackedLockTime, Script, Transaction, TxIn, TxOut,
+        },
+    };
+    use std::str::FromStr;
+
+    fn tx(input: Option<OutPoint>, values: &[u64]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: input
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    ..Default::default()
+                })
+                .into_iter()
+                .collect(),
+            output: values
+                .iter()
+                .map(|value| TxOut {
+                    value: *value,
+                    script_pubkey: Script::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_history_csv() {
+        let deposit = tx(None, &[100_000]);
+        let coin = Coin {
+            outpoint: OutPoint {
+                txid: deposit.txid(),
+                vout: 0,
+            },
+            amount: Amount::from_sat(100_000),
+            block_height: Some(1),
+            is_immature: false,
+            is_replaceable: false,
+            spend_info: None,
+        };
+        let payment = tx(Some(coin.outpoint), &[60_000, 39_000]);
+        let mut labels = Labels::default();
+        labels.set(
+            LabelKind::Tx,
+            payment.txid().to_string(),
+            "Rent, \"May\"".to_string(),
+        );
+
+        let txs = vec![
+            HistoryTransaction::new(payment.clone(), None, None, vec![coin], vec![1]),
+            HistoryTransaction::new(deposit.clone(), Some(1), Some(1672531200), vec![], vec![0]),
+        ];
+        assert_eq!(
+            history_csv(&txs, &labels),
+            format!(
+                "{}\n\
+                ,{},-0.00061000,0.00001000,,\"Rent, \"\"May\"\"\"\n\
+                2023-01-01 00:00:00,{},0.00100000,,1,\n",
+                HISTORY_CSV_HEADER,
+                payment.txid(),
+                deposit.txid(),
+            )
+        );
+    }
+
+    #[test]
+    fn test_wallet_backup() {
+        let desc = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+        let fingerprint = Fingerprint::from_str("abcdef01").unwrap();
+        let mut labels = Labels::default();
+        labels.set(LabelKind::Tx, "txid".to_string(), "Rent".to_string());
+        let wallet = Wallet::new(MultipathDescriptor::from_str(desc).unwrap())
+            .with_name("Vault".to_string())
+            .with_key_aliases(
+                vec![(fingerprint, "Alice".to_string())]
+                    .into_iter()
+                    .collect(),
+            )
+            .with_labels(labels.clone());
+
+        let content = serde_json::to_string(&WalletBackup::new(&wallet, true)).unwrap();
+        let restored = WalletBackup::from_json(&content)
+            .unwrap()
+            .unwrap()
+            .wallet()
+            .unwrap();
+        assert_eq!(restored.name, "Vault");
+        assert_eq!(restored.main_descriptor.to_string(), desc);
+        assert_eq!(restored.keys_aliases, wallet.keys_aliases);
+        assert_eq!(restored.labels, labels);
+        assert!(restored.signer.is_none());
+
+        let backup = WalletBackup::new(&wallet, false);
+        assert!(backup.labels.is_none());
+        assert!(!serde_json::to_string(&backup).unwrap().contains("labels"));
+
+        // A bare descriptor or a descriptor backup is not a wallet backup.
+        assert!(WalletBackup::from_json(desc).is_none());
+        assert!(WalletBackup::from_json(&format!("{{\"descriptor\": \"{}\"}}", desc)).is_none());
+
+        let mut later = WalletBackup::new(&wallet, false);
+        later.version = WALLET_BACKUP_VERSION + 1;
+        let content = serde_json::to_string(&later).unwrap();
+        assert!(WalletBackup::from_json(&content).unwrap().is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use liana::miniscript::bitcoin::Network;
+use serde::{Deserialize, Serialize};
+
+///! Labels is the module to handle the labels the user gave to the transactions, addresses and
+///! coins of the wallet. They are stored next to the settings file, in the BIP329 format.
+pub const DEFAULT_FILE_NAME: &str = "labels.jsonl";
+
+/// Type of the object a label refers to, named as in BIP329.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelKind {
+    /// A transaction, referred to by its txid.
+    Tx,
+    /// An address of the wallet.
+    Addr,
+    /// A coin, referred to by its outpoint.
+    Output,
+}
+
+/// A line of a BIP329 export. Records of the other types of the BIP, as well as their optional
+/// fields, are ignored.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Record {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(rename = "ref")]
+    reference: String,
+    label: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels {
+    labels: BTreeMap<String, (LabelKind, String)>,
+}
+
+impl Labels {
+    /// Read the labels of the wallet, none if the file was never written.
+    pub fn from_file(datadir: PathBuf, network: Network) -> Result<Self, LabelsError> {
+        let path = Self::path(datadir, network);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(LabelsError::ReadingFile(format!(
+                    "Reading labels file: {}",
+                    e
+                )))
+            }
+        };
+        let mut labels = Self::default();
+        labels.import(&content)?;
+        Ok(labels)
+    }
+
+    /// Read the labels like `from_file`, but start without any if the file is corrupt for the
+    /// wallet to still load. The corrupt file is moved aside so it's not overwritten when new
+    /// labels are written.
+    pub fn from_file_or_default(datadir: PathBuf, network: Network) -> Result<Self, LabelsError> {
+        match Self::from_file(datadir.clone(), network) {
+            Err(LabelsError::Corrupt(line, e)) => {
+                let path = Self::path(datadir, network);
+                let backup_path = path.with_file_name(format!("{}.corrupt", DEFAULT_FILE_NAME));
+                tracing::error!(
+                    "Invalid label at line {} of the labels file: {}. Moving it to '{}' and starting without labels.",
+                    line,
+                    e,
+                    backup_path.display()
+                );
+                fs::rename(&path, &backup_path)
+                    .map_err(|e| LabelsError::WritingFile(e.to_string()))?;
+                Ok(Self::default())
+            }
+            res => res,
+        }
+    }
+
+    /// Write the labels to a temporary file which then atomically replaces the labels file.
+    pub fn to_file(&self, datadir: PathBuf, network: Network) -> Result<(), LabelsError> {
+        let path = Self::path(datadir, network);
+        let tmp_path = path.with_file_name(format!("{}.tmp", DEFAULT_FILE_NAME));
+        if let Err(e) = write_synced(&tmp_path, self.export().as_bytes()) {
+            tracing::warn!("failed to write to file: {:?}", e);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(LabelsError::WritingFile(e.to_string()));
+        }
+        fs::rename(&tmp_path, &path).map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            LabelsError::WritingFile(e.to_string())
+        })
+    }
+
+    fn path(datadir: PathBuf, network: Network) -> PathBuf {
+        let mut path = datadir;
+        path.push(network.to_string());
+        path.push(DEFAULT_FILE_NAME);
+        path
+    }
+
+    pub fn get(&self, reference: &str) -> Option<&str> {
+        self.labels.get(reference).map(|(_, label)| label.as_str())
+    }
+
+    /// Label the given object, an empty label removes the previous one.
+    pub fn set(&mut self, kind: LabelKind, reference: String, label: String) {
+        let label = label.trim();
+        if label.is_empty() {
+            self.labels.remove(&reference);
+        } else {
+            self.labels.insert(reference, (kind, label.to_string()));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Export the labels in the BIP329 format, one JSON record per line.
+    pub fn export(&self) -> String {
+        self.labels
+            .iter()
+            .filter_map(|(reference, (kind, label))| {
+                let kind = match kind {
+                    LabelKind::Tx => "tx",
+                    LabelKind::Addr => "addr",
+                    LabelKind::Output => "output",
+                };
+                serde_json::to_string(&Record {
+                    kind: kind.to_string(),
+                    reference: reference.clone(),
+                    label: label.clone(),
+                })
+                .ok()
+            })
+            .fold(String::new(), |mut content, line| {
+                content.push_str(&line);
+                content.push('\n');
+                content
+            })
+    }
+
+    /// Import the labels of a BIP329 export, replacing the existing label of the same objects.
+    /// Returns the number of labels imported.
+    pub fn import(&mut self, content: &str) -> Result<usize, LabelsError> {
+        let mut records = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(line)
+                .map_err(|e| LabelsError::Corrupt(i + 1, e.to_string()))?;
+            let kind = match record.kind.as_str() {
+                "tx" => LabelKind::Tx,
+                "addr" => LabelKind::Addr,
+                "output" => LabelKind::Output,
+                _ => continue,
+            };
+            records.push((kind, record.reference, record.label));
+        }
+        let count = records.len();
+        for (kind, reference, label) in records {
+            self.set(kind, reference, label);
+        }
+        Ok(count)
+    }
+}
+
+fn write_synced(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+#[derive(Debug, Clone)]
+pub enum LabelsError {
+    ReadingFile(String),
+    /// The line at the given number is not a valid BIP329 record.
+    Corrupt(usize, String),
+    WritingFile(String),
+}
+
+impl std::fmt::Display for LabelsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ReadingFile(e) => write!(f, "Error while reading file: {}", e),
+            Self::Corrupt(line, e) => write!(f, "Invalid label at line {}: {}", line, e),
+            Self::WritingFile(e) => write!(f, "Error while writing file: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_and_import() {
+        let mut labels = Labels::default();
+        labels.set(
+            LabelKind::Tx,
+            "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd".to_string(),
+            "Rent".to_string(),
+        );
+        labels.set(
+            LabelKind::Output,
+            "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1".to_string(),
+            " Change ".to_string(),
+        );
+        labels.set(
+            LabelKind::Addr,
+            "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c".to_string(),
+            String::new(),
+        );
+        assert_eq!(
+            labels.export(),
+            "{\"type\":\"tx\",\"ref\":\"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd\",\"label\":\"Rent\"}\n\
+            {\"type\":\"output\",\"ref\":\"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1\",\"label\":\"Change\"}\n"
+        );
+
+        let mut imported = Labels::default();
+        assert_eq!(imported.import(&labels.export()).unwrap(), 2);
+        assert_eq!(imported, labels);
+    }
+
+    #[test]
+    fn import_bip329() {
+        let content = "{\"type\":\"tx\",\"ref\":\"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd\",\"label\":\"Transaction\",\"origin\":\"wpkh([d34db33f/84'/0'/0'])\"}\n\
+            {\"type\":\"addr\",\"ref\":\"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c\",\"label\":\"Address\"}\n\
+            \n\
+            {\"type\":\"xpub\",\"ref\":\"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8\",\"label\":\"Extended Public Key\"}\n\
+            {\"type\":\"output\",\"ref\":\"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1\",\"label\":\"Output\",\"spendable\":false}\n";
+        let mut labels = Labels::default();
+        assert_eq!(labels.import(content).unwrap(), 3);
+        assert_eq!(
+            labels.get("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd"),
+            Some("Transaction")
+        );
+        assert_eq!(
+            labels.get("bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c"),
+            Some("Address")
+        );
+        assert_eq!(
+            labels.get("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1"),
+            Some("Output")
+        );
+
+        // A line which is not a record does not import any label.
+        let mut labels = Labels::default();
+        assert!(matches!(
+            labels.import("{\"type\":\"tx\",\"ref\":\"a\",\"label\":\"b\"}\nnot json"),
+            Err(LabelsError::Corrupt(2, _))
+        ));
+        assert!(labels.is_empty());
+    }
+
+    #[test]
+    fn corrupt_labels_file() {
+        let datadir = std::env::temp_dir().join(format!("liana-gui-labels-{}", std::process::id()));
+        fs::create_dir_all(datadir.join("bitcoin")).unwrap();
+        let path = datadir.join("bitcoin").join(DEFAULT_FILE_NAME);
+        fs::write(&path, "not json").unwrap();
+
+        assert!(matches!(
+            Labels::from_file(datadir.clone(), Network::Bitcoin),
+            Err(LabelsError::Corrupt(1, _))
+        ));
+
+        // The wallet loads without labels, and the corrupt file is kept aside.
+        let labels = Labels::from_file_or_default(datadir.clone(), Network::Bitcoin).unwrap();
+        assert!(labels.is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(path.with_file_name(format!("{}.corrupt", DEFAULT_FILE_NAME)))
+                .unwrap(),
+            "not json"
+        );
+
+        fs::remove_dir_all(&datadir).unwrap();
+    }
+}
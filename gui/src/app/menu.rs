@@ -6,5 +6,11 @@ pub enum Menu {
     Settings,
     Coins,
     CreateSpendTx,
+    PrivacyRefresh,
+    /// Consolidation of the small coins, suggested on the overview.
+    Consolidate,
     Recovery,
+    /// Restart the timelock of the coins whose recovery path becomes available soon.
+    TimelockRefresh,
+    Reconcile,
 }
@@ -2,14 +2,17 @@ use std::sync::Arc;
 
 use liana::{
     config::Config as DaemonConfig,
-    miniscript::bitcoin::{
-        util::{bip32::Fingerprint, psbt::Psbt},
-        Address,
-    },
+    miniscript::bitcoin::util::{bip32::Fingerprint, psbt::Psbt},
 };
 
 use crate::{
-    app::{error::Error, view, wallet::Wallet},
+    app::{
+        address_book::AddressBook,
+        error::Error,
+        price::{Price, PriceError},
+        view,
+        wallet::Wallet,
+    },
     daemon::model::*,
     hw::HardwareWallet,
 };
@@ -17,23 +20,53 @@ use crate::{
 #[derive(Debug)]
 pub enum Message {
     Tick,
+    /// Time to display the next frame of an animated QR code.
+    QrCodeFrame,
+    /// The content of a file of scanned QR code frames.
+    QrCodeFramesRead(Result<String, Error>),
     View(view::Message),
     LoadDaemonConfig(Box<DaemonConfig>),
     DaemonConfigLoaded(Result<(), Error>),
     LoadWallet,
     WalletLoaded(Result<Arc<Wallet>, Error>),
     Info(Result<GetInfoResult, Error>),
-    ReceiveAddress(Result<Address, Error>),
+    FeeEstimates(Result<FeeEstimates, Error>),
+    Price(Result<Price, PriceError>),
+    /// A new receiving address, along with the highest derivation index of the receiving
+    /// addresses which received coins.
+    ReceiveAddress(Result<(GetAddressResult, Option<u32>), Error>),
+    /// The device with this fingerprint displayed the receive address.
+    AddressDisplayed(Result<Fingerprint, Error>),
     Coins(Result<Vec<Coin>, Error>),
     SpendTxs(Result<Vec<SpendTx>, Error>),
+    SpendTxsCleanedUp(Result<CleanupSpendsResult, Error>),
     Psbt(Result<Psbt, Error>),
+    /// The PSBT read from a file.
+    PsbtFileRead(Result<Psbt, Error>),
+    /// The PSBT decoded from the given edit of a PSBT input, if it was valid.
+    DecodedPsbt(usize, Option<Psbt>),
     Recovery(Result<SpendTx, Error>),
+    TimelockRefresh(Result<SpendTx, Error>),
     Signed(Result<(Psbt, Fingerprint), Error>),
+    PsbtExported(Result<(), Error>),
     WalletRegistered(Result<Fingerprint, Error>),
     Updated(Result<(), Error>),
     Saved(Result<(), Error>),
-    StartRescan(Result<(), Error>),
+    LabelsUpdated(Result<(), Error>),
+    AddressBookUpdated(Result<AddressBook, Error>),
+    StartRescan(Result<u32, Error>),
+    BalanceChecked(Result<CheckBalanceResult, Error>),
+    /// The labels of the bitcoind watch-only wallet, to be previewed before importing them.
+    BackendLabels(Result<Vec<AddressLabel>, Error>),
     ConnectedHardwareWallets(Vec<HardwareWallet>),
+    HardwareWalletsTested(Vec<HardwareWallet>, Vec<String>),
     HistoryTransactions(Result<Vec<HistoryTransaction>, Error>),
     PendingTransactions(Result<Vec<HistoryTransaction>, Error>),
+    HistoryExported(Result<(), Error>),
+    /// The user confirmed the deletion of the wallet data.
+    DeleteWallet,
+    /// Close the wallet to go back to the launcher.
+    SwitchWallet,
+    /// Apply these logging directives without restarting.
+    SetLogFilter(String),
 }
@@ -1,9 +1,15 @@
+pub mod address_book;
 pub mod cache;
 pub mod config;
+pub mod export;
+pub mod labels;
 pub mod menu;
 pub mod message;
+pub mod price;
 pub mod settings;
 pub mod state;
+pub mod unit;
+pub mod ur;
 pub mod view;
 pub mod wallet;
 
@@ -11,9 +17,9 @@ mod error;
 
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use iced::{clipboard, time, Command, Element, Subscription};
 use tracing::{info, warn};
@@ -23,13 +29,25 @@ pub use liana::{config::Config as DaemonConfig, miniscript::bitcoin};
 pub use config::Config;
 pub use message::Message;
 
-use state::{CoinsPanel, CreateSpendPanel, Home, ReceivePanel, RecoveryPanel, SpendPanel, State};
+use state::{
+    CoinsPanel, CreateSpendPanel, Home, ReceivePanel, ReconcilePanel, RecoveryPanel, SpendPanel,
+    State, TimelockRefreshPanel,
+};
 
 use crate::{
-    app::{cache::Cache, error::Error, menu::Menu, wallet::Wallet},
+    app::{
+        cache::Cache,
+        error::Error,
+        labels::{LabelKind, Labels},
+        menu::Menu,
+        wallet::Wallet,
+    },
     daemon::Daemon,
 };
 
+/// Interval at which the price of bitcoin in the fiat currency of the wallet is refreshed.
+const PRICE_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 pub struct App {
     data_dir: PathBuf,
     state: Box<dyn State>,
@@ -37,6 +55,15 @@ pub struct App {
     config: Config,
     wallet: Arc<Wallet>,
     daemon: Arc<dyn Daemon + Sync + Send>,
+    /// Timestamp of the rescan started by the user, if any.
+    rescan_start: Option<u32>,
+    /// Password of the encrypted hot signer mnemonics, to read them again when the wallet is
+    /// reloaded.
+    mnemonic_password: Option<String>,
+    /// The wallet is being closed to switch to another one: no new command is started.
+    switching: bool,
+    /// Last time the price of bitcoin in the fiat currency of the wallet was requested.
+    price_requested_at: Option<Instant>,
 }
 
 impl App {
@@ -46,6 +73,7 @@ impl App {
         config: Config,
         daemon: Arc<dyn Daemon + Sync + Send>,
         data_dir: PathBuf,
+        mnemonic_password: Option<String>,
     ) -> (App, Command<Message>) {
         let state: Box<dyn State> = Home::new(wallet.clone(), &cache.coins).into();
         let cmd = state.load(daemon.clone());
@@ -57,6 +85,10 @@ impl App {
                 config,
                 daemon,
                 wallet,
+                rescan_start: None,
+                mnemonic_password,
+                switching: false,
+                price_requested_at: None,
             },
             cmd,
         )
@@ -64,9 +96,12 @@ impl App {
 
     fn load_state(&mut self, menu: &Menu) -> Command<Message> {
         self.state = match menu {
-            menu::Menu::Settings => {
-                state::SettingsState::new(self.data_dir.clone(), self.wallet.clone()).into()
-            }
+            menu::Menu::Settings => state::SettingsState::new(
+                self.data_dir.clone(),
+                self.wallet.clone(),
+                self.config.log_directives(),
+            )
+            .into(),
             menu::Menu::Home => Home::new(self.wallet.clone(), &self.cache.coins).into(),
             menu::Menu::Coins => CoinsPanel::new(
                 &self.cache.coins,
@@ -80,7 +115,11 @@ impl App {
                 self.cache.blockheight as u32,
             )
             .into(),
-            menu::Menu::Receive => ReceivePanel::default().into(),
+            menu::Menu::TimelockRefresh => {
+                TimelockRefreshPanel::new(self.wallet.clone(), &self.cache.coins).into()
+            }
+            menu::Menu::Receive => ReceivePanel::new(self.wallet.clone()).into(),
+            menu::Menu::Reconcile => ReconcilePanel::default().into(),
             menu::Menu::Spend => SpendPanel::new(self.wallet.clone(), &self.cache.spend_txs).into(),
             menu::Menu::CreateSpendTx => CreateSpendPanel::new(
                 self.wallet.clone(),
@@ -88,11 +127,42 @@ impl App {
                 self.cache.blockheight as u32,
             )
             .into(),
+            menu::Menu::PrivacyRefresh => CreateSpendPanel::new_privacy_refresh(
+                self.wallet.clone(),
+                &self.cache.coins,
+                self.cache.blockheight as u32,
+            )
+            .into(),
+            menu::Menu::Consolidate => match self.wallet.consolidation_suggestion {
+                Some(suggestion) => CreateSpendPanel::new_consolidation(
+                    self.wallet.clone(),
+                    &self.cache.coins,
+                    self.cache.blockheight as u32,
+                    self.cache
+                        .consolidation_feerate()
+                        .unwrap_or(self.cache.mempool_min_feerate),
+                    suggestion.future_feerate,
+                )
+                .into(),
+                None => CreateSpendPanel::new_privacy_refresh(
+                    self.wallet.clone(),
+                    &self.cache.coins,
+                    self.cache.blockheight as u32,
+                )
+                .into(),
+            },
         };
         self.state.load(self.daemon.clone())
     }
 
+    pub fn network(&self) -> bitcoin::Network {
+        self.cache.network
+    }
+
     pub fn subscription(&self) -> Subscription<Message> {
+        if self.switching {
+            return Subscription::none();
+        }
         Subscription::batch(vec![
             time::every(Duration::from_secs(5)).map(|_| Message::Tick),
             self.state.subscription(),
@@ -110,33 +180,129 @@ impl App {
         }
     }
 
+    /// Stop the internal daemon before switching to another wallet. Fails while commands using
+    /// the daemon are in flight: their result must not be delivered to the next wallet. No new
+    /// periodic command is started in the meantime.
+    pub fn stop_for_switch(&mut self) -> Result<(), Error> {
+        self.switching = true;
+        let daemon = Arc::get_mut(&mut self.daemon).ok_or_else(|| {
+            Error::Unexpected("Commands are still in flight with the daemon".to_string())
+        })?;
+        if !daemon.is_external() {
+            info!("Stopping internal daemon before switching wallet...");
+            daemon.stop().map_err(Error::Daemon)?;
+            info!("Internal daemon stopped");
+        }
+        Ok(())
+    }
+
+    pub fn datadir_path(&self) -> &PathBuf {
+        &self.data_dir
+    }
+
+    /// Stop the internal daemon and delete the data directory of the wallet.
+    pub fn delete_wallet(&mut self) -> Result<(), Error> {
+        if self.daemon.is_external() {
+            return Err(Error::Unexpected(
+                "The data of a wallet using an external daemon cannot be deleted".to_string(),
+            ));
+        }
+        info!("Stopping internal daemon before deleting the wallet...");
+        Arc::get_mut(&mut self.daemon)
+            .ok_or_else(|| Error::Unexpected("The daemon is busy, please retry".to_string()))?
+            .stop()
+            .map_err(Error::Daemon)?;
+        info!("Internal daemon stopped");
+        delete_wallet_datadir(&self.data_dir, self.cache.network)
+    }
+
     pub fn update(&mut self, message: Message) -> Command<Message> {
+        let mut completed_scan = None;
         // Update cache when values are passing by.
         // State will handle the error case.
         match &message {
             Message::Coins(Ok(coins)) => {
-                self.cache.coins = coins.clone();
+                self.cache
+                    .set_coins(coins.clone(), self.wallet.displayed_history_months);
             }
             Message::SpendTxs(Ok(txs)) => {
                 self.cache.spend_txs = txs.clone();
             }
             Message::Info(Ok(info)) => {
                 self.cache.blockheight = info.block_height;
-                self.cache.rescan_progress = info.rescan_progress;
+                self.cache.sync_progress = info.sync;
+                self.cache.mempool_min_feerate = info.mempool_min_feerate;
+                self.cache.gap_limit = info.addresses.gap_limit;
+                if self.cache.rescan_progress.is_some() && info.rescan_progress.is_none() {
+                    completed_scan = Some(
+                        self.wallet
+                            .scan_parameters(self.rescan_start.take(), info.addresses.gap_limit),
+                    );
+                }
+                self.cache
+                    .set_rescan_progress(info.rescan_progress, Instant::now());
+            }
+            Message::FeeEstimates(Ok(estimates)) => {
+                self.cache.fee_estimates = Some(estimates.clone());
+            }
+            Message::StartRescan(Ok(t)) => {
+                self.cache.set_rescan_progress(Some(0.0), Instant::now());
+                self.rescan_start = Some(*t);
             }
-            Message::StartRescan(Ok(())) => {
-                self.cache.rescan_progress = Some(0.0);
+            Message::StartRescan(Err(e)) => {
+                warn!("Failed to start the rescan: {}", e);
+            }
+            // The logger was updated, show these directives the next time the settings are open.
+            Message::SetLogFilter(directives) => {
+                self.config.log_filter = Some(directives.clone());
             }
             _ => {}
         };
 
+        if let Some(scan) = completed_scan {
+            info!("Rescan completed, recording the scan parameters");
+            return Command::batch(vec![
+                Command::perform(
+                    record_last_scan(
+                        self.data_dir.clone(),
+                        self.cache.network,
+                        self.wallet.clone(),
+                        scan,
+                    ),
+                    |res| {
+                        if let Err(e) = res {
+                            warn!("Failed to record the scan parameters: {}", e);
+                        }
+                        Message::LoadWallet
+                    },
+                ),
+                self.state.update(self.daemon.clone(), &self.cache, message),
+            ]);
+        }
+
         match message {
             Message::Tick => {
+                let price = self.refresh_price();
                 let daemon = self.daemon.clone();
-                Command::perform(
+                let info = Command::perform(
                     async move { daemon.get_info().map_err(|e| e.into()) },
                     Message::Info,
-                )
+                );
+                // The feerate estimates only change with new blocks, don't query them at each
+                // tick.
+                let blockheight = self.cache.blockheight;
+                if self.cache.fee_estimates.as_ref().map(|e| e.blockheight) == Some(blockheight) {
+                    return Command::batch(vec![info, price]);
+                }
+                let daemon = self.daemon.clone();
+                Command::batch(vec![
+                    info,
+                    price,
+                    Command::perform(
+                        async move { daemon.fee_estimates(blockheight).map_err(|e| e.into()) },
+                        Message::FeeEstimates,
+                    ),
+                ])
             }
             Message::LoadDaemonConfig(cfg) => {
                 let path = self.config.daemon_config_path.clone().expect(
@@ -151,10 +317,74 @@ impl App {
             }
             Message::View(view::Message::Menu(menu)) => self.load_state(&menu),
             Message::View(view::Message::Clipboard(text)) => clipboard::write(text),
+            Message::View(view::Message::Label(view::LabelMessage::Save(
+                kind,
+                reference,
+                label,
+            ))) => Command::perform(
+                save_label(
+                    self.data_dir.clone(),
+                    self.cache.network,
+                    kind,
+                    reference,
+                    label,
+                ),
+                Message::LabelsUpdated,
+            ),
+            Message::LabelsUpdated(Ok(())) => self.update(Message::LoadWallet),
+            Message::Price(res) => {
+                match res {
+                    // The currency may have been changed in the meantime.
+                    Ok(price) => {
+                        if self.wallet.fiat_currency.as_ref() == Some(&price.currency) {
+                            self.cache.price = Some(price);
+                        }
+                    }
+                    // The last price, if any, is kept. Otherwise only bitcoin amounts are shown.
+                    Err(e) => warn!("{}", e),
+                }
+                Command::none()
+            }
+            Message::View(view::Message::DismissConsolidationSuggestion) => {
+                self.cache.consolidation_suggestion_dismissed = true;
+                Command::none()
+            }
             _ => self.state.update(self.daemon.clone(), &self.cache, message),
         }
     }
 
+    /// Fetch the price of bitcoin in the fiat currency of the wallet if it was not requested
+    /// for a while, or if the currency changed. Nothing is fetched without a price source.
+    fn refresh_price(&mut self) -> Command<Message> {
+        let (source, currency) = match (&self.config.price_source, &self.wallet.fiat_currency) {
+            (Some(source), Some(currency)) => (source.clone(), currency.clone()),
+            _ => {
+                self.cache.price = None;
+                return Command::none();
+            }
+        };
+        let currency_changed = self
+            .cache
+            .price
+            .as_ref()
+            .map(|price| price.currency != currency)
+            .unwrap_or(false);
+        if currency_changed {
+            self.cache.price = None;
+            self.price_requested_at = None;
+        }
+        if let Some(requested_at) = self.price_requested_at {
+            if requested_at.elapsed() < PRICE_REFRESH_INTERVAL {
+                return Command::none();
+            }
+        }
+        self.price_requested_at = Some(Instant::now());
+        Command::perform(
+            async move { price::fetch_price(&source, &currency).await },
+            Message::Price,
+        )
+    }
+
     pub fn load_daemon_config(
         &mut self,
         daemon_config_path: &PathBuf,
@@ -190,8 +420,14 @@ impl App {
             &self.config,
             &self.data_dir,
             self.cache.network,
+            self.mnemonic_password.as_deref(),
         )?;
 
+        self.cache.unit = wallet.display_unit;
+        self.cache.watch_only = wallet.watch_only;
+        self.cache.confirmation_tiers = wallet.confirmation_tiers.clone();
+        self.cache.labels = wallet.labels.clone();
+        self.cache.address_book = wallet.address_book.clone();
         self.wallet = Arc::new(wallet);
 
         Ok(self.wallet.clone())
@@ -201,3 +437,95 @@ impl App {
         self.state.view(&self.cache).map(Message::View)
     }
 }
+
+/// Record in the settings of the wallet the parameters it was last scanned with.
+async fn record_last_scan(
+    data_dir: PathBuf,
+    network: bitcoin::Network,
+    wallet: Arc<Wallet>,
+    scan: settings::ScanParameters,
+) -> Result<(), Error> {
+    let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
+    let checksum = wallet.descriptor_checksum();
+    let wallet_setting = settings
+        .wallets
+        .iter_mut()
+        .find(|w| w.descriptor_checksum == checksum)
+        .ok_or_else(|| {
+            Error::Unexpected(format!(
+                "No settings for the wallet with checksum {}",
+                checksum
+            ))
+        })?;
+    wallet_setting.last_scan = Some(scan);
+
+    settings.to_file(data_dir, network)?;
+
+    Ok(())
+}
+
+/// Label the given object in the labels file of the wallet.
+async fn save_label(
+    data_dir: PathBuf,
+    network: bitcoin::Network,
+    kind: LabelKind,
+    reference: String,
+    label: String,
+) -> Result<(), Error> {
+    let mut labels = Labels::from_file(data_dir.clone(), network)?;
+    labels.set(kind, reference, label);
+    labels.to_file(data_dir, network)?;
+    Ok(())
+}
+
+/// Delete the data directory of the wallet on this network. Only the network directory is
+/// removed, the data of the wallets on the other networks are left untouched.
+pub fn delete_wallet_datadir(data_dir: &Path, network: bitcoin::Network) -> Result<(), Error> {
+    let network_dir = data_dir.join(network.to_string());
+    if !network_dir.is_dir() {
+        return Err(Error::Unexpected(format!(
+            "No wallet data directory at '{}'",
+            network_dir.to_string_lossy()
+        )));
+    }
+    warn!(
+        "Deleting the wallet data directory at '{}'",
+        network_dir.to_string_lossy()
+    );
+    std::fs::remove_dir_all(&network_dir).map_err(|e| {
+        Error::Unexpected(format!(
+            "Failed to delete the wallet data directory at '{}': {}",
+            network_dir.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_wallet_datadir() {
+        let data_dir =
+            std::env::temp_dir().join(format!("liana-gui-delete-datadir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let testnet_dir = data_dir.join(bitcoin::Network::Testnet.to_string());
+        let signet_dir = data_dir.join(bitcoin::Network::Signet.to_string());
+        std::fs::create_dir_all(testnet_dir.join("lianad")).unwrap();
+        std::fs::write(testnet_dir.join("settings.json"), "{\"wallets\": []}").unwrap();
+        std::fs::create_dir_all(&signet_dir).unwrap();
+        std::fs::write(signet_dir.join("settings.json"), "{\"wallets\": []}").unwrap();
+
+        // Only the directory of the given network is removed.
+        delete_wallet_datadir(&data_dir, bitcoin::Network::Testnet).unwrap();
+        assert!(!testnet_dir.exists());
+        assert!(signet_dir.join("settings.json").is_file());
+
+        // There is nothing left to delete.
+        assert!(delete_wallet_datadir(&data_dir, bitcoin::Network::Testnet).is_err());
+        assert!(delete_wallet_datadir(&data_dir, bitcoin::Network::Regtest).is_err());
+
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! Price of bitcoin in a fiat currency, to display the amounts in the currency of the user.
+//!
+//! The price is fetched from a configurable HTTP(S) endpoint returning a JSON document, for
+//! instance `https://mempool.space/api/v1/prices` returning `{"time": 1683730000, "USD": 27000}`.
+
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use liana::miniscript::bitcoin::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+/// Time after which a fetch of the price is abandoned.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum size of a response, the price document is expected to be small.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// Placeholder replaced by the currency code in the url and the pointer of the price source.
+const CURRENCY_PLACEHOLDER: &str = "{currency}";
+
+/// Where the price is fetched from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PriceSource {
+    /// Url of the endpoint, for instance "https://mempool.space/api/v1/prices". The
+    /// "{currency}" placeholder is replaced by the currency code.
+    pub url: String,
+    /// JSON pointer to the price of one bitcoin in the returned document, for instance
+    /// "/{currency}". The "{currency}" placeholder is replaced by the currency code.
+    pub pointer: String,
+}
+
+/// The price of one bitcoin in a fiat currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    pub currency: String,
+    pub value: f64,
+    /// Time the price was fetched at.
+    pub timestamp: u32,
+}
+
+impl Price {
+    /// The value of the amount in the currency followed by the currency code, with two decimal
+    /// places and the units grouped by thousands.
+    pub fn format(&self, a: &Amount) -> String {
+        let cents = (a.to_sat() as f64 / 100_000_000.0 * self.value * 100.0).round() as u64;
+        let digits = (cents / 100).to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(' ');
+            }
+            grouped.push(c);
+        }
+        format!("{}.{:02} {}", grouped, cents % 100, self.currency)
+    }
+
+    /// The time the price was fetched at, as HH:MM UTC.
+    pub fn time(&self) -> String {
+        NaiveDateTime::from_timestamp(self.timestamp as i64, 0)
+            .format("%H:%M UTC")
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriceError {
+    Url(String),
+    Http(String),
+    Document(String),
+}
+
+impl std::fmt::Display for PriceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Url(e) => write!(f, "Invalid price source url: {}", e),
+            Self::Http(e) => write!(f, "Failed to fetch the price: {}", e),
+            Self::Document(e) => write!(f, "Invalid price document: {}", e),
+        }
+    }
+}
+
+/// Fetch the price of one bitcoin in the currency. It resolves once the endpoint answered or
+/// the request timed out.
+pub async fn fetch_price(source: &PriceSource, currency: &str) -> Result<Price, PriceError> {
+    let url = source.url.replace(CURRENCY_PLACEHOLDER, currency);
+    let body = http_get(&url).await?;
+    let value = parse_price(
+        &body,
+        &source.pointer.replace(CURRENCY_PLACEHOLDER, currency),
+    )?;
+    Ok(Price {
+        currency: currency.to_string(),
+        value,
+        timestamp: utils::now(),
+    })
+}
+
+/// Read the price at the JSON pointer of the document. It may be a number or a string.
+fn parse_price(body: &[u8], pointer: &str) -> Result<f64, PriceError> {
+    let document: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| PriceError::Document(e.to_string()))?;
+    let value = document
+        .pointer(pointer)
+        .ok_or_else(|| PriceError::Document(format!("No value at '{}'", pointer)))?;
+    let price = match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| PriceError::Document(format!("Value at '{}' is not a number", pointer)))?;
+    if price.is_finite() && price > 0.0 {
+        Ok(price)
+    } else {
+        Err(PriceError::Document(format!(
+            "Value at '{}' is not a price",
+            pointer
+        )))
+    }
+}
+
+/// Get the body of the document at the url, over HTTP or HTTPS.
+async fn http_get(url: &str) -> Result<Vec<u8>, PriceError> {
+    let url = reqwest::Url::parse(url).map_err(|e| PriceError::Url(e.to_string()))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(PriceError::Url(format!("'{}' is not a http(s) url", url)));
+    }
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| PriceError::Http(e.to_string()))?;
+    let mut response = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| PriceError::Http(e.to_string()))?;
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| PriceError::Http(e.to_string()))?
+    {
+        if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+            return Err(PriceError::Http("Response too large".to_string()));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_price() {
+        let body = br#"{"time":1683730000,"USD":27123.5,"EUR":"24900"}"#;
+        assert_eq!(parse_price(body, "/USD").unwrap(), 27123.5);
+        assert_eq!(parse_price(body, "/EUR").unwrap(), 24900.0);
+        assert!(parse_price(body, "/GBP").is_err());
+        assert!(parse_price(body, "").is_err());
+        assert!(parse_price(b"not json", "/USD").is_err());
+    }
+
+    // A server answering a single request with this status line and body.
+    fn dummy_server(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_price() {
+        let url = dummy_server("200 OK", r#"{"time":1683730000,"USD":27123.5}"#);
+        let source = PriceSource {
+            url: format!("{}/api/v1/prices", url),
+            pointer: "/{currency}".to_string(),
+        };
+        let price = fetch_price(&source, "USD").await.unwrap();
+        assert_eq!(price.currency, "USD");
+        assert_eq!(price.value, 27123.5);
+
+        let url = dummy_server("404 Not Found", "{}");
+        let source = PriceSource {
+            url,
+            pointer: "/{currency}".to_string(),
+        };
+        assert!(matches!(
+            fetch_price(&source, "USD").await,
+            Err(PriceError::Http(_))
+        ));
+
+        let source = PriceSource {
+            url: "ftp://prices.local/".to_string(),
+            pointer: "/{currency}".to_string(),
+        };
+        assert!(matches!(
+            fetch_price(&source, "USD").await,
+            Err(PriceError::Url(_))
+        ));
+    }
+
+    #[test]
+    fn test_price_format() {
+        let price = Price {
+            currency: "USD".to_string(),
+            value: 27123.5,
+            timestamp: 1683730000,
+        };
+        assert_eq!(
+            price.format(&Amount::from_sat(100_000_000)),
+            "27 123.50 USD"
+        );
+        assert_eq!(price.format(&Amount::from_sat(1_234)), "0.33 USD");
+        assert_eq!(
+            price.format(&Amount::from_sat(250_000_000_000)),
+            "67 808 750.00 USD"
+        );
+        assert_eq!(price.time(), "14:46 UTC");
+    }
+}
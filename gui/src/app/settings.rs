@@ -1,12 +1,20 @@
 use std::collections::HashMap;
-use std::fs::OpenOptions;
+use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use liana::miniscript::bitcoin::{util::bip32::Fingerprint, Network};
+use liana::{
+    descriptors::MultipathDescriptor,
+    miniscript::bitcoin::{util::bip32::Fingerprint, Amount, Network},
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{app::wallet::Wallet, hw::HardwareWalletConfig};
+use crate::{
+    app::{labels::Labels, unit::Unit, wallet::Wallet},
+    hw::HardwareWalletConfig,
+};
 
 ///! Settings is the module to handle the GUI settings file.
 ///! The settings file is used by the GUI to store useful information.
@@ -17,25 +25,58 @@ pub struct Settings {
     pub wallets: Vec<WalletSetting>,
 }
 
+/// Copy of the settings file as of the previous successful write, used if the file is found
+/// corrupted.
+pub const BACKUP_FILE_NAME: &str = "settings.json.bak";
+
+/// Distinguishes the temporary files of concurrent writes.
+static WRITE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 impl Settings {
     pub fn from_file(datadir: PathBuf, network: Network) -> Result<Self, SettingsError> {
         let mut path = datadir;
         path.push(network.to_string());
         path.push(DEFAULT_FILE_NAME);
 
-        let config = std::fs::read(path)
-            .map_err(|e| match e.kind() {
-                std::io::ErrorKind::NotFound => SettingsError::NotFound,
-                _ => SettingsError::ReadingFile(format!("Reading settings file: {}", e)),
-            })
-            .and_then(|file_content| {
-                serde_json::from_slice::<Settings>(&file_content).map_err(|e| {
-                    SettingsError::ReadingFile(format!("Parsing settings file: {}", e))
-                })
-            })?;
-        Ok(config)
+        match Self::read(&path) {
+            Err(SettingsError::Corrupt(e)) => {
+                path.set_file_name(BACKUP_FILE_NAME);
+                match Self::read(&path) {
+                    Ok(settings) => {
+                        tracing::warn!(
+                            "Settings file is corrupted ({}), using the backup of the previous write",
+                            e
+                        );
+                        Ok(settings)
+                    }
+                    Err(_) => Err(SettingsError::Corrupt(e)),
+                }
+            }
+            res => res,
+        }
+    }
+
+    /// The settings of the wallet with this descriptor checksum. Settings written before the
+    /// descriptor was changed refer to the previous one: fall back to the first wallet.
+    pub fn wallet(&self, descriptor_checksum: &str) -> Option<&WalletSetting> {
+        self.wallets
+            .iter()
+            .find(|w| w.descriptor_checksum == descriptor_checksum)
+            .or_else(|| self.wallets.first())
     }
 
+    fn read(path: &Path) -> Result<Self, SettingsError> {
+        let file_content = fs::read(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SettingsError::NotFound,
+            _ => SettingsError::ReadingFile(format!("Reading settings file: {}", e)),
+        })?;
+        serde_json::from_slice::<Settings>(&file_content)
+            .map_err(|e| SettingsError::Corrupt(format!("Parsing settings file: {}", e)))
+    }
+
+    /// Write the settings to a temporary file which then atomically replaces the settings file,
+    /// so that an interrupted write never leaves a truncated file. The replaced file is kept as
+    /// a backup if it was valid.
     pub fn to_file(&self, datadir: PathBuf, network: Network) -> Result<(), SettingsError> {
         let mut path = datadir;
         path.push(network.to_string());
@@ -45,18 +86,50 @@ impl Settings {
             SettingsError::WritingFile(format!("Failed to serialize settings: {}", e))
         })?;
 
-        let mut settings_file = OpenOptions::new()
-            .write(true)
-            .open(path)
-            .map_err(|e| SettingsError::WritingFile(e.to_string()))?;
-
-        settings_file.write_all(content.as_bytes()).map_err(|e| {
+        let tmp_path = path.with_file_name(format!(
+            "{}.{}.{}.tmp",
+            DEFAULT_FILE_NAME,
+            std::process::id(),
+            WRITE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(e) = write_synced(&tmp_path, content.as_bytes()) {
             tracing::warn!("failed to write to file: {:?}", e);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(SettingsError::WritingFile(e.to_string()));
+        }
+
+        // A corrupted file must not replace the backup of the last valid one.
+        if Self::read(&path).is_ok() {
+            if let Err(e) = fs::copy(&path, path.with_file_name(BACKUP_FILE_NAME)) {
+                tracing::warn!("failed to back up the settings file: {:?}", e);
+            }
+        }
+
+        fs::rename(&tmp_path, &path).map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
             SettingsError::WritingFile(e.to_string())
-        })
+        })?;
+
+        // Persist the rename itself.
+        #[cfg(unix)]
+        {
+            if let Some(dir) = path.parent() {
+                if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+                    tracing::warn!("failed to sync the settings directory: {:?}", e);
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
+fn write_synced(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WalletSetting {
     pub name: String,
@@ -65,6 +138,43 @@ pub struct WalletSetting {
     pub keys: Vec<KeySetting>,
     #[serde(default)]
     pub hardware_wallets: Vec<HardwareWalletConfig>,
+    /// Number of months of spent coins displayed, the daemon keeps them all regardless.
+    /// Everything is displayed if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displayed_history_months: Option<u32>,
+    /// Parameters used by the last full scan of the wallet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_scan: Option<ScanParameters>,
+    /// Unit the amounts are displayed in, BTC if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_unit: Option<Unit>,
+    /// Fiat currency the amounts are also displayed in, for instance "USD", if a price source
+    /// is configured. Amounts are only displayed in bitcoin if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiat_currency: Option<String>,
+    /// Number of months after which the user is reminded to check that their signing devices
+    /// still work and their backups are intact. No reminder if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_reminder_months: Option<u32>,
+    /// Timestamp of the last time the user confirmed their signing devices and backups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_verification: Option<u32>,
+    /// Suggest on the overview to consolidate the small coins when the feerate is low.
+    /// No suggestion if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consolidation_suggestion: Option<ConsolidationSuggestion>,
+    /// Monitoring instance of a wallet whose keys are all held elsewhere: it never loads a
+    /// hot signer and does not offer to sign.
+    #[serde(default)]
+    pub watch_only: bool,
+    /// Number of confirmations after which coins and transactions are considered final,
+    /// depending on their amount. The default tiers are used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation_tiers: Option<ConfirmationTiers>,
+    /// Whether the user was already offered to import the labels of the bitcoind watch-only
+    /// wallet, and either imported or dismissed them.
+    #[serde(default)]
+    pub backend_labels_offered: bool,
 }
 
 impl WalletSetting {
@@ -75,6 +185,77 @@ impl WalletSetting {
         }
         map
     }
+
+    /// The names given by the user to the wallet and its keys, along with the labels of the
+    /// wallet, to be shared with another instance of the same wallet. It does not contain any
+    /// secret.
+    pub fn annotations(&self, labels: &Labels) -> Annotations {
+        Annotations {
+            descriptor_checksum: self.descriptor_checksum.clone(),
+            name: self.name.clone(),
+            keys: self.keys.clone(),
+            labels: labels.export(),
+        }
+    }
+
+    /// Replace the names of the wallet and of its keys by the ones of another instance of
+    /// the same wallet. The labels are to be imported separately.
+    pub fn apply_annotations(&mut self, annotations: Annotations) -> Result<(), SettingsError> {
+        if annotations.descriptor_checksum != self.descriptor_checksum {
+            return Err(SettingsError::Unexpected(format!(
+                "The names were exported from another wallet (descriptor checksum '{}')",
+                annotations.descriptor_checksum
+            )));
+        }
+        self.name = annotations.name;
+        self.keys = annotations.keys;
+        Ok(())
+    }
+}
+
+/// Names of a wallet and of its keys, along with its labels, exported from an instance of the
+/// wallet to be imported into another one, for instance a watch-only one.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Annotations {
+    pub descriptor_checksum: String,
+    pub name: String,
+    #[serde(default)]
+    pub keys: Vec<KeySetting>,
+    /// The labels of the transactions, addresses and coins, in the BIP329 format.
+    #[serde(default)]
+    pub labels: String,
+}
+
+/// Backup of the descriptor of a wallet, along with the names of its keys for a restored
+/// wallet to get them back.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DescriptorBackup {
+    pub descriptor: String,
+    #[serde(default)]
+    pub keys: Vec<KeySetting>,
+}
+
+impl DescriptorBackup {
+    /// Parse the content of a backup file, either the bare descriptor or the JSON backup. The
+    /// descriptor must have its checksum for the backup to be verified.
+    pub fn parse(content: &str) -> Result<(MultipathDescriptor, Vec<KeySetting>), String> {
+        let content = content.trim();
+        let backup = if content.starts_with('{') {
+            serde_json::from_str::<DescriptorBackup>(content)
+                .map_err(|e| format!("Invalid descriptor backup: {}", e))?
+        } else {
+            DescriptorBackup {
+                descriptor: content.to_string(),
+                keys: Vec::new(),
+            }
+        };
+        let descriptor = backup.descriptor.trim();
+        if !descriptor.contains('#') {
+            return Err("The descriptor is missing its checksum".to_string());
+        }
+        let descriptor = MultipathDescriptor::from_str(descriptor).map_err(|e| e.to_string())?;
+        Ok((descriptor, backup.keys))
+    }
 }
 
 impl From<&Wallet> for WalletSetting {
@@ -92,11 +273,93 @@ impl From<&Wallet> for WalletSetting {
                 })
                 .collect(),
             descriptor_checksum: w.descriptor_checksum(),
+            displayed_history_months: w.displayed_history_months,
+            last_scan: w.last_scan.clone(),
+            display_unit: Some(w.display_unit),
+            fiat_currency: w.fiat_currency.clone(),
+            verification_reminder_months: w.verification_reminder_months,
+            last_verification: w.last_verification,
+            consolidation_suggestion: w.consolidation_suggestion,
+            watch_only: w.watch_only,
+            confirmation_tiers: Some(w.confirmation_tiers.clone()),
+            backend_labels_offered: w.backend_labels_offered,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// The parameters a scan of the chain was done with. A rescan is needed to pick up
+/// every coin if the current parameters differ from the ones of the last scan.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScanParameters {
+    pub descriptor_checksum: String,
+    pub gap_limit: u32,
+    /// Timestamp the scan started from, the wallet creation date if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_timestamp: Option<u32>,
+}
+
+/// The feerates a consolidation of the small coins is suggested with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConsolidationSuggestion {
+    /// Maximum feerate in sats/vb the consolidation is suggested at.
+    pub max_feerate: u64,
+    /// Feerate in sats/vb the coins are expected to be spent at otherwise, used to estimate
+    /// the savings.
+    pub future_feerate: u64,
+}
+
+/// Amounts below `below_sats` are final after `confirmations` confirmations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConfirmationTier {
+    pub below_sats: u64,
+    pub confirmations: u32,
+}
+
+/// The number of confirmations an amount is considered final after grows with the amount.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConfirmationTiers {
+    pub tiers: Vec<ConfirmationTier>,
+    /// Confirmations required for the amounts above every tier.
+    pub above: u32,
+}
+
+impl Default for ConfirmationTiers {
+    /// Under 0.01 BTC final at 1 confirmation, under 1 BTC at 3, larger at 6.
+    fn default() -> Self {
+        Self {
+            tiers: vec![
+                ConfirmationTier {
+                    below_sats: 1_000_000,
+                    confirmations: 1,
+                },
+                ConfirmationTier {
+                    below_sats: 100_000_000,
+                    confirmations: 3,
+                },
+            ],
+            above: 6,
+        }
+    }
+}
+
+impl ConfirmationTiers {
+    /// The confirmations required by the lowest tier the amount is below, whatever the order
+    /// the tiers were written in.
+    pub fn required_confirmations(&self, amount: Amount) -> u32 {
+        self.tiers
+            .iter()
+            .filter(|tier| amount.to_sat() < tier.below_sats)
+            .min_by_key(|tier| tier.below_sats)
+            .map(|tier| tier.confirmations)
+            .unwrap_or(self.above)
+    }
+
+    pub fn is_final(&self, amount: Amount, confirmations: u32) -> bool {
+        confirmations >= self.required_confirmations(amount)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct KeySetting {
     pub name: String,
     pub master_fingerprint: Fingerprint,
@@ -106,6 +369,8 @@ pub struct KeySetting {
 pub enum SettingsError {
     NotFound,
     ReadingFile(String),
+    /// The settings file, and its backup if any, could not be parsed.
+    Corrupt(String),
     WritingFile(String),
     Unexpected(String),
 }
@@ -115,8 +380,223 @@ impl std::fmt::Display for SettingsError {
         match self {
             Self::NotFound => write!(f, "Settings file not found"),
             Self::ReadingFile(e) => write!(f, "Error while reading file: {}", e),
+            Self::Corrupt(e) => write!(f, "Corrupted settings file: {}", e),
             Self::WritingFile(e) => write!(f, "Error while writing file: {}", e),
             Self::Unexpected(e) => write!(f, "Unexpected error: {}", e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::labels::LabelKind;
+
+    fn setting(name: &str, keys: Vec<KeySetting>) -> WalletSetting {
+        WalletSetting {
+            name: name.to_string(),
+            descriptor_checksum: "2qj59a9y".to_string(),
+            keys,
+            hardware_wallets: Vec::new(),
+            displayed_history_months: None,
+            last_scan: None,
+            display_unit: None,
+            fiat_currency: None,
+            verification_reminder_months: None,
+            last_verification: None,
+            consolidation_suggestion: None,
+            watch_only: false,
+            confirmation_tiers: None,
+            backend_labels_offered: false,
+        }
+    }
+
+    #[test]
+    fn test_settings_wallet() {
+        let mut other = setting("Other", Vec::new());
+        other.descriptor_checksum = "aaaaaaaa".to_string();
+        let settings = Settings {
+            wallets: vec![other, setting("Ours", Vec::new())],
+        };
+        assert_eq!(settings.wallet("2qj59a9y").unwrap().name, "Ours");
+        assert_eq!(settings.wallet("bbbbbbbb").unwrap().name, "Other");
+        assert!(Settings {
+            wallets: Vec::new()
+        }
+        .wallet("2qj59a9y")
+        .is_none());
+    }
+
+    #[test]
+    fn test_settings_truncated_write() {
+        let network = Network::Testnet;
+        let datadir =
+            std::env::temp_dir().join(format!("liana-gui-settings-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&datadir);
+        let network_dir = datadir.join(network.to_string());
+        fs::create_dir_all(&network_dir).unwrap();
+        let path = network_dir.join(DEFAULT_FILE_NAME);
+
+        let first = Settings {
+            wallets: vec![setting("First", Vec::new())],
+        };
+        let second = Settings {
+            wallets: vec![setting("Second", Vec::new())],
+        };
+        first.to_file(datadir.clone(), network).unwrap();
+        second.to_file(datadir.clone(), network).unwrap();
+        assert_eq!(
+            Settings::from_file(datadir.clone(), network)
+                .unwrap()
+                .wallets[0]
+                .name,
+            "Second"
+        );
+
+        // A write interrupted in the middle of the file falls back to the previous write.
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+        assert_eq!(
+            Settings::from_file(datadir.clone(), network)
+                .unwrap()
+                .wallets[0]
+                .name,
+            "First"
+        );
+
+        // The next write does not replace the backup with the corrupted file.
+        second.to_file(datadir.clone(), network).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Settings>(
+                &fs::read(network_dir.join(BACKUP_FILE_NAME)).unwrap()
+            )
+            .unwrap()
+            .wallets[0]
+                .name,
+            "First"
+        );
+
+        // Without a valid backup the corruption is reported.
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+        fs::remove_file(network_dir.join(BACKUP_FILE_NAME)).unwrap();
+        assert!(matches!(
+            Settings::from_file(datadir.clone(), network),
+            Err(SettingsError::Corrupt(_))
+        ));
+
+        // No temporary file is left behind.
+        assert_eq!(fs::read_dir(&network_dir).unwrap().count(), 1);
+        fs::remove_dir_all(&datadir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_annotations() {
+        let keys = vec![KeySetting {
+            name: "Alice".to_string(),
+            master_fingerprint: Fingerprint::from_str("abcdef01").unwrap(),
+        }];
+        let main = setting("Vault", keys.clone());
+        let mut monitoring = setting("Liana", Vec::new());
+        monitoring.watch_only = true;
+
+        let mut labels = Labels::default();
+        labels.set(
+            LabelKind::Tx,
+            "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5".to_string(),
+            "Rent".to_string(),
+        );
+        let annotations = main.annotations(&labels);
+        let exported = serde_json::to_string(&annotations).unwrap();
+        let imported: Annotations = serde_json::from_str(&exported).unwrap();
+        let mut monitoring_labels = Labels::default();
+        monitoring_labels.import(&imported.labels).unwrap();
+        assert_eq!(monitoring_labels, labels);
+        monitoring.apply_annotations(imported).unwrap();
+        assert_eq!(monitoring.name, "Vault");
+        assert_eq!(monitoring.keys, keys);
+        assert!(monitoring.watch_only);
+
+        // A bundle exported before the labels were part of it is still accepted.
+        let imported: Annotations =
+            serde_json::from_str(r#"{"descriptor_checksum":"2qj59a9y","name":"Vault","keys":[]}"#)
+                .unwrap();
+        assert!(imported.labels.is_empty());
+
+        // The names of another wallet are rejected.
+        let mut other = setting("Other", Vec::new());
+        other.descriptor_checksum = "aaaaaaaa".to_string();
+        assert!(other.apply_annotations(annotations).is_err());
+        assert_eq!(other.name, "Other");
+    }
+
+    #[test]
+    fn test_descriptor_backup() {
+        let desc = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+        let (descriptor, keys) = DescriptorBackup::parse(&format!("{}\n", desc)).unwrap();
+        assert_eq!(descriptor.to_string(), desc);
+        assert!(keys.is_empty());
+
+        let backup = DescriptorBackup {
+            descriptor: desc.to_string(),
+            keys: vec![KeySetting {
+                name: "Alice".to_string(),
+                master_fingerprint: Fingerprint::from_str("abcdef01").unwrap(),
+            }],
+        };
+        let (descriptor, keys) =
+            DescriptorBackup::parse(&serde_json::to_string_pretty(&backup).unwrap()).unwrap();
+        assert_eq!(descriptor.to_string(), desc);
+        assert_eq!(keys, backup.keys);
+
+        // The checksum is required and verified.
+        let (without_checksum, _) = desc.split_once('#').unwrap();
+        assert!(DescriptorBackup::parse(without_checksum).is_err());
+        assert!(DescriptorBackup::parse(&desc.replace("#2qj59a9y", "#2qj59a9z")).is_err());
+        assert!(DescriptorBackup::parse(&format!("{{\"descriptor\": {}}}", desc)).is_err());
+    }
+
+    #[test]
+    fn test_required_confirmations() {
+        let tiers = ConfirmationTiers::default();
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(0)), 1);
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(999_999)), 1);
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(1_000_000)), 3);
+        assert_eq!(
+            tiers.required_confirmations(Amount::from_sat(99_999_999)),
+            3
+        );
+        assert_eq!(
+            tiers.required_confirmations(Amount::from_sat(100_000_000)),
+            6
+        );
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(u64::MAX)), 6);
+
+        assert!(!tiers.is_final(Amount::from_sat(1_000_000), 2));
+        assert!(tiers.is_final(Amount::from_sat(1_000_000), 3));
+        assert!(!tiers.is_final(Amount::from_sat(1_000), 0));
+
+        // The tiers do not have to be sorted.
+        let tiers = ConfirmationTiers {
+            tiers: vec![
+                ConfirmationTier {
+                    below_sats: 100_000_000,
+                    confirmations: 3,
+                },
+                ConfirmationTier {
+                    below_sats: 1_000_000,
+                    confirmations: 1,
+                },
+            ],
+            above: 6,
+        };
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(999_999)), 1);
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(1_000_000)), 3);
+
+        // Without any tier, everything requires the same number of confirmations.
+        let tiers = ConfirmationTiers {
+            tiers: Vec::new(),
+            above: 2,
+        };
+        assert_eq!(tiers.required_confirmations(Amount::from_sat(1)), 2);
+    }
+}
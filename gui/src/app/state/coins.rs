@@ -1,17 +1,91 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use iced::{Command, Element};
+use liana::miniscript::bitcoin::{util::psbt::Psbt, OutPoint};
 
 use crate::{
     app::{cache::Cache, error::Error, menu::Menu, message::Message, state::State, view},
-    daemon::{model::Coin, Daemon},
+    daemon::{
+        model::{Coin, HistoryTransaction},
+        Daemon, DaemonError,
+    },
+    ui::component::{form, modal},
 };
 
+/// A child transaction spending an unconfirmed coin to bump the feerate of its parent.
+pub struct CpfpModal {
+    outpoint: OutPoint,
+    feerate: form::Value<String>,
+    processing: bool,
+    created: bool,
+    error: Option<Error>,
+}
+
+impl CpfpModal {
+    pub fn new(outpoint: OutPoint) -> Self {
+        Self {
+            outpoint,
+            feerate: form::Value::default(),
+            processing: false,
+            created: false,
+            error: None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        message: view::CoinsMessage,
+    ) -> Command<Message> {
+        match message {
+            view::CoinsMessage::FeerateEdited(s) => {
+                self.feerate.valid = s.parse::<u64>().map(|f| f > 0).unwrap_or(false);
+                self.feerate.value = s;
+            }
+            view::CoinsMessage::Confirm => {
+                let feerate_vb = match self.feerate.value.parse::<u64>() {
+                    Ok(f) if self.feerate.valid => f,
+                    _ => return Command::none(),
+                };
+                let outpoint = self.outpoint;
+                self.processing = true;
+                self.error = None;
+                return Command::perform(
+                    async move {
+                        let psbt = daemon.create_cpfp(&outpoint, feerate_vb)?;
+                        daemon.update_spend_tx(&psbt)?;
+                        Ok(psbt)
+                    },
+                    |res: Result<Psbt, DaemonError>| Message::Psbt(res.map_err(|e| e.into())),
+                );
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+
+    fn view(&self) -> Element<view::Message> {
+        view::coins::cpfp_modal(
+            self.error.as_ref(),
+            &self.outpoint,
+            &self.feerate,
+            self.processing,
+            self.created,
+        )
+    }
+}
+
 pub struct CoinsPanel {
     coins: Vec<Coin>,
     selected: Vec<usize>,
+    /// Transaction of a coin lineage selected by the user.
+    selected_tx: Option<HistoryTransaction>,
     warning: Option<Error>,
+    cpfp: Option<CpfpModal>,
+    /// Labels edited by the user and not saved yet, by reference of the labelled object.
+    labels_edited: HashMap<String, String>,
     /// timelock value to pass for the heir to consume a coin.
     timelock: u32,
 }
@@ -21,7 +95,10 @@ impl CoinsPanel {
         let mut panel = Self {
             coins: Vec::new(),
             selected: Vec::new(),
+            selected_tx: None,
             warning: None,
+            cpfp: None,
+            labels_edited: HashMap::new(),
             timelock,
         };
         panel.update_coins(coins);
@@ -58,18 +135,39 @@ impl CoinsPanel {
 
 impl State for CoinsPanel {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
-        view::dashboard(
+        if let Some(tx) = &self.selected_tx {
+            return view::modal(
+                false,
+                self.warning.as_ref(),
+                view::home::event_view(cache, tx, &self.labels_edited),
+                None::<Element<view::Message>>,
+            );
+        }
+        let content = view::dashboard(
             &Menu::Coins,
             cache,
             self.warning.as_ref(),
-            view::coins::coins_view(cache, &self.coins, self.timelock, &self.selected),
-        )
+            view::coins::coins_view(
+                cache,
+                &self.coins,
+                self.timelock,
+                &self.selected,
+                &self.labels_edited,
+            ),
+        );
+        if let Some(cpfp) = &self.cpfp {
+            modal::Modal::new(content, cpfp.view())
+                .on_blur(Some(view::Message::Coins(view::CoinsMessage::Cancel)))
+                .into()
+        } else {
+            content
+        }
     }
 
     fn update(
         &mut self,
-        _daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
         message: Message,
     ) -> Command<Message> {
         match message {
@@ -88,6 +186,59 @@ impl State for CoinsPanel {
                     self.selected.push(i);
                 }
             }
+            Message::View(view::Message::SelectTransaction(txid)) => {
+                let coins = cache.coins.clone();
+                return Command::perform(
+                    async move {
+                        daemon
+                            .get_history_txs(&coins, &[txid])
+                            .map_err(|e| e.into())
+                    },
+                    Message::HistoryTransactions,
+                );
+            }
+            Message::HistoryTransactions(res) => match res {
+                Err(e) => self.warning = Some(e),
+                Ok(txs) => {
+                    self.warning = None;
+                    self.selected_tx = txs.into_iter().next();
+                }
+            },
+            Message::View(view::Message::Close) => {
+                self.selected_tx = None;
+            }
+            Message::View(view::Message::Coins(msg)) => match msg {
+                view::CoinsMessage::BumpFee(outpoint) => {
+                    self.cpfp = Some(CpfpModal::new(outpoint));
+                }
+                view::CoinsMessage::Cancel => {
+                    self.cpfp = None;
+                }
+                msg => {
+                    if let Some(cpfp) = &mut self.cpfp {
+                        return cpfp.update(daemon, msg);
+                    }
+                }
+            },
+            Message::View(view::Message::Label(view::LabelMessage::Edited(reference, label))) => {
+                self.labels_edited.insert(reference, label);
+            }
+            Message::LabelsUpdated(Err(e)) => self.warning = Some(e),
+            Message::WalletLoaded(Ok(wallet)) => {
+                // The edits saved are now the labels of the wallet.
+                self.labels_edited.retain(|reference, label| {
+                    wallet.labels.get(reference).unwrap_or_default() != label.trim()
+                });
+            }
+            Message::Psbt(res) => {
+                if let Some(cpfp) = &mut self.cpfp {
+                    cpfp.processing = false;
+                    match res {
+                        Ok(_) => cpfp.created = true,
+                        Err(e) => cpfp.error = Some(e),
+                    }
+                }
+            }
             _ => {}
         };
         Command::none()
@@ -133,24 +284,32 @@ mod tests {
                 outpoint: bitcoin::OutPoint { txid, vout: 2 },
                 amount: bitcoin::Amount::from_sat(1),
                 block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
                 spend_info: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 3 },
                 amount: bitcoin::Amount::from_sat(1),
                 block_height: None,
+                is_immature: false,
+                is_replaceable: false,
                 spend_info: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 0 },
                 amount: bitcoin::Amount::from_sat(1),
                 block_height: Some(2),
+                is_immature: false,
+                is_replaceable: false,
                 spend_info: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 1 },
                 amount: bitcoin::Amount::from_sat(1),
                 block_height: Some(3),
+                is_immature: false,
+                is_replaceable: false,
                 spend_info: None,
             },
         ]);
@@ -1,26 +1,41 @@
 mod coins;
+mod reconcile;
 mod recovery;
 mod settings;
 mod spend;
+mod timelock_refresh;
 
-use std::convert::TryInto;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use iced::{widget::qr_code, Command, Subscription};
 use iced::{widget::Column, Element};
-use liana::miniscript::bitcoin::{Address, Amount};
+use liana::miniscript::bitcoin::{
+    secp256k1,
+    util::bip32::{ChildNumber, Fingerprint},
+    Address, Amount,
+};
+use tracing::warn;
 
 use super::{cache::Cache, error::Error, menu::Menu, message::Message, view, wallet::Wallet};
 
-use crate::daemon::{
-    model::{remaining_sequence, Coin, HistoryTransaction},
-    Daemon,
+use crate::{
+    daemon::{
+        model::{balance_history, remaining_sequence, Coin, GetAddressResult, HistoryTransaction},
+        Daemon,
+    },
+    hw::{display_address, list_hardware_wallets, HardwareWallet},
+    utils,
 };
 pub use coins::CoinsPanel;
+pub use reconcile::ReconcilePanel;
 pub use recovery::RecoveryPanel;
 pub use settings::SettingsState;
-pub use spend::{CreateSpendPanel, SpendPanel};
+pub use spend::{consolidation_savings, input_vsize, CreateSpendPanel, SpendPanel};
+pub use timelock_refresh::TimelockRefreshPanel;
+
+/// Maximum number of points of the balance chart of the home page.
+const BALANCE_CHART_POINTS: usize = 200;
 
 pub trait State {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message>;
@@ -43,11 +58,17 @@ pub trait State {
 pub struct Home {
     wallet: Arc<Wallet>,
     balance: Amount,
+    /// Confirmed balance after each block it changed at, recomputed from the coins at each
+    /// update so that reorged coins are accounted for.
+    balance_history: Vec<(i32, Amount)>,
+    show_balance_chart: bool,
     recovery_warning: Option<(Amount, usize)>,
     recovery_alert: Option<(Amount, usize)>,
     pending_events: Vec<HistoryTransaction>,
     events: Vec<HistoryTransaction>,
     selected_event: Option<usize>,
+    /// Labels edited by the user and not saved yet, by reference of the labelled object.
+    labels_edited: HashMap<String, String>,
     warning: Option<Error>,
 }
 
@@ -68,11 +89,14 @@ impl Home {
                     })
                     .sum(),
             ),
+            balance_history: balance_history(coins, BALANCE_CHART_POINTS),
+            show_balance_chart: false,
             recovery_alert: None,
             recovery_warning: None,
             selected_event: None,
             events: Vec::new(),
             pending_events: Vec::new(),
+            labels_edited: HashMap::new(),
             warning: None,
         }
     }
@@ -89,20 +113,61 @@ impl State for Home {
             return view::modal(
                 false,
                 self.warning.as_ref(),
-                view::home::event_view(cache, event),
+                view::home::event_view(cache, event, &self.labels_edited),
                 None::<Element<view::Message>>,
             );
         }
+        // The coin set contains spent coins as well, an empty one means the wallet never
+        // received anything. Wait for the end of the sync to not show it prematurely.
+        let is_empty_wallet = cache.coins.is_empty()
+            && cache.hidden_coins.count == 0
+            && self.pending_events.is_empty()
+            && self.events.is_empty()
+            && (cache.sync_progress - 1.0_f64).abs() < f64::EPSILON;
+        let rescan_needed =
+            self.wallet.rescan_needed(cache.gap_limit) && cache.rescan_progress.is_none();
+        let now = utils::now();
+        // Only suggest to consolidate while the estimated feerate is below the threshold set by
+        // the user.
+        let consolidation = self
+            .wallet
+            .consolidation_suggestion
+            .filter(|_| !cache.consolidation_suggestion_dismissed)
+            .and_then(|suggestion| {
+                let feerate = cache
+                    .consolidation_feerate()
+                    .filter(|feerate| *feerate <= suggestion.max_feerate)?;
+                consolidation_savings(
+                    &cache.coins,
+                    input_vsize(&self.wallet.main_descriptor),
+                    feerate,
+                    suggestion.future_feerate,
+                )
+            });
         view::dashboard(
             &Menu::Home,
             cache,
             None,
             view::home::home_view(
+                is_empty_wallet,
+                rescan_needed,
+                self.wallet.verification_due(now),
+                consolidation,
                 &self.balance,
+                if self.show_balance_chart {
+                    Some(&self.balance_history)
+                } else {
+                    None
+                },
                 self.recovery_warning.as_ref(),
                 self.recovery_alert.as_ref(),
                 &self.pending_events,
                 &self.events,
+                cache.blockheight,
+                &cache.confirmation_tiers,
+                &cache.labels,
+                cache.unit,
+                cache.price.as_ref(),
             ),
         )
     }
@@ -118,6 +183,7 @@ impl State for Home {
                 Err(e) => self.warning = Some(e),
                 Ok(coins) => {
                     self.warning = None;
+                    self.balance_history = balance_history(&coins, BALANCE_CHART_POINTS);
                     self.balance = Amount::from_sat(0);
                     let mut recovery_warning = (Amount::from_sat(0), 0);
                     let mut recovery_alert = (Amount::from_sat(0), 0);
@@ -165,9 +231,23 @@ impl State for Home {
                     }
                 }
             },
+            Message::WalletLoaded(Ok(wallet)) => {
+                // The edits saved are now the labels of the wallet.
+                self.labels_edited.retain(|reference, label| {
+                    wallet.labels.get(reference).unwrap_or_default() != label.trim()
+                });
+                self.wallet = wallet;
+            }
+            Message::View(view::Message::Label(view::LabelMessage::Edited(reference, label))) => {
+                self.labels_edited.insert(reference, label);
+            }
+            Message::LabelsUpdated(Err(e)) => self.warning = Some(e),
             Message::View(view::Message::Close) => {
                 self.selected_event = None;
             }
+            Message::View(view::Message::ShowBalanceChart(show)) => {
+                self.show_balance_chart = show;
+            }
             Message::View(view::Message::Select(i)) => {
                 self.selected_event = Some(i);
             }
@@ -218,12 +298,7 @@ impl State for Home {
         let daemon1 = daemon.clone();
         let daemon2 = daemon.clone();
         let daemon3 = daemon.clone();
-        let now: u32 = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            .try_into()
-            .unwrap();
+        let now = utils::now();
         Command::batch(vec![
             Command::perform(
                 async move { daemon3.list_pending_txs().map_err(|e| e.into()) },
@@ -256,13 +331,62 @@ impl From<Home> for Box<dyn State> {
     }
 }
 
-#[derive(Default)]
 pub struct ReceivePanel {
+    wallet: Arc<Wallet>,
     address: Option<Address>,
+    /// Derivation index of the address, only set if the wallet descriptor derives the address
+    /// back at this index: the devices display the address they derive themselves.
+    derivation_index: Option<ChildNumber>,
     qr_code: Option<qr_code::State>,
+    hws: Vec<HardwareWallet>,
+    chosen_hw: Option<usize>,
+    /// The devices which displayed the address.
+    verified: Vec<Fingerprint>,
+    /// Highest derivation index of the receiving addresses which received coins, if any.
+    used_receive_index: Option<u32>,
+    /// Whether the address is at or below the highest index of the receiving addresses which
+    /// received coins: it was likely given out before. Cleared if the user dismisses it.
+    address_reused: bool,
+    /// Whether a fresh address is being fetched.
+    fetching_fresh_address: bool,
     warning: Option<Error>,
 }
 
+impl ReceivePanel {
+    pub fn new(wallet: Arc<Wallet>) -> Self {
+        Self {
+            wallet,
+            address: None,
+            derivation_index: None,
+            qr_code: None,
+            hws: Vec::new(),
+            chosen_hw: None,
+            verified: Vec::new(),
+            used_receive_index: None,
+            address_reused: false,
+            fetching_fresh_address: false,
+            warning: None,
+        }
+    }
+
+    /// The connected devices holding a key of the wallet, which can display its addresses.
+    fn verification_hws(&self) -> Vec<(usize, &HardwareWallet)> {
+        let descriptor_keys = self.wallet.descriptor_keys();
+        self.hws
+            .iter()
+            .enumerate()
+            .filter(|(_, hw)| match hw {
+                HardwareWallet::Supported {
+                    fingerprint,
+                    address_display: Some(_),
+                    ..
+                } => descriptor_keys.contains(fingerprint),
+                _ => false,
+            })
+            .collect()
+    }
+}
+
 impl State for ReceivePanel {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
         if let Some(address) = &self.address {
@@ -270,7 +394,19 @@ impl State for ReceivePanel {
                 &Menu::Receive,
                 cache,
                 self.warning.as_ref(),
-                view::receive::receive(address, self.qr_code.as_ref().unwrap()),
+                view::receive::receive(
+                    address,
+                    self.qr_code.as_ref().unwrap(),
+                    if self.derivation_index.is_some() {
+                        self.verification_hws()
+                    } else {
+                        Vec::new()
+                    },
+                    self.chosen_hw,
+                    &self.verified,
+                    self.address_reused,
+                    self.fetching_fresh_address,
+                ),
             )
         } else {
             view::dashboard(&Menu::Receive, cache, self.warning.as_ref(), Column::new())
@@ -278,37 +414,159 @@ impl State for ReceivePanel {
     }
     fn update(
         &mut self,
-        _daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
         message: Message,
     ) -> Command<Message> {
-        if let Message::ReceiveAddress(res) = message {
-            match res {
-                Ok(address) => {
+        match message {
+            Message::ReceiveAddress(res) => match res {
+                Ok((res, used_receive_index)) => {
                     self.warning = None;
-                    self.qr_code = Some(qr_code::State::new(address.to_qr_uri()).unwrap());
-                    self.address = Some(address);
+                    self.fetching_fresh_address = false;
+                    self.used_receive_index = used_receive_index;
+                    self.address_reused = used_receive_index
+                        .map(|index| u32::from(res.derivation_index) <= index)
+                        .unwrap_or(false);
+                    self.qr_code = Some(qr_code::State::new(res.address.to_qr_uri()).unwrap());
+                    let secp = secp256k1::Secp256k1::verification_only();
+                    self.derivation_index = if self
+                        .wallet
+                        .main_descriptor
+                        .receive_descriptor()
+                        .derive(res.derivation_index, &secp)
+                        .address(cache.network)
+                        == res.address
+                    {
+                        Some(res.derivation_index)
+                    } else {
+                        warn!(
+                            "Address {} is not derived by the wallet descriptor at index {}",
+                            res.address, res.derivation_index
+                        );
+                        None
+                    };
+                    self.address = Some(res.address);
+                    self.verified = Vec::new();
+                }
+                Err(e) => {
+                    self.fetching_fresh_address = false;
+                    self.warning = Some(e);
+                }
+            },
+            Message::View(view::Message::FreshAddress) => {
+                if let (false, Some(used_receive_index)) =
+                    (self.fetching_fresh_address, self.used_receive_index)
+                {
+                    self.fetching_fresh_address = true;
+                    return Command::perform(
+                        fresh_address(daemon, used_receive_index),
+                        Message::ReceiveAddress,
+                    );
+                }
+            }
+            Message::View(view::Message::DismissAddressReuse) => {
+                self.address_reused = false;
+            }
+            Message::ConnectedHardwareWallets(hws) => {
+                self.hws = hws;
+            }
+            Message::View(view::Message::Reload) => {
+                if self.chosen_hw.is_none() {
+                    self.hws = Vec::new();
+                    return Command::perform(
+                        list_hws(self.wallet.clone()),
+                        Message::ConnectedHardwareWallets,
+                    );
+                }
+            }
+            Message::View(view::Message::SelectHardwareWallet(i)) => {
+                if let (
+                    None,
+                    Some(index),
+                    Some(HardwareWallet::Supported {
+                        address_display: Some(device),
+                        fingerprint,
+                        ..
+                    }),
+                ) = (self.chosen_hw, self.derivation_index, self.hws.get(i))
+                {
+                    self.chosen_hw = Some(i);
+                    self.warning = None;
+                    let device = device.clone();
+                    let fingerprint = *fingerprint;
+                    return Command::perform(
+                        async move {
+                            display_address(device, index)
+                                .await
+                                .map(|()| fingerprint)
+                                .map_err(Error::from)
+                        },
+                        Message::AddressDisplayed,
+                    );
+                }
+            }
+            Message::AddressDisplayed(res) => {
+                self.chosen_hw = None;
+                match res {
+                    Ok(fingerprint) => {
+                        if !self.verified.contains(&fingerprint) {
+                            self.verified.push(fingerprint);
+                        }
+                    }
+                    Err(e) => self.warning = Some(e),
                 }
-                Err(e) => self.warning = Some(e),
             }
+            _ => {}
         };
         Command::none()
     }
 
     fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Command<Message> {
         let daemon = daemon.clone();
-        Command::perform(
-            async move {
-                daemon
-                    .get_new_address()
-                    .map(|res| res.address)
-                    .map_err(|e| e.into())
-            },
-            Message::ReceiveAddress,
-        )
+        Command::batch(vec![
+            Command::perform(new_address(daemon), Message::ReceiveAddress),
+            Command::perform(
+                list_hws(self.wallet.clone()),
+                Message::ConnectedHardwareWallets,
+            ),
+        ])
+    }
+}
+
+/// Get a new receiving address, along with the highest derivation index of the receiving
+/// addresses which received coins.
+async fn new_address(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+) -> Result<(GetAddressResult, Option<u32>), Error> {
+    let used_receive_index = daemon.get_info()?.addresses.used_receive_index;
+    let address = daemon.get_new_address()?;
+    Ok((address, used_receive_index))
+}
+
+/// Get new receiving addresses until one is past the highest derivation index of the receiving
+/// addresses which received coins. The daemon never derives the same address twice, so the
+/// skipped ones won't be given out again.
+async fn fresh_address(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+    used_receive_index: u32,
+) -> Result<(GetAddressResult, Option<u32>), Error> {
+    loop {
+        let address = daemon.get_new_address()?;
+        if u32::from(address.derivation_index) > used_receive_index {
+            return Ok((address, Some(used_receive_index)));
+        }
     }
 }
 
+async fn list_hws(wallet: Arc<Wallet>) -> Vec<HardwareWallet> {
+    list_hardware_wallets(
+        &wallet.hardware_wallets,
+        Some((&wallet.name, &wallet.main_descriptor.to_string())),
+        wallet.main_descriptor.is_taproot(),
+    )
+    .await
+}
+
 impl From<ReceivePanel> for Box<dyn State> {
     fn from(s: ReceivePanel) -> Box<dyn State> {
         Box::new(s)
@@ -334,27 +592,99 @@ mod tests {
         utils::{mock::Daemon, sandbox::Sandbox},
     };
 
-    use liana::miniscript::bitcoin::Address;
     use serde_json::json;
     use std::str::FromStr;
 
+    const DESC: &str = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+
+    fn getinfo_response(used_receive_index: Option<u32>) -> serde_json::Value {
+        json!({
+            "version": "0.3.0",
+            "network": "testnet",
+            "block_height": 1,
+            "sync": 1.0,
+            "descriptors": {"main": DESC},
+            "rescan_progress": null,
+            "mempool_min_feerate": 1,
+            "addresses": {
+                "gap_limit": 200,
+                "receive_index": 3,
+                "change_index": 0,
+                "watched_range_end": 999,
+                "used_receive_index": used_receive_index,
+            },
+        })
+    }
+
+    fn getnewaddress_response(desc: &MultipathDescriptor, index: u32) -> serde_json::Value {
+        let secp = secp256k1::Secp256k1::verification_only();
+        json!(GetAddressResult {
+            address: desc
+                .receive_descriptor()
+                .derive(ChildNumber::from(index), &secp)
+                .address(Cache::default().network),
+            derivation_index: ChildNumber::from(index),
+        })
+    }
+
     #[tokio::test]
     async fn test_receive_panel() {
-        let addr =
-            Address::from_str("tb1qkldgvljmjpxrjq2ev5qxe8dvhn0dph9q85pwtfkjeanmwdue2akqj4twxj")
-                .unwrap();
-        let daemon = Daemon::new(vec![(
-            Some(json!({"method": "getnewaddress", "params": Option::<Request>::None})),
-            Ok(json!(GetAddressResult {
-                address: addr.clone()
-            })),
-        )]);
-
-        let sandbox: Sandbox<ReceivePanel> = Sandbox::new(ReceivePanel::default());
+        let desc = MultipathDescriptor::from_str(DESC).unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        let addr = desc
+            .receive_descriptor()
+            .derive(ChildNumber::from(3), &secp)
+            .address(Cache::default().network);
+        let daemon = Daemon::new(vec![
+            (
+                Some(json!({"method": "getinfo", "params": Option::<Request>::None})),
+                Ok(getinfo_response(Some(1))),
+            ),
+            (
+                Some(json!({"method": "getnewaddress", "params": Option::<Request>::None})),
+                Ok(getnewaddress_response(&desc, 3)),
+            ),
+        ]);
+
+        let wallet = Arc::new(Wallet::new(desc));
+        let sandbox: Sandbox<ReceivePanel> = Sandbox::new(ReceivePanel::new(wallet));
         let client = Arc::new(Lianad::new(daemon.run()));
         let sandbox = sandbox.load(client, &Cache::default()).await;
 
         let panel = sandbox.state();
         assert_eq!(panel.address, Some(addr));
+        // The wallet descriptor derives the address back, it can be displayed on a device.
+        assert_eq!(panel.derivation_index, Some(ChildNumber::from(3)));
+        // No address at this index or after it received coins.
+        assert!(!panel.address_reused);
+    }
+
+    #[tokio::test]
+    async fn test_receive_panel_address_reuse() {
+        let desc = MultipathDescriptor::from_str(DESC).unwrap();
+        let daemon = Daemon::new(vec![
+            (None, Ok(getinfo_response(Some(4)))),
+            (None, Ok(getnewaddress_response(&desc, 3))),
+            (None, Ok(getnewaddress_response(&desc, 4))),
+            (None, Ok(getnewaddress_response(&desc, 5))),
+        ]);
+
+        let wallet = Arc::new(Wallet::new(desc));
+        let sandbox: Sandbox<ReceivePanel> = Sandbox::new(ReceivePanel::new(wallet));
+        let client = Arc::new(Lianad::new(daemon.run()));
+        let cache = Cache::default();
+        let sandbox = sandbox.load(client.clone(), &cache).await;
+
+        // An address after this one received coins, it was likely given out before.
+        assert!(sandbox.state().address_reused);
+        assert_eq!(sandbox.state().derivation_index, Some(ChildNumber::from(3)));
+
+        // The addresses are skipped up to the first one past the used ones.
+        let sandbox = sandbox
+            .update(client, &cache, Message::View(view::Message::FreshAddress))
+            .await;
+        let panel = sandbox.state();
+        assert!(!panel.address_reused);
+        assert_eq!(panel.derivation_index, Some(ChildNumber::from(5)));
     }
 }
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use iced::{Command, Element};
+
+use liana::miniscript::bitcoin::Txid;
+
+use crate::{
+    app::{
+        cache::Cache, error::Error, export, menu::Menu, message::Message, state::State, unit::Unit,
+        view,
+    },
+    daemon::{
+        model::{reconcile, ExpectedTransaction, Reconciliation},
+        Daemon,
+    },
+    ui::component::form,
+};
+
+/// Parse the `txid,amount` lines of a CSV file, further columns are ignored. A first line
+/// not starting with a txid is a header and is skipped, as are empty lines. The amounts are in
+/// the given unit and their sign is ignored, since bookkeeping software often records outgoing
+/// payments as negative amounts. The amounts of the lines with the same txid are summed, so
+/// that a batch of payments can be recorded as several lines.
+pub fn parse_expected_transactions(
+    content: &str,
+    unit: Unit,
+) -> Result<Vec<ExpectedTransaction>, String> {
+    let mut expected: Vec<ExpectedTransaction> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let mut fields = line.split(',').map(|f| f.trim().trim_matches('"').trim());
+        let txid = fields.next().unwrap_or_default();
+        if txid.is_empty() {
+            continue;
+        }
+        let txid = match Txid::from_str(txid) {
+            Ok(txid) => txid,
+            Err(_) if i == 0 => continue,
+            Err(_) => return Err(format!("Line {}: invalid txid '{}'", i + 1, txid)),
+        };
+        let amount = fields.next().unwrap_or_default();
+        let amount = unit
+            .parse(amount.trim_start_matches('-'))
+            .map_err(|e| format!("Line {}: invalid amount '{}': {}", i + 1, amount, e))?;
+        if let Some(entry) = expected.iter_mut().find(|entry| entry.txid == txid) {
+            entry.amount += amount;
+        } else {
+            expected.push(ExpectedTransaction { txid, amount });
+        }
+    }
+    Ok(expected)
+}
+
+/// Compare the transactions of the books of the user with the wallet history. The wallet is
+/// only read from.
+#[derive(Default)]
+pub struct ReconcilePanel {
+    csv_file: form::Value<String>,
+    expected: Vec<ExpectedTransaction>,
+    reconciliation: Option<Reconciliation>,
+    processing: bool,
+    /// Path of the CSV file the wallet history is exported to.
+    export_file: form::Value<String>,
+    exported: bool,
+    warning: Option<Error>,
+}
+
+impl State for ReconcilePanel {
+    fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
+        view::dashboard(
+            &Menu::Reconcile,
+            cache,
+            self.warning.as_ref(),
+            view::reconcile::reconcile_view(
+                &self.csv_file,
+                self.processing,
+                self.reconciliation.as_ref(),
+                &self.export_file,
+                self.exported,
+                cache.unit,
+            ),
+        )
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Command<Message> {
+        match message {
+            Message::View(view::Message::Reconcile(view::ReconcileMessage::CsvFileEdited(
+                path,
+            ))) => {
+                self.csv_file.valid = !path.is_empty();
+                self.csv_file.value = path;
+            }
+            Message::View(view::Message::Reconcile(view::ReconcileMessage::Reconcile)) => {
+                if self.processing || self.csv_file.value.is_empty() {
+                    return Command::none();
+                }
+                let expected = std::fs::read_to_string(Path::new(&self.csv_file.value))
+                    .map_err(|e| {
+                        format!("Failed to read CSV from '{}': {}", self.csv_file.value, e)
+                    })
+                    .and_then(|content| parse_expected_transactions(&content, cache.unit));
+                match expected {
+                    Ok(expected) => {
+                        self.expected = expected;
+                        self.reconciliation = None;
+                        self.warning = None;
+                        self.processing = true;
+                        return Command::perform(
+                            async move { daemon.list_all_history_txs().map_err(|e| e.into()) },
+                            Message::HistoryTransactions,
+                        );
+                    }
+                    Err(e) => self.warning = Some(Error::Unexpected(e)),
+                }
+            }
+            Message::View(view::Message::Reconcile(view::ReconcileMessage::ExportFileEdited(
+                path,
+            ))) => {
+                self.export_file.valid = !path.is_empty();
+                self.export_file.value = path;
+                self.exported = false;
+            }
+            Message::View(view::Message::Reconcile(view::ReconcileMessage::Export)) => {
+                if self.processing || self.export_file.value.is_empty() {
+                    return Command::none();
+                }
+                self.warning = None;
+                self.exported = false;
+                self.processing = true;
+                let path = PathBuf::from(&self.export_file.value);
+                let labels = cache.labels.clone();
+                return Command::perform(
+                    async move {
+                        let txs = daemon.list_all_history_txs()?;
+                        export::export_history(&path, &txs, &labels)
+                    },
+                    Message::HistoryExported,
+                );
+            }
+            Message::HistoryExported(res) => {
+                self.processing = false;
+                match res {
+                    Ok(()) => self.exported = true,
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::HistoryTransactions(res) => {
+                self.processing = false;
+                match res {
+                    Ok(txs) => self.reconciliation = Some(reconcile(&self.expected, &txs)),
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            _ => {}
+        };
+        Command::none()
+    }
+}
+
+impl From<ReconcilePanel> for Box<dyn State> {
+    fn from(s: ReconcilePanel) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::miniscript::bitcoin::Amount;
+
+    #[test]
+    fn test_parse_expected_transactions() {
+        let txid = "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810";
+        let other_txid = "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3";
+        let csv = format!(
+            "txid,amount,memo\n\
+            {},0.00004000,first half\n\
+            \"{}\",-0.00006000,second half\n\
+            \n\
+            {},0.00025000,\n",
+            txid, txid, other_txid,
+        );
+        assert_eq!(
+            parse_expected_transactions(&csv, Unit::Btc).unwrap(),
+            vec![
+                ExpectedTransaction {
+                    txid: Txid::from_str(txid).unwrap(),
+                    amount: Amount::from_sat(10_000),
+                },
+                ExpectedTransaction {
+                    txid: Txid::from_str(other_txid).unwrap(),
+                    amount: Amount::from_sat(25_000),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_expected_transactions(&format!("{},1 000", txid), Unit::Sats).unwrap()[0].amount,
+            Amount::from_sat(1_000)
+        );
+
+        assert!(parse_expected_transactions(&format!("{},abc", txid), Unit::Btc).is_err());
+        assert!(parse_expected_transactions("txid,amount\nnot a txid,1", Unit::Btc).is_err());
+    }
+}
@@ -1,7 +1,8 @@
+use std::convert::TryInto;
 use std::str::FromStr;
 use std::sync::Arc;
 
-use iced::{Command, Element};
+use iced::{Command, Element, Subscription};
 
 use crate::{
     app::{
@@ -42,7 +43,7 @@ impl RecoveryPanel {
         for coin in coins {
             if coin.spend_info.is_none() {
                 // recoverable coins are coins that can be recoverable next block.
-                if remaining_sequence(coin, blockheight, timelock) > 1 {
+                if remaining_sequence(coin, blockheight, timelock) > 1 || coin.is_immature {
                     locked_coins.0 += 1;
                     locked_coins.1 += coin.amount;
                 } else {
@@ -76,7 +77,9 @@ impl State for RecoveryPanel {
                     &self.locked_coins,
                     &self.recoverable_coins,
                     &self.feerate,
+                    cache.mempool_min_feerate,
                     &self.recipient,
+                    cache.unit,
                 ),
                 None::<Element<view::Message>>,
             )
@@ -101,6 +104,7 @@ impl State for RecoveryPanel {
                             // recoverable coins are coins that can be recoverable next block.
                             if remaining_sequence(&coin, cache.blockheight as u32, self.timelock)
                                 > 1
+                                || coin.is_immature
                             {
                                 self.locked_coins.0 += 1;
                                 self.locked_coins.1 += coin.amount;
@@ -135,17 +139,22 @@ impl State for RecoveryPanel {
                 }
                 view::Message::CreateSpend(view::CreateSpendMessage::FeerateEdited(feerate)) => {
                     self.feerate.value = feerate;
-                    self.feerate.valid =
-                        self.feerate.value.parse::<u64>().is_ok() && self.feerate.value != "0";
+                    self.feerate.valid = self
+                        .feerate
+                        .value
+                        .parse::<u64>()
+                        .map(|f| f > 0 && f >= cache.mempool_min_feerate)
+                        .unwrap_or(false);
                 }
                 view::Message::Next => {
                     let address = Address::from_str(&self.recipient.value).expect("Checked before");
                     let feerate_vb = self.feerate.value.parse::<u64>().expect("Checked before");
                     self.warning = None;
                     let desc = self.wallet.main_descriptor.clone();
+                    let timelock = self.timelock.try_into().ok();
                     return Command::perform(
                         async move {
-                            let psbt = daemon.create_recovery(address, feerate_vb)?;
+                            let psbt = daemon.create_recovery(address, feerate_vb, timelock)?;
                             let coins = daemon.list_coins().map(|res| res.coins)?;
                             let coins = coins
                                 .iter()
@@ -158,7 +167,7 @@ impl State for RecoveryPanel {
                                 .copied()
                                 .collect();
                             let sigs = desc.partial_spend_info(&psbt).unwrap();
-                            Ok(SpendTx::new(psbt, coins, sigs))
+                            Ok(SpendTx::new(psbt, coins, sigs, &desc))
                         },
                         Message::Recovery,
                     );
@@ -178,6 +187,14 @@ impl State for RecoveryPanel {
         Command::none()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(generated) = &self.generated {
+            generated.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
     fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Command<Message> {
         let daemon = daemon.clone();
         Command::perform(
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use iced::{Command, Element};
+
+use liana::miniscript::bitcoin::{Address, Network};
+
+use crate::{
+    app::{
+        address_book::{AddressBook, Contact},
+        cache::Cache,
+        error::Error,
+        message::Message,
+        state::State,
+        view,
+    },
+    daemon::Daemon,
+    ui::component::form,
+};
+
+/// Add, edit and delete the recipients saved by the user.
+pub struct AddressBookSettingsState {
+    data_dir: PathBuf,
+    address_book: AddressBook,
+    /// Index of the contact being edited, none if a new one is being added.
+    editing: Option<usize>,
+    name: form::Value<String>,
+    address: form::Value<String>,
+    warning: Option<Error>,
+    processing: bool,
+}
+
+impl AddressBookSettingsState {
+    pub fn new(data_dir: PathBuf, address_book: AddressBook) -> Self {
+        Self {
+            data_dir,
+            address_book,
+            editing: None,
+            name: form::Value::default(),
+            address: form::Value::default(),
+            warning: None,
+            processing: false,
+        }
+    }
+
+    fn reset_form(&mut self) {
+        self.editing = None;
+        self.name = form::Value::default();
+        self.address = form::Value::default();
+    }
+
+    fn save(&mut self, address_book: AddressBook, network: Network) -> Command<Message> {
+        self.processing = true;
+        self.warning = None;
+        Command::perform(
+            save_address_book(self.data_dir.clone(), network, address_book),
+            Message::AddressBookUpdated,
+        )
+    }
+}
+
+impl State for AddressBookSettingsState {
+    fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
+        view::settings::address_book_section(
+            cache,
+            self.warning.as_ref(),
+            &self.address_book,
+            self.editing,
+            &self.name,
+            &self.address,
+            self.processing,
+        )
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Command<Message> {
+        match message {
+            Message::AddressBookUpdated(res) => {
+                self.processing = false;
+                match res {
+                    Ok(address_book) => {
+                        self.address_book = address_book;
+                        self.reset_form();
+                        // Reload the wallet for the spend panel to pick up the change.
+                        return Command::perform(async {}, |_| Message::LoadWallet);
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ContactNameEdited(
+                name,
+            ))) => {
+                self.name.valid = name.is_empty() || !name.trim().is_empty();
+                self.name.value = name;
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ContactAddressEdited(address),
+            )) => {
+                self.address.valid = address.is_empty()
+                    || Address::from_str(address.trim())
+                        .map(|a| a.is_valid_for_network(cache.network))
+                        .unwrap_or(false);
+                self.address.value = address;
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::EditContact(i))) => {
+                if let Some(contact) = self.address_book.contacts().get(i) {
+                    self.editing = Some(i);
+                    self.name = form::Value {
+                        value: contact.name.clone(),
+                        valid: true,
+                    };
+                    self.address = form::Value {
+                        value: contact.address.clone(),
+                        valid: contact.is_valid_for(cache.network),
+                    };
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::CancelContactEdit)) => {
+                self.reset_form();
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::SaveContact)) => {
+                if self.processing || !self.name.valid || !self.address.valid {
+                    return Command::none();
+                }
+                let mut address_book = self.address_book.clone();
+                match address_book.set(
+                    self.editing,
+                    Contact {
+                        name: self.name.value.clone(),
+                        address: self.address.value.clone(),
+                    },
+                ) {
+                    Ok(()) => return self.save(address_book, cache.network),
+                    Err(e) => self.warning = Some(e.into()),
+                }
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::DeleteContact(i))) => {
+                if self.processing {
+                    return Command::none();
+                }
+                // The indexes of the contacts after the deleted one change.
+                self.reset_form();
+                let mut address_book = self.address_book.clone();
+                address_book.remove(i);
+                return self.save(address_book, cache.network);
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+}
+
+impl From<AddressBookSettingsState> for Box<dyn State> {
+    fn from(s: AddressBookSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
+
+async fn save_address_book(
+    data_dir: PathBuf,
+    network: Network,
+    address_book: AddressBook,
+) -> Result<AddressBook, Error> {
+    address_book.to_file(data_dir, network)?;
+    Ok(address_book)
+}
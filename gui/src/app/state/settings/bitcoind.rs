@@ -12,7 +12,7 @@ use liana::config::{BitcoinConfig, BitcoindConfig, Config};
 
 use crate::{
     app::{cache::Cache, error::Error, message::Message, state::settings::Setting, view, State},
-    daemon::Daemon,
+    daemon::{model::CheckBalanceResult, Daemon},
     ui::component::form,
 };
 
@@ -24,17 +24,21 @@ pub struct BitcoindSettingsState {
 
     settings: Vec<Box<dyn Setting>>,
     current: Option<usize>,
+
+    /// Result of the last comparison of the tracked balance with the node's UTxO set.
+    balance_check: Option<CheckBalanceResult>,
+    checking_balance: bool,
 }
 
 impl BitcoindSettingsState {
     pub fn new(config: Option<Config>, cache: &Cache, daemon_is_external: bool) -> Self {
-        let settings = if let Some(config) = &config {
+        // There is no bitcoind to configure if the daemon uses an Electrum server.
+        let settings = if let Some((config, bitcoind_config)) = config
+            .as_ref()
+            .and_then(|config| Some((config, config.bitcoind_config.clone()?)))
+        {
             vec![
-                BitcoindSettings::new(
-                    config.bitcoin_config.clone(),
-                    config.bitcoind_config.clone().unwrap(),
-                )
-                .into(),
+                BitcoindSettings::new(config.bitcoin_config.clone(), bitcoind_config).into(),
                 RescanSetting::new(cache.rescan_progress).into(),
             ]
         } else {
@@ -45,9 +49,11 @@ impl BitcoindSettingsState {
             daemon_is_external,
             warning: None,
             config_updated: false,
-            settings,
             // If a scan is running, the current setting edited is the Rescan panel.
-            current: cache.rescan_progress.map(|_| 1),
+            current: cache.rescan_progress.map(|_| settings.len() - 1),
+            settings,
+            balance_check: None,
+            checking_balance: false,
         }
     }
 }
@@ -89,6 +95,23 @@ impl State for BitcoindSettingsState {
                     }
                 }
             },
+            Message::View(view::Message::Settings(view::SettingsMessage::CheckBalance)) => {
+                if !self.checking_balance {
+                    self.checking_balance = true;
+                    self.warning = None;
+                    return Command::perform(
+                        async move { daemon.check_balance().map_err(|e| e.into()) },
+                        Message::BalanceChecked,
+                    );
+                }
+            }
+            Message::BalanceChecked(res) => {
+                self.checking_balance = false;
+                match res {
+                    Ok(result) => self.balance_check = Some(result),
+                    Err(e) => self.warning = Some(e),
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::Edit(i, msg))) => {
                 if let Some(setting) = self.settings.get_mut(i) {
                     match msg {
@@ -118,6 +141,11 @@ impl State for BitcoindSettingsState {
                     })
                 })
                 .collect(),
+            view::settings::balance_check(
+                self.balance_check.as_ref(),
+                self.checking_balance,
+                cache.unit,
+            ),
         )
     }
 }
@@ -210,6 +238,26 @@ impl Setting for BitcoindSettings {
                     daemon_config.bitcoind_config = Some(liana::config::BitcoindConfig {
                         cookie_path: new_path.unwrap(),
                         addr: new_addr.unwrap(),
+                        zmq_block_addr: daemon_config
+                            .bitcoind_config
+                            .as_ref()
+                            .and_then(|c| c.zmq_block_addr),
+                        rpc_retries: daemon_config
+                            .bitcoind_config
+                            .as_ref()
+                            .and_then(|c| c.rpc_retries),
+                        rpc_timeout_secs: daemon_config
+                            .bitcoind_config
+                            .as_ref()
+                            .and_then(|c| c.rpc_timeout_secs),
+                        signet_challenge: daemon_config
+                            .bitcoind_config
+                            .as_ref()
+                            .and_then(|c| c.signet_challenge.clone()),
+                        proxy_addr: daemon_config
+                            .bitcoind_config
+                            .as_ref()
+                            .and_then(|c| c.proxy_addr),
                     });
                     self.processing = true;
                     return Command::perform(async move { daemon_config }, |cfg| {
@@ -250,6 +298,8 @@ pub struct RescanSetting {
     year: form::Value<String>,
     month: form::Value<String>,
     day: form::Value<String>,
+    /// Height of the block to rescan from, an alternative to the date.
+    height: form::Value<String>,
 }
 
 impl From<RescanSetting> for Box<dyn Setting> {
@@ -276,7 +326,7 @@ impl Setting for RescanSetting {
     fn update(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        cache: &Cache,
         message: view::SettingsEditMessage,
     ) -> Command<Message> {
         match message {
@@ -296,11 +346,34 @@ impl Setting for RescanSetting {
                         "rescan_year" => self.year.value = value,
                         "rescan_month" => self.month.value = value,
                         "rescan_day" => self.day.value = value,
+                        "rescan_height" => {
+                            self.height.valid = value.is_empty()
+                                || i32::from_str(&value)
+                                    .map(|h| h > 0 && h <= cache.blockheight)
+                                    .unwrap_or(false);
+                            self.height.value = value;
+                        }
                         _ => {}
                     }
                 }
             }
             view::SettingsEditMessage::Confirm => {
+                // A valid block height takes precedence over the date.
+                if let Some(height) = i32::from_str(&self.height.value)
+                    .ok()
+                    .filter(|_| self.height.valid)
+                {
+                    self.processing = true;
+                    info!("Asking deamon to rescan from block height: {}", height);
+                    return Command::perform(
+                        async move {
+                            daemon
+                                .start_rescan_from_height(height)
+                                .map_err(|e| e.into())
+                        },
+                        Message::StartRescan,
+                    );
+                }
                 let date_time = NaiveDate::from_ymd(
                     i32::from_str(&self.year.value).unwrap_or(1),
                     u32::from_str(&self.month.value).unwrap_or(1),
@@ -311,7 +384,7 @@ impl Setting for RescanSetting {
                 self.processing = true;
                 info!("Asking deamon to rescan with timestamp: {}", t);
                 return Command::perform(
-                    async move { daemon.start_rescan(t).map_err(|e| e.into()) },
+                    async move { daemon.start_rescan(t).map(|_| t).map_err(|e| e.into()) },
                     Message::StartRescan,
                 );
             }
@@ -324,7 +397,10 @@ impl Setting for RescanSetting {
             &self.year,
             &self.month,
             &self.day,
+            &self.height,
+            cache.blockheight,
             cache.rescan_progress,
+            cache.rescan_samples.remaining_time(),
             self.success,
             self.processing,
             can_edit,
@@ -1,3 +1,4 @@
+mod address_book;
 mod bitcoind;
 mod wallet;
 
@@ -7,14 +8,19 @@ use std::sync::Arc;
 
 use iced::{Command, Element};
 
+use address_book::AddressBookSettingsState;
 use bitcoind::BitcoindSettingsState;
 use wallet::WalletSettingsState;
 
 use crate::{
     app::{cache::Cache, error::Error, message::Message, state::State, view, wallet::Wallet},
-    daemon::Daemon,
+    daemon::{model::GetInfoAddresses, Daemon},
+    logger::parse_log_filter,
+    ui::component::form,
 };
 
+use tracing_subscriber::filter::LevelFilter;
+
 trait Setting: std::fmt::Debug {
     fn edited(&mut self, success: bool);
     fn update(
@@ -29,14 +35,17 @@ trait Setting: std::fmt::Debug {
 pub struct SettingsState {
     data_dir: PathBuf,
     wallet: Arc<Wallet>,
+    /// The logging directives in use.
+    log_directives: String,
     setting: Option<Box<dyn State>>,
 }
 
 impl SettingsState {
-    pub fn new(data_dir: PathBuf, wallet: Arc<Wallet>) -> Self {
+    pub fn new(data_dir: PathBuf, wallet: Arc<Wallet>, log_directives: String) -> Self {
         Self {
             data_dir,
             wallet,
+            log_directives,
             setting: None,
         }
     }
@@ -71,6 +80,30 @@ impl State for SettingsState {
                     .map(|s| s.load(daemon))
                     .unwrap_or_else(Command::none)
             }
+            Message::View(view::Message::Settings(view::SettingsMessage::AddressBookSection)) => {
+                self.setting = Some(
+                    AddressBookSettingsState::new(
+                        self.data_dir.clone(),
+                        cache.address_book.clone(),
+                    )
+                    .into(),
+                );
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::LoggingSection)) => {
+                self.setting = Some(LoggingSettingsState::new(self.log_directives.clone()).into());
+                Command::none()
+            }
+            Message::SetLogFilter(directives) => {
+                self.log_directives = directives.clone();
+                self.setting
+                    .as_mut()
+                    .map(|s| s.update(daemon, cache, message))
+                    .unwrap_or_else(Command::none)
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::SwitchWallet)) => {
+                Command::perform(async {}, |_| Message::SwitchWallet)
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::EditWalletSettings)) => {
                 self.setting = Some(
                     WalletSettingsState::new(self.data_dir.clone(), self.wallet.clone()).into(),
@@ -106,6 +139,7 @@ impl From<SettingsState> for Box<dyn State> {
 #[derive(Default)]
 pub struct AboutSettingsState {
     daemon_version: Option<String>,
+    addresses: Option<GetInfoAddresses>,
     warning: Option<Error>,
 }
 
@@ -117,6 +151,7 @@ impl AboutSettingsState {
             } else {
                 None
             },
+            addresses: None,
             warning: None,
         }
     }
@@ -124,7 +159,12 @@ impl AboutSettingsState {
 
 impl State for AboutSettingsState {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
-        view::settings::about_section(cache, self.warning.as_ref(), self.daemon_version.as_ref())
+        view::settings::about_section(
+            cache,
+            self.warning.as_ref(),
+            self.daemon_version.as_ref(),
+            self.addresses.as_ref(),
+        )
     }
 
     fn update(
@@ -135,7 +175,10 @@ impl State for AboutSettingsState {
     ) -> Command<Message> {
         if let Message::Info(res) = message {
             match res {
-                Ok(info) => self.daemon_version = Some(info.version),
+                Ok(info) => {
+                    self.daemon_version = Some(info.version);
+                    self.addresses = Some(info.addresses);
+                }
                 Err(e) => self.warning = Some(e),
             }
         }
@@ -156,3 +199,61 @@ impl From<AboutSettingsState> for Box<dyn State> {
         Box::new(s)
     }
 }
+
+/// Change the levels of the logged events without restarting. The change is not saved in the
+/// configuration file.
+pub struct LoggingSettingsState {
+    log_filter: form::Value<String>,
+    applied: bool,
+}
+
+impl LoggingSettingsState {
+    pub fn new(log_directives: String) -> Self {
+        LoggingSettingsState {
+            log_filter: form::Value {
+                value: log_directives,
+                valid: true,
+            },
+            applied: false,
+        }
+    }
+}
+
+impl State for LoggingSettingsState {
+    fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
+        view::settings::logging_section(cache, &self.log_filter, self.applied)
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        _cache: &Cache,
+        message: Message,
+    ) -> Command<Message> {
+        match message {
+            Message::View(view::Message::Settings(view::SettingsMessage::LogFilterEdited(
+                directives,
+            ))) => {
+                self.log_filter.valid = parse_log_filter(&directives, LevelFilter::INFO).is_ok();
+                self.log_filter.value = directives;
+                self.applied = false;
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ApplyLogFilter)) => {
+                if self.log_filter.valid {
+                    let directives = self.log_filter.value.clone();
+                    return Command::perform(async move { directives }, Message::SetLogFilter);
+                }
+            }
+            Message::SetLogFilter(_) => self.applied = true,
+            _ => {}
+        }
+
+        Command::none()
+    }
+}
+
+impl From<LoggingSettingsState> for Box<dyn State> {
+    fn from(s: LoggingSettingsState) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
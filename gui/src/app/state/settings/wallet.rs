@@ -1,28 +1,62 @@
 use std::collections::HashSet;
 use std::convert::From;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use iced::{Command, Element};
 
-use liana::miniscript::bitcoin::{hashes::hex::ToHex, util::bip32::Fingerprint, Network};
+use liana::miniscript::bitcoin::{hashes::hex::ToHex, util::bip32::Fingerprint, Amount, Network};
 
 use crate::{
     app::{
-        cache::Cache, error::Error, message::Message, settings, state::State, view, wallet::Wallet,
+        cache::Cache,
+        error::Error,
+        export,
+        labels::{LabelKind, Labels},
+        message::Message,
+        settings,
+        state::State,
+        unit::Unit,
+        view,
+        wallet::Wallet,
+    },
+    daemon::{model::AddressLabel, Daemon},
+    hw::{
+        list_hardware_wallets, list_hardware_wallets_with_errors, HardwareWallet,
+        HardwareWalletConfig,
     },
-    daemon::Daemon,
-    hw::{list_hardware_wallets, HardwareWallet, HardwareWalletConfig},
     ui::component::{form, modal},
+    utils,
 };
 
 pub struct WalletSettingsState {
     data_dir: PathBuf,
     warning: Option<Error>,
     descriptor: String,
+    verification_code: String,
     keys_aliases: Vec<(Fingerprint, form::Value<String>)>,
+    displayed_history: form::Value<String>,
+    display_unit: Unit,
+    fiat_currency: form::Value<String>,
+    verification_reminder: form::Value<String>,
+    consolidation_max_feerate: form::Value<String>,
+    consolidation_future_feerate: form::Value<String>,
+    /// Path of the file the names of the wallet and its keys are exported to or imported from.
+    annotations_file: form::Value<String>,
+    annotations_exported: bool,
+    /// Path of the file the labels are exported to or imported from, in the BIP329 format.
+    labels_file: form::Value<String>,
+    labels_exported: bool,
+    /// Labels of the bitcoind watch-only wallet previewed before being imported.
+    backend_labels: Option<Vec<AddressLabel>>,
+    /// Path of the file the wallet is backed up to, without any secret.
+    backup_file: form::Value<String>,
+    backup_labels: bool,
+    backup_exported: bool,
     wallet: Arc<Wallet>,
     modal: Option<RegisterWalletModal>,
+    hws_test: Option<HardwareWalletsTest>,
+    delete_wallet: Option<DeleteWalletModal>,
     processing: bool,
     updated: bool,
 }
@@ -32,15 +66,89 @@ impl WalletSettingsState {
         WalletSettingsState {
             data_dir,
             descriptor: wallet.main_descriptor.to_string(),
+            verification_code: wallet.main_descriptor.verification_code(),
             keys_aliases: Self::keys_aliases(&wallet),
+            displayed_history: Self::displayed_history(&wallet),
+            display_unit: wallet.display_unit,
+            fiat_currency: Self::fiat_currency(&wallet),
+            verification_reminder: Self::verification_reminder(&wallet),
+            consolidation_max_feerate: Self::consolidation_feerate(&wallet, |s| s.max_feerate),
+            consolidation_future_feerate: Self::consolidation_feerate(&wallet, |s| {
+                s.future_feerate
+            }),
+            annotations_file: form::Value::default(),
+            annotations_exported: false,
+            labels_file: form::Value::default(),
+            labels_exported: false,
+            backend_labels: None,
+            backup_file: form::Value::default(),
+            backup_labels: true,
+            backup_exported: false,
             wallet,
             warning: None,
             modal: None,
+            hws_test: None,
+            delete_wallet: None,
             processing: false,
             updated: false,
         }
     }
 
+    fn displayed_history(wallet: &Wallet) -> form::Value<String> {
+        form::Value {
+            value: wallet
+                .displayed_history_months
+                .map(|months| months.to_string())
+                .unwrap_or_default(),
+            valid: true,
+        }
+    }
+
+    fn fiat_currency(wallet: &Wallet) -> form::Value<String> {
+        form::Value {
+            value: wallet.fiat_currency.clone().unwrap_or_default(),
+            valid: true,
+        }
+    }
+
+    fn verification_reminder(wallet: &Wallet) -> form::Value<String> {
+        form::Value {
+            value: wallet
+                .verification_reminder_months
+                .map(|months| months.to_string())
+                .unwrap_or_default(),
+            valid: true,
+        }
+    }
+
+    fn consolidation_feerate(
+        wallet: &Wallet,
+        feerate: fn(&settings::ConsolidationSuggestion) -> u64,
+    ) -> form::Value<String> {
+        form::Value {
+            value: wallet
+                .consolidation_suggestion
+                .as_ref()
+                .map(|suggestion| feerate(suggestion).to_string())
+                .unwrap_or_default(),
+            valid: true,
+        }
+    }
+
+    /// The consolidation suggestion is only enabled if both feerates are set.
+    fn consolidation_suggestion(&self) -> Option<settings::ConsolidationSuggestion> {
+        match (
+            self.consolidation_max_feerate.value.parse::<u64>(),
+            self.consolidation_future_feerate.value.parse::<u64>(),
+        ) {
+            (Ok(max_feerate), Ok(future_feerate)) => Some(settings::ConsolidationSuggestion {
+                max_feerate,
+                future_feerate,
+            }),
+            _ => None,
+        }
+    }
+
     fn keys_aliases(wallet: &Wallet) -> Vec<(Fingerprint, form::Value<String>)> {
         let mut keys_aliases: Vec<(Fingerprint, form::Value<String>)> = wallet
             .keys_aliases
@@ -74,7 +182,25 @@ impl State for WalletSettingsState {
             cache,
             self.warning.as_ref(),
             &self.descriptor,
+            &self.verification_code,
             &self.keys_aliases,
+            &self.displayed_history,
+            self.display_unit,
+            &self.fiat_currency,
+            &self.verification_reminder,
+            self.wallet.last_verification,
+            &self.consolidation_max_feerate,
+            &self.consolidation_future_feerate,
+            self.wallet.watch_only,
+            &self.annotations_file,
+            self.annotations_exported,
+            &self.labels_file,
+            self.labels_exported,
+            self.wallet.backend_labels_offered,
+            self.backend_labels.as_deref(),
+            &self.backup_file,
+            self.backup_labels,
+            self.backup_exported,
             self.processing,
             self.updated,
         );
@@ -82,6 +208,14 @@ impl State for WalletSettingsState {
             modal::Modal::new(content, m.view())
                 .on_blur(Some(view::Message::Close))
                 .into()
+        } else if let Some(t) = &self.hws_test {
+            modal::Modal::new(content, t.view(&self.wallet))
+                .on_blur(Some(view::Message::Close))
+                .into()
+        } else if let Some(d) = &self.delete_wallet {
+            modal::Modal::new(content, d.view(&self.wallet, cache))
+                .on_blur(Some(view::Message::Close))
+                .into()
         } else {
             content
         }
@@ -113,6 +247,14 @@ impl State for WalletSettingsState {
                             modal.wallet = wallet.clone();
                         }
                         self.keys_aliases = Self::keys_aliases(&wallet);
+                        self.displayed_history = Self::displayed_history(&wallet);
+                        self.display_unit = wallet.display_unit;
+                        self.fiat_currency = Self::fiat_currency(&wallet);
+                        self.verification_reminder = Self::verification_reminder(&wallet);
+                        self.consolidation_max_feerate =
+                            Self::consolidation_feerate(&wallet, |s| s.max_feerate);
+                        self.consolidation_future_feerate =
+                            Self::consolidation_feerate(&wallet, |s| s.future_feerate);
                         self.wallet = wallet;
                     }
                     Err(e) => self.warning = Some(e),
@@ -131,12 +273,294 @@ impl State for WalletSettingsState {
                 }
                 Command::none()
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::DisplayedHistoryEdited(value),
+            )) => {
+                self.displayed_history.valid = value.is_empty() || value.parse::<u32>().is_ok();
+                self.displayed_history.value = value;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::DisplayUnitEdited(
+                unit,
+            ))) => {
+                self.display_unit = unit;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::FiatCurrencyEdited(
+                value,
+            ))) => {
+                self.fiat_currency.valid =
+                    value.is_empty() || value.chars().all(|c| c.is_ascii_alphabetic());
+                self.fiat_currency.value = value;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::VerificationReminderEdited(value),
+            )) => {
+                self.verification_reminder.valid = value.is_empty()
+                    || value
+                        .parse::<u32>()
+                        .map(|months| months > 0)
+                        .unwrap_or(false);
+                self.verification_reminder.value = value;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ConsolidationMaxFeerateEdited(value),
+            )) => {
+                self.consolidation_max_feerate.valid =
+                    value.is_empty() || value.parse::<u64>().is_ok();
+                self.consolidation_max_feerate.value = value;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ConsolidationFutureFeerateEdited(value),
+            )) => {
+                self.consolidation_future_feerate.valid =
+                    value.is_empty() || value.parse::<u64>().is_ok();
+                self.consolidation_future_feerate.value = value;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::WatchOnlyToggled(
+                watch_only,
+            ))) => {
+                if self.processing {
+                    return Command::none();
+                }
+                // The mnemonic of a hot signer must not be left on a monitoring instance.
+                if watch_only && self.wallet.signer.is_some() {
+                    self.warning = Some(Error::Unexpected(
+                        "The data directory holds the mnemonic of a key of this wallet, \
+                        a watch-only wallet must not hold any key"
+                            .to_string(),
+                    ));
+                    return Command::none();
+                }
+                self.warning = None;
+                self.processing = true;
+                self.updated = false;
+                Command::perform(
+                    set_watch_only(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        watch_only,
+                    ),
+                    Message::Updated,
+                )
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::AnnotationsFileEdited(path),
+            )) => {
+                self.annotations_file.valid = !path.is_empty();
+                self.annotations_file.value = path;
+                self.annotations_exported = false;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ExportAnnotations)) => {
+                if self.annotations_file.value.is_empty() {
+                    return Command::none();
+                }
+                let annotations =
+                    settings::WalletSetting::from(self.wallet.as_ref()).annotations(&cache.labels);
+                match serde_json::to_string_pretty(&annotations)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| {
+                        std::fs::write(&self.annotations_file.value, content)
+                            .map_err(|e| e.to_string())
+                    }) {
+                    Ok(()) => {
+                        self.warning = None;
+                        self.annotations_exported = true;
+                    }
+                    Err(e) => {
+                        self.warning = Some(Error::Unexpected(format!(
+                            "Failed to export the names to '{}': {}",
+                            self.annotations_file.value, e
+                        )))
+                    }
+                }
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ImportAnnotations)) => {
+                if self.processing || self.annotations_file.value.is_empty() {
+                    return Command::none();
+                }
+                self.warning = None;
+                self.processing = true;
+                self.updated = false;
+                Command::perform(
+                    import_annotations(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        PathBuf::from(&self.annotations_file.value),
+                    ),
+                    Message::Updated,
+                )
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::BackupFileEdited(
+                path,
+            ))) => {
+                self.backup_file.valid = !path.is_empty();
+                self.backup_file.value = path;
+                self.backup_exported = false;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::BackupLabelsToggled(
+                with_labels,
+            ))) => {
+                self.backup_labels = with_labels;
+                self.backup_exported = false;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ExportBackup)) => {
+                if self.backup_file.value.is_empty() {
+                    return Command::none();
+                }
+                match export::export_wallet_backup(
+                    Path::new(&self.backup_file.value),
+                    &self.wallet,
+                    self.backup_labels,
+                ) {
+                    Ok(()) => {
+                        self.warning = None;
+                        self.backup_exported = true;
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::LabelsFileEdited(
+                path,
+            ))) => {
+                self.labels_file.valid = !path.is_empty();
+                self.labels_file.value = path;
+                self.labels_exported = false;
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ExportLabels)) => {
+                if self.labels_file.value.is_empty() {
+                    return Command::none();
+                }
+                match std::fs::write(&self.labels_file.value, self.wallet.labels.export()) {
+                    Ok(()) => {
+                        self.warning = None;
+                        self.labels_exported = true;
+                    }
+                    Err(e) => {
+                        self.warning = Some(Error::Unexpected(format!(
+                            "Failed to export the labels to '{}': {}",
+                            self.labels_file.value, e
+                        )))
+                    }
+                }
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ImportLabels)) => {
+                if self.processing || self.labels_file.value.is_empty() {
+                    return Command::none();
+                }
+                self.warning = None;
+                self.processing = true;
+                self.updated = false;
+                Command::perform(
+                    import_labels(
+                        self.data_dir.clone(),
+                        cache.network,
+                        PathBuf::from(&self.labels_file.value),
+                    ),
+                    Message::Updated,
+                )
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::FetchBackendLabels)) => {
+                if self.processing {
+                    return Command::none();
+                }
+                self.warning = None;
+                self.processing = true;
+                Command::perform(
+                    async move {
+                        daemon
+                            .list_backend_labels()
+                            .map(|res| res.labels)
+                            .map_err(|e| e.into())
+                    },
+                    Message::BackendLabels,
+                )
+            }
+            Message::BackendLabels(res) => {
+                self.processing = false;
+                match res {
+                    Ok(labels) => self.backend_labels = Some(labels),
+                    Err(e) => self.warning = Some(e),
+                }
+                Command::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ImportBackendLabels)) => {
+                if self.processing {
+                    return Command::none();
+                }
+                self.warning = None;
+                self.processing = true;
+                self.updated = false;
+                Command::perform(
+                    import_backend_labels(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        self.backend_labels.take().unwrap_or_default(),
+                    ),
+                    Message::Updated,
+                )
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::DismissBackendLabels)) => {
+                if self.processing {
+                    return Command::none();
+                }
+                self.backend_labels = None;
+                self.warning = None;
+                self.processing = true;
+                self.updated = false;
+                // Nothing is imported, the import is just not offered anymore.
+                Command::perform(
+                    import_backend_labels(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        Vec::new(),
+                    ),
+                    Message::Updated,
+                )
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ConfirmVerification)) => {
+                self.hws_test = None;
+                self.processing = true;
+                self.updated = false;
+                Command::perform(
+                    record_verification(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        utils::now(),
+                    ),
+                    Message::Updated,
+                )
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::Save)) => {
+                if !self.displayed_history.valid
+                    || !self.fiat_currency.valid
+                    || !self.verification_reminder.valid
+                    || !self.consolidation_max_feerate.valid
+                    || !self.consolidation_future_feerate.valid
+                {
+                    return Command::none();
+                }
                 self.modal = None;
                 self.processing = true;
                 self.updated = false;
                 Command::perform(
-                    update_keys_aliases(
+                    update_wallet_settings(
                         self.data_dir.clone(),
                         cache.network,
                         self.wallet.clone(),
@@ -144,14 +568,57 @@ impl State for WalletSettingsState {
                             .iter()
                             .map(|(fg, name)| (*fg, name.value.to_owned()))
                             .collect(),
+                        self.displayed_history.value.parse::<u32>().ok(),
+                        self.display_unit,
+                        if self.fiat_currency.value.is_empty() {
+                            None
+                        } else {
+                            Some(self.fiat_currency.value.to_uppercase())
+                        },
+                        self.verification_reminder.value.parse::<u32>().ok(),
+                        self.consolidation_suggestion(),
                     ),
                     Message::Updated,
                 )
             }
             Message::View(view::Message::Close) => {
                 self.modal = None;
+                self.hws_test = None;
+                self.delete_wallet = None;
                 Command::none()
             }
+            Message::View(view::Message::Settings(view::SettingsMessage::DeleteWallet)) => {
+                self.delete_wallet = Some(DeleteWalletModal::new(daemon.is_external()));
+                Command::none()
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::DeleteWalletConfirmationEdited(_),
+            ))
+            | Message::View(view::Message::Settings(
+                view::SettingsMessage::DeleteWalletIgnoreBalance(_),
+            ))
+            | Message::View(view::Message::Settings(view::SettingsMessage::ConfirmDeleteWallet)) => {
+                let wallet = self.wallet.clone();
+                self.delete_wallet
+                    .as_mut()
+                    .map(|d| d.update(&wallet, cache, message))
+                    .unwrap_or_else(Command::none)
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::TestHardwareWallets)) => {
+                let test = HardwareWalletsTest::default();
+                let cmd = test.load(self.wallet.clone());
+                self.hws_test = Some(test);
+                cmd
+            }
+            Message::View(view::Message::Reload) | Message::HardwareWalletsTested(..)
+                if self.hws_test.is_some() =>
+            {
+                let wallet = self.wallet.clone();
+                self.hws_test
+                    .as_mut()
+                    .map(|t| t.update(wallet, message))
+                    .unwrap_or_else(Command::none)
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::RegisterWallet)) => {
                 self.modal = Some(RegisterWalletModal::new(
                     self.data_dir.clone(),
@@ -287,6 +754,139 @@ impl RegisterWalletModal {
     }
 }
 
+/// Diagnostic of the connected hardware wallets: it does not interact with the devices beyond
+/// retrieving their information.
+pub struct HardwareWalletsTest {
+    hws: Vec<HardwareWallet>,
+    errors: Vec<String>,
+    processing: bool,
+}
+
+impl Default for HardwareWalletsTest {
+    fn default() -> Self {
+        Self {
+            hws: Vec::new(),
+            errors: Vec::new(),
+            processing: true,
+        }
+    }
+}
+
+impl HardwareWalletsTest {
+    fn view<'a>(&'a self, wallet: &'a Wallet) -> Element<'a, view::Message> {
+        view::settings::hardware_wallets_test_modal(
+            &self.hws,
+            &self.errors,
+            self.processing,
+            wallet.descriptor_keys(),
+            &wallet.keys_aliases,
+        )
+    }
+
+    fn update(&mut self, wallet: Arc<Wallet>, message: Message) -> Command<Message> {
+        match message {
+            Message::View(view::Message::Reload) => {
+                if self.processing {
+                    return Command::none();
+                }
+                self.hws = Vec::new();
+                self.errors = Vec::new();
+                self.processing = true;
+                self.load(wallet)
+            }
+            Message::HardwareWalletsTested(hws, errors) => {
+                self.processing = false;
+                self.hws = hws;
+                self.errors = errors;
+                Command::none()
+            }
+            _ => Command::none(),
+        }
+    }
+
+    fn load(&self, wallet: Arc<Wallet>) -> Command<Message> {
+        Command::perform(
+            async move {
+                list_hardware_wallets_with_errors(
+                    &wallet.hardware_wallets,
+                    Some((&wallet.name, &wallet.main_descriptor.to_string())),
+                    wallet.main_descriptor.is_taproot(),
+                )
+                .await
+            },
+            |(hws, errors)| Message::HardwareWalletsTested(hws, errors),
+        )
+    }
+}
+
+/// Confirmation of the deletion of the wallet data. The user must type the name of the wallet,
+/// and explicitly acknowledge the remaining balance if there is any.
+pub struct DeleteWalletModal {
+    confirmation: form::Value<String>,
+    ignore_balance: bool,
+    daemon_is_external: bool,
+}
+
+impl DeleteWalletModal {
+    fn new(daemon_is_external: bool) -> Self {
+        Self {
+            confirmation: form::Value::default(),
+            ignore_balance: false,
+            daemon_is_external,
+        }
+    }
+
+    fn can_delete(&self, wallet: &Wallet, balance: Amount) -> bool {
+        !self.daemon_is_external
+            && self.confirmation.value == wallet.name
+            && (balance == Amount::from_sat(0) || self.ignore_balance)
+    }
+
+    fn view<'a>(&'a self, wallet: &'a Wallet, cache: &Cache) -> Element<'a, view::Message> {
+        let balance = unspent_balance(cache);
+        view::settings::delete_wallet_modal(
+            &wallet.name,
+            &self.confirmation,
+            balance,
+            self.ignore_balance,
+            self.daemon_is_external,
+            self.can_delete(wallet, balance),
+            cache.unit,
+        )
+    }
+
+    fn update(&mut self, wallet: &Wallet, cache: &Cache, message: Message) -> Command<Message> {
+        match message {
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::DeleteWalletConfirmationEdited(value),
+            )) => {
+                self.confirmation.valid = value.is_empty() || wallet.name.starts_with(&value);
+                self.confirmation.value = value;
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::DeleteWalletIgnoreBalance(ignore),
+            )) => {
+                self.ignore_balance = ignore;
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::ConfirmDeleteWallet)) => {
+                if self.can_delete(wallet, unspent_balance(cache)) {
+                    return Command::perform(async {}, |_| Message::DeleteWallet);
+                }
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+}
+
+fn unspent_balance(cache: &Cache) -> Amount {
+    cache
+        .coins
+        .iter()
+        .filter(|coin| coin.spend_info.is_none())
+        .fold(Amount::from_sat(0), |acc, coin| acc + coin.amount)
+}
+
 async fn register_wallet(
     data_dir: PathBuf,
     network: Network,
@@ -329,11 +929,17 @@ async fn register_wallet(
     Ok(fingerprint)
 }
 
-async fn update_keys_aliases(
+#[allow(clippy::too_many_arguments)]
+async fn update_wallet_settings(
     data_dir: PathBuf,
     network: Network,
     wallet: Arc<Wallet>,
     keys_aliases: Vec<(Fingerprint, String)>,
+    displayed_history_months: Option<u32>,
+    display_unit: Unit,
+    fiat_currency: Option<String>,
+    verification_reminder_months: Option<u32>,
+    consolidation_suggestion: Option<settings::ConsolidationSuggestion>,
 ) -> Result<(), Error> {
     let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
     let checksum = wallet.descriptor_checksum();
@@ -349,6 +955,146 @@ async fn update_keys_aliases(
                 name,
             })
             .collect();
+        wallet_setting.displayed_history_months = displayed_history_months;
+        wallet_setting.display_unit = Some(display_unit);
+        wallet_setting.fiat_currency = fiat_currency;
+        // The reminder is counted from the moment it's set if the user never confirmed a
+        // verification before.
+        if verification_reminder_months.is_some() && wallet_setting.last_verification.is_none() {
+            wallet_setting.last_verification = Some(utils::now());
+        }
+        wallet_setting.verification_reminder_months = verification_reminder_months;
+        wallet_setting.consolidation_suggestion = consolidation_suggestion;
+    }
+
+    settings.to_file(data_dir, network)?;
+
+    Ok(())
+}
+
+/// Record that the user just checked their signing devices and backups.
+async fn record_verification(
+    data_dir: PathBuf,
+    network: Network,
+    wallet: Arc<Wallet>,
+    timestamp: u32,
+) -> Result<(), Error> {
+    let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
+    let checksum = wallet.descriptor_checksum();
+    if let Some(wallet_setting) = settings
+        .wallets
+        .iter_mut()
+        .find(|w| w.descriptor_checksum == checksum)
+    {
+        wallet_setting.last_verification = Some(timestamp);
+    }
+
+    settings.to_file(data_dir, network)?;
+
+    Ok(())
+}
+
+async fn set_watch_only(
+    data_dir: PathBuf,
+    network: Network,
+    wallet: Arc<Wallet>,
+    watch_only: bool,
+) -> Result<(), Error> {
+    let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
+    let checksum = wallet.descriptor_checksum();
+    if let Some(wallet_setting) = settings
+        .wallets
+        .iter_mut()
+        .find(|w| w.descriptor_checksum == checksum)
+    {
+        wallet_setting.watch_only = watch_only;
+    }
+
+    settings.to_file(data_dir, network)?;
+
+    Ok(())
+}
+
+/// Replace the names of the wallet and its keys by the ones exported from another instance, and
+/// add its labels to the ones of the wallet.
+async fn import_annotations(
+    data_dir: PathBuf,
+    network: Network,
+    wallet: Arc<Wallet>,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let annotations = std::fs::read(&path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.to_string_lossy(), e))
+        .and_then(|content| {
+            serde_json::from_slice::<settings::Annotations>(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", path.to_string_lossy(), e))
+        })
+        .map_err(Error::Unexpected)?;
+    let imported_labels = annotations.labels.clone();
+    let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
+    let checksum = wallet.descriptor_checksum();
+    if let Some(wallet_setting) = settings
+        .wallets
+        .iter_mut()
+        .find(|w| w.descriptor_checksum == checksum)
+    {
+        wallet_setting.apply_annotations(annotations)?;
+    }
+
+    settings.to_file(data_dir.clone(), network)?;
+
+    if !imported_labels.is_empty() {
+        let mut labels = Labels::from_file(data_dir.clone(), network)?;
+        labels.import(&imported_labels)?;
+        labels.to_file(data_dir, network)?;
+    }
+
+    Ok(())
+}
+
+/// Add the labels of a BIP329 export to the ones of the wallet.
+async fn import_labels(data_dir: PathBuf, network: Network, path: PathBuf) -> Result<(), Error> {
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        Error::Unexpected(format!(
+            "Failed to read '{}': {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })?;
+    let mut labels = Labels::from_file(data_dir.clone(), network)?;
+    labels.import(&content)?;
+    labels.to_file(data_dir, network)?;
+
+    Ok(())
+}
+
+/// Label the addresses of the wallet as they were in the bitcoind watch-only wallet, without
+/// overwriting the labels already set in Liana, and record that the import was offered.
+async fn import_backend_labels(
+    data_dir: PathBuf,
+    network: Network,
+    wallet: Arc<Wallet>,
+    backend_labels: Vec<AddressLabel>,
+) -> Result<(), Error> {
+    if !backend_labels.is_empty() {
+        let mut labels = Labels::from_file(data_dir.clone(), network)?;
+        for AddressLabel { address, label } in backend_labels {
+            let address = address.to_string();
+            if labels.get(&address).is_none() {
+                labels.set(LabelKind::Addr, address, label);
+            }
+        }
+        labels.to_file(data_dir.clone(), network)?;
+    }
+
+    let mut settings = settings::Settings::from_file(data_dir.clone(), network)?;
+    let checksum = wallet.descriptor_checksum();
+    if let Some(wallet_setting) = settings
+        .wallets
+        .iter_mut()
+        .find(|w| w.descriptor_checksum == checksum)
+    {
+        wallet_setting.backend_labels_offered = true;
     }
 
     settings.to_file(data_dir, network)?;
@@ -360,6 +1106,7 @@ async fn list_hws(wallet: Arc<Wallet>) -> Vec<HardwareWallet> {
     list_hardware_wallets(
         &wallet.hardware_wallets,
         Some((&wallet.name, &wallet.main_descriptor.to_string())),
+        wallet.main_descriptor.is_taproot(),
     )
     .await
 }
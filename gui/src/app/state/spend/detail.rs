@@ -1,11 +1,19 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use iced::{Command, Element};
+use iced::{
+    futures::future::{abortable, AbortHandle},
+    time,
+    widget::qr_code,
+    Command, Element, Subscription,
+};
 use liana::{
     descriptors::LianaDescInfo,
     miniscript::bitcoin::{
         consensus,
         util::{bip32::Fingerprint, psbt::Psbt},
+        EcdsaSighashType,
     },
 };
 
@@ -14,18 +22,21 @@ use crate::{
         cache::Cache,
         error::Error,
         message::Message,
+        ur::{self, PsbtDecoder, PsbtEncoder},
         view,
         view::spend::detail,
         wallet::{Wallet, WalletError},
     },
     daemon::{
-        model::{SpendStatus, SpendTx},
-        Daemon,
+        model::{SpendStatus, SpendSummary, SpendTx},
+        Daemon, DaemonError,
     },
     hw::{list_hardware_wallets, HardwareWallet},
     ui::component::{form, modal},
 };
 
+use super::PsbtInput;
+
 trait Action {
     fn warning(&self) -> Option<&Error> {
         None
@@ -41,6 +52,9 @@ trait Action {
     ) -> Command<Message> {
         Command::none()
     }
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::none()
+    }
     fn view(&self) -> Element<view::Message>;
 }
 
@@ -71,10 +85,18 @@ impl SpendTxState {
         }
     }
 
+    pub fn subscription(&self) -> Subscription<Message> {
+        if let Some(action) = &self.action {
+            action.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
     pub fn update(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        cache: &Cache,
         message: Message,
     ) -> Command<Message> {
         match &message {
@@ -85,8 +107,13 @@ impl SpendTxState {
                 view::SpendTxMessage::Delete => {
                     self.action = Some(Box::new(DeleteAction::default()));
                 }
-                view::SpendTxMessage::Sign => {
-                    let action = SignAction::new(self.wallet.clone());
+                view::SpendTxMessage::Sign if !self.wallet.watch_only => {
+                    let summary = self.tx.summary(
+                        &self.wallet.main_descriptor,
+                        cache.network,
+                        cache.blockheight as u32,
+                    );
+                    let action = SignAction::new(self.wallet.clone(), summary);
                     let cmd = action.load(daemon);
                     self.action = Some(Box::new(action));
                     return cmd;
@@ -97,9 +124,15 @@ impl SpendTxState {
                     self.action = Some(Box::new(action));
                     return cmd;
                 }
+                view::SpendTxMessage::Export => {
+                    self.action = Some(Box::new(ExportAction::new(&self.tx.psbt)));
+                }
                 view::SpendTxMessage::Broadcast => {
                     self.action = Some(Box::new(BroadcastAction::default()));
                 }
+                view::SpendTxMessage::BumpFee => {
+                    self.action = Some(Box::new(BumpFeeAction::new(&self.tx)));
+                }
                 view::SpendTxMessage::Save => {
                     self.action = Some(Box::new(SaveAction::default()));
                 }
@@ -131,6 +164,9 @@ impl SpendTxState {
             &self.desc_info,
             &self.wallet.keys_aliases,
             cache.network,
+            cache.unit,
+            cache.price.as_ref(),
+            self.wallet.watch_only,
         );
         if let Some(action) = &self.action {
             modal::Modal::new(content, action.view())
@@ -220,6 +256,202 @@ impl Action for BroadcastAction {
     }
 }
 
+/// Replace a broadcast transaction by one paying a higher feerate, deducting the additional fee
+/// from its change output. The replacement is stored to be signed and broadcast.
+pub struct BumpFeeAction {
+    feerate: form::Value<String>,
+    processing: bool,
+    bumped: bool,
+    error: Option<Error>,
+}
+
+impl BumpFeeAction {
+    pub fn new(tx: &SpendTx) -> Self {
+        let error = if tx.change_indexes.is_empty() {
+            Some(Error::Unexpected(
+                "The transaction has no change output to deduct the additional fee from."
+                    .to_string(),
+            ))
+        } else {
+            None
+        };
+        Self {
+            feerate: form::Value {
+                value: (tx.feerate_vb() + 2).to_string(),
+                valid: true,
+            },
+            processing: false,
+            bumped: false,
+            error,
+        }
+    }
+}
+
+impl Action for BumpFeeAction {
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        message: Message,
+        tx: &mut SpendTx,
+    ) -> Command<Message> {
+        match message {
+            Message::View(view::Message::Spend(view::SpendTxMessage::BumpFeeFeerateEdited(s))) => {
+                self.feerate.valid = s.parse::<u64>().map(|f| f > 0).unwrap_or(false);
+                self.feerate.value = s;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::Confirm)) => {
+                if !self.feerate.valid || tx.change_indexes.is_empty() {
+                    return Command::none();
+                }
+                let feerate_vb = match self.feerate.value.parse::<u64>() {
+                    Ok(f) => f,
+                    Err(_) => return Command::none(),
+                };
+                let daemon = daemon.clone();
+                let txid = tx.psbt.unsigned_tx.txid();
+                self.processing = true;
+                self.error = None;
+                return Command::perform(
+                    async move {
+                        let psbt = daemon.rbf_spend_tx(&txid, feerate_vb)?;
+                        daemon.update_spend_tx(&psbt)?;
+                        Ok(psbt)
+                    },
+                    |res: Result<Psbt, DaemonError>| Message::Psbt(res.map_err(|e| e.into())),
+                );
+            }
+            Message::Psbt(res) => {
+                self.processing = false;
+                match res {
+                    Ok(_) => self.bumped = true,
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+
+    fn view(&self) -> Element<view::Message> {
+        detail::bump_fee_action(
+            self.error.as_ref(),
+            &self.feerate,
+            self.processing,
+            self.bumped,
+        )
+    }
+}
+
+/// Time each frame of an animated QR code is displayed.
+const QR_CODE_FRAME_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Hand the PSBT over to an external signer, with the signatures it already has.
+pub struct ExportAction {
+    psbt: Psbt,
+    qr_code: Option<qr_code::State>,
+    /// Too large PSBTs cannot be encoded as a single QR code, they are displayed as an
+    /// animated QR code instead.
+    frames: Option<PsbtEncoder>,
+    psbt_file: form::Value<String>,
+    exported: bool,
+    error: Option<Error>,
+}
+
+impl ExportAction {
+    pub fn new(psbt: &Psbt) -> Self {
+        let mut action = Self {
+            psbt: psbt.clone(),
+            qr_code: qr_code::State::new(psbt.to_string()).ok(),
+            frames: None,
+            psbt_file: form::Value::default(),
+            exported: false,
+            error: None,
+        };
+        if action.qr_code.is_none() {
+            match PsbtEncoder::new(psbt) {
+                Ok(frames) => {
+                    action.frames = Some(frames);
+                    action.next_frame();
+                }
+                Err(e) => action.error = Some(e),
+            }
+        }
+        action
+    }
+
+    fn next_frame(&mut self) {
+        if let Some(frames) = &mut self.frames {
+            match frames.next_frame() {
+                Ok(frame) => self.qr_code = qr_code::State::new(frame).ok(),
+                Err(e) => {
+                    self.frames = None;
+                    self.qr_code = None;
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+impl Action for ExportAction {
+    fn warning(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if self.frames.is_some() {
+            time::every(QR_CODE_FRAME_INTERVAL).map(|_| Message::QrCodeFrame)
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        message: Message,
+        _tx: &mut SpendTx,
+    ) -> Command<Message> {
+        match message {
+            Message::QrCodeFrame => self.next_frame(),
+            Message::View(view::Message::Spend(view::SpendTxMessage::PsbtFileEdited(path))) => {
+                self.psbt_file.valid = !path.is_empty();
+                self.psbt_file.value = path;
+                self.exported = false;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::ExportPsbtFile)) => {
+                if self.psbt_file.value.is_empty() {
+                    return Command::none();
+                }
+                return Command::perform(
+                    export_psbt(PathBuf::from(&self.psbt_file.value), self.psbt.clone()),
+                    Message::PsbtExported,
+                );
+            }
+            Message::PsbtExported(res) => match res {
+                Ok(()) => {
+                    self.exported = true;
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            },
+            _ => {}
+        }
+        Command::none()
+    }
+
+    fn view(&self) -> Element<view::Message> {
+        detail::export_action(
+            self.error.as_ref(),
+            self.psbt.to_string(),
+            self.qr_code.as_ref(),
+            self.frames.as_ref().map(|frames| frames.fragment_count()),
+            &self.psbt_file,
+            self.exported,
+        )
+    }
+}
+
 #[derive(Default)]
 pub struct DeleteAction {
     deleted: bool,
@@ -262,24 +494,136 @@ impl Action for DeleteAction {
 
 pub struct SignAction {
     wallet: Arc<Wallet>,
+    summary: SpendSummary,
+    /// The user reviewed the summary of the spend and confirmed it.
+    confirmed: bool,
+    /// Sighash type the inputs are signed with, ALL unless changed by the user.
+    sighash: EcdsaSighashType,
     chosen_hw: Option<usize>,
+    /// The device to sign again with, if it failed for a reason the user can fix on it.
+    retry_hw: Option<usize>,
     processing: bool,
+    /// Handle to stop waiting for the device the user is currently signing with.
+    signing: Option<AbortHandle>,
+    /// Devices whose signing was cancelled while they may still be waiting for the user.
+    busy: Vec<Fingerprint>,
     hws: Vec<HardwareWallet>,
     error: Option<Error>,
     signed: Vec<Fingerprint>,
+    /// Devices to sign with, in order, once the signature of the current one is stored.
+    queue: Vec<Fingerprint>,
+    /// The signed PSBT and the signer, waiting for the PSBT to be stored before replacing the
+    /// one of the spend.
+    pending: Option<(Psbt, Fingerprint)>,
+    /// Path of the file the PSBT is exported to for an air-gapped signing device, and from
+    /// which the signed PSBT is imported back.
+    psbt_file: form::Value<String>,
+    exported: bool,
 }
 
 impl SignAction {
-    pub fn new(wallet: Arc<Wallet>) -> Self {
+    pub fn new(wallet: Arc<Wallet>, summary: SpendSummary) -> Self {
         Self {
             wallet,
+            summary,
+            confirmed: false,
+            sighash: EcdsaSighashType::All,
             chosen_hw: None,
+            retry_hw: None,
             processing: false,
+            signing: None,
+            busy: Vec::new(),
             hws: Vec::new(),
             error: None,
             signed: Vec::new(),
+            queue: Vec::new(),
+            pending: None,
+            psbt_file: form::Value::default(),
+            exported: false,
         }
     }
+
+    /// The connected devices which can provide a missing signature of the spending path.
+    fn signable_hws(&self) -> Vec<Fingerprint> {
+        self.hws
+            .iter()
+            .filter_map(|hw| match hw {
+                HardwareWallet::Supported { fingerprint, .. } => Some(*fingerprint),
+                _ => None,
+            })
+            .filter(|fingerprint| {
+                !self.signed.contains(fingerprint)
+                    && !self.busy.contains(fingerprint)
+                    && self
+                        .summary
+                        .keys
+                        .iter()
+                        .any(|(fg, signed)| fg == fingerprint && !signed)
+            })
+            .collect()
+    }
+
+    /// The key of the hot signer and whether a connected device holds the same key, in which
+    /// case signing with one of them makes the other redundant: both give the same signature.
+    fn hot_signer(&self) -> Option<(Fingerprint, bool)> {
+        self.wallet.signer.as_ref().map(|signer| {
+            let fingerprint = signer.fingerprint();
+            (
+                fingerprint,
+                self.hws
+                    .iter()
+                    .any(|hw| hw.fingerprint() == Some(fingerprint)),
+            )
+        })
+    }
+
+    fn sign_with_hw(&mut self, i: usize, tx: &SpendTx) -> Command<Message> {
+        if let Some(HardwareWallet::Supported {
+            fingerprint,
+            device,
+            ..
+        }) = self.hws.get(i)
+        {
+            if self.processing || self.busy.contains(fingerprint) {
+                return Command::none();
+            }
+            // Hardware wallets are only known to sign with SIGHASH_ALL.
+            if self.sighash != EcdsaSighashType::All {
+                self.queue.clear();
+                self.error = Some(Error::UnsupportedSighash(self.sighash));
+                return Command::none();
+            }
+            self.chosen_hw = Some(i);
+            self.retry_hw = None;
+            self.processing = true;
+            let psbt = with_sighash(tx.psbt.clone(), self.sighash);
+            // The transports of the devices can't be interrupted, so cancelling only
+            // makes us stop waiting for the signature.
+            let (signing, handle) = abortable(sign_psbt(device.clone(), *fingerprint, psbt));
+            self.signing = Some(handle);
+            return Command::perform(signing, |res| {
+                Message::Signed(res.unwrap_or_else(|_| {
+                    Err(Error::Unexpected("Signing was cancelled".to_string()))
+                }))
+            });
+        }
+        Command::none()
+    }
+
+    /// Start signing with the next queued device still connected, if any.
+    fn sign_with_next_hw(&mut self, tx: &SpendTx) -> Command<Message> {
+        while !self.queue.is_empty() {
+            let fingerprint = self.queue.remove(0);
+            if let Some(i) = self
+                .hws
+                .iter()
+                .position(|hw| hw.fingerprint() == Some(fingerprint))
+            {
+                return self.sign_with_hw(i, tx);
+            }
+        }
+        Command::none()
+    }
 }
 
 impl Action for SignAction {
@@ -300,53 +644,151 @@ impl Action for SignAction {
         tx: &mut SpendTx,
     ) -> Command<Message> {
         match message {
+            Message::View(view::Message::Spend(view::SpendTxMessage::Confirm)) => {
+                self.confirmed = true;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::SighashSelected(sighash))) => {
+                self.sighash = sighash;
+                self.error = None;
+            }
             Message::View(view::Message::SelectHardwareWallet(i)) => {
-                if let Some(HardwareWallet::Supported {
-                    fingerprint,
-                    device,
-                    ..
-                }) = self.hws.get(i)
-                {
-                    self.chosen_hw = Some(i);
-                    self.processing = true;
-                    let psbt = tx.psbt.clone();
-                    return Command::perform(
-                        sign_psbt(device.clone(), *fingerprint, psbt),
-                        Message::Signed,
-                    );
+                return self.sign_with_hw(i, tx);
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::RetrySigning)) => {
+                if let Some(i) = self.retry_hw.take() {
+                    self.error = None;
+                    return self.sign_with_hw(i, tx);
+                }
+            }
+            // Sign with every connected device which can provide a missing signature, one
+            // after the other.
+            Message::View(view::Message::Spend(view::SpendTxMessage::SignWithAllDevices)) => {
+                if self.processing {
+                    return Command::none();
+                }
+                self.queue = self.signable_hws();
+                return self.sign_with_next_hw(tx);
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::CancelSigning)) => {
+                self.queue.clear();
+                if let Some(handle) = self.signing.take() {
+                    handle.abort();
+                    // The device may still display the transaction, it cannot be used again
+                    // until the list of devices is refreshed.
+                    if let Some(fingerprint) = self
+                        .chosen_hw
+                        .and_then(|i| self.hws.get(i))
+                        .and_then(|hw| hw.fingerprint())
+                    {
+                        self.busy.push(fingerprint);
+                    }
+                    self.processing = false;
+                    self.chosen_hw = None;
                 }
             }
             Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)) => {
+                // The key may already have signed through a device holding the same key.
+                match self.hot_signer() {
+                    Some((fingerprint, _))
+                        if !self.processing && !self.signed.contains(&fingerprint) => {}
+                    _ => return Command::none(),
+                }
                 self.processing = true;
                 return Command::perform(
-                    sign_psbt_with_hot_signer(self.wallet.clone(), tx.psbt.clone()),
+                    sign_psbt_with_hot_signer(
+                        self.wallet.clone(),
+                        with_sighash(tx.psbt.clone(), self.sighash),
+                    ),
                     Message::Signed,
                 );
             }
-            Message::Signed(res) => match res {
+            Message::View(view::Message::Spend(view::SpendTxMessage::PsbtFileEdited(path))) => {
+                self.psbt_file.valid = !path.is_empty();
+                self.psbt_file.value = path;
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::ExportPsbtFile)) => {
+                if self.processing || self.psbt_file.value.is_empty() {
+                    return Command::none();
+                }
+                return Command::perform(
+                    export_psbt(
+                        PathBuf::from(&self.psbt_file.value),
+                        with_sighash(tx.psbt.clone(), self.sighash),
+                    ),
+                    Message::PsbtExported,
+                );
+            }
+            Message::PsbtExported(res) => match res {
+                Ok(()) => {
+                    self.exported = true;
+                    self.error = None;
+                }
                 Err(e) => self.error = Some(e),
+            },
+            Message::View(view::Message::Spend(view::SpendTxMessage::ImportSignedPsbtFile)) => {
+                if self.processing || self.psbt_file.value.is_empty() {
+                    return Command::none();
+                }
+                self.processing = true;
+                self.error = None;
+                return Command::perform(
+                    import_signed_psbt(PathBuf::from(&self.psbt_file.value), tx.psbt.clone()),
+                    Message::Signed,
+                );
+            }
+            // The result of a cancelled signing is dropped.
+            Message::Signed(_) if !self.processing => {}
+            Message::Signed(res) => match res {
+                Err(e) => {
+                    self.queue.clear();
+                    self.signing = None;
+                    self.processing = false;
+                    self.retry_hw = match &e {
+                        Error::Signer(kind, _) if kind.is_retryable() => self.chosen_hw,
+                        _ => None,
+                    };
+                    self.chosen_hw = None;
+                    self.error = Some(e);
+                }
                 Ok((psbt, fingerprint)) => {
+                    self.signing = None;
                     self.error = None;
-                    self.signed.push(fingerprint);
+                    self.pending = Some((psbt.clone(), fingerprint));
                     let daemon = daemon.clone();
-                    tx.psbt = psbt.clone();
                     return Command::perform(
                         async move { daemon.update_spend_tx(&psbt).map_err(|e| e.into()) },
                         Message::Updated,
                     );
                 }
             },
-            Message::Updated(res) => match res {
-                Ok(()) => {
-                    self.processing = false;
-                    tx.sigs = self
-                        .wallet
-                        .main_descriptor
-                        .partial_spend_info(&tx.psbt)
-                        .unwrap();
+            // The spend is only updated once the signed PSBT is stored, so that a signature
+            // is never displayed without being saved.
+            Message::Updated(res) => {
+                self.processing = false;
+                match (res, self.pending.take()) {
+                    (Ok(()), Some((psbt, fingerprint))) => {
+                        self.signed.push(fingerprint);
+                        tx.psbt = psbt;
+                        tx.sigs = self
+                            .wallet
+                            .main_descriptor
+                            .partial_spend_info(&tx.psbt)
+                            .unwrap();
+                        self.summary.update_sigs(&tx.sigs);
+                        // Only move on to the next device once this signature is stored.
+                        if self.summary.sigs_count < self.summary.threshold {
+                            return self.sign_with_next_hw(tx);
+                        }
+                        self.queue.clear();
+                    }
+                    (Ok(()), None) => {}
+                    (Err(e), _) => {
+                        self.queue.clear();
+                        self.chosen_hw = None;
+                        self.error = Some(e);
+                    }
                 }
-                Err(e) => self.error = Some(e),
-            },
+            }
             // We add the new hws without dropping the reference of the previous ones.
             Message::ConnectedHardwareWallets(hws) => {
                 for h in hws {
@@ -360,8 +802,13 @@ impl Action for SignAction {
                 }
             }
             Message::View(view::Message::Reload) => {
+                if self.processing {
+                    return Command::none();
+                }
                 self.hws = Vec::new();
+                self.busy = Vec::new();
                 self.chosen_hw = None;
+                self.retry_hw = None;
                 self.error = None;
                 return self.load(daemon);
             }
@@ -370,21 +817,162 @@ impl Action for SignAction {
         Command::none()
     }
     fn view(&self) -> Element<view::Message> {
+        if !self.confirmed {
+            return view::spend::detail::sign_readiness_view(
+                &self.summary,
+                &self.wallet.keys_aliases,
+                self.wallet.display_unit,
+            );
+        }
         view::spend::detail::sign_action(
             self.error.as_ref(),
+            &self.summary,
+            &self.wallet.keys_aliases,
             &self.hws,
-            self.wallet.signer.as_ref().map(|s| s.fingerprint()),
+            self.hot_signer(),
             self.processing,
             self.chosen_hw,
+            self.retry_hw.is_some(),
             &self.signed,
+            &self.busy,
+            self.queue.len(),
+            self.signable_hws().len() > 1,
+            self.sighash,
+            &self.psbt_file,
+            self.exported,
         )
     }
 }
 
+/// Set the sighash type of every input of the PSBT. SIGHASH_ALL is the default and is
+/// left implicit.
+fn with_sighash(mut psbt: Psbt, sighash: EcdsaSighashType) -> Psbt {
+    for input in &mut psbt.inputs {
+        input.sighash_type = if sighash == EcdsaSighashType::All {
+            None
+        } else {
+            Some(sighash.into())
+        };
+    }
+    psbt
+}
+
+/// Read a PSBT signed by an air-gapped device, either as binary or base64 encoded, and merge its
+/// signatures into the given PSBT. Returns the merged PSBT along with the fingerprint of the
+/// device that signed it.
+async fn import_signed_psbt(path: PathBuf, psbt: Psbt) -> Result<(Psbt, Fingerprint), Error> {
+    let signed = read_psbt_file(&path)?;
+    if signed.unsigned_tx.txid() != psbt.unsigned_tx.txid() {
+        return Err(Error::Unexpected(
+            "The signed PSBT is not for this transaction".to_string(),
+        ));
+    }
+
+    // The signer is identified by the key origin of its new signatures.
+    let fingerprint = signed
+        .inputs
+        .iter()
+        .zip(psbt.inputs.iter())
+        .find_map(|(signed_input, input)| {
+            signed_input
+                .partial_sigs
+                .keys()
+                .filter(|pk| !input.partial_sigs.contains_key(pk))
+                .find_map(|pk| signed_input.bip32_derivation.get(&pk.inner))
+                .or_else(|| {
+                    signed_input
+                        .tap_script_sigs
+                        .keys()
+                        .filter(|key| !input.tap_script_sigs.contains_key(key))
+                        .map(|(pk, _)| pk)
+                        .chain(
+                            signed_input
+                                .tap_key_sig
+                                .filter(|_| input.tap_key_sig.is_none())
+                                .and(signed_input.tap_internal_key.as_ref()),
+                        )
+                        .find_map(|pk| signed_input.tap_key_origins.get(pk))
+                        .map(|(_, source)| source)
+                })
+                .map(|(fingerprint, _)| *fingerprint)
+        })
+        .ok_or_else(|| {
+            Error::Unexpected("The file does not contain any new signature".to_string())
+        })?;
+
+    let mut merged = psbt;
+    merge_signatures(&mut merged, &signed);
+    Ok((merged, fingerprint))
+}
+
+/// Write the PSBT to the given file, in its binary format.
+async fn export_psbt(path: PathBuf, psbt: Psbt) -> Result<(), Error> {
+    std::fs::write(&path, consensus::serialize(&psbt)).map_err(|e| {
+        Error::Unexpected(format!(
+            "Failed to write PSBT to '{}': {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+/// Read a PSBT from a file, either base64 encoded or in its binary format.
+fn read_psbt_file(path: &Path) -> Result<Psbt, Error> {
+    let content = std::fs::read(path).map_err(|e| {
+        Error::Unexpected(format!(
+            "Failed to read the PSBT from '{}': {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })?;
+    std::str::from_utf8(&content)
+        .ok()
+        .and_then(|s| base64::decode(s.trim()).ok())
+        .and_then(|bytes| consensus::deserialize(&bytes).ok())
+        .or_else(|| consensus::deserialize(&content).ok())
+        .ok_or_else(|| {
+            Error::Unexpected(format!(
+                "'{}' contains neither a base64 encoded nor a binary PSBT",
+                path.to_string_lossy()
+            ))
+        })
+}
+
+/// Add to the PSBT the signatures of the inputs of the updated PSBT spending the same coins.
+fn merge_signatures(psbt: &mut Psbt, updated: &Psbt) {
+    for (i, input) in psbt.inputs.iter_mut().enumerate() {
+        if psbt
+            .unsigned_tx
+            .input
+            .get(i)
+            .map(|tx_in| tx_in.previous_output)
+            != updated
+                .unsigned_tx
+                .input
+                .get(i)
+                .map(|tx_in| tx_in.previous_output)
+        {
+            continue;
+        }
+        if let Some(updated_input) = updated.inputs.get(i) {
+            input
+                .partial_sigs
+                .extend(updated_input.partial_sigs.clone().into_iter());
+            input
+                .tap_script_sigs
+                .extend(updated_input.tap_script_sigs.clone().into_iter());
+            if input.tap_key_sig.is_none() {
+                input.tap_key_sig = updated_input.tap_key_sig;
+            }
+        }
+    }
+}
+
 async fn list_hws(wallet: Arc<Wallet>) -> Vec<HardwareWallet> {
     list_hardware_wallets(
         &wallet.hardware_wallets,
         Some((&wallet.name, &wallet.main_descriptor.to_string())),
+        wallet.main_descriptor.is_taproot(),
     )
     .await
 }
@@ -394,9 +982,7 @@ async fn sign_psbt_with_hot_signer(
     psbt: Psbt,
 ) -> Result<(Psbt, Fingerprint), Error> {
     if let Some(signer) = &wallet.signer {
-        let psbt = signer.sign_psbt(psbt).map_err(|e| {
-            WalletError::HotSigner(format!("Hot signer failed to sign psbt: {}", e))
-        })?;
+        let psbt = signer.sign_psbt(psbt).map_err(Error::from)?;
         Ok((psbt, signer.fingerprint()))
     } else {
         Err(WalletError::HotSigner("Hot signer not loaded".to_string()).into())
@@ -415,7 +1001,11 @@ async fn sign_psbt(
 pub struct UpdateAction {
     wallet: Arc<Wallet>,
     psbt: String,
-    updated: form::Value<String>,
+    updated: PsbtInput,
+    /// Frames of the animated QR code of the updated PSBT scanned so far.
+    frames: PsbtDecoder,
+    frames_file: form::Value<String>,
+    psbt_file: form::Value<String>,
     processing: bool,
     error: Option<Error>,
     success: bool,
@@ -426,12 +1016,26 @@ impl UpdateAction {
         Self {
             wallet,
             psbt,
-            updated: form::Value::default(),
+            updated: PsbtInput::default(),
+            frames: PsbtDecoder::default(),
+            frames_file: form::Value::default(),
+            psbt_file: form::Value::default(),
             processing: false,
             error: None,
             success: false,
         }
     }
+
+    /// Receive the frames of an animated QR code, separated by whitespaces. Once the PSBT is
+    /// complete, it is decoded as if it was entered by the user.
+    fn receive_frames(&mut self, frames: &str) -> Result<Command<Message>, Error> {
+        for frame in frames.split_whitespace() {
+            if let Some(psbt) = self.frames.receive(frame)? {
+                return Ok(self.updated.edit(psbt.to_string()));
+            }
+        }
+        Ok(Command::none())
+    }
 }
 
 impl Action for UpdateAction {
@@ -441,9 +1045,13 @@ impl Action for UpdateAction {
         } else {
             view::spend::detail::update_spend_view(
                 self.psbt.clone(),
-                &self.updated,
+                &self.updated.value,
+                self.frames.missing_frames(),
+                &self.frames_file,
+                &self.psbt_file,
                 self.error.as_ref(),
                 self.processing,
+                self.updated.decoding,
             )
         }
     }
@@ -461,31 +1069,8 @@ impl Action for UpdateAction {
                     Ok(()) => {
                         self.success = true;
                         self.error = None;
-                        let psbt = consensus::encode::deserialize::<Psbt>(
-                            &base64::decode(&self.updated.value).unwrap(),
-                        )
-                        .expect("Already checked");
-                        for (i, input) in tx.psbt.inputs.iter_mut().enumerate() {
-                            if tx
-                                .psbt
-                                .unsigned_tx
-                                .input
-                                .get(i)
-                                .map(|tx_in| tx_in.previous_output)
-                                != psbt
-                                    .unsigned_tx
-                                    .input
-                                    .get(i)
-                                    .map(|tx_in| tx_in.previous_output)
-                            {
-                                continue;
-                            }
-                            if let Some(updated_input) = psbt.inputs.get(i) {
-                                input
-                                    .partial_sigs
-                                    .extend(updated_input.partial_sigs.clone().into_iter());
-                            }
-                        }
+                        let psbt = self.updated.psbt.as_ref().expect("Checked before updating");
+                        merge_signatures(&mut tx.psbt, psbt);
                         tx.sigs = self
                             .wallet
                             .main_descriptor
@@ -495,23 +1080,107 @@ impl Action for UpdateAction {
                     Err(e) => self.error = e.into(),
                 }
             }
+            // A frame typed by a scanner is only received once complete, the input is then
+            // cleared for the next one.
+            Message::View(view::Message::ImportSpend(view::ImportSpendMessage::PsbtEdited(s)))
+                if !self.processing && ur::is_ur(&s) =>
+            {
+                self.updated.value.value = String::new();
+                match self.receive_frames(&s) {
+                    Ok(cmd) => {
+                        self.updated.value.valid = true;
+                        return cmd;
+                    }
+                    Err(_) => {
+                        self.updated.value.value = s;
+                        self.updated.value.valid = false;
+                    }
+                }
+            }
+            // The PSBT sent to the daemon is kept until it answers.
             Message::View(view::Message::ImportSpend(view::ImportSpendMessage::PsbtEdited(s))) => {
-                self.updated.value = s;
-                if let Some(psbt) = base64::decode(&self.updated.value)
-                    .ok()
-                    .and_then(|bytes| consensus::encode::deserialize::<Psbt>(&bytes).ok())
-                {
-                    self.updated.valid = tx.psbt.unsigned_tx.txid() == psbt.unsigned_tx.txid();
+                if !self.processing {
+                    return self.updated.edit(s);
+                }
+            }
+            Message::View(view::Message::ImportSpend(
+                view::ImportSpendMessage::QrCodeFileEdited(path),
+            )) => {
+                self.frames_file.valid = !path.is_empty();
+                self.frames_file.value = path;
+            }
+            Message::View(view::Message::ImportSpend(
+                view::ImportSpendMessage::ImportQrCodeFile,
+            )) => {
+                if self.processing || self.frames_file.value.is_empty() {
+                    return Command::none();
                 }
+                let path = PathBuf::from(&self.frames_file.value);
+                return Command::perform(
+                    async move {
+                        std::fs::read_to_string(&path).map_err(|e| {
+                            Error::Unexpected(format!(
+                                "Failed to read the QR code frames from '{}': {}",
+                                path.to_string_lossy(),
+                                e
+                            ))
+                        })
+                    },
+                    Message::QrCodeFramesRead,
+                );
+            }
+            Message::QrCodeFramesRead(res) => {
+                match res.and_then(|frames| self.receive_frames(&frames)) {
+                    Ok(cmd) => {
+                        self.error = None;
+                        return cmd;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Message::View(view::Message::ImportSpend(
+                view::ImportSpendMessage::PsbtFileEdited(path),
+            )) => {
+                self.psbt_file.valid = !path.is_empty();
+                self.psbt_file.value = path;
+            }
+            // The loaded PSBT replaces the one entered, it is merged once confirmed.
+            Message::View(view::Message::ImportSpend(view::ImportSpendMessage::ImportPsbtFile)) => {
+                if self.processing || self.psbt_file.value.is_empty() {
+                    return Command::none();
+                }
+                let path = PathBuf::from(&self.psbt_file.value);
+                return Command::perform(
+                    async move { read_psbt_file(&path) },
+                    Message::PsbtFileRead,
+                );
+            }
+            Message::PsbtFileRead(res) => match res {
+                Ok(psbt) => {
+                    let txid = tx.psbt.unsigned_tx.txid();
+                    self.updated
+                        .loaded(psbt, |psbt| psbt.unsigned_tx.txid() == txid);
+                    self.error = if self.updated.psbt.is_some() {
+                        None
+                    } else {
+                        Some(Error::Unexpected(format!(
+                            "The PSBT in '{}' is not for this transaction",
+                            self.psbt_file.value
+                        )))
+                    };
+                }
+                Err(e) => self.error = Some(e),
+            },
+            // The updated PSBT must be for the same transaction.
+            Message::DecodedPsbt(edit, psbt) => {
+                let txid = tx.psbt.unsigned_tx.txid();
+                self.updated
+                    .decoded(edit, psbt, |psbt| psbt.unsigned_tx.txid() == txid);
             }
             Message::View(view::Message::ImportSpend(view::ImportSpendMessage::Confirm)) => {
-                if self.updated.valid {
+                if let Some(updated) = self.updated.psbt.clone() {
                     self.processing = true;
                     self.error = None;
-                    let updated: Psbt = consensus::encode::deserialize(
-                        &base64::decode(&self.updated.value).expect("Already checked"),
-                    )
-                    .unwrap();
                     return Command::perform(
                         async move { daemon.update_spend_tx(&updated).map_err(|e| e.into()) },
                         Message::Updated,
@@ -524,3 +1193,272 @@ impl Action for UpdateAction {
         Command::none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::error::SignerErrorKind,
+        daemon::{client::Lianad, model::Coin},
+        signer::Signer,
+        utils::mock::Daemon as MockDaemon,
+    };
+    use liana::{
+        descriptors::MultipathDescriptor,
+        miniscript::bitcoin::{
+            self, util::bip32::Fingerprint, Amount, Network, OutPoint, PackedLockTime, Script,
+            Transaction, TxIn, TxOut,
+        },
+        signer::HotSigner,
+    };
+    use std::str::FromStr;
+
+    const DESC: &str = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+
+    #[test]
+    fn test_sign_action_persistence_failure() {
+        let wallet = Arc::new(Wallet::new(MultipathDescriptor::from_str(DESC).unwrap()));
+        let outpoint = OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: outpoint,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        let mut tx = SpendTx::new(
+            psbt.clone(),
+            vec![Coin {
+                outpoint,
+                amount: Amount::from_sat(20_000),
+                block_height: Some(1),
+                is_immature: false,
+                is_replaceable: false,
+                spend_info: None,
+            }],
+            wallet.main_descriptor.partial_spend_info(&psbt).unwrap(),
+            &wallet.main_descriptor,
+        );
+        let summary = tx.summary(&wallet.main_descriptor, Network::Testnet, 1);
+        let daemon: Arc<dyn Daemon + Sync + Send> =
+            Arc::new(Lianad::new(MockDaemon::new(Vec::new()).run()));
+
+        let mut signed = psbt.clone();
+        signed.inputs[0].partial_sigs.insert(
+            bitcoin::PublicKey::from_str(
+                "023a664c5617412f0b292665b1fd9d766456a7a3b1614c7e7c5f411200ff1958ef",
+            )
+            .unwrap(),
+            bitcoin::EcdsaSig::from_str("304402204004fcdbb9c0d0cbf585f58cee34dccb012efbd8fc2b0d5e97760045ae35803802201a0bd7ec2383e0b93748abc9946c8e17a8312e314dab85982aeba650e738cbf401").unwrap(),
+        );
+        let fingerprint = Fingerprint::from_str("abcdef01").unwrap();
+
+        let mut action = SignAction::new(wallet, summary);
+        action.processing = true;
+        let _ = action.update(
+            daemon.clone(),
+            Message::Signed(Ok((signed.clone(), fingerprint))),
+            &mut tx,
+        );
+        // The spend is not updated until the signed PSBT is stored.
+        assert_eq!(tx.psbt, psbt);
+
+        // If it could not be stored, the spend is left as it was before signing.
+        let _ = action.update(
+            daemon.clone(),
+            Message::Updated(Err(Error::Unexpected("Failed to store".to_string()))),
+            &mut tx,
+        );
+        assert_eq!(tx.psbt, psbt);
+        assert!(action.signed.is_empty());
+        assert!(action.error.is_some());
+        assert!(!action.processing);
+
+        // Once stored, the spend has the new signature.
+        action.processing = true;
+        let _ = action.update(
+            daemon.clone(),
+            Message::Signed(Ok((signed.clone(), fingerprint))),
+            &mut tx,
+        );
+        // A queued device which is not connected anymore is skipped.
+        action.queue = vec![Fingerprint::from_str("aabbccdd").unwrap()];
+        let _ = action.update(daemon, Message::Updated(Ok(())), &mut tx);
+        assert_eq!(tx.psbt, signed);
+        assert_eq!(action.signed, vec![fingerprint]);
+        assert!(action.error.is_none());
+        assert!(action.queue.is_empty());
+        assert!(!action.processing);
+    }
+
+    #[test]
+    fn test_sign_action_hot_signer_same_key() {
+        let signer = Signer::new(
+            HotSigner::from_str(
+                Network::Testnet,
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            )
+            .unwrap(),
+        );
+        let fingerprint = signer.fingerprint();
+        let wallet =
+            Arc::new(Wallet::new(MultipathDescriptor::from_str(DESC).unwrap()).with_signer(signer));
+        let outpoint = OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: outpoint,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        let mut tx = SpendTx::new(
+            psbt.clone(),
+            Vec::new(),
+            wallet.main_descriptor.partial_spend_info(&psbt).unwrap(),
+            &wallet.main_descriptor,
+        );
+        let summary = tx.summary(&wallet.main_descriptor, Network::Testnet, 1);
+        let daemon: Arc<dyn Daemon + Sync + Send> =
+            Arc::new(Lianad::new(MockDaemon::new(Vec::new()).run()));
+
+        let mut action = SignAction::new(wallet, summary);
+        assert_eq!(action.hot_signer(), Some((fingerprint, false)));
+
+        // The key signed through a device with the same fingerprint: the hot signer would only
+        // give the same signature again.
+        action.signed.push(fingerprint);
+        let _ = action.update(
+            daemon.clone(),
+            Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)),
+            &mut tx,
+        );
+        assert!(!action.processing);
+        // Nor can a device with this key be queued once the key signed.
+        assert!(action.signable_hws().is_empty());
+
+        action.signed.clear();
+        let _ = action.update(
+            daemon,
+            Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)),
+            &mut tx,
+        );
+        assert!(action.processing);
+    }
+
+    #[test]
+    fn test_sign_action_retry() {
+        let wallet = Arc::new(Wallet::new(MultipathDescriptor::from_str(DESC).unwrap()));
+        let outpoint = OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: outpoint,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        let mut tx = SpendTx::new(
+            psbt.clone(),
+            Vec::new(),
+            wallet.main_descriptor.partial_spend_info(&psbt).unwrap(),
+            &wallet.main_descriptor,
+        );
+        let summary = tx.summary(&wallet.main_descriptor, Network::Testnet, 1);
+        let daemon: Arc<dyn Daemon + Sync + Send> =
+            Arc::new(Lianad::new(MockDaemon::new(Vec::new()).run()));
+        let mut action = SignAction::new(wallet, summary);
+
+        // The user can unlock the device and sign again with it.
+        action.processing = true;
+        action.chosen_hw = Some(0);
+        let _ = action.update(
+            daemon.clone(),
+            Message::Signed(Err(Error::Signer(
+                SignerErrorKind::Locked,
+                "Locked device".to_string(),
+            ))),
+            &mut tx,
+        );
+        assert_eq!(action.retry_hw, Some(0));
+        assert!(action.error.is_some());
+
+        // A device without any key of the transaction will not do better a second time.
+        action.processing = true;
+        action.chosen_hw = Some(0);
+        let _ = action.update(
+            daemon,
+            Message::Signed(Err(Error::Signer(
+                SignerErrorKind::WrongDevice,
+                "Device did not sign".to_string(),
+            ))),
+            &mut tx,
+        );
+        assert_eq!(action.retry_hw, None);
+    }
+
+    #[test]
+    fn test_read_psbt_file() {
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: OutPoint::from_str(
+                    "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+                )
+                .unwrap(),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("liana-gui-psbt-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A base64 encoded PSBT, possibly followed by a new line.
+        let path = dir.join("base64.psbt");
+        std::fs::write(&path, format!("{}\n", psbt)).unwrap();
+        assert_eq!(read_psbt_file(&path).unwrap(), psbt);
+
+        // A binary PSBT.
+        let path = dir.join("binary.psbt");
+        std::fs::write(&path, consensus::serialize(&psbt)).unwrap();
+        assert_eq!(read_psbt_file(&path).unwrap(), psbt);
+
+        // Neither of them, or no file at all.
+        let path = dir.join("invalid.psbt");
+        std::fs::write(&path, "not a psbt").unwrap();
+        assert!(read_psbt_file(&path).is_err());
+        assert!(read_psbt_file(&dir.join("missing.psbt")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
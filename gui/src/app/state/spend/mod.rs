@@ -1,8 +1,9 @@
 pub mod detail;
 mod step;
+
 use std::sync::Arc;
 
-use iced::{Command, Element};
+use iced::{Command, Element, Subscription};
 
 use liana::miniscript::bitcoin::{consensus, util::psbt::Psbt};
 
@@ -16,12 +17,16 @@ use crate::{
     ui::component::{form, modal},
 };
 
+pub use step::{consolidation_savings, input_vsize};
+
 pub struct SpendPanel {
     wallet: Arc<Wallet>,
     selected_tx: Option<detail::SpendTxState>,
     spend_txs: Vec<SpendTx>,
     warning: Option<Error>,
     import_tx: Option<ImportSpendState>,
+    /// Number of drafts deleted by the last clean up.
+    cleaned_up: Option<usize>,
 }
 
 impl SpendPanel {
@@ -32,6 +37,7 @@ impl SpendPanel {
             warning: None,
             selected_tx: None,
             import_tx: None,
+            cleaned_up: None,
         }
     }
 }
@@ -45,7 +51,7 @@ impl State for SpendPanel {
                 &Menu::Spend,
                 cache,
                 self.warning.as_ref(),
-                view::spend::spend_view(&self.spend_txs),
+                view::spend::spend_view(&self.spend_txs, self.cleaned_up, cache.unit),
             );
             if let Some(import_tx) = &self.import_tx {
                 modal::Modal::new(list_view, import_tx.view())
@@ -75,6 +81,19 @@ impl State for SpendPanel {
                     self.spend_txs = txs;
                 }
             },
+            Message::View(view::Message::CleanupSpends) => {
+                return Command::perform(
+                    async move { daemon.cleanup_spend_txs().map_err(|e| e.into()) },
+                    Message::SpendTxsCleanedUp,
+                );
+            }
+            Message::SpendTxsCleanedUp(res) => match res {
+                Err(e) => self.warning = Some(e),
+                Ok(res) => {
+                    self.cleaned_up = Some(res.deleted.len());
+                    return self.load(daemon);
+                }
+            },
             Message::View(view::Message::ImportSpend(view::ImportSpendMessage::Import)) => {
                 if self.import_tx.is_none() {
                     self.import_tx = Some(ImportSpendState::new());
@@ -111,6 +130,14 @@ impl State for SpendPanel {
         Command::none()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(tx) = &self.selected_tx {
+            tx.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
     fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Command<Message> {
         let daemon = daemon.clone();
         Command::perform(
@@ -140,7 +167,27 @@ impl CreateSpendPanel {
             draft: step::TransactionDraft::default(),
             current: 0,
             steps: vec![
-                Box::new(step::ChooseRecipients::new(coins)),
+                Box::new(step::ChooseRecipients::new(coins, wallet.display_unit)),
+                Box::new(step::ChooseCoins::new(
+                    descriptor,
+                    coins.to_vec(),
+                    timelock,
+                    blockheight,
+                )),
+                Box::new(step::SaveSpend::new(wallet)),
+            ],
+        }
+    }
+
+    /// Spend selected coins back to fresh addresses of the wallet, for privacy.
+    pub fn new_privacy_refresh(wallet: Arc<Wallet>, coins: &[Coin], blockheight: u32) -> Self {
+        let descriptor = wallet.main_descriptor.clone();
+        let timelock = descriptor.timelock_value();
+        Self {
+            draft: step::TransactionDraft::default(),
+            current: 0,
+            steps: vec![
+                Box::new(step::ChooseRefreshOutputs::default()),
                 Box::new(step::ChooseCoins::new(
                     descriptor,
                     coins.to_vec(),
@@ -151,6 +198,31 @@ impl CreateSpendPanel {
             ],
         }
     }
+
+    /// Spend the small coins back to a single fresh address of the wallet, with the
+    /// consolidation mode enabled at the given feerates.
+    pub fn new_consolidation(
+        wallet: Arc<Wallet>,
+        coins: &[Coin],
+        blockheight: u32,
+        feerate: u64,
+        future_feerate: u64,
+    ) -> Self {
+        let descriptor = wallet.main_descriptor.clone();
+        let timelock = descriptor.timelock_value();
+        Self {
+            draft: step::TransactionDraft::default(),
+            current: 0,
+            steps: vec![
+                Box::new(step::ChooseRefreshOutputs::default()),
+                Box::new(
+                    step::ChooseCoins::new(descriptor, coins.to_vec(), timelock, blockheight)
+                        .with_consolidation(feerate, future_feerate),
+                ),
+                Box::new(step::SaveSpend::new(wallet)),
+            ],
+        }
+    }
 }
 
 impl State for CreateSpendPanel {
@@ -192,6 +264,14 @@ impl State for CreateSpendPanel {
         Command::none()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(step) = self.steps.get(self.current) {
+            step.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
     fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Command<Message> {
         let daemon = daemon.clone();
         Command::perform(
@@ -212,8 +292,71 @@ impl From<CreateSpendPanel> for Box<dyn State> {
     }
 }
 
+/// The base64 encoding of a PSBT starts with the PSBT magic bytes `psbt\xff`.
+const BASE64_PSBT_PREFIX: &str = "cHNidP8";
+
+/// A base64 encoded PSBT entered by the user. It is decoded in the background, so that a large
+/// PSBT, like the one of a consolidation of many coins, does not freeze the interface.
+#[derive(Default)]
+pub struct PsbtInput {
+    pub value: form::Value<String>,
+    /// The PSBT decoded from the latest edit, if valid.
+    pub psbt: Option<Psbt>,
+    pub decoding: bool,
+    /// Number of the latest edit, the decoding results of the previous ones are discarded.
+    edit: usize,
+}
+
+impl PsbtInput {
+    /// Record an edit and return the command decoding it. An input which cannot be a PSBT is
+    /// rejected right away.
+    pub fn edit(&mut self, s: String) -> Command<Message> {
+        self.edit += 1;
+        self.psbt = None;
+        self.value.value = s;
+        let value = self.value.value.trim();
+        if !value.starts_with(BASE64_PSBT_PREFIX) || value.len() % 4 != 0 {
+            self.decoding = false;
+            self.value.valid = value.is_empty();
+            return Command::none();
+        }
+        self.decoding = true;
+        self.value.valid = true;
+        let (edit, value) = (self.edit, value.to_string());
+        Command::perform(
+            async move {
+                base64::decode(value)
+                    .ok()
+                    .and_then(|bytes| consensus::encode::deserialize::<Psbt>(&bytes).ok())
+            },
+            move |psbt| Message::DecodedPsbt(edit, psbt),
+        )
+    }
+
+    /// Record the PSBT decoded from an edit, if it is the latest one. The PSBT is accepted if
+    /// it passes the given check.
+    pub fn decoded(&mut self, edit: usize, psbt: Option<Psbt>, check: impl Fn(&Psbt) -> bool) {
+        if edit != self.edit {
+            return;
+        }
+        self.decoding = false;
+        self.psbt = psbt.filter(check);
+        self.value.valid = self.psbt.is_some();
+    }
+
+    /// Record a PSBT loaded at once, like from a file, in place of the one entered. A decoding
+    /// in progress is discarded. The PSBT is accepted if it passes the given check.
+    pub fn loaded(&mut self, psbt: Psbt, check: impl Fn(&Psbt) -> bool) {
+        self.edit += 1;
+        self.decoding = false;
+        self.value.value = psbt.to_string();
+        self.psbt = Some(psbt).filter(check);
+        self.value.valid = self.psbt.is_some();
+    }
+}
+
 pub struct ImportSpendState {
-    imported: form::Value<String>,
+    imported: PsbtInput,
     processing: bool,
     error: Option<Error>,
     success: bool,
@@ -222,7 +365,7 @@ pub struct ImportSpendState {
 impl ImportSpendState {
     pub fn new() -> Self {
         Self {
-            imported: form::Value::default(),
+            imported: PsbtInput::default(),
             processing: false,
             error: None,
             success: false,
@@ -235,7 +378,12 @@ impl ImportSpendState {
         if self.success {
             view::spend::import_spend_success_view()
         } else {
-            view::spend::import_spend_view(&self.imported, self.error.as_ref(), self.processing)
+            view::spend::import_spend_view(
+                &self.imported.value,
+                self.error.as_ref(),
+                self.processing,
+                self.imported.decoding,
+            )
         }
     }
 
@@ -257,20 +405,15 @@ impl ImportSpendState {
                 }
             }
             Message::View(view::Message::ImportSpend(view::ImportSpendMessage::PsbtEdited(s))) => {
-                self.imported.value = s;
-                self.imported.valid = base64::decode(&self.imported.value)
-                    .ok()
-                    .and_then(|bytes| consensus::encode::deserialize::<Psbt>(&bytes).ok())
-                    .is_some();
+                if !self.processing {
+                    return self.imported.edit(s);
+                }
             }
+            Message::DecodedPsbt(edit, psbt) => self.imported.decoded(edit, psbt, |_| true),
             Message::View(view::Message::ImportSpend(view::ImportSpendMessage::Confirm)) => {
-                if self.imported.valid {
+                if let Some(imported) = self.imported.psbt.clone() {
                     self.processing = true;
                     self.error = None;
-                    let imported: Psbt = consensus::encode::deserialize(
-                        &base64::decode(&self.imported.value).expect("Already checked"),
-                    )
-                    .unwrap();
                     return Command::perform(
                         async move { daemon.update_spend_tx(&imported).map_err(|e| e.into()) },
                         Message::Updated,
@@ -283,3 +426,58 @@ impl ImportSpendState {
         Command::none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::miniscript::bitcoin::{PackedLockTime, Script, Transaction, TxOut};
+
+    fn psbt(value: u64) -> Psbt {
+        Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: Vec::new(),
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_psbt_input() {
+        let mut input = PsbtInput::default();
+
+        // Rejected without being decoded.
+        let _ = input.edit("not a psbt".to_string());
+        assert!(!input.decoding);
+        assert!(!input.value.valid);
+        let _ = input.edit(String::new());
+        assert!(input.value.valid);
+
+        let _ = input.edit(base64::encode(consensus::serialize(&psbt(1))));
+        assert!(input.decoding);
+        let first_edit = input.edit;
+        let _ = input.edit(base64::encode(consensus::serialize(&psbt(2))));
+        // The result of a previous edit is discarded.
+        input.decoded(first_edit, Some(psbt(1)), |_| true);
+        assert!(input.decoding);
+        assert!(input.psbt.is_none());
+
+        // The decoded PSBT must pass the check.
+        input.decoded(input.edit, Some(psbt(2)), |p| {
+            p.unsigned_tx.output[0].value == 1
+        });
+        assert!(!input.decoding);
+        assert!(!input.value.valid);
+        assert!(input.psbt.is_none());
+
+        let _ = input.edit(base64::encode(consensus::serialize(&psbt(2))));
+        input.decoded(input.edit, Some(psbt(2)), |p| {
+            p.unsigned_tx.output[0].value == 2
+        });
+        assert!(input.value.valid);
+        assert_eq!(input.psbt, Some(psbt(2)));
+    }
+}
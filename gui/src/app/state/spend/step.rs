@@ -1,21 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{hash_map::RandomState, HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
 use std::str::FromStr;
 use std::sync::Arc;
 
-use iced::{Command, Element};
+use iced::{Command, Element, Subscription};
 use liana::{
     descriptors::MultipathDescriptor,
     miniscript::bitcoin::{
-        self, util::psbt::Psbt, Address, Amount, Denomination, Network, OutPoint,
+        self, secp256k1, util::psbt::Psbt, Address, Amount, Network, OutPoint, Txid,
     },
 };
+use tracing::warn;
 
 use crate::{
     app::{
-        cache::Cache, error::Error, message::Message, state::spend::detail, view, wallet::Wallet,
+        cache::Cache, error::Error, message::Message, state::spend::detail, unit::Unit, view,
+        wallet::Wallet,
     },
     daemon::{
-        model::{remaining_sequence, Coin, SpendTx},
+        model::{cpfp_feerate, feerate_vb, remaining_sequence, Coin, SpendTx, UnconfirmedParent},
         Daemon,
     },
     ui::component::form,
@@ -24,13 +27,129 @@ use crate::{
 /// See: https://github.com/wizardsardine/liana/blob/master/src/commands/mod.rs#L32
 const DUST_OUTPUT_SATS: u64 = 5_000;
 
+/// Size of a transaction input without its witness: outpoint, scriptSig length and nSequence.
+const TXIN_BASE_VSIZE: u64 = 32 + 4 + 1 + 4;
+
+/// nValue size + scriptPubKey CompactSize + OP_0 + PUSH32 + <wit program>
+const P2WSH_TXO_SIZE: usize = 8 + 1 + 1 + 1 + 32;
+
+/// Maximum number of outputs of a privacy refresh.
+const MAX_REFRESH_OUTPUTS: usize = 10;
+
+/// Size of a transaction without its inputs and outputs: version, input and output counts,
+/// locktime and segwit marker and flag, rounded up.
+const TX_BASE_VSIZE: u64 = 4 + 1 + 1 + 4 + 1;
+
+/// A coin is small if the fee to spend it at the future feerate is more than a hundredth of
+/// its amount.
+const SMALL_COIN_FEE_RATIO: u64 = 100;
+
+/// Minimum number of small coins a consolidation is suggested for.
+const CONSOLIDATION_SUGGESTION_MIN_COINS: usize = 5;
+
+/// Size of an input spending a coin of the descriptor, with its worst case witness.
+pub fn input_vsize(descriptor: &MultipathDescriptor) -> u64 {
+    TXIN_BASE_VSIZE + descriptor.max_sat_weight() as u64 / 4
+}
+
+/// A coin is worth consolidating if it is small given the size of its input, and spending it
+/// now at `feerate` costs less than spending it later at the expected `future_feerate`. A coin
+/// which does not even pay for its own input at the current feerate is never worth
+/// consolidating, and a large coin is not worth the loss of privacy of merging it.
+pub fn is_worth_consolidating(
+    amount: Amount,
+    input_vsize: u64,
+    feerate: u64,
+    future_feerate: u64,
+) -> bool {
+    let cost_now = input_vsize * feerate;
+    let cost_later = input_vsize * future_feerate;
+    cost_now < cost_later
+        && amount.to_sat() > cost_now
+        && amount.to_sat() < cost_later * SMALL_COIN_FEE_RATIO
+}
+
+/// The number of small coins worth consolidating at `feerate` and the fee saved by spending
+/// them in a single coin now rather than each of them later at `future_feerate`, accounting
+/// for the consolidation transaction and the later spend of its output. None if there are
+/// not enough small coins for a consolidation to save anything.
+pub fn consolidation_savings(
+    coins: &[Coin],
+    input_vsize: u64,
+    feerate: u64,
+    future_feerate: u64,
+) -> Option<(usize, Amount)> {
+    let count = coins
+        .iter()
+        .filter(|coin| {
+            coin.spend_info.is_none()
+                && !coin.is_immature
+                && is_worth_consolidating(coin.amount, input_vsize, feerate, future_feerate)
+        })
+        .count();
+    if count < CONSOLIDATION_SUGGESTION_MIN_COINS {
+        return None;
+    }
+    let cost_later = count as u64 * input_vsize * future_feerate;
+    let cost_now = (TX_BASE_VSIZE + count as u64 * input_vsize + P2WSH_TXO_SIZE as u64) * feerate
+        + input_vsize * future_feerate;
+    if cost_now >= cost_later {
+        return None;
+    }
+    Some((count, Amount::from_sat(cost_later - cost_now)))
+}
+
 #[derive(Default, Clone)]
 pub struct TransactionDraft {
     inputs: Vec<Coin>,
     outputs: HashMap<Address, u64>,
+    /// Set if the coins are spent back to fresh addresses of the wallet.
+    refresh: Option<PrivacyRefresh>,
+    /// Set if all the selected coins are sent to this address, without change.
+    sweep: Option<Address>,
     generated: Option<Psbt>,
 }
 
+/// A spend of coins back to fresh addresses of the wallet, only meant to break the chain
+/// analysis heuristics. The outputs are generated once the coins and feerate are known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrivacyRefresh {
+    pub output_count: usize,
+    /// Split the amount randomly between the outputs instead of evenly.
+    pub randomize: bool,
+}
+
+/// Split the total amount between outputs proportionally to the given weights. The rounding
+/// remainder goes to the last output.
+pub fn split_amount(total: u64, weights: &[u64]) -> Vec<u64> {
+    let weights_sum: u64 = weights.iter().sum();
+    if weights_sum == 0 {
+        return Vec::new();
+    }
+    let mut amounts: Vec<u64> = weights
+        .iter()
+        .map(|w| (total as u128 * *w as u128 / weights_sum as u128) as u64)
+        .collect();
+    let rest = total - amounts.iter().sum::<u64>();
+    if let Some(last) = amounts.last_mut() {
+        *last += rest;
+    }
+    amounts
+}
+
+/// Weights between 50 and 150, so that no output is more than three times bigger than another.
+/// This is not meant to be cryptographically secure, only to avoid round and equal amounts.
+fn random_weights(count: usize) -> Vec<u64> {
+    let state = RandomState::new();
+    (0..count)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_usize(i);
+            50 + hasher.finish() % 101
+        })
+        .collect()
+}
+
 pub trait Step {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message>;
     fn update(
@@ -42,22 +161,30 @@ pub trait Step {
     ) -> Command<Message>;
     fn apply(&self, _draft: &mut TransactionDraft) {}
     fn load(&mut self, _draft: &TransactionDraft) {}
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::none()
+    }
 }
 
 pub struct ChooseRecipients {
+    /// Unit the amounts of the recipients are entered with.
+    unit: Unit,
     balance_available: Amount,
     recipients: Vec<Recipient>,
+    /// The amount isn't set by the user but is the value of the coins minus the fees.
+    sweep: bool,
     is_valid: bool,
     is_duplicate: bool,
 }
 
 impl ChooseRecipients {
-    pub fn new(coins: &[Coin]) -> Self {
+    pub fn new(coins: &[Coin], unit: Unit) -> Self {
         Self {
+            unit,
             balance_available: coins
                 .iter()
                 .filter_map(|coin| {
-                    if coin.spend_info.is_none() {
+                    if coin.spend_info.is_none() && !coin.is_immature {
                         Some(coin.amount)
                     } else {
                         None
@@ -65,6 +192,7 @@ impl ChooseRecipients {
                 })
                 .sum(),
             recipients: vec![Recipient::default()],
+            sweep: false,
             is_valid: false,
             is_duplicate: false,
         }
@@ -72,16 +200,27 @@ impl ChooseRecipients {
     fn check_valid(&mut self) {
         self.is_valid = !self.recipients.is_empty();
         self.is_duplicate = false;
-        for (i, recipient) in self.recipients.iter().enumerate() {
-            if !recipient.valid() {
+        // Two addresses may be encoded differently but pay to the same script. The outputs are
+        // keyed by address, so one of the payments would be silently dropped.
+        let mut scripts = HashSet::with_capacity(self.recipients.len());
+        for recipient in &self.recipients {
+            let valid = if self.sweep {
+                !recipient.address.value.is_empty() && recipient.address.valid
+            } else {
+                recipient.valid()
+            };
+            if !valid {
                 self.is_valid = false;
             }
-            if !self.is_duplicate {
-                self.is_duplicate = self.recipients[..i]
-                    .iter()
-                    .any(|r| r.address.value == recipient.address.value);
+            if let Ok(address) = Address::from_str(&recipient.address.value) {
+                if !scripts.insert(address.script_pubkey()) {
+                    self.is_duplicate = true;
+                }
             }
         }
+        if self.is_duplicate {
+            self.is_valid = false;
+        }
     }
 }
 
@@ -101,11 +240,20 @@ impl Step for ChooseRecipients {
                 view::CreateSpendMessage::DeleteRecipient(i) => {
                     self.recipients.remove(*i);
                 }
+                view::CreateSpendMessage::SweepToggled(sweep) => {
+                    self.sweep = *sweep;
+                    if self.sweep {
+                        self.recipients.truncate(1);
+                        if self.recipients.is_empty() {
+                            self.recipients.push(Recipient::default());
+                        }
+                    }
+                }
                 view::CreateSpendMessage::RecipientEdited(i, _, _) => {
                     self.recipients
                         .get_mut(*i)
                         .unwrap()
-                        .update(cache.network, msg);
+                        .update(cache.network, self.unit, msg);
                 }
                 _ => {}
             }
@@ -117,31 +265,48 @@ impl Step for ChooseRecipients {
 
     fn apply(&self, draft: &mut TransactionDraft) {
         let mut outputs: HashMap<Address, u64> = HashMap::new();
+        if self.sweep {
+            draft.sweep = self
+                .recipients
+                .first()
+                .map(|r| Address::from_str(&r.address.value).expect("Checked before"));
+            draft.outputs = outputs;
+            return;
+        }
+        draft.sweep = None;
         for recipient in &self.recipients {
             outputs.insert(
                 Address::from_str(&recipient.address.value).expect("Checked before"),
-                recipient.amount().expect("Checked before"),
+                recipient.amount(self.unit).expect("Checked before"),
             );
         }
         draft.outputs = outputs;
     }
 
-    fn view<'a>(&'a self, _cache: &'a Cache) -> Element<'a, view::Message> {
+    fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
         view::spend::step::choose_recipients_view(
             &self.balance_available,
             self.recipients
                 .iter()
                 .enumerate()
-                .map(|(i, recipient)| recipient.view(i).map(view::Message::CreateSpend))
+                .map(|(i, recipient)| {
+                    recipient
+                        .view(i, self.unit, self.sweep, cache)
+                        .map(view::Message::CreateSpend)
+                })
                 .collect(),
-            Amount::from_sat(
+            Amount::from_sat(if self.sweep {
+                0
+            } else {
                 self.recipients
                     .iter()
-                    .map(|r| r.amount().unwrap_or(0_u64))
-                    .sum(),
-            ),
+                    .map(|r| r.amount(self.unit).unwrap_or(0_u64))
+                    .sum()
+            }),
             self.is_valid,
             self.is_duplicate,
+            self.sweep,
+            self.unit,
         )
     }
 }
@@ -153,12 +318,13 @@ struct Recipient {
 }
 
 impl Recipient {
-    fn amount(&self) -> Result<u64, Error> {
+    fn amount(&self, unit: Unit) -> Result<u64, Error> {
         if self.amount.value.is_empty() {
             return Err(Error::Unexpected("Amount should be non-zero".to_string()));
         }
 
-        let amount = Amount::from_str_in(&self.amount.value, Denomination::Bitcoin)
+        let amount = unit
+            .parse(&self.amount.value)
             .map_err(|_| Error::Unexpected("cannot parse output amount".to_string()))?;
 
         if amount.to_sat() == 0 {
@@ -187,14 +353,14 @@ impl Recipient {
             && self.amount.valid
     }
 
-    fn update(&mut self, network: Network, message: view::CreateSpendMessage) {
+    fn update(&mut self, network: Network, unit: Unit, message: view::CreateSpendMessage) {
         match message {
             view::CreateSpendMessage::RecipientEdited(_, "address", address) => {
                 self.address.value = address;
                 if let Ok(address) = Address::from_str(&self.address.value) {
                     self.address.valid = address.is_valid_for_network(network);
                     if !self.amount.value.is_empty() {
-                        self.amount.valid = self.amount().is_ok();
+                        self.amount.valid = self.amount(unit).is_ok();
                     }
                 } else if self.address.value.is_empty() {
                     // Make the error disappear if we deleted the invalid address
@@ -206,7 +372,7 @@ impl Recipient {
             view::CreateSpendMessage::RecipientEdited(_, "amount", amount) => {
                 self.amount.value = amount;
                 if !self.amount.value.is_empty() {
-                    self.amount.valid = self.amount().is_ok();
+                    self.amount.valid = self.amount(unit).is_ok();
                 } else {
                     // Make the error disappear if we deleted the invalid amount
                     self.amount.valid = true;
@@ -216,19 +382,44 @@ impl Recipient {
         };
     }
 
-    fn view(&self, i: usize) -> Element<view::CreateSpendMessage> {
-        view::spend::step::recipient_view(i, &self.address, &self.amount)
+    fn view<'a>(
+        &'a self,
+        i: usize,
+        unit: Unit,
+        sweep: bool,
+        cache: &'a Cache,
+    ) -> Element<'a, view::CreateSpendMessage> {
+        view::spend::step::recipient_view(
+            i,
+            &self.address,
+            &self.amount,
+            unit,
+            sweep,
+            &cache.address_book,
+            cache.network,
+        )
     }
 }
 
 pub struct ChooseCoins {
     descriptor: MultipathDescriptor,
+    /// Size of the witness of an input, spent through the primary path as any Spend created
+    /// here.
+    satisfaction_vsize: u64,
     timelock: u32,
     coins: Vec<(Coin, bool)>,
     recipients: Vec<(Address, Amount)>,
 
     amount_left_to_select: Option<Amount>,
     feerate: form::Value<String>,
+    /// Select every coin cheaper to spend now than at the consolidation feerate.
+    consolidation: bool,
+    consolidation_feerate: form::Value<String>,
+    refresh: Option<PrivacyRefresh>,
+    sweep: Option<Address>,
+    /// The fee and size of the unconfirmed parents of the selected coins, once fetched. The fee
+    /// is unknown if some of the inputs of the parent are not coins of the wallet.
+    parents: HashMap<Txid, Option<(Amount, u64)>>,
     generated: Option<Psbt>,
     warning: Option<Error>,
 }
@@ -243,7 +434,8 @@ impl ChooseCoins {
         let mut coins: Vec<(Coin, bool)> = coins
             .into_iter()
             .filter_map(|c| {
-                if c.spend_info.is_none() {
+                // Immature coinbase outputs can't be spent yet.
+                if c.spend_info.is_none() && !c.is_immature {
                     Some((c, false))
                 } else {
                     None
@@ -261,18 +453,82 @@ impl ChooseCoins {
                 a.block_height.cmp(&b.block_height)
             }
         });
+        let satisfaction_vsize = descriptor
+            .path_sat_vbytes(None, &secp256k1::Secp256k1::verification_only())
+            .expect("There is always a primary path") as u64;
         Self {
             descriptor,
+            satisfaction_vsize,
             timelock,
             coins,
             recipients: Vec::new(),
             feerate: form::Value::default(),
+            consolidation: false,
+            consolidation_feerate: form::Value::default(),
+            refresh: None,
+            sweep: None,
+            parents: HashMap::new(),
             generated: None,
             warning: None,
             amount_left_to_select: None,
         }
     }
 
+    /// Enable the consolidation mode with the given feerates and select the coins worth
+    /// consolidating.
+    pub fn with_consolidation(mut self, feerate: u64, future_feerate: u64) -> Self {
+        self.feerate.value = feerate.to_string();
+        self.consolidation = true;
+        self.consolidation_feerate.value = future_feerate.to_string();
+        self.select_coins_to_consolidate();
+        self
+    }
+
+    /// Select the coins worth consolidating if the consolidation mode is enabled.
+    fn select_coins_to_consolidate(&mut self) {
+        if !self.consolidation {
+            return;
+        }
+        let (feerate, future_feerate) = match (
+            self.feerate.value.parse::<u64>(),
+            self.consolidation_feerate.value.parse::<u64>(),
+        ) {
+            (Ok(feerate), Ok(future_feerate)) => (feerate, future_feerate),
+            _ => return,
+        };
+        let input_vsize = input_vsize(&self.descriptor);
+        for (coin, selected) in &mut self.coins {
+            if is_worth_consolidating(coin.amount, input_vsize, feerate, future_feerate) {
+                *selected = true;
+            }
+        }
+        self.amount_left_to_select();
+    }
+
+    /// The number of coins selected and the number of coins the wallet will have once the
+    /// transaction is confirmed, assuming it has a change output.
+    fn consolidation_summary(&self) -> Option<(usize, usize)> {
+        if !self.consolidation {
+            return None;
+        }
+        let selected = self.coins.iter().filter(|(_, selected)| *selected).count();
+        Some((selected, self.coins.len() - selected + 1))
+    }
+
+    /// The total value of the selected coins, in satoshis.
+    fn selected_amount(&self) -> u64 {
+        self.coins
+            .iter()
+            .filter_map(|(c, selected)| {
+                if *selected {
+                    Some(c.amount.to_sat())
+                } else {
+                    None
+                }
+            })
+            .sum()
+    }
+
     fn amount_left_to_select(&mut self) {
         // We need the feerate in order to compute the required amount of BTC to
         // select. Return early if we don't to not do unnecessary computation.
@@ -284,13 +540,42 @@ impl ChooseCoins {
             }
         };
 
-        // The coins to be included in this transaction.
-        let selected_coins: Vec<_> = self
-            .coins
+        let selected_amount = self.selected_amount();
+
+        // Now the calculation of the amount left to be selected by the user is a simple
+        // substraction between the value needed by the transaction to be created and the
+        // value that was selected already.
+        let output_sum: u64 = self
+            .recipients
             .iter()
-            .filter_map(|(c, selected)| if *selected { Some(c) } else { None })
-            .collect();
+            .map(|(_, amount)| amount.to_sat())
+            .sum();
+        let needed_amount: u64 = self.estimated_vsize() * feerate + output_sum;
+        self.amount_left_to_select = Some(Amount::from_sat(
+            needed_amount.saturating_sub(selected_amount),
+        ));
+    }
+
+    /// An error if no coin is selected, or if the selected coins don't cover the amount sent to
+    /// the recipients plus the fees at the chosen feerate.
+    fn shortfall_error(&self) -> Option<Error> {
+        if !self.coins.iter().any(|(_, selected)| *selected) {
+            return Some(Error::Unexpected(
+                "At least one coin must be selected".to_string(),
+            ));
+        }
+        match self.amount_left_to_select {
+            Some(left) if left > Amount::from_sat(0) => Some(Error::Unexpected(format!(
+                "The selected coins are short of {} to cover the recipients and the fees",
+                left
+            ))),
+            _ => None,
+        }
+    }
 
+    /// The size in vbytes of the transaction to be created with the selected coins, once its
+    /// inputs are satisfied through the primary path.
+    fn estimated_vsize(&self) -> u64 {
         // A dummy representation of the transaction that will be computed, for
         // the purpose of computing its size in order to anticipate the fees needed.
         // NOTE: we make the conservative estimation a change output will always be
@@ -298,8 +583,10 @@ impl ChooseCoins {
         let tx_template = bitcoin::Transaction {
             version: 2,
             lock_time: bitcoin::PackedLockTime(0),
-            input: selected_coins
+            input: self
+                .coins
                 .iter()
+                .filter(|(_, selected)| *selected)
                 .map(|_| bitcoin::TxIn::default())
                 .collect(),
             output: self
@@ -311,27 +598,129 @@ impl ChooseCoins {
                 })
                 .collect(),
         };
-        // nValue size + scriptPubKey CompactSize + OP_0 + PUSH32 + <wit program>
-        const CHANGE_TXO_SIZE: usize = 8 + 1 + 1 + 1 + 32;
-        let satisfaction_vsize = self.descriptor.max_sat_weight() / 4;
-        let transaction_size =
-            tx_template.vsize() + satisfaction_vsize * tx_template.input.len() + CHANGE_TXO_SIZE;
+        let refresh_outputs = self.refresh.map(|r| r.output_count).unwrap_or(0);
+        (tx_template.vsize() + P2WSH_TXO_SIZE * (1 + refresh_outputs)) as u64
+            + self.satisfaction_vsize * tx_template.input.len() as u64
+    }
 
-        // Now the calculation of the amount left to be selected by the user is a simple
-        // substraction between the value needed by the transaction to be created and the
-        // value that was selected already.
-        let selected_amount = selected_coins.iter().map(|c| c.amount.to_sat()).sum();
-        let output_sum: u64 = tx_template.output.iter().map(|o| o.value).sum();
-        let needed_amount: u64 = transaction_size as u64 * feerate + output_sum;
-        self.amount_left_to_select = Some(Amount::from_sat(
-            needed_amount.saturating_sub(selected_amount),
-        ));
+    /// The fee the transaction would pay at each of the feerates the user may hesitate between:
+    /// the minimum accepted by the node, the chosen one and the expected future feerate of a
+    /// consolidation. Sorted by increasing feerate.
+    fn fee_comparison(&self, mempool_min_feerate: u64) -> Vec<(u64, Amount)> {
+        let mut feerates = vec![mempool_min_feerate];
+        if let Ok(feerate) = self.feerate.value.parse::<u64>() {
+            feerates.push(feerate);
+        }
+        if self.consolidation {
+            if let Ok(feerate) = self.consolidation_feerate.value.parse::<u64>() {
+                feerates.push(feerate);
+            }
+        }
+        feerates.sort_unstable();
+        feerates.dedup();
+
+        let vsize = self.estimated_vsize();
+        feerates
+            .into_iter()
+            .map(|feerate| (feerate, Amount::from_sat(vsize * feerate)))
+            .collect()
+    }
+
+    /// The unconfirmed transactions creating the selected coins.
+    fn unconfirmed_parents(&self) -> Vec<UnconfirmedParent> {
+        let mut parents: Vec<UnconfirmedParent> = Vec::new();
+        for (coin, _) in self
+            .coins
+            .iter()
+            .filter(|(coin, selected)| *selected && coin.block_height.is_none())
+        {
+            let txid = coin.outpoint.txid;
+            if !parents.iter().any(|parent| parent.txid == txid) {
+                parents.push(UnconfirmedParent {
+                    txid,
+                    feerate: self
+                        .parents
+                        .get(&txid)
+                        .cloned()
+                        .flatten()
+                        .map(|(fee, vsize)| feerate_vb(&fee, vsize)),
+                });
+            }
+        }
+        parents
+    }
+
+    /// The feerate this transaction needs for it and its unconfirmed parents paying less than
+    /// the chosen feerate to confirm together at the chosen feerate, if there are such parents.
+    fn cpfp_feerate(&self) -> Option<u64> {
+        let feerate = self.feerate.value.parse::<u64>().ok()?;
+        let (fee, vsize) = self
+            .unconfirmed_parents()
+            .iter()
+            .filter_map(|parent| self.parents.get(&parent.txid).cloned().flatten())
+            .filter(|(fee, vsize)| feerate_vb(fee, *vsize) < feerate)
+            .fold((Amount::from_sat(0), 0), |(fees, vsizes), (fee, vsize)| {
+                (fees + fee, vsizes + vsize)
+            });
+        if vsize == 0 {
+            return None;
+        }
+        Some(cpfp_feerate(fee, vsize, self.estimated_vsize(), feerate))
+    }
+
+    /// Fetch the unconfirmed parents of the selected coins which were not fetched yet.
+    fn fetch_parents(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+    ) -> Command<Message> {
+        let txids: Vec<Txid> = self
+            .unconfirmed_parents()
+            .into_iter()
+            .map(|parent| parent.txid)
+            .filter(|txid| !self.parents.contains_key(txid))
+            .collect();
+        if txids.is_empty() {
+            return Command::none();
+        }
+        for txid in &txids {
+            self.parents.insert(*txid, None);
+        }
+        let coins = cache.coins.clone();
+        Command::perform(
+            async move { daemon.get_history_txs(&coins, &txids).map_err(|e| e.into()) },
+            Message::HistoryTransactions,
+        )
+    }
+
+    /// The fee of the transaction at the given feerate, with the selected coins.
+    fn estimated_fee(&self, feerate: u64) -> u64 {
+        let inputs = self.coins.iter().filter(|(_, selected)| *selected).count();
+        let tx_template = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn::default(); inputs],
+            output: Vec::new(),
+        };
+        let refresh_outputs = self.refresh.map(|r| r.output_count).unwrap_or(0);
+        let transaction_size = (tx_template.vsize() + P2WSH_TXO_SIZE * refresh_outputs) as u64
+            + self.satisfaction_vsize * inputs as u64;
+        transaction_size * feerate
     }
 }
 
 impl Step for ChooseCoins {
     fn load(&mut self, draft: &TransactionDraft) {
         self.warning = None;
+        self.refresh = draft.refresh;
+        // Everything is swept by default, the user may still leave some coins out (for instance
+        // the unconfirmed ones).
+        if draft.sweep.is_some() && self.sweep.is_none() {
+            for (_, selected) in &mut self.coins {
+                *selected = true;
+            }
+        }
+        self.sweep = draft.sweep.clone();
         self.recipients = draft
             .outputs
             .iter()
@@ -352,7 +741,7 @@ impl Step for ChooseCoins {
     fn update(
         &mut self,
         daemon: Arc<dyn Daemon + Sync + Send>,
-        _cache: &Cache,
+        cache: &Cache,
         draft: &TransactionDraft,
         message: Message,
     ) -> Command<Message> {
@@ -360,10 +749,15 @@ impl Step for ChooseCoins {
             Message::View(view::Message::CreateSpend(view::CreateSpendMessage::FeerateEdited(
                 s,
             ))) => {
-                if s.parse::<u64>().is_ok() {
+                // The node would reject a transaction below its mempool minimum feerate.
+                if s.parse::<u64>()
+                    .map(|f| f >= cache.mempool_min_feerate)
+                    .unwrap_or(false)
+                {
                     self.feerate.value = s;
                     self.feerate.valid = true;
                     self.amount_left_to_select();
+                    self.select_coins_to_consolidate();
                 } else if s.is_empty() {
                     self.feerate.value = "".to_string();
                     self.feerate.valid = true;
@@ -374,6 +768,21 @@ impl Step for ChooseCoins {
                 }
                 self.warning = None;
             }
+            Message::View(view::Message::CreateSpend(
+                view::CreateSpendMessage::ConsolidationToggled(consolidation),
+            )) => {
+                self.consolidation = consolidation;
+                self.select_coins_to_consolidate();
+                return self.fetch_parents(daemon, cache);
+            }
+            Message::View(view::Message::CreateSpend(
+                view::CreateSpendMessage::ConsolidationFeerateEdited(s),
+            )) => {
+                self.consolidation_feerate.valid = s.is_empty() || s.parse::<u64>().is_ok();
+                self.consolidation_feerate.value = s;
+                self.select_coins_to_consolidate();
+                return self.fetch_parents(daemon, cache);
+            }
             Message::View(view::Message::CreateSpend(view::CreateSpendMessage::Generate)) => {
                 let inputs: Vec<OutPoint> = self
                     .coins
@@ -384,7 +793,58 @@ impl Step for ChooseCoins {
                     .collect();
                 let outputs = draft.outputs.clone();
                 let feerate_vb = self.feerate.value.parse::<u64>().unwrap_or(0);
-                self.warning = None;
+                // Don't bother the daemon if the selected coins can't fund the transaction.
+                self.warning = self.shortfall_error();
+                if self.warning.is_some() {
+                    return Command::none();
+                }
+                if let Some(refresh) = self.refresh {
+                    // The amount left to select is the fee the transaction needs once every
+                    // selected coin is counted: what remains is split between the outputs.
+                    let selected_amount = self.selected_amount();
+                    let fee = self.estimated_fee(feerate_vb);
+                    let weights = if refresh.randomize {
+                        random_weights(refresh.output_count)
+                    } else {
+                        vec![1; refresh.output_count]
+                    };
+                    let amounts = split_amount(selected_amount.saturating_sub(fee), &weights);
+                    if amounts.iter().any(|a| *a < DUST_OUTPUT_SATS) {
+                        self.warning = Some(Error::Unexpected(
+                            "The selected coins are not enough to create outputs above the \
+                            dust limit"
+                                .to_string(),
+                        ));
+                        return Command::none();
+                    }
+                    return Command::perform(
+                        create_privacy_refresh(daemon, inputs, amounts, feerate_vb),
+                        Message::Psbt,
+                    );
+                }
+                if let Some(address) = self.sweep.clone() {
+                    // The value of the single output is what's left once the fees are paid.
+                    let left = self
+                        .selected_amount()
+                        .saturating_sub(self.estimated_vsize() * feerate_vb);
+                    if left < DUST_OUTPUT_SATS {
+                        self.warning = Some(Error::Unexpected(
+                            "The selected coins are not enough to create an output above the \
+                            dust limit"
+                                .to_string(),
+                        ));
+                        return Command::none();
+                    }
+                    return Command::perform(
+                        async move {
+                            daemon
+                                .create_sweep_tx(&address, &inputs, feerate_vb)
+                                .map(|res| res.psbt)
+                                .map_err(|e| e.into())
+                        },
+                        Message::Psbt,
+                    );
+                }
                 return Command::perform(
                     async move {
                         daemon
@@ -406,8 +866,25 @@ impl Step for ChooseCoins {
                 if let Some(coin) = self.coins.get_mut(i) {
                     coin.1 = !coin.1;
                     self.amount_left_to_select();
+                    return self.fetch_parents(daemon, cache);
                 }
             }
+            // The fee of a parent is only known if all its inputs are coins of the wallet.
+            Message::HistoryTransactions(Ok(txs)) => {
+                for tx in txs {
+                    let fee = tx
+                        .fee_amount
+                        .filter(|_| tx.coins.len() == tx.tx.input.len());
+                    self.parents
+                        .insert(tx.tx.txid(), fee.map(|fee| (fee, tx.tx.vsize() as u64)));
+                }
+            }
+            Message::HistoryTransactions(Err(e)) => {
+                warn!(
+                    "Failed to fetch the unconfirmed parents of the coins: {}",
+                    e
+                );
+            }
             _ => {}
         }
 
@@ -421,11 +898,91 @@ impl Step for ChooseCoins {
             &self.coins,
             self.amount_left_to_select.as_ref(),
             &self.feerate,
+            self.consolidation,
+            &self.consolidation_feerate,
+            self.consolidation_summary(),
+            self.fee_comparison(cache.mempool_min_feerate),
+            self.unconfirmed_parents(),
+            self.cpfp_feerate(),
             self.warning.as_ref(),
         )
     }
 }
 
+/// First step of a privacy refresh: how the coins are spent back to the wallet.
+pub struct ChooseRefreshOutputs {
+    output_count: form::Value<String>,
+    randomize: bool,
+}
+
+impl Default for ChooseRefreshOutputs {
+    fn default() -> Self {
+        Self {
+            output_count: form::Value {
+                value: "1".to_string(),
+                valid: true,
+            },
+            randomize: false,
+        }
+    }
+}
+
+impl Step for ChooseRefreshOutputs {
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        _cache: &Cache,
+        _draft: &TransactionDraft,
+        message: Message,
+    ) -> Command<Message> {
+        match message {
+            Message::View(view::Message::CreateSpend(
+                view::CreateSpendMessage::RefreshOutputCountEdited(s),
+            )) => {
+                self.output_count.valid = s
+                    .parse::<usize>()
+                    .map(|count| count > 0 && count <= MAX_REFRESH_OUTPUTS)
+                    .unwrap_or(false);
+                self.output_count.value = s;
+            }
+            Message::View(view::Message::CreateSpend(
+                view::CreateSpendMessage::RefreshRandomizeToggled(randomize),
+            )) => {
+                self.randomize = randomize;
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+
+    fn apply(&self, draft: &mut TransactionDraft) {
+        draft.outputs = HashMap::new();
+        draft.refresh = Some(PrivacyRefresh {
+            output_count: self.output_count.value.parse::<usize>().unwrap_or(1),
+            randomize: self.randomize,
+        });
+    }
+
+    fn view<'a>(&'a self, _cache: &'a Cache) -> Element<'a, view::Message> {
+        view::spend::step::choose_refresh_outputs_view(&self.output_count, self.randomize)
+    }
+}
+
+async fn create_privacy_refresh(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+    inputs: Vec<OutPoint>,
+    amounts: Vec<u64>,
+    feerate_vb: u64,
+) -> Result<Psbt, Error> {
+    let mut outputs = HashMap::new();
+    for amount in amounts {
+        let address = daemon.get_new_address()?.address;
+        outputs.insert(address, amount);
+    }
+    let res = daemon.create_spend_tx(&inputs, &outputs, feerate_vb)?;
+    Ok(res.psbt)
+}
+
 pub struct SaveSpend {
     wallet: Arc<Wallet>,
     spend: Option<detail::SpendTxState>,
@@ -450,7 +1007,12 @@ impl Step for SaveSpend {
             .unwrap();
         self.spend = Some(detail::SpendTxState::new(
             self.wallet.clone(),
-            SpendTx::new(psbt, draft.inputs.clone(), sigs),
+            SpendTx::new(
+                psbt,
+                draft.inputs.clone(),
+                sigs,
+                &self.wallet.main_descriptor,
+            ),
             false,
         ));
     }
@@ -469,7 +1031,352 @@ impl Step for SaveSpend {
         }
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(spend) = &self.spend {
+            spend.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
         self.spend.as_ref().unwrap().view(cache)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_worth_consolidating() {
+        // 41 vbytes of base input and 76 of witness.
+        let input_vsize = 117;
+
+        // Fees are expected to rise: consolidating is cheaper now.
+        assert!(is_worth_consolidating(
+            Amount::from_sat(10_000),
+            input_vsize,
+            2,
+            20
+        ));
+
+        // Fees are expected to be the same or to drop: better wait.
+        assert!(!is_worth_consolidating(
+            Amount::from_sat(10_000),
+            input_vsize,
+            20,
+            20
+        ));
+        assert!(!is_worth_consolidating(
+            Amount::from_sat(10_000),
+            input_vsize,
+            20,
+            2
+        ));
+
+        // The coin does not pay for its own input at the current feerate.
+        assert!(!is_worth_consolidating(
+            Amount::from_sat(200),
+            input_vsize,
+            2,
+            20
+        ));
+        assert!(is_worth_consolidating(
+            Amount::from_sat(235),
+            input_vsize,
+            2,
+            20
+        ));
+        assert!(!is_worth_consolidating(
+            Amount::from_sat(234),
+            input_vsize,
+            2,
+            20
+        ));
+
+        // The coin is too large for its spending fee to matter.
+        assert!(is_worth_consolidating(
+            Amount::from_sat(233_999),
+            input_vsize,
+            2,
+            20
+        ));
+        assert!(!is_worth_consolidating(
+            Amount::from_sat(234_000),
+            input_vsize,
+            2,
+            20
+        ));
+        // The same coin is small if its input is larger, or the future feerate higher.
+        assert!(is_worth_consolidating(
+            Amount::from_sat(234_000),
+            2 * input_vsize,
+            2,
+            20
+        ));
+        assert!(is_worth_consolidating(
+            Amount::from_sat(234_000),
+            input_vsize,
+            2,
+            21
+        ));
+    }
+
+    #[test]
+    fn test_consolidation_savings() {
+        let input_vsize = 117;
+        let coin = |vout: u32, amount: u64| Coin {
+            outpoint: OutPoint::from_str(&format!(
+                "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:{}",
+                vout
+            ))
+            .unwrap(),
+            amount: Amount::from_sat(amount),
+            block_height: Some(1),
+            is_immature: false,
+            is_replaceable: false,
+            spend_info: None,
+        };
+        let mut coins: Vec<Coin> = (0..6).map(|vout| coin(vout, 10_000)).collect();
+        // Neither a big coin nor a spent one is counted.
+        coins.push(coin(6, 1_000_000));
+        let mut spent = coin(7, 10_000);
+        spent.spend_info = Some(liana::commands::LCSpendInfo {
+            txid: spent.outpoint.txid,
+            height: None,
+            settled: false,
+            replaced_txid: None,
+        });
+        coins.push(spent);
+
+        // Spending the 6 coins later costs 6 * 117 * 20 sats. Consolidating them now costs
+        // (11 + 6 * 117 + 43) * 2 sats, plus 117 * 20 sats to spend the new coin later.
+        assert_eq!(
+            consolidation_savings(&coins, input_vsize, 2, 20),
+            Some((6, Amount::from_sat(14_040 - 1_512 - 2_340)))
+        );
+
+        // Fees are not expected to rise.
+        assert_eq!(consolidation_savings(&coins, input_vsize, 20, 20), None);
+
+        // Not enough small coins.
+        assert_eq!(consolidation_savings(&coins[2..], input_vsize, 2, 20), None);
+    }
+
+    #[test]
+    fn test_split_amount() {
+        assert_eq!(split_amount(100_000, &[1]), vec![100_000]);
+        assert_eq!(
+            split_amount(100_000, &[1, 1, 1]),
+            vec![33_333, 33_333, 33_334]
+        );
+        assert_eq!(split_amount(100_000, &[50, 150]), vec![25_000, 75_000]);
+        assert_eq!(split_amount(100_000, &[]), Vec::<u64>::new());
+
+        let weights = random_weights(5);
+        assert_eq!(weights.len(), 5);
+        assert!(weights.iter().all(|w| *w >= 50 && *w <= 150));
+        assert_eq!(split_amount(123_456, &weights).iter().sum::<u64>(), 123_456);
+    }
+
+    #[test]
+    fn test_fee_comparison() {
+        let descriptor = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y").unwrap();
+        let mut step = ChooseCoins::new(descriptor, Vec::new(), 10000, 0);
+
+        // Only the mempool minimum until a feerate is set.
+        let fees = step.fee_comparison(1);
+        assert_eq!(fees.len(), 1);
+        let vsize = fees[0].1.to_sat();
+
+        step.feerate.value = "10".to_string();
+        step.consolidation = true;
+        step.consolidation_feerate.value = "10".to_string();
+        assert_eq!(
+            step.fee_comparison(1),
+            vec![
+                (1, Amount::from_sat(vsize)),
+                (10, Amount::from_sat(10 * vsize))
+            ]
+        );
+
+        step.consolidation_feerate.value = "5".to_string();
+        assert_eq!(
+            step.fee_comparison(1)
+                .into_iter()
+                .map(|(feerate, _)| feerate)
+                .collect::<Vec<_>>(),
+            vec![1, 5, 10]
+        );
+    }
+
+    #[test]
+    fn test_unconfirmed_parents() {
+        let descriptor = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y").unwrap();
+        let coin = |vout: u32, block_height: Option<i32>| Coin {
+            outpoint: OutPoint::from_str(&format!(
+                "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:{}",
+                vout
+            ))
+            .unwrap(),
+            amount: Amount::from_sat(100_000),
+            block_height,
+            is_immature: false,
+            is_replaceable: false,
+            spend_info: None,
+        };
+        let mut step = ChooseCoins::new(
+            descriptor,
+            vec![coin(0, Some(1)), coin(1, None), coin(2, None)],
+            10000,
+            0,
+        );
+        step.feerate.value = "10".to_string();
+        let txid = step.coins[0].0.outpoint.txid;
+
+        // Confirmed coins have no parent to wait for.
+        step.coins[0].1 = true;
+        assert!(step.unconfirmed_parents().is_empty());
+        assert_eq!(step.cpfp_feerate(), None);
+
+        // Two coins created by the same unconfirmed transaction, not fetched yet.
+        step.coins[1].1 = true;
+        step.coins[2].1 = true;
+        assert_eq!(
+            step.unconfirmed_parents(),
+            vec![UnconfirmedParent {
+                txid,
+                feerate: None
+            }]
+        );
+        assert_eq!(step.cpfp_feerate(), None);
+
+        // A parent paying less than the chosen feerate must be bumped by this transaction.
+        step.parents
+            .insert(txid, Some((Amount::from_sat(200), 200)));
+        assert_eq!(step.unconfirmed_parents()[0].feerate, Some(1));
+        assert_eq!(
+            step.cpfp_feerate(),
+            Some(cpfp_feerate(
+                Amount::from_sat(200),
+                200,
+                step.estimated_vsize(),
+                10
+            ))
+        );
+        assert!(step.cpfp_feerate().unwrap() > 10);
+
+        step.parents
+            .insert(txid, Some((Amount::from_sat(4_000), 200)));
+        assert_eq!(step.cpfp_feerate(), None);
+    }
+
+    #[test]
+    fn test_shortfall_error() {
+        let descriptor = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y").unwrap();
+        let coin = |vout: u32| Coin {
+            outpoint: OutPoint::from_str(&format!(
+                "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:{}",
+                vout
+            ))
+            .unwrap(),
+            amount: Amount::from_sat(100_000),
+            block_height: Some(1),
+            is_immature: false,
+            is_replaceable: false,
+            spend_info: None,
+        };
+        let mut step = ChooseCoins::new(descriptor, vec![coin(0), coin(1)], 10000, 0);
+        step.recipients = vec![(
+            Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap(),
+            Amount::from_sat(150_000),
+        )];
+        step.feerate.value = "10".to_string();
+
+        // No coin selected.
+        step.amount_left_to_select();
+        assert!(step.shortfall_error().is_some());
+
+        // A single coin doesn't cover the recipient.
+        step.coins[0].1 = true;
+        step.amount_left_to_select();
+        let left = step.amount_left_to_select.unwrap();
+        assert!(left > Amount::from_sat(50_000));
+        assert!(step
+            .shortfall_error()
+            .unwrap()
+            .to_string()
+            .contains(&left.to_string()));
+
+        // Both coins cover the recipient and the fees.
+        step.coins[1].1 = true;
+        step.amount_left_to_select();
+        assert_eq!(step.amount_left_to_select, Some(Amount::from_sat(0)));
+        assert!(step.shortfall_error().is_none());
+    }
+
+    #[test]
+    fn test_duplicate_recipients() {
+        let recipient = |address: &str| Recipient {
+            address: form::Value {
+                value: address.to_string(),
+                valid: true,
+            },
+            amount: form::Value {
+                value: "0.001".to_string(),
+                valid: true,
+            },
+        };
+        let mut step = ChooseRecipients::new(&[], Unit::Btc);
+        step.recipients = vec![
+            recipient("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv"),
+            recipient("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+        ];
+        step.check_valid();
+        assert!(step.is_valid);
+        assert!(!step.is_duplicate);
+
+        // The same output script, whatever the encoding of the address.
+        step.recipients
+            .push(recipient("BC1QNSEXK3GNUYAYU92FC3TCZVC7K62U22A22UA2KV"));
+        step.check_valid();
+        assert!(!step.is_valid);
+        assert!(step.is_duplicate);
+
+        step.recipients.pop();
+        step.check_valid();
+        assert!(step.is_valid);
+    }
+
+    #[test]
+    fn test_sweep_recipient() {
+        let mut step = ChooseRecipients::new(&[], Unit::Btc);
+        step.recipients = vec![
+            Recipient {
+                address: form::Value {
+                    value: "bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv".to_string(),
+                    valid: true,
+                },
+                amount: form::Value::default(),
+            },
+            Recipient::default(),
+        ];
+        step.check_valid();
+        assert!(!step.is_valid);
+
+        // A single recipient is kept, and its amount is not needed.
+        step.sweep = true;
+        step.recipients.truncate(1);
+        step.check_valid();
+        assert!(step.is_valid);
+
+        let mut draft = TransactionDraft::default();
+        step.apply(&mut draft);
+        assert!(draft.outputs.is_empty());
+        assert_eq!(
+            draft.sweep,
+            Some(Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap())
+        );
+    }
+}
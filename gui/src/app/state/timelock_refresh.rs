@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use iced::{Command, Element, Subscription};
+
+use crate::{
+    app::{
+        cache::Cache,
+        error::Error,
+        menu::Menu,
+        message::Message,
+        state::spend::detail,
+        state::{redirect, State},
+        view,
+        wallet::Wallet,
+    },
+    daemon::{
+        model::{remaining_sequence, Coin, SpendTx},
+        Daemon,
+    },
+    ui::component::form,
+};
+
+use liana::miniscript::bitcoin::Amount;
+
+/// Spend back to the wallet the coins whose recovery path becomes available soon, so their
+/// timelock restarts while the owner is still active.
+pub struct TimelockRefreshPanel {
+    wallet: Arc<Wallet>,
+    coins: Vec<Coin>,
+    warning: Option<Error>,
+    /// Refresh the coins whose recovery path is available within this number of blocks.
+    threshold: form::Value<String>,
+    feerate: form::Value<String>,
+    generated: Option<detail::SpendTxState>,
+    /// Timelocks of the recovery paths of the wallet, and the one of the path to refresh the
+    /// coins for.
+    timelocks: Vec<u16>,
+    timelock: u16,
+}
+
+impl TimelockRefreshPanel {
+    pub fn new(wallet: Arc<Wallet>, coins: &[Coin]) -> Self {
+        let timelocks: Vec<u16> = wallet
+            .main_descriptor
+            .info()
+            .recovery_paths()
+            .keys()
+            .copied()
+            .collect();
+        // By default the path available first.
+        let timelock = timelocks[0];
+        Self {
+            wallet,
+            coins: coins.to_vec(),
+            warning: None,
+            // The coins for which the home page warns about the recovery path.
+            threshold: form::Value {
+                value: (u32::from(timelock) * 10 / 100).to_string(),
+                valid: true,
+            },
+            feerate: form::Value::default(),
+            generated: None,
+            timelocks,
+            timelock,
+        }
+    }
+
+    /// The number and value of the coins to be refreshed with the current threshold.
+    fn coins_to_refresh(&self, blockheight: u32) -> (usize, Amount) {
+        let threshold = match self.threshold.value.parse::<u32>() {
+            Ok(threshold) if self.threshold.valid => threshold,
+            _ => return (0, Amount::from_sat(0)),
+        };
+        self.coins
+            .iter()
+            .filter(|coin| {
+                coin.spend_info.is_none()
+                    && coin.block_height.is_some()
+                    && !coin.is_immature
+                    && remaining_sequence(coin, blockheight, self.timelock.into()) <= threshold
+            })
+            .fold((0, Amount::from_sat(0)), |(count, value), coin| {
+                (count + 1, value + coin.amount)
+            })
+    }
+}
+
+impl State for TimelockRefreshPanel {
+    fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
+        if let Some(generated) = &self.generated {
+            generated.view(cache)
+        } else {
+            view::modal(
+                false,
+                self.warning.as_ref(),
+                view::timelock_refresh::timelock_refresh(
+                    self.coins_to_refresh(cache.blockheight as u32),
+                    &self.timelocks,
+                    self.timelock,
+                    &self.threshold,
+                    &self.feerate,
+                    cache.mempool_min_feerate,
+                    cache.unit,
+                ),
+                None::<Element<view::Message>>,
+            )
+        }
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        cache: &Cache,
+        message: Message,
+    ) -> Command<Message> {
+        match message {
+            Message::Coins(res) => match res {
+                Err(e) => self.warning = Some(e),
+                Ok(coins) => {
+                    self.warning = None;
+                    self.coins = coins;
+                }
+            },
+            Message::TimelockRefresh(res) => match res {
+                Ok(tx) => {
+                    self.generated = Some(detail::SpendTxState::new(self.wallet.clone(), tx, false))
+                }
+                Err(e) => self.warning = Some(e),
+            },
+            Message::View(msg) => match msg {
+                view::Message::Close => return redirect(Menu::Home),
+                view::Message::Previous => self.generated = None,
+                view::Message::CreateSpend(view::CreateSpendMessage::RefreshThresholdEdited(
+                    threshold,
+                )) => {
+                    self.threshold.value = threshold;
+                    self.threshold.valid = self.threshold.value.parse::<u32>().is_ok();
+                }
+                view::Message::CreateSpend(view::CreateSpendMessage::RefreshTimelockSelected(
+                    timelock,
+                )) => {
+                    self.timelock = timelock;
+                }
+                view::Message::CreateSpend(view::CreateSpendMessage::FeerateEdited(feerate)) => {
+                    self.feerate.value = feerate;
+                    self.feerate.valid = self
+                        .feerate
+                        .value
+                        .parse::<u64>()
+                        .map(|f| f > 0 && f >= cache.mempool_min_feerate)
+                        .unwrap_or(false);
+                }
+                view::Message::Next => {
+                    let threshold = self.threshold.value.parse::<u32>().expect("Checked before");
+                    let feerate_vb = self.feerate.value.parse::<u64>().expect("Checked before");
+                    let timelock = self.timelock;
+                    self.warning = None;
+                    let desc = self.wallet.main_descriptor.clone();
+                    return Command::perform(
+                        async move {
+                            let psbt =
+                                daemon.create_refresh(threshold, feerate_vb, Some(timelock))?;
+                            let coins = daemon.list_coins().map(|res| res.coins)?;
+                            let coins = coins
+                                .iter()
+                                .filter(|coin| {
+                                    psbt.unsigned_tx
+                                        .input
+                                        .iter()
+                                        .any(|input| input.previous_output == coin.outpoint)
+                                })
+                                .copied()
+                                .collect();
+                            let sigs = desc.partial_spend_info(&psbt).unwrap();
+                            Ok(SpendTx::new(psbt, coins, sigs, &desc))
+                        },
+                        Message::TimelockRefresh,
+                    );
+                }
+                _ => {
+                    if let Some(generated) = &mut self.generated {
+                        return generated.update(daemon, cache, Message::View(msg));
+                    }
+                }
+            },
+            _ => {
+                if let Some(generated) = &mut self.generated {
+                    return generated.update(daemon, cache, message);
+                }
+            }
+        };
+        Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if let Some(generated) = &self.generated {
+            generated.subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn load(&self, daemon: Arc<dyn Daemon + Sync + Send>) -> Command<Message> {
+        let daemon = daemon.clone();
+        Command::perform(
+            async move {
+                daemon
+                    .list_coins()
+                    .map(|res| res.coins)
+                    .map_err(|e| e.into())
+            },
+            Message::Coins,
+        )
+    }
+}
+
+impl From<TimelockRefreshPanel> for Box<dyn State> {
+    fn from(s: TimelockRefreshPanel) -> Box<dyn State> {
+        Box::new(s)
+    }
+}
@@ -0,0 +1,92 @@
+use liana::miniscript::bitcoin::{util::amount::ParseAmountError, Amount, Denomination};
+use serde::{Deserialize, Serialize};
+
+/// The unit amounts are displayed in and entered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Btc,
+    Mbtc,
+    Sats,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 3] = [Unit::Btc, Unit::Mbtc, Unit::Sats];
+
+    pub fn denomination(&self) -> Denomination {
+        match self {
+            Self::Btc => Denomination::Bitcoin,
+            Self::Mbtc => Denomination::MilliBitcoin,
+            Self::Sats => Denomination::Satoshi,
+        }
+    }
+
+    /// The amount in this unit, without the unit label. Every decimal place is kept so that
+    /// the amounts of a list are aligned, and satoshis are grouped by thousands.
+    pub fn format_value(&self, a: &Amount) -> String {
+        let sats = a.to_sat();
+        match self {
+            Self::Btc => format!("{}.{:08}", sats / 100_000_000, sats % 100_000_000),
+            Self::Mbtc => format!("{}.{:05}", sats / 100_000, sats % 100_000),
+            Self::Sats => {
+                let digits = sats.to_string();
+                let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+                for (i, c) in digits.chars().enumerate() {
+                    if i > 0 && (digits.len() - i) % 3 == 0 {
+                        grouped.push(' ');
+                    }
+                    grouped.push(c);
+                }
+                grouped
+            }
+        }
+    }
+
+    /// The amount in this unit followed by the unit label.
+    pub fn format(&self, a: &Amount) -> String {
+        format!("{} {}", self.format_value(a), self)
+    }
+
+    /// Parse an amount entered by the user in this unit. Spaces are ignored, so that a
+    /// grouped amount can be pasted back.
+    pub fn parse(&self, s: &str) -> Result<Amount, ParseAmountError> {
+        Amount::from_str_in(&s.replace(' ', ""), self.denomination())
+    }
+}
+
+impl std::default::Default for Unit {
+    fn default() -> Self {
+        Self::Btc
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Btc => write!(f, "BTC"),
+            Self::Mbtc => write!(f, "mBTC"),
+            Self::Sats => write!(f, "sats"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse() {
+        let a = Amount::from_sat(1_234_567);
+        assert_eq!(Unit::Btc.format(&a), "0.01234567 BTC");
+        assert_eq!(Unit::Mbtc.format(&a), "12.34567 mBTC");
+        assert_eq!(Unit::Sats.format(&a), "1 234 567 sats");
+        assert_eq!(Unit::Sats.format_value(&Amount::from_sat(567)), "567");
+        assert_eq!(Unit::Sats.format_value(&Amount::from_sat(0)), "0");
+
+        for unit in Unit::ALL {
+            assert_eq!(unit.parse(&unit.format_value(&a)).unwrap(), a);
+        }
+        assert_eq!(Unit::Mbtc.parse("0.05").unwrap(), Amount::from_sat(5_000));
+        assert!(Unit::Sats.parse("0.5").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+use liana::miniscript::bitcoin::{consensus, util::psbt::Psbt};
+
+use crate::app::error::Error;
+
+///! Ur is the module to transfer PSBTs through animated QR codes, encoded as BC-UR
+///! `crypto-psbt` fountain-coded frames.
+pub const PSBT_UR_TYPE: &str = "crypto-psbt";
+
+/// Maximum number of bytes of the PSBT carried by a single frame, small enough for the frame
+/// to be scanned by a phone camera.
+const MAX_FRAGMENT_LENGTH: usize = 200;
+
+/// Whether the input looks like a BC-UR frame rather than a base64 encoded PSBT.
+pub fn is_ur(s: &str) -> bool {
+    s.trim().get(..3).map(|p| p.eq_ignore_ascii_case("ur:")) == Some(true)
+}
+
+/// Endless sequence of frames of a PSBT, each one to be displayed as a QR code. The first ones
+/// are the fragments of the PSBT, the next ones are combinations of them helping a scanner which
+/// missed some frames.
+pub struct PsbtEncoder {
+    encoder: ::ur::Encoder,
+}
+
+impl PsbtEncoder {
+    pub fn new(psbt: &Psbt) -> Result<Self, Error> {
+        let encoder = ::ur::Encoder::new(
+            &cbor_bytes(&consensus::serialize(psbt)),
+            MAX_FRAGMENT_LENGTH,
+            PSBT_UR_TYPE,
+        )
+        .map_err(|e| Error::Unexpected(format!("Failed to encode the PSBT: {}", e)))?;
+        Ok(Self { encoder })
+    }
+
+    pub fn fragment_count(&self) -> usize {
+        self.encoder.fragment_count()
+    }
+
+    /// The next frame, in upper case for the QR code to use the compact alphanumeric mode.
+    pub fn next_frame(&mut self) -> Result<String, Error> {
+        self.encoder
+            .next_part()
+            .map(|part| part.to_uppercase())
+            .map_err(|e| Error::Unexpected(format!("Failed to encode the PSBT: {}", e)))
+    }
+}
+
+/// Frames of a PSBT scanned so far.
+#[derive(Default)]
+pub struct PsbtDecoder {
+    decoder: ::ur::Decoder,
+    /// Number of fragments of the PSBT being received.
+    fragment_count: Option<usize>,
+    /// Sequence numbers of the fragments received, the combined frames are not counted.
+    received: BTreeSet<usize>,
+}
+
+impl PsbtDecoder {
+    /// Receive a frame and return the PSBT once enough frames were received. A frame of another
+    /// PSBT than the one being received starts the decoding over.
+    pub fn receive(&mut self, frame: &str) -> Result<Option<Psbt>, Error> {
+        let frame = frame.trim().to_lowercase();
+        let content = frame
+            .strip_prefix("ur:")
+            .and_then(|f| f.strip_prefix(PSBT_UR_TYPE))
+            .and_then(|f| f.strip_prefix('/'))
+            .ok_or_else(|| Error::Unexpected("The QR code is not a PSBT".to_string()))?;
+
+        // A single part frame carries the whole PSBT.
+        let (sequence, count) = match content.split_once('/') {
+            Some((sequence, _)) => parse_sequence(sequence)
+                .ok_or_else(|| Error::Unexpected("Invalid QR code frame".to_string()))?,
+            None => {
+                let (_, data) = ::ur::decode(&frame)
+                    .map_err(|e| Error::Unexpected(format!("Invalid QR code frame: {}", e)))?;
+                return decode_psbt(&data).map(Some);
+            }
+        };
+
+        if self.fragment_count != Some(count) {
+            *self = Self {
+                fragment_count: Some(count),
+                ..Self::default()
+            };
+        }
+        self.decoder
+            .receive(&frame)
+            .map_err(|e| Error::Unexpected(format!("Invalid QR code frame: {}", e)))?;
+        if sequence <= count {
+            self.received.insert(sequence);
+        }
+
+        if !self.decoder.complete() {
+            return Ok(None);
+        }
+        let data = self
+            .decoder
+            .message()
+            .map_err(|e| Error::Unexpected(format!("Invalid QR code frames: {}", e)))?
+            .ok_or_else(|| Error::Unexpected("Invalid QR code frames".to_string()))?;
+        *self = Self::default();
+        decode_psbt(&data).map(Some)
+    }
+
+    /// Number of fragments of the PSBT not received yet, if one is being received. The combined
+    /// frames may complete the PSBT before all of them are received.
+    pub fn missing_frames(&self) -> Option<usize> {
+        self.fragment_count
+            .map(|count| count.saturating_sub(self.received.len()))
+    }
+}
+
+/// Parse the `<sequence number>-<fragment count>` of a multi-part frame.
+fn parse_sequence(s: &str) -> Option<(usize, usize)> {
+    let (sequence, count) = s.split_once('-')?;
+    Some((sequence.parse().ok()?, count.parse().ok()?))
+}
+
+fn decode_psbt(data: &[u8]) -> Result<Psbt, Error> {
+    cbor_unwrap(data)
+        .and_then(|bytes| consensus::deserialize(bytes).ok())
+        .ok_or_else(|| Error::Unexpected("The QR code does not contain a valid PSBT".to_string()))
+}
+
+/// The `crypto-psbt` type is the PSBT as a CBOR byte string.
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let len = data.len();
+    let mut cbor = Vec::with_capacity(len + 9);
+    if len < 24 {
+        cbor.push(0x40 | len as u8);
+    } else if len <= u8::MAX as usize {
+        cbor.push(0x58);
+        cbor.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        cbor.push(0x59);
+        cbor.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as usize {
+        cbor.push(0x5a);
+        cbor.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        cbor.push(0x5b);
+        cbor.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    cbor.extend_from_slice(data);
+    cbor
+}
+
+fn cbor_unwrap(cbor: &[u8]) -> Option<&[u8]> {
+    let (&head, rest) = cbor.split_first()?;
+    if head >> 5 != 2 {
+        return None;
+    }
+    let (len, data) = match head & 0x1f {
+        n @ 0..=23 => (n as usize, rest),
+        24 => (*rest.first()? as usize, rest.get(1..)?),
+        25 => (
+            u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize,
+            rest.get(2..)?,
+        ),
+        26 => (
+            u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize,
+            rest.get(4..)?,
+        ),
+        27 => (
+            u64::from_be_bytes(rest.get(..8)?.try_into().ok()?) as usize,
+            rest.get(8..)?,
+        ),
+        _ => return None,
+    };
+    if data.len() == len {
+        Some(data)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::miniscript::bitcoin::{PackedLockTime, Script, Transaction, TxIn, TxOut};
+
+    fn psbt(outputs: usize) -> Psbt {
+        Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn::default()],
+            output: (0..outputs)
+                .map(|i| TxOut {
+                    value: i as u64,
+                    script_pubkey: Script::new(),
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cbor_bytes() {
+        for len in [0, 23, 24, 255, 256, 65_535, 65_536] {
+            let data = vec![7; len];
+            assert_eq!(cbor_unwrap(&cbor_bytes(&data)), Some(&data[..]));
+        }
+        assert_eq!(cbor_bytes(&[1, 2])[0], 0x42);
+        assert_eq!(cbor_unwrap(&[0x43, 1, 2]), None);
+        assert_eq!(cbor_unwrap(&[0x82, 1, 2]), None);
+    }
+
+    #[test]
+    fn test_psbt_frames() {
+        let psbt = psbt(30);
+        let mut encoder = PsbtEncoder::new(&psbt).unwrap();
+        let count = encoder.fragment_count();
+        assert!(count > 1);
+        let frames: Vec<String> = (0..count).map(|_| encoder.next_frame().unwrap()).collect();
+        assert!(frames.iter().all(|f| is_ur(f)));
+
+        let mut decoder = PsbtDecoder::default();
+        assert_eq!(decoder.missing_frames(), None);
+        for frame in &frames[..count - 1] {
+            assert!(decoder.receive(frame).unwrap().is_none());
+            // Receiving the same frame twice does not count.
+            assert!(decoder.receive(frame).unwrap().is_none());
+        }
+        assert_eq!(decoder.missing_frames(), Some(1));
+        assert_eq!(
+            decoder.receive(&frames[count - 1]).unwrap(),
+            Some(psbt.clone())
+        );
+        assert_eq!(decoder.missing_frames(), None);
+
+        // The frames of another PSBT start the decoding over.
+        let mut decoder = PsbtDecoder::default();
+        decoder.receive(&frames[0]).unwrap();
+        let mut other = PsbtEncoder::new(&self::psbt(100)).unwrap();
+        decoder.receive(&other.next_frame().unwrap()).unwrap();
+        assert_eq!(decoder.missing_frames(), Some(other.fragment_count() - 1));
+
+        assert!(decoder.receive("ur:bytes/lpadaxcs").is_err());
+        assert!(!is_ur(&psbt.to_string()));
+    }
+}
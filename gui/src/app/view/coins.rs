@@ -1,17 +1,27 @@
+use std::collections::HashMap;
+
 use iced::{
-    widget::{Button, Column, Container, Row},
+    widget::{Button, Column, Container, Row, Space},
     Alignment, Element, Length,
 };
+use liana::miniscript::bitcoin::{OutPoint, Txid};
 
 use crate::{
     app::{
         cache::Cache,
-        view::{message::Message, util::*},
+        error::Error,
+        labels::{LabelKind, Labels},
+        settings::ConfirmationTiers,
+        unit::Unit,
+        view::{label::label_editor, message::*, util::*, warning::warn},
+    },
+    daemon::model::{
+        blocks_to_days, coin_parents, confirmations, remaining_maturity, remaining_sequence, Coin,
+        RecoveryStatus,
     },
-    daemon::model::{remaining_sequence, Coin},
     ui::{
         color,
-        component::{badge, button, card, separation, text::*},
+        component::{badge, button, card, form, separation, text::*},
         icon,
         util::Collection,
     },
@@ -22,6 +32,7 @@ pub fn coins_view<'a>(
     coins: &'a [Coin],
     timelock: u32,
     selected: &[usize],
+    labels_edited: &HashMap<String, String>,
 ) -> Element<'a, Message> {
     Column::new()
         .push(
@@ -40,10 +51,15 @@ pub fn coins_view<'a>(
                     |col, (i, coin)| {
                         col.push(coin_list_view(
                             coin,
+                            &coin_parents(coin, &cache.coins),
                             timelock,
                             cache.blockheight as u32,
                             i,
                             selected.contains(&i),
+                            &cache.confirmation_tiers,
+                            &cache.labels,
+                            labels_edited,
+                            cache.unit,
                         ))
                     },
                 )),
@@ -53,14 +69,43 @@ pub fn coins_view<'a>(
         .into()
 }
 
-#[allow(clippy::collapsible_else_if)]
-fn coin_list_view(
-    coin: &Coin,
+fn recovery_status_view<'a>(status: RecoveryStatus) -> Container<'a, Message> {
+    Container::new(match status {
+        RecoveryStatus::Available => text("Recovery available now")
+            .bold()
+            .small()
+            .style(color::ALERT),
+        RecoveryStatus::InBlocks(blocks) => text(format!(
+            "Recovery available in {} blocks (~{} days)",
+            blocks,
+            blocks_to_days(blocks)
+        ))
+        .bold()
+        .small(),
+        RecoveryStatus::Pending(blocks) => text(format!(
+            "Unconfirmed: recovery available {} blocks (~{} days) after confirmation",
+            blocks,
+            blocks_to_days(blocks)
+        ))
+        .small(),
+    })
+}
+
+#[allow(clippy::collapsible_else_if, clippy::too_many_arguments)]
+fn coin_list_view<'a>(
+    coin: &'a Coin,
+    parents: &[&Coin],
     timelock: u32,
     blockheight: u32,
     index: usize,
     collapsed: bool,
-) -> Container<Message> {
+    confirmation_tiers: &ConfirmationTiers,
+    labels: &Labels,
+    labels_edited: &HashMap<String, String>,
+    unit: Unit,
+) -> Container<'a, Message> {
+    let confs = confirmations(coin.block_height, blockheight as i32);
+    let required_confs = confirmation_tiers.required_confirmations(coin.amount);
     Container::new(
         Column::new()
             .push(
@@ -111,16 +156,31 @@ fn coin_list_view(
                                         ))
                                     }
                                 })
-                                .push_maybe(if coin.block_height.is_none() {
+                                .push_maybe(if coin.is_replaceable {
+                                    Some(badge::replaceable())
+                                } else if coin.block_height.is_none() {
                                     Some(badge::unconfirmed())
+                                } else if confs < required_confs {
+                                    Some(badge::confirmations(confs, required_confs))
                                 } else {
                                     None
                                 })
+                                .push_maybe(remaining_maturity(coin, blockheight).map(|blocks| {
+                                    text(format!(
+                                        "Immature coinbase, spendable in {} blocks",
+                                        blocks
+                                    ))
+                                    .small()
+                                    .style(color::WARNING)
+                                }))
+                                .push_maybe(labels.get(&coin.outpoint.to_string()).map(|label| {
+                                    text(label.to_string()).small().bold()
+                                }))
                                 .spacing(10)
                                 .align_items(Alignment::Center)
                                 .width(Length::Fill),
                         )
-                        .push(amount(&coin.amount))
+                        .push(amount(&coin.amount, unit))
                         .align_items(Alignment::Center)
                         .spacing(20),
                 )
@@ -138,24 +198,11 @@ fn coin_list_view(
                                 .padding(10)
                                 .spacing(5)
                                 .push_maybe(if coin.spend_info.is_none() {
-                                    if let Some(b) = coin.block_height {
-                                        if blockheight > b as u32 + timelock {
-                                            Some(Container::new(
-                                                text("The recovery path is available")
-                                                    .bold()
-                                                    .small()
-                                                    .style(color::ALERT),
-                                            ))
-                                        } else {
-                                            Some(Container::new(
-                                                text(format!("The recovery path will be available in {} blocks", b as u32 + timelock - blockheight))
-                                                .bold()
-                                                .small(),
-                                            ))
-                                        }
-                                    } else {
-                                        None
-                                    }
+                                    Some(recovery_status_view(RecoveryStatus::new(
+                                        coin,
+                                        blockheight,
+                                        timelock,
+                                    )))
                                 } else {
                                     None
                                 })
@@ -180,14 +227,47 @@ fn coin_list_view(
                                                 .spacing(5)
                                         })),
                                 )
+                                .push(label_editor(
+                                    LabelKind::Output,
+                                    coin.outpoint.to_string(),
+                                    labels,
+                                    labels_edited,
+                                ))
+                                .push(coin_lineage_view(coin, parents, unit))
+                                .push_maybe(
+                                    if coin.block_height.is_none() && coin.spend_info.is_none() {
+                                        Some(
+                                            Row::new()
+                                                .push(Space::with_width(Length::Fill))
+                                                .push(
+                                                    button::border(None, "Bump fee")
+                                                        .on_press(Message::Coins(
+                                                            CoinsMessage::BumpFee(coin.outpoint),
+                                                        ))
+                                                        .width(Length::Units(150)),
+                                                ),
+                                        )
+                                    } else {
+                                        None
+                                    },
+                                )
                                 .push_maybe(coin.spend_info.map(|info| {
                                     Column::new()
                                         .push(
                                             Row::new()
+                                                .align_items(Alignment::Center)
                                                 .push(text("Spend txid:").small().bold())
                                                 .push(text(format!("{}", info.txid)).small())
+                                                .push(transaction_link(info.txid))
                                                 .spacing(5),
                                         )
+                                        .push_maybe(info.replaced_txid.map(|txid| {
+                                            Row::new()
+                                                .align_items(Alignment::Center)
+                                                .push(text("Replaces spend txid:").small().bold())
+                                                .push(text(format!("{}", txid)).small())
+                                                .spacing(5)
+                                        }))
                                         .push(if let Some(height) = info.height {
                                             Row::new()
                                                 .push(text("Spend block height:").small().bold())
@@ -206,3 +286,92 @@ fn coin_list_view(
     )
     .style(card::SimpleCardStyle)
 }
+
+pub fn cpfp_modal<'a>(
+    warning: Option<&Error>,
+    outpoint: &OutPoint,
+    feerate: &form::Value<String>,
+    processing: bool,
+    created: bool,
+) -> Element<'a, Message> {
+    if created {
+        card::simple(text(
+            "The transaction is saved. Sign and broadcast it from the PSBTs menu to bump the fee.",
+        ))
+        .width(Length::Units(400))
+        .align_x(iced::alignment::Horizontal::Center)
+        .into()
+    } else {
+        card::simple(
+            Column::new()
+                .spacing(10)
+                .push_maybe(warning.map(|w| warn(Some(w))))
+                .push(text("Bump the fee of the pending payment").bold())
+                .push(
+                    text(format!(
+                        "The coin {} is spent back to the wallet with a fee high enough for \
+                        both transactions to reach the target feerate.",
+                        outpoint
+                    ))
+                    .small(),
+                )
+                .push(
+                    form::Form::new("Feerate (sat/vbyte)", feerate, |msg| {
+                        Message::Coins(CoinsMessage::FeerateEdited(msg))
+                    })
+                    .warning("Invalid feerate")
+                    .size(20)
+                    .padding(10),
+                )
+                .push(Row::new().push(Column::new().width(Length::Fill)).push(
+                    if feerate.valid && !feerate.value.is_empty() && !processing {
+                        button::primary(None, "Bump fee")
+                            .on_press(Message::Coins(CoinsMessage::Confirm))
+                    } else {
+                        button::primary(None, "Bump fee")
+                    },
+                )),
+        )
+        .width(Length::Units(400))
+        .into()
+    }
+}
+
+/// The transaction that created the coin and the wallet coins it spent.
+fn coin_lineage_view<'a>(coin: &Coin, parents: &[&Coin], unit: Unit) -> Column<'a, Message> {
+    Column::new()
+        .spacing(5)
+        .push(
+            Row::new()
+                .align_items(Alignment::Center)
+                .push(text("Created by txid:").small().bold())
+                .push(text(format!("{}", coin.outpoint.txid)).small())
+                .push(transaction_link(coin.outpoint.txid))
+                .spacing(5),
+        )
+        .push_maybe(if parents.is_empty() {
+            None
+        } else {
+            Some(text("Funded by the wallet coins:").small().bold())
+        })
+        .push(
+            parents
+                .iter()
+                .fold(Column::new().spacing(5), |col, parent| {
+                    col.push(
+                        Row::new()
+                            .align_items(Alignment::Center)
+                            .spacing(5)
+                            .push(text(format!("{}", parent.outpoint)).small())
+                            .push(text(unit.format(&parent.amount)).small())
+                            .push(transaction_link(parent.outpoint.txid)),
+                    )
+                }),
+        )
+}
+
+fn transaction_link<'a>(txid: Txid) -> Button<'a, Message> {
+    button::transparent(None, "See transaction")
+        .on_press(Message::SelectTransaction(txid))
+        .padding(0)
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::NaiveDateTime;
 
 use iced::{
@@ -8,7 +10,7 @@ use iced::{
 
 use crate::ui::{
     color,
-    component::{badge, button::Style, card, text::*},
+    component::{badge, button, button::Style, card, chart::LineChart, text::*},
     icon,
     util::Collection,
 };
@@ -17,23 +19,52 @@ use liana::miniscript::bitcoin;
 use crate::{
     app::{
         cache::Cache,
-        view::{message::Message, util::*},
+        labels::{LabelKind, Labels},
+        menu::Menu,
+        price::Price,
+        settings::ConfirmationTiers,
+        unit::Unit,
+        view::{label::label_editor, message::Message, util::*},
     },
-    daemon::model::HistoryTransaction,
+    daemon::model::{confirmations, HistoryTransaction},
 };
 
 pub const HISTORY_EVENT_PAGE_SIZE: u64 = 20;
 
+#[allow(clippy::too_many_arguments)]
 pub fn home_view<'a>(
+    is_empty_wallet: bool,
+    rescan_needed: bool,
+    verification_due: bool,
+    consolidation: Option<(usize, bitcoin::Amount)>,
     balance: &'a bitcoin::Amount,
+    balance_history: Option<&Vec<(i32, bitcoin::Amount)>>,
     recovery_warning: Option<&(bitcoin::Amount, usize)>,
     recovery_alert: Option<&(bitcoin::Amount, usize)>,
     pending_events: &[HistoryTransaction],
     events: &Vec<HistoryTransaction>,
+    blockheight: i32,
+    confirmation_tiers: &ConfirmationTiers,
+    labels: &Labels,
+    unit: Unit,
+    price: Option<&Price>,
 ) -> Element<'a, Message> {
     Column::new()
+        .push_maybe(if rescan_needed {
+            Some(rescan_needed_view())
+        } else {
+            None
+        })
+        .push_maybe(if verification_due {
+            Some(verification_due_view())
+        } else {
+            None
+        })
+        .push_maybe(consolidation.map(|(count, savings)| consolidation_view(count, savings, unit)))
         .push(Column::new().padding(40))
-        .push(amount_with_size(balance, 50))
+        .push(amount_with_size(balance, unit, 50))
+        .push_maybe(fiat_amount(balance, price))
+        .push(balance_chart_view(balance_history, unit))
         .push_maybe(recovery_warning.map(|(a, c)| {
             Row::new()
                 .spacing(15)
@@ -47,9 +78,14 @@ pub fn home_view<'a>(
                             c
                         )))
                         .push(text("("))
-                        .push(amount(a))
+                        .push(amount(a, unit))
                         .push(text(")")),
                 )
+                .push(
+                    button::border(None, "Refresh")
+                        .on_press(Message::Menu(Menu::TimelockRefresh))
+                        .width(Length::Units(150)),
+                )
                 .padding(10)
         }))
         .push_maybe(recovery_alert.map(|(a, c)| {
@@ -62,30 +98,50 @@ pub fn home_view<'a>(
                         .spacing(5)
                         .push(text(format!("Recovery path is available for {} coins", c)))
                         .push(text("("))
-                        .push(amount(a))
+                        .push(amount(a, unit))
                         .push(text(")")),
                 )
+                .push(
+                    button::border(None, "Refresh")
+                        .on_press(Message::Menu(Menu::TimelockRefresh))
+                        .width(Length::Units(150)),
+                )
                 .padding(10)
         }))
+        .push_maybe(if is_empty_wallet {
+            Some(empty_wallet_view())
+        } else {
+            None
+        })
         .push(
             Column::new()
                 .spacing(10)
-                .push(
-                    pending_events
-                        .iter()
-                        .enumerate()
-                        .fold(Column::new().spacing(10), |col, (i, event)| {
-                            col.push(event_list_view(i, event))
-                        }),
-                )
-                .push(
-                    events
-                        .iter()
-                        .enumerate()
-                        .fold(Column::new().spacing(10), |col, (i, event)| {
-                            col.push(event_list_view(i + pending_events.len(), event))
-                        }),
-                )
+                .push(pending_events.iter().enumerate().fold(
+                    Column::new().spacing(10),
+                    |col, (i, event)| {
+                        col.push(event_list_view(
+                            i,
+                            event,
+                            labels.get(&event.tx.txid().to_string()),
+                            blockheight,
+                            confirmation_tiers,
+                            unit,
+                        ))
+                    },
+                ))
+                .push(events.iter().enumerate().fold(
+                    Column::new().spacing(10),
+                    |col, (i, event)| {
+                        col.push(event_list_view(
+                            i + pending_events.len(),
+                            event,
+                            labels.get(&event.tx.txid().to_string()),
+                            blockheight,
+                            confirmation_tiers,
+                            unit,
+                        ))
+                    },
+                ))
                 .push_maybe(
                     if events.len() % HISTORY_EVENT_PAGE_SIZE as usize == 0 && !events.is_empty() {
                         Some(
@@ -113,7 +169,185 @@ pub fn home_view<'a>(
         .into()
 }
 
-fn event_list_view<'a>(i: usize, event: &HistoryTransaction) -> Element<'a, Message> {
+/// Optional chart of the confirmed balance over the block heights it changed at.
+fn balance_chart_view<'a>(
+    history: Option<&Vec<(i32, bitcoin::Amount)>>,
+    unit: Unit,
+) -> Element<'a, Message> {
+    let toggle = Row::new()
+        .push(Column::new().width(Length::Fill))
+        .push(if history.is_some() {
+            button::transparent(None, "Hide balance chart")
+                .on_press(Message::ShowBalanceChart(false))
+        } else {
+            button::transparent(None, "Show balance chart")
+                .on_press(Message::ShowBalanceChart(true))
+        });
+    match history {
+        Some(history) if history.len() > 1 => Column::new()
+            .spacing(10)
+            .push(toggle)
+            .push(
+                LineChart::new(
+                    history
+                        .iter()
+                        .map(|(height, amount)| {
+                            (
+                                *height as f32,
+                                amount.to_float_in(unit.denomination()) as f32,
+                            )
+                        })
+                        .collect(),
+                )
+                .view(150),
+            )
+            .push(
+                Row::new()
+                    .push(text(format!("Block {}", history[0].0)).small())
+                    .push(Column::new().width(Length::Fill))
+                    .push(text(format!("Block {}", history[history.len() - 1].0)).small()),
+            )
+            .into(),
+        Some(_) => Column::new()
+            .spacing(10)
+            .push(toggle)
+            .push(text("Not enough confirmed history to draw a chart yet.").small())
+            .into(),
+        None => toggle.into(),
+    }
+}
+
+/// Reminder to check the signing devices and backups, once the period set by the user elapsed.
+fn verification_due_view<'a>() -> Element<'a, Message> {
+    Container::new(
+        Row::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(icon::chip_icon())
+            .push(
+                text(
+                    "It's time to check that your hardware devices still work and that your \
+                    backups are intact. Test your devices from the wallet settings.",
+                )
+                .width(Length::Fill),
+            )
+            .push(
+                button::border(None, "Settings")
+                    .on_press(Message::Menu(Menu::Settings))
+                    .width(Length::Units(150)),
+            ),
+    )
+    .padding(15)
+    .width(Length::Fill)
+    .style(card::SimpleCardStyle)
+    .into()
+}
+
+/// Suggestion to consolidate the small coins while the feerate is low, with the fee it
+/// would save compared to spending them later.
+fn consolidation_view<'a>(
+    count: usize,
+    savings: bitcoin::Amount,
+    unit: Unit,
+) -> Element<'a, Message> {
+    Container::new(
+        Row::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(icon::coin_icon())
+            .push(
+                Column::new()
+                    .width(Length::Fill)
+                    .push(text(format!(
+                        "Fees are low: consolidating your {} small coins now could save about",
+                        count
+                    )))
+                    .push(
+                        Row::new()
+                            .spacing(5)
+                            .push(amount(&savings, unit))
+                            .push(text("in future fees.")),
+                    ),
+            )
+            .push(
+                button::border(None, "Consolidate")
+                    .on_press(Message::Menu(Menu::Consolidate))
+                    .width(Length::Units(150)),
+            )
+            .push(
+                Button::new(icon::cross_icon())
+                    .on_press(Message::DismissConsolidationSuggestion)
+                    .style(Style::Transparent.into()),
+            ),
+    )
+    .padding(15)
+    .width(Length::Fill)
+    .style(card::SimpleCardStyle)
+    .into()
+}
+
+/// Banner displayed when the wallet parameters changed since the last scan of the chain.
+fn rescan_needed_view<'a>() -> Element<'a, Message> {
+    Container::new(
+        Row::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(icon::warning_octagon_icon().style(color::WARNING))
+            .push(
+                text(
+                    "The wallet configuration changed since the last scan of the blockchain, \
+                    some coins may be missing. Rescan the blockchain to find them.",
+                )
+                .style(color::WARNING)
+                .width(Length::Fill),
+            )
+            .push(
+                button::border(None, "Rescan")
+                    .on_press(Message::Menu(Menu::Settings))
+                    .width(Length::Units(150)),
+            ),
+    )
+    .padding(15)
+    .width(Length::Fill)
+    .style(card::WarningCardStyle)
+    .into()
+}
+
+/// Onboarding card displayed when the wallet has no coins and no history yet.
+fn empty_wallet_view<'a>() -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(icon::wallet_icon().size(50).style(color::DARK_GREY))
+            .push(text("No coins yet").bold())
+            .push(text(
+                "Your wallet is ready. Generate your first receive address to get started.",
+            ))
+            .push(
+                button::primary(Some(icon::receive_icon()), "Receive")
+                    .on_press(Message::Menu(Menu::Receive))
+                    .width(Length::Units(200)),
+            ),
+    )
+    .width(Length::Fill)
+    .into()
+}
+
+fn event_list_view<'a>(
+    i: usize,
+    event: &HistoryTransaction,
+    label: Option<&str>,
+    blockheight: i32,
+    confirmation_tiers: &ConfirmationTiers,
+    unit: Unit,
+) -> Element<'a, Message> {
+    let confs = confirmations(event.height, blockheight);
+    let required_confs = confirmation_tiers.required_confirmations(if event.is_external() {
+        event.incoming_amount
+    } else {
+        event.outgoing_amount
+    });
     Container::new(
         Button::new(
             Row::new()
@@ -132,6 +366,12 @@ fn event_list_view<'a>(i: usize, event: &HistoryTransaction) -> Element<'a, Mess
                         } else {
                             badge::unconfirmed()
                         })
+                        .push_maybe(if event.height.is_some() && confs < required_confs {
+                            Some(badge::confirmations(confs, required_confs))
+                        } else {
+                            None
+                        })
+                        .push_maybe(label.map(|label| text(label.to_string()).small().bold()))
                         .spacing(10)
                         .align_items(Alignment::Center)
                         .width(Length::Fill),
@@ -140,13 +380,24 @@ fn event_list_view<'a>(i: usize, event: &HistoryTransaction) -> Element<'a, Mess
                     Row::new()
                         .spacing(5)
                         .push(text("+"))
-                        .push(amount(&event.incoming_amount))
+                        .push(amount(&event.incoming_amount, unit))
                         .align_items(Alignment::Center)
                 } else {
                     Row::new()
                         .spacing(5)
-                        .push(text("-"))
-                        .push(amount(&event.outgoing_amount))
+                        .push(
+                            Column::new()
+                                .push(
+                                    Row::new()
+                                        .spacing(5)
+                                        .push(text("-"))
+                                        .push(amount(&event.outgoing_amount, unit))
+                                        .align_items(Alignment::Center),
+                                )
+                                .push_maybe(event.fee_amount.zip(event.feerate_vb()).map(
+                                    |(amount, feerate_vb)| fee_small(&amount, feerate_vb, unit),
+                                )),
+                        )
                         .align_items(Alignment::Center)
                 })
                 .align_items(Alignment::Center)
@@ -160,7 +411,31 @@ fn event_list_view<'a>(i: usize, event: &HistoryTransaction) -> Element<'a, Mess
     .into()
 }
 
-pub fn event_view<'a>(cache: &Cache, event: &'a HistoryTransaction) -> Element<'a, Message> {
+/// The spending transactions this one replaced, if any.
+fn replaced_txids_view<'a>(replaced: Vec<bitcoin::Txid>) -> Option<Row<'a, Message>> {
+    if replaced.is_empty() {
+        return None;
+    }
+    Some(
+        Row::new()
+            .width(Length::Fill)
+            .push(Container::new(text("Replaces:").bold()).width(Length::Fill))
+            .push(
+                replaced
+                    .into_iter()
+                    .fold(Column::new().align_items(Alignment::End), |col, txid| {
+                        col.push(text(txid.to_string()).small())
+                    })
+                    .width(Length::Shrink),
+            ),
+    )
+}
+
+pub fn event_view<'a>(
+    cache: &Cache,
+    event: &'a HistoryTransaction,
+    labels_edited: &HashMap<String, String>,
+) -> Element<'a, Message> {
     Column::new()
         .push(
             Row::new()
@@ -173,14 +448,23 @@ pub fn event_view<'a>(cache: &Cache, event: &'a HistoryTransaction) -> Element<'
                 .align_items(Alignment::Center),
         )
         .push(if event.is_external() {
-            amount_with_size(&event.incoming_amount, 50)
+            amount_with_size(&event.incoming_amount, cache.unit, 50)
         } else {
-            amount_with_size(&event.outgoing_amount, 50)
+            amount_with_size(&event.outgoing_amount, cache.unit, 50)
         })
+        .push_maybe(fiat_amount(
+            if event.is_external() {
+                &event.incoming_amount
+            } else {
+                &event.outgoing_amount
+            },
+            cache.price.as_ref(),
+        ))
         .push_maybe(
             event
                 .fee_amount
-                .map(|fee| Row::new().push(text("Miner Fee: ")).push(amount(&fee))),
+                .zip(event.feerate_vb())
+                .map(|(amount, feerate_vb)| fee(&amount, feerate_vb, cache.unit)),
         )
         .push(card::simple(
             Column::new()
@@ -191,6 +475,23 @@ pub fn event_view<'a>(cache: &Cache, event: &'a HistoryTransaction) -> Element<'
                         .push(Container::new(text("Date:").bold()).width(Length::Fill))
                         .push(Container::new(text(format!("{}", date))).width(Length::Shrink))
                 }))
+                .push_maybe(if event.is_external() || event.time.is_none() {
+                    None
+                } else {
+                    Some(
+                        Row::new()
+                            .width(Length::Fill)
+                            .push(Container::new(text("Status:").bold()).width(Length::Fill))
+                            .push(
+                                Container::new(text(if event.is_settled() {
+                                    "Settled"
+                                } else {
+                                    "Awaiting confirmations"
+                                }))
+                                .width(Length::Shrink),
+                            ),
+                    )
+                })
                 .push(
                     Row::new()
                         .width(Length::Fill)
@@ -208,6 +509,13 @@ pub fn event_view<'a>(cache: &Cache, event: &'a HistoryTransaction) -> Element<'
                                 .width(Length::Shrink),
                         ),
                 )
+                .push_maybe(replaced_txids_view(event.replaced_txids()))
+                .push(label_editor(
+                    LabelKind::Tx,
+                    event.tx.txid().to_string(),
+                    &cache.labels,
+                    labels_edited,
+                ))
                 .spacing(5),
         ))
         .push(super::spend::detail::inputs_and_outputs_view(
@@ -224,6 +532,7 @@ pub fn event_view<'a>(cache: &Cache, event: &'a HistoryTransaction) -> Element<'
             } else {
                 None
             },
+            cache.unit,
         ))
         .align_items(Alignment::Center)
         .spacing(20)
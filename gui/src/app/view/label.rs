@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use iced::{
+    widget::{Container, Row},
+    Alignment, Element, Length,
+};
+
+use crate::{
+    app::{
+        labels::{LabelKind, Labels},
+        view::message::{LabelMessage, Message},
+    },
+    ui::{
+        component::{button, form, text::*},
+        util::Collection,
+    },
+};
+
+/// Label of a transaction, an address or a coin, which the user can edit and save.
+pub fn label_editor<'a>(
+    kind: LabelKind,
+    reference: String,
+    labels: &Labels,
+    labels_edited: &HashMap<String, String>,
+) -> Element<'a, Message> {
+    let saved = labels.get(&reference).unwrap_or_default();
+    let value = labels_edited
+        .get(&reference)
+        .cloned()
+        .unwrap_or_else(|| saved.to_string());
+    let changed = value != saved;
+    let edited_reference = reference.clone();
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Label:").small().bold())
+        .push(
+            Container::new(
+                form::Form::new(
+                    "Add a label",
+                    &form::Value {
+                        value: value.clone(),
+                        valid: true,
+                    },
+                    move |msg| Message::Label(LabelMessage::Edited(edited_reference.clone(), msg)),
+                )
+                .padding(5),
+            )
+            .width(Length::Fill),
+        )
+        .push_maybe(if changed {
+            Some(
+                button::border(None, "Save")
+                    .on_press(Message::Label(LabelMessage::Save(kind, reference, value)))
+                    .width(Length::Units(100)),
+            )
+        } else {
+            None
+        })
+        .into()
+}
@@ -1,5 +1,5 @@
-use crate::app::menu::Menu;
-use liana::miniscript::bitcoin::util::bip32::Fingerprint;
+use crate::app::{labels::LabelKind, menu::Menu, unit::Unit};
+use liana::miniscript::bitcoin::{util::bip32::Fingerprint, EcdsaSighashType, OutPoint, Txid};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -8,13 +8,25 @@ pub enum Message {
     Menu(Menu),
     Close,
     Select(usize),
+    SelectTransaction(Txid),
     Settings(SettingsMessage),
     CreateSpend(CreateSpendMessage),
     ImportSpend(ImportSpendMessage),
+    /// Delete the drafts replaced by a final transaction.
+    CleanupSpends,
     Spend(SpendTxMessage),
+    Coins(CoinsMessage),
+    Label(LabelMessage),
+    Reconcile(ReconcileMessage),
     Next,
     Previous,
     SelectHardwareWallet(usize),
+    ShowBalanceChart(bool),
+    DismissConsolidationSuggestion,
+    /// Replace the receiving address which was likely given out before by a fresh one.
+    FreshAddress,
+    /// Keep the receiving address which was likely given out before.
+    DismissAddressReuse,
 }
 
 #[derive(Debug, Clone)]
@@ -23,7 +35,17 @@ pub enum CreateSpendMessage {
     DeleteRecipient(usize),
     SelectCoin(usize),
     RecipientEdited(usize, &'static str, String),
+    /// Send all the selected coins to a single recipient, without change.
+    SweepToggled(bool),
     FeerateEdited(String),
+    ConsolidationToggled(bool),
+    ConsolidationFeerateEdited(String),
+    RefreshOutputCountEdited(String),
+    RefreshRandomizeToggled(bool),
+    /// Number of blocks within which the recovery path of the coins to refresh is available.
+    RefreshThresholdEdited(String),
+    /// Timelock of the recovery path the coins to refresh are kept from reaching.
+    RefreshTimelockSelected(u16),
     Generate,
 }
 
@@ -31,9 +53,24 @@ pub enum CreateSpendMessage {
 pub enum ImportSpendMessage {
     Import,
     PsbtEdited(String),
+    /// Path of a file with the frames of an animated QR code, as scanned.
+    QrCodeFileEdited(String),
+    ImportQrCodeFile,
+    /// Path of a file with a PSBT, either base64 encoded or binary.
+    PsbtFileEdited(String),
+    ImportPsbtFile,
     Confirm,
 }
 
+#[derive(Debug, Clone)]
+pub enum ReconcileMessage {
+    CsvFileEdited(String),
+    Reconcile,
+    ExportFileEdited(String),
+    /// Write the whole wallet history to a CSV file.
+    Export,
+}
+
 #[derive(Debug, Clone)]
 pub enum SpendTxMessage {
     Delete,
@@ -43,9 +80,37 @@ pub enum SpendTxMessage {
     Confirm,
     Cancel,
     SelectHotSigner,
+    CancelSigning,
+    /// Sign again with the device which failed for a reason the user could fix.
+    RetrySigning,
+    SignWithAllDevices,
+    PsbtFileEdited(String),
+    ExportPsbtFile,
+    ImportSignedPsbtFile,
+    SighashSelected(EcdsaSighashType),
     EditPsbt,
+    Export,
     PsbtEdited(String),
     Next,
+    BumpFee,
+    BumpFeeFeerateEdited(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum CoinsMessage {
+    /// Bump the feerate of the transaction which created this unconfirmed coin by spending it.
+    BumpFee(OutPoint),
+    FeerateEdited(String),
+    Confirm,
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub enum LabelMessage {
+    /// The label of the object with the given reference was edited.
+    Edited(String, String),
+    /// Save the label of the object of the given kind and reference.
+    Save(LabelKind, String, String),
 }
 
 #[derive(Debug, Clone)]
@@ -53,10 +118,51 @@ pub enum SettingsMessage {
     EditBitcoindSettings,
     EditWalletSettings,
     AboutSection,
+    LoggingSection,
+    AddressBookSection,
+    ContactNameEdited(String),
+    ContactAddressEdited(String),
+    /// Fill the form with the contact at this index, to edit it.
+    EditContact(usize),
+    DeleteContact(usize),
+    SaveContact,
+    CancelContactEdit,
+    LogFilterEdited(String),
+    ApplyLogFilter,
+    /// Go back to the launcher to open another wallet, or the wallet of another network.
+    SwitchWallet,
     RegisterWallet,
+    TestHardwareWallets,
     FingerprintAliasEdited(Fingerprint, String),
+    DisplayedHistoryEdited(String),
+    DisplayUnitEdited(Unit),
+    FiatCurrencyEdited(String),
+    VerificationReminderEdited(String),
+    ConsolidationMaxFeerateEdited(String),
+    ConsolidationFutureFeerateEdited(String),
+    WatchOnlyToggled(bool),
+    AnnotationsFileEdited(String),
+    ExportAnnotations,
+    ImportAnnotations,
+    LabelsFileEdited(String),
+    ExportLabels,
+    ImportLabels,
+    /// Look for labels set on our addresses in the bitcoind watch-only wallet.
+    FetchBackendLabels,
+    ImportBackendLabels,
+    DismissBackendLabels,
+    BackupFileEdited(String),
+    /// Whether the labels are included in the wallet backup.
+    BackupLabelsToggled(bool),
+    ExportBackup,
+    ConfirmVerification,
+    CheckBalance,
     Save,
     Edit(usize, SettingsEditMessage),
+    DeleteWallet,
+    DeleteWalletConfirmationEdited(String),
+    DeleteWalletIgnoreBalance(bool),
+    ConfirmDeleteWallet,
 }
 
 #[derive(Debug, Clone)]
@@ -5,10 +5,13 @@ mod warning;
 pub mod coins;
 pub mod home;
 pub mod hw;
+pub mod label;
 pub mod receive;
+pub mod reconcile;
 pub mod recovery;
 pub mod settings;
 pub mod spend;
+pub mod timelock_refresh;
 
 pub use message::*;
 use warning::warn;
@@ -20,7 +23,9 @@ use iced::{
 
 use crate::ui::{
     component::{badge, button, container, separation, text::*},
-    icon::{coin_icon, cross_icon, home_icon, receive_icon, send_icon, settings_icon},
+    icon::{
+        coin_icon, cross_icon, history_icon, home_icon, receive_icon, send_icon, settings_icon,
+    },
     util::Collection,
 };
 
@@ -199,6 +204,16 @@ pub fn sidebar<'a>(menu: &Menu, cache: &'a Cache) -> widget::Container<'a, Messa
             .width(iced::Length::Units(200))
     };
 
+    let reconcile_button = if *menu == Menu::Reconcile {
+        button::primary(Some(history_icon()), "Reconcile")
+            .on_press(Message::Menu(Menu::Reconcile))
+            .width(iced::Length::Units(200))
+    } else {
+        button::transparent(Some(history_icon()), "Reconcile")
+            .on_press(Message::Menu(Menu::Reconcile))
+            .width(iced::Length::Units(200))
+    };
+
     let settings_button = if *menu == Menu::Settings {
         button::primary(Some(settings_icon()), "Settings")
             .on_press(Message::Menu(Menu::Settings))
@@ -217,6 +232,17 @@ pub fn sidebar<'a>(menu: &Menu, cache: &'a Cache) -> widget::Container<'a, Messa
                     .push(
                         Column::new()
                             .push(Container::new(text("Liana").bold()).padding(10))
+                            .push_maybe(if cache.watch_only {
+                                Some(
+                                    Container::new(
+                                        Container::new(text("  Watch-only  ").small())
+                                            .style(badge::PillStyle::Simple),
+                                    )
+                                    .padding([0, 10]),
+                                )
+                            } else {
+                                None
+                            })
                             .push(separation().width(Length::Units(200)))
                             .spacing(10),
                     )
@@ -224,6 +250,7 @@ pub fn sidebar<'a>(menu: &Menu, cache: &'a Cache) -> widget::Container<'a, Messa
                     .push(coins_button)
                     .push(spend_button)
                     .push(receive_button)
+                    .push(reconcile_button)
                     .spacing(15)
                     .height(Length::Fill),
             )
@@ -232,9 +259,12 @@ pub fn sidebar<'a>(menu: &Menu, cache: &'a Cache) -> widget::Container<'a, Messa
                     Column::new()
                         .spacing(10)
                         .push_maybe(cache.rescan_progress.map(|p| {
-                            Container::new(text(format!("  Rescan...{:.2}%  ", p * 100.0)))
-                                .padding(5)
-                                .style(badge::PillStyle::Simple)
+                            Container::new(text(format!(
+                                "  {}  ",
+                                util::rescan_progress(p, cache.rescan_samples.remaining_time())
+                            )))
+                            .padding(5)
+                            .style(badge::PillStyle::Simple)
                         }))
                         .push(settings_button),
                 )
@@ -1,36 +1,132 @@
 use iced::{
     widget::{
         qr_code::{self, QRCode},
-        Button, Column, Row,
+        Button, Column, Container, Row,
     },
-    Alignment, Element,
+    Alignment, Element, Length,
 };
 
-use liana::miniscript::bitcoin;
+use liana::miniscript::bitcoin::{self, util::bip32::Fingerprint};
 
-use crate::ui::{
-    component::{button, card, text::*},
-    icon,
+use crate::{
+    app::view::hw::hw_list_view,
+    hw::HardwareWallet,
+    ui::{
+        color,
+        component::{button, card, text::*},
+        icon,
+        util::Collection,
+    },
 };
 
 use super::message::Message;
 
-pub fn receive<'a>(address: &'a bitcoin::Address, qr: &'a qr_code::State) -> Element<'a, Message> {
-    card::simple(
-        Column::new()
-            .push(QRCode::new(qr).cell_size(10))
-            .push(
-                Row::new()
-                    .push(text(address.to_string()).small())
+pub fn receive<'a>(
+    address: &'a bitcoin::Address,
+    qr: &'a qr_code::State,
+    hws: Vec<(usize, &'a HardwareWallet)>,
+    chosen_hw: Option<usize>,
+    verified: &[Fingerprint],
+    address_reused: bool,
+    fetching_fresh_address: bool,
+) -> Element<'a, Message> {
+    Column::new()
+        .push_maybe(if address_reused {
+            Some(address_reuse_warning(fetching_fresh_address))
+        } else {
+            None
+        })
+        .push(card::simple(
+            Column::new()
+                .push(QRCode::new(qr).cell_size(10))
+                .push(
+                    Row::new()
+                        .push(text(address.to_string()).small())
+                        .push(
+                            Button::new(icon::clipboard_icon())
+                                .on_press(Message::Clipboard(address.to_string()))
+                                .style(button::Style::TransparentBorder.into()),
+                        )
+                        .align_items(Alignment::Center),
+                )
+                .align_items(Alignment::Center)
+                .spacing(20),
+        ))
+        .push_maybe(if hws.is_empty() {
+            None
+        } else {
+            Some(card::simple(
+                Column::new()
                     .push(
-                        Button::new(icon::clipboard_icon())
-                            .on_press(Message::Clipboard(address.to_string()))
-                            .style(button::Style::TransparentBorder.into()),
+                        Row::new()
+                            .push(
+                                text("Verify the address on a signing device:")
+                                    .bold()
+                                    .width(Length::Fill),
+                            )
+                            .push(if chosen_hw.is_some() {
+                                button::border(None, "Refresh")
+                            } else {
+                                button::border(None, "Refresh").on_press(Message::Reload)
+                            })
+                            .align_items(Alignment::Center),
                     )
-                    .align_items(Alignment::Center),
-            )
+                    .push(
+                        hws.into_iter()
+                            .fold(Column::new().spacing(10), |col, (i, hw)| {
+                                col.push(hw_list_view(
+                                    i,
+                                    hw,
+                                    Some(i) == chosen_hw,
+                                    chosen_hw.is_some(),
+                                    hw.fingerprint().and_then(|f| {
+                                        if verified.contains(&f) {
+                                            Some("Verified")
+                                        } else {
+                                            None
+                                        }
+                                    }),
+                                ))
+                            }),
+                    )
+                    .spacing(10),
+            ))
+        })
+        .spacing(20)
+        .into()
+}
+
+/// Warning displayed when the address was likely given out before, since an address at this
+/// index or after it already received coins. Paying the same address twice links the payments.
+fn address_reuse_warning<'a>(fetching_fresh_address: bool) -> Element<'a, Message> {
+    Container::new(
+        Row::new()
+            .spacing(20)
             .align_items(Alignment::Center)
-            .spacing(20),
+            .push(icon::warning_icon().style(color::WARNING))
+            .push(
+                text(
+                    "This address may have been given out before: an address at this index or \
+                    after it already received coins. Use a fresh address to preserve your privacy.",
+                )
+                .style(color::WARNING)
+                .width(Length::Fill),
+            )
+            .push(if fetching_fresh_address {
+                button::primary(None, "Use a fresh address").width(Length::Units(200))
+            } else {
+                button::primary(None, "Use a fresh address")
+                    .on_press(Message::FreshAddress)
+                    .width(Length::Units(200))
+            })
+            .push(
+                Button::new(icon::cross_icon())
+                    .on_press(Message::DismissAddressReuse)
+                    .style(button::Style::Transparent.into()),
+            ),
     )
+    .padding(15)
+    .width(Length::Fill)
+    .style(card::WarningCardStyle)
     .into()
 }
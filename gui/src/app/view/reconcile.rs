@@ -0,0 +1,208 @@
+use iced::{
+    widget::{Column, Container, Row},
+    Alignment, Element, Length,
+};
+
+use liana::miniscript::bitcoin::Amount;
+
+use crate::{
+    app::{
+        unit::Unit,
+        view::message::{Message, ReconcileMessage},
+    },
+    daemon::model::{ExpectedTransaction, Reconciliation},
+    ui::{
+        color,
+        component::{badge, button, card, form, separation, text::*},
+        util::Collection,
+    },
+};
+
+pub fn reconcile_view<'a>(
+    csv_file: &form::Value<String>,
+    processing: bool,
+    reconciliation: Option<&'a Reconciliation>,
+    export_file: &form::Value<String>,
+    exported: bool,
+    unit: Unit,
+) -> Element<'a, Message> {
+    Column::new()
+        .spacing(20)
+        .push(text("Reconcile").bold().size(50))
+        .push(
+            card::simple(
+                Column::new()
+                    .spacing(10)
+                    .push(
+                        text(format!(
+                            "Compare the transactions of your books with the wallet history. \
+                            The CSV file lists a transaction per line as 'txid,amount', with the \
+                            amount in {}: the amount received for a deposit, or the amount sent \
+                            for a payment. The wallet is not modified.",
+                            unit
+                        ))
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Path of the CSV file", csv_file, |path| {
+                            Message::Reconcile(ReconcileMessage::CsvFileEdited(path))
+                        })
+                        .warning("Please enter a path")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(Row::new().push(Column::new().width(Length::Fill)).push(
+                        if csv_file.valid && !csv_file.value.is_empty() && !processing {
+                            button::primary(None, "Reconcile")
+                                .on_press(Message::Reconcile(ReconcileMessage::Reconcile))
+                        } else {
+                            button::primary(None, "Reconcile")
+                        },
+                    )),
+            )
+            .width(Length::Fill),
+        )
+        .push_maybe(reconciliation.map(|r| reconciliation_view(r, unit)))
+        .push(
+            card::simple(
+                Column::new()
+                    .spacing(10)
+                    .push(text("Export history:").bold())
+                    .push(
+                        text(
+                            "Write all the transactions of the wallet to a CSV file with their \
+                            date, txid, net amount and fee in BTC, confirmation height and label. \
+                            The date of the unconfirmed transactions is left empty.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Path of the CSV file", export_file, |path| {
+                            Message::Reconcile(ReconcileMessage::ExportFileEdited(path))
+                        })
+                        .warning("Please enter a path")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Column::new().width(Length::Fill))
+                            .push_maybe(if exported {
+                                Some(text("Exported").style(color::SUCCESS))
+                            } else {
+                                None
+                            })
+                            .push(
+                                if export_file.valid && !export_file.value.is_empty() && !processing
+                                {
+                                    button::border(None, "Export")
+                                        .on_press(Message::Reconcile(ReconcileMessage::Export))
+                                } else {
+                                    button::border(None, "Export")
+                                },
+                            ),
+                    ),
+            )
+            .width(Length::Fill),
+        )
+        .into()
+}
+
+fn reconciliation_view<'a>(reconciliation: &'a Reconciliation, unit: Unit) -> Element<'a, Message> {
+    Column::new()
+        .spacing(20)
+        .push(if reconciliation.is_reconciled() {
+            text(format!(
+                "All the {} transactions match the wallet history",
+                reconciliation.matched.len()
+            ))
+            .style(color::SUCCESS)
+        } else {
+            text(format!(
+                "{} matching, {} with a different amount, {} missing from the wallet, \
+                {} not in the file",
+                reconciliation.matched.len(),
+                reconciliation.mismatched.len(),
+                reconciliation.missing.len(),
+                reconciliation.unexpected.len(),
+            ))
+            .style(color::WARNING)
+        })
+        .push_maybe(section(
+            "Different amount",
+            reconciliation
+                .mismatched
+                .iter()
+                .map(|(entry, actual)| entry_view(entry, Some(actual), unit))
+                .collect(),
+        ))
+        .push_maybe(section(
+            "Missing from the wallet",
+            reconciliation
+                .missing
+                .iter()
+                .map(|entry| entry_view(entry, None, unit))
+                .collect(),
+        ))
+        .push_maybe(section(
+            "Not in the file",
+            reconciliation
+                .unexpected
+                .iter()
+                .map(|entry| entry_view(entry, None, unit))
+                .collect(),
+        ))
+        .push_maybe(section(
+            "Matching",
+            reconciliation
+                .matched
+                .iter()
+                .map(|entry| entry_view(entry, None, unit))
+                .collect(),
+        ))
+        .into()
+}
+
+fn section<'a>(title: &'static str, rows: Vec<Row<'a, Message>>) -> Option<Container<'a, Message>> {
+    if rows.is_empty() {
+        return None;
+    }
+    let count = rows.len();
+    Some(
+        card::simple(
+            rows.into_iter().fold(
+                Column::new().spacing(10).push(
+                    Row::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(text(title).bold())
+                        .push(
+                            Container::new(text(format!("  {}  ", count)).small())
+                                .style(badge::PillStyle::Simple),
+                        ),
+                ),
+                |col, row| col.push(separation().width(Length::Fill)).push(row),
+            ),
+        )
+        .width(Length::Fill),
+    )
+}
+
+fn entry_view<'a>(
+    entry: &ExpectedTransaction,
+    actual: Option<&Amount>,
+    unit: Unit,
+) -> Row<'a, Message> {
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text(entry.txid.to_string()).small().width(Length::Fill))
+        .push(text(unit.format(&entry.amount)).small())
+        .push_maybe(actual.map(|a| {
+            text(format!("in the wallet: {}", unit.format(a)))
+                .small()
+                .style(color::WARNING)
+        }))
+}
@@ -6,7 +6,13 @@ use iced::{
 use liana::miniscript::bitcoin::Amount;
 
 use crate::{
-    app::view::message::{CreateSpendMessage, Message},
+    app::{
+        unit::Unit,
+        view::{
+            message::{CreateSpendMessage, Message},
+            util::min_feerate_view,
+        },
+    },
     ui::{
         component::{button, form, text::*},
         icon,
@@ -19,7 +25,9 @@ pub fn recovery<'a>(
     locked_coins: &(usize, Amount),
     recoverable_coins: &(usize, Amount),
     feerate: &form::Value<String>,
+    min_feerate: u64,
     address: &'a form::Value<String>,
+    unit: Unit,
 ) -> Element<'a, Message> {
     Column::new()
         .push(Space::with_height(Length::Units(100)))
@@ -35,7 +43,8 @@ pub fn recovery<'a>(
         .push(
             Container::new(Row::new().push(text(format!(
                 "{} ({} coins) will be spendable through the recovery path in the next block",
-                recoverable_coins.1, recoverable_coins.0
+                unit.format(&recoverable_coins.1),
+                recoverable_coins.0
             ))))
             .center_x(),
         )
@@ -43,7 +52,8 @@ pub fn recovery<'a>(
             Some(
                 Container::new(Row::new().push(text(format!(
                     "{} ({} coins) are not yet spendable through the recovery path",
-                    locked_coins.1, locked_coins.0
+                    unit.format(&locked_coins.1),
+                    locked_coins.0
                 ))))
                 .center_x(),
             )
@@ -78,6 +88,7 @@ pub fn recovery<'a>(
                     )
                     .width(Length::Units(250)),
                 )
+                .push(min_feerate_view(min_feerate))
                 .push(
                     if feerate.valid
                         && !feerate.value.is_empty()
@@ -1,23 +1,32 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::time::Duration;
 
+use chrono::NaiveDateTime;
 use iced::{
     alignment,
-    widget::{self, Button, Column, Container, ProgressBar, Row, Space},
+    widget::{self, Button, Checkbox, Column, Container, PickList, ProgressBar, Row, Space},
     Alignment, Element, Length,
 };
 
-use liana::miniscript::bitcoin::{util::bip32::Fingerprint, Network};
+use liana::miniscript::bitcoin::{util::bip32::Fingerprint, Amount, Network};
 
 use super::{dashboard, message::*};
 
 use crate::{
     app::{
+        address_book::AddressBook,
         cache::Cache,
         error::Error,
         menu::Menu,
-        view::{hw, warning::warn},
+        unit::Unit,
+        view::{
+            hw,
+            util::{amount, rescan_progress},
+            warning::warn,
+        },
     },
+    daemon::model::{AddressLabel, CheckBalanceResult, GetInfoAddresses},
     hw::HardwareWallet,
     ui::{
         color,
@@ -112,12 +121,67 @@ pub fn list(cache: &Cache) -> Element<Message> {
                 .width(Length::Fill)
                 .style(card::SimpleCardStyle)
             )
+            .push(
+                Container::new(
+                    Button::new(
+                        Row::new()
+                            .push(badge::Badge::new(icon::person_icon()))
+                            .push(text("Address book").bold())
+                            .padding(10)
+                            .spacing(20)
+                            .align_items(Alignment::Center)
+                            .width(Length::Fill),
+                    )
+                    .width(Length::Fill)
+                    .style(button::Style::Border.into())
+                    .on_press(Message::Settings(SettingsMessage::AddressBookSection))
+                )
+                .width(Length::Fill)
+                .style(card::SimpleCardStyle)
+            )
+            .push(
+                Container::new(
+                    Button::new(
+                        Row::new()
+                            .push(badge::Badge::new(icon::pencil_icon()))
+                            .push(text("Logging").bold())
+                            .padding(10)
+                            .spacing(20)
+                            .align_items(Alignment::Center)
+                            .width(Length::Fill),
+                    )
+                    .width(Length::Fill)
+                    .style(button::Style::Border.into())
+                    .on_press(Message::Settings(SettingsMessage::LoggingSection))
+                )
+                .width(Length::Fill)
+                .style(card::SimpleCardStyle)
+            )
+            .push(
+                Container::new(
+                    Button::new(
+                        Row::new()
+                            .push(badge::Badge::new(icon::wallet_icon()))
+                            .push(text("Switch wallet or network").bold())
+                            .padding(10)
+                            .spacing(20)
+                            .align_items(Alignment::Center)
+                            .width(Length::Fill),
+                    )
+                    .width(Length::Fill)
+                    .style(button::Style::Border.into())
+                    .on_press(Message::Settings(SettingsMessage::SwitchWallet))
+                )
+                .width(Length::Fill)
+                .style(card::SimpleCardStyle)
+            )
     )
 }
 pub fn bitcoind_settings<'a>(
     cache: &'a Cache,
     warning: Option<&Error>,
     settings: Vec<Element<'a, Message>>,
+    balance_check: Element<'a, Message>,
 ) -> Element<'a, Message> {
     dashboard(
         &Menu::Settings,
@@ -141,7 +205,8 @@ pub fn bitcoind_settings<'a>(
                             .on_press(Message::Settings(SettingsMessage::EditBitcoindSettings)),
                     ),
             )
-            .push(widget::Column::with_children(settings).spacing(20)),
+            .push(widget::Column::with_children(settings).spacing(20))
+            .push(balance_check),
     )
 }
 
@@ -149,6 +214,7 @@ pub fn about_section<'a>(
     cache: &'a Cache,
     warning: Option<&Error>,
     lianad_version: Option<&String>,
+    addresses: Option<&GetInfoAddresses>,
 ) -> Element<'a, Message> {
     dashboard(
         &Menu::Settings,
@@ -198,6 +264,279 @@ pub fn about_section<'a>(
                         ),
                 )
                 .width(Length::Fill),
+            )
+            .push_maybe(addresses.map(addresses_card)),
+    )
+}
+
+/// The derivation indexes of the addresses and how far the Bitcoin backend watches for coins, to
+/// check that a payment to an address given out long ago would be detected.
+fn addresses_card<'a>(addresses: &GetInfoAddresses) -> Element<'a, Message> {
+    let highest_index = std::cmp::max(addresses.receive_index, addresses.change_index);
+    card::simple(
+        Column::new()
+            .push(
+                Row::new()
+                    .push(badge::Badge::new(icon::receive_icon()))
+                    .push(text("Addresses").bold())
+                    .padding(10)
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .width(Length::Fill),
+            )
+            .push(separation().width(Length::Fill))
+            .push(Space::with_height(Length::Units(10)))
+            .push(
+                Column::new()
+                    .spacing(5)
+                    .push(
+                        Row::new()
+                            .push(text("Gap limit:").bold().width(Length::Fill))
+                            .push(text(addresses.gap_limit.to_string())),
+                    )
+                    .push(
+                        Row::new()
+                            .push(text("Highest derivation index:").bold().width(Length::Fill))
+                            .push(text(highest_index.to_string())),
+                    )
+                    .push(
+                        Row::new()
+                            .push(text("Watched up to index:").bold().width(Length::Fill))
+                            .push(text(match addresses.watched_range_end {
+                                Some(range_end) => range_end.to_string(),
+                                None => "Gap limit past the last used address".to_string(),
+                            })),
+                    )
+                    .push_maybe(
+                        addresses
+                            .watched_range_end
+                            .filter(|range_end| {
+                                highest_index.saturating_add(addresses.gap_limit) > *range_end
+                            })
+                            .map(|_| {
+                                text("The watched range will be extended at the next poll.").small()
+                            }),
+                    ),
+            ),
+    )
+    .width(Length::Fill)
+    .into()
+}
+
+pub fn address_book_section<'a>(
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    address_book: &'a AddressBook,
+    editing: Option<usize>,
+    name: &form::Value<String>,
+    address: &form::Value<String>,
+    processing: bool,
+) -> Element<'a, Message> {
+    dashboard(
+        &Menu::Settings,
+        cache,
+        warning,
+        Column::new()
+            .spacing(20)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Button::new(text("Settings").size(30).bold())
+                            .style(button::Style::Transparent.into())
+                            .on_press(Message::Menu(Menu::Settings)),
+                    )
+                    .push(icon::chevron_right().size(30))
+                    .push(
+                        Button::new(text("Address book").size(30).bold())
+                            .style(button::Style::Transparent.into())
+                            .on_press(Message::Settings(SettingsMessage::AddressBookSection)),
+                    ),
+            )
+            .push(
+                card::simple(if address_book.contacts().is_empty() {
+                    Column::new().push(text("No recipient saved yet."))
+                } else {
+                    address_book.contacts().iter().enumerate().fold(
+                        Column::new().spacing(10),
+                        |col, (i, contact)| {
+                            col.push(
+                                Row::new()
+                                    .spacing(10)
+                                    .align_items(Alignment::Center)
+                                    .push(
+                                        Column::new()
+                                            .width(Length::Fill)
+                                            .push(text(&contact.name).bold())
+                                            .push(text(&contact.address).small())
+                                            .push_maybe(if contact.is_valid_for(cache.network) {
+                                                None
+                                            } else {
+                                                Some(
+                                                    text(format!(
+                                                        "Not an address of the {} network, it \
+                                                            is not offered when spending.",
+                                                        cache.network
+                                                    ))
+                                                    .small()
+                                                    .style(color::ALERT),
+                                                )
+                                            }),
+                                    )
+                                    .push(
+                                        button::transparent(Some(icon::pencil_icon()), "")
+                                            .on_press(Message::Settings(
+                                                SettingsMessage::EditContact(i),
+                                            )),
+                                    )
+                                    .push(if processing {
+                                        button::transparent(Some(icon::trash_icon()), "")
+                                    } else {
+                                        button::transparent(Some(icon::trash_icon()), "").on_press(
+                                            Message::Settings(SettingsMessage::DeleteContact(i)),
+                                        )
+                                    }),
+                            )
+                        },
+                    )
+                })
+                .width(Length::Fill),
+            )
+            .push(
+                card::simple(
+                    Column::new()
+                        .spacing(10)
+                        .push(
+                            text(if editing.is_some() {
+                                "Edit the recipient:"
+                            } else {
+                                "Save a recipient:"
+                            })
+                            .bold(),
+                        )
+                        .push(
+                            form::Form::new("Name", name, |msg| {
+                                Message::Settings(SettingsMessage::ContactNameEdited(msg))
+                            })
+                            .warning("Please enter a name")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .push(
+                            form::Form::new("Address", address, |msg| {
+                                Message::Settings(SettingsMessage::ContactAddressEdited(msg))
+                            })
+                            .warning("Invalid address (maybe it is for another network?)")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .push(
+                            Row::new()
+                                .spacing(10)
+                                .push(Space::with_width(Length::Fill))
+                                .push_maybe(editing.map(|_| {
+                                    button::transparent(None, " Cancel ")
+                                        .on_press(Message::Settings(
+                                            SettingsMessage::CancelContactEdit,
+                                        ))
+                                        .padding(5)
+                                }))
+                                .push(
+                                    if !processing
+                                        && name.valid
+                                        && !name.value.trim().is_empty()
+                                        && address.valid
+                                        && !address.value.is_empty()
+                                    {
+                                        button::primary(None, " Save ")
+                                            .on_press(Message::Settings(
+                                                SettingsMessage::SaveContact,
+                                            ))
+                                            .padding(5)
+                                    } else {
+                                        button::primary(None, " Save ").padding(5)
+                                    },
+                                ),
+                        ),
+                )
+                .width(Length::Fill),
+            ),
+    )
+}
+
+pub fn logging_section<'a>(
+    cache: &'a Cache,
+    log_filter: &form::Value<String>,
+    applied: bool,
+) -> Element<'a, Message> {
+    dashboard(
+        &Menu::Settings,
+        cache,
+        None,
+        Column::new()
+            .spacing(20)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Button::new(text("Settings").size(30).bold())
+                            .style(button::Style::Transparent.into())
+                            .on_press(Message::Menu(Menu::Settings)),
+                    )
+                    .push(icon::chevron_right().size(30))
+                    .push(
+                        Button::new(text("Logging").size(30).bold())
+                            .style(button::Style::Transparent.into())
+                            .on_press(Message::Settings(SettingsMessage::LoggingSection)),
+                    ),
+            )
+            .push(
+                card::simple(
+                    Column::new()
+                        .spacing(10)
+                        .push(text("Log levels:").bold())
+                        .push(
+                            text(
+                                "Comma-separated levels (error, warn, info, debug or trace) of \
+                                the logged events. A level alone applies to all the events, \
+                                a module=level directive to the events of a module, for instance \
+                                info,liana::bitcoin::poller=trace. The change lasts until the \
+                                application is closed: set log_filter in gui.toml to keep it.",
+                            )
+                            .small(),
+                        )
+                        .push(
+                            form::Form::new("Directives", log_filter, |msg| {
+                                Message::Settings(SettingsMessage::LogFilterEdited(msg))
+                            })
+                            .warning("Please enter valid directives")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .push(
+                            Row::new()
+                                .spacing(10)
+                                .align_items(Alignment::Center)
+                                .push_maybe(if applied {
+                                    Some(text("Applied").style(color::SUCCESS))
+                                } else {
+                                    None
+                                })
+                                .push(Space::with_width(Length::Fill))
+                                .push(if log_filter.valid && !applied {
+                                    button::primary(None, "Apply")
+                                        .on_press(Message::Settings(
+                                            SettingsMessage::ApplyLogFilter,
+                                        ))
+                                        .padding(5)
+                                } else {
+                                    button::primary(None, "Apply").padding(5)
+                                }),
+                        ),
+                )
+                .width(Length::Fill),
             ),
     )
 }
@@ -417,7 +756,10 @@ pub fn rescan<'a>(
     year: &form::Value<String>,
     month: &form::Value<String>,
     day: &form::Value<String>,
+    height: &form::Value<String>,
+    tip_height: i32,
     scan_progress: Option<f64>,
+    scan_remaining: Option<Duration>,
     success: bool,
     processing: bool,
     can_edit: bool,
@@ -443,7 +785,7 @@ pub fn rescan<'a>(
                     Column::new()
                         .width(Length::Fill)
                         .push(ProgressBar::new(0.0..=1.0, p as f32).width(Length::Fill))
-                        .push(text(format!("Rescan...{:.2}%", p * 100.0))),
+                        .push(text(rescan_progress(p, scan_remaining))),
                 )
             } else {
                 Container::new(
@@ -478,14 +820,33 @@ pub fn rescan<'a>(
                                 .align_items(Alignment::Center)
                                 .spacing(10),
                         )
+                        .push(
+                            Row::new()
+                                .push(text("Or from block height:").bold().small())
+                                .push(
+                                    form::Form::new(&tip_height.to_string(), height, |value| {
+                                        SettingsEditMessage::FieldEdited("rescan_height", value)
+                                    })
+                                    .warning("Block height must be at most the current one")
+                                    .size(20)
+                                    .padding(5),
+                                )
+                                .align_items(Alignment::Center)
+                                .spacing(10),
+                        )
                         .push(
                             if can_edit
                                 && !processing
-                                && (is_ok_and(&u32::from_str(&year.value), |&v| v > 0)
+                                && ((is_ok_and(&u32::from_str(&year.value), |&v| v > 0)
                                     && is_ok_and(&u32::from_str(&month.value), |&v| {
                                         v > 0 && v <= 12
                                     })
-                                    && is_ok_and(&u32::from_str(&day.value), |&v| v > 0 && v <= 31))
+                                    && is_ok_and(&u32::from_str(&day.value), |&v| {
+                                        v > 0 && v <= 31
+                                    }))
+                                    || is_ok_and(&i32::from_str(&height.value), |&v| {
+                                        v > 0 && v <= tip_height
+                                    }))
                             {
                                 Row::new().push(Column::new().width(Length::Fill)).push(
                                     button::primary(None, "Start rescan")
@@ -511,6 +872,90 @@ pub fn rescan<'a>(
     .into()
 }
 
+/// Advanced diagnostic comparing the balance tracked by Liana with the coins of the wallet
+/// descriptor found in the UTxO set of bitcoind.
+pub fn balance_check<'a>(
+    result: Option<&CheckBalanceResult>,
+    processing: bool,
+    unit: Unit,
+) -> Element<'a, Message> {
+    card::simple(Container::new(
+        Column::new()
+            .push(
+                Row::new()
+                    .push(badge::Badge::new(icon::coin_icon()))
+                    .push(
+                        text("Check balance against the UTxO set")
+                            .bold()
+                            .width(Length::Fill),
+                    )
+                    .push_maybe(result.map(|res| {
+                        if res.untracked.is_empty() && res.missing.is_empty() {
+                            text("Balances match").style(color::SUCCESS)
+                        } else {
+                            text("Discrepancies found").style(color::ALERT)
+                        }
+                    }))
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .width(Length::Fill),
+            )
+            .push(separation().width(Length::Fill))
+            .push(
+                text(
+                    "Scan the UTxO set of the node for the coins of the wallet descriptors, \
+                    without importing them or rescanning. This does not modify the wallet \
+                    and may take a few minutes.",
+                )
+                .small(),
+            )
+            .push_maybe(result.map(|res| {
+                Column::new()
+                    .spacing(5)
+                    .push(
+                        Row::new()
+                            .spacing(5)
+                            .push(text("Tracked by Liana:").bold().small())
+                            .push(amount(&res.tracked_balance, unit)),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(5)
+                            .push(text("Found in the UTxO set:").bold().small())
+                            .push(amount(&res.utxo_set_balance, unit)),
+                    )
+                    .push(res.untracked.iter().fold(Column::new(), |col, op| {
+                        col.push(
+                            text(format!("Not tracked by Liana: {}", op))
+                                .small()
+                                .style(color::ALERT),
+                        )
+                    }))
+                    .push(res.missing.iter().fold(Column::new(), |col, op| {
+                        col.push(
+                            text(format!("Missing from the UTxO set: {}", op))
+                                .small()
+                                .style(color::ALERT),
+                        )
+                    }))
+            }))
+            .push(
+                Row::new()
+                    .push(Column::new().width(Length::Fill))
+                    .push(if processing {
+                        button::primary(None, "Checking...").width(Length::Shrink)
+                    } else {
+                        button::primary(None, "Check balance")
+                            .on_press(Message::Settings(SettingsMessage::CheckBalance))
+                            .width(Length::Shrink)
+                    }),
+            )
+            .spacing(20),
+    ))
+    .width(Length::Fill)
+    .into()
+}
+
 fn is_ok_and<T, E>(res: &Result<T, E>, f: impl FnOnce(&T) -> bool) -> bool {
     if let Ok(v) = res {
         f(v)
@@ -519,11 +964,109 @@ fn is_ok_and<T, E>(res: &Result<T, E>, f: impl FnOnce(&T) -> bool) -> bool {
     }
 }
 
+/// The short code of the descriptor, to compare with the one of another installation of the
+/// wallet.
+fn verification_code_view<'a>(code: &str) -> Row<'a, Message> {
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Verification code:").bold())
+        .push(text(code.to_owned()).size(25).bold())
+}
+
+/// One-time offer to import the labels set on the addresses of the wallet directly in the
+/// bitcoind watch-only wallet, with a preview of the labels once they were fetched.
+fn backend_labels_import(
+    backend_labels: Option<&[AddressLabel]>,
+    processing: bool,
+) -> Element<Message> {
+    let col = Column::new()
+        .spacing(10)
+        .push(separation().width(Length::Fill));
+    match backend_labels {
+        None => col.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(
+                    text(
+                        "If you labeled the addresses of this wallet in the bitcoind \
+                        watch-only wallet, those labels can be imported once.",
+                    )
+                    .small()
+                    .width(Length::Fill),
+                )
+                .push(if processing {
+                    button::border(None, "Look for labels")
+                } else {
+                    button::border(None, "Look for labels")
+                        .on_press(Message::Settings(SettingsMessage::FetchBackendLabels))
+                }),
+        ),
+        Some(labels) => col
+            .push(if labels.is_empty() {
+                text("No label was found in the bitcoind watch-only wallet.").small()
+            } else {
+                text(format!(
+                    "{} label(s) found in the bitcoind watch-only wallet. The addresses \
+                    already labeled in Liana keep their label.",
+                    labels.len()
+                ))
+                .small()
+            })
+            .push(labels.iter().fold(Column::new().spacing(5), |col, entry| {
+                col.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(text(&entry.label).small().bold().width(Length::Fill))
+                        .push(text(entry.address.to_string()).small()),
+                )
+            }))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push(if processing {
+                        button::border(None, "Dismiss")
+                    } else {
+                        button::border(None, "Dismiss")
+                            .on_press(Message::Settings(SettingsMessage::DismissBackendLabels))
+                    })
+                    .push(if processing || labels.is_empty() {
+                        button::primary(None, "Import")
+                    } else {
+                        button::primary(None, "Import")
+                            .on_press(Message::Settings(SettingsMessage::ImportBackendLabels))
+                    }),
+            ),
+    }
+    .into()
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn wallet_settings<'a>(
     cache: &'a Cache,
     warning: Option<&Error>,
     descriptor: &'a str,
+    verification_code: &'a str,
     keys_aliases: &[(Fingerprint, form::Value<String>)],
+    displayed_history: &form::Value<String>,
+    display_unit: Unit,
+    fiat_currency: &form::Value<String>,
+    verification_reminder: &form::Value<String>,
+    last_verification: Option<u32>,
+    consolidation_max_feerate: &form::Value<String>,
+    consolidation_future_feerate: &form::Value<String>,
+    watch_only: bool,
+    annotations_file: &form::Value<String>,
+    annotations_exported: bool,
+    labels_file: &form::Value<String>,
+    labels_exported: bool,
+    backend_labels_offered: bool,
+    backend_labels: Option<&'a [AddressLabel]>,
+    backup_file: &form::Value<String>,
+    backup_labels: bool,
+    backup_exported: bool,
     processing: bool,
     updated: bool,
 ) -> Element<'a, Message> {
@@ -553,6 +1096,7 @@ pub fn wallet_settings<'a>(
                 Column::new()
                     .push(text("Wallet descriptor:").bold())
                     .push(text(descriptor.to_owned()).small())
+                    .push(verification_code_view(verification_code))
                     .push(
                         Row::new()
                             .spacing(10)
@@ -561,6 +1105,12 @@ pub fn wallet_settings<'a>(
                                 button::border(Some(icon::clipboard_icon()), "Copy")
                                     .on_press(Message::Clipboard(descriptor.to_owned())),
                             )
+                            .push(
+                                button::border(Some(icon::chip_icon()), "Test hardware devices")
+                                    .on_press(Message::Settings(
+                                        SettingsMessage::TestHardwareWallets,
+                                    )),
+                            )
                             .push(
                                 button::primary(
                                     Some(icon::chip_icon()),
@@ -596,6 +1146,116 @@ pub fn wallet_settings<'a>(
                             )
                         },
                     ))
+                    .push(text("Displayed transaction history:").bold())
+                    .push(
+                        text(
+                            "Number of months of spent coins displayed in detail, \
+                            older ones are only displayed as a summary. The wallet keeps them all \
+                            regardless. Leave empty to display everything.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Months", displayed_history, |msg| {
+                            Message::Settings(SettingsMessage::DisplayedHistoryEdited(msg))
+                        })
+                        .warning("Please enter a number of months")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(text("Display unit:").bold().width(Length::Fill))
+                            .push(
+                                PickList::new(&Unit::ALL[..], Some(display_unit), |unit| {
+                                    Message::Settings(SettingsMessage::DisplayUnitEdited(unit))
+                                })
+                                .padding(5),
+                            ),
+                    )
+                    .push(text("Fiat currency:").bold())
+                    .push(
+                        text(
+                            "Currency the balance and the transaction amounts are also \
+                            displayed in, for instance USD. It requires a price source in the \
+                            configuration file. Leave empty to only display bitcoin amounts.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Currency", fiat_currency, |msg| {
+                            Message::Settings(SettingsMessage::FiatCurrencyEdited(msg))
+                        })
+                        .warning("Please enter a currency code")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(text("Verification reminder:").bold())
+                    .push(
+                        text(
+                            "Number of months after which you are reminded to test your \
+                            hardware devices and check your backups. Leave empty for no reminder.",
+                        )
+                        .small(),
+                    )
+                    .push_maybe(last_verification.map(|t| {
+                        text(format!(
+                            "Last verified: {}",
+                            NaiveDateTime::from_timestamp(t as i64, 0)
+                        ))
+                        .small()
+                    }))
+                    .push(
+                        form::Form::new("Months", verification_reminder, |msg| {
+                            Message::Settings(SettingsMessage::VerificationReminderEdited(msg))
+                        })
+                        .warning("Please enter a number of months")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(text("Consolidation suggestion:").bold())
+                    .push(
+                        text(
+                            "Suggest on the overview to consolidate the small coins when the \
+                            feerate is at most the given one, with the savings estimated against \
+                            the feerate you expect to pay later. Leave empty for no suggestion.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(
+                                form::Form::new(
+                                    "Maximum feerate (sats/vbyte)",
+                                    consolidation_max_feerate,
+                                    |msg| {
+                                        Message::Settings(
+                                            SettingsMessage::ConsolidationMaxFeerateEdited(msg),
+                                        )
+                                    },
+                                )
+                                .warning("Please enter a feerate")
+                                .size(20)
+                                .padding(10),
+                            )
+                            .push(
+                                form::Form::new(
+                                    "Future feerate (sats/vbyte)",
+                                    consolidation_future_feerate,
+                                    |msg| {
+                                        Message::Settings(
+                                            SettingsMessage::ConsolidationFutureFeerateEdited(msg),
+                                        )
+                                    },
+                                )
+                                .warning("Please enter a feerate")
+                                .size(20)
+                                .padding(10),
+                            ),
+                    )
                     .push(
                         Row::new()
                             .align_items(Alignment::Center)
@@ -618,10 +1278,281 @@ pub fn wallet_settings<'a>(
                             }),
                     )
                     .spacing(10),
+            ))
+            .push(card::simple(
+                Column::new()
+                    .spacing(10)
+                    .push(text("Monitoring:").bold())
+                    .push(
+                        text(
+                            "A watch-only wallet holds no key: it only shows the coins and \
+                            transactions, and signing is done on another instance of the wallet. \
+                            The names of the wallet and of its keys, along with the labels, can \
+                            be exported to a file and imported on another instance, the file does \
+                            not contain any secret.",
+                        )
+                        .small(),
+                    )
+                    .push(Checkbox::new("Watch-only", watch_only, |checked| {
+                        Message::Settings(SettingsMessage::WatchOnlyToggled(checked))
+                    }))
+                    .push(
+                        form::Form::new("Path of the names file", annotations_file, |path| {
+                            Message::Settings(SettingsMessage::AnnotationsFileEdited(path))
+                        })
+                        .warning("Please enter a path")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Space::with_width(Length::Fill))
+                            .push_maybe(if annotations_exported {
+                                Some(text("Exported").style(color::SUCCESS))
+                            } else {
+                                None
+                            })
+                            .push(
+                                if annotations_file.valid && !annotations_file.value.is_empty() {
+                                    button::border(None, "Export names").on_press(
+                                        Message::Settings(SettingsMessage::ExportAnnotations),
+                                    )
+                                } else {
+                                    button::border(None, "Export names")
+                                },
+                            )
+                            .push(
+                                if annotations_file.valid
+                                    && !annotations_file.value.is_empty()
+                                    && !processing
+                                {
+                                    button::primary(None, "Import names").on_press(
+                                        Message::Settings(SettingsMessage::ImportAnnotations),
+                                    )
+                                } else {
+                                    button::primary(None, "Import names")
+                                },
+                            ),
+                    ),
+            ))
+            .push(card::simple(
+                Column::new()
+                    .spacing(10)
+                    .push(text("Labels:").bold())
+                    .push(
+                        text(
+                            "The labels of the transactions, addresses and coins can be \
+                            exported to a file in the BIP329 format, and imported from another \
+                            wallet supporting it.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Path of the labels file", labels_file, |path| {
+                            Message::Settings(SettingsMessage::LabelsFileEdited(path))
+                        })
+                        .warning("Please enter a path")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Space::with_width(Length::Fill))
+                            .push_maybe(if labels_exported {
+                                Some(text("Exported").style(color::SUCCESS))
+                            } else {
+                                None
+                            })
+                            .push(if labels_file.valid && !labels_file.value.is_empty() {
+                                button::border(None, "Export labels")
+                                    .on_press(Message::Settings(SettingsMessage::ExportLabels))
+                            } else {
+                                button::border(None, "Export labels")
+                            })
+                            .push(
+                                if labels_file.valid && !labels_file.value.is_empty() && !processing
+                                {
+                                    button::primary(None, "Import labels")
+                                        .on_press(Message::Settings(SettingsMessage::ImportLabels))
+                                } else {
+                                    button::primary(None, "Import labels")
+                                },
+                            ),
+                    )
+                    .push_maybe(if backend_labels_offered {
+                        None
+                    } else {
+                        Some(backend_labels_import(backend_labels, processing))
+                    }),
+            ))
+            .push(card::simple(
+                Column::new()
+                    .spacing(10)
+                    .push(text("Backup:").bold())
+                    .push(
+                        text(
+                            "The descriptor, the names of the keys, the registered signing \
+                            devices and optionally the labels can be backed up to a single file, \
+                            to restore the wallet with the installer. The file does not contain \
+                            any mnemonic: the one of a hot signer must be backed up separately.",
+                        )
+                        .small(),
+                    )
+                    .push(
+                        form::Form::new("Path of the backup file", backup_file, |path| {
+                            Message::Settings(SettingsMessage::BackupFileEdited(path))
+                        })
+                        .warning("Please enter a path")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Checkbox::new(
+                                "Include the labels",
+                                backup_labels,
+                                |checked| {
+                                    Message::Settings(SettingsMessage::BackupLabelsToggled(checked))
+                                },
+                            ))
+                            .push(Space::with_width(Length::Fill))
+                            .push_maybe(if backup_exported {
+                                Some(text("Exported").style(color::SUCCESS))
+                            } else {
+                                None
+                            })
+                            .push(if backup_file.valid && !backup_file.value.is_empty() {
+                                button::primary(None, "Backup wallet")
+                                    .on_press(Message::Settings(SettingsMessage::ExportBackup))
+                            } else {
+                                button::primary(None, "Backup wallet")
+                            }),
+                    ),
+            ))
+            .push(card::simple(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .width(Length::Fill)
+                            .push(text("Delete wallet:").bold())
+                            .push(
+                                text("Remove all the data of this wallet from this computer.")
+                                    .small(),
+                            ),
+                    )
+                    .push(
+                        button::alert(Some(icon::trash_icon()), "Delete wallet")
+                            .on_press(Message::Settings(SettingsMessage::DeleteWallet)),
+                    ),
             )),
     )
 }
 
+pub fn delete_wallet_modal<'a>(
+    wallet_name: &'a str,
+    confirmation: &form::Value<String>,
+    balance: Amount,
+    ignore_balance: bool,
+    daemon_is_external: bool,
+    can_delete: bool,
+    unit: Unit,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .push(text("Delete wallet").bold())
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(icon::warning_icon().style(color::WARNING))
+                    .push(
+                        text(
+                            "Make sure you have a backup of the wallet descriptor and of the \
+                            mnemonics of all the keys before going further. Without them the \
+                            funds of this wallet cannot be recovered. Any mnemonic stored by \
+                            Liana for this wallet will be deleted as well.",
+                        )
+                        .style(color::WARNING),
+                    ),
+            )
+            .push_maybe(if daemon_is_external {
+                Some(
+                    text(
+                        "This wallet is managed by an external daemon, its data cannot be \
+                        deleted from here.",
+                    )
+                    .style(color::ALERT),
+                )
+            } else {
+                None
+            })
+            .push_maybe(if balance.to_sat() > 0 {
+                Some(
+                    Column::new()
+                        .spacing(10)
+                        .push(
+                            Row::new()
+                                .spacing(5)
+                                .align_items(Alignment::Center)
+                                .push(
+                                    text("This wallet still has a balance of").style(color::ALERT),
+                                )
+                                .push(amount(&balance, unit)),
+                        )
+                        .push(Checkbox::new(
+                            "Delete the wallet anyway",
+                            ignore_balance,
+                            |checked| {
+                                Message::Settings(SettingsMessage::DeleteWalletIgnoreBalance(
+                                    checked,
+                                ))
+                            },
+                        )),
+                )
+            } else {
+                None
+            })
+            .push(
+                text(format!(
+                    "Type the wallet name '{}' to confirm:",
+                    wallet_name
+                ))
+                .small(),
+            )
+            .push(
+                form::Form::new(wallet_name, confirmation, |msg| {
+                    Message::Settings(SettingsMessage::DeleteWalletConfirmationEdited(msg))
+                })
+                .warning("The name does not match the wallet name")
+                .size(20)
+                .padding(10),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push(button::border(None, "Cancel").on_press(Message::Close))
+                    .push(if can_delete {
+                        button::alert(Some(icon::trash_icon()), "Delete")
+                            .on_press(Message::Settings(SettingsMessage::ConfirmDeleteWallet))
+                    } else {
+                        button::alert(Some(icon::trash_icon()), "Delete")
+                    }),
+            ),
+    )
+    .width(Length::Units(600))
+    .into()
+}
+
 pub fn register_wallet_modal<'a>(
     warning: Option<&Error>,
     hws: &'a [HardwareWallet],
@@ -669,3 +1600,115 @@ pub fn register_wallet_modal<'a>(
         .width(Length::Units(500))
         .into()
 }
+
+/// Read-only report of the detected hardware wallets and of the errors encountered while
+/// connecting to them.
+pub fn hardware_wallets_test_modal<'a>(
+    hws: &'a [HardwareWallet],
+    errors: &'a [String],
+    processing: bool,
+    descriptor_keys: HashSet<Fingerprint>,
+    keys_aliases: &'a HashMap<Fingerprint, String>,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .push(
+                Row::new()
+                    .push(text("Detected devices:").bold().width(Length::Fill))
+                    .push(if processing {
+                        button::border(None, "Refresh")
+                    } else {
+                        button::border(None, "Refresh").on_press(Message::Reload)
+                    })
+                    .align_items(Alignment::Center),
+            )
+            .push_maybe(if processing {
+                Some(text("Looking for devices...").small())
+            } else if hws.is_empty() {
+                Some(text("No device detected. Make sure it is plugged and unlocked.").small())
+            } else {
+                None
+            })
+            .push(hws.iter().fold(Column::new().spacing(10), |col, hw| {
+                col.push(card::simple(
+                    Column::new()
+                        .spacing(5)
+                        .push(text(format!("{}", hw.kind())).bold())
+                        .push(match hw {
+                            HardwareWallet::Supported {
+                                fingerprint,
+                                version,
+                                ..
+                            } => Column::new()
+                                .spacing(5)
+                                .push(text(format!("fingerprint: {}", fingerprint)).small())
+                                .push(
+                                    text(format!(
+                                        "version: {}",
+                                        version
+                                            .as_ref()
+                                            .map(|v| v.to_string())
+                                            .unwrap_or_else(|| "unknown".to_string())
+                                    ))
+                                    .small(),
+                                )
+                                .push(if descriptor_keys.contains(fingerprint) {
+                                    text(match keys_aliases.get(fingerprint) {
+                                        Some(alias) => {
+                                            format!("Matches the key '{}' of the wallet", alias)
+                                        }
+                                        None => "Matches a key of the wallet".to_string(),
+                                    })
+                                    .small()
+                                    .style(color::SUCCESS)
+                                } else {
+                                    text("Does not match any key of the wallet")
+                                        .small()
+                                        .style(color::WARNING)
+                                }),
+                            HardwareWallet::Unsupported {
+                                version, message, ..
+                            } => Column::new()
+                                .spacing(5)
+                                .push_maybe(
+                                    version
+                                        .as_ref()
+                                        .map(|v| text(format!("version: {}", v)).small()),
+                                )
+                                .push(text(message).small().style(color::WARNING)),
+                        }),
+                ))
+            }))
+            .push_maybe(if errors.is_empty() {
+                None
+            } else {
+                Some(
+                    errors.iter().fold(
+                        Column::new()
+                            .spacing(5)
+                            .push(text("Connection errors:").bold()),
+                        |col, e| col.push(text(e).small().style(color::ALERT)),
+                    ),
+                )
+            })
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        text("Devices work and backups are intact?")
+                            .small()
+                            .width(Length::Fill),
+                    )
+                    .push(if processing {
+                        button::primary(None, "Mark as verified")
+                    } else {
+                        button::primary(None, "Mark as verified")
+                            .on_press(Message::Settings(SettingsMessage::ConfirmVerification))
+                    }),
+            ),
+    )
+    .width(Length::Units(500))
+    .into()
+}
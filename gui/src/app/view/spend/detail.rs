@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
 use iced::{
-    widget::{scrollable, tooltip, Button, Column, Container, Row, Scrollable, Space},
+    widget::{
+        qr_code::{self, QRCode},
+        scrollable, tooltip, Button, Column, Container, PickList, Row, Scrollable, Space,
+    },
     Alignment, Element, Length,
 };
 
@@ -9,16 +12,18 @@ use liana::{
     descriptors::{LianaDescInfo, PathInfo, PathSpendInfo},
     miniscript::bitcoin::{
         util::bip32::{DerivationPath, Fingerprint},
-        Address, Amount, Network, Transaction,
+        Address, Amount, EcdsaSighashType, Network, Transaction,
     },
 };
 
 use crate::{
     app::{
         error::Error,
+        price::Price,
+        unit::Unit,
         view::{hw::hw_list_view, message::*, util::*, warning::warn},
     },
-    daemon::model::{Coin, SpendStatus, SpendTx},
+    daemon::model::{Coin, SpendPath, SpendStatus, SpendSummary, SpendTx, SpentInput},
     hw::HardwareWallet,
     ui::{
         color,
@@ -33,12 +38,16 @@ use crate::{
     },
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn spend_view<'a>(
     tx: &'a SpendTx,
     saved: bool,
     desc_info: &'a LianaDescInfo,
     key_aliases: &'a HashMap<Fingerprint, String>,
     network: Network,
+    unit: Unit,
+    price: Option<&Price>,
+    watch_only: bool,
 ) -> Element<'a, Message> {
     spend_modal(
         saved,
@@ -46,14 +55,15 @@ pub fn spend_view<'a>(
         Column::new()
             .align_items(Alignment::Center)
             .spacing(20)
-            .push(spend_header(tx))
-            .push(spend_overview_view(tx, desc_info, key_aliases))
+            .push(spend_header(tx, unit, price))
+            .push(spend_overview_view(tx, desc_info, key_aliases, watch_only))
             .push(inputs_and_outputs_view(
                 &tx.coins,
                 &tx.psbt.unsigned_tx,
                 network,
                 Some(tx.change_indexes.clone()),
-                None,
+                Some(tx.receive_indexes.clone()),
+                unit,
             )),
     )
 }
@@ -109,6 +119,136 @@ pub fn broadcast_action<'a>(warning: Option<&Error>, saved: bool) -> Element<'a,
     }
 }
 
+pub fn bump_fee_action<'a>(
+    warning: Option<&Error>,
+    feerate: &form::Value<String>,
+    processing: bool,
+    bumped: bool,
+) -> Element<'a, Message> {
+    if bumped {
+        card::simple(text(
+            "The replacement transaction is saved. Sign and broadcast it to bump the fee.",
+        ))
+        .width(Length::Units(400))
+        .align_x(iced::alignment::Horizontal::Center)
+        .into()
+    } else {
+        card::simple(
+            Column::new()
+                .spacing(10)
+                .push_maybe(warning.map(|w| warn(Some(w))))
+                .push(text("Bump the fee of the transaction").bold())
+                .push(
+                    text(
+                        "The replacement spends the same coins to the same recipients. \
+                        The additional fee is deducted from the change output.",
+                    )
+                    .small(),
+                )
+                .push(
+                    form::Form::new("Feerate (sat/vbyte)", feerate, |msg| {
+                        Message::Spend(SpendTxMessage::BumpFeeFeerateEdited(msg))
+                    })
+                    .warning("Invalid feerate")
+                    .size(20)
+                    .padding(10),
+                )
+                .push(Row::new().push(Column::new().width(Length::Fill)).push(
+                    if feerate.valid && !feerate.value.is_empty() && !processing {
+                        button::primary(None, "Bump fee")
+                            .on_press(Message::Spend(SpendTxMessage::Confirm))
+                    } else {
+                        button::primary(None, "Bump fee")
+                    },
+                )),
+        )
+        .width(Length::Units(400))
+        .into()
+    }
+}
+
+pub fn export_action<'a>(
+    warning: Option<&Error>,
+    psbt: String,
+    qr_code: Option<&'a qr_code::State>,
+    // Number of fragments of the PSBT if the QR code is animated.
+    fragment_count: Option<usize>,
+    psbt_file: &form::Value<String>,
+    exported: bool,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(20)
+            .push_maybe(warning.map(|w| warn(Some(w))))
+            .push(text("Export the PSBT").bold())
+            .push(
+                text(
+                    "The PSBT contains the signatures the transaction already has. \
+                    Give it to the signing device or the cosigner, then update the \
+                    transaction with the signed PSBT.",
+                )
+                .small(),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(text("Base64:").bold().width(Length::Fill))
+                    .push(
+                        button::border(Some(icon::clipboard_icon()), "Copy")
+                            .on_press(Message::Clipboard(psbt)),
+                    ),
+            )
+            .push(if let Some(qr_code) = qr_code {
+                Container::new(
+                    Column::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(QRCode::new(qr_code).cell_size(5))
+                        .push_maybe(fragment_count.map(|count| {
+                            text(format!(
+                                "Animated QR code of {} frames, keep scanning until the \
+                                signing device has all of them",
+                                count
+                            ))
+                            .small()
+                        })),
+                )
+                .width(Length::Fill)
+                .center_x()
+            } else {
+                Container::new(text("The PSBT is too large to be displayed as a QR code").small())
+            })
+            .push(
+                form::Form::new("Path of the PSBT file", psbt_file, |path| {
+                    Message::Spend(SpendTxMessage::PsbtFileEdited(path))
+                })
+                .warning("Please enter a path")
+                .size(20)
+                .padding(10),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push_maybe(if exported {
+                        Some(text("PSBT exported").style(color::SUCCESS))
+                    } else {
+                        None
+                    })
+                    .push(Column::new().width(Length::Fill))
+                    .push(if psbt_file.valid && !psbt_file.value.is_empty() {
+                        button::primary(None, "Export to file")
+                            .on_press(Message::Spend(SpendTxMessage::ExportPsbtFile))
+                    } else {
+                        button::primary(None, "Export to file")
+                    }),
+            ),
+    )
+    .width(Length::Units(600))
+    .into()
+}
+
 pub fn delete_action<'a>(warning: Option<&Error>, deleted: bool) -> Element<'a, Message> {
     if deleted {
         card::simple(
@@ -194,17 +334,16 @@ pub fn spend_modal<'a, T: Into<Element<'a, Message>>>(
         .into()
 }
 
-fn spend_header<'a>(tx: &SpendTx) -> Element<'a, Message> {
+fn spend_header<'a>(tx: &SpendTx, unit: Unit, price: Option<&Price>) -> Element<'a, Message> {
     Column::new()
         .spacing(20)
         .align_items(Alignment::Center)
         .push(
             Row::new()
                 .push(badge::Badge::new(icon::send_icon()).style(badge::Style::Standard))
-                .push(if tx.sigs.recovery_path().is_some() {
-                    text("Recovery").bold()
-                } else {
-                    text("Spend").bold()
+                .push(match tx.path {
+                    SpendPath::Recovery(_) => text("Recovery").bold(),
+                    SpendPath::Primary => text("Spend").bold(),
                 })
                 .spacing(5)
                 .align_items(Alignment::Center),
@@ -215,15 +354,22 @@ fn spend_header<'a>(tx: &SpendTx) -> Element<'a, Message> {
             SpendStatus::Spent => Some(badge::spent()),
             _ => None,
         })
+        .push_maybe(match tx.path {
+            SpendPath::Recovery(timelock) => Some(card::warning(format!(
+                "This transaction spends through the recovery path available {} blocks after the \
+                 coins were received. It is meant for inheritance or the loss of the primary \
+                 keys, make sure this is what you intend.",
+                timelock
+            ))),
+            SpendPath::Primary => None,
+        })
         .push(
             Column::new()
                 .align_items(Alignment::Center)
-                .push(amount_with_size(&tx.spend_amount, 50))
-                .push(
-                    Row::new()
-                        .push(text("Miner fee: "))
-                        .push(amount(&tx.fee_amount)),
-                ),
+                .push(amount_with_size(&tx.spend_amount, unit, 50))
+                .push_maybe(fiat_amount(&tx.spend_amount, price))
+                .push(fee(&tx.fee_amount, tx.feerate_vb(), unit))
+                .push(text(format!("Size: {} vbytes", tx.vbytes)).small()),
         )
         .into()
 }
@@ -232,6 +378,7 @@ fn spend_overview_view<'a>(
     tx: &'a SpendTx,
     desc_info: &'a LianaDescInfo,
     key_aliases: &'a HashMap<Fingerprint, String>,
+    watch_only: bool,
 ) -> Element<'a, Message> {
     Container::new(
         Column::new()
@@ -269,9 +416,29 @@ fn spend_overview_view<'a>(
                                     .style(button::Style::TransparentBorder.into()),
                             )
                             .align_items(Alignment::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(text("Version:").bold().width(Length::Fill))
+                            .push(text(tx.psbt.unsigned_tx.version.to_string()).small())
+                            .align_items(Alignment::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(text("Spending path:").bold().width(Length::Fill))
+                            .push(
+                                text(match tx.path {
+                                    SpendPath::Primary => "Primary".to_string(),
+                                    SpendPath::Recovery(timelock) => {
+                                        format!("Recovery (after {} blocks)", timelock)
+                                    }
+                                })
+                                .small(),
+                            )
+                            .align_items(Alignment::Center),
                     ),
             )
-            .push(signatures(tx, desc_info, key_aliases)),
+            .push(signatures(tx, desc_info, key_aliases, watch_only)),
     )
     .style(card::SimpleCardStyle)
     .into()
@@ -281,6 +448,7 @@ pub fn signatures<'a>(
     tx: &'a SpendTx,
     desc_info: &'a LianaDescInfo,
     keys_aliases: &'a HashMap<Fingerprint, String>,
+    watch_only: bool,
 ) -> Element<'a, Message> {
     Column::new()
         .push(
@@ -361,20 +529,38 @@ pub fn signatures<'a>(
                         Column::new()
                             .padding(15)
                             .spacing(10)
-                            .push(text(if tx.sigs.recovery_path().is_some() {
-                                "2 spending paths available. Finalizing this transaction requires either:"
+                            .push(text(if tx.sigs.recovery_paths().is_empty() {
+                                "1 spending path available. Finalizing this transaction requires:".to_string()
                             } else {
-                                "1 spending path available. Finalizing this transaction requires:"
+                                format!(
+                                    "{} spending paths available. Finalizing this transaction requires either:",
+                                    tx.sigs.recovery_paths().len() + 1
+                                )
                             }))
                             .push(path_view(
                                 desc_info.primary_path(),
                                 tx.sigs.primary_path(),
                                 keys_aliases,
                             ))
-                            .push_maybe(tx.sigs.recovery_path().as_ref().map(|path| {
-                                let (_, keys) = desc_info.recovery_path();
-                                path_view(keys, path, keys_aliases)
-                            })),
+                            .push(tx.sigs.recovery_paths().iter().fold(
+                                Column::new().spacing(10),
+                                |col, (timelock, path)| {
+                                    col.push_maybe(desc_info.recovery_paths().get(timelock).map(
+                                        |keys| {
+                                            Column::new()
+                                                .spacing(5)
+                                                .push(
+                                                    text(format!(
+                                                        "Recovery path (after {} blocks):",
+                                                        timelock
+                                                    ))
+                                                    .small(),
+                                                )
+                                                .push(path_view(keys, path, keys_aliases))
+                                        },
+                                    ))
+                                },
+                            )),
                     ),
                 )
             },
@@ -385,18 +571,29 @@ pub fn signatures<'a>(
                     Container::new(
                         Row::new()
                             .push(Space::with_width(Length::Fill))
-                            .push_maybe(if tx.path_ready().is_none() {
-                                Some(
+                            .push(
+                                button::border(None, "Export PSBT")
+                                    .on_press(Message::Spend(SpendTxMessage::Export))
+                                    .width(Length::Units(150)),
+                            )
+                            .push_maybe(if tx.path_ready().is_none() && watch_only {
+                                Some(Container::new(
+                                    text("Watch-only: this wallet cannot sign")
+                                        .small()
+                                        .style(color::DARK_GREY),
+                                ))
+                            } else if tx.path_ready().is_none() {
+                                Some(Container::new(
                                     button::primary(None, "Sign")
                                         .on_press(Message::Spend(SpendTxMessage::Sign))
                                         .width(Length::Units(150)),
-                                )
+                                ))
                             } else {
-                                Some(
+                                Some(Container::new(
                                     button::primary(None, "Broadcast")
                                         .on_press(Message::Spend(SpendTxMessage::Broadcast))
                                         .width(Length::Units(150)),
-                                )
+                                ))
                             })
                             .align_items(Alignment::Center)
                             .spacing(20),
@@ -404,6 +601,23 @@ pub fn signatures<'a>(
                     .padding(15),
                 ),
             )
+        } else if tx.status == SpendStatus::Broadcast {
+            Some(
+                Column::new().push(separation().width(Length::Fill)).push(
+                    Container::new(
+                        Row::new()
+                            .push(Space::with_width(Length::Fill))
+                            .push(
+                                button::border(None, "Bump fee")
+                                    .on_press(Message::Spend(SpendTxMessage::BumpFee))
+                                    .width(Length::Units(150)),
+                            )
+                            .align_items(Alignment::Center)
+                            .spacing(20),
+                    )
+                    .padding(15),
+                ),
+            )
         } else {
             None
         })
@@ -506,6 +720,7 @@ pub fn inputs_and_outputs_view<'a>(
     network: Network,
     change_indexes: Option<Vec<usize>>,
     receive_indexes: Option<Vec<usize>>,
+    unit: Unit,
 ) -> Element<'a, Message> {
     Column::new()
         .push(
@@ -579,7 +794,7 @@ pub fn inputs_and_outputs_view<'a>(
                                                                 ),
                                                         ),
                                                 )
-                                                .push(amount(&coin.amount)),
+                                                .push(amount(&coin.amount, unit)),
                                         )
                                     })
                                     .into()
@@ -660,7 +875,7 @@ pub fn inputs_and_outputs_view<'a>(
                                                         ),
                                                     )
                                                     .push(
-                                                        amount(&Amount::from_sat(output.value))
+                                                        amount(&Amount::from_sat(output.value), unit)
                                                     ),
                                             )
                                             .push_maybe(
@@ -671,6 +886,18 @@ pub fn inputs_and_outputs_view<'a>(
                                                                 .padding(5)
                                                                 .style(badge::PillStyle::Success),
                                                         )
+                                                    } else if !receive_indexes
+                                                        .as_ref()
+                                                        .map(|indexes| indexes.contains(&i))
+                                                        .unwrap_or(false)
+                                                    {
+                                                        // Outgoing transaction: whatever does not
+                                                        // come back to the wallet leaves it.
+                                                        Some(
+                                                            Container::new(text("External recipient"))
+                                                                .padding(5)
+                                                                .style(badge::PillStyle::Simple),
+                                                        )
                                                     } else {
                                                         None
                                                     }
@@ -682,7 +909,13 @@ pub fn inputs_and_outputs_view<'a>(
                                                 if let Some(indexes) = receive_indexes.as_ref() {
                                                     if indexes.contains(&i) {
                                                         Some(
-                                                            Container::new(text("Deposit"))
+                                                            Container::new(text(
+                                                                if change_indexes.is_some() {
+                                                                    "To your wallet"
+                                                                } else {
+                                                                    "Deposit"
+                                                                },
+                                                            ))
                                                                 .padding(5)
                                                                 .style(badge::PillStyle::Success),
                                                         )
@@ -704,18 +937,229 @@ pub fn inputs_and_outputs_view<'a>(
         .into()
 }
 
+/// Summary of the spend to be confirmed by the user before choosing a signing device.
+pub fn sign_readiness_view<'a>(
+    summary: &'a SpendSummary,
+    key_aliases: &'a HashMap<Fingerprint, String>,
+    unit: Unit,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text("Review the transaction before signing").bold())
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(text("Amount:").bold().width(Length::Fill))
+                    .push(amount(&summary.spend_amount, unit)),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(text("Fee:").bold().width(Length::Fill))
+                    .push(amount(&summary.fee_amount, unit))
+                    .push(text(format!("({} sats/vbyte)", summary.feerate_vb))),
+            )
+            .push(separation().width(Length::Fill))
+            .push(text("Recipients:").bold())
+            .push(summary.recipients.iter().fold(
+                Column::new().spacing(5),
+                |col, (address, value)| {
+                    col.push(
+                        Row::new()
+                            .spacing(5)
+                            .align_items(Alignment::Center)
+                            .push(text(address.to_string()).small().width(Length::Fill))
+                            .push(amount(value, unit)),
+                    )
+                },
+            ))
+            .push(spent_inputs_view(&summary.inputs, unit))
+            .push(separation().width(Length::Fill))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(text("Spending path:").bold().width(Length::Fill))
+                    .push(if let Some(timelock) = summary.recovery_timelock {
+                        text(format!("Recovery (after {} blocks)", timelock))
+                    } else {
+                        text("Primary")
+                    }),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(text("Signatures:").bold().width(Length::Fill))
+                    .push(text(format!(
+                        "{}/{}",
+                        std::cmp::min(summary.sigs_count, summary.threshold),
+                        summary.threshold
+                    ))),
+            )
+            .push(summary.keys.iter().fold(
+                Column::new().spacing(5),
+                |col, (fingerprint, signed)| {
+                    col.push(
+                        Row::new()
+                            .spacing(5)
+                            .align_items(Alignment::Center)
+                            .push(icon::key_icon())
+                            .push(if let Some(alias) = key_aliases.get(fingerprint) {
+                                text(alias.to_string()).width(Length::Fill)
+                            } else {
+                                text(fingerprint.to_string()).width(Length::Fill)
+                            })
+                            .push_maybe(if *signed {
+                                Some(text("Signed").style(color::SUCCESS))
+                            } else {
+                                None
+                            }),
+                    )
+                },
+            ))
+            .push_maybe(
+                if summary.recoverable_coins > 0 && summary.recovery_timelock.is_none() {
+                    Some(card::warning(format!(
+                        "The recovery path is already available for {} of the coins spent",
+                        summary.recoverable_coins
+                    )))
+                } else {
+                    None
+                },
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Column::new().width(Length::Fill))
+                    .push(
+                        button::transparent(None, "Cancel")
+                            .on_press(Message::Spend(SpendTxMessage::Cancel)),
+                    )
+                    .push(
+                        button::primary(None, "Confirm and sign")
+                            .on_press(Message::Spend(SpendTxMessage::Confirm)),
+                    ),
+            ),
+    )
+    .width(Length::Units(600))
+    .into()
+}
+
+/// The coins spent by the transaction, to be checked one by one before signing.
+fn spent_inputs_view<'a>(inputs: &'a [SpentInput], unit: Unit) -> Element<'a, Message> {
+    let header = move |icon: iced::widget::Text<'static>| {
+        Button::new(
+            Row::new()
+                .align_items(Alignment::Center)
+                .push(
+                    text(format!(
+                        "{} spent coin{}",
+                        inputs.len(),
+                        if inputs.len() == 1 { "" } else { "s" }
+                    ))
+                    .bold()
+                    .width(Length::Fill),
+                )
+                .push(icon),
+        )
+        .padding(5)
+        .width(Length::Fill)
+        .style(button::Style::TransparentBorder.into())
+    };
+    Collapse::new(
+        move || header(icon::collapse_icon()),
+        move || header(icon::collapsed_icon()),
+        move || {
+            inputs
+                .iter()
+                .fold(
+                    Column::new().spacing(10),
+                    |col: Column<'a, Message>, input| {
+                        col.push(separation().width(Length::Fill))
+                            .push(spent_input_view(input, unit))
+                    },
+                )
+                .into()
+        },
+    )
+    .into()
+}
+
+fn spent_input_view<'a>(input: &SpentInput, unit: Unit) -> Column<'a, Message> {
+    let outpoint = input.outpoint.to_string();
+    let input_amount: Element<'a, Message> = match &input.amount {
+        Some(value) => amount(value, unit).into(),
+        None => text("Unknown amount").small().into(),
+    };
+    Column::new()
+        .spacing(5)
+        .push(
+            Row::new()
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .push(text(outpoint.clone()).small().width(Length::Fill))
+                .push(
+                    Button::new(icon::clipboard_icon())
+                        .on_press(Message::Clipboard(outpoint))
+                        .style(button::Style::TransparentBorder.into()),
+                )
+                .push(input_amount),
+        )
+        .push_maybe(input.address.as_ref().map(|address| {
+            Row::new()
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .push(text(address.to_string()).small().width(Length::Fill))
+                .push(
+                    Button::new(icon::clipboard_icon())
+                        .on_press(Message::Clipboard(address.to_string()))
+                        .style(button::Style::TransparentBorder.into()),
+                )
+        }))
+        .push(match input.derivation {
+            Some((true, index)) => text(format!("Change address #{}", index)).small(),
+            Some((false, index)) => text(format!("Receive address #{}", index)).small(),
+            None => text("Not an address of this wallet")
+                .small()
+                .style(color::ALERT),
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn sign_action<'a>(
     warning: Option<&Error>,
+    summary: &SpendSummary,
+    key_aliases: &HashMap<Fingerprint, String>,
     hws: &'a [HardwareWallet],
-    signer: Option<Fingerprint>,
+    signer: Option<(Fingerprint, bool)>,
     processing: bool,
     chosen_hw: Option<usize>,
+    can_retry: bool,
     signed: &[Fingerprint],
+    busy: &[Fingerprint],
+    queued: usize,
+    can_sign_with_all: bool,
+    sighash: EcdsaSighashType,
+    psbt_file: &form::Value<String>,
+    exported: bool,
 ) -> Element<'a, Message> {
     Column::new()
         .push_maybe(warning.map(|w| warn(Some(w))))
+        .push_maybe(if can_retry {
+            Some(
+                Row::new()
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::primary(None, "Retry")
+                            .on_press(Message::Spend(SpendTxMessage::RetrySigning)),
+                    ),
+            )
+        } else {
+            None
+        })
         .push(card::simple(
             Column::new()
+                .push(signatures_progress_view(summary, key_aliases))
                 .push(
                     Column::new()
                         .push(
@@ -725,18 +1169,25 @@ pub fn sign_action<'a>(
                                         .bold()
                                         .width(Length::Fill),
                                 )
-                                .push(button::border(None, "Refresh").on_press(Message::Reload))
+                                .push(if processing {
+                                    button::border(None, "Refresh")
+                                } else {
+                                    button::border(None, "Refresh").on_press(Message::Reload)
+                                })
                                 .align_items(Alignment::Center),
                         )
                         .spacing(10)
                         .push(hws.iter().enumerate().fold(
                             Column::new().spacing(10),
                             |col, (i, hw)| {
+                                // A device whose signing was cancelled may still be waiting
+                                // for the user, it is displayed as processing.
+                                let busy = hw.fingerprint().map_or(false, |f| busy.contains(&f));
                                 col.push(hw_list_view(
                                     i,
                                     hw,
-                                    Some(i) == chosen_hw,
-                                    processing,
+                                    Some(i) == chosen_hw || busy,
+                                    processing || busy,
                                     hw.fingerprint().and_then(|f| {
                                         if signed.contains(&f) {
                                             Some("Signed")
@@ -747,8 +1198,9 @@ pub fn sign_action<'a>(
                                 ))
                             },
                         ))
-                        .push_maybe(signer.map(|fingerprint| {
-                            Button::new(
+                        .push_maybe(signer.map(|(fingerprint, same_key_as_device)| {
+                            let already_signed = signed.contains(&fingerprint);
+                            let hot_signer_button = Button::new(
                                 Row::new()
                                     .align_items(Alignment::Center)
                                     .push(
@@ -759,10 +1211,22 @@ pub fn sign_action<'a>(
                                                 text(format!("fingerprint: {}", fingerprint))
                                                     .small(),
                                             )
+                                            .push_maybe(if same_key_as_device {
+                                                Some(
+                                                    text(
+                                                        "Same key as a connected device: \
+                                                        signing with either one is enough",
+                                                    )
+                                                    .small()
+                                                    .style(color::WARNING),
+                                                )
+                                            } else {
+                                                None
+                                            })
                                             .spacing(5)
                                             .width(Length::Fill),
                                     )
-                                    .push_maybe(if signed.contains(&fingerprint) {
+                                    .push_maybe(if already_signed {
                                         Some(
                                             Row::new()
                                                 .align_items(Alignment::Center)
@@ -776,13 +1240,55 @@ pub fn sign_action<'a>(
                                         None
                                     }),
                             )
-                            .on_press(Message::Spend(SpendTxMessage::SelectHotSigner))
                             .padding(10)
                             .style(button::Style::Border.into())
-                            .width(Length::Fill)
+                            .width(Length::Fill);
+                            if processing || already_signed {
+                                hot_signer_button
+                            } else {
+                                hot_signer_button.on_press(Message::Spend(SpendTxMessage::SelectHotSigner))
+                            }
                         }))
                         .width(Length::Fill),
                 )
+                .push_maybe(if can_sign_with_all && !processing {
+                    Some(
+                        button::border(Some(icon::chip_icon()), "Sign with all connected devices")
+                            .on_press(Message::Spend(SpendTxMessage::SignWithAllDevices)),
+                    )
+                } else {
+                    None
+                })
+                .push_maybe(if processing && queued > 0 {
+                    Some(
+                        text(format!(
+                            "{} more device(s) will be asked to sign once this signature is stored.",
+                            queued
+                        ))
+                        .small(),
+                    )
+                } else {
+                    None
+                })
+                .push_maybe(if processing && chosen_hw.is_some() {
+                    Some(
+                        Row::new()
+                            .push(
+                                text("The device does not respond?")
+                                    .small()
+                                    .width(Length::Fill),
+                            )
+                            .push(
+                                button::border(None, "Cancel")
+                                    .on_press(Message::Spend(SpendTxMessage::CancelSigning)),
+                            )
+                            .align_items(Alignment::Center),
+                    )
+                } else {
+                    None
+                })
+                .push(file_signer_view(psbt_file, exported, processing))
+                .push(sighash_picker(sighash))
                 .spacing(20)
                 .width(Length::Fill)
                 .align_items(Alignment::Center),
@@ -791,11 +1297,145 @@ pub fn sign_action<'a>(
         .into()
 }
 
+/// The number of signatures of the spending path and the keys which can still provide one.
+fn signatures_progress_view<'a>(
+    summary: &SpendSummary,
+    key_aliases: &HashMap<Fingerprint, String>,
+) -> Column<'a, Message> {
+    let missing: Vec<String> = summary
+        .keys
+        .iter()
+        .filter(|(_, signed)| !signed)
+        .map(|(fingerprint, _)| {
+            key_aliases
+                .get(fingerprint)
+                .cloned()
+                .unwrap_or_else(|| fingerprint.to_string())
+        })
+        .collect();
+    Column::new()
+        .spacing(5)
+        .width(Length::Fill)
+        .push(
+            Row::new()
+                .spacing(5)
+                .push(text("Signatures:").bold().width(Length::Fill))
+                .push(text(format!(
+                    "{}/{}",
+                    std::cmp::min(summary.sigs_count, summary.threshold),
+                    summary.threshold
+                ))),
+        )
+        .push_maybe(
+            if summary.sigs_count < summary.threshold && !missing.is_empty() {
+                Some(text(format!("Still needed from: {}", missing.join(", "))).small())
+            } else {
+                None
+            },
+        )
+}
+
+/// Sign with an air-gapped device through a file, for instance on a SD card: the PSBT is
+/// written to the given path and the signed PSBT is read back from it.
+fn file_signer_view<'a>(
+    psbt_file: &form::Value<String>,
+    exported: bool,
+    processing: bool,
+) -> Column<'a, Message> {
+    let can_use_file = psbt_file.valid && !psbt_file.value.is_empty();
+    Column::new()
+        .spacing(10)
+        .push(text("Air-gapped signing device (file):").bold())
+        .push(
+            text(
+                "Export the PSBT to a file, sign it with the device and import the signed PSBT. \
+                The path can be changed before importing if the device saved it to another file.",
+            )
+            .small(),
+        )
+        .push(
+            form::Form::new("Path of the PSBT file", psbt_file, |path| {
+                Message::Spend(SpendTxMessage::PsbtFileEdited(path))
+            })
+            .warning("Please enter a path")
+            .size(20)
+            .padding(10),
+        )
+        .push(
+            Row::new()
+                .spacing(10)
+                .push_maybe(if exported {
+                    Some(text("PSBT exported").style(color::SUCCESS))
+                } else {
+                    None
+                })
+                .push(Column::new().width(Length::Fill))
+                .push(if can_use_file && !processing {
+                    button::border(None, "Export PSBT")
+                        .on_press(Message::Spend(SpendTxMessage::ExportPsbtFile))
+                } else {
+                    button::border(None, "Export PSBT")
+                })
+                .push(if can_use_file && exported && !processing {
+                    button::primary(None, "Import signed PSBT")
+                        .on_press(Message::Spend(SpendTxMessage::ImportSignedPsbtFile))
+                } else {
+                    button::primary(None, "Import signed PSBT")
+                })
+                .align_items(Alignment::Center),
+        )
+}
+
+const SIGHASH_TYPES: [EcdsaSighashType; 6] = [
+    EcdsaSighashType::All,
+    EcdsaSighashType::None,
+    EcdsaSighashType::Single,
+    EcdsaSighashType::AllPlusAnyoneCanPay,
+    EcdsaSighashType::NonePlusAnyoneCanPay,
+    EcdsaSighashType::SinglePlusAnyoneCanPay,
+];
+
+/// Advanced option to sign with another sighash type than SIGHASH_ALL.
+fn sighash_picker<'a>(sighash: EcdsaSighashType) -> Column<'a, Message> {
+    Column::new()
+        .spacing(10)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text("Advanced: sighash type").small().width(Length::Fill))
+                .push(
+                    PickList::new(&SIGHASH_TYPES[..], Some(sighash), |sighash| {
+                        Message::Spend(SpendTxMessage::SighashSelected(sighash))
+                    })
+                    .padding(5),
+                ),
+        )
+        .push_maybe(if sighash != EcdsaSighashType::All {
+            Some(
+                text(
+                    "Warning: with a sighash type other than SIGHASH_ALL, the signature does not \
+                    commit to the whole transaction. Anyone can then modify the parts it does not \
+                    commit to, for instance redirect the funds or increase the fee. Only use it \
+                    if you know exactly what you are doing.",
+                )
+                .small()
+                .style(color::WARNING),
+            )
+        } else {
+            None
+        })
+}
+
 pub fn update_spend_view<'a>(
     psbt: String,
     updated: &form::Value<String>,
+    missing_frames: Option<usize>,
+    frames_file: &form::Value<String>,
+    psbt_file: &form::Value<String>,
     error: Option<&Error>,
     processing: bool,
+    decoding: bool,
 ) -> Element<'a, Message> {
     Column::new()
         .push(warn(error))
@@ -824,10 +1464,86 @@ pub fn update_spend_view<'a>(
                             .size(20)
                             .padding(10),
                         )
+                        .push_maybe(missing_frames.map(|missing| {
+                            text(format!(
+                                "Animated QR code: {} frame{} still missing",
+                                missing,
+                                if missing == 1 { " is" } else { "s are" }
+                            ))
+                            .small()
+                        }))
+                        .push(
+                            Row::new()
+                                .spacing(10)
+                                .align_items(Alignment::Center)
+                                .push(
+                                    form::Form::new(
+                                        "Path of the file with the scanned QR code frames",
+                                        frames_file,
+                                        |path| {
+                                            Message::ImportSpend(
+                                                ImportSpendMessage::QrCodeFileEdited(path),
+                                            )
+                                        },
+                                    )
+                                    .warning("Please enter a path")
+                                    .size(20)
+                                    .padding(10),
+                                )
+                                .push(
+                                    if frames_file.valid
+                                        && !frames_file.value.is_empty()
+                                        && !processing
+                                    {
+                                        button::border(None, "Import").on_press(
+                                            Message::ImportSpend(
+                                                ImportSpendMessage::ImportQrCodeFile,
+                                            ),
+                                        )
+                                    } else {
+                                        button::border(None, "Import")
+                                    },
+                                ),
+                        )
+                        .push(
+                            Row::new()
+                                .spacing(10)
+                                .align_items(Alignment::Center)
+                                .push(
+                                    form::Form::new(
+                                        "Path of a PSBT file, binary or base64",
+                                        psbt_file,
+                                        |path| {
+                                            Message::ImportSpend(
+                                                ImportSpendMessage::PsbtFileEdited(path),
+                                            )
+                                        },
+                                    )
+                                    .warning("Please enter a path")
+                                    .size(20)
+                                    .padding(10),
+                                )
+                                .push(
+                                    if psbt_file.valid && !psbt_file.value.is_empty() && !processing
+                                    {
+                                        button::border(None, "Load").on_press(Message::ImportSpend(
+                                            ImportSpendMessage::ImportPsbtFile,
+                                        ))
+                                    } else {
+                                        button::border(None, "Load")
+                                    },
+                                ),
+                        )
                         .push(Row::new().push(Space::with_width(Length::Fill)).push(
-                            if updated.valid && !updated.value.is_empty() && !processing {
+                            if updated.valid
+                                && !updated.value.is_empty()
+                                && !processing
+                                && !decoding
+                            {
                                 button::primary(None, "Update")
                                     .on_press(Message::ImportSpend(ImportSpendMessage::Confirm))
+                            } else if decoding {
+                                button::primary(None, "Checking PSBT...")
                             } else if processing {
                                 button::primary(None, "Processing...")
                             } else {
@@ -7,7 +7,7 @@ use iced::{
 };
 
 use crate::{
-    app::{error::Error, menu::Menu, view::util::*},
+    app::{error::Error, menu::Menu, unit::Unit, view::util::*},
     daemon::model::{SpendStatus, SpendTx},
     ui::{
         color,
@@ -23,6 +23,7 @@ pub fn import_spend_view<'a>(
     imported: &form::Value<String>,
     error: Option<&Error>,
     processing: bool,
+    decoding: bool,
 ) -> Element<'a, Message> {
     Column::new()
         .push(warn(error))
@@ -39,9 +40,11 @@ pub fn import_spend_view<'a>(
                     .padding(10),
                 )
                 .push(Row::new().push(Space::with_width(Length::Fill)).push(
-                    if imported.valid && !imported.value.is_empty() && !processing {
+                    if imported.valid && !imported.value.is_empty() && !processing && !decoding {
                         button::primary(None, "Import")
                             .on_press(Message::ImportSpend(ImportSpendMessage::Confirm))
+                    } else if decoding {
+                        button::primary(None, "Checking PSBT...")
                     } else if processing {
                         button::primary(None, "Processing...")
                     } else {
@@ -66,16 +69,34 @@ pub fn import_spend_success_view<'a>() -> Element<'a, Message> {
         .into()
 }
 
-pub fn spend_view<'a>(spend_txs: &[SpendTx]) -> Element<'a, Message> {
+pub fn spend_view<'a>(
+    spend_txs: &[SpendTx],
+    cleaned_up: Option<usize>,
+    unit: Unit,
+) -> Element<'a, Message> {
     Column::new()
         .push(
             Row::new()
                 .spacing(10)
                 .push(Column::new().width(Length::Fill))
+                .push_maybe(
+                    if spend_txs
+                        .iter()
+                        .any(|tx| tx.status == SpendStatus::Deprecated)
+                    {
+                        Some(button::border(None, "Clean up").on_press(Message::CleanupSpends))
+                    } else {
+                        None
+                    },
+                )
                 .push(
                     button::border(Some(icon::import_icon()), "Import")
                         .on_press(Message::ImportSpend(ImportSpendMessage::Import)),
                 )
+                .push(
+                    button::border(Some(icon::shield_icon()), "Privacy refresh")
+                        .on_press(Message::Menu(Menu::PrivacyRefresh)),
+                )
                 .push(
                     button::primary(Some(icon::plus_icon()), "New")
                         .on_press(Message::Menu(Menu::CreateSpendTx)),
@@ -85,7 +106,17 @@ pub fn spend_view<'a>(spend_txs: &[SpendTx]) -> Element<'a, Message> {
             Container::new(
                 Row::new()
                     .push(text(format!(" {}", spend_txs.len())).bold())
-                    .push(text(" draft transactions")),
+                    .push(text(" draft transactions"))
+                    .push_maybe(cleaned_up.map(|count| {
+                        text(if count == 0 {
+                            ", none of the replaced ones could be deleted until their \
+                            replacement is final"
+                                .to_string()
+                        } else {
+                            format!(", {} replaced ones deleted", count)
+                        })
+                        .small()
+                    })),
             )
             .width(Length::Fill),
         )
@@ -95,7 +126,7 @@ pub fn spend_view<'a>(spend_txs: &[SpendTx]) -> Element<'a, Message> {
                     .iter()
                     .enumerate()
                     .fold(Column::new().spacing(10), |col, (i, tx)| {
-                        col.push(spend_tx_list_view(i, tx))
+                        col.push(spend_tx_list_view(i, tx, unit))
                     }),
             ),
         )
@@ -104,14 +135,14 @@ pub fn spend_view<'a>(spend_txs: &[SpendTx]) -> Element<'a, Message> {
         .into()
 }
 
-fn spend_tx_list_view<'a>(i: usize, tx: &SpendTx) -> Element<'a, Message> {
+fn spend_tx_list_view<'a>(i: usize, tx: &SpendTx, unit: Unit) -> Element<'a, Message> {
     Container::new(
         Button::new(
             Row::new()
                 .push(
                     Row::new()
                         .push(badge::spend())
-                        .push(if let Some(sigs) = tx.sigs.recovery_path() {
+                        .push(if let Some((_, sigs)) = tx.sigs.recovery_path() {
                             Row::new()
                                 .spacing(10)
                                 .align_items(Alignment::Center)
@@ -163,8 +194,8 @@ fn spend_tx_list_view<'a>(i: usize, tx: &SpendTx) -> Element<'a, Message> {
                 })
                 .push(
                     Column::new()
-                        .push(amount(&tx.spend_amount))
-                        .push(text(format!("fee: {:8}", tx.fee_amount.to_btc())).small())
+                        .push(amount(&tx.spend_amount, unit))
+                        .push(fee_small(&tx.fee_amount, tx.feerate_vb(), unit))
                         .width(Length::Shrink),
                 )
                 .align_items(Alignment::Center)
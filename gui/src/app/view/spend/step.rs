@@ -1,21 +1,29 @@
 use iced::{
-    widget::{self, Button, Column, Container, Row},
+    widget::{self, Button, Checkbox, Column, Container, PickList, Row},
     Alignment, Element, Length,
 };
 
-use liana::miniscript::bitcoin::Amount;
+use liana::miniscript::bitcoin::{Amount, Network};
 
 use crate::{
     app::{
+        address_book::{AddressBook, Contact},
         cache::Cache,
         error::Error,
-        view::{message::*, modal, util::amount},
+        unit::Unit,
+        view::{
+            message::*,
+            modal,
+            util::{amount, min_feerate_view},
+        },
     },
-    daemon::model::{remaining_sequence, Coin},
+    daemon::model::{remaining_sequence, Coin, UnconfirmedParent},
     ui::{
         color,
         component::{
-            badge, button, card, form,
+            badge, button, card,
+            collapse::Collapse,
+            form,
             text::{text, Text},
         },
         icon,
@@ -29,6 +37,8 @@ pub fn choose_recipients_view<'a>(
     total_amount: Amount,
     is_valid: bool,
     duplicate: bool,
+    sweep: bool,
+    unit: Unit,
 ) -> Element<'a, Message> {
     modal(
         false,
@@ -37,11 +47,20 @@ pub fn choose_recipients_view<'a>(
             .push(text("Choose recipients").bold().size(50))
             .push(
                 Column::new()
+                    .push(Checkbox::new(
+                        "Sweep: send all the selected coins to a single address",
+                        sweep,
+                        |checked| Message::CreateSpend(CreateSpendMessage::SweepToggled(checked)),
+                    ))
                     .push(widget::Column::with_children(recipients).spacing(10))
-                    .push(
-                        button::transparent(Some(icon::plus_icon()), "Add recipient")
-                            .on_press(Message::CreateSpend(CreateSpendMessage::AddRecipient)),
-                    )
+                    .push_maybe(if sweep {
+                        None
+                    } else {
+                        Some(
+                            button::transparent(Some(icon::plus_icon()), "Add recipient")
+                                .on_press(Message::CreateSpend(CreateSpendMessage::AddRecipient)),
+                        )
+                    })
                     .padding(10)
                     .max_width(1000)
                     .spacing(10),
@@ -58,8 +77,8 @@ pub fn choose_recipients_view<'a>(
                             Row::new()
                                 .align_items(Alignment::Center)
                                 .spacing(5)
-                                .push(text(format!("{}", total_amount)).bold())
-                                .push(text(format!("/ {}", balance_available))),
+                                .push(text(unit.format(&total_amount)).bold())
+                                .push(text(format!("/ {}", unit.format(balance_available)))),
                         )
                         .width(Length::Fill),
                     )
@@ -81,12 +100,30 @@ pub fn choose_recipients_view<'a>(
     )
 }
 
+/// Maximum number of saved recipients suggested while the address is typed.
+const MAX_CONTACT_SUGGESTIONS: usize = 5;
+
 pub fn recipient_view<'a>(
     index: usize,
     address: &form::Value<String>,
     amount: &form::Value<String>,
+    unit: Unit,
+    sweep: bool,
+    address_book: &'a AddressBook,
+    network: Network,
 ) -> Element<'a, CreateSpendMessage> {
-    Row::new()
+    let contacts: Vec<Contact> = address_book.valid_contacts(network).cloned().collect();
+    let contact = address_book.contact(address.value.trim());
+    // Saved recipients whose name or address contains what was typed.
+    let suggestions: Vec<&Contact> = if contact.is_none() {
+        address_book
+            .search(&address.value, network)
+            .take(MAX_CONTACT_SUGGESTIONS)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let row = Row::new()
         .push(
             form::Form::new("Address", address, move |msg| {
                 CreateSpendMessage::RecipientEdited(index, "address", msg)
@@ -95,33 +132,146 @@ pub fn recipient_view<'a>(
             .size(20)
             .padding(10),
         )
-        .push(
+        .push_maybe(if contacts.is_empty() {
+            None
+        } else {
+            Some(
+                PickList::new(contacts, None, move |contact: Contact| {
+                    CreateSpendMessage::RecipientEdited(index, "address", contact.address)
+                })
+                .placeholder("Address book")
+                .padding(10)
+                .width(Length::Units(150)),
+            )
+        })
+        .push(if sweep {
+            // The amount is the value of the selected coins minus the fees.
+            Container::new(text("All the selected coins, minus the fees").small())
+                .padding(10)
+                .width(Length::Units(300))
+        } else {
             Container::new(
-                form::Form::new("Amount", amount, move |msg| {
+                form::Form::new(&format!("Amount ({})", unit), amount, move |msg| {
                     CreateSpendMessage::RecipientEdited(index, "amount", msg)
                 })
-                .warning("Invalid amount. Must be > 0.00005000 BTC.")
+                .warning(match unit {
+                    Unit::Btc => "Invalid amount. Must be > 0.00005000 BTC.",
+                    Unit::Mbtc => "Invalid amount. Must be > 0.05000 mBTC.",
+                    Unit::Sats => "Invalid amount. Must be > 5 000 sats.",
+                })
                 .size(20)
                 .padding(10),
             )
-            .width(Length::Units(300)),
-        )
+            .width(Length::Units(300))
+        })
         .spacing(5)
         .push(
             button::transparent(Some(icon::trash_icon()), "")
                 .on_press(CreateSpendMessage::DeleteRecipient(index))
                 .width(Length::Shrink),
         )
-        .width(Length::Fill)
+        .width(Length::Fill);
+    Column::new()
+        .spacing(5)
+        .push(row)
+        .push_maybe(contact.map(|contact| text(format!("Saved as: {}", contact.name)).small()))
+        .push_maybe(if suggestions.is_empty() {
+            None
+        } else {
+            Some(
+                suggestions
+                    .into_iter()
+                    .fold(Column::new().spacing(5), |col, contact| {
+                        col.push(
+                            Button::new(text(contact.to_string()).small())
+                                .style(button::Style::TransparentBorder.into())
+                                .on_press(CreateSpendMessage::RecipientEdited(
+                                    index,
+                                    "address",
+                                    contact.address.clone(),
+                                )),
+                        )
+                    }),
+            )
+        })
         .into()
 }
 
+pub fn choose_refresh_outputs_view<'a>(
+    output_count: &form::Value<String>,
+    randomize: bool,
+) -> Element<'a, Message> {
+    modal(
+        false,
+        None,
+        Column::new()
+            .push(text("Privacy refresh").bold().size(50))
+            .push(
+                Column::new()
+                    .push(text(
+                        "Send the selected coins back to fresh addresses of the wallet at the \
+                        chosen feerate. This only makes the history of the coins harder to follow \
+                        on-chain, it does not refresh their timelock.",
+                    ))
+                    .push(
+                        Container::new(
+                            form::Form::new("Number of outputs", output_count, |msg| {
+                                Message::CreateSpend(CreateSpendMessage::RefreshOutputCountEdited(
+                                    msg,
+                                ))
+                            })
+                            .warning("Must be between 1 and 10")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .width(Length::Units(300)),
+                    )
+                    .push(Checkbox::new(
+                        "Split the amount randomly between the outputs",
+                        randomize,
+                        |checked| {
+                            Message::CreateSpend(CreateSpendMessage::RefreshRandomizeToggled(
+                                checked,
+                            ))
+                        },
+                    ))
+                    .padding(10)
+                    .max_width(1000)
+                    .spacing(10),
+            )
+            .spacing(20)
+            .align_items(Alignment::Center),
+        Some(
+            Container::new(
+                Row::new()
+                    .align_items(Alignment::Center)
+                    .push(Container::new(text("")).width(Length::Fill))
+                    .push(if output_count.valid {
+                        button::primary(None, "Next")
+                            .on_press(Message::Next)
+                            .width(Length::Units(100))
+                    } else {
+                        button::primary(None, "Next").width(Length::Units(100))
+                    }),
+            )
+            .padding(20),
+        ),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn choose_coins_view<'a>(
     cache: &Cache,
     timelock: u32,
     coins: &[(Coin, bool)],
     amount_left: Option<&Amount>,
     feerate: &form::Value<String>,
+    consolidation: bool,
+    consolidation_feerate: &form::Value<String>,
+    consolidation_summary: Option<(usize, usize)>,
+    fee_comparison: Vec<(u64, Amount)>,
+    unconfirmed_parents: Vec<UnconfirmedParent>,
+    cpfp_feerate: Option<u64>,
     error: Option<&Error>,
 ) -> Element<'a, Message> {
     modal(
@@ -130,16 +280,46 @@ pub fn choose_coins_view<'a>(
         Column::new()
             .push(text("Choose coins and feerate").bold().size(50))
             .push(
-                Container::new(
-                    form::Form::new("Feerate (sat/vbyte)", feerate, move |msg| {
-                        Message::CreateSpend(CreateSpendMessage::FeerateEdited(msg))
-                    })
-                    .warning("Invalid feerate")
-                    .size(20)
-                    .padding(10),
-                )
-                .width(Length::Units(250)),
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Container::new(
+                            form::Form::new("Feerate (sat/vbyte)", feerate, move |msg| {
+                                Message::CreateSpend(CreateSpendMessage::FeerateEdited(msg))
+                            })
+                            .warning("Invalid feerate")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .width(Length::Units(250)),
+                    )
+                    .push_maybe(
+                        feerate
+                            .value
+                            .parse::<u64>()
+                            .ok()
+                            .and_then(|feerate| {
+                                cache
+                                    .fee_estimates
+                                    .as_ref()
+                                    .and_then(|estimates| estimates.confirmation_time(feerate))
+                            })
+                            .map(|time| text(format!("Estimated confirmation: {}", time)).small()),
+                    ),
             )
+            .push(min_feerate_view(cache.mempool_min_feerate))
+            .push(fee_comparison_view(fee_comparison, cache.unit))
+            .push_maybe(if unconfirmed_parents.is_empty() {
+                None
+            } else {
+                Some(unconfirmed_parents_view(unconfirmed_parents, cpfp_feerate))
+            })
+            .push(consolidation_view(
+                consolidation,
+                consolidation_feerate,
+                consolidation_summary,
+            ))
             .push(
                 Column::new()
                     .padding(10)
@@ -153,6 +333,7 @@ pub fn choose_coins_view<'a>(
                                 timelock,
                                 cache.blockheight as u32,
                                 *selected,
+                                cache.unit,
                             ))
                         },
                     )),
@@ -168,13 +349,21 @@ pub fn choose_coins_view<'a>(
                             Row::new()
                                 .spacing(5)
                                 .push(text("Amount left to select:"))
-                                .push(text(amount_left.to_string()).bold())
+                                .push(if *amount_left > Amount::from_sat(0) {
+                                    text(cache.unit.format(amount_left))
+                                        .bold()
+                                        .style(color::ALERT)
+                                } else {
+                                    text(cache.unit.format(amount_left)).bold()
+                                })
                         } else {
                             Row::new().push(text("Feerate needs to be set."))
                         })
                         .width(Length::Fill),
                     )
-                    .push(if Some(&Amount::from_sat(0)) == amount_left {
+                    // The selection is checked when generating the transaction, to tell the
+                    // user by how much it falls short.
+                    .push(if amount_left.is_some() {
                         button::primary(None, "Next")
                             .on_press(Message::CreateSpend(CreateSpendMessage::Generate))
                             .width(Length::Units(100))
@@ -187,12 +376,156 @@ pub fn choose_coins_view<'a>(
     )
 }
 
+/// A table of the fee the transaction would pay at a few candidate feerates, hidden by default.
+fn fee_comparison_view<'a>(fee_comparison: Vec<(u64, Amount)>, unit: Unit) -> Element<'a, Message> {
+    Container::new(Collapse::new(
+        || {
+            Button::new(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(text("Compare fees").small())
+                    .push(icon::collapse_icon().small()),
+            )
+            .style(button::Style::TransparentBorder.into())
+        },
+        || {
+            Button::new(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(text("Compare fees").small())
+                    .push(icon::collapsed_icon().small()),
+            )
+            .style(button::Style::TransparentBorder.into())
+        },
+        move || {
+            fee_comparison
+                .iter()
+                .fold(
+                    Column::new().spacing(5).push(
+                        Row::new()
+                            .spacing(20)
+                            .push(text("Feerate").small().bold().width(Length::Units(150)))
+                            .push(text("Total fee").small().bold()),
+                    ),
+                    |col: Column<'a, Message>, (feerate, fee)| {
+                        col.push(
+                            Row::new()
+                                .spacing(20)
+                                .push(
+                                    text(format!("{} sats/vbyte", feerate))
+                                        .small()
+                                        .width(Length::Units(150)),
+                                )
+                                .push(text(unit.format(fee)).small()),
+                        )
+                    },
+                )
+                .into()
+        },
+    ))
+    .width(Length::Units(400))
+    .into()
+}
+
+/// The selected coins created by unconfirmed transactions, which the spend has to wait for.
+fn unconfirmed_parents_view<'a>(
+    parents: Vec<UnconfirmedParent>,
+    cpfp_feerate: Option<u64>,
+) -> Element<'a, Message> {
+    Container::new(
+        parents
+            .iter()
+            .fold(
+                Column::new().spacing(5).push(
+                    text(
+                        "Some of the selected coins are unconfirmed: this transaction \
+                        can't confirm before the transactions creating them.",
+                    )
+                    .bold(),
+                ),
+                |col, parent| {
+                    col.push(
+                        Row::new()
+                            .spacing(20)
+                            .push(text(parent.txid.to_string()).small().width(Length::Fill))
+                            .push(
+                                text(match parent.feerate {
+                                    Some(feerate) => format!("{} sats/vbyte", feerate),
+                                    None => "unknown feerate".to_string(),
+                                })
+                                .small(),
+                            ),
+                    )
+                },
+            )
+            .push_maybe(cpfp_feerate.map(|feerate| {
+                text(format!(
+                    "Some of them pay a lower feerate. Use a feerate of {} sats/vbyte \
+                    for them to confirm together with this transaction at the chosen feerate.",
+                    feerate
+                ))
+                .small()
+            })),
+    )
+    .padding(15)
+    .width(Length::Fill)
+    .style(card::WarningCardStyle)
+    .into()
+}
+
+fn consolidation_view<'a>(
+    consolidation: bool,
+    consolidation_feerate: &form::Value<String>,
+    consolidation_summary: Option<(usize, usize)>,
+) -> Element<'a, Message> {
+    Column::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(Checkbox::new(
+            "Consolidation mode: select every coin cheaper to spend now than later",
+            consolidation,
+            |checked| Message::CreateSpend(CreateSpendMessage::ConsolidationToggled(checked)),
+        ))
+        .push_maybe(if consolidation {
+            Some(
+                Container::new(
+                    form::Form::new(
+                        "Expected future feerate (sat/vbyte)",
+                        consolidation_feerate,
+                        move |msg| {
+                            Message::CreateSpend(CreateSpendMessage::ConsolidationFeerateEdited(
+                                msg,
+                            ))
+                        },
+                    )
+                    .warning("Invalid feerate")
+                    .size(20)
+                    .padding(10),
+                )
+                .width(Length::Units(350)),
+            )
+        } else {
+            None
+        })
+        .push_maybe(consolidation_summary.map(|(selected, remaining)| {
+            text(format!(
+                "{} coins will be consolidated, the wallet will then have {} coins.",
+                selected, remaining
+            ))
+            .small()
+        }))
+        .into()
+}
+
 fn coin_list_view<'a>(
     i: usize,
     coin: &Coin,
     timelock: u32,
     blockheight: u32,
     selected: bool,
+    unit: Unit,
 ) -> Element<'a, Message> {
     Container::new(
         Button::new(
@@ -248,7 +581,7 @@ fn coin_list_view<'a>(
                         .align_items(Alignment::Center)
                         .width(Length::Fill),
                 )
-                .push(amount(&coin.amount))
+                .push(amount(&coin.amount, unit))
                 .align_items(Alignment::Center)
                 .spacing(20),
         )
@@ -0,0 +1,125 @@
+use iced::{
+    widget::{Column, Container, PickList, Row, Space},
+    Alignment, Element, Length,
+};
+
+use liana::miniscript::bitcoin::Amount;
+
+use crate::{
+    app::{
+        unit::Unit,
+        view::{
+            message::{CreateSpendMessage, Message},
+            util::min_feerate_view,
+        },
+    },
+    ui::{
+        component::{button, form, text::*},
+        icon,
+        util::Collection,
+    },
+};
+
+pub fn timelock_refresh<'a>(
+    coins_to_refresh: (usize, Amount),
+    timelocks: &[u16],
+    timelock: u16,
+    threshold: &form::Value<String>,
+    feerate: &form::Value<String>,
+    min_feerate: u64,
+    unit: Unit,
+) -> Element<'a, Message> {
+    Column::new()
+        .push(Space::with_height(Length::Units(100)))
+        .push(
+            Row::new()
+                .push(Container::new(
+                    icon::hourglass_icon().width(Length::Units(100)).size(50),
+                ))
+                .push(text("Refresh the coins").size(50).bold())
+                .align_items(Alignment::Center)
+                .spacing(1),
+        )
+        .push(
+            Container::new(text(format!(
+                "The recovery path of a coin becomes available {} blocks after it was received. \
+                 Spending the coins back to the wallet restarts their timelock.",
+                timelock
+            )))
+            .center_x(),
+        )
+        .push(
+            Container::new(text(format!(
+                "{} ({} coins) will be refreshed",
+                unit.format(&coins_to_refresh.1),
+                coins_to_refresh.0
+            )))
+            .center_x(),
+        )
+        .push(Space::with_height(Length::Units(20)))
+        .push(
+            Column::new()
+                .push_maybe(if timelocks.len() > 1 {
+                    Some(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(text("Recovery path with a timelock of").bold())
+                            .push(
+                                PickList::new(timelocks.to_vec(), Some(timelock), |timelock| {
+                                    Message::CreateSpend(
+                                        CreateSpendMessage::RefreshTimelockSelected(timelock),
+                                    )
+                                })
+                                .padding(5),
+                            )
+                            .push(text("blocks").bold()),
+                    )
+                } else {
+                    None
+                })
+                .push(text("Refresh the coins whose recovery path is available within:").bold())
+                .push(
+                    Container::new(
+                        form::Form::new("Number of blocks", threshold, move |msg| {
+                            Message::CreateSpend(CreateSpendMessage::RefreshThresholdEdited(msg))
+                        })
+                        .warning("Invalid number of blocks")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .width(Length::Units(250)),
+                )
+                .push(
+                    Container::new(
+                        form::Form::new("Feerate (sat/vbyte)", feerate, move |msg| {
+                            Message::CreateSpend(CreateSpendMessage::FeerateEdited(msg))
+                        })
+                        .warning("Invalid feerate")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .width(Length::Units(250)),
+                )
+                .push(min_feerate_view(min_feerate))
+                .push(
+                    if feerate.valid
+                        && !feerate.value.is_empty()
+                        && threshold.valid
+                        && !threshold.value.is_empty()
+                        && coins_to_refresh.0 != 0
+                    {
+                        button::primary(None, "Next")
+                            .on_press(Message::Next)
+                            .width(Length::Units(200))
+                    } else {
+                        button::primary(None, "Next").width(Length::Units(200))
+                    },
+                )
+                .spacing(20)
+                .align_items(Alignment::Center),
+        )
+        .align_items(Alignment::Center)
+        .spacing(20)
+        .into()
+}
@@ -1,17 +1,76 @@
+use std::time::Duration;
+
 use iced::{widget::Row, Element};
 use liana::miniscript::bitcoin::Amount;
 
-use crate::ui::{color, component::text::*, util::Collection};
+use crate::{
+    app::{price::Price, unit::Unit},
+    ui::{color, component::text::*, util::Collection},
+};
+
+pub fn amount<'a, T: 'a>(a: &Amount, unit: Unit) -> impl Into<Element<'a, T>> {
+    amount_with_size(a, unit, TEXT_REGULAR_SIZE)
+}
+
+/// Display the total fee paid by a transaction along with its feerate.
+pub fn fee<'a, T: 'a>(a: &Amount, feerate_vb: u64, unit: Unit) -> impl Into<Element<'a, T>> {
+    Row::new()
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .push(text("Miner fee:"))
+        .push(amount(a, unit))
+        .push(text(format!("({} sats/vbyte)", feerate_vb)))
+}
 
-pub fn amount<'a, T: 'a>(a: &Amount) -> impl Into<Element<'a, T>> {
-    amount_with_size(a, TEXT_REGULAR_SIZE)
+/// The value of the amount in the fiat currency of the wallet, if its price is known, along with
+/// the time of the price.
+pub fn fiat_amount<'a>(a: &Amount, price: Option<&Price>) -> Option<iced::widget::Text<'a>> {
+    price.map(|price| {
+        text(format!(
+            "~{} (price as of {})",
+            price.format(a),
+            price.time()
+        ))
+        .small()
+    })
 }
 
-pub fn amount_with_size<'a, T: 'a>(a: &Amount, size: u16) -> impl Into<Element<'a, T>> {
+/// The minimum feerate the node currently accepts in its mempool.
+pub fn min_feerate_view<'a>(min_feerate_vb: u64) -> iced::widget::Text<'a> {
+    text(format!(
+        "Minimum feerate accepted by the node: {} sats/vbyte",
+        min_feerate_vb
+    ))
+    .small()
+}
+
+/// Small text version of the fee display, for list items.
+pub fn fee_small<'a>(a: &Amount, feerate_vb: u64, unit: Unit) -> iced::widget::Text<'a> {
+    text(format!(
+        "fee: {} ({} sats/vbyte)",
+        unit.format(a),
+        feerate_vb
+    ))
+    .small()
+}
+
+pub fn amount_with_size<'a, T: 'a>(a: &Amount, unit: Unit, size: u16) -> impl Into<Element<'a, T>> {
     let spacing = if size > TEXT_REGULAR_SIZE { 10 } else { 5 };
-    let sats = format!("{:.8}", a.to_btc());
+    let row = match unit {
+        Unit::Btc => btc_digits(a, size, spacing),
+        // Only bitcoin amounts are long enough to need their digits grouped.
+        Unit::Mbtc | Unit::Sats => Row::new().push(split_digits(unit.format_value(a), size).into()),
+    };
+
+    Row::with_children(vec![row.into(), text(unit.to_string()).size(size).into()])
+        .spacing(spacing)
+        .align_items(iced::Alignment::Center)
+}
+
+fn btc_digits<'a, T: 'a>(a: &Amount, size: u16, spacing: u16) -> Row<'a, T> {
+    let sats = Unit::Btc.format_value(a);
     assert!(sats.len() >= 9);
-    let row = Row::new()
+    Row::new()
         .spacing(spacing)
         .push(split_digits(sats[0..sats.len() - 6].to_string(), size).into())
         .push(if a.to_sat() < 1_000_000 {
@@ -35,11 +94,7 @@ pub fn amount_with_size<'a, T: 'a>(a: &Amount, size: u16) -> impl Into<Element<'
                         .size(size),
                 )
                 .into()
-        });
-
-    Row::with_children(vec![row.into(), text("BTC").size(size).into()])
-        .spacing(spacing)
-        .align_items(iced::Alignment::Center)
+        })
 }
 
 fn split_digits<'a, T: 'a>(mut s: String, size: u16) -> impl Into<Element<'a, T>> {
@@ -58,3 +113,19 @@ fn split_digits<'a, T: 'a>(mut s: String, size: u16) -> impl Into<Element<'a, T>
     }
     Row::new().push(text(s).bold().size(size))
 }
+
+/// Progress of a rescan in percent, followed by its estimated remaining time if known.
+pub fn rescan_progress(progress: f64, remaining: Option<Duration>) -> String {
+    let progress = format!("Rescan...{:.2}%", progress * 100.0);
+    match remaining.map(|d| d.as_secs() / 60) {
+        None => progress,
+        Some(0) => format!("{} (less than a minute left)", progress),
+        Some(mins) if mins < 60 => format!("{} (about {} min left)", progress, mins),
+        Some(mins) => format!(
+            "{} (about {} h {} min left)",
+            progress,
+            mins / 60,
+            mins % 60
+        ),
+    }
+}
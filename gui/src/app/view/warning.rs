@@ -37,7 +37,8 @@ impl From<&Error> for WarningMessage {
                 }
             },
             Error::Unexpected(_) => WarningMessage("Unknown error".to_string()),
-            Error::HardwareWallet(_) => WarningMessage("Hardware wallet error".to_string()),
+            Error::Signer(kind, _) => WarningMessage(kind.to_string()),
+            Error::UnsupportedSighash(_) => WarningMessage(error.to_string()),
         }
     }
 }
@@ -2,18 +2,34 @@ use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 use crate::{
-    app::{config::Config, settings},
+    app::{
+        address_book::{AddressBook, AddressBookError},
+        config::Config,
+        labels::{Labels, LabelsError},
+        settings,
+        unit::Unit,
+    },
     hw::HardwareWalletConfig,
     signer::Signer,
 };
 
-use liana::{miniscript::bitcoin, signer::HotSigner};
+use liana::{
+    miniscript::bitcoin,
+    signer::{HotSigner, SignerError},
+};
 
 use liana::descriptors::MultipathDescriptor;
 use liana::miniscript::bitcoin::util::bip32::Fingerprint;
 
 pub const DEFAULT_WALLET_NAME: &str = "Liana";
 
+/// Approximate number of seconds in a month, for the verification reminder.
+const SECONDS_PER_MONTH: u32 = 30 * 24 * 60 * 60;
+
+/// Default number of addresses the daemon derives ahead of the last used derivation index
+/// when looking for coins.
+pub const GAP_LIMIT: u32 = 200;
+
 #[derive(Debug)]
 pub struct Wallet {
     pub name: String,
@@ -21,6 +37,22 @@ pub struct Wallet {
     pub keys_aliases: HashMap<Fingerprint, String>,
     pub hardware_wallets: Vec<HardwareWalletConfig>,
     pub signer: Option<Signer>,
+    pub displayed_history_months: Option<u32>,
+    pub last_scan: Option<settings::ScanParameters>,
+    pub display_unit: Unit,
+    pub fiat_currency: Option<String>,
+    pub verification_reminder_months: Option<u32>,
+    pub last_verification: Option<u32>,
+    pub consolidation_suggestion: Option<settings::ConsolidationSuggestion>,
+    /// Monitoring instance: no hot signer is loaded and signing is not offered.
+    pub watch_only: bool,
+    pub confirmation_tiers: settings::ConfirmationTiers,
+    /// The import of the labels of the bitcoind watch-only wallet was already offered.
+    pub backend_labels_offered: bool,
+    /// Labels of the transactions, addresses and coins of the wallet.
+    pub labels: Labels,
+    /// Recipients saved by the user.
+    pub address_book: AddressBook,
 }
 
 impl Wallet {
@@ -31,9 +63,26 @@ impl Wallet {
             keys_aliases: HashMap::new(),
             hardware_wallets: Vec::new(),
             signer: None,
+            displayed_history_months: None,
+            last_scan: None,
+            display_unit: Unit::default(),
+            fiat_currency: None,
+            verification_reminder_months: None,
+            last_verification: None,
+            consolidation_suggestion: None,
+            watch_only: false,
+            confirmation_tiers: settings::ConfirmationTiers::default(),
+            backend_labels_offered: false,
+            labels: Labels::default(),
+            address_book: AddressBook::default(),
         }
     }
 
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
     pub fn with_key_aliases(mut self, aliases: HashMap<Fingerprint, String>) -> Self {
         self.keys_aliases = aliases;
         self
@@ -44,6 +93,69 @@ impl Wallet {
         self
     }
 
+    pub fn with_displayed_history(mut self, months: Option<u32>) -> Self {
+        self.displayed_history_months = months;
+        self
+    }
+
+    pub fn with_last_scan(mut self, last_scan: Option<settings::ScanParameters>) -> Self {
+        self.last_scan = last_scan;
+        self
+    }
+
+    pub fn with_display_unit(mut self, unit: Unit) -> Self {
+        self.display_unit = unit;
+        self
+    }
+
+    pub fn with_fiat_currency(mut self, currency: Option<String>) -> Self {
+        self.fiat_currency = currency;
+        self
+    }
+
+    pub fn with_verification_reminder(
+        mut self,
+        months: Option<u32>,
+        last_verification: Option<u32>,
+    ) -> Self {
+        self.verification_reminder_months = months;
+        self.last_verification = last_verification;
+        self
+    }
+
+    pub fn with_consolidation_suggestion(
+        mut self,
+        suggestion: Option<settings::ConsolidationSuggestion>,
+    ) -> Self {
+        self.consolidation_suggestion = suggestion;
+        self
+    }
+
+    pub fn with_watch_only(mut self, watch_only: bool) -> Self {
+        self.watch_only = watch_only;
+        self
+    }
+
+    pub fn with_confirmation_tiers(mut self, tiers: settings::ConfirmationTiers) -> Self {
+        self.confirmation_tiers = tiers;
+        self
+    }
+
+    pub fn with_backend_labels_offered(mut self, offered: bool) -> Self {
+        self.backend_labels_offered = offered;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_address_book(mut self, address_book: AddressBook) -> Self {
+        self.address_book = address_book;
+        self
+    }
+
     pub fn with_signer(mut self, signer: Signer) -> Self {
         self.signer = Some(signer);
         self
@@ -55,8 +167,10 @@ impl Wallet {
         for (fingerprint, _) in info.primary_path().thresh_origins().1.iter() {
             descriptor_keys.insert(*fingerprint);
         }
-        for (fingerprint, _) in info.recovery_path().1.thresh_origins().1.iter() {
-            descriptor_keys.insert(*fingerprint);
+        for path in info.recovery_paths().values() {
+            for (fingerprint, _) in path.thresh_origins().1.iter() {
+                descriptor_keys.insert(*fingerprint);
+            }
         }
         descriptor_keys
     }
@@ -70,11 +184,49 @@ impl Wallet {
             .to_string()
     }
 
+    /// Parameters of a scan of the chain started now from the given timestamp, with the gap
+    /// limit the daemon is configured with.
+    pub fn scan_parameters(
+        &self,
+        start_timestamp: Option<u32>,
+        gap_limit: u32,
+    ) -> settings::ScanParameters {
+        settings::ScanParameters {
+            descriptor_checksum: self.descriptor_checksum(),
+            gap_limit,
+            start_timestamp,
+        }
+    }
+
+    /// Whether the last scan of the chain may have missed coins of the wallet because
+    /// it was done with a different descriptor or a lower gap limit than the one the daemon is
+    /// now configured with.
+    pub fn rescan_needed(&self, gap_limit: u32) -> bool {
+        self.last_scan
+            .as_ref()
+            .map(|last| {
+                last.descriptor_checksum != self.descriptor_checksum() || last.gap_limit < gap_limit
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether the user should be reminded to check their signing devices and backups at the
+    /// given timestamp. Never the case if no reminder was set.
+    pub fn verification_due(&self, now: u32) -> bool {
+        match (self.verification_reminder_months, self.last_verification) {
+            (Some(months), Some(last)) => {
+                now >= last.saturating_add(months.saturating_mul(SECONDS_PER_MONTH))
+            }
+            _ => false,
+        }
+    }
+
     pub fn load_settings(
         self,
         gui_config: &Config,
         datadir_path: &Path,
         network: bitcoin::Network,
+        mnemonic_password: Option<&str>,
     ) -> Result<Self, WalletError> {
         let gui_config_hws = gui_config
             .hardware_wallets
@@ -84,9 +236,36 @@ impl Wallet {
 
         let mut wallet = match settings::Settings::from_file(datadir_path.to_path_buf(), network) {
             Ok(settings) => {
-                if let Some(wallet_setting) = settings.wallets.first() {
+                if let Some(wallet_setting) = settings.wallet(&self.descriptor_checksum()) {
+                    // Settings written before the scan parameters were tracked only know
+                    // the descriptor the wallet was created with.
+                    let last_scan = wallet_setting.last_scan.clone().unwrap_or_else(|| {
+                        settings::ScanParameters {
+                            descriptor_checksum: wallet_setting.descriptor_checksum.clone(),
+                            gap_limit: GAP_LIMIT,
+                            start_timestamp: None,
+                        }
+                    });
                     self.with_hardware_wallets(wallet_setting.hardware_wallets.clone())
                         .with_key_aliases(wallet_setting.keys_aliases())
+                        .with_displayed_history(wallet_setting.displayed_history_months)
+                        .with_last_scan(Some(last_scan))
+                        .with_display_unit(wallet_setting.display_unit.unwrap_or_default())
+                        .with_fiat_currency(wallet_setting.fiat_currency.clone())
+                        .with_verification_reminder(
+                            wallet_setting.verification_reminder_months,
+                            wallet_setting.last_verification,
+                        )
+                        .with_consolidation_suggestion(wallet_setting.consolidation_suggestion)
+                        .with_name(wallet_setting.name.clone())
+                        .with_watch_only(wallet_setting.watch_only)
+                        .with_confirmation_tiers(
+                            wallet_setting
+                                .confirmation_tiers
+                                .clone()
+                                .unwrap_or_default(),
+                        )
+                        .with_backend_labels_offered(wallet_setting.backend_labels_offered)
                 } else {
                     self.with_hardware_wallets(gui_config_hws)
                 }
@@ -95,16 +274,39 @@ impl Wallet {
             Err(e) => return Err(e.into()),
         };
 
-        let hot_signers = match HotSigner::from_datadir(datadir_path, network) {
+        wallet = wallet
+            .with_labels(Labels::from_file_or_default(
+                datadir_path.to_path_buf(),
+                network,
+            )?)
+            .with_address_book(AddressBook::from_file_or_default(
+                datadir_path.to_path_buf(),
+                network,
+            )?);
+
+        // A watch-only wallet does not even read the mnemonics of the datadir.
+        if wallet.watch_only {
+            return Ok(wallet);
+        }
+
+        let hot_signers = match mnemonic_password {
+            Some(password) => HotSigner::from_datadir_encrypted(datadir_path, network, password),
+            None => HotSigner::from_datadir(datadir_path, network),
+        };
+        let hot_signers = match hot_signers {
             Ok(signers) => signers,
             Err(e) => match e {
-                liana::signer::SignerError::MnemonicStorage(e) => {
+                SignerError::MnemonicStorage(e) => {
                     if e.kind() == std::io::ErrorKind::NotFound {
                         Vec::new()
                     } else {
                         return Err(WalletError::HotSigner(e.to_string()));
                     }
                 }
+                SignerError::EncryptedMnemonic => {
+                    return Err(WalletError::MnemonicPasswordRequired)
+                }
+                SignerError::MnemonicDecryption => return Err(WalletError::MnemonicDecryption),
                 _ => return Err(WalletError::HotSigner(e.to_string())),
             },
         };
@@ -127,6 +329,12 @@ impl Wallet {
 pub enum WalletError {
     Settings(settings::SettingsError),
     HotSigner(String),
+    /// The mnemonics of the hot signers are encrypted and no password was given.
+    MnemonicPasswordRequired,
+    /// The mnemonics of the hot signers could not be decrypted with the given password.
+    MnemonicDecryption,
+    Labels(LabelsError),
+    AddressBook(AddressBookError),
 }
 
 impl std::fmt::Display for WalletError {
@@ -134,6 +342,15 @@ impl std::fmt::Display for WalletError {
         match self {
             Self::Settings(e) => write!(f, "Failed to load settings: {}", e),
             Self::HotSigner(e) => write!(f, "Failed to load hot signer: {}", e),
+            Self::MnemonicPasswordRequired => {
+                write!(f, "A password is needed to decrypt the hot signer mnemonic")
+            }
+            Self::MnemonicDecryption => write!(
+                f,
+                "Failed to decrypt the hot signer mnemonic: wrong password or corrupted file"
+            ),
+            Self::Labels(e) => write!(f, "Failed to load labels: {}", e),
+            Self::AddressBook(e) => write!(f, "Failed to load address book: {}", e),
         }
     }
 }
@@ -143,3 +360,15 @@ impl From<settings::SettingsError> for WalletError {
         WalletError::Settings(error)
     }
 }
+
+impl From<LabelsError> for WalletError {
+    fn from(error: LabelsError) -> Self {
+        WalletError::Labels(error)
+    }
+}
+
+impl From<AddressBookError> for WalletError {
+    fn from(error: AddressBookError) -> Self {
+        WalletError::AddressBook(error)
+    }
+}
@@ -95,6 +95,22 @@ impl<C: Client + Debug> Daemon for Lianad<C> {
         )
     }
 
+    fn create_sweep_tx(
+        &self,
+        address: &Address,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.call(
+            "createsweep",
+            Some(vec![
+                json!(address),
+                json!(coins_outpoints),
+                json!(feerate_vb),
+            ]),
+        )
+    }
+
     fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError> {
         let spend_tx = base64::encode(consensus::serialize(psbt));
         let _res: serde_json::value::Value = self.call("updatespend", Some(vec![spend_tx]))?;
@@ -107,6 +123,10 @@ impl<C: Client + Debug> Daemon for Lianad<C> {
         Ok(())
     }
 
+    fn cleanup_spend_txs(&self) -> Result<CleanupSpendsResult, DaemonError> {
+        self.call("cleanupspendtxs", Option::<Request>::None)
+    }
+
     fn broadcast_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError> {
         let _res: serde_json::value::Value =
             self.call("broadcastspend", Some(vec![txid.to_string()]))?;
@@ -118,6 +138,11 @@ impl<C: Client + Debug> Daemon for Lianad<C> {
         Ok(())
     }
 
+    fn start_rescan_from_height(&self, height: i32) -> Result<u32, DaemonError> {
+        let res: StartRescanResult = self.call("startrescanfromheight", Some(vec![height]))?;
+        Ok(res.timestamp)
+    }
+
     fn list_confirmed_txs(
         &self,
         start: u32,
@@ -134,13 +159,59 @@ impl<C: Client + Debug> Daemon for Lianad<C> {
         self.call("listtransactions", Some(vec![txids]))
     }
 
-    fn create_recovery(&self, address: Address, feerate_vb: u64) -> Result<Psbt, DaemonError> {
+    fn create_recovery(
+        &self,
+        address: Address,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError> {
+        let mut input = vec![json!(address), json!(feerate_vb)];
+        if let Some(timelock) = timelock {
+            input.push(json!(timelock));
+        }
+        let res: CreateSpendResult = self.call("createrecovery", Some(input))?;
+        Ok(res.psbt)
+    }
+
+    fn create_refresh(
+        &self,
+        threshold: u32,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError> {
+        let mut input = vec![json!(threshold), json!(feerate_vb)];
+        if let Some(timelock) = timelock {
+            input.push(json!(timelock));
+        }
+        let res: CreateSpendResult = self.call("createrefresh", Some(input))?;
+        Ok(res.psbt)
+    }
+
+    fn rbf_spend_tx(&self, txid: &Txid, feerate_vb: u64) -> Result<Psbt, DaemonError> {
+        let res: CreateSpendResult =
+            self.call("rbfpsbt", Some(vec![json!(txid), json!(feerate_vb)]))?;
+        Ok(res.psbt)
+    }
+
+    fn create_cpfp(&self, outpoint: &OutPoint, feerate_vb: u64) -> Result<Psbt, DaemonError> {
         let res: CreateSpendResult = self.call(
-            "createrecovery",
-            Some(vec![json!(address), json!(feerate_vb)]),
+            "createcpfp",
+            Some(vec![json!(outpoint.to_string()), json!(feerate_vb)]),
         )?;
         Ok(res.psbt)
     }
+
+    fn check_balance(&self) -> Result<CheckBalanceResult, DaemonError> {
+        self.call("checkbalance", Option::<Request>::None)
+    }
+
+    fn estimate_feerate(&self, conf_targets: &[u16]) -> Result<EstimateFeerateResult, DaemonError> {
+        self.call("estimatefeerate", Some(vec![json!(conf_targets)]))
+    }
+
+    fn list_backend_labels(&self) -> Result<ListBackendLabelsResult, DaemonError> {
+        self.call("listbackendlabels", Option::<Request>::None)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
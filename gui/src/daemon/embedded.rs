@@ -3,6 +3,7 @@ use std::sync::RwLock;
 
 use super::{model::*, Daemon, DaemonError};
 use liana::{
+    commands::ChangeDestination,
     config::Config,
     miniscript::bitcoin::{util::psbt::Psbt, Address, OutPoint, Txid},
     DaemonHandle,
@@ -64,14 +65,14 @@ impl Daemon for EmbeddedDaemon {
     }
 
     fn get_info(&self) -> Result<GetInfoResult, DaemonError> {
-        Ok(self
-            .handle
+        self.handle
             .as_ref()
             .ok_or(DaemonError::NoAnswer)?
             .read()
             .unwrap()
             .control
-            .get_info())
+            .get_info()
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
     }
 
     fn get_new_address(&self) -> Result<GetAddressResult, DaemonError> {
@@ -113,25 +114,25 @@ impl Daemon for EmbeddedDaemon {
         end: u32,
         limit: u64,
     ) -> Result<ListTransactionsResult, DaemonError> {
-        Ok(self
-            .handle
+        self.handle
             .as_ref()
             .ok_or(DaemonError::NoAnswer)?
             .read()
             .unwrap()
             .control
-            .list_confirmed_transactions(start, end, limit))
+            .list_confirmed_transactions(start, end, limit)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
     }
 
     fn list_txs(&self, txids: &[Txid]) -> Result<ListTransactionsResult, DaemonError> {
-        Ok(self
-            .handle
+        self.handle
             .as_ref()
             .ok_or(DaemonError::NoAnswer)?
             .read()
             .unwrap()
             .control
-            .list_transactions(txids))
+            .list_transactions(txids)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
     }
 
     fn create_spend_tx(
@@ -146,7 +147,28 @@ impl Daemon for EmbeddedDaemon {
             .read()
             .unwrap()
             .control
-            .create_spend(destinations, coins_outpoints, feerate_vb)
+            .create_spend(
+                destinations,
+                coins_outpoints,
+                feerate_vb,
+                &ChangeDestination::Auto,
+            )
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+    }
+
+    fn create_sweep_tx(
+        &self,
+        address: &Address,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .create_sweep(address.clone(), coins_outpoints, feerate_vb)
             .map_err(|e| DaemonError::Unexpected(e.to_string()))
     }
 
@@ -172,6 +194,17 @@ impl Daemon for EmbeddedDaemon {
         Ok(())
     }
 
+    fn cleanup_spend_txs(&self) -> Result<CleanupSpendsResult, DaemonError> {
+        Ok(self
+            .handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .cleanup_spends())
+    }
+
     fn broadcast_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError> {
         self.handle
             .as_ref()
@@ -194,15 +227,106 @@ impl Daemon for EmbeddedDaemon {
             .map_err(|e| DaemonError::Unexpected(e.to_string()))
     }
 
-    fn create_recovery(&self, address: Address, feerate_vb: u64) -> Result<Psbt, DaemonError> {
+    fn start_rescan_from_height(&self, height: i32) -> Result<u32, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .start_rescan_from_height(height)
+            .map(|res| res.timestamp)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+    }
+
+    fn create_recovery(
+        &self,
+        address: Address,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .create_recovery(address, feerate_vb, timelock)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+            .map(|res| res.psbt)
+    }
+
+    fn create_refresh(
+        &self,
+        threshold: u32,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .create_refresh(threshold, feerate_vb, timelock)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+            .map(|res| res.psbt)
+    }
+
+    fn rbf_spend_tx(&self, txid: &Txid, feerate_vb: u64) -> Result<Psbt, DaemonError> {
         self.handle
             .as_ref()
             .ok_or(DaemonError::NoAnswer)?
             .read()
             .unwrap()
             .control
-            .create_recovery(address, feerate_vb)
+            .rbf_psbt(txid, feerate_vb)
             .map_err(|e| DaemonError::Unexpected(e.to_string()))
             .map(|res| res.psbt)
     }
+
+    fn create_cpfp(&self, outpoint: &OutPoint, feerate_vb: u64) -> Result<Psbt, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .create_cpfp(outpoint, feerate_vb)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+            .map(|res| res.psbt)
+    }
+
+    fn check_balance(&self) -> Result<CheckBalanceResult, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .check_balance()
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+    }
+
+    fn estimate_feerate(&self, conf_targets: &[u16]) -> Result<EstimateFeerateResult, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .estimate_feerate(conf_targets)
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+    }
+
+    fn list_backend_labels(&self) -> Result<ListBackendLabelsResult, DaemonError> {
+        self.handle
+            .as_ref()
+            .ok_or(DaemonError::NoAnswer)?
+            .read()
+            .unwrap()
+            .control
+            .list_backend_labels()
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))
+    }
 }
@@ -58,18 +58,57 @@ pub trait Daemon: Debug {
         destinations: &HashMap<Address, u64>,
         feerate_vb: u64,
     ) -> Result<model::CreateSpendResult, DaemonError>;
+    fn create_sweep_tx(
+        &self,
+        address: &Address,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<model::CreateSpendResult, DaemonError>;
     fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError>;
     fn delete_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
+    fn cleanup_spend_txs(&self) -> Result<model::CleanupSpendsResult, DaemonError>;
     fn broadcast_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
     fn start_rescan(&self, t: u32) -> Result<(), DaemonError>;
+    /// Returns the date the rescan was started from.
+    fn start_rescan_from_height(&self, height: i32) -> Result<u32, DaemonError>;
     fn list_confirmed_txs(
         &self,
         _start: u32,
         _end: u32,
         _limit: u64,
     ) -> Result<model::ListTransactionsResult, DaemonError>;
-    fn create_recovery(&self, address: Address, feerate_vb: u64) -> Result<Psbt, DaemonError>;
+    fn create_recovery(
+        &self,
+        address: Address,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError>;
+    /// Spend back to the wallet the coins whose recovery path with the given timelock (the one
+    /// available first if None) is available within `threshold` blocks, to restart their
+    /// timelock.
+    fn create_refresh(
+        &self,
+        threshold: u32,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<Psbt, DaemonError>;
+    fn rbf_spend_tx(&self, txid: &Txid, feerate_vb: u64) -> Result<Psbt, DaemonError>;
+    fn create_cpfp(&self, outpoint: &OutPoint, feerate_vb: u64) -> Result<Psbt, DaemonError>;
     fn list_txs(&self, txid: &[Txid]) -> Result<model::ListTransactionsResult, DaemonError>;
+    fn check_balance(&self) -> Result<model::CheckBalanceResult, DaemonError>;
+    fn estimate_feerate(
+        &self,
+        conf_targets: &[u16],
+    ) -> Result<model::EstimateFeerateResult, DaemonError>;
+    /// The labels the wallet of the Bitcoin backend has for the addresses of the wallet.
+    fn list_backend_labels(&self) -> Result<model::ListBackendLabelsResult, DaemonError>;
+
+    /// Get the feerate estimates of the Bitcoin backend for the usual confirmation targets, as
+    /// of the given block height.
+    fn fee_estimates(&self, blockheight: i32) -> Result<model::FeeEstimates, DaemonError> {
+        let res = self.estimate_feerate(&model::FEE_ESTIMATE_CONF_TARGETS)?;
+        Ok(model::FeeEstimates::new(blockheight, res.estimates))
+    }
 
     fn list_spend_transactions(&self) -> Result<Vec<model::SpendTx>, DaemonError> {
         let info = self.get_info()?;
@@ -92,7 +131,12 @@ pub trait Daemon: Debug {
                 .main
                 .partial_spend_info(&tx.psbt)
                 .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
-            spend_txs.push(model::SpendTx::new(tx.psbt, coins, sigs))
+            spend_txs.push(model::SpendTx::new(
+                tx.psbt,
+                coins,
+                sigs,
+                &info.descriptors.main,
+            ))
         }
         Ok(spend_txs)
     }
@@ -142,13 +186,30 @@ pub trait Daemon: Debug {
             }
         }
 
-        let txs = self.list_txs(&txids)?.transactions;
+        self.get_history_txs(&coins, &txids)
+    }
+
+    /// Returns all the transactions of the wallet, the unconfirmed ones first and then the
+    /// confirmed ones from the most recent.
+    fn list_all_history_txs(&self) -> Result<Vec<model::HistoryTransaction>, DaemonError> {
+        let mut txs = self.list_pending_txs()?;
+        txs.extend(self.list_history_txs(0, u32::MAX, u32::MAX.into())?);
+        Ok(txs)
+    }
+
+    /// Returns the given transactions along with the wallet coins they spend and create.
+    fn get_history_txs(
+        &self,
+        coins: &[model::Coin],
+        txids: &[Txid],
+    ) -> Result<Vec<model::HistoryTransaction>, DaemonError> {
+        let txs = self.list_txs(txids)?.transactions;
         Ok(txs
             .into_iter()
             .map(|tx| {
                 let mut tx_coins = Vec::new();
                 let mut change_indexes = Vec::new();
-                for coin in &coins {
+                for coin in coins {
                     if coin.outpoint.txid == tx.tx.txid() {
                         change_indexes.push(coin.outpoint.vout as usize)
                     } else if tx
@@ -1,10 +1,24 @@
+use std::collections::{BTreeMap, HashMap};
+
 pub use liana::{
     commands::{
-        CreateSpendResult, GetAddressResult, GetInfoResult, ListCoinsEntry, ListCoinsResult,
-        ListSpendEntry, ListSpendResult, ListTransactionsResult, TransactionInfo,
+        AddressLabel, CheckBalanceResult, CleanupSpendsResult, CreateSpendResult,
+        EstimateFeerateResult, FeerateEstimate, GetAddressResult, GetInfoAddresses, GetInfoResult,
+        ListBackendLabelsResult, ListCoinsEntry, ListCoinsResult, ListSpendEntry, ListSpendResult,
+        ListTransactionsResult, StartRescanResult, TransactionInfo,
+    },
+    descriptors::{
+        LianaDescInfo, MultipathDescriptor, PartialSpendInfo, PathSpendInfo, TapKeyOrigins,
+    },
+    miniscript::bitcoin::{
+        secp256k1,
+        util::{
+            bip32::{ChildNumber, Fingerprint, KeySource},
+            psbt,
+            psbt::Psbt,
+        },
+        Address, Amount, Network, OutPoint, Script, SignedAmount, Transaction, Txid,
     },
-    descriptors::{PartialSpendInfo, PathSpendInfo},
-    miniscript::bitcoin::{util::psbt::Psbt, Amount, Transaction},
 };
 
 pub type Coin = ListCoinsEntry;
@@ -21,15 +35,238 @@ pub fn remaining_sequence(coin: &Coin, blockheight: u32, timelock: u32) -> u32 {
     }
 }
 
+/// Approximate number of blocks mined in a day.
+pub const BLOCKS_PER_DAY: u32 = 144;
+
+/// When the recovery path becomes available for a coin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryStatus {
+    /// The recovery path can be used to spend the coin.
+    Available,
+    /// The recovery path will be available in this number of blocks.
+    InBlocks(u32),
+    /// The coin is unconfirmed: the recovery path will be available this number of blocks after
+    /// it confirms.
+    Pending(u32),
+}
+
+impl RecoveryStatus {
+    /// Returns the status of the recovery path with the given relative timelock for the coin at
+    /// the given tip.
+    pub fn new(coin: &Coin, blockheight: u32, timelock: u32) -> Self {
+        if coin.block_height.is_none() {
+            return Self::Pending(timelock);
+        }
+        match remaining_sequence(coin, blockheight, timelock) {
+            0 => Self::Available,
+            blocks => Self::InBlocks(blocks),
+        }
+    }
+}
+
+/// Approximate number of days it takes to mine the given number of blocks, rounded up.
+pub fn blocks_to_days(blocks: u32) -> u32 {
+    (blocks + BLOCKS_PER_DAY - 1) / BLOCKS_PER_DAY
+}
+
+/// Number of confirmations at the given tip of a transaction confirmed at `height`.
+pub fn confirmations(height: Option<i32>, blockheight: i32) -> u32 {
+    match height {
+        Some(height) if blockheight >= height => (blockheight - height + 1) as u32,
+        _ => 0,
+    }
+}
+
+/// Number of blocks after which a coinbase output may be spent.
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// Returns the number of blocks before an immature coinbase output can be spent, or None if the
+/// coin is not an immature coinbase output.
+pub fn remaining_maturity(coin: &Coin, blockheight: u32) -> Option<u32> {
+    if !coin.is_immature {
+        return None;
+    }
+    // A coinbase output can be spent in a block COINBASE_MATURITY blocks after its own.
+    Some(
+        coin.block_height
+            .map(|h| (h as u32 + COINBASE_MATURITY).saturating_sub(blockheight + 1))
+            .unwrap_or(COINBASE_MATURITY),
+    )
+}
+
+/// Returns the wallet coins spent by the transaction that created the given coin.
+pub fn coin_parents<'a>(coin: &Coin, coins: &'a [Coin]) -> Vec<&'a Coin> {
+    coins
+        .iter()
+        .filter(|c| {
+            c.spend_info
+                .map(|info| info.txid == coin.outpoint.txid)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Returns the confirmed balance after each block height at which it changed, replaying the
+/// confirmed receptions and spends of the coins in block order. Long histories are downsampled
+/// to at most `max_points` points, always keeping the latest balance.
+pub fn balance_history(coins: &[Coin], max_points: usize) -> Vec<(i32, Amount)> {
+    let mut changes: BTreeMap<i32, i64> = BTreeMap::new();
+    for coin in coins {
+        if let Some(height) = coin.block_height {
+            *changes.entry(height).or_default() += coin.amount.to_sat() as i64;
+            if let Some(spend_height) = coin.spend_info.and_then(|info| info.height) {
+                *changes.entry(spend_height).or_default() -= coin.amount.to_sat() as i64;
+            }
+        }
+    }
+
+    let mut balance: i64 = 0;
+    let history: Vec<(i32, Amount)> = changes
+        .into_iter()
+        .map(|(height, change)| {
+            balance += change;
+            (height, Amount::from_sat(balance.max(0) as u64))
+        })
+        .collect();
+
+    if max_points < 2 || history.len() <= max_points {
+        return history;
+    }
+    let step = (history.len() - 1) as f64 / (max_points - 1) as f64;
+    (0..max_points)
+        .map(|i| history[(i as f64 * step).round() as usize])
+        .collect()
+}
+
+/// Returns the feerate in sats/vbyte of a transaction paying the given fee, rounded up.
+pub fn feerate_vb(fee: &Amount, vsize: u64) -> u64 {
+    if vsize == 0 {
+        return 0;
+    }
+    (fee.to_sat() + vsize - 1) / vsize
+}
+
+/// Returns the feerate in sats/vbyte a child transaction of `child_vsize` must pay for the
+/// package of it and its unconfirmed parents, which pay `parents_fee` for `parents_vsize`, to
+/// reach the `target` feerate (Child Pays For Parent). It is never below the target.
+pub fn cpfp_feerate(parents_fee: Amount, parents_vsize: u64, child_vsize: u64, target: u64) -> u64 {
+    let package_fee = target * (parents_vsize + child_vsize);
+    let child_fee = Amount::from_sat(package_fee.saturating_sub(parents_fee.to_sat()));
+    std::cmp::max(feerate_vb(&child_fee, child_vsize), target)
+}
+
+/// Confirmation targets, in blocks, the feerate estimates are queried for.
+pub const FEE_ESTIMATE_CONF_TARGETS: [u16; 4] = [1, 3, 6, 36];
+
+/// Approximate time for a transaction to confirm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationTime {
+    NextBlock,
+    HalfHour,
+    Hour,
+    Hours,
+    MoreThanHours,
+}
+
+impl std::fmt::Display for ConfirmationTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NextBlock => write!(f, "Next block"),
+            Self::HalfHour => write!(f, "~30 minutes"),
+            Self::Hour => write!(f, "~1 hour"),
+            Self::Hours => write!(f, "A few hours"),
+            Self::MoreThanHours => write!(f, "More than a few hours"),
+        }
+    }
+}
+
+/// The feerate estimates of the Bitcoin backend for increasing confirmation targets, as of the
+/// block they were fetched at. They only change with new blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeEstimates {
+    pub blockheight: i32,
+    /// Feerate in sats/vbyte per confirmation target, sorted by target. The targets the backend
+    /// does not have enough data for are left out.
+    estimates: Vec<(u16, u64)>,
+}
+
+impl FeeEstimates {
+    pub fn new(blockheight: i32, estimates: Vec<FeerateEstimate>) -> Self {
+        let mut estimates: Vec<(u16, u64)> = estimates
+            .into_iter()
+            .filter_map(|est| est.feerate.map(|feerate| (est.conf_target, feerate)))
+            .collect();
+        estimates.sort_unstable();
+        Self {
+            blockheight,
+            estimates,
+        }
+    }
+
+    /// The feerate for a transaction to confirm within this number of blocks, from the estimate
+    /// of the largest target not above it. None if the backend gave no such estimate.
+    pub fn feerate(&self, conf_target: u16) -> Option<u64> {
+        self.estimates
+            .iter()
+            .rev()
+            .find(|(target, _)| *target <= conf_target)
+            .map(|(_, feerate)| *feerate)
+    }
+
+    /// The approximate time for a transaction paying this feerate to confirm, from the shortest
+    /// target whose estimate it reaches. None if the backend gave no estimate at all.
+    pub fn confirmation_time(&self, feerate_vb: u64) -> Option<ConfirmationTime> {
+        if self.estimates.is_empty() {
+            return None;
+        }
+        let time = match self
+            .estimates
+            .iter()
+            .find(|(_, estimate)| feerate_vb >= *estimate)
+        {
+            Some((1, _)) => ConfirmationTime::NextBlock,
+            Some((2..=3, _)) => ConfirmationTime::HalfHour,
+            Some((4..=6, _)) => ConfirmationTime::Hour,
+            Some(_) => ConfirmationTime::Hours,
+            None => ConfirmationTime::MoreThanHours,
+        };
+        Some(time)
+    }
+}
+
+/// An unconfirmed transaction creating a coin to be spent: the spend cannot confirm before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnconfirmedParent {
+    pub txid: Txid,
+    /// Unknown if some of its inputs are not coins of the wallet.
+    pub feerate: Option<u64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SpendTx {
     pub coins: Vec<Coin>,
     pub psbt: Psbt,
+    /// Outputs paying to a change address of the wallet.
     pub change_indexes: Vec<usize>,
+    /// Outputs paying to a receive address of the wallet.
+    pub receive_indexes: Vec<usize>,
     pub spend_amount: Amount,
     pub fee_amount: Amount,
+    /// Virtual size of the transaction once all its inputs are satisfied through the spending
+    /// path it was created for.
+    pub vbytes: u64,
     pub status: SpendStatus,
     pub sigs: PartialSpendInfo,
+    pub path: SpendPath,
+}
+
+/// The spending path a transaction is set to use. A recovery path is only available once the
+/// sequence of the inputs is at least its timelock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendPath {
+    Primary,
+    /// The recovery path available after this number of blocks.
+    Recovery(u16),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,17 +278,37 @@ pub enum SpendStatus {
 }
 
 impl SpendTx {
-    pub fn new(psbt: Psbt, coins: Vec<Coin>, sigs: PartialSpendInfo) -> Self {
+    pub fn new(
+        psbt: Psbt,
+        coins: Vec<Coin>,
+        sigs: PartialSpendInfo,
+        desc: &MultipathDescriptor,
+    ) -> Self {
+        let secp = secp256k1::Secp256k1::verification_only();
         let mut change_indexes = Vec::new();
+        let mut receive_indexes = Vec::new();
         let (change_amount, spend_amount) = psbt.unsigned_tx.output.iter().enumerate().fold(
             (Amount::from_sat(0), Amount::from_sat(0)),
-            |(change, spend), (i, output)| {
-                if !psbt.outputs[i].bip32_derivation.is_empty() {
+            |(change, spend), (i, output)| match wallet_derivation(
+                desc,
+                key_sources(
+                    &psbt.outputs[i].bip32_derivation,
+                    &psbt.outputs[i].tap_key_origins,
+                ),
+                &output.script_pubkey,
+                &secp,
+            )
+            .map(|(kind, _)| kind)
+            {
+                Some(OutputKind::Change) => {
                     change_indexes.push(i);
                     (change + Amount::from_sat(output.value), spend)
-                } else {
+                }
+                Some(OutputKind::Receive) => {
+                    receive_indexes.push(i);
                     (change, spend + Amount::from_sat(output.value))
                 }
+                None => (change, spend + Amount::from_sat(output.value)),
             },
         );
 
@@ -71,30 +328,252 @@ impl SpendTx {
                 }
             }
         }
+        // In a collaborative transaction some inputs are not our coins, use the value of the
+        // spent output given in the PSBT.
+        for (txin, psbtin) in psbt.unsigned_tx.input.iter().zip(psbt.inputs.iter()) {
+            if !coins.iter().any(|c| c.outpoint == txin.previous_output) {
+                if let Some(utxo) = &psbtin.witness_utxo {
+                    inputs_amount += Amount::from_sat(utxo.value);
+                }
+            }
+        }
+
+        let path = match sigs.recovery_path() {
+            Some((timelock, _)) => SpendPath::Recovery(timelock),
+            None => SpendPath::Primary,
+        };
+        // The satisfaction of a recovery path is larger than of the primary one.
+        let recovery_timelock = match path {
+            SpendPath::Recovery(timelock) => Some(timelock),
+            SpendPath::Primary => None,
+        };
+        let sat_vbytes = desc
+            .path_sat_vbytes(recovery_timelock, &secp)
+            .unwrap_or_else(|| desc.max_sat_vbytes());
+        let vbytes = (psbt.unsigned_tx.vsize() + sat_vbytes * psbt.unsigned_tx.input.len()) as u64;
 
         Self {
             coins,
             psbt,
             change_indexes,
+            receive_indexes,
             spend_amount,
-            fee_amount: inputs_amount - spend_amount - change_amount,
+            // Unknown if the value of an external input is missing from the PSBT.
+            fee_amount: inputs_amount
+                .checked_sub(spend_amount + change_amount)
+                .unwrap_or_else(|| Amount::from_sat(0)),
+            vbytes,
             status,
             sigs,
+            path,
         }
     }
 
+    /// Returns the feerate in sats/vbyte of the transaction, computed from its size once
+    /// satisfied with signatures of the maximum size: the actual feerate may be slightly higher.
+    pub fn feerate_vb(&self) -> u64 {
+        feerate_vb(&self.fee_amount, self.vbytes)
+    }
+
     /// Returns the path ready if it exists.
     pub fn path_ready(&self) -> Option<&PathSpendInfo> {
         let path = self.sigs.primary_path();
         if path.sigs_count >= path.threshold {
             return Some(path);
         }
-        if let Some(path) = self.sigs.recovery_path() {
-            if path.sigs_count >= path.threshold {
-                return Some(path);
+        self.sigs
+            .recovery_paths()
+            .values()
+            .find(|path| path.sigs_count >= path.threshold)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputKind {
+    Change,
+    Receive,
+}
+
+/// The origins of the keys of a PSBT input or output, for P2WSH as well as Taproot.
+fn key_sources<'a>(
+    bip32_derivation: &'a BTreeMap<secp256k1::PublicKey, KeySource>,
+    tap_key_origins: &'a TapKeyOrigins,
+) -> impl Iterator<Item = &'a KeySource> {
+    bip32_derivation
+        .values()
+        .chain(tap_key_origins.values().map(|(_, source)| source))
+}
+
+/// Whether the script is one of the wallet addresses, and its derivation index. The derivation
+/// paths of the PSBT input or output are not trusted as is: the script is only recognized if the
+/// descriptor derived at the given index gives it back.
+fn wallet_derivation<'a>(
+    desc: &MultipathDescriptor,
+    mut key_sources: impl Iterator<Item = &'a KeySource>,
+    script: &Script,
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+) -> Option<(OutputKind, ChildNumber)> {
+    key_sources.find_map(|(_, path)| {
+        let index = *path.into_iter().last()?;
+        if !index.is_normal() {
+            return None;
+        }
+        if desc.change_descriptor().derive(index, secp).script_pubkey() == *script {
+            Some((OutputKind::Change, index))
+        } else if desc
+            .receive_descriptor()
+            .derive(index, secp)
+            .script_pubkey()
+            == *script
+        {
+            Some((OutputKind::Receive, index))
+        } else {
+            None
+        }
+    })
+}
+
+/// What is about to be signed, to be reviewed by the user before signing a spend.
+#[derive(Debug, Clone)]
+pub struct SpendSummary {
+    pub spend_amount: Amount,
+    pub fee_amount: Amount,
+    pub feerate_vb: u64,
+    pub recipients: Vec<(Address, Amount)>,
+    /// The timelock of the recovery path the transaction will be spent through, if any.
+    pub recovery_timelock: Option<u16>,
+    pub threshold: usize,
+    pub sigs_count: usize,
+    /// The master fingerprints of the keys of the spending path and whether they
+    /// already signed.
+    pub keys: Vec<(Fingerprint, bool)>,
+    /// Number of the spent coins for which the recovery path is already available.
+    pub recoverable_coins: usize,
+    /// The coins spent by the transaction, in the order of its inputs.
+    pub inputs: Vec<SpentInput>,
+}
+
+/// A coin spent by a transaction, as described by its PSBT input.
+#[derive(Debug, Clone)]
+pub struct SpentInput {
+    pub outpoint: OutPoint,
+    /// Unknown if the PSBT input does not include the spent output.
+    pub amount: Option<Amount>,
+    pub address: Option<Address>,
+    /// Whether the coin was received on a change address and its derivation index, if it was
+    /// checked against the wallet descriptor.
+    pub derivation: Option<(bool, ChildNumber)>,
+}
+
+impl SpendTx {
+    pub fn summary(
+        &self,
+        desc: &MultipathDescriptor,
+        network: Network,
+        blockheight: u32,
+    ) -> SpendSummary {
+        let desc_info = desc.info();
+        let recipients = self
+            .psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.change_indexes.contains(i))
+            .filter_map(|(_, output)| {
+                Address::from_script(&output.script_pubkey, network)
+                    .ok()
+                    .map(|addr| (addr, Amount::from_sat(output.value)))
+            })
+            .collect();
+
+        let (recovery_timelock, path_info, sigs) = match self.sigs.recovery_path() {
+            Some((timelock, sigs)) => (
+                Some(timelock),
+                desc_info
+                    .recovery_paths()
+                    .get(&timelock)
+                    .expect("Spend info is only given for the descriptor's recovery paths"),
+                sigs,
+            ),
+            None => (None, desc_info.primary_path(), self.sigs.primary_path()),
+        };
+        let (threshold, origins) = path_info.thresh_origins();
+        let mut keys: Vec<(Fingerprint, bool)> = Vec::new();
+        for (fingerprint, _) in origins {
+            if keys.iter().any(|(fg, _)| *fg == fingerprint) {
+                continue;
+            }
+            let signed = sigs.signed_pubkeys.keys().any(|(fg, _)| *fg == fingerprint);
+            keys.push((fingerprint, signed));
+        }
+
+        let timelock = desc_info.recovery_path().0 as u32;
+        let recoverable_coins = self
+            .coins
+            .iter()
+            .filter(|coin| remaining_sequence(coin, blockheight, timelock) == 0)
+            .count();
+
+        let secp = secp256k1::Secp256k1::verification_only();
+        let inputs = self
+            .psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .zip(self.psbt.inputs.iter())
+            .map(|(txin, psbtin)| {
+                let utxo = psbtin.witness_utxo.as_ref();
+                SpentInput {
+                    outpoint: txin.previous_output,
+                    amount: utxo.map(|utxo| Amount::from_sat(utxo.value)),
+                    address: utxo
+                        .and_then(|utxo| Address::from_script(&utxo.script_pubkey, network).ok()),
+                    derivation: utxo.and_then(|utxo| {
+                        wallet_derivation(
+                            desc,
+                            key_sources(&psbtin.bip32_derivation, &psbtin.tap_key_origins),
+                            &utxo.script_pubkey,
+                            &secp,
+                        )
+                        .map(|(kind, index)| (kind == OutputKind::Change, index))
+                    }),
+                }
+            })
+            .collect();
+
+        SpendSummary {
+            spend_amount: self.spend_amount,
+            fee_amount: self.fee_amount,
+            feerate_vb: self.feerate_vb(),
+            recipients,
+            recovery_timelock,
+            threshold,
+            sigs_count: sigs.sigs_count,
+            keys,
+            recoverable_coins,
+            inputs,
+        }
+    }
+}
+
+impl SpendSummary {
+    /// Update the signatures of the spending path with the ones of the given PSBT.
+    pub fn update_sigs(&mut self, sigs: &PartialSpendInfo) {
+        let path_sigs = if let Some(timelock) = self.recovery_timelock {
+            sigs.recovery_paths().get(&timelock)
+        } else {
+            Some(sigs.primary_path())
+        };
+        if let Some(path_sigs) = path_sigs {
+            self.sigs_count = path_sigs.sigs_count;
+            for (fingerprint, signed) in self.keys.iter_mut() {
+                *signed = path_sigs
+                    .signed_pubkeys
+                    .keys()
+                    .any(|(fg, _)| fg == fingerprint);
             }
         }
-        None
     }
 }
 
@@ -155,4 +634,563 @@ impl HistoryTransaction {
     pub fn is_external(&self) -> bool {
         self.coins.is_empty()
     }
+
+    /// Whether this transaction spends our coins and has enough confirmations to be considered
+    /// final by the daemon.
+    pub fn is_settled(&self) -> bool {
+        !self.is_external()
+            && self
+                .coins
+                .iter()
+                .all(|coin| coin.spend_info.map(|info| info.settled).unwrap_or(false))
+    }
+
+    /// The transactions spending our coins that were replaced by this one, for instance through
+    /// RBF.
+    pub fn replaced_txids(&self) -> Vec<Txid> {
+        let txid = self.tx.txid();
+        let mut replaced: Vec<Txid> = self
+            .coins
+            .iter()
+            .filter_map(|coin| coin.spend_info)
+            .filter(|info| info.txid == txid)
+            .filter_map(|info| info.replaced_txid)
+            .collect();
+        replaced.sort();
+        replaced.dedup();
+        replaced
+    }
+
+    /// Returns the feerate in sats/vbyte of the transaction if we know the amount of its
+    /// inputs, which is only the case for transactions spending our coins.
+    pub fn feerate_vb(&self) -> Option<u64> {
+        self.fee_amount
+            .map(|fee| feerate_vb(&fee, self.tx.vsize() as u64))
+    }
+
+    /// The change of the balance of the wallet caused by this transaction, fee included.
+    pub fn net_amount(&self) -> SignedAmount {
+        let spent: u64 = self.coins.iter().map(|coin| coin.amount.to_sat()).sum();
+        SignedAmount::from_sat(self.incoming_amount.to_sat() as i64 - spent as i64)
+    }
+}
+
+/// A transaction of the books of the user, as listed in the imported CSV file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedTransaction {
+    pub txid: Txid,
+    pub amount: Amount,
+}
+
+/// The result of the comparison of the expected transactions with the wallet history.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub matched: Vec<ExpectedTransaction>,
+    /// Expected transactions found in the wallet with another amount, along with this amount.
+    pub mismatched: Vec<(ExpectedTransaction, Amount)>,
+    /// Expected transactions the wallet does not know about.
+    pub missing: Vec<ExpectedTransaction>,
+    /// Transactions of the wallet absent from the expected ones.
+    pub unexpected: Vec<ExpectedTransaction>,
+}
+
+impl Reconciliation {
+    pub fn is_reconciled(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// The amount a transaction of the history is recorded with: the amount received for a
+/// deposit, the amount sent to others for a spend.
+fn history_amount(tx: &HistoryTransaction) -> Amount {
+    if tx.is_external() {
+        tx.incoming_amount
+    } else {
+        tx.outgoing_amount
+    }
+}
+
+/// Compare the transactions expected by the user with the wallet history.
+pub fn reconcile(
+    expected: &[ExpectedTransaction],
+    history: &[HistoryTransaction],
+) -> Reconciliation {
+    let actual: HashMap<Txid, Amount> = history
+        .iter()
+        .map(|tx| (tx.tx.txid(), history_amount(tx)))
+        .collect();
+    let mut reconciliation = Reconciliation::default();
+    for entry in expected {
+        match actual.get(&entry.txid) {
+            Some(amount) if *amount == entry.amount => reconciliation.matched.push(entry.clone()),
+            Some(amount) => reconciliation.mismatched.push((entry.clone(), *amount)),
+            None => reconciliation.missing.push(entry.clone()),
+        }
+    }
+    reconciliation.unexpected = history
+        .iter()
+        .filter(|tx| !expected.iter().any(|entry| entry.txid == tx.tx.txid()))
+        .map(|tx| ExpectedTransaction {
+            txid: tx.tx.txid(),
+            amount: history_amount(tx),
+        })
+        .collect();
+    reconciliation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::{
+        commands::LCSpendInfo,
+        miniscript::bitcoin::{
+            util::bip32::{ChildNumber, DerivationPath},
+            OutPoint, PackedLockTime, Sequence, TxIn, TxOut,
+        },
+    };
+    use std::str::FromStr;
+
+    const DESC: &str = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+
+    fn coin(vout: u32, sats: u64, height: Option<i32>, spend_height: Option<i32>) -> Coin {
+        let outpoint = OutPoint::from_str(&format!(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:{}",
+            vout
+        ))
+        .unwrap();
+        Coin {
+            amount: Amount::from_sat(sats),
+            outpoint,
+            block_height: height,
+            is_immature: false,
+            is_replaceable: false,
+            spend_info: spend_height.map(|h| LCSpendInfo {
+                txid: outpoint.txid,
+                height: Some(h),
+                settled: false,
+                replaced_txid: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_replaced_txids() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn::default()],
+            output: vec![],
+        };
+        let replaced =
+            Txid::from_str("4753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810")
+                .unwrap();
+        let mut coins = vec![
+            coin(0, 1_000, Some(10), Some(20)),
+            coin(1, 500, Some(20), Some(20)),
+        ];
+        for coin in &mut coins {
+            coin.spend_info = Some(LCSpendInfo {
+                txid: tx.txid(),
+                height: Some(20),
+                settled: false,
+                replaced_txid: Some(replaced),
+            });
+        }
+        let history_tx = HistoryTransaction::new(tx.clone(), Some(20), None, coins.clone(), vec![]);
+        assert_eq!(history_tx.replaced_txids(), vec![replaced]);
+
+        // A transaction which was not the spend of a replaced one.
+        coins[0].spend_info.as_mut().unwrap().replaced_txid = None;
+        coins[1].spend_info = None;
+        let history_tx = HistoryTransaction::new(tx, Some(20), None, coins, vec![]);
+        assert!(history_tx.replaced_txids().is_empty());
+    }
+
+    #[test]
+    fn test_confirmations() {
+        assert_eq!(confirmations(None, 100), 0);
+        assert_eq!(confirmations(Some(100), 100), 1);
+        assert_eq!(confirmations(Some(95), 100), 6);
+        // The tip may lag behind the height of a transaction just fetched.
+        assert_eq!(confirmations(Some(101), 100), 0);
+    }
+
+    #[test]
+    fn test_recovery_status() {
+        assert_eq!(
+            RecoveryStatus::new(&coin(0, 1_000, None, None), 100, 10),
+            RecoveryStatus::Pending(10)
+        );
+        assert_eq!(
+            RecoveryStatus::new(&coin(0, 1_000, Some(95), None), 100, 10),
+            RecoveryStatus::InBlocks(5)
+        );
+        assert_eq!(
+            RecoveryStatus::new(&coin(0, 1_000, Some(80), None), 100, 10),
+            RecoveryStatus::Available
+        );
+
+        assert_eq!(blocks_to_days(0), 0);
+        assert_eq!(blocks_to_days(1), 1);
+        assert_eq!(blocks_to_days(144), 1);
+        assert_eq!(blocks_to_days(145), 2);
+    }
+
+    #[test]
+    fn test_balance_history() {
+        let coins = vec![
+            coin(0, 1_000, Some(10), Some(20)),
+            coin(1, 500, Some(20), None),
+            coin(2, 300, Some(15), None),
+            // Unconfirmed coins are not part of the history.
+            coin(3, 10_000, None, None),
+        ];
+        assert_eq!(
+            balance_history(&coins, 10),
+            vec![
+                (10, Amount::from_sat(1_000)),
+                (15, Amount::from_sat(1_300)),
+                (20, Amount::from_sat(800)),
+            ]
+        );
+
+        let coins: Vec<Coin> = (0..100).map(|i| coin(i, 1, Some(i as i32), None)).collect();
+        let history = balance_history(&coins, 10);
+        assert_eq!(history.len(), 10);
+        assert_eq!(history[0], (0, Amount::from_sat(1)));
+        assert_eq!(history[9], (99, Amount::from_sat(100)));
+    }
+
+    #[test]
+    fn test_spend_tx_wallet_outputs() {
+        let desc = MultipathDescriptor::from_str(DESC).unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        let index = ChildNumber::from_normal_idx(3).unwrap();
+        let change_script = desc
+            .change_descriptor()
+            .derive(index, &secp)
+            .script_pubkey();
+        let receive_script = desc
+            .receive_descriptor()
+            .derive(index, &secp)
+            .script_pubkey();
+        let other_script = desc
+            .receive_descriptor()
+            .derive(ChildNumber::from_normal_idx(4).unwrap(), &secp)
+            .script_pubkey();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: coin(0, 0, None, None).outpoint,
+                ..Default::default()
+            }],
+            output: vec![change_script, receive_script, other_script, Script::new()]
+                .into_iter()
+                .map(|script_pubkey| TxOut {
+                    value: 1_000,
+                    script_pubkey,
+                })
+                .collect(),
+        })
+        .unwrap();
+        // All the outputs but the last claim a derivation at index 3: it must not be enough to
+        // make an output of the third party look like it belongs to the wallet.
+        let key = secp256k1::PublicKey::from_str(
+            "023a664c5617412f0b292665b1fd9d766456a7a3b1614c7e7c5f411200ff1958ef",
+        )
+        .unwrap();
+        for output in psbt.outputs.iter_mut().take(3) {
+            output.bip32_derivation.insert(
+                key,
+                (
+                    Fingerprint::from_str("abcdef01").unwrap(),
+                    DerivationPath::from_str("m/1/3").unwrap(),
+                ),
+            );
+        }
+
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(
+            psbt.clone(),
+            vec![coin(0, 5_000, Some(1), None)],
+            sigs,
+            &desc,
+        );
+        assert_eq!(tx.path, SpendPath::Primary);
+        assert_eq!(tx.change_indexes, vec![0]);
+        assert_eq!(tx.receive_indexes, vec![1]);
+        assert_eq!(tx.spend_amount, Amount::from_sat(3_000));
+        assert_eq!(tx.fee_amount, Amount::from_sat(1_000));
+        // Spent through the primary path, the transaction is smaller than in the worst case.
+        let max_vbytes = tx.psbt.unsigned_tx.vsize() + desc.max_sat_vbytes();
+        assert!((tx.vbytes as usize) < max_vbytes);
+
+        // With a sequence enabling it, the transaction uses the recovery path.
+        psbt.unsigned_tx.input[0].sequence = Sequence::from_height(10_000);
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(psbt, vec![coin(0, 5_000, Some(1), None)], sigs, &desc);
+        assert_eq!(tx.path, SpendPath::Recovery(10_000));
+    }
+
+    #[test]
+    fn test_spend_tx_external_inputs() {
+        let desc = MultipathDescriptor::from_str(DESC).unwrap();
+        let external_op = OutPoint::from_str(
+            "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3:1",
+        )
+        .unwrap();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![coin(0, 0, None, None).outpoint, external_op]
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: 14_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        let coins = vec![coin(0, 5_000, Some(1), None)];
+
+        // The value of the input of the other participant is taken from the PSBT.
+        psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: 10_000,
+            script_pubkey: Script::new(),
+        });
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(psbt.clone(), coins.clone(), sigs, &desc);
+        assert_eq!(tx.spend_amount, Amount::from_sat(14_000));
+        assert_eq!(tx.fee_amount, Amount::from_sat(1_000));
+
+        // Without it the fee is unknown.
+        psbt.inputs[1].witness_utxo = None;
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(psbt, coins, sigs, &desc);
+        assert_eq!(tx.fee_amount, Amount::from_sat(0));
+    }
+
+    #[test]
+    fn test_spend_summary_inputs() {
+        let desc = MultipathDescriptor::from_str(DESC).unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        let index = ChildNumber::from_normal_idx(3).unwrap();
+        let receive_script = desc
+            .receive_descriptor()
+            .derive(index, &secp)
+            .script_pubkey();
+        let external_op = OutPoint::from_str(
+            "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3:1",
+        )
+        .unwrap();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![coin(0, 0, None, None).outpoint, external_op]
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: 14_000,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap();
+        // Both inputs claim a derivation at the receive index 3, only the first one pays to it.
+        let key = secp256k1::PublicKey::from_str(
+            "023a664c5617412f0b292665b1fd9d766456a7a3b1614c7e7c5f411200ff1958ef",
+        )
+        .unwrap();
+        for (input, script_pubkey) in psbt
+            .inputs
+            .iter_mut()
+            .zip(vec![receive_script.clone(), Script::new()])
+        {
+            input.witness_utxo = Some(TxOut {
+                value: 10_000,
+                script_pubkey,
+            });
+            input.bip32_derivation.insert(
+                key,
+                (
+                    Fingerprint::from_str("abcdef01").unwrap(),
+                    DerivationPath::from_str("m/0/3").unwrap(),
+                ),
+            );
+        }
+
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(
+            psbt.clone(),
+            vec![coin(0, 10_000, Some(1), None)],
+            sigs,
+            &desc,
+        );
+        let summary = tx.summary(&desc, Network::Testnet, 1);
+        assert_eq!(summary.inputs.len(), 2);
+        assert_eq!(summary.inputs[0].outpoint, coin(0, 0, None, None).outpoint);
+        assert_eq!(summary.inputs[0].amount, Some(Amount::from_sat(10_000)));
+        assert_eq!(
+            summary.inputs[0].address,
+            Address::from_script(&receive_script, Network::Testnet).ok()
+        );
+        assert_eq!(summary.inputs[0].derivation, Some((false, index)));
+        assert_eq!(summary.inputs[1].outpoint, external_op);
+        assert_eq!(summary.inputs[1].derivation, None);
+
+        // Without the spent output, only the outpoint is known.
+        psbt.inputs[1].witness_utxo = None;
+        let sigs = desc.partial_spend_info(&psbt).unwrap();
+        let tx = SpendTx::new(psbt, vec![coin(0, 10_000, Some(1), None)], sigs, &desc);
+        let summary = tx.summary(&desc, Network::Testnet, 1);
+        assert_eq!(summary.inputs[1].amount, None);
+        assert_eq!(summary.inputs[1].address, None);
+    }
+
+    fn history_tx(value: u64, deposit: bool) -> HistoryTransaction {
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: Vec::new(),
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        };
+        let change_indexes = if deposit { vec![0] } else { Vec::new() };
+        HistoryTransaction::new(tx, Some(1), Some(1), Vec::new(), change_indexes)
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let deposit = history_tx(10_000, true);
+        let other_deposit = history_tx(20_000, true);
+        let unexpected = history_tx(30_000, true);
+        let expected = vec![
+            ExpectedTransaction {
+                txid: deposit.tx.txid(),
+                amount: Amount::from_sat(10_000),
+            },
+            ExpectedTransaction {
+                txid: other_deposit.tx.txid(),
+                amount: Amount::from_sat(25_000),
+            },
+            ExpectedTransaction {
+                txid: coin(0, 0, None, None).outpoint.txid,
+                amount: Amount::from_sat(1_000),
+            },
+        ];
+
+        let reconciliation = reconcile(
+            &expected,
+            &[deposit.clone(), other_deposit, unexpected.clone()],
+        );
+        assert!(!reconciliation.is_reconciled());
+        assert_eq!(reconciliation.matched, vec![expected[0].clone()]);
+        assert_eq!(
+            reconciliation.mismatched,
+            vec![(expected[1].clone(), Amount::from_sat(20_000))]
+        );
+        assert_eq!(reconciliation.missing, vec![expected[2].clone()]);
+        assert_eq!(
+            reconciliation.unexpected,
+            vec![ExpectedTransaction {
+                txid: unexpected.tx.txid(),
+                amount: Amount::from_sat(30_000),
+            }]
+        );
+
+        assert!(reconcile(&expected[..1], &[deposit]).is_reconciled());
+    }
+
+    #[test]
+    fn test_cpfp_feerate() {
+        // A 200 vb parent paying 1 sat/vb and a 100 vb child, to confirm at 10 sats/vb: the
+        // package must pay 3000 sats, 2800 of which by the child.
+        assert_eq!(cpfp_feerate(Amount::from_sat(200), 200, 100, 10), 28);
+        // A parent paying more than the target does not lower the feerate of the child.
+        assert_eq!(cpfp_feerate(Amount::from_sat(10_000), 200, 100, 10), 10);
+        assert_eq!(cpfp_feerate(Amount::from_sat(201), 200, 100, 10), 28);
+    }
+
+    #[test]
+    fn test_confirmation_time() {
+        let estimate = |conf_target, feerate| FeerateEstimate {
+            conf_target,
+            feerate,
+        };
+        let estimates = FeeEstimates::new(
+            100,
+            vec![
+                estimate(6, Some(10)),
+                estimate(1, Some(30)),
+                estimate(3, Some(20)),
+                estimate(36, None),
+            ],
+        );
+        assert_eq!(
+            estimates.confirmation_time(45),
+            Some(ConfirmationTime::NextBlock)
+        );
+        assert_eq!(
+            estimates.confirmation_time(30),
+            Some(ConfirmationTime::NextBlock)
+        );
+        assert_eq!(
+            estimates.confirmation_time(29),
+            Some(ConfirmationTime::HalfHour)
+        );
+        assert_eq!(
+            estimates.confirmation_time(10),
+            Some(ConfirmationTime::Hour)
+        );
+        assert_eq!(
+            estimates.confirmation_time(9),
+            Some(ConfirmationTime::MoreThanHours)
+        );
+
+        // Only the known estimates are used.
+        let estimates = FeeEstimates::new(100, vec![estimate(1, None), estimate(36, Some(5))]);
+        assert_eq!(
+            estimates.confirmation_time(50),
+            Some(ConfirmationTime::Hours)
+        );
+        assert_eq!(
+            estimates.confirmation_time(4),
+            Some(ConfirmationTime::MoreThanHours)
+        );
+        assert_eq!(FeeEstimates::new(100, vec![]).confirmation_time(50), None);
+    }
+
+    #[test]
+    fn fee_estimates_feerate() {
+        let estimate = |conf_target, feerate| FeerateEstimate {
+            conf_target,
+            feerate,
+        };
+        let estimates = FeeEstimates::new(
+            100,
+            vec![
+                estimate(1, Some(30)),
+                estimate(3, None),
+                estimate(6, Some(10)),
+                estimate(36, Some(2)),
+            ],
+        );
+        assert_eq!(estimates.feerate(1), Some(30));
+        assert_eq!(estimates.feerate(3), Some(30));
+        assert_eq!(estimates.feerate(6), Some(10));
+        assert_eq!(estimates.feerate(100), Some(2));
+        assert_eq!(estimates.feerate(0), None);
+        assert_eq!(FeeEstimates::new(100, vec![]).feerate(6), None);
+    }
 }
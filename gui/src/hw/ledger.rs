@@ -0,0 +1,176 @@
+//! Ledger Bitcoin app, driven through its client library.
+//!
+//! The versions of async-hwi built against our version of rust-bitcoin cannot ask the device to
+//! display an address, and do not give access to the underlying client. This implements the
+//! `HWI` interface the same way async-hwi does while keeping the client at hand to display the
+//! addresses of the wallet.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::str::FromStr;
+
+use async_hwi::{
+    ledger::{extract_keys_and_template, TransportTcp},
+    DeviceKind, Error as HWIError, Version as DeviceVersion, HWI,
+};
+use async_trait::async_trait;
+use ledger_bitcoin_client::{
+    apdu::{APDUCommand, StatusWord},
+    async_client::{BitcoinClient, Transport},
+    wallet::Version,
+    WalletPolicy,
+};
+use ledger_transport_hid::TransportNativeHID;
+use liana::miniscript::bitcoin::util::{
+    bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint},
+    psbt::Psbt,
+};
+
+use super::AddressDisplay;
+
+pub use ledger_transport_hid::hidapi::{DeviceInfo, HidApi};
+
+pub struct Ledger<T: Transport> {
+    client: BitcoinClient<T>,
+    wallet: Option<(WalletPolicy, Option<[u8; 32]>)>,
+    kind: DeviceKind,
+}
+
+impl<T: Transport> Ledger<T> {
+    pub fn load_wallet(
+        &mut self,
+        name: impl Into<String>,
+        policy: &str,
+        hmac: Option<[u8; 32]>,
+    ) -> Result<(), HWIError> {
+        let (descriptor_template, keys) = extract_keys_and_template(policy)?;
+        let wallet = WalletPolicy::new(name.into(), Version::V2, descriptor_template, keys);
+        self.wallet = Some((wallet, hmac));
+        Ok(())
+    }
+}
+
+impl<T: Transport> std::fmt::Debug for Ledger<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ledger").field("kind", &self.kind).finish()
+    }
+}
+
+#[async_trait]
+impl<T: Transport + Sync + Send> HWI for Ledger<T> {
+    fn device_kind(&self) -> DeviceKind {
+        self.kind
+    }
+
+    async fn get_version(&self) -> Result<DeviceVersion, HWIError> {
+        let (_, version, _) = self.client.get_version().await?;
+        DeviceVersion::from_str(&version)
+    }
+
+    async fn is_connected(&self) -> Result<(), HWIError> {
+        self.client.get_master_fingerprint().await?;
+        Ok(())
+    }
+
+    async fn get_master_fingerprint(&self) -> Result<Fingerprint, HWIError> {
+        Ok(self.client.get_master_fingerprint().await?)
+    }
+
+    async fn get_extended_pubkey(
+        &self,
+        path: &DerivationPath,
+        display: bool,
+    ) -> Result<ExtendedPubKey, HWIError> {
+        Ok(self.client.get_extended_pubkey(path, display).await?)
+    }
+
+    async fn register_wallet(
+        &self,
+        name: &str,
+        policy: &str,
+    ) -> Result<Option<[u8; 32]>, HWIError> {
+        let (descriptor_template, keys) = extract_keys_and_template(policy)?;
+        let wallet = WalletPolicy::new(name.to_string(), Version::V2, descriptor_template, keys);
+        let (_id, hmac) = self.client.register_wallet(&wallet).await?;
+        Ok(Some(hmac))
+    }
+
+    async fn sign_tx(&self, psbt: &mut Psbt) -> Result<(), HWIError> {
+        // Ledger cannot sign without the wallet policy.
+        let (policy, hmac) = self.wallet.as_ref().ok_or(HWIError::UnimplementedMethod)?;
+        let sigs = self.client.sign_psbt(psbt, policy, hmac.as_ref()).await?;
+        for (i, key, sig) in sigs {
+            let input = psbt.inputs.get_mut(i).ok_or(HWIError::DeviceDidNotSign)?;
+            input.partial_sigs.insert(key, sig);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Transport + Sync + Send> AddressDisplay for Ledger<T> {
+    async fn display_address(&self, index: ChildNumber) -> Result<(), HWIError> {
+        // The device can only derive the addresses of a wallet it registered.
+        let (policy, hmac) = self.wallet.as_ref().ok_or(HWIError::UnimplementedMethod)?;
+        self.client
+            .get_wallet_address(policy, hmac.as_ref(), false, index.into(), true)
+            .await?;
+        Ok(())
+    }
+}
+
+impl Ledger<TransportHID> {
+    pub fn enumerate(api: &HidApi) -> impl Iterator<Item = &DeviceInfo> {
+        TransportNativeHID::list_ledgers(api)
+    }
+
+    pub fn connect(api: &HidApi, device: &DeviceInfo) -> Result<Self, HWIError> {
+        let hid =
+            TransportNativeHID::open_device(api, device).map_err(|_| HWIError::DeviceNotFound)?;
+        Ok(Ledger {
+            client: BitcoinClient::new(TransportHID(hid)),
+            wallet: None,
+            kind: DeviceKind::Ledger,
+        })
+    }
+}
+
+pub type LedgerSimulator = Ledger<TransportTcp>;
+
+impl LedgerSimulator {
+    pub async fn try_connect() -> Result<Self, HWIError> {
+        let transport = TransportTcp::new()
+            .await
+            .map_err(|_| HWIError::DeviceNotFound)?;
+        Ok(Ledger {
+            client: BitcoinClient::new(transport),
+            wallet: None,
+            kind: DeviceKind::LedgerSimulator,
+        })
+    }
+}
+
+/// Transport with the Ledger device.
+pub struct TransportHID(TransportNativeHID);
+
+#[async_trait]
+impl Transport for TransportHID {
+    type Error = Box<dyn Error>;
+    async fn exchange(&self, cmd: &APDUCommand) -> Result<(StatusWord, Vec<u8>), Self::Error> {
+        self.0
+            .exchange(&ledger_apdu::APDUCommand {
+                ins: cmd.ins,
+                cla: cmd.cla,
+                p1: cmd.p1,
+                p2: cmd.p2,
+                data: cmd.data.clone(),
+            })
+            .map(|answer| {
+                (
+                    StatusWord::try_from(answer.retcode()).unwrap_or(StatusWord::Unknown),
+                    answer.data().to_vec(),
+                )
+            })
+            .map_err(|e| e.into())
+    }
+}
@@ -1,13 +1,20 @@
+mod ledger;
+
+use std::fmt::Debug;
 use std::sync::Arc;
 
-use async_hwi::{ledger, specter, DeviceKind, Error as HWIError, Version, HWI};
+use async_hwi::{specter, DeviceKind, Error as HWIError, Version, HWI};
+use async_trait::async_trait;
 use liana::miniscript::bitcoin::{
     hashes::hex::{FromHex, ToHex},
-    util::bip32::Fingerprint,
+    util::bip32::{ChildNumber, Fingerprint},
 };
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
+const TAPROOT_UNSUPPORTED: &str = "Taproot is not supported by this device";
+const LEDGER_TAPROOT_UNSUPPORTED: &str = "Minimal supported app version for Taproot is 2.2.0";
+
 #[derive(Debug, Clone)]
 pub enum HardwareWallet {
     Unsupported {
@@ -17,6 +24,8 @@ pub enum HardwareWallet {
     },
     Supported {
         device: Arc<dyn HWI + Send + Sync>,
+        /// The same device, if it can show the addresses of the wallet.
+        address_display: Option<Arc<dyn AddressDisplay + Send + Sync>>,
         kind: DeviceKind,
         fingerprint: Fingerprint,
         version: Option<Version>,
@@ -30,6 +39,7 @@ impl HardwareWallet {
         let version = device.get_version().await.ok();
         Ok(Self::Supported {
             device,
+            address_display: None,
             kind,
             fingerprint,
             version,
@@ -81,30 +91,79 @@ impl HardwareWalletConfig {
 pub async fn list_hardware_wallets(
     cfg: &[HardwareWalletConfig],
     wallet: Option<(&str, &str)>,
+    taproot: bool,
 ) -> Vec<HardwareWallet> {
+    list_hardware_wallets_with_errors(cfg, wallet, taproot)
+        .await
+        .0
+}
+
+/// A device which can show on its screen the addresses of the wallet it loaded.
+#[async_trait]
+pub trait AddressDisplay: Debug {
+    /// Show the receive address at this derivation index.
+    async fn display_address(&self, index: ChildNumber) -> Result<(), HWIError>;
+}
+
+/// Ask the device to display the receive address of the wallet at this derivation index, for the
+/// user to check it against the address displayed by the application. Ledger devices must have
+/// loaded the wallet, see `list_hardware_wallets`.
+pub async fn display_address(
+    device: Arc<dyn AddressDisplay + Send + Sync>,
+    index: ChildNumber,
+) -> Result<(), HWIError> {
+    device.display_address(index).await
+}
+
+/// List the connected hardware wallets along with the errors encountered while trying to
+/// connect to the devices (insufficient permissions, locked device, ..).
+/// If `taproot` is set, the devices that can't sign for a Taproot descriptor are listed as
+/// unsupported.
+pub async fn list_hardware_wallets_with_errors(
+    cfg: &[HardwareWalletConfig],
+    wallet: Option<(&str, &str)>,
+    taproot: bool,
+) -> (Vec<HardwareWallet>, Vec<String>) {
     let mut hws: Vec<HardwareWallet> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
     match specter::SpecterSimulator::try_connect().await {
         Ok(device) => match HardwareWallet::new(Arc::new(device)).await {
+            // Specter does not support Taproot miniscript.
+            Ok(hw) if taproot => hws.push(HardwareWallet::Unsupported {
+                kind: *hw.kind(),
+                version: None,
+                message: TAPROOT_UNSUPPORTED.to_string(),
+            }),
             Ok(hw) => hws.push(hw),
             Err(e) => {
                 debug!("{}", e);
+                errors.push(format!("Specter simulator: {}", e));
             }
         },
         Err(HWIError::DeviceNotFound) => {}
         Err(e) => {
             debug!("{}", e);
+            errors.push(format!("Specter simulator: {}", e));
         }
     }
     match specter::Specter::try_connect_serial().await {
         Ok(device) => match HardwareWallet::new(Arc::new(device)).await {
+            // Specter does not support Taproot miniscript.
+            Ok(hw) if taproot => hws.push(HardwareWallet::Unsupported {
+                kind: *hw.kind(),
+                version: None,
+                message: TAPROOT_UNSUPPORTED.to_string(),
+            }),
             Ok(hw) => hws.push(hw),
             Err(e) => {
                 debug!("{}", e);
+                errors.push(format!("Specter: {}", e));
             }
         },
         Err(HWIError::DeviceNotFound) => {}
         Err(e) => {
             debug!("{}", e);
+            errors.push(format!("Specter: {}", e));
         }
     }
     match ledger::LedgerSimulator::try_connect().await {
@@ -123,11 +182,19 @@ pub async fn list_hardware_wallets(
                 }
 
                 let version = device.get_version().await.ok();
-                if ledger_version_supported(version.as_ref()) {
+                if taproot && !ledger_version_supports_taproot(version.as_ref()) {
+                    hws.push(HardwareWallet::Unsupported {
+                        kind: device.device_kind(),
+                        version,
+                        message: LEDGER_TAPROOT_UNSUPPORTED.to_string(),
+                    });
+                } else if ledger_version_supported(version.as_ref()) {
+                    let device = Arc::new(device);
                     hws.push(HardwareWallet::Supported {
                         kind: device.device_kind(),
                         fingerprint,
-                        device: Arc::new(device),
+                        device: device.clone(),
+                        address_display: Some(device),
                         version,
                     });
                 } else {
@@ -138,7 +205,9 @@ pub async fn list_hardware_wallets(
                     });
                 }
             }
-            Err(_) => {
+            Err(e) => {
+                // Either the device is locked or the app version is too old.
+                errors.push(format!("Ledger simulator: {}", e));
                 hws.push(HardwareWallet::Unsupported {
                     kind: device.device_kind(),
                     version: None,
@@ -149,11 +218,16 @@ pub async fn list_hardware_wallets(
         Err(HWIError::DeviceNotFound) => {}
         Err(e) => {
             debug!("{}", e);
+            errors.push(format!("Ledger simulator: {}", e));
         }
     }
     match ledger::HidApi::new() {
         Err(e) => {
             debug!("{}", e);
+            errors.push(format!(
+                "Failed to access the USB devices, check the permissions: {}",
+                e
+            ));
         }
         Ok(api) => {
             for detected in ledger::Ledger::<ledger::TransportHID>::enumerate(&api) {
@@ -173,11 +247,19 @@ pub async fn list_hardware_wallets(
                             }
 
                             let version = device.get_version().await.ok();
-                            if ledger_version_supported(version.as_ref()) {
+                            if taproot && !ledger_version_supports_taproot(version.as_ref()) {
+                                hws.push(HardwareWallet::Unsupported {
+                                    kind: device.device_kind(),
+                                    version,
+                                    message: LEDGER_TAPROOT_UNSUPPORTED.to_string(),
+                                });
+                            } else if ledger_version_supported(version.as_ref()) {
+                                let device = Arc::new(device);
                                 hws.push(HardwareWallet::Supported {
                                     kind: device.device_kind(),
                                     fingerprint,
-                                    device: Arc::new(device),
+                                    device: device.clone(),
+                                    address_display: Some(device),
                                     version,
                                 });
                             } else {
@@ -188,7 +270,9 @@ pub async fn list_hardware_wallets(
                                 });
                             }
                         }
-                        Err(_) => {
+                        Err(e) => {
+                            // Either the device is locked or the app version is too old.
+                            errors.push(format!("Ledger: {}", e));
                             hws.push(HardwareWallet::Unsupported {
                                 kind: device.device_kind(),
                                 version: None,
@@ -199,12 +283,20 @@ pub async fn list_hardware_wallets(
                     Err(HWIError::DeviceNotFound) => {}
                     Err(e) => {
                         debug!("{}", e);
+                        errors.push(format!("Ledger: {}", e));
                     }
                 }
             }
         }
     }
-    hws
+    (hws, errors)
+}
+
+/// Taproot miniscript is supported by the Ledger Bitcoin app since version 2.2.0.
+fn ledger_version_supports_taproot(version: Option<&Version>) -> bool {
+    version
+        .map(|version| version.major > 2 || (version.major == 2 && version.minor >= 2))
+        .unwrap_or(false)
 }
 
 fn ledger_version_supported(version: Option<&Version>) -> bool {
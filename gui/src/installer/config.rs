@@ -21,6 +21,7 @@ impl TryFrom<Context> for LianaConfig {
             data_dir: Some(ctx.data_dir),
             bitcoin_config: ctx.bitcoin_config,
             bitcoind_config: ctx.bitcoind_config,
+            electrum_config: ctx.electrum_config,
         })
     }
 }
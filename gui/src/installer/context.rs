@@ -4,16 +4,17 @@ use std::time::Duration;
 
 use crate::{
     app::{
-        settings::{KeySetting, Settings, WalletSetting},
-        wallet::DEFAULT_WALLET_NAME,
+        settings::{KeySetting, ScanParameters, Settings, WalletSetting},
+        wallet::{Wallet, DEFAULT_WALLET_NAME, GAP_LIMIT},
     },
     hw::HardwareWalletConfig,
     signer::Signer,
 };
 use async_hwi::DeviceKind;
 use liana::{
+    bip39,
     config::Config,
-    config::{BitcoinConfig, BitcoindConfig},
+    config::{BitcoinConfig, BitcoindConfig, ElectrumConfig},
     descriptors::MultipathDescriptor,
     miniscript::bitcoin,
 };
@@ -22,6 +23,7 @@ use liana::{
 pub struct Context {
     pub bitcoin_config: BitcoinConfig,
     pub bitcoind_config: Option<BitcoindConfig>,
+    pub electrum_config: Option<ElectrumConfig>,
     pub descriptor: Option<MultipathDescriptor>,
     pub keys: Vec<KeySetting>,
     pub hws: Vec<(
@@ -31,6 +33,15 @@ pub struct Context {
     )>,
     pub data_dir: PathBuf,
     pub signer: Option<Arc<Signer>>,
+    /// Language of the last mnemonic backed up, used as default for recovery.
+    pub mnemonic_language: bip39::Language,
+    /// Password the mnemonic of the hot signer is encrypted with on disk, if any.
+    pub mnemonic_password: Option<String>,
+    /// Wallet restored from a backup, with its name, signing devices and labels.
+    pub restored_wallet: Option<Arc<Wallet>>,
+    /// Height of the block to rescan the chain from once the wallet is started, for a wallet
+    /// used before its creation date.
+    pub rescan_from_height: Option<i32>,
 }
 
 impl Context {
@@ -39,18 +50,26 @@ impl Context {
             bitcoin_config: BitcoinConfig {
                 network,
                 poll_interval_secs: Duration::from_secs(30),
+                final_spend_confirmations: 6,
+                cleanup_spends: false,
+                gap_limit: GAP_LIMIT,
             },
             hws: Vec::new(),
             keys: Vec::new(),
             bitcoind_config: None,
+            electrum_config: None,
             descriptor: None,
             data_dir,
             signer: None,
+            mnemonic_language: bip39::Language::English,
+            mnemonic_password: None,
+            restored_wallet: None,
+            rescan_from_height: None,
         }
     }
 
     pub fn extract_gui_settings(&self) -> Settings {
-        let hardware_wallets = self
+        let mut hardware_wallets: Vec<HardwareWalletConfig> = self
             .hws
             .iter()
             .filter_map(|(kind, fingerprint, token)| {
@@ -59,20 +78,50 @@ impl Context {
                     .map(|token| HardwareWalletConfig::new(kind, *fingerprint, token))
             })
             .collect();
+        // The devices registered again during the installation replace the restored ones.
+        if let Some(wallet) = &self.restored_wallet {
+            for hw in &wallet.hardware_wallets {
+                if !hardware_wallets
+                    .iter()
+                    .any(|h| h.fingerprint == hw.fingerprint)
+                {
+                    hardware_wallets.push(hw.clone());
+                }
+            }
+        }
+        let descriptor_checksum = self
+            .descriptor
+            .as_ref()
+            .unwrap()
+            .to_string()
+            .split_once('#')
+            .map(|(_, checksum)| checksum)
+            .unwrap()
+            .to_string();
         Settings {
             wallets: vec![WalletSetting {
-                name: DEFAULT_WALLET_NAME.to_string(),
-                descriptor_checksum: self
-                    .descriptor
+                name: self
+                    .restored_wallet
                     .as_ref()
-                    .unwrap()
-                    .to_string()
-                    .split_once('#')
-                    .map(|(_, checksum)| checksum)
-                    .unwrap()
-                    .to_string(),
+                    .map(|w| w.name.clone())
+                    .unwrap_or_else(|| DEFAULT_WALLET_NAME.to_string()),
+                descriptor_checksum: descriptor_checksum.clone(),
                 keys: self.keys.clone(),
                 hardware_wallets,
+                displayed_history_months: None,
+                display_unit: None,
+                fiat_currency: None,
+                verification_reminder_months: None,
+                last_verification: None,
+                consolidation_suggestion: None,
+                watch_only: false,
+                confirmation_tiers: None,
+                backend_labels_offered: false,
+                last_scan: Some(ScanParameters {
+                    descriptor_checksum,
+                    gap_limit: self.bitcoin_config.gap_limit,
+                    start_timestamp: None,
+                }),
             }],
         }
     }
@@ -86,6 +135,7 @@ impl Context {
             data_dir: Some(self.data_dir.clone()),
             bitcoin_config: self.bitcoin_config.clone(),
             bitcoind_config: self.bitcoind_config.clone(),
+            electrum_config: self.electrum_config.clone(),
         }
     }
 }
@@ -1,6 +1,9 @@
-use liana::miniscript::{
-    bitcoin::{util::bip32::Fingerprint, Network},
-    DescriptorPublicKey,
+use liana::{
+    bip39,
+    miniscript::{
+        bitcoin::{util::bip32::Fingerprint, Network},
+        DescriptorPublicKey,
+    },
 };
 use std::path::PathBuf;
 
@@ -13,7 +16,10 @@ pub enum Message {
     ParticipateWallet,
     ImportWallet,
     UserActionDone(bool),
-    Exit(PathBuf),
+    /// The installation is done, start the wallet with this configuration and rescan the chain
+    /// from this block height if any.
+    Exit(PathBuf, Option<i32>),
+    Abort,
     Clibpboard(String),
     Next,
     Skip,
@@ -31,6 +37,14 @@ pub enum Message {
     ConnectedHardwareWallets(Vec<HardwareWallet>),
     WalletRegistered(Result<(Fingerprint, Option<[u8; 32]>), Error>),
     MnemonicWord(usize, String),
+    MnemonicLanguage(bip39::Language),
+    /// Number of words of the mnemonic to generate or to import, 12 or 24.
+    MnemonicWordCount(usize),
+    /// Optional BIP39 passphrase of the mnemonic, and its confirmation.
+    MnemonicPassphrase(String),
+    MnemonicPassphraseConfirmation(String),
+    MnemonicStoragePassword(String),
+    MnemonicStoragePasswordConfirmation(String),
     ImportMnemonic(bool),
 }
 
@@ -38,20 +52,36 @@ pub enum Message {
 pub enum DefineBitcoind {
     CookiePathEdited(String),
     AddressEdited(String),
+    SignetChallengeEdited(String),
+    ProxyEdited(String),
+    /// Connect to an Electrum server instead of a bitcoind.
+    UseElectrum(bool),
+    ElectrumAddressEdited(String),
+    RescanHeightEdited(String),
+    GapLimitEdited(String),
 }
 
 #[derive(Debug, Clone)]
 pub enum DefineDescriptor {
     ImportDescriptor(String),
-    /// AddKey(is_recovery)
-    AddKey(bool),
-    Key(bool, usize, DefineKey),
+    /// Path of a backup file: the bare descriptor, its JSON backup with the names of the keys,
+    /// or a full wallet backup.
+    DescriptorFileEdited(String),
+    LoadDescriptorFile,
+    /// AddKey(path): the path is None for the primary path or the index of the recovery path.
+    AddKey(Option<usize>),
+    Key(Option<usize>, usize, DefineKey),
+    AddRecoveryPath,
+    DeleteRecoveryPath(usize),
     HWXpubImported(Result<DescriptorPublicKey, Error>),
     XPubEdited(String),
     EditName,
     NameEdited(String),
-    SequenceEdited(String),
-    ThresholdEdited(bool, usize),
+    /// SequenceEdited(recovery path index, value)
+    SequenceEdited(usize, String),
+    ThresholdEdited(Option<usize>, usize),
+    /// Whether to create a Taproot descriptor instead of a P2WSH one.
+    Taproot(bool),
     ConfirmXpub,
 }
 
@@ -29,6 +29,10 @@ pub struct Installer {
 }
 
 impl Installer {
+    pub fn network(&self) -> bitcoin::Network {
+        self.context.bitcoin_config.network
+    }
+
     fn previous(&mut self) {
         if self.current > 0 {
             self.current -= 1;
@@ -55,6 +59,25 @@ impl Installer {
 
     pub fn stop(&mut self) {}
 
+    /// The directory the installer was started for.
+    pub fn datadir(&self) -> &PathBuf {
+        &self.context.data_dir
+    }
+
+    /// Discard the installation in progress: the steps overwrite the sensitive data they hold
+    /// and the references to the hot signer are dropped, which overwrites its mnemonic in memory
+    /// once the last one is gone.
+    pub fn abort(&mut self) {
+        info!("Installation aborted. Discarding the installer data.");
+        for step in self.steps.iter_mut() {
+            step.discard();
+        }
+        self.context.signer = None;
+        self.context.mnemonic_password = None;
+        self.steps = vec![Welcome::default().into()];
+        self.current = 0;
+    }
+
     fn next(&mut self) -> Command<Message> {
         let current_step = self
             .steps
@@ -131,10 +154,14 @@ impl Installer {
                 Command::none()
             }
             Message::Install => {
-                self.steps
+                let current_step = self
+                    .steps
                     .get_mut(self.current)
-                    .expect("There is always a step")
-                    .update(message);
+                    .expect("There is always a step");
+                current_step.update(message);
+                if !current_step.apply(&mut self.context) {
+                    return Command::none();
+                }
                 Command::perform(install(self.context.clone()), Message::Installed)
             }
             Message::Installed(Err(e)) => {
@@ -220,7 +247,7 @@ pub async fn install(ctx: Context) -> Result<PathBuf, Error> {
 
     info!("daemon checked");
 
-    let mut network_datadir_path = data_dir;
+    let mut network_datadir_path = data_dir.clone();
     network_datadir_path.push(cfg.bitcoin_config.network.to_string());
 
     // Step needed because of ValueAfterTable error in the toml serialize implementation.
@@ -237,19 +264,21 @@ pub async fn install(ctx: Context) -> Result<PathBuf, Error> {
     info!("Daemon configuration file created");
 
     if let Some(signer) = &ctx.signer {
-        signer
-            .store(
-                &cfg.data_dir().expect("Already checked"),
-                cfg.bitcoin_config.network,
-            )
-            .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
+        let datadir = cfg.data_dir().expect("Already checked");
+        match &ctx.mnemonic_password {
+            Some(password) => {
+                signer.store_encrypted(&datadir, cfg.bitcoin_config.network, password)
+            }
+            None => signer.store(&datadir, cfg.bitcoin_config.network),
+        }
+        .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
         info!("Hot signer mnemonic stored");
     }
 
     // create liana GUI configuration file
     let gui_config_path = create_and_write_file(
-        network_datadir_path.clone(),
+        network_datadir_path,
         gui_config::DEFAULT_FILE_NAME,
         toml::to_string(&gui_config::Config::new(
             daemon_config_path.canonicalize().map_err(|e| {
@@ -264,16 +293,26 @@ pub async fn install(ctx: Context) -> Result<PathBuf, Error> {
 
     // create liana GUI settings file
     let settings: gui_settings::Settings = ctx.extract_gui_settings();
-    create_and_write_file(
-        network_datadir_path,
-        gui_settings::DEFAULT_FILE_NAME,
-        serde_json::to_string_pretty(&settings)
-            .map_err(|e| Error::Unexpected(format!("Failed to serialize settings: {}", e)))?
-            .as_bytes(),
-    )?;
+    settings
+        .to_file(data_dir.clone(), cfg.bitcoin_config.network)
+        .map_err(|e| Error::CannotWriteToFile(e.to_string()))?;
 
     info!("Settings file created");
 
+    // The hot signer of a restored wallet is matched with its keys when the wallet is loaded.
+    if let Some(wallet) = ctx
+        .restored_wallet
+        .as_ref()
+        .filter(|w| !w.labels.is_empty())
+    {
+        wallet
+            .labels
+            .to_file(data_dir, cfg.bitcoin_config.network)
+            .map_err(|e| Error::CannotWriteToFile(e.to_string()))?;
+
+        info!("Labels of the restored wallet written");
+    }
+
     Ok(gui_config_path)
 }
 
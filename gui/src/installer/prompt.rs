@@ -7,5 +7,13 @@ pub const DEFINE_DESCRIPTOR_SEQUENCE_TOOLTIP: &str =
 pub const DEFINE_DESCRIPTOR_FINGERPRINT_TOOLTIP: &str =
     "The alias is applied on all the keys derived from the same seed";
 pub const REGISTER_DESCRIPTOR_HELP: &str = "To be used with the wallet, a device needs the descriptor. If the descriptor contains one or more keys imported from an external signing device, the descriptor must be registered on it. Registration on a device is not a substitute for backing up the descriptor.";
+pub const NON_STANDARD_DERIVATION_WARNING: &str = "Some keys derive the receive and change addresses with extra steps after the extended public key instead of the usual '/<0;1>/*'. Make sure your signing devices and any other wallet you may restore the descriptor in support this derivation scheme, or they may not find your coins.";
 pub const MNEMONIC_HELP: &str = "A hot key generated on this computer was used for creating this wallet. It needs to be backed up. \n Keep it in a safe place. Never share it with anyone.";
 pub const RECOVER_MNEMONIC_HELP: &str = "If you were using a hot key (a key stored on the computer) in your wallet, you will need to recover it from mnemonics to be able to sign transactions again. Otherwise you can directly go the next step.";
+pub const VERIFICATION_CODE_HELP: &str = "Compare this code with the one displayed by the other installations of this wallet, for instance with a cosigner over the phone: the same code means the same descriptor.";
+pub const DEFINE_DESCRIPTOR_TAPROOT_TOOLTIP: &str =
+    "Use a Taproot descriptor. Spending through the primary path is cheaper and more private, but not all signing devices support it yet.";
+pub const ELECTRUM_TCP_HELP: &str = "Only plain TCP connections are supported. Use a server running on this computer, or a tunnel to a remote one.";
+pub const RESCAN_FROM_HEIGHT_HELP: &str = "If the wallet received coins before today, for instance if it is restored from a backup, the chain is rescanned from this block once the wallet is started. The rescan can also be started later from the settings.";
+pub const GAP_LIMIT_HELP: &str = "Number of addresses watched ahead of the last used one. Payments to addresses beyond it are not detected, so increase it if many addresses were shared without being paid. Leave empty for the default.";
+pub const SIGNET_CHALLENGE_HELP: &str = "Only for a custom signet: the hex-encoded challenge script bitcoind was started with (-signetchallenge). Leave empty for the default signet.";
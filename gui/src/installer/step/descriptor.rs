@@ -1,10 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use iced::{Command, Element};
+use tracing::warn;
+
 use liana::{
+    bip39,
     descriptors::{LianaDescKeys, MultipathDescriptor},
     miniscript::{
         bitcoin::{
@@ -20,14 +23,18 @@ use liana::{
 use async_hwi::DeviceKind;
 
 use crate::{
-    app::settings::KeySetting,
+    app::{
+        export::WalletBackup,
+        settings::{DescriptorBackup, KeySetting},
+        wallet::Wallet,
+    },
     hw::{list_hardware_wallets, HardwareWallet},
     installer::{
         message::{self, Message},
         step::{Context, Step},
         view, Error,
     },
-    signer::Signer,
+    signer::{Signer, SignerError},
     ui::component::{form, modal::Modal},
 };
 
@@ -47,11 +54,14 @@ pub struct DefineDescriptor {
     data_dir: Option<PathBuf>,
     spending_keys: Vec<DescriptorKey>,
     spending_threshold: usize,
-    recovery_keys: Vec<DescriptorKey>,
-    recovery_threshold: usize,
-    sequence: form::Value<String>,
+    recovery_paths: Vec<RecoveryPath>,
+    /// Whether to create a Taproot descriptor instead of a P2WSH one.
+    taproot: bool,
     modal: Option<Box<dyn DescriptorKeyModal>>,
     signer: Arc<Signer>,
+    /// BIP39 passphrase of the hot signer and its confirmation, applied once they match.
+    passphrase: String,
+    passphrase_confirmation: String,
 
     error: Option<String>,
 }
@@ -64,23 +74,48 @@ impl DefineDescriptor {
             network_valid: true,
             spending_keys: vec![DescriptorKey::default()],
             spending_threshold: 1,
-            recovery_keys: vec![DescriptorKey::default()],
-            recovery_threshold: 1,
-            sequence: form::Value::default(),
+            recovery_paths: vec![RecoveryPath::default()],
+            taproot: false,
             modal: None,
             signer: Arc::new(Signer::generate(Network::Bitcoin).unwrap()),
+            passphrase: String::new(),
+            passphrase_confirmation: String::new(),
             error: None,
         }
     }
 
     fn valid(&self) -> bool {
         !self.spending_keys.is_empty()
-            && !self.recovery_keys.is_empty()
-            && !self.sequence.value.is_empty()
+            && !self
+                .recovery_paths
+                .iter()
+                .any(|path| path.keys.is_empty() || path.sequence.value.is_empty())
             && !self.spending_keys.iter().any(|k| k.key.is_none())
             && !self.spending_keys.iter().any(|k| k.key.is_none())
     }
 
+    /// The keys and threshold of the primary path if `path` is None, or else of the recovery path
+    /// at the given index.
+    fn path_mut(&mut self, path: Option<usize>) -> Option<(&mut Vec<DescriptorKey>, &mut usize)> {
+        match path {
+            None => Some((&mut self.spending_keys, &mut self.spending_threshold)),
+            Some(i) => self
+                .recovery_paths
+                .get_mut(i)
+                .map(|path| (&mut path.keys, &mut path.threshold)),
+        }
+    }
+
+    fn recovery_keys(&self) -> impl Iterator<Item = &DescriptorKey> {
+        self.recovery_paths.iter().flat_map(|path| path.keys.iter())
+    }
+
+    fn recovery_keys_mut(&mut self) -> impl Iterator<Item = &mut DescriptorKey> {
+        self.recovery_paths
+            .iter_mut()
+            .flat_map(|path| path.keys.iter_mut())
+    }
+
     fn set_network(&mut self, network: Network) {
         self.network = network;
         if let Some(signer) = Arc::get_mut(&mut self.signer) {
@@ -93,11 +128,58 @@ impl DefineDescriptor {
         for key in self.spending_keys.iter_mut() {
             key.check_network(self.network);
         }
-        for key in self.recovery_keys.iter_mut() {
-            key.check_network(self.network);
+        let network = self.network;
+        for key in self.recovery_keys_mut() {
+            key.check_network(network);
+        }
+    }
+
+    // The seed depends on the mnemonic words: a new signer is generated and the keys
+    // derived from the previous one are cleared.
+    fn set_mnemonic(&mut self, language: bip39::Language, word_count: usize) {
+        if language == self.signer.language() && word_count == self.signer.mnemonic_word_count() {
+            return;
+        }
+        match Signer::generate_in(self.network, language, word_count)
+            .and_then(|signer| protect(signer, &self.passphrase, &self.passphrase_confirmation))
+        {
+            Ok(signer) => self.set_signer(signer),
+            Err(e) => self.error = Some(e.to_string()),
         }
     }
 
+    // The seed depends on the passphrase as well, but the mnemonic is kept.
+    fn set_passphrase(&mut self, passphrase: String, confirmation: String) {
+        self.passphrase = passphrase;
+        self.passphrase_confirmation = confirmation;
+        if self.passphrase != self.passphrase_confirmation {
+            return;
+        }
+        match self.signer.with_passphrase(&self.passphrase) {
+            Ok(signer) => {
+                if signer.fingerprint() != self.signer.fingerprint() {
+                    self.set_signer(signer);
+                }
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn set_signer(&mut self, signer: Signer) {
+        let fingerprint = self.signer.fingerprint();
+        for key in self.spending_keys.iter_mut().chain(
+            self.recovery_paths
+                .iter_mut()
+                .flat_map(|path| path.keys.iter_mut()),
+        ) {
+            if key.key.as_ref().map(|k| k.master_fingerprint()) == Some(fingerprint) {
+                *key = DescriptorKey::default();
+            }
+        }
+        self.signer = Arc::new(signer);
+        self.check_for_duplicate();
+    }
+
     // TODO: Improve algo
     // Mark as duplicate every defined key that have the same name but not the same fingerprint.
     // And every undefined_key that have a same name than an other key.
@@ -122,7 +204,7 @@ impl DefineDescriptor {
                 }
             }
         }
-        for recovery_key in &self.recovery_keys {
+        for recovery_key in self.recovery_keys() {
             if let Some(key) = &recovery_key.key {
                 if let Some(fg) = all_names.get(&recovery_key.name) {
                     if fg != &key.master_fingerprint() {
@@ -144,7 +226,7 @@ impl DefineDescriptor {
                 spending_key.duplicate_key = duplicate_keys.contains(key);
             }
         }
-        for recovery_key in self.recovery_keys.iter_mut() {
+        for recovery_key in self.recovery_keys_mut() {
             if let Some(key) = &recovery_key.key {
                 recovery_key.duplicate_key = duplicate_keys.contains(key);
             }
@@ -157,7 +239,7 @@ impl DefineDescriptor {
                 spending_key.name = name.clone();
             }
         }
-        for recovery_key in &mut self.recovery_keys {
+        for recovery_key in self.recovery_keys_mut() {
             if recovery_key.key.as_ref().map(|k| k.master_fingerprint()) == Some(fingerprint) {
                 recovery_key.name = name.clone();
             }
@@ -195,7 +277,9 @@ impl DefineDescriptor {
                 }
             };
         update_mapping(&self.spending_keys, &mut mapping);
-        update_mapping(&self.recovery_keys, &mut mapping);
+        for path in &self.recovery_paths {
+            update_mapping(&path.keys, &mut mapping);
+        }
         mapping
     }
 
@@ -206,7 +290,7 @@ impl DefineDescriptor {
                 map.insert(key.master_fingerprint(), spending_key.name.clone());
             }
         }
-        for recovery_key in &self.recovery_keys {
+        for recovery_key in self.recovery_keys() {
             if let Some(key) = recovery_key.key.as_ref() {
                 map.insert(key.master_fingerprint(), recovery_key.name.clone());
             }
@@ -225,31 +309,54 @@ impl Step for DefineDescriptor {
                 self.modal = None;
             }
             Message::Network(network) => self.set_network(network),
+            Message::MnemonicLanguage(language) => {
+                self.set_mnemonic(language, self.signer.mnemonic_word_count())
+            }
+            Message::MnemonicWordCount(word_count) => {
+                self.set_mnemonic(self.signer.language(), word_count)
+            }
+            Message::MnemonicPassphrase(passphrase) => {
+                let confirmation = std::mem::take(&mut self.passphrase_confirmation);
+                self.set_passphrase(passphrase, confirmation);
+            }
+            Message::MnemonicPassphraseConfirmation(confirmation) => {
+                let passphrase = std::mem::take(&mut self.passphrase);
+                self.set_passphrase(passphrase, confirmation);
+            }
             Message::DefineDescriptor(msg) => {
                 match msg {
-                    message::DefineDescriptor::ThresholdEdited(is_recovery, value) => {
-                        if is_recovery {
-                            self.recovery_threshold = value;
-                        } else {
-                            self.spending_threshold = value;
+                    message::DefineDescriptor::Taproot(taproot) => {
+                        self.taproot = taproot;
+                    }
+                    message::DefineDescriptor::ThresholdEdited(path, value) => {
+                        if let Some((_, threshold)) = self.path_mut(path) {
+                            *threshold = value;
+                        }
+                    }
+                    message::DefineDescriptor::SequenceEdited(i, seq) => {
+                        if let Some(path) = self.recovery_paths.get_mut(i) {
+                            path.sequence.valid = true;
+                            if seq.is_empty() || seq.parse::<u16>().is_ok() {
+                                path.sequence.value = seq;
+                            }
                         }
                     }
-                    message::DefineDescriptor::SequenceEdited(seq) => {
-                        self.sequence.valid = true;
-                        if seq.is_empty() || seq.parse::<u16>().is_ok() {
-                            self.sequence.value = seq;
+                    message::DefineDescriptor::AddRecoveryPath => {
+                        self.recovery_paths.push(RecoveryPath::default());
+                    }
+                    message::DefineDescriptor::DeleteRecoveryPath(i) => {
+                        if self.recovery_paths.len() > 1 && i < self.recovery_paths.len() {
+                            self.recovery_paths.remove(i);
+                            self.check_for_duplicate();
                         }
                     }
-                    message::DefineDescriptor::AddKey(is_recovery) => {
-                        if is_recovery {
-                            self.recovery_keys.push(DescriptorKey::default());
-                            self.recovery_threshold += 1;
-                        } else {
-                            self.spending_keys.push(DescriptorKey::default());
-                            self.spending_threshold += 1;
+                    message::DefineDescriptor::AddKey(path) => {
+                        if let Some((keys, threshold)) = self.path_mut(path) {
+                            keys.push(DescriptorKey::default());
+                            *threshold += 1;
                         }
                     }
-                    message::DefineDescriptor::Key(is_recovery, i, msg) => match msg {
+                    message::DefineDescriptor::Key(path, i, msg) => match msg {
                         message::DefineKey::Clipboard(key) => {
                             return Command::perform(async move { key }, Message::Clibpboard);
                         }
@@ -258,47 +365,33 @@ impl Step for DefineDescriptor {
                                 name.clone(),
                                 imported_key.master_fingerprint(),
                             );
-                            if is_recovery {
-                                if let Some(recovery_key) = self.recovery_keys.get_mut(i) {
-                                    recovery_key.name = name;
-                                    recovery_key.key = Some(imported_key);
-                                    recovery_key.check_network(self.network);
-                                }
-                            } else if let Some(spending_key) = self.spending_keys.get_mut(i) {
-                                spending_key.name = name;
-                                spending_key.key = Some(imported_key);
-                                spending_key.check_network(self.network);
+                            let network = self.network;
+                            if let Some(key) =
+                                self.path_mut(path).and_then(|(keys, _)| keys.get_mut(i))
+                            {
+                                key.name = name;
+                                key.key = Some(imported_key);
+                                key.check_network(network);
                             }
                             self.modal = None;
                             self.check_for_duplicate();
                         }
                         message::DefineKey::Edit => {
-                            if is_recovery {
-                                if let Some(recovery_key) = self.recovery_keys.get(i) {
-                                    let modal = EditXpubModal::new(
-                                        recovery_key.name.clone(),
-                                        recovery_key.key.as_ref(),
-                                        i,
-                                        is_recovery,
-                                        self.network,
-                                        self.fingerprint_account_index_mappping(),
-                                        self.keys_aliases(),
-                                        self.signer.clone(),
-                                    );
-                                    let cmd = modal.load();
-                                    self.modal = Some(Box::new(modal));
-                                    return cmd;
-                                }
-                            } else if let Some(spending_key) = self.spending_keys.get(i) {
+                            let key = match path {
+                                None => self.spending_keys.get(i),
+                                Some(p) => self.recovery_paths.get(p).and_then(|p| p.keys.get(i)),
+                            };
+                            if let Some(key) = key {
                                 let modal = EditXpubModal::new(
-                                    spending_key.name.clone(),
-                                    spending_key.key.as_ref(),
+                                    key.name.clone(),
+                                    key.key.as_ref(),
                                     i,
-                                    is_recovery,
+                                    path,
                                     self.network,
                                     self.fingerprint_account_index_mappping(),
                                     self.keys_aliases(),
                                     self.signer.clone(),
+                                    self.taproot,
                                 );
                                 let cmd = modal.load();
                                 self.modal = Some(Box::new(modal));
@@ -306,15 +399,12 @@ impl Step for DefineDescriptor {
                             }
                         }
                         message::DefineKey::Delete => {
-                            if is_recovery {
-                                self.recovery_keys.remove(i);
-                                if self.recovery_threshold > self.recovery_keys.len() {
-                                    self.recovery_threshold -= 1;
-                                }
-                            } else {
-                                self.spending_keys.remove(i);
-                                if self.spending_threshold > self.spending_keys.len() {
-                                    self.spending_threshold -= 1;
+                            if let Some((keys, threshold)) = self.path_mut(path) {
+                                if i < keys.len() {
+                                    keys.remove(i);
+                                    if *threshold > keys.len() {
+                                        *threshold -= 1;
+                                    }
                                 }
                             }
                             self.check_for_duplicate();
@@ -342,94 +432,65 @@ impl Step for DefineDescriptor {
     }
 
     fn apply(&mut self, ctx: &mut Context) -> bool {
+        if self.passphrase != self.passphrase_confirmation {
+            self.error = Some("The passphrases do not match".to_string());
+            return false;
+        }
         ctx.bitcoin_config.network = self.network;
         ctx.keys = Vec::new();
-        let mut signer_is_used = false;
-        let mut spending_keys: Vec<DescriptorPublicKey> = Vec::new();
-        for spending_key in self.spending_keys.iter().clone() {
-            if let Some(DescriptorPublicKey::XPub(xpub)) = spending_key.key.as_ref() {
-                if let Some((master_fingerprint, _)) = xpub.origin {
-                    ctx.keys.push(KeySetting {
-                        master_fingerprint,
-                        name: spending_key.name.clone(),
-                    });
-                }
-                let xpub = DescriptorMultiXKey {
-                    origin: xpub.origin.clone(),
-                    xkey: xpub.xkey,
-                    derivation_paths: DerivPaths::new(vec![
-                        DerivationPath::from_str("m/0").unwrap(),
-                        DerivationPath::from_str("m/1").unwrap(),
-                    ])
-                    .unwrap(),
-                    wildcard: Wildcard::Unhardened,
-                };
-                spending_keys.push(DescriptorPublicKey::MultiXPub(xpub));
-            }
-        }
-
-        let mut recovery_keys: Vec<DescriptorPublicKey> = Vec::new();
-        for recovery_key in self.recovery_keys.iter().clone() {
-            if let Some(DescriptorPublicKey::XPub(xpub)) = recovery_key.key.as_ref() {
-                if let Some((master_fingerprint, _)) = xpub.origin {
-                    ctx.keys.push(KeySetting {
-                        master_fingerprint,
-                        name: recovery_key.name.clone(),
-                    });
-                    if master_fingerprint == self.signer.fingerprint() {
-                        signer_is_used = true;
-                    }
-                }
-                let xpub = DescriptorMultiXKey {
-                    origin: xpub.origin.clone(),
-                    xkey: xpub.xkey,
-                    derivation_paths: DerivPaths::new(vec![
-                        DerivationPath::from_str("m/0").unwrap(),
-                        DerivationPath::from_str("m/1").unwrap(),
-                    ])
-                    .unwrap(),
-                    wildcard: Wildcard::Unhardened,
-                };
-                recovery_keys.push(DescriptorPublicKey::MultiXPub(xpub));
-            }
+        let spending_keys = multipath_keys(&self.spending_keys, &mut ctx.keys);
+
+        let mut recovery_paths = Vec::with_capacity(self.recovery_paths.len());
+        let mut timelocks = HashSet::with_capacity(self.recovery_paths.len());
+        for path in self.recovery_paths.iter_mut() {
+            let keys = multipath_keys(&path.keys, &mut ctx.keys);
+            let sequence = path.sequence.value.parse::<u16>();
+            // Two recovery paths cannot have the same timelock.
+            path.sequence.valid = match sequence {
+                Ok(sequence) => timelocks.insert(sequence),
+                Err(_) => false,
+            };
+            recovery_paths.push((sequence, path.threshold, keys));
         }
-
-        let sequence = self.sequence.value.parse::<u16>();
-        self.sequence.valid = sequence.is_ok();
+        let signer_is_used = self.recovery_keys().any(|key| {
+            key.key.as_ref().map(|k| k.master_fingerprint()) == Some(self.signer.fingerprint())
+        });
 
         if !self.network_valid
-            || !self.sequence.valid
-            || recovery_keys.is_empty()
+            || self.recovery_paths.iter().any(|path| !path.sequence.valid)
+            || recovery_paths.iter().any(|(_, _, keys)| keys.is_empty())
             || spending_keys.is_empty()
         {
             return false;
         }
 
-        let spending_keys = if spending_keys.len() == 1 {
-            LianaDescKeys::from_single(spending_keys[0].clone())
-        } else {
-            match LianaDescKeys::from_multi(self.spending_threshold, spending_keys) {
-                Ok(keys) => keys,
-                Err(e) => {
-                    self.error = Some(e.to_string());
-                    return false;
-                }
+        let spending_keys = match liana_desc_keys(self.spending_threshold, spending_keys) {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.error = Some(e);
+                return false;
             }
         };
 
-        let recovery_keys = if recovery_keys.len() == 1 {
-            LianaDescKeys::from_single(recovery_keys[0].clone())
-        } else {
-            match LianaDescKeys::from_multi(self.recovery_threshold, recovery_keys) {
-                Ok(keys) => keys,
+        let mut recovery_paths_keys = BTreeMap::new();
+        for (sequence, threshold, keys) in recovery_paths {
+            match liana_desc_keys(threshold, keys) {
+                Ok(keys) => {
+                    recovery_paths_keys.insert(sequence.expect("Checked above"), keys);
+                }
                 Err(e) => {
-                    self.error = Some(e.to_string());
+                    self.error = Some(e);
                     return false;
                 }
             }
-        };
+        }
 
-        let desc = match MultipathDescriptor::new(spending_keys, recovery_keys, sequence.unwrap()) {
+        let desc = if self.taproot {
+            MultipathDescriptor::new_taproot(spending_keys, recovery_paths_keys)
+        } else {
+            MultipathDescriptor::new_with_recovery_paths(spending_keys, recovery_paths_keys)
+        };
+        let desc = match desc {
             Ok(desc) => desc,
             Err(e) => {
                 self.error = Some(e.to_string());
@@ -449,27 +510,26 @@ impl Step for DefineDescriptor {
             progress,
             self.network,
             self.network_valid,
+            self.signer.language(),
+            self.signer.mnemonic_word_count(),
+            &self.passphrase,
+            &self.passphrase_confirmation,
+            self.taproot,
             self.spending_keys
                 .iter()
                 .enumerate()
                 .map(|(i, key)| {
                     key.view().map(move |msg| {
-                        Message::DefineDescriptor(message::DefineDescriptor::Key(false, i, msg))
+                        Message::DefineDescriptor(message::DefineDescriptor::Key(None, i, msg))
                     })
                 })
                 .collect(),
-            self.recovery_keys
+            self.spending_threshold,
+            self.recovery_paths
                 .iter()
                 .enumerate()
-                .map(|(i, key)| {
-                    key.view().map(move |msg| {
-                        Message::DefineDescriptor(message::DefineDescriptor::Key(true, i, msg))
-                    })
-                })
+                .map(|(i, path)| path.view(i, self.recovery_paths.len() > 1))
                 .collect(),
-            &self.sequence,
-            self.spending_threshold,
-            self.recovery_threshold,
             self.valid(),
             self.error.as_ref(),
         );
@@ -487,6 +547,88 @@ impl Step for DefineDescriptor {
     }
 }
 
+/// Converts the keys to the multipath keys used in the descriptor and records their names.
+fn multipath_keys(
+    keys: &[DescriptorKey],
+    settings: &mut Vec<KeySetting>,
+) -> Vec<DescriptorPublicKey> {
+    let mut multipath_keys = Vec::with_capacity(keys.len());
+    for key in keys {
+        if let Some(DescriptorPublicKey::XPub(xpub)) = key.key.as_ref() {
+            if let Some((master_fingerprint, _)) = xpub.origin {
+                settings.push(KeySetting {
+                    master_fingerprint,
+                    name: key.name.clone(),
+                });
+            }
+            let xpub = DescriptorMultiXKey {
+                origin: xpub.origin.clone(),
+                xkey: xpub.xkey,
+                derivation_paths: DerivPaths::new(vec![
+                    DerivationPath::from_str("m/0").unwrap(),
+                    DerivationPath::from_str("m/1").unwrap(),
+                ])
+                .unwrap(),
+                wildcard: Wildcard::Unhardened,
+            };
+            multipath_keys.push(DescriptorPublicKey::MultiXPub(xpub));
+        }
+    }
+    multipath_keys
+}
+
+fn liana_desc_keys(
+    threshold: usize,
+    mut keys: Vec<DescriptorPublicKey>,
+) -> Result<LianaDescKeys, String> {
+    if keys.len() == 1 {
+        Ok(LianaDescKeys::from_single(keys.remove(0)))
+    } else {
+        LianaDescKeys::from_multi(threshold, keys).map_err(|e| e.to_string())
+    }
+}
+
+/// A set of keys that can spend the coins once they are old enough.
+pub struct RecoveryPath {
+    keys: Vec<DescriptorKey>,
+    threshold: usize,
+    sequence: form::Value<String>,
+}
+
+impl Default for RecoveryPath {
+    fn default() -> Self {
+        Self {
+            keys: vec![DescriptorKey::default()],
+            threshold: 1,
+            sequence: form::Value::default(),
+        }
+    }
+}
+
+impl RecoveryPath {
+    pub fn view(&self, index: usize, deletable: bool) -> Element<Message> {
+        view::recovery_path(
+            index,
+            self.keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    key.view().map(move |msg| {
+                        Message::DefineDescriptor(message::DefineDescriptor::Key(
+                            Some(index),
+                            i,
+                            msg,
+                        ))
+                    })
+                })
+                .collect(),
+            self.threshold,
+            &self.sequence,
+            deletable,
+        )
+    }
+}
+
 pub struct DescriptorKey {
     pub name: String,
     pub valid: bool,
@@ -547,6 +689,36 @@ fn check_key_network(key: &DescriptorPublicKey, network: Network) -> bool {
     }
 }
 
+/// Check the xpubs of the descriptor are all for this network, since a wallet using the keys of
+/// another network would be unusable. The error identifies the first key which is not.
+pub fn check_descriptor_network(
+    desc: &MultipathDescriptor,
+    network: Network,
+) -> Result<(), String> {
+    // Xpubs are either for mainnet or for all the test networks.
+    let xpub_network = if network == Network::Bitcoin {
+        Network::Bitcoin
+    } else {
+        Network::Testnet
+    };
+    match desc.xpub_not_for_net(xpub_network) {
+        Some(fingerprint) => Err(format!(
+            "The key with fingerprint {} is not for the {} network",
+            fingerprint, network
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Protect a newly generated signer with the passphrase, once its confirmation matches.
+fn protect(signer: Signer, passphrase: &str, confirmation: &str) -> Result<Signer, SignerError> {
+    if passphrase.is_empty() || passphrase != confirmation {
+        Ok(signer)
+    } else {
+        signer.with_passphrase(passphrase)
+    }
+}
+
 impl Default for DefineDescriptor {
     fn default() -> Self {
         Self::new()
@@ -560,7 +732,8 @@ impl From<DefineDescriptor> for Box<dyn Step> {
 }
 
 pub struct EditXpubModal {
-    is_recovery: bool,
+    /// None for the primary path, or else the index of the recovery path.
+    path: Option<usize>,
     key_index: usize,
     network: Network,
     error: Option<Error>,
@@ -575,6 +748,8 @@ pub struct EditXpubModal {
 
     chosen_hw: Option<usize>,
     hws: Vec<HardwareWallet>,
+    /// Whether the key is for a Taproot descriptor, which not all devices support.
+    taproot: bool,
 
     signer: Arc<Signer>,
     chosen_signer: bool,
@@ -586,11 +761,12 @@ impl EditXpubModal {
         name: String,
         key: Option<&DescriptorPublicKey>,
         key_index: usize,
-        is_recovery: bool,
+        path: Option<usize>,
         network: Network,
         account_indexes: HashMap<Fingerprint, ChildNumber>,
         keys_aliases: HashMap<Fingerprint, String>,
         signer: Arc<Signer>,
+        taproot: bool,
     ) -> Self {
         Self {
             form_name: form::Value {
@@ -603,11 +779,12 @@ impl EditXpubModal {
             },
             keys_aliases,
             account_indexes,
-            is_recovery,
+            path,
             key_index,
             chosen_hw: None,
             processing: false,
             hws: Vec::new(),
+            taproot,
             error: None,
             network,
             edit_name: false,
@@ -617,7 +794,7 @@ impl EditXpubModal {
     }
     fn load(&self) -> Command<Message> {
         Command::perform(
-            list_hardware_wallets(&[], None),
+            list_hardware_wallets(&[], None, self.taproot),
             Message::ConnectedHardwareWallets,
         )
     }
@@ -748,13 +925,13 @@ impl DescriptorKeyModal for EditXpubModal {
             Message::DefineDescriptor(message::DefineDescriptor::ConfirmXpub) => {
                 if let Ok(key) = DescriptorPublicKey::from_str(&self.form_xpub.value) {
                     let key_index = self.key_index;
-                    let is_recovery = self.is_recovery;
+                    let path = self.path;
                     let name = self.form_name.value.clone();
                     return Command::perform(
-                        async move { (is_recovery, key_index, key) },
-                        |(is_recovery, key_index, key)| {
+                        async move { (path, key_index, key) },
+                        |(path, key_index, key)| {
                             message::DefineDescriptor::Key(
-                                is_recovery,
+                                path,
                                 key_index,
                                 message::DefineKey::Edited(name, key),
                             )
@@ -933,6 +1110,9 @@ pub struct ParticipateXpub {
 
     xpubs_hw: Vec<HardwareWalletXpubs>,
     xpubs_signer: SignerXpubs,
+    /// BIP39 passphrase of the hot signer and its confirmation, applied once they match.
+    passphrase: String,
+    passphrase_confirmation: String,
 }
 
 impl ParticipateXpub {
@@ -944,6 +1124,8 @@ impl ParticipateXpub {
             xpubs_hw: Vec::new(),
             shared: false,
             xpubs_signer: SignerXpubs::new(Arc::new(Signer::generate(Network::Bitcoin).unwrap())),
+            passphrase: String::new(),
+            passphrase_confirmation: String::new(),
         }
     }
 
@@ -961,6 +1143,36 @@ impl ParticipateXpub {
             self.network_valid = !network_datadir.exists();
         }
     }
+
+    // The xpubs derived from the previous signer are discarded along with it.
+    fn set_mnemonic(&mut self, language: bip39::Language, word_count: usize) {
+        let signer = &self.xpubs_signer.signer;
+        if language == signer.language() && word_count == signer.mnemonic_word_count() {
+            return;
+        }
+        match Signer::generate_in(self.network, language, word_count)
+            .and_then(|signer| protect(signer, &self.passphrase, &self.passphrase_confirmation))
+        {
+            Ok(signer) => self.xpubs_signer = SignerXpubs::new(Arc::new(signer)),
+            Err(e) => warn!("Failed to generate the hot signer: {}", e),
+        }
+    }
+
+    fn set_passphrase(&mut self, passphrase: String, confirmation: String) {
+        self.passphrase = passphrase;
+        self.passphrase_confirmation = confirmation;
+        if self.passphrase != self.passphrase_confirmation {
+            return;
+        }
+        match self.xpubs_signer.signer.with_passphrase(&self.passphrase) {
+            Ok(signer) => {
+                if signer.fingerprint() != self.xpubs_signer.signer.fingerprint() {
+                    self.xpubs_signer = SignerXpubs::new(Arc::new(signer));
+                }
+            }
+            Err(e) => warn!("Failed to derive the hot signer: {}", e),
+        }
+    }
 }
 
 impl Default for ParticipateXpub {
@@ -977,6 +1189,22 @@ impl Step for ParticipateXpub {
             Message::Network(network) => {
                 self.set_network(network);
             }
+            Message::MnemonicLanguage(language) => {
+                let word_count = self.xpubs_signer.signer.mnemonic_word_count();
+                self.set_mnemonic(language, word_count);
+            }
+            Message::MnemonicWordCount(word_count) => {
+                let language = self.xpubs_signer.signer.language();
+                self.set_mnemonic(language, word_count);
+            }
+            Message::MnemonicPassphrase(passphrase) => {
+                let confirmation = std::mem::take(&mut self.passphrase_confirmation);
+                self.set_passphrase(passphrase, confirmation);
+            }
+            Message::MnemonicPassphraseConfirmation(confirmation) => {
+                let passphrase = std::mem::take(&mut self.passphrase);
+                self.set_passphrase(passphrase, confirmation);
+            }
             Message::UserActionDone(shared) => self.shared = shared,
             Message::ImportXpub(i, res) => {
                 if let Some(hw) = self.xpubs_hw.get_mut(i) {
@@ -1018,12 +1246,16 @@ impl Step for ParticipateXpub {
 
     fn load(&self) -> Command<Message> {
         Command::perform(
-            list_hardware_wallets(&[], None),
+            list_hardware_wallets(&[], None, false),
             Message::ConnectedHardwareWallets,
         )
     }
 
     fn apply(&mut self, ctx: &mut Context) -> bool {
+        // The mismatch is shown next to the passphrase inputs.
+        if self.passphrase != self.passphrase_confirmation {
+            return false;
+        }
         ctx.bitcoin_config.network = self.network;
         // Drop connections to hardware wallets.
         self.xpubs_hw = Vec::new();
@@ -1040,6 +1272,10 @@ impl Step for ParticipateXpub {
             progress,
             self.network,
             self.network_valid,
+            self.xpubs_signer.signer.language(),
+            self.xpubs_signer.signer.mnemonic_word_count(),
+            &self.passphrase,
+            &self.passphrase_confirmation,
             self.xpubs_hw
                 .iter()
                 .enumerate()
@@ -1063,6 +1299,11 @@ pub struct ImportDescriptor {
     change_network: bool,
     data_dir: Option<PathBuf>,
     imported_descriptor: form::Value<String>,
+    descriptor_file: form::Value<String>,
+    /// Names of the keys loaded along with the descriptor from a backup file.
+    keys: Vec<KeySetting>,
+    /// Wallet restored from a wallet backup file.
+    restored_wallet: Option<Arc<Wallet>>,
     error: Option<String>,
 }
 
@@ -1074,6 +1315,9 @@ impl ImportDescriptor {
             network_valid: true,
             data_dir: None,
             imported_descriptor: form::Value::default(),
+            descriptor_file: form::Value::default(),
+            keys: Vec::new(),
+            restored_wallet: None,
             error: None,
         }
     }
@@ -1093,6 +1337,41 @@ impl Step for ImportDescriptor {
             Message::DefineDescriptor(message::DefineDescriptor::ImportDescriptor(desc)) => {
                 self.imported_descriptor.value = desc;
                 self.imported_descriptor.valid = true;
+                // The names loaded from a file may not be the ones of the edited descriptor.
+                self.keys = Vec::new();
+                self.restored_wallet = None;
+            }
+            Message::DefineDescriptor(message::DefineDescriptor::DescriptorFileEdited(path)) => {
+                self.descriptor_file.valid = !path.is_empty();
+                self.descriptor_file.value = path;
+            }
+            Message::DefineDescriptor(message::DefineDescriptor::LoadDescriptorFile) => {
+                if self.descriptor_file.value.is_empty() {
+                    return Command::none();
+                }
+                match std::fs::read_to_string(&self.descriptor_file.value)
+                    .map_err(|e| format!("Failed to read '{}': {}", self.descriptor_file.value, e))
+                    .and_then(|content| match WalletBackup::from_json(&content) {
+                        Some(backup) => backup.and_then(|backup| {
+                            let wallet = backup.wallet()?;
+                            Ok((
+                                wallet.main_descriptor.clone(),
+                                backup.keys,
+                                Some(Arc::new(wallet)),
+                            ))
+                        }),
+                        None => DescriptorBackup::parse(&content)
+                            .map(|(descriptor, keys)| (descriptor, keys, None)),
+                    }) {
+                    Ok((descriptor, keys, restored_wallet)) => {
+                        self.imported_descriptor.value = descriptor.to_string();
+                        self.imported_descriptor.valid = true;
+                        self.keys = keys;
+                        self.restored_wallet = restored_wallet;
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
             }
             _ => {}
         };
@@ -1112,8 +1391,16 @@ impl Step for ImportDescriptor {
         // descriptor forms for import or creation cannot be both empty or filled.
         if !self.imported_descriptor.value.is_empty() {
             if let Ok(desc) = MultipathDescriptor::from_str(&self.imported_descriptor.value) {
+                if let Err(e) = check_descriptor_network(&desc, self.network) {
+                    self.imported_descriptor.valid = false;
+                    self.error = Some(e);
+                    return false;
+                }
                 self.imported_descriptor.valid = true;
+                self.error = None;
                 ctx.descriptor = Some(desc);
+                ctx.keys = self.keys.clone();
+                ctx.restored_wallet = self.restored_wallet.clone();
                 true
             } else {
                 self.imported_descriptor.valid = false;
@@ -1131,6 +1418,7 @@ impl Step for ImportDescriptor {
             self.network,
             self.network_valid,
             &self.imported_descriptor,
+            &self.descriptor_file,
             self.error.as_ref(),
         )
     }
@@ -1218,7 +1506,14 @@ impl Step for RegisterDescriptor {
     }
     fn load(&self) -> Command<Message> {
         Command::perform(
-            list_hardware_wallets(&[], None),
+            list_hardware_wallets(
+                &[],
+                None,
+                self.descriptor
+                    .as_ref()
+                    .map(|desc| desc.is_taproot())
+                    .unwrap_or(false),
+            ),
             Message::ConnectedHardwareWallets,
         )
     }
@@ -1227,6 +1522,8 @@ impl Step for RegisterDescriptor {
         view::register_descriptor(
             progress,
             desc.to_string(),
+            desc.verification_code(),
+            &desc.keys_derivation(),
             &self.hws,
             &self.registered,
             self.error.as_ref(),
@@ -1273,7 +1570,12 @@ impl Step for BackupDescriptor {
     }
     fn view(&self, progress: (usize, usize)) -> Element<Message> {
         let desc = self.descriptor.as_ref().unwrap();
-        view::backup_descriptor(progress, desc.to_string(), self.done)
+        view::backup_descriptor(
+            progress,
+            desc.to_string(),
+            desc.verification_code(),
+            self.done,
+        )
     }
 }
 
@@ -1282,3 +1584,76 @@ impl From<BackupDescriptor> for Box<dyn Step> {
         Box::new(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::miniscript::bitcoin::secp256k1;
+
+    // The key the hot signer gives for this account, as imported by the user.
+    fn signer_key(signer: &Signer, network: Network, account: u32) -> DescriptorPublicKey {
+        let path =
+            generate_derivation_path(network, ChildNumber::from_hardened_idx(account).unwrap());
+        DescriptorPublicKey::from_str(&format!(
+            "[{}{}]{}",
+            signer.fingerprint(),
+            path.to_string().trim_start_matches('m'),
+            signer.get_extended_pubkey(&path)
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_regtest_descriptor() {
+        let network = Network::Regtest;
+        let signer = Signer::generate(network).unwrap();
+        let mut step = DefineDescriptor::new();
+        step.spending_keys[0].key = Some(signer_key(&signer, network, 0));
+        step.recovery_paths[0].keys[0].key = Some(signer_key(&signer, network, 1));
+        step.recovery_paths[0].sequence.value = "144".to_string();
+        // Choosing the network checks the keys against it.
+        step.set_network(network);
+        assert!(step.spending_keys[0].valid);
+        assert!(step.recovery_paths[0].keys[0].valid);
+
+        let mut ctx = Context::new(Network::Bitcoin, PathBuf::new());
+        assert!(step.apply(&mut ctx));
+        assert_eq!(ctx.bitcoin_config.network, network);
+        let secp = secp256k1::Secp256k1::verification_only();
+        let address = ctx
+            .descriptor
+            .unwrap()
+            .receive_descriptor()
+            .derive(ChildNumber::from_normal_idx(0).unwrap(), &secp)
+            .address(network);
+        assert!(address.to_string().starts_with("bcrt1"));
+        assert!(address.is_valid_for_network(network));
+
+        // A mainnet key is not valid on regtest.
+        let mainnet_signer = Signer::generate(Network::Bitcoin).unwrap();
+        step.spending_keys[0].key = Some(signer_key(&mainnet_signer, Network::Bitcoin, 0));
+        step.set_network(network);
+        assert!(!step.spending_keys[0].valid);
+    }
+
+    #[test]
+    fn test_import_descriptor_network() {
+        let desc = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+        let mut step = ImportDescriptor::new(true);
+        let _ = step.update(Message::DefineDescriptor(
+            message::DefineDescriptor::ImportDescriptor(desc.to_string()),
+        ));
+
+        // A testnet descriptor pasted into a mainnet install.
+        let mut ctx = Context::new(Network::Bitcoin, PathBuf::new());
+        assert!(!step.apply(&mut ctx));
+        assert!(!step.imported_descriptor.valid);
+        assert!(step.error.as_ref().unwrap().contains("abcdef01"));
+        assert!(ctx.descriptor.is_none());
+
+        step.network = Network::Signet;
+        assert!(step.apply(&mut ctx));
+        assert!(step.error.is_none());
+        assert!(ctx.descriptor.is_some());
+    }
+}
@@ -2,19 +2,40 @@ use std::collections::HashSet;
 use std::sync::Arc;
 
 use iced::{Command, Element};
-use liana::{bip39, signer::HotSigner};
+use liana::{
+    bip39,
+    signer::{HotSigner, MNEMONIC_WORD_COUNTS},
+};
+use zeroize::Zeroize;
 
 use crate::{
-    installer::{context::Context, message::Message, step::Step, view},
+    installer::{
+        context::Context,
+        message::Message,
+        step::{descriptor::check_descriptor_network, Step},
+        view,
+    },
     signer::Signer,
 };
 
-#[derive(Default)]
 pub struct BackupMnemonic {
-    words: [&'static str; 12],
+    words: Vec<&'static str>,
+    language: bip39::Language,
+    passphrase: bool,
     done: bool,
 }
 
+impl Default for BackupMnemonic {
+    fn default() -> Self {
+        Self {
+            words: Vec::new(),
+            language: bip39::Language::English,
+            passphrase: false,
+            done: false,
+        }
+    }
+}
+
 impl From<BackupMnemonic> for Box<dyn Step> {
     fn from(s: BackupMnemonic) -> Box<dyn Step> {
         Box::new(s)
@@ -25,6 +46,8 @@ impl Step for BackupMnemonic {
     fn load_context(&mut self, ctx: &Context) {
         if let Some(signer) = &ctx.signer {
             self.words = signer.mnemonic();
+            self.language = signer.language();
+            self.passphrase = signer.has_passphrase();
         }
     }
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -33,17 +56,34 @@ impl Step for BackupMnemonic {
         }
         Command::none()
     }
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        ctx.mnemonic_language = self.language;
+        true
+    }
     fn skip(&self, ctx: &Context) -> bool {
         ctx.signer.is_none()
     }
     fn view(&self, progress: (usize, usize)) -> Element<Message> {
-        view::backup_mnemonic(progress, &self.words, self.done)
+        view::backup_mnemonic(
+            progress,
+            &self.words,
+            self.language,
+            self.passphrase,
+            self.done,
+        )
+    }
+
+    fn discard(&mut self) {
+        self.words = Vec::new();
+        self.passphrase = false;
     }
 }
 
 pub struct RecoverMnemonic {
     language: bip39::Language,
-    words: [(String, bool); 12],
+    words: Vec<(String, bool)>,
+    passphrase: String,
+    passphrase_confirmation: String,
     current: usize,
     suggestions: Vec<String>,
     error: Option<String>,
@@ -55,7 +95,9 @@ impl Default for RecoverMnemonic {
     fn default() -> Self {
         Self {
             language: bip39::Language::English,
-            words: Default::default(),
+            words: vec![Default::default(); 12],
+            passphrase: String::new(),
+            passphrase_confirmation: String::new(),
             current: 0,
             suggestions: Vec::new(),
             error: None,
@@ -72,8 +114,18 @@ impl From<RecoverMnemonic> for Box<dyn Step> {
 }
 
 impl Step for RecoverMnemonic {
+    fn load_context(&mut self, ctx: &Context) {
+        self.language = ctx.mnemonic_language;
+    }
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
+            Message::MnemonicLanguage(language) => {
+                self.language = language;
+                self.suggestions = Vec::new();
+                for (word, valid) in self.words.iter_mut() {
+                    *valid = self.language.words_by_prefix(word).contains(&word.as_ref());
+                }
+            }
             Message::MnemonicWord(index, value) => {
                 if let Some((word, valid)) = self.words.get_mut(index) {
                     if value.len() >= 3 {
@@ -93,6 +145,23 @@ impl Step for RecoverMnemonic {
                     *word = value;
                 }
             }
+            Message::MnemonicWordCount(word_count) => {
+                for (word, _) in self.words.iter_mut().skip(word_count) {
+                    word.zeroize();
+                }
+                self.words.resize(word_count, Default::default());
+                self.current = self.current.min(word_count.saturating_sub(1));
+                self.suggestions = Vec::new();
+                self.error = None;
+            }
+            Message::MnemonicPassphrase(passphrase) => {
+                self.passphrase = passphrase;
+                self.error = None;
+            }
+            Message::MnemonicPassphraseConfirmation(confirmation) => {
+                self.passphrase_confirmation = confirmation;
+                self.error = None;
+            }
             Message::ImportMnemonic(recover) => self.recover = recover,
             Message::Skip => {
                 self.skip = true;
@@ -111,13 +180,26 @@ impl Step for RecoverMnemonic {
             return true;
         }
 
-        let words: Vec<String> = self
-            .words
-            .iter()
-            .filter_map(|(s, valid)| if *valid { Some(s.clone()) } else { None })
-            .collect();
+        if !MNEMONIC_WORD_COUNTS.contains(&self.words.len())
+            || self.words.iter().any(|(_, valid)| !valid)
+        {
+            self.error = Some(format!(
+                "The mnemonic must be made of {} valid words",
+                self.words.len()
+            ));
+            return false;
+        }
+        if self.passphrase != self.passphrase_confirmation {
+            self.error = Some("The passphrases do not match".to_string());
+            return false;
+        }
+        let words: Vec<&str> = self.words.iter().map(|(s, _)| s.as_str()).collect();
 
-        let seed = match HotSigner::from_str(ctx.bitcoin_config.network, &words.join(" ")) {
+        let seed = match HotSigner::from_str_with_passphrase(
+            ctx.bitcoin_config.network,
+            &words.join(" "),
+            &self.passphrase,
+        ) {
             Ok(seed) => seed,
             Err(e) => {
                 self.error = Some(e.to_string());
@@ -129,13 +211,20 @@ impl Step for RecoverMnemonic {
         let fingerprint = signer.fingerprint();
 
         if let Some(descriptor) = &ctx.descriptor {
+            // The fingerprints don't depend on the network.
+            if let Err(e) = check_descriptor_network(descriptor, ctx.bitcoin_config.network) {
+                self.error = Some(e);
+                return false;
+            }
             let info = descriptor.info();
             let mut descriptor_keys = HashSet::new();
             for (fingerprint, _) in info.primary_path().thresh_origins().1.iter() {
                 descriptor_keys.insert(*fingerprint);
             }
-            for (fingerprint, _) in info.recovery_path().1.thresh_origins().1.iter() {
-                descriptor_keys.insert(*fingerprint);
+            for path in info.recovery_paths().values() {
+                for (fingerprint, _) in path.thresh_origins().1.iter() {
+                    descriptor_keys.insert(*fingerprint);
+                }
             }
             if !descriptor_keys.contains(&fingerprint) {
                 self.error =
@@ -151,11 +240,24 @@ impl Step for RecoverMnemonic {
     fn view(&self, progress: (usize, usize)) -> Element<Message> {
         view::recover_mnemonic(
             progress,
+            self.language,
             &self.words,
+            &self.passphrase,
+            &self.passphrase_confirmation,
             self.current,
             &self.suggestions,
             self.recover,
             self.error.as_ref(),
         )
     }
+
+    fn discard(&mut self) {
+        for (word, valid) in self.words.iter_mut() {
+            word.zeroize();
+            *valid = false;
+        }
+        self.passphrase.zeroize();
+        self.passphrase_confirmation.zeroize();
+        self.suggestions.clear();
+    }
 }
@@ -11,9 +11,12 @@ use std::path::PathBuf;
 use std::str::FromStr;
 
 use iced::{Command, Element};
-use liana::{config::BitcoindConfig, miniscript::bitcoin};
+use liana::{
+    config::{BitcoindConfig, ElectrumConfig},
+    miniscript::bitcoin::{self, hashes::hex::FromHex},
+};
 
-use crate::ui::component::form;
+use crate::{app::wallet::GAP_LIMIT, ui::component::form};
 
 use crate::installer::{
     context::Context,
@@ -36,6 +39,8 @@ pub trait Step {
     fn apply(&mut self, _ctx: &mut Context) -> bool {
         true
     }
+    /// Overwrite the sensitive data held by the step, called when the installation is aborted.
+    fn discard(&mut self) {}
 }
 
 #[derive(Default)]
@@ -56,6 +61,19 @@ impl From<Welcome> for Box<dyn Step> {
 pub struct DefineBitcoind {
     cookie_path: form::Value<String>,
     address: form::Value<String>,
+    /// Challenge of the custom signet bitcoind runs on, empty for the default signet.
+    signet_challenge: form::Value<String>,
+    /// Address of the SOCKS5 proxy to connect through, if any.
+    proxy: form::Value<String>,
+    /// Connect to an Electrum server at this address instead of a bitcoind.
+    use_electrum: bool,
+    electrum_address: form::Value<String>,
+    /// Optional height of the block to rescan the chain from once the wallet is started.
+    rescan_height: form::Value<String>,
+    /// Number of addresses the daemon derives ahead of the last used one, empty for the default.
+    gap_limit: form::Value<String>,
+    /// Network the default cookie path and address were set for.
+    network: Option<bitcoin::Network>,
 }
 
 fn bitcoind_default_cookie_path(network: &bitcoin::Network) -> Option<String> {
@@ -106,18 +124,33 @@ impl DefineBitcoind {
         Self {
             cookie_path: form::Value::default(),
             address: form::Value::default(),
+            signet_challenge: form::Value::default(),
+            proxy: form::Value::default(),
+            use_electrum: false,
+            electrum_address: form::Value::default(),
+            rescan_height: form::Value::default(),
+            gap_limit: form::Value::default(),
+            network: None,
         }
     }
 }
 
 impl Step for DefineBitcoind {
     fn load_context(&mut self, ctx: &Context) {
-        if self.cookie_path.value.is_empty() {
-            self.cookie_path.value =
-                bitcoind_default_cookie_path(&ctx.bitcoin_config.network).unwrap_or_default()
+        let network = ctx.bitcoin_config.network;
+        // If the user went back to choose another network, the defaults of the previous one are
+        // replaced but not the values the user entered.
+        let previous = self.network.replace(network);
+        if self.cookie_path.value.is_empty()
+            || previous.and_then(|n| bitcoind_default_cookie_path(&n))
+                == Some(self.cookie_path.value.clone())
+        {
+            self.cookie_path.value = bitcoind_default_cookie_path(&network).unwrap_or_default()
         }
-        if self.address.value.is_empty() {
-            self.address.value = bitcoind_default_address(&ctx.bitcoin_config.network);
+        if self.address.value.is_empty()
+            || previous.map(|n| bitcoind_default_address(&n)) == Some(self.address.value.clone())
+        {
+            self.address.value = bitcoind_default_address(&network);
         }
     }
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -127,45 +160,144 @@ impl Step for DefineBitcoind {
                     self.address.value = address;
                     self.address.valid = true;
                 }
+                message::DefineBitcoind::SignetChallengeEdited(challenge) => {
+                    self.signet_challenge.value = challenge;
+                    self.signet_challenge.valid = true;
+                }
                 message::DefineBitcoind::CookiePathEdited(path) => {
                     self.cookie_path.value = path;
                     self.address.valid = true;
                 }
+                message::DefineBitcoind::ProxyEdited(proxy) => {
+                    self.proxy.value = proxy;
+                    self.proxy.valid = true;
+                }
+                message::DefineBitcoind::UseElectrum(use_electrum) => {
+                    self.use_electrum = use_electrum;
+                }
+                message::DefineBitcoind::ElectrumAddressEdited(address) => {
+                    self.electrum_address.value = address;
+                    self.electrum_address.valid = true;
+                }
+                message::DefineBitcoind::RescanHeightEdited(height) => {
+                    self.rescan_height.value = height;
+                    self.rescan_height.valid = true;
+                }
+                message::DefineBitcoind::GapLimitEdited(gap_limit) => {
+                    self.gap_limit.value = gap_limit;
+                    self.gap_limit.valid = true;
+                }
             };
         };
         Command::none()
     }
 
     fn apply(&mut self, ctx: &mut Context) -> bool {
-        match (
-            PathBuf::from_str(&self.cookie_path.value),
-            std::net::SocketAddr::from_str(&self.address.value),
-        ) {
-            (Err(_), Ok(_)) => {
-                self.cookie_path.valid = false;
-                false
+        let rescan_height = self.rescan_height.value.trim();
+        if rescan_height.is_empty() {
+            ctx.rescan_from_height = None;
+        } else {
+            match i32::from_str(rescan_height) {
+                Ok(height) if height > 0 => ctx.rescan_from_height = Some(height),
+                _ => {
+                    self.rescan_height.valid = false;
+                    return false;
+                }
             }
-            (Ok(_), Err(_)) => {
-                self.address.valid = false;
-                false
+        }
+
+        let gap_limit = self.gap_limit.value.trim();
+        if gap_limit.is_empty() {
+            ctx.bitcoin_config.gap_limit = GAP_LIMIT;
+        } else {
+            match u32::from_str(gap_limit) {
+                Ok(gap_limit) if gap_limit > 0 => ctx.bitcoin_config.gap_limit = gap_limit,
+                _ => {
+                    self.gap_limit.valid = false;
+                    return false;
+                }
             }
-            (Err(_), Err(_)) => {
-                self.cookie_path.valid = false;
-                self.address.valid = false;
-                false
+        }
+
+        if self.use_electrum {
+            // A host name is accepted as well as an IP address, only check there is a port.
+            let addr = self.electrum_address.value.trim();
+            self.electrum_address.valid = addr
+                .rsplitn(2, ':')
+                .next()
+                .map(|port| port != addr && u16::from_str(port).is_ok())
+                .unwrap_or(false);
+            if !self.electrum_address.valid {
+                return false;
             }
-            (Ok(path), Ok(addr)) => {
+            ctx.bitcoind_config = None;
+            ctx.electrum_config = Some(ElectrumConfig {
+                addr: addr.to_string(),
+            });
+            return true;
+        }
+
+        let cookie_path = PathBuf::from_str(&self.cookie_path.value);
+        let addr = std::net::SocketAddr::from_str(&self.address.value);
+        // The proxy is optional.
+        let proxy_addr = if self.proxy.value.trim().is_empty() {
+            Ok(None)
+        } else {
+            std::net::SocketAddr::from_str(self.proxy.value.trim()).map(Some)
+        };
+        self.cookie_path.valid = cookie_path.is_ok();
+        self.address.valid = addr.is_ok();
+        self.proxy.valid = proxy_addr.is_ok();
+
+        let challenge = self.signet_challenge.value.trim();
+        let signet_challenge =
+            if ctx.bitcoin_config.network != bitcoin::Network::Signet || challenge.is_empty() {
+                None
+            } else {
+                Some(challenge.to_string())
+            };
+        self.signet_challenge.valid = signet_challenge
+            .as_ref()
+            .map(|c| Vec::<u8>::from_hex(c).is_ok())
+            .unwrap_or(true);
+        if !self.signet_challenge.valid {
+            return false;
+        }
+
+        match (cookie_path, addr, proxy_addr) {
+            (Ok(path), Ok(addr), Ok(proxy_addr)) => {
+                ctx.electrum_config = None;
                 ctx.bitcoind_config = Some(BitcoindConfig {
                     cookie_path: path,
                     addr,
+                    zmq_block_addr: None,
+                    rpc_retries: None,
+                    rpc_timeout_secs: None,
+                    signet_challenge,
+                    proxy_addr,
                 });
                 true
             }
+            _ => false,
         }
     }
 
     fn view(&self, progress: (usize, usize)) -> Element<Message> {
-        view::define_bitcoin(progress, &self.address, &self.cookie_path)
+        view::define_bitcoin(
+            progress,
+            &self.address,
+            &self.cookie_path,
+            if self.network == Some(bitcoin::Network::Signet) {
+                Some(&self.signet_challenge)
+            } else {
+                None
+            },
+            &self.proxy,
+            self.use_electrum,
+            &self.electrum_address,
+            &self.rescan_height,
+            &self.gap_limit,
+        )
     }
 }
 
@@ -186,6 +318,9 @@ pub struct Final {
     context: Option<Context>,
     warning: Option<String>,
     config_path: Option<PathBuf>,
+    /// Optional password to encrypt the hot signer mnemonic with, and its confirmation.
+    password: String,
+    password_confirmation: String,
 }
 
 impl Final {
@@ -195,6 +330,8 @@ impl Final {
             generating: false,
             warning: None,
             config_path: None,
+            password: String::new(),
+            password_confirmation: String::new(),
         }
     }
 }
@@ -220,11 +357,34 @@ impl Step for Final {
                 self.config_path = None;
                 self.warning = None;
             }
+            Message::MnemonicStoragePassword(password) => self.password = password,
+            Message::MnemonicStoragePasswordConfirmation(confirmation) => {
+                self.password_confirmation = confirmation
+            }
             _ => {}
         };
         Command::none()
     }
 
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        if self.password != self.password_confirmation {
+            self.generating = false;
+            self.warning = Some("The passwords do not match".to_string());
+            return false;
+        }
+        ctx.mnemonic_password = if ctx.signer.is_some() && !self.password.is_empty() {
+            Some(self.password.clone())
+        } else {
+            None
+        };
+        true
+    }
+
+    fn discard(&mut self) {
+        self.password.clear();
+        self.password_confirmation.clear();
+    }
+
     fn view(&self, progress: (usize, usize)) -> Element<Message> {
         let ctx = self.context.as_ref().unwrap();
         let desc = ctx.descriptor.as_ref().unwrap().to_string();
@@ -235,6 +395,8 @@ impl Step for Final {
             self.generating,
             self.config_path.as_ref(),
             self.warning.as_ref(),
+            &self.password,
+            &self.password_confirmation,
         )
     }
 }
@@ -6,9 +6,14 @@ use iced::{alignment, Alignment, Element, Length};
 
 use std::collections::HashSet;
 
-use liana::miniscript::bitcoin;
+use liana::{
+    bip39,
+    descriptors::KeyDerivation,
+    miniscript::bitcoin::{self, util::bip32::DerivationPath},
+};
 
 use crate::{
+    app::wallet::GAP_LIMIT,
     hw::HardwareWallet,
     installer::{
         context::Context,
@@ -75,6 +80,168 @@ const NETWORKS: [Network; 4] = [
     Network::Regtest,
 ];
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MnemonicLanguage(bip39::Language);
+
+impl std::fmt::Display for MnemonicLanguage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0 {
+            bip39::Language::English => write!(f, "English"),
+            bip39::Language::SimplifiedChinese => write!(f, "Chinese (simplified)"),
+            bip39::Language::TraditionalChinese => write!(f, "Chinese (traditional)"),
+            bip39::Language::Czech => write!(f, "Czech"),
+            bip39::Language::French => write!(f, "French"),
+            bip39::Language::Italian => write!(f, "Italian"),
+            bip39::Language::Japanese => write!(f, "Japanese"),
+            bip39::Language::Korean => write!(f, "Korean"),
+            bip39::Language::Spanish => write!(f, "Spanish"),
+        }
+    }
+}
+
+const MNEMONIC_LANGUAGES: [MnemonicLanguage; 9] = [
+    MnemonicLanguage(bip39::Language::English),
+    MnemonicLanguage(bip39::Language::SimplifiedChinese),
+    MnemonicLanguage(bip39::Language::TraditionalChinese),
+    MnemonicLanguage(bip39::Language::Czech),
+    MnemonicLanguage(bip39::Language::French),
+    MnemonicLanguage(bip39::Language::Italian),
+    MnemonicLanguage(bip39::Language::Japanese),
+    MnemonicLanguage(bip39::Language::Korean),
+    MnemonicLanguage(bip39::Language::Spanish),
+];
+
+fn mnemonic_language_picker<'a>(language: bip39::Language) -> Row<'a, Message> {
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Mnemonic language:").bold())
+        .push(Container::new(
+            PickList::new(
+                &MNEMONIC_LANGUAGES[..],
+                Some(MnemonicLanguage(language)),
+                |lang| Message::MnemonicLanguage(lang.0),
+            )
+            .padding(10),
+        ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MnemonicWordCount(usize);
+
+impl std::fmt::Display for MnemonicWordCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} words", self.0)
+    }
+}
+
+const MNEMONIC_WORD_COUNTS: [MnemonicWordCount; 2] = [MnemonicWordCount(12), MnemonicWordCount(24)];
+
+fn mnemonic_word_count_picker<'a>(word_count: usize) -> Row<'a, Message> {
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Mnemonic length:").bold())
+        .push(Container::new(
+            PickList::new(
+                &MNEMONIC_WORD_COUNTS[..],
+                Some(MnemonicWordCount(word_count)),
+                |count| Message::MnemonicWordCount(count.0),
+            )
+            .padding(10),
+        ))
+}
+
+/// Masked inputs of the optional BIP39 passphrase of the mnemonic, typed twice.
+fn mnemonic_passphrase_inputs<'a>(passphrase: &str, confirmation: &str) -> Column<'a, Message> {
+    Column::new()
+        .spacing(10)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text("Mnemonic passphrase:").bold())
+                .push(
+                    Container::new(
+                        TextInput::new("Optional", passphrase, Message::MnemonicPassphrase)
+                            .password()
+                            .padding(10),
+                    )
+                    .width(Length::Units(250)),
+                )
+                .push(
+                    Container::new(
+                        TextInput::new(
+                            "Confirm the passphrase",
+                            confirmation,
+                            Message::MnemonicPassphraseConfirmation,
+                        )
+                        .password()
+                        .padding(10),
+                    )
+                    .width(Length::Units(250)),
+                ),
+        )
+        .push(if passphrase != confirmation {
+            text("The passphrases do not match")
+                .small()
+                .style(color::ALERT)
+        } else {
+            text(
+                "The passphrase is needed along with the words to recover the wallet, \
+                leave it empty for none.",
+            )
+            .small()
+        })
+}
+
+fn mnemonic_storage_password_inputs<'a>(
+    password: &str,
+    confirmation: &str,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text("Mnemonic encryption:").small().bold())
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new(
+                            "Password (optional)",
+                            password,
+                            Message::MnemonicStoragePassword,
+                        )
+                        .password()
+                        .padding(10),
+                    )
+                    .push(
+                        TextInput::new(
+                            "Confirm the password",
+                            confirmation,
+                            Message::MnemonicStoragePasswordConfirmation,
+                        )
+                        .password()
+                        .padding(10),
+                    ),
+            )
+            .push(if password != confirmation {
+                text("The passwords do not match")
+                    .small()
+                    .style(color::ALERT)
+            } else {
+                text(
+                    "The mnemonic stored on this computer is encrypted with this password, \
+                    which is then asked each time the wallet is opened. Leave it empty to store \
+                    the mnemonic unencrypted.",
+                )
+                .small()
+            }),
+    )
+    .width(Length::Fill)
+    .into()
+}
+
 pub fn welcome<'a>() -> Element<'a, Message> {
     Container::new(Container::new(
         Column::new()
@@ -141,11 +308,14 @@ pub fn define_descriptor<'a>(
     progress: (usize, usize),
     network: bitcoin::Network,
     network_valid: bool,
+    mnemonic_language: bip39::Language,
+    mnemonic_word_count: usize,
+    passphrase: &str,
+    passphrase_confirmation: &str,
+    taproot: bool,
     spending_keys: Vec<Element<'a, Message>>,
-    recovery_keys: Vec<Element<'a, Message>>,
-    sequence: &form::Value<String>,
     spending_threshold: usize,
-    recovery_threshold: usize,
+    recovery_paths: Vec<Element<'a, Message>>,
     valid: bool,
     error: Option<&String>,
 ) -> Element<'a, Message> {
@@ -166,6 +336,14 @@ pub fn define_descriptor<'a>(
                 "A data directory already exists for this network".to_string(),
             ))
         })
+        .push(Space::with_width(Length::Units(20)))
+        .push(mnemonic_language_picker(mnemonic_language))
+        .push(mnemonic_word_count_picker(mnemonic_word_count))
+        .push(Space::with_width(Length::Units(20)))
+        .push(Checkbox::new("Taproot", taproot, |taproot| {
+            Message::DefineDescriptor(message::DefineDescriptor::Taproot(taproot))
+        }))
+        .push(tooltip(prompt::DEFINE_DESCRIPTOR_TAPROOT_TOOLTIP))
         .padding(50);
 
     let col_spending_keys = Column::new()
@@ -187,7 +365,7 @@ pub fn define_descriptor<'a>(
                             spending_keys.len(),
                             |value| {
                                 Message::DefineDescriptor(
-                                    message::DefineDescriptor::ThresholdEdited(false, value),
+                                    message::DefineDescriptor::ThresholdEdited(None, value),
                                 )
                             },
                         ))
@@ -213,7 +391,7 @@ pub fn define_descriptor<'a>(
                                     .style(button::Style::TransparentBorder.into())
                                     .on_press(
                                         Message::DefineDescriptor(
-                                            message::DefineDescriptor::AddKey(false),
+                                            message::DefineDescriptor::AddKey(None),
                                         ),
                                     ),
                                 )
@@ -227,24 +405,128 @@ pub fn define_descriptor<'a>(
         )
         .spacing(10);
 
-    let col_recovery_keys = Column::new()
+    let col_recovery_paths = Column::new()
+        .push(Column::with_children(recovery_paths).spacing(25))
+        .push(
+            Container::new(
+                button::border(Some(icon::plus_icon()), "Add a recovery path").on_press(
+                    Message::DefineDescriptor(message::DefineDescriptor::AddRecoveryPath),
+                ),
+            )
+            .width(Length::Fill)
+            .align_x(alignment::Horizontal::Center),
+        )
+        .spacing(25);
+
+    layout(
+        progress,
+        Column::new()
+            .push(Space::with_height(Length::Units(30)))
+            .push(text("Create the wallet").bold().size(50))
+            .push(
+                Column::new()
+                    .push(row_network)
+                    .push(
+                        Container::new(mnemonic_passphrase_inputs(
+                            passphrase,
+                            passphrase_confirmation,
+                        ))
+                        .padding([0, 50]),
+                    )
+                    .push(col_spending_keys)
+                    .push(col_recovery_paths)
+                    .spacing(25),
+            )
+            .push(if !valid {
+                button::primary(None, "Next").width(Length::Units(200))
+            } else {
+                button::primary(None, "Next")
+                    .width(Length::Units(200))
+                    .on_press(Message::Next)
+            })
+            .push_maybe(error.map(|e| card::error("Failed to create descriptor", e.to_string())))
+            .push(Space::with_height(Length::Units(20)))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(50)
+            .align_items(Alignment::Center),
+    )
+}
+
+pub fn recovery_path<'a>(
+    index: usize,
+    keys: Vec<Element<'a, Message>>,
+    threshold: usize,
+    sequence: &form::Value<String>,
+    deletable: bool,
+) -> Element<'a, Message> {
+    let col_sequence = Container::new(
+        Row::new()
+            .spacing(50)
+            .align_items(Alignment::Center)
+            .push(Container::new(icon::arrow_down().size(50)).align_x(alignment::Horizontal::Right))
+            .push(
+                Column::new()
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(text("Blocks before recovery:").bold())
+                            .push(tooltip(prompt::DEFINE_DESCRIPTOR_SEQUENCE_TOOLTIP)),
+                    )
+                    .push(
+                        Container::new(
+                            form::Form::new("Number of blocks", sequence, move |msg| {
+                                Message::DefineDescriptor(
+                                    message::DefineDescriptor::SequenceEdited(index, msg),
+                                )
+                            })
+                            .warning("Please enter correct block number, different for each recovery path")
+                            .size(20)
+                            .padding(10),
+                        )
+                        .width(Length::Units(150)),
+                    )
+                    .spacing(10),
+            )
+            .padding(20),
+    )
+    .width(Length::Fill)
+    .align_x(alignment::Horizontal::Center);
+
+    let col_keys = Column::new()
         .push(
             Row::new()
+                .align_items(Alignment::Center)
                 .push(Space::with_width(Length::Units(50)))
-                .push(text("Recovery path:").bold()),
+                .push(
+                    text(format!("Recovery path {}:", index + 1))
+                        .bold()
+                        .width(Length::Fill),
+                )
+                .push_maybe(if deletable {
+                    Some(
+                        button::transparent(Some(icon::trash_icon()), "Delete").on_press(
+                            Message::DefineDescriptor(
+                                message::DefineDescriptor::DeleteRecoveryPath(index),
+                            ),
+                        ),
+                    )
+                } else {
+                    None
+                }),
         )
         .push(separation().width(Length::Fill))
         .push(
             Container::new(
                 Row::new()
                     .align_items(Alignment::Center)
-                    .push_maybe(if recovery_keys.len() > 1 {
+                    .push_maybe(if keys.len() > 1 {
                         Some(threshsold_input::threshsold_input(
-                            recovery_threshold,
-                            recovery_keys.len(),
-                            |value| {
+                            threshold,
+                            keys.len(),
+                            move |value| {
                                 Message::DefineDescriptor(
-                                    message::DefineDescriptor::ThresholdEdited(true, value),
+                                    message::DefineDescriptor::ThresholdEdited(Some(index), value),
                                 )
                             },
                         ))
@@ -256,7 +538,7 @@ pub fn define_descriptor<'a>(
                             Row::new()
                                 .spacing(5)
                                 .align_items(Alignment::Center)
-                                .push(Row::with_children(recovery_keys).spacing(5))
+                                .push(Row::with_children(keys).spacing(5))
                                 .push(
                                     Button::new(
                                         Container::new(icon::plus_icon().size(50))
@@ -270,7 +552,7 @@ pub fn define_descriptor<'a>(
                                     .style(button::Style::TransparentBorder.into())
                                     .on_press(
                                         Message::DefineDescriptor(
-                                            message::DefineDescriptor::AddKey(true),
+                                            message::DefineDescriptor::AddKey(Some(index)),
                                         ),
                                     ),
                                 )
@@ -284,66 +566,7 @@ pub fn define_descriptor<'a>(
         )
         .spacing(10);
 
-    let col_sequence = Container::new(
-        Row::new()
-            .spacing(50)
-            .align_items(Alignment::Center)
-            .push(Container::new(icon::arrow_down().size(50)).align_x(alignment::Horizontal::Right))
-            .push(
-                Column::new()
-                    .push(
-                        Row::new()
-                            .spacing(10)
-                            .push(text("Blocks before recovery:").bold())
-                            .push(tooltip(prompt::DEFINE_DESCRIPTOR_SEQUENCE_TOOLTIP)),
-                    )
-                    .push(
-                        Container::new(
-                            form::Form::new("Number of blocks", sequence, |msg| {
-                                Message::DefineDescriptor(
-                                    message::DefineDescriptor::SequenceEdited(msg),
-                                )
-                            })
-                            .warning("Please enter correct block number")
-                            .size(20)
-                            .padding(10),
-                        )
-                        .width(Length::Units(150)),
-                    )
-                    .spacing(10),
-            )
-            .padding(20),
-    )
-    .width(Length::Fill)
-    .align_x(alignment::Horizontal::Center);
-
-    layout(
-        progress,
-        Column::new()
-            .push(Space::with_height(Length::Units(30)))
-            .push(text("Create the wallet").bold().size(50))
-            .push(
-                Column::new()
-                    .push(row_network)
-                    .push(col_spending_keys)
-                    .push(col_sequence)
-                    .push(col_recovery_keys)
-                    .spacing(25),
-            )
-            .push(if !valid {
-                button::primary(None, "Next").width(Length::Units(200))
-            } else {
-                button::primary(None, "Next")
-                    .width(Length::Units(200))
-                    .on_press(Message::Next)
-            })
-            .push_maybe(error.map(|e| card::error("Failed to create descriptor", e.to_string())))
-            .push(Space::with_height(Length::Units(20)))
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .spacing(50)
-            .align_items(Alignment::Center),
-    )
+    Column::new().push(col_sequence).push(col_keys).into()
 }
 
 pub fn import_descriptor<'a>(
@@ -352,6 +575,7 @@ pub fn import_descriptor<'a>(
     network: bitcoin::Network,
     network_valid: bool,
     imported_descriptor: &form::Value<String>,
+    descriptor_file: &form::Value<String>,
     error: Option<&String>,
 ) -> Element<'a, Message> {
     let row_network = Row::new()
@@ -381,6 +605,28 @@ pub fn import_descriptor<'a>(
             .size(20)
             .padding(10),
         )
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(
+                    form::Form::new("Path of a wallet or descriptor backup file", descriptor_file, |msg| {
+                        Message::DefineDescriptor(message::DefineDescriptor::DescriptorFileEdited(
+                            msg,
+                        ))
+                    })
+                    .warning("Please enter a path")
+                    .size(20)
+                    .padding(10),
+                )
+                .push(if descriptor_file.valid && !descriptor_file.value.is_empty() {
+                    button::border(None, "Load from file").on_press(Message::DefineDescriptor(
+                        message::DefineDescriptor::LoadDescriptorFile,
+                    ))
+                } else {
+                    button::border(None, "Load from file")
+                }),
+        )
         .spacing(10);
     layout(
         progress,
@@ -602,10 +848,15 @@ pub fn hardware_wallet_xpubs<'a>(
     .into()
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn participate_xpub<'a>(
     progress: (usize, usize),
     network: bitcoin::Network,
     network_valid: bool,
+    mnemonic_language: bip39::Language,
+    mnemonic_word_count: usize,
+    passphrase: &str,
+    passphrase_confirmation: &str,
     hws: Vec<Element<'a, Message>>,
     signer: Element<'a, Message>,
     shared: bool,
@@ -626,7 +877,10 @@ pub fn participate_xpub<'a>(
             Some(card::warning(
                 "A data directory already exists for this network".to_string(),
             ))
-        });
+        })
+        .push(Space::with_width(Length::Units(20)))
+        .push(mnemonic_language_picker(mnemonic_language))
+        .push(mnemonic_word_count_picker(mnemonic_word_count));
 
     layout(
         progress,
@@ -636,7 +890,11 @@ pub fn participate_xpub<'a>(
                 Column::new()
                     .spacing(20)
                     .width(Length::Fill)
-                    .push(row_network),
+                    .push(row_network)
+                    .push(mnemonic_passphrase_inputs(
+                        passphrase,
+                        passphrase_confirmation,
+                    )),
             )
             .push(
                 Column::new()
@@ -682,6 +940,8 @@ pub fn participate_xpub<'a>(
 pub fn register_descriptor<'a>(
     progress: (usize, usize),
     descriptor: String,
+    verification_code: String,
+    keys_derivation: &[KeyDerivation],
     hws: &'a [HardwareWallet],
     registered: &HashSet<bitcoin::util::bip32::Fingerprint>,
     error: Option<&Error>,
@@ -704,8 +964,17 @@ pub fn register_descriptor<'a>(
                                 .on_press(Message::Clibpboard(descriptor)),
                         ),
                     )
+                    .push(verification_code_view(verification_code))
+                    .push(keys_derivation_view(keys_derivation))
                     .spacing(10),
             ))
+            .push_maybe(if keys_derivation.iter().all(|key| key.is_standard()) {
+                None
+            } else {
+                Some(card::warning(
+                    prompt::NON_STANDARD_DERIVATION_WARNING.to_string(),
+                ))
+            })
             .push(text(prompt::REGISTER_DESCRIPTOR_HELP))
             .push_maybe(error.map(|e| card::error("Failed to register descriptor", e.to_string())))
             .push(
@@ -764,9 +1033,42 @@ pub fn register_descriptor<'a>(
     )
 }
 
+/// The receive and change derivation paths of every key of the descriptor, relative to its xpub.
+fn keys_derivation_view<'a>(keys_derivation: &[KeyDerivation]) -> Column<'a, Message> {
+    // Display a path relative to the xpub, without the leading 'm'.
+    fn relative(path: &DerivationPath) -> String {
+        path.to_string().trim_start_matches('m').to_string()
+    }
+    keys_derivation.iter().fold(
+        Column::new()
+            .spacing(5)
+            .push(text("Keys derivation:").small().bold()),
+        |col, key| {
+            let (fingerprint, origin) = &key.origin;
+            col.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        text(format!("[{}{}]", fingerprint, relative(origin)))
+                            .small()
+                            .width(Length::Fill),
+                    )
+                    .push(text(format!("receive: xpub{}/*", relative(&key.receive_path))).small())
+                    .push(text(format!("change: xpub{}/*", relative(&key.change_path))).small())
+                    .push_maybe(if key.is_standard() {
+                        None
+                    } else {
+                        Some(icon::warning_icon().small().style(color::WARNING))
+                    }),
+            )
+        },
+    )
+}
+
 pub fn backup_descriptor<'a>(
     progress: (usize, usize),
     descriptor: String,
+    verification_code: String,
     done: bool,
 ) -> Element<'a, Message> {
     layout(
@@ -815,6 +1117,7 @@ pub fn backup_descriptor<'a>(
                                 .on_press(Message::Clibpboard(descriptor)),
                         ),
                     )
+                    .push(verification_code_view(verification_code))
                     .spacing(10)
                     .max_width(1000),
             ))
@@ -838,6 +1141,20 @@ pub fn backup_descriptor<'a>(
     )
 }
 
+/// The short code of the descriptor, to compare with the other installations of the wallet.
+fn verification_code_view<'a>(code: String) -> Column<'a, Message> {
+    Column::new()
+        .spacing(5)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text("Verification code:").small().bold())
+                .push(text(code).size(25).bold()),
+        )
+        .push(text(prompt::VERIFICATION_CODE_HELP).small())
+}
+
 pub fn help_backup<'a>() -> Element<'a, Message> {
     text(prompt::BACKUP_DESCRIPTOR_HELP).small().into()
 }
@@ -846,7 +1163,81 @@ pub fn define_bitcoin<'a>(
     progress: (usize, usize),
     address: &form::Value<String>,
     cookie_path: &form::Value<String>,
+    signet_challenge: Option<&form::Value<String>>,
+    proxy: &form::Value<String>,
+    use_electrum: bool,
+    electrum_address: &form::Value<String>,
+    rescan_height: &form::Value<String>,
+    gap_limit: &form::Value<String>,
 ) -> Element<'a, Message> {
+    let col_advanced = Column::new()
+        .push(text("Advanced settings").bold().size(25))
+        .push(text("Rescan from block height (optional):").bold())
+        .push(
+            form::Form::new("Block height", rescan_height, |msg| {
+                Message::DefineBitcoind(message::DefineBitcoind::RescanHeightEdited(msg))
+            })
+            .warning("Please enter a block height above 0")
+            .size(20)
+            .padding(10),
+        )
+        .push(text(prompt::RESCAN_FROM_HEIGHT_HELP).small())
+        .push(text("Gap limit (optional):").bold())
+        .push(
+            form::Form::new(&GAP_LIMIT.to_string(), gap_limit, |msg| {
+                Message::DefineBitcoind(message::DefineBitcoind::GapLimitEdited(msg))
+            })
+            .warning("Please enter a number of addresses above 0")
+            .size(20)
+            .padding(10),
+        )
+        .push(text(prompt::GAP_LIMIT_HELP).small())
+        .spacing(10);
+
+    let use_electrum_checkbox = Checkbox::new(
+        "Connect to an Electrum server instead",
+        use_electrum,
+        |checked| Message::DefineBitcoind(message::DefineBitcoind::UseElectrum(checked)),
+    );
+
+    if use_electrum {
+        let col_electrum = Column::new()
+            .push(text("Address:").bold())
+            .push(
+                form::Form::new("127.0.0.1:50001", electrum_address, |msg| {
+                    Message::DefineBitcoind(message::DefineBitcoind::ElectrumAddressEdited(msg))
+                })
+                .warning("Please enter a correct address, for instance 127.0.0.1:50001")
+                .size(20)
+                .padding(10),
+            )
+            .push(text(prompt::ELECTRUM_TCP_HELP).small())
+            .spacing(10);
+
+        return layout(
+            progress,
+            Column::new()
+                .push(
+                    text("Set up connection to the Electrum server")
+                        .bold()
+                        .size(50),
+                )
+                .push(use_electrum_checkbox)
+                .push(col_electrum)
+                .push(col_advanced)
+                .push(
+                    button::primary(None, "Next")
+                        .on_press(Message::Next)
+                        .width(Length::Units(200)),
+                )
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .padding(100)
+                .spacing(50)
+                .align_items(Alignment::Center),
+        );
+    }
+
     let col_address = Column::new()
         .push(text("Address:").bold())
         .push(
@@ -871,6 +1262,19 @@ pub fn define_bitcoin<'a>(
         )
         .spacing(10);
 
+    let col_proxy = Column::new()
+        .push(text("SOCKS5 proxy (optional):").bold())
+        .push(
+            form::Form::new("127.0.0.1:9050", proxy, |msg| {
+                Message::DefineBitcoind(message::DefineBitcoind::ProxyEdited(msg))
+            })
+            .warning("Please enter a correct proxy address, for instance 127.0.0.1:9050")
+            .size(20)
+            .padding(10),
+        )
+        .push(text("To connect to a remote node through Tor or an SSH tunnel.").small())
+        .spacing(10);
+
     layout(
         progress,
         Column::new()
@@ -879,8 +1283,27 @@ pub fn define_bitcoin<'a>(
                     .bold()
                     .size(50),
             )
+            .push(use_electrum_checkbox)
             .push(col_address)
             .push(col_cookie)
+            .push_maybe(signet_challenge.map(|challenge| {
+                Column::new()
+                    .push(text("Signet challenge (optional):").bold())
+                    .push(
+                        form::Form::new("Default signet", challenge, |msg| {
+                            Message::DefineBitcoind(message::DefineBitcoind::SignetChallengeEdited(
+                                msg,
+                            ))
+                        })
+                        .warning("Please enter the challenge script in hex")
+                        .size(20)
+                        .padding(10),
+                    )
+                    .push(text(prompt::SIGNET_CHALLENGE_HELP).small())
+                    .spacing(10)
+            }))
+            .push(col_proxy)
+            .push(col_advanced)
             .push(
                 button::primary(None, "Next")
                     .on_press(Message::Next)
@@ -894,6 +1317,7 @@ pub fn define_bitcoin<'a>(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn install<'a>(
     progress: (usize, usize),
     context: &Context,
@@ -901,6 +1325,8 @@ pub fn install<'a>(
     generating: bool,
     config_path: Option<&std::path::PathBuf>,
     warning: Option<&'a String>,
+    password: &str,
+    password_confirmation: &str,
 ) -> Element<'a, Message> {
     layout(
         progress,
@@ -966,7 +1392,20 @@ pub fn install<'a>(
                                 .width(Length::Fill),
                             )
                         })
-                        .push(
+                        .push(if let Some(electrum_config) = &context.electrum_config {
+                            card::simple(
+                                Column::new()
+                                    .push(text("Electrum server:").small().bold())
+                                    .push(
+                                        Row::new()
+                                            .spacing(5)
+                                            .align_items(Alignment::Center)
+                                            .push(text("Address:").small())
+                                            .push(text(electrum_config.addr.clone()).small()),
+                                    ),
+                            )
+                            .width(Length::Fill)
+                        } else {
                             card::simple(
                                 Column::new()
                                     .push(text("Bitcoind:").small().bold())
@@ -1002,11 +1441,22 @@ pub fn install<'a>(
                                             ),
                                     ),
                             )
-                            .width(Length::Fill),
-                        ),
+                            .width(Length::Fill)
+                        }),
                 )
                 .max_width(1000),
             )
+            .push_maybe(if context.signer.is_some() && config_path.is_none() {
+                Some(
+                    Container::new(mnemonic_storage_password_inputs(
+                        password,
+                        password_confirmation,
+                    ))
+                    .max_width(1000),
+                )
+            } else {
+                None
+            })
             .push(Space::with_height(Length::Units(50)))
             .push_maybe(warning.map(|e| card::invalid(text(e))))
             .push(if generating {
@@ -1017,7 +1467,7 @@ pub fn install<'a>(
                         .push(Container::new(text("Installed !")))
                         .push(Container::new(
                             button::primary(None, "Start")
-                                .on_press(Message::Exit(path.clone()))
+                                .on_press(Message::Exit(path.clone(), context.rescan_from_height))
                                 .width(Length::Units(200)),
                         ))
                         .align_items(Alignment::Center)
@@ -1419,7 +1869,9 @@ fn hw_list_view(
 
 pub fn backup_mnemonic<'a>(
     progress: (usize, usize),
-    words: &'a [&'static str; 12],
+    words: &'a [&'static str],
+    language: bip39::Language,
+    passphrase: bool,
     done: bool,
 ) -> Element<'a, Message> {
     layout(
@@ -1427,6 +1879,24 @@ pub fn backup_mnemonic<'a>(
         Column::new()
             .push(text("Backup your mnemonic").bold().size(50))
             .push(text(prompt::MNEMONIC_HELP))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(text("Mnemonic language:").bold())
+                    .push(text(MnemonicLanguage(language).to_string())),
+            )
+            .push_maybe(if passphrase {
+                Some(
+                    text(
+                        "This mnemonic is protected by the passphrase you entered: back it up \
+                        as well, the words alone do not recover the wallet.",
+                    )
+                    .bold()
+                    .style(color::WARNING),
+                )
+            } else {
+                None
+            })
             .push(
                 words
                     .iter()
@@ -1463,9 +1933,13 @@ pub fn backup_mnemonic<'a>(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn recover_mnemonic<'a>(
     progress: (usize, usize),
-    words: &'a [(String, bool); 12],
+    language: bip39::Language,
+    words: &'a [(String, bool)],
+    passphrase: &str,
+    passphrase_confirmation: &str,
     current: usize,
     suggestions: &'a Vec<String>,
     recover: bool,
@@ -1480,6 +1954,12 @@ pub fn recover_mnemonic<'a>(
                 Some(
                     Column::new()
                         .align_items(Alignment::Center)
+                        .push(
+                            Row::new()
+                                .spacing(20)
+                                .push(mnemonic_language_picker(language))
+                                .push(mnemonic_word_count_picker(words.len())),
+                        )
                         .push(
                             Container::new(if !suggestions.is_empty() {
                                 suggestions.iter().fold(Row::new().spacing(5), |row, sugg| {
@@ -1524,6 +2004,10 @@ pub fn recover_mnemonic<'a>(
                             },
                         ))
                         .push(Space::with_height(Length::Units(50)))
+                        .push(mnemonic_passphrase_inputs(
+                            passphrase,
+                            passphrase_confirmation,
+                        ))
                         .push_maybe(error.map(|e| card::invalid(text(e).style(color::ALERT)))),
                 )
             } else {
@@ -1551,7 +2035,10 @@ pub fn recover_mnemonic<'a>(
                             .width(Length::Units(200)),
                     )
                     .push(
-                        if words.iter().any(|(_, valid)| !valid) || error.is_some() {
+                        if words.iter().any(|(_, valid)| !valid)
+                            || error.is_some()
+                            || passphrase != passphrase_confirmation
+                        {
                             button::primary(None, "Next").width(Length::Units(200))
                         } else {
                             button::primary(None, "Next")
@@ -1575,8 +2062,20 @@ fn layout<'a>(
     Container::new(Scrollable::new(
         Column::new()
             .push(
-                Container::new(button::transparent(None, "< Previous").on_press(Message::Previous))
-                    .padding(5),
+                Row::new()
+                    .push(
+                        Container::new(
+                            button::transparent(None, "< Previous").on_press(Message::Previous),
+                        )
+                        .padding(5),
+                    )
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        Container::new(
+                            button::transparent(None, "Abort and discard").on_press(Message::Abort),
+                        )
+                        .padding(5),
+                    ),
             )
             .push(
                 Container::new(text(format!("{}/{}", progress.0, progress.1)))
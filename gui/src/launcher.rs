@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
 use iced::{
     widget::{Button, Column, Container, Row},
@@ -10,38 +13,54 @@ use liana::{config::ConfigError, miniscript::bitcoin::Network};
 use crate::{
     app,
     ui::{
-        component::{badge, button, card, text::*},
+        component::{
+            badge, button, card,
+            network::{network_color, network_name},
+            text::*,
+        },
         icon,
         util::*,
     },
 };
 
+/// Subdirectory of the data directory holding the wallets created in addition to the ones of
+/// the data directory itself, each in a directory of its own.
+pub const WALLETS_DIR_NAME: &str = "wallets";
+
+/// A wallet found in the data directory.
+#[derive(Debug, Clone)]
+pub struct WalletEntry {
+    /// The directory whose network subdirectory holds the configuration, the settings and the
+    /// data of the wallet.
+    pub datadir_path: PathBuf,
+    pub network: Network,
+    /// The name of the wallet in its settings, if any.
+    pub name: Option<String>,
+}
+
 pub struct Launcher {
-    choices: Vec<Network>,
+    choices: Vec<WalletEntry>,
     datadir_path: PathBuf,
     error: Option<String>,
 }
 
 impl Launcher {
+    /// The launcher of the data directory, or of the data directory the given wallet directory
+    /// is in.
     pub fn new(datadir_path: PathBuf) -> Self {
-        let mut choices = Vec::new();
-        for network in [
-            Network::Bitcoin,
-            Network::Testnet,
-            Network::Signet,
-            Network::Regtest,
-        ] {
-            if datadir_path.join(network.to_string()).exists() {
-                choices.push(network)
-            }
-        }
+        let datadir_path = root_datadir(datadir_path);
         Self {
+            choices: list_wallets(&datadir_path),
             datadir_path,
-            choices,
             error: None,
         }
     }
 
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
     pub fn stop(&mut self) {}
 
     pub fn subscription(&self) -> Subscription<Message> {
@@ -51,21 +70,36 @@ impl Launcher {
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::View(ViewMessage::StartInstall) => {
-                let datadir_path = self.datadir_path.clone();
+                // A new wallet never overwrites the files of an existing one.
+                let datadir_path = new_wallet_datadir(&self.datadir_path);
+                if let Err(e) = std::fs::create_dir_all(&datadir_path) {
+                    self.error = Some(format!(
+                        "Failed to create the directory of the new wallet: {}",
+                        e
+                    ));
+                    return Command::none();
+                }
                 Command::perform(async move { datadir_path }, Message::Install)
             }
-            Message::View(ViewMessage::Check(network)) => Command::perform(
-                check_network_datadir(self.datadir_path.clone(), network),
-                Message::Checked,
-            ),
+            Message::View(ViewMessage::Check(i)) => match self.choices.get(i) {
+                Some(choice) => Command::perform(
+                    check_network_datadir(choice.datadir_path.clone(), choice.network),
+                    move |res| Message::Checked(res.map(|_| i)),
+                ),
+                None => Command::none(),
+            },
             Message::Checked(res) => match res {
                 Err(e) => {
                     self.error = Some(e);
                     Command::none()
                 }
-                Ok(network) => {
-                    let datadir_path = self.datadir_path.clone();
-                    let mut path = self.datadir_path.clone();
+                Ok(i) => {
+                    let WalletEntry {
+                        datadir_path,
+                        network,
+                        ..
+                    } = self.choices[i].clone();
+                    let mut path = datadir_path.clone();
                     path.push(network.to_string());
                     path.push(app::config::DEFAULT_FILE_NAME);
                     let cfg = app::Config::from_file(&path).expect("Already checked");
@@ -88,11 +122,12 @@ impl Launcher {
                     .push(
                         self.choices
                             .iter()
+                            .enumerate()
                             .fold(
                                 Column::new()
-                                    .push(text("Select network:").small().bold())
+                                    .push(text("Select wallet:").small().bold())
                                     .spacing(10),
-                                |col, choice| {
+                                |col, (i, choice)| {
                                     col.push(
                                         Button::new(
                                             Row::new()
@@ -100,7 +135,7 @@ impl Launcher {
                                                 .align_items(Alignment::Center)
                                                 .push(
                                                     badge::Badge::new(icon::bitcoin_icon()).style(
-                                                        match choice {
+                                                        match choice.network {
                                                             Network::Bitcoin => {
                                                                 badge::Style::Bitcoin
                                                             }
@@ -108,14 +143,18 @@ impl Launcher {
                                                         },
                                                     ),
                                                 )
-                                                .push(text(match choice {
-                                                    Network::Bitcoin => "Bitcoin Mainnet",
-                                                    Network::Testnet => "Bitcoin Testnet",
-                                                    Network::Signet => "Bitcoin Signet",
-                                                    Network::Regtest => "Bitcoin Regtest",
-                                                })),
+                                                .push_maybe(
+                                                    choice
+                                                        .name
+                                                        .as_ref()
+                                                        .map(|name| text(name).bold()),
+                                                )
+                                                .push(
+                                                    text(network_name(choice.network))
+                                                        .style(network_color(choice.network)),
+                                                ),
                                         )
-                                        .on_press(ViewMessage::Check(*choice))
+                                        .on_press(ViewMessage::Check(i))
                                         .padding(10)
                                         .width(Length::Fill)
                                         .style(button::Style::Border.into()),
@@ -128,7 +167,7 @@ impl Launcher {
                                         .spacing(20)
                                         .align_items(Alignment::Center)
                                         .push(badge::Badge::new(icon::plus_icon()))
-                                        .push(text("Install Liana on another network")),
+                                        .push(text("Create a new wallet")),
                                 )
                                 .on_press(ViewMessage::StartInstall)
                                 .padding(10)
@@ -152,14 +191,71 @@ impl Launcher {
 pub enum Message {
     View(ViewMessage),
     Install(PathBuf),
-    Checked(Result<Network, String>),
+    /// The index of the wallet whose configuration was checked.
+    Checked(Result<usize, String>),
     Run(PathBuf, app::config::Config, Network),
 }
 
 #[derive(Debug, Clone)]
 pub enum ViewMessage {
     StartInstall,
-    Check(Network),
+    /// Check the configuration of the wallet at this index before running it.
+    Check(usize),
+}
+
+/// The data directory a wallet directory is in, if it is one of the additional wallets.
+fn root_datadir(datadir_path: PathBuf) -> PathBuf {
+    let root = datadir_path
+        .parent()
+        .filter(|parent| parent.file_name() == Some(OsStr::new(WALLETS_DIR_NAME)))
+        .and_then(|parent| parent.parent())
+        .map(Path::to_path_buf);
+    root.unwrap_or(datadir_path)
+}
+
+/// The wallets of the data directory itself, one per network, followed by the additional
+/// wallets.
+fn list_wallets(datadir_path: &Path) -> Vec<WalletEntry> {
+    let mut dirs = vec![datadir_path.to_path_buf()];
+    if let Ok(entries) = std::fs::read_dir(datadir_path.join(WALLETS_DIR_NAME)) {
+        let mut wallet_dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        wallet_dirs.sort();
+        dirs.extend(wallet_dirs);
+    }
+
+    let mut wallets = Vec::new();
+    for dir in dirs {
+        for network in &[
+            Network::Bitcoin,
+            Network::Testnet,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            if dir.join(network.to_string()).exists() {
+                let name = app::settings::Settings::from_file(dir.clone(), *network)
+                    .ok()
+                    .and_then(|settings| settings.wallets.first().map(|w| w.name.clone()));
+                wallets.push(WalletEntry {
+                    datadir_path: dir.clone(),
+                    network: *network,
+                    name,
+                });
+            }
+        }
+    }
+    wallets
+}
+
+/// A directory for a new wallet, which does not exist yet.
+fn new_wallet_datadir(datadir_path: &Path) -> PathBuf {
+    let wallets_dir = datadir_path.join(WALLETS_DIR_NAME);
+    (1..)
+        .map(|i| wallets_dir.join(format!("wallet-{}", i)))
+        .find(|path| !path.exists())
+        .expect("Must find a free directory name")
 }
 
 async fn check_network_datadir(mut path: PathBuf, network: Network) -> Result<Network, String> {
@@ -206,3 +302,39 @@ async fn check_network_datadir(mut path: PathBuf, network: Network) -> Result<Ne
 
     Ok(network)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_wallets() {
+        let datadir =
+            std::env::temp_dir().join(format!("liana-gui-launcher-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&datadir);
+        std::fs::create_dir_all(datadir.join(Network::Testnet.to_string())).unwrap();
+
+        let wallet_dir = new_wallet_datadir(&datadir);
+        assert_eq!(wallet_dir, datadir.join(WALLETS_DIR_NAME).join("wallet-1"));
+        std::fs::create_dir_all(wallet_dir.join(Network::Bitcoin.to_string())).unwrap();
+        assert_eq!(
+            new_wallet_datadir(&datadir),
+            datadir.join(WALLETS_DIR_NAME).join("wallet-2")
+        );
+
+        // The wallets of the data directory come first.
+        let wallets = list_wallets(&datadir);
+        assert_eq!(wallets.len(), 2);
+        assert_eq!(wallets[0].datadir_path, datadir);
+        assert_eq!(wallets[0].network, Network::Testnet);
+        assert_eq!(wallets[1].datadir_path, wallet_dir);
+        assert_eq!(wallets[1].network, Network::Bitcoin);
+        assert!(wallets[1].name.is_none());
+
+        // Going back to the launcher from a wallet lists all of them.
+        assert_eq!(root_datadir(wallet_dir), datadir);
+        assert_eq!(root_datadir(datadir.clone()), datadir);
+
+        std::fs::remove_dir_all(&datadir).unwrap();
+    }
+}
@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use iced::{
-    widget::{Column, Container, ProgressBar, Row},
+    widget::{Column, Container, ProgressBar, Row, TextInput},
     Element,
 };
 use iced::{Alignment, Command, Length, Subscription};
@@ -12,7 +12,9 @@ use tracing::{debug, info};
 
 use liana::{
     config::{Config, ConfigError},
+    descriptors::MultipathDescriptor,
     miniscript::bitcoin,
+    signer::{HotSigner, SignerError},
     StartupError,
 };
 
@@ -20,10 +22,12 @@ use crate::{
     app::{
         cache::Cache,
         config::Config as GUIConfig,
+        settings::{Settings, SettingsError},
         wallet::{Wallet, WalletError},
     },
     daemon::{client, embedded::EmbeddedDaemon, model::*, Daemon, DaemonError},
     ui::{
+        color,
         component::{button, notification, text::*},
         icon,
         util::Collection,
@@ -37,6 +41,11 @@ pub struct Loader {
     pub network: bitcoin::Network,
     pub gui_config: GUIConfig,
     pub daemon_started: bool,
+    /// Password of the encrypted hot signer mnemonics, given by the user once prompted.
+    pub mnemonic_password: Option<String>,
+    /// Height of the block to rescan the chain from once the application is loaded, as set
+    /// during the installation.
+    pub rescan_from_height: Option<i32>,
 
     step: Step,
 }
@@ -48,6 +57,12 @@ pub enum Step {
         daemon: Arc<dyn Daemon + Sync + Send>,
         progress: f64,
     },
+    /// The hot signer mnemonics are encrypted, the user is prompted for their password.
+    Unlock {
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        password: String,
+        error: Option<String>,
+    },
     Error(Box<Error>),
 }
 
@@ -80,6 +95,8 @@ impl Loader {
                 gui_config,
                 step: Step::Connecting,
                 daemon_started: false,
+                mnemonic_password: None,
+                rescan_from_height: None,
             },
             Command::perform(connect(path), Message::Loaded),
         )
@@ -151,6 +168,7 @@ impl Loader {
                                     self.gui_config.clone(),
                                     self.datadir_path.clone(),
                                     self.network,
+                                    self.mnemonic_password.clone(),
                                 ),
                                 Message::Synced,
                             );
@@ -169,9 +187,37 @@ impl Loader {
         }
     }
 
+    fn on_locked(&mut self, error: Option<String>) -> Command<Message> {
+        self.mnemonic_password = None;
+        if let Step::Syncing { daemon, .. } = &self.step {
+            self.step = Step::Unlock {
+                daemon: daemon.clone(),
+                password: String::new(),
+                error,
+            };
+        }
+        Command::none()
+    }
+
+    fn on_unlock(&mut self) -> Command<Message> {
+        if let Step::Unlock {
+            daemon, password, ..
+        } = &mut self.step
+        {
+            self.mnemonic_password = Some(std::mem::take(password));
+            let daemon = daemon.clone();
+            self.step = Step::Syncing {
+                daemon: daemon.clone(),
+                progress: 1.0,
+            };
+            return Command::perform(sync(daemon, false), Message::Syncing);
+        }
+        Command::none()
+    }
+
     pub fn stop(&mut self) {
         info!("Close requested");
-        if let Step::Syncing { daemon, .. } = &mut self.step {
+        if let Step::Syncing { daemon, .. } | Step::Unlock { daemon, .. } = &mut self.step {
             if !daemon.is_external() {
                 info!("Stopping internal daemon...");
                 if let Some(d) = Arc::get_mut(daemon) {
@@ -186,17 +232,31 @@ impl Loader {
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::View(ViewMessage::Retry) => {
-                let (loader, cmd) = Self::new(
+                let (mut loader, cmd) = Self::new(
                     self.datadir_path.clone(),
                     self.gui_config.clone(),
                     self.network,
                 );
+                loader.rescan_from_height = self.rescan_from_height.take();
                 *self = loader;
                 cmd
             }
             Message::Started(res) => self.on_start(res),
             Message::Loaded(res) => self.on_load(res),
             Message::Syncing(res) => self.on_sync(res),
+            Message::View(ViewMessage::MnemonicPasswordEdited(value)) => {
+                if let Step::Unlock { password, .. } = &mut self.step {
+                    *password = value;
+                }
+                Command::none()
+            }
+            Message::View(ViewMessage::Unlock) => self.on_unlock(),
+            Message::Synced(Err(Error::Wallet(WalletError::MnemonicPasswordRequired))) => {
+                self.on_locked(None)
+            }
+            Message::Synced(Err(Error::Wallet(WalletError::MnemonicDecryption))) => {
+                self.on_locked(Some(WalletError::MnemonicDecryption.to_string()))
+            }
             Message::Synced(Err(e)) => {
                 self.step = Step::Error(Box::new(e));
                 Command::none()
@@ -224,27 +284,127 @@ pub async fn load_application(
     gui_config: GUIConfig,
     datadir_path: PathBuf,
     network: bitcoin::Network,
+    mnemonic_password: Option<String>,
 ) -> Result<(Arc<Wallet>, Cache, Arc<dyn Daemon + Sync + Send>), Error> {
+    let problems = check_datadir(&datadir_path, network, &gui_config, &info.descriptors.main);
+    if !problems.is_empty() {
+        return Err(Error::Datadir(problems));
+    }
+
+    let wallet = Wallet::new(info.descriptors.main).load_settings(
+        &gui_config,
+        &datadir_path,
+        network,
+        mnemonic_password.as_deref(),
+    )?;
+
     let coins = daemon.list_coins().map(|res| res.coins)?;
     let spend_txs = daemon.list_spend_transactions()?;
-    let cache = Cache {
+    let mut cache = Cache {
         network: info.network,
         blockheight: info.block_height,
-        coins,
+        sync_progress: info.sync,
+        mempool_min_feerate: info.mempool_min_feerate,
+        gap_limit: info.addresses.gap_limit,
+        unit: wallet.display_unit,
+        watch_only: wallet.watch_only,
+        confirmation_tiers: wallet.confirmation_tiers.clone(),
+        labels: wallet.labels.clone(),
+        address_book: wallet.address_book.clone(),
         spend_txs,
         ..Default::default()
     };
-
-    let wallet =
-        Wallet::new(info.descriptors.main).load_settings(&gui_config, &datadir_path, network)?;
+    cache.set_coins(coins, wallet.displayed_history_months);
 
     Ok((Arc::new(wallet), cache, daemon))
 }
 
+/// A problem with the content of the wallet datadir.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DatadirProblem {
+    MissingNetworkDirectory(PathBuf),
+    CorruptedSettings(String),
+    CorruptedMnemonics(String),
+    CorruptedDaemonConfig(String),
+    DescriptorMismatch,
+}
+
+impl std::fmt::Display for DatadirProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingNetworkDirectory(path) => {
+                write!(
+                    f,
+                    "The wallet directory {} is missing",
+                    path.to_string_lossy()
+                )
+            }
+            Self::CorruptedSettings(e) => write!(f, "The GUI settings are unreadable: {}", e),
+            Self::CorruptedMnemonics(e) => {
+                write!(f, "The mnemonics of the hot signers are unreadable: {}", e)
+            }
+            Self::CorruptedDaemonConfig(e) => {
+                write!(f, "The daemon configuration is unreadable: {}", e)
+            }
+            Self::DescriptorMismatch => write!(
+                f,
+                "The descriptor of the daemon configuration is not the one of the running daemon"
+            ),
+        }
+    }
+}
+
+/// Check the content of the wallet datadir before loading the wallet, and return all the
+/// problems found at once.
+pub fn check_datadir(
+    datadir_path: &Path,
+    network: bitcoin::Network,
+    gui_config: &GUIConfig,
+    descriptor: &MultipathDescriptor,
+) -> Vec<DatadirProblem> {
+    let mut problems = Vec::new();
+
+    let network_dir = datadir_path.join(network.to_string());
+    if !network_dir.is_dir() {
+        problems.push(DatadirProblem::MissingNetworkDirectory(network_dir));
+        return problems;
+    }
+
+    // The settings file is optional, but must be valid if it exists.
+    match Settings::from_file(datadir_path.to_path_buf(), network) {
+        Ok(_) | Err(SettingsError::NotFound) => {}
+        Err(e) => problems.push(DatadirProblem::CorruptedSettings(e.to_string())),
+    }
+
+    // The mnemonics folder is optional as well, but every mnemonic it contains must be valid.
+    match HotSigner::from_datadir(datadir_path, network) {
+        Ok(_) => {}
+        Err(SignerError::MnemonicStorage(e)) if e.kind() == ErrorKind::NotFound => {}
+        // Encrypted mnemonics are checked once decrypted, when loading the wallet.
+        Err(SignerError::EncryptedMnemonic) => {}
+        Err(e) => problems.push(DatadirProblem::CorruptedMnemonics(e.to_string())),
+    }
+
+    if let Some(path) = &gui_config.daemon_config_path {
+        match Config::from_file(Some(path.clone())) {
+            Ok(cfg) => {
+                if cfg.main_descriptor.to_string() != descriptor.to_string() {
+                    problems.push(DatadirProblem::DescriptorMismatch);
+                }
+            }
+            Err(e) => problems.push(DatadirProblem::CorruptedDaemonConfig(e.to_string())),
+        }
+    }
+
+    problems
+}
+
 #[derive(Clone, Debug)]
 pub enum ViewMessage {
     Retry,
     SwitchNetwork,
+    MnemonicPasswordEdited(String),
+    Unlock,
 }
 
 pub fn view(step: &Step) -> Element<ViewMessage> {
@@ -268,7 +428,35 @@ pub fn view(step: &Step) -> Element<ViewMessage> {
             Column::new()
                 .width(Length::Fill)
                 .push(ProgressBar::new(0.0..=1.0, *progress as f32).width(Length::Fill))
-                .push(text("Syncing the wallet with the blockchain...")),
+                .push(text(format!(
+                    "Waiting for the Bitcoin node to finish syncing with the network ({:.2}%)...",
+                    progress * 100.0
+                ))),
+        ),
+        Step::Unlock {
+            password, error, ..
+        } => cover(
+            None,
+            Column::new()
+                .spacing(20)
+                .width(Length::Fill)
+                .align_items(Alignment::Center)
+                .push(text("The mnemonic of the hot signer is encrypted").bold())
+                .push(
+                    Container::new(
+                        TextInput::new("Password", password, ViewMessage::MnemonicPasswordEdited)
+                            .password()
+                            .on_submit(ViewMessage::Unlock)
+                            .padding(10),
+                    )
+                    .width(Length::Units(300)),
+                )
+                .push_maybe(error.as_ref().map(|e| text(e).small().style(color::ALERT)))
+                .push(
+                    button::primary(None, "Unlock")
+                        .width(Length::Units(200))
+                        .on_press(ViewMessage::Unlock),
+                ),
         ),
         Step::Error(error) => cover(
             Some(("Error while starting the internal daemon", error)),
@@ -283,6 +471,13 @@ pub fn view(step: &Step) -> Element<ViewMessage> {
                         Error::Daemon(DaemonError::Start(StartupError::Bitcoind(_)))
                     ) {
                         text("Liana failed to start, please check if bitcoind is running")
+                    } else if matches!(
+                        error.as_ref(),
+                        Error::Daemon(DaemonError::Start(StartupError::Electrum(_)))
+                    ) {
+                        text("Liana failed to start, please check the Electrum server is reachable")
+                    } else if matches!(error.as_ref(), Error::Datadir(_)) {
+                        text("Liana failed to load the wallet, please check its data directory")
                     } else {
                         text("Liana failed to start")
                     },
@@ -291,7 +486,7 @@ pub fn view(step: &Step) -> Element<ViewMessage> {
                     Row::new()
                         .spacing(10)
                         .push(
-                            button::border(None, "Use another Bitcoin network")
+                            button::border(None, "Use another wallet")
                                 .on_press(ViewMessage::SwitchNetwork),
                         )
                         .push(
@@ -360,6 +555,7 @@ pub enum Error {
     Wallet(WalletError),
     Config(ConfigError),
     Daemon(DaemonError),
+    Datadir(Vec<DatadirProblem>),
 }
 
 impl std::fmt::Display for Error {
@@ -368,6 +564,13 @@ impl std::fmt::Display for Error {
             Self::Config(e) => write!(f, "Config error: {}", e),
             Self::Wallet(e) => write!(f, "Wallet error: {}", e),
             Self::Daemon(e) => write!(f, "Liana daemon error: {}", e),
+            Self::Datadir(problems) => {
+                write!(f, "Wallet data directory error:")?;
+                for problem in problems {
+                    write!(f, "\n- {}", problem)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -397,3 +600,81 @@ fn socket_path(datadir: &Path, network: bitcoin::Network) -> PathBuf {
     path.push("lianad_rpc");
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const DESC: &str = "wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y";
+
+    fn tmp_datadir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("liana-gui-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(path.join(bitcoin::Network::Testnet.to_string())).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_check_datadir() {
+        let network = bitcoin::Network::Testnet;
+        let descriptor = MultipathDescriptor::from_str(DESC).unwrap();
+        let mut gui_config = GUIConfig::new(PathBuf::new());
+        gui_config.daemon_config_path = None;
+
+        // An empty network directory is fine, the settings and mnemonics are optional.
+        let datadir = tmp_datadir("empty");
+        assert!(check_datadir(&datadir, network, &gui_config, &descriptor).is_empty());
+
+        // A missing network directory is reported.
+        assert_eq!(
+            check_datadir(&datadir, bitcoin::Network::Signet, &gui_config, &descriptor),
+            vec![DatadirProblem::MissingNetworkDirectory(
+                datadir.join(bitcoin::Network::Signet.to_string())
+            )]
+        );
+        std::fs::remove_dir_all(&datadir).unwrap();
+
+        // Corrupted settings and mnemonics are all reported at once.
+        let datadir = tmp_datadir("corrupted");
+        let network_dir = datadir.join(network.to_string());
+        std::fs::write(network_dir.join("settings.json"), "{\"wallets\": [").unwrap();
+        std::fs::create_dir(network_dir.join("mnemonics")).unwrap();
+        std::fs::write(
+            network_dir.join("mnemonics").join("mnemonic-abcdef01.txt"),
+            "not a mnemonic",
+        )
+        .unwrap();
+        let problems = check_datadir(&datadir, network, &gui_config, &descriptor);
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[0], DatadirProblem::CorruptedSettings(_)));
+        assert!(matches!(problems[1], DatadirProblem::CorruptedMnemonics(_)));
+
+        // A valid settings file is accepted.
+        std::fs::write(network_dir.join("settings.json"), "{\"wallets\": []}").unwrap();
+        std::fs::remove_dir_all(network_dir.join("mnemonics")).unwrap();
+        assert!(check_datadir(&datadir, network, &gui_config, &descriptor).is_empty());
+
+        // Encrypted mnemonics are only checked once decrypted, when loading the wallet.
+        HotSigner::generate(network)
+            .unwrap()
+            .store_encrypted(
+                &datadir,
+                network,
+                &bitcoin::secp256k1::Secp256k1::signing_only(),
+                "password",
+            )
+            .unwrap();
+        assert!(check_datadir(&datadir, network, &gui_config, &descriptor).is_empty());
+
+        // A missing daemon configuration file is reported.
+        gui_config.daemon_config_path = Some(network_dir.join("daemon.toml"));
+        let problems = check_datadir(&datadir, network, &gui_config, &descriptor);
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            problems[0],
+            DatadirProblem::CorruptedDaemonConfig(_)
+        ));
+        std::fs::remove_dir_all(&datadir).unwrap();
+    }
+}
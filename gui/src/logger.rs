@@ -1,6 +1,10 @@
 use liana::miniscript::bitcoin::Network;
 use std::path::PathBuf;
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
 use tracing::error;
 use tracing_subscriber::{
     filter,
@@ -12,6 +16,139 @@ use tracing_subscriber::{
 const INSTALLER_LOG_FILE_NAME: &str = "installer.log";
 const GUI_LOG_FILE_NAME: &str = "liana-gui.log";
 
+/// Environment variable overriding the logging directives of the configuration, for instance
+/// `LIANA_GUI_LOG=info,liana::bitcoin::poller=trace`.
+pub const LOG_FILTER_ENV_VAR: &str = "LIANA_GUI_LOG";
+
+/// Parse comma-separated logging directives: a `target=level` directive sets the level of the
+/// events of a module and its submodules, a bare `level` sets the level of the other events. The
+/// given level is used for the other events if the directives do not set one.
+pub fn parse_log_filter(
+    directives: &str,
+    default_level: filter::LevelFilter,
+) -> Result<filter::Targets, filter::ParseError> {
+    let targets: filter::Targets = directives.parse()?;
+    if targets.default_level().is_some() {
+        Ok(targets)
+    } else {
+        Ok(targets.with_default(default_level))
+    }
+}
+
+/// The logging directives set in the environment, if any. They override the configuration.
+fn env_log_filter(default_level: filter::LevelFilter) -> Option<filter::Targets> {
+    let directives = std::env::var(LOG_FILTER_ENV_VAR).ok()?;
+    match parse_log_filter(&directives, default_level) {
+        Ok(targets) => Some(targets),
+        Err(e) => {
+            eprintln!("Ignoring invalid {}: {}", LOG_FILTER_ENV_VAR, e);
+            None
+        }
+    }
+}
+
+/// When the log file is rotated and how many rotated files are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRotation {
+    /// Size in bytes past which the log file is rotated.
+    pub max_size: u64,
+    /// Number of rotated files kept along with the current one.
+    pub max_files: usize,
+}
+
+impl Default for LogRotation {
+    /// Rotate at 10MB, keep 5 rotated files.
+    fn default() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// A log file rotated once it reaches the maximum size: it is renamed with a `.1` suffix, the
+/// files rotated before are shifted to the next suffix and the oldest beyond the number to keep
+/// is removed. A record is written at once under a lock, so the records of concurrent threads
+/// are neither interleaved nor lost during a rotation.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    state: Mutex<RotatingFileState>,
+}
+
+struct RotatingFileState {
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Open the log file, appending to the records of the previous runs.
+    pub fn open(path: PathBuf, rotation: LogRotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            rotation,
+            state: Mutex::new(RotatingFileState { file, size }),
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{}", index));
+        self.path.with_file_name(file_name)
+    }
+
+    fn rotate(&self, state: &mut RotatingFileState) -> io::Result<()> {
+        if self.rotation.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(self.rotated_path(self.rotation.max_files)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            for index in (1..self.rotation.max_files).rev() {
+                let path = self.rotated_path(index);
+                if path.exists() {
+                    fs::rename(path, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        state.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        state.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        // A record is never split across two files.
+        if state.size > 0 && state.size + buf.len() as u64 > self.rotation.max_size {
+            // Keep writing to the current file rather than losing records. It can't be logged
+            // from within the logger.
+            if let Err(e) = self.rotate(&mut state) {
+                eprintln!("Failed to rotate the log file: {}", e);
+            }
+        }
+        state.file.write_all(buf)?;
+        state.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .file
+            .flush()
+    }
+}
+
 #[derive(Debug)]
 pub enum LoggerError {
     Io(std::io::Error),
@@ -35,12 +172,14 @@ pub struct Logger {
         Layer<Registry, format::DefaultFields, format::Format, BoxMakeWriter>,
         Registry,
     >,
-    level_handle: reload::Handle<filter::LevelFilter, Registry>,
+    level_handle: reload::Handle<filter::Targets, Registry>,
 }
 
 impl Logger {
     pub fn setup(log_level: filter::LevelFilter) -> Logger {
-        let (log_level, level_handle) = reload::Layer::new(log_level);
+        let log_filter = env_log_filter(log_level)
+            .unwrap_or_else(|| filter::Targets::new().with_default(log_level));
+        let (log_filter, level_handle) = reload::Layer::new(log_filter);
         let writer = BoxMakeWriter::new(std::io::stderr);
         let file_log = tracing_subscriber::fmt::layer()
             .with_writer(writer)
@@ -51,7 +190,7 @@ impl Logger {
             .with(
                 stdout_log
                     .and_then(file_log)
-                    .with_filter(log_level)
+                    .with_filter(log_filter)
                     // Add a filter to *both* layers that rejects spans and
                     // events whose targets start with `<prefix>`.
                     .with_filter(filter::filter_fn(|metadata| {
@@ -77,7 +216,9 @@ impl Logger {
 
     pub fn set_installer_mode(&self, mut datadir: PathBuf, log_level: filter::LevelFilter) {
         datadir.push(INSTALLER_LOG_FILE_NAME);
-        if let Err(e) = self.set_layer(datadir, log_level) {
+        let log_filter = env_log_filter(log_level)
+            .unwrap_or_else(|| filter::Targets::new().with_default(log_level));
+        if let Err(e) = self.set_layer(datadir, log_filter, LogRotation::default()) {
             error!("Failed to change logger settings: {:#?}", e);
         }
     }
@@ -86,11 +227,23 @@ impl Logger {
         &self,
         mut datadir: PathBuf,
         network: Network,
-        log_level: filter::LevelFilter,
+        log_filter: filter::Targets,
+        rotation: LogRotation,
     ) {
         datadir.push(network.to_string());
         datadir.push(GUI_LOG_FILE_NAME);
-        if let Err(e) = self.set_layer(datadir, log_level) {
+        let default_level = log_filter
+            .default_level()
+            .unwrap_or(filter::LevelFilter::INFO);
+        let log_filter = env_log_filter(default_level).unwrap_or(log_filter);
+        if let Err(e) = self.set_layer(datadir, log_filter, rotation) {
+            error!("Failed to change logger settings: {:#?}", e);
+        }
+    }
+
+    /// Change the levels of the logged events, without restarting.
+    pub fn set_log_filter(&self, log_filter: filter::Targets) {
+        if let Err(e) = self.level_handle.modify(|filter| *filter = log_filter) {
             error!("Failed to change logger settings: {:#?}", e);
         }
     }
@@ -102,15 +255,147 @@ impl Logger {
         }
     }
 
+    /// Stop writing the logs to a file, for instance before deleting the directory it is in.
+    pub fn set_launcher_mode(&self) {
+        if let Err(e) = self
+            .file_handle
+            .modify(|layer| *layer.writer_mut() = BoxMakeWriter::new(std::io::stderr))
+        {
+            error!("Failed to change logger settings: {:#?}", e);
+        }
+    }
+
     pub fn set_layer(
         &self,
         destination_path: PathBuf,
-        log_level: filter::LevelFilter,
+        log_filter: filter::Targets,
+        rotation: LogRotation,
     ) -> Result<(), LoggerError> {
-        let file = File::create(destination_path)?;
+        let file = RotatingFile::open(destination_path, rotation)?;
         self.file_handle
             .modify(|layer| *layer.writer_mut() = BoxMakeWriter::new(Arc::new(file)))?;
-        self.level_handle.modify(|filter| *filter = log_level)?;
+        self.level_handle.modify(|filter| *filter = log_filter)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("liana-gui-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_log_filter() {
+        use filter::LevelFilter;
+        use tracing::Level;
+
+        let targets = parse_log_filter("liana::bitcoin::poller=trace", LevelFilter::INFO).unwrap();
+        assert!(targets.would_enable("liana::bitcoin::poller", &Level::TRACE));
+        assert!(targets.would_enable("liana::bitcoin::poller::looper", &Level::TRACE));
+        assert!(!targets.would_enable("liana::bitcoin", &Level::DEBUG));
+        assert!(targets.would_enable("liana_gui::app", &Level::INFO));
+        assert!(!targets.would_enable("liana_gui::app", &Level::DEBUG));
+
+        // The directives set the level of the other events.
+        let targets = parse_log_filter("warn,liana_gui=debug", LevelFilter::INFO).unwrap();
+        assert!(targets.would_enable("liana_gui::app", &Level::DEBUG));
+        assert!(!targets.would_enable("liana::bitcoin", &Level::INFO));
+        assert!(targets.would_enable("liana::bitcoin", &Level::WARN));
+
+        assert!(parse_log_filter("liana=loud", LevelFilter::INFO).is_err());
+    }
+
+    #[test]
+    fn test_log_rotation() {
+        let dir = test_dir("log-rotation");
+        let path = dir.join(GUI_LOG_FILE_NAME);
+        let rotation = LogRotation {
+            max_size: 100,
+            max_files: 2,
+        };
+        let file = RotatingFile::open(path.clone(), rotation).unwrap();
+        let record = |i: usize| format!("{:059}\n", i);
+
+        // Writing past the size threshold starts a new file.
+        (&file).write_all(record(1).as_bytes()).unwrap();
+        (&file).write_all(record(2).as_bytes()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), record(2));
+        assert_eq!(
+            fs::read_to_string(dir.join("liana-gui.log.1")).unwrap(),
+            record(1)
+        );
+
+        // Only the most recent rotated files are kept.
+        (&file).write_all(record(3).as_bytes()).unwrap();
+        (&file).write_all(record(4).as_bytes()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), record(4));
+        assert_eq!(
+            fs::read_to_string(dir.join("liana-gui.log.1")).unwrap(),
+            record(3)
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("liana-gui.log.2")).unwrap(),
+            record(2)
+        );
+        assert!(!dir.join("liana-gui.log.3").exists());
+
+        // Reopening appends to the current file.
+        drop(file);
+        let file = RotatingFile::open(path.clone(), rotation).unwrap();
+        (&file).write_all(b"5\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}5\n", record(4))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_rotation_concurrent_writes() {
+        let dir = test_dir("log-rotation-concurrent");
+        let path = dir.join(GUI_LOG_FILE_NAME);
+        let rotation = LogRotation {
+            max_size: 1_000,
+            max_files: 100,
+        };
+        let file = Arc::new(RotatingFile::open(path, rotation).unwrap());
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        (&*file)
+                            .write_all(format!("thread {} record {:040}\n", t, i).as_bytes())
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Every record is found whole in one of the files.
+        let mut lines = Vec::new();
+        for entry in fs::read_dir(&dir).unwrap() {
+            let content = fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(content.len() <= 1_000);
+            lines.extend(content.lines().map(|l| l.to_string()));
+        }
+        assert_eq!(lines.len(), 400);
+        for line in lines {
+            assert_eq!(line.len(), 56);
+            assert!(line.starts_with("thread "));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
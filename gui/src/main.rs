@@ -2,9 +2,9 @@
 
 use std::{error::Error, io::Write, path::PathBuf, str::FromStr};
 
-use iced::{executor, Application, Command, Element, Settings, Subscription};
+use iced::{executor, widget::Column, Application, Command, Element, Settings, Subscription};
 use tracing::{error, info};
-use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::filter::{LevelFilter, Targets};
 extern crate serde;
 extern crate serde_json;
 
@@ -19,7 +19,8 @@ use liana_gui::{
     installer::{self, Installer},
     launcher::{self, Launcher},
     loader::{self, Loader},
-    logger::Logger,
+    logger::{parse_log_filter, Logger},
+    ui::component::network::network_banner,
 };
 
 #[derive(Debug, PartialEq)]
@@ -56,6 +57,8 @@ fn parse_args(args: Vec<String>) -> Result<Vec<Arg>, Box<dyn Error>> {
 pub struct GUI {
     state: State,
     logger: Logger,
+    /// Whether the user dismissed the mainnet banner. The ones of the test networks stay.
+    network_banner_dismissed: bool,
 }
 
 enum State {
@@ -73,6 +76,7 @@ pub enum Message {
     Load(Box<loader::Message>),
     Run(Box<app::Message>),
     Event(iced_native::Event),
+    DismissNetworkBanner,
 }
 
 async fn ctrl_c() -> Result<(), ()> {
@@ -105,6 +109,7 @@ impl Application for GUI {
                     Self {
                         state: State::Launcher(Box::new(launcher)),
                         logger,
+                        network_banner_dismissed: false,
                     },
                     Command::perform(ctrl_c(), |_| Message::CtrlC),
                 )
@@ -116,6 +121,7 @@ impl Application for GUI {
                     Self {
                         state: State::Installer(Box::new(install)),
                         logger,
+                        network_banner_dismissed: false,
                     },
                     Command::batch(vec![
                         command.map(|msg| Message::Install(Box::new(msg))),
@@ -127,13 +133,16 @@ impl Application for GUI {
                 logger.set_running_mode(
                     datadir_path.clone(),
                     network,
-                    cfg.log_level().unwrap_or(LevelFilter::INFO),
+                    cfg.log_filter()
+                        .unwrap_or_else(|_| Targets::new().with_default(LevelFilter::INFO)),
+                    cfg.log_rotation(),
                 );
                 let (loader, command) = Loader::new(datadir_path, cfg, network);
                 (
                     Self {
                         state: State::Loader(Box::new(loader)),
                         logger,
+                        network_banner_dismissed: false,
                     },
                     Command::batch(vec![
                         command.map(|msg| Message::Load(Box::new(msg))),
@@ -161,6 +170,10 @@ impl Application for GUI {
                 };
                 iced::window::close()
             }
+            (_, Message::DismissNetworkBanner) => {
+                self.network_banner_dismissed = true;
+                Command::none()
+            }
             (State::Launcher(l), Message::Launch(msg)) => match *msg {
                 launcher::Message::Install(datadir_path) => {
                     self.logger
@@ -174,7 +187,9 @@ impl Application for GUI {
                     self.logger.set_running_mode(
                         datadir_path.clone(),
                         network,
-                        cfg.log_level().unwrap_or(LevelFilter::INFO),
+                        cfg.log_filter()
+                            .unwrap_or_else(|_| Targets::new().with_default(LevelFilter::INFO)),
+                        cfg.log_rotation(),
                     );
                     let (loader, command) = Loader::new(datadir_path, cfg, network);
                     self.state = State::Loader(Box::new(loader));
@@ -183,7 +198,7 @@ impl Application for GUI {
                 _ => l.update(*msg).map(|msg| Message::Launch(Box::new(msg))),
             },
             (State::Installer(i), Message::Install(msg)) => {
-                if let installer::Message::Exit(path) = *msg {
+                if let installer::Message::Exit(path, rescan_from_height) = *msg {
                     let cfg = app::Config::from_file(&path).unwrap();
                     let daemon_cfg =
                         DaemonConfig::from_file(cfg.daemon_config_path.clone()).unwrap();
@@ -196,36 +211,105 @@ impl Application for GUI {
                     self.logger.set_running_mode(
                         datadir_path.clone(),
                         daemon_cfg.bitcoin_config.network,
-                        cfg.log_level().unwrap_or(LevelFilter::INFO),
+                        cfg.log_filter()
+                            .unwrap_or_else(|_| Targets::new().with_default(LevelFilter::INFO)),
+                        cfg.log_rotation(),
                     );
                     self.logger.remove_install_log_file(datadir_path.clone());
-                    let (loader, command) =
+                    let (mut loader, command) =
                         Loader::new(datadir_path, cfg, daemon_cfg.bitcoin_config.network);
+                    loader.rescan_from_height = rescan_from_height;
                     self.state = State::Loader(Box::new(loader));
                     command.map(|msg| Message::Load(Box::new(msg)))
+                } else if let installer::Message::Abort = *msg {
+                    let datadir_path = i.datadir().clone();
+                    i.abort();
+                    self.logger.set_launcher_mode();
+                    self.logger.remove_install_log_file(datadir_path.clone());
+                    self.state = State::Launcher(Box::new(Launcher::new(datadir_path)));
+                    Command::none()
                 } else {
                     i.update(*msg).map(|msg| Message::Install(Box::new(msg)))
                 }
             }
             (State::Loader(loader), Message::Load(msg)) => match *msg {
                 loader::Message::View(loader::ViewMessage::SwitchNetwork) => {
+                    loader.stop();
+                    self.logger.set_launcher_mode();
                     self.state =
                         State::Launcher(Box::new(Launcher::new(loader.datadir_path.clone())));
                     Command::none()
                 }
                 loader::Message::Synced(Ok((wallet, cache, daemon))) => {
-                    let (app, command) = App::new(
+                    let (app, mut command) = App::new(
                         cache,
                         wallet,
                         loader.gui_config.clone(),
-                        daemon,
+                        daemon.clone(),
                         loader.datadir_path.clone(),
+                        loader.mnemonic_password.take(),
                     );
+                    if let Some(height) = loader.rescan_from_height.take() {
+                        info!("Starting the rescan from block height {}", height);
+                        command = Command::batch(vec![
+                            command,
+                            Command::perform(
+                                async move {
+                                    daemon
+                                        .start_rescan_from_height(height)
+                                        .map_err(|e| e.into())
+                                },
+                                app::Message::StartRescan,
+                            ),
+                        ]);
+                    }
                     self.state = State::App(app);
                     command.map(|msg| Message::Run(Box::new(msg)))
                 }
                 _ => loader.update(*msg).map(|msg| Message::Load(Box::new(msg))),
             },
+            (State::App(a), Message::Run(msg)) if matches!(*msg, app::Message::DeleteWallet) => {
+                let datadir_path = a.datadir_path().clone();
+                // The log file is within the wallet data directory.
+                self.logger.set_launcher_mode();
+                let mut launcher = Launcher::new(datadir_path.clone());
+                if let Err(e) = a.delete_wallet() {
+                    error!("Failed to delete wallet: {}", e);
+                    launcher = launcher.with_error(format!("Failed to delete wallet: {}", e));
+                }
+                self.state = State::Launcher(Box::new(launcher));
+                Command::none()
+            }
+            (State::App(a), Message::Run(msg)) if matches!(*msg, app::Message::SwitchWallet) => {
+                match a.stop_for_switch() {
+                    Ok(()) => {
+                        self.logger.set_launcher_mode();
+                        self.state =
+                            State::Launcher(Box::new(Launcher::new(a.datadir_path().clone())));
+                        Command::none()
+                    }
+                    // Let the commands in flight complete before trying again.
+                    Err(e) => {
+                        info!("Waiting before switching wallet: {}", e);
+                        Command::perform(
+                            async { std::thread::sleep(std::time::Duration::from_millis(500)) },
+                            |_| Message::Run(Box::new(app::Message::SwitchWallet)),
+                        )
+                    }
+                }
+            }
+            (State::App(a), Message::Run(msg)) if matches!(*msg, app::Message::SetLogFilter(_)) => {
+                if let app::Message::SetLogFilter(directives) = msg.as_ref() {
+                    match parse_log_filter(directives, LevelFilter::INFO) {
+                        Ok(log_filter) => {
+                            info!("Applying logging directives: {}", directives);
+                            self.logger.set_log_filter(log_filter);
+                        }
+                        Err(e) => error!("Invalid logging directives '{}': {}", directives, e),
+                    }
+                }
+                a.update(*msg).map(|msg| Message::Run(Box::new(msg)))
+            }
             (State::App(i), Message::Run(msg)) => {
                 i.update(*msg).map(|msg| Message::Run(Box::new(msg)))
             }
@@ -246,11 +330,36 @@ impl Application for GUI {
     }
 
     fn view(&self) -> Element<Self::Message> {
-        match &self.state {
-            State::Installer(v) => v.view().map(|msg| Message::Install(Box::new(msg))),
-            State::App(v) => v.view().map(|msg| Message::Run(Box::new(msg))),
-            State::Launcher(v) => v.view().map(|msg| Message::Launch(Box::new(msg))),
-            State::Loader(v) => v.view().map(|msg| Message::Load(Box::new(msg))),
+        let (network, content) = match &self.state {
+            State::Installer(v) => (
+                Some(v.network()),
+                v.view().map(|msg| Message::Install(Box::new(msg))),
+            ),
+            State::App(v) => (
+                Some(v.network()),
+                v.view().map(|msg| Message::Run(Box::new(msg))),
+            ),
+            // The launcher colors each network it lists instead.
+            State::Launcher(v) => (None, v.view().map(|msg| Message::Launch(Box::new(msg)))),
+            State::Loader(v) => (
+                Some(v.network),
+                v.view().map(|msg| Message::Load(Box::new(msg))),
+            ),
+        };
+        match network {
+            Some(network)
+                if !(network == bitcoin::Network::Bitcoin && self.network_banner_dismissed) =>
+            {
+                Column::new()
+                    // The top-level message is not Clone, as a button message must be.
+                    .push(
+                        Element::from(network_banner(network, ()))
+                            .map(|()| Message::DismissNetworkBanner),
+                    )
+                    .push(content)
+                    .into()
+            }
+            _ => content,
         }
     }
 
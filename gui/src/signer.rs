@@ -1,6 +1,7 @@
 pub use liana::signer::SignerError;
 
 use liana::{
+    bip39,
     miniscript::bitcoin::{
         secp256k1,
         util::{
@@ -39,14 +40,41 @@ impl Signer {
         self.key.set_network(network)
     }
 
-    pub fn mnemonic(&self) -> [&'static str; 12] {
+    pub fn mnemonic(&self) -> Vec<&'static str> {
         self.key.words()
     }
 
+    /// This signer with the same mnemonic protected by the given BIP39 passphrase.
+    pub fn with_passphrase(&self, passphrase: &str) -> Result<Self, SignerError> {
+        Ok(Self::new(self.key.with_passphrase(passphrase)?))
+    }
+
+    pub fn has_passphrase(&self) -> bool {
+        self.key.has_passphrase()
+    }
+
+    pub fn mnemonic_word_count(&self) -> usize {
+        self.key.words().len()
+    }
+
     pub fn generate(network: Network) -> Result<Self, SignerError> {
         Ok(Self::new(HotSigner::generate(network)?))
     }
 
+    pub fn generate_in(
+        network: Network,
+        language: bip39::Language,
+        word_count: usize,
+    ) -> Result<Self, SignerError> {
+        Ok(Self::new(HotSigner::generate_in(
+            network, language, word_count,
+        )?))
+    }
+
+    pub fn language(&self) -> bip39::Language {
+        self.key.language()
+    }
+
     pub fn fingerprint(&self) -> Fingerprint {
         self.fingerprint
     }
@@ -66,4 +94,14 @@ impl Signer {
     ) -> Result<(), SignerError> {
         self.key.store(datadir_root, network, &self.curve)
     }
+
+    pub fn store_encrypted(
+        &self,
+        datadir_root: &std::path::Path,
+        network: Network,
+        password: &str,
+    ) -> Result<(), SignerError> {
+        self.key
+            .store_encrypted(datadir_root, network, &self.curve, password)
+    }
 }
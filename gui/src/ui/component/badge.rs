@@ -122,6 +122,7 @@ pub enum PillStyle {
     InversePrimary,
     Primary,
     Success,
+    Warning,
     Simple,
 }
 
@@ -147,6 +148,12 @@ impl widget::container::StyleSheet for PillStyle {
                 text_color: iced::Color::WHITE.into(),
                 ..widget::container::Appearance::default()
             },
+            Self::Warning => widget::container::Appearance {
+                background: color::WARNING.into(),
+                border_radius: 10.0,
+                text_color: iced::Color::WHITE.into(),
+                ..widget::container::Appearance::default()
+            },
             Self::Simple => widget::container::Appearance {
                 background: color::BACKGROUND.into(),
                 border_radius: 10.0,
@@ -182,6 +189,35 @@ pub fn unconfirmed<'a, T: 'a>() -> widget::container::Container<'a, T> {
     )
 }
 
+/// Confirmed, but not yet final for its amount.
+pub fn confirmations<'a, T: 'a>(confirmations: u32, required: u32) -> Container<'a, T> {
+    Container::new(
+        tooltip::Tooltip::new(
+            Container::new(
+                text(format!("  {}/{} confirmations  ", confirmations, required)).small(),
+            )
+            .padding(3)
+            .style(PillStyle::Simple),
+            "Not final yet for this amount",
+            tooltip::Position::Top,
+        )
+        .style(card::SimpleCardStyle),
+    )
+}
+
+pub fn replaceable<'a, T: 'a>() -> widget::container::Container<'a, T> {
+    Container::new(
+        tooltip::Tooltip::new(
+            Container::new(text("  RBF - wait for confirmation  ").small())
+                .padding(3)
+                .style(PillStyle::Warning),
+            "The sender may still replace or cancel this payment until it is confirmed",
+            tooltip::Position::Top,
+        )
+        .style(card::SimpleCardStyle),
+    )
+}
+
 pub fn deprecated<'a, T: 'a>() -> widget::container::Container<'a, T> {
     Container::new(
         tooltip::Tooltip::new(
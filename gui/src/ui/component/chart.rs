@@ -0,0 +1,75 @@
+use iced::{
+    widget::canvas::{self, Canvas, Cursor, Frame, Geometry, Path, Stroke},
+    Element, Length, Point, Rectangle, Theme,
+};
+
+use crate::ui::color;
+
+/// A minimal line chart of the given (x, y) points, scaled to fill its bounds.
+pub struct LineChart {
+    points: Vec<(f32, f32)>,
+}
+
+impl LineChart {
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        Self { points }
+    }
+
+    pub fn view<'a, Message: 'a>(self, height: u16) -> Element<'a, Message> {
+        Canvas::new(self)
+            .width(Length::Fill)
+            .height(Length::Units(height))
+            .into()
+    }
+}
+
+impl<Message> canvas::Program<Message> for LineChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+        if self.points.len() < 2 {
+            return vec![frame.into_geometry()];
+        }
+
+        let (min_x, max_x) = self
+            .points
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), (x, _)| {
+                (min.min(*x), max.max(*x))
+            });
+        let max_y = self.points.iter().fold(0.0_f32, |max, (_, y)| max.max(*y));
+        let width_x = if max_x > min_x { max_x - min_x } else { 1.0 };
+        let height_y = if max_y > 0.0 { max_y } else { 1.0 };
+        let (frame_width, frame_height) = (frame.width(), frame.height());
+        let to_point = |(x, y): &(f32, f32)| {
+            Point::new(
+                (x - min_x) / width_x * frame_width,
+                frame_height - y / height_y * frame_height,
+            )
+        };
+
+        // The balance only changes at the given points, hence a step line.
+        let path = Path::new(|builder| {
+            let mut previous = to_point(&self.points[0]);
+            builder.move_to(previous);
+            for point in self.points.iter().skip(1).map(to_point) {
+                builder.line_to(Point::new(point.x, previous.y));
+                builder.line_to(point);
+                previous = point;
+            }
+        });
+        frame.stroke(
+            &path,
+            Stroke::default().with_width(2.0).with_color(color::PRIMARY),
+        );
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -1,10 +1,12 @@
 pub mod badge;
 pub mod button;
 pub mod card;
+pub mod chart;
 pub mod collapse;
 pub mod container;
 pub mod form;
 pub mod modal;
+pub mod network;
 pub mod notification;
 pub mod text;
 pub mod tooltip;
@@ -0,0 +1,92 @@
+use iced::{
+    widget::{container, Button, Container, Row},
+    Alignment, Color, Length,
+};
+
+use liana::miniscript::bitcoin::Network;
+
+use crate::ui::{
+    color,
+    component::{button, text::*},
+    icon,
+    util::Collection,
+};
+
+/// The color identifying a network, the same wherever the network is displayed.
+pub fn network_color(network: Network) -> Color {
+    match network {
+        Network::Bitcoin => color::SUCCESS,
+        Network::Testnet => color::WARNING,
+        Network::Signet => color::INFO,
+        Network::Regtest => color::ALERT,
+    }
+}
+
+pub fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "Bitcoin Mainnet",
+        Network::Testnet => "Bitcoin Testnet",
+        Network::Signet => "Bitcoin Signet",
+        Network::Regtest => "Bitcoin Regtest",
+    }
+}
+
+/// Banner displayed on top of every screen, stating the network the application operates on.
+/// Only the mainnet one can be dismissed, with the given message: on the test networks it must
+/// stay to tell them apart from mainnet at a glance.
+pub fn network_banner<'a, T: Clone + 'a>(network: Network, dismiss: T) -> Container<'a, T> {
+    Container::new(
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(
+                Container::new(
+                    text(if network == Network::Bitcoin {
+                        network_name(network).to_string()
+                    } else {
+                        format!("{}: the coins have no value", network_name(network))
+                    })
+                    .small()
+                    .bold()
+                    .style(color::FOREGROUND),
+                )
+                .width(Length::Fill)
+                .center_x(),
+            )
+            .push_maybe(if network == Network::Bitcoin {
+                Some(
+                    Button::new(icon::cross_icon().style(color::FOREGROUND))
+                        .on_press(dismiss)
+                        .style(button::Style::Transparent.into()),
+                )
+            } else {
+                None
+            }),
+    )
+    .padding(5)
+    .width(Length::Fill)
+    .style(BannerStyle(network))
+}
+
+pub struct BannerStyle(pub Network);
+impl container::StyleSheet for BannerStyle {
+    type Style = iced::Theme;
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            background: network_color(self.0).into(),
+            ..container::Appearance::default()
+        }
+    }
+}
+
+impl From<BannerStyle> for Box<dyn container::StyleSheet<Style = iced::Theme>> {
+    fn from(s: BannerStyle) -> Box<dyn container::StyleSheet<Style = iced::Theme>> {
+        Box::new(s)
+    }
+}
+
+impl From<BannerStyle> for iced::theme::Container {
+    fn from(i: BannerStyle) -> iced::theme::Container {
+        iced::theme::Container::Custom(i.into())
+    }
+}
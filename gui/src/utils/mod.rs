@@ -1,5 +1,41 @@
+use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[cfg(test)]
 pub mod sandbox;
 
 #[cfg(test)]
 pub mod mock;
+
+/// Number of seconds between the UNIX epoch and the given time, as stored in the settings.
+/// Times before the epoch give 0 and times after 2106 give u32::MAX.
+pub fn unix_timestamp(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs().try_into().unwrap_or(u32::MAX))
+        .unwrap_or(0)
+}
+
+/// The current time as a UNIX timestamp.
+pub fn now() -> u32 {
+    unix_timestamp(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_unix_timestamp() {
+        assert_eq!(unix_timestamp(UNIX_EPOCH), 0);
+        assert_eq!(
+            unix_timestamp(UNIX_EPOCH + Duration::from_secs(1_683_730_000)),
+            1_683_730_000
+        );
+        assert_eq!(unix_timestamp(UNIX_EPOCH - Duration::from_secs(1)), 0);
+        assert_eq!(
+            unix_timestamp(UNIX_EPOCH + Duration::from_secs(u64::from(u32::MAX) + 1)),
+            u32::MAX
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! A transport for the JSONRPC client which connects to bitcoind through a SOCKS5 proxy.
+//!
+//! The version of the jsonrpc crate we use can't go through a proxy. This is the same minimal HTTP
+//! client as its `SimpleHttpTransport`, only the connection is first established through the
+//! proxy. We only support proxies which don't require authentication, such as Tor's.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use jsonrpc::{client::Transport, simple_http::Error, Request, Response};
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+// Errors with the proxy are reported as socket errors, so they are treated as such by the caller
+// (i.e. as transient).
+fn proxy_error(msg: String) -> Error {
+    Error::SocketError(io::Error::new(
+        io::ErrorKind::Other,
+        format!("SOCKS5 proxy: {}", msg),
+    ))
+}
+
+// Read a line from the response, failing if it's not complete before the deadline.
+fn read_line(reader: &mut impl BufRead, deadline: Instant) -> Result<String, Error> {
+    let mut line = String::new();
+    while Instant::now() < deadline {
+        match reader.read_line(&mut line) {
+            Ok(0) => thread::sleep(Duration::from_millis(5)),
+            Ok(_) => return Ok(line),
+            Err(e) => return Err(Error::SocketError(e)),
+        }
+    }
+    Err(Error::Timeout)
+}
+
+/// Send JSONRPC requests over HTTP to the given address, through a SOCKS5 proxy.
+pub struct SocksHttpTransport {
+    proxy_addr: SocketAddr,
+    addr: SocketAddr,
+    path: String,
+    timeout: Duration,
+    basic_auth: String,
+}
+
+impl SocksHttpTransport {
+    pub fn new(
+        proxy_addr: SocketAddr,
+        addr: SocketAddr,
+        path: String,
+        timeout: Duration,
+        cookie: &str,
+    ) -> Self {
+        SocksHttpTransport {
+            proxy_addr,
+            addr,
+            path,
+            timeout,
+            basic_auth: format!("Basic {}", base64::encode(cookie.as_bytes())),
+        }
+    }
+
+    // Connect to the proxy and have it open a connection to bitcoind.
+    fn connect(&self) -> Result<TcpStream, Error> {
+        let mut sock = TcpStream::connect_timeout(&self.proxy_addr, self.timeout)?;
+        sock.set_read_timeout(Some(self.timeout))?;
+        sock.set_write_timeout(Some(self.timeout))?;
+
+        // The only authentication method we offer is none.
+        sock.write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])?;
+        let mut method = [0; 2];
+        sock.read_exact(&mut method)?;
+        if method != [SOCKS_VERSION, NO_AUTHENTICATION] {
+            return Err(proxy_error(
+                "no acceptable authentication method".to_string(),
+            ));
+        }
+
+        let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+        match self.addr {
+            SocketAddr::V4(addr) => {
+                request.push(ATYP_IPV4);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+            SocketAddr::V6(addr) => {
+                request.push(ATYP_IPV6);
+                request.extend_from_slice(&addr.ip().octets());
+            }
+        }
+        request.extend_from_slice(&self.addr.port().to_be_bytes());
+        sock.write_all(&request)?;
+
+        // The reply ends with the address the proxy connected from, which we don't need but must
+        // read entirely before the HTTP response.
+        let mut reply = [0; 4];
+        sock.read_exact(&mut reply)?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(proxy_error(format!("invalid version {}", reply[0])));
+        }
+        if reply[1] != 0 {
+            return Err(proxy_error(format!(
+                "failed to connect to '{}' (reply code {})",
+                self.addr, reply[1]
+            )));
+        }
+        let addr_len = match reply[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            ATYP_DOMAIN => {
+                let mut len = [0; 1];
+                sock.read_exact(&mut len)?;
+                len[0] as usize
+            }
+            atyp => return Err(proxy_error(format!("invalid address type {}", atyp))),
+        };
+        let mut bound_addr = vec![0; addr_len + 2];
+        sock.read_exact(&mut bound_addr)?;
+
+        Ok(sock)
+    }
+
+    fn request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let deadline = Instant::now() + self.timeout;
+        let mut sock = self.connect()?;
+
+        let body = serde_json::to_vec(&req)?;
+        let mut http_req = format!(
+            "POST {} HTTP/1.1\r\nConnection: Close\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAuthorization: {}\r\n\r\n",
+            self.path,
+            body.len(),
+            self.basic_auth
+        )
+        .into_bytes();
+        http_req.extend_from_slice(&body);
+        sock.write_all(&http_req)?;
+        sock.flush()?;
+
+        let mut reader = BufReader::new(sock);
+        let status_line = read_line(&mut reader, deadline)?;
+        if status_line.len() < 12 || !status_line.starts_with("HTTP/1.1 ") {
+            return Err(Error::HttpParseError);
+        }
+        let status = status_line[9..12]
+            .parse::<u16>()
+            .map_err(|_| Error::HttpParseError)?;
+
+        // Skip the headers.
+        while read_line(&mut reader, deadline)? != "\r\n" {}
+
+        // Bitcoind may reply with a JSONRPC error along with a non-200 status, try to parse it
+        // first as it's more meaningful.
+        let resp_body = read_line(&mut reader, deadline)?;
+        serde_json::from_str(&resp_body).map_err(|e| {
+            if status != 200 {
+                Error::HttpErrorCode(status)
+            } else {
+                e.into()
+            }
+        })
+    }
+}
+
+impl Transport for SocksHttpTransport {
+    fn send_request(&self, req: Request) -> Result<Response, jsonrpc::Error> {
+        Ok(self.request(req)?)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        Ok(self.request(reqs)?)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "http://{}{} (through socks5://{})",
+            self.addr, self.path, self.proxy_addr
+        )
+    }
+}
@@ -1,7 +1,19 @@
 use crate::bitcoin::{d::BlockStats, BlockChainTip};
 
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
 use miniscript::bitcoin;
 
+// Only the blocks with more confirmations than this are cached. We assume they can't be reorged
+// out of the chain.
+const BLOCK_CACHE_MIN_CONFS: i32 = 100;
+
+// The maximum number of block hashes, and block headers, kept in the cache.
+const BLOCK_CACHE_SIZE: usize = 1_000;
+
 /// Truncate the sync progress, rounding it up if it gets above 0.999. Note this also caps the
 /// progress to 1.0, as bitcoind could temporarily return value >1.0 in getblockchaininfo's
 /// "verificationprogress" field.
@@ -20,25 +32,25 @@ pub fn roundup_progress(progress: f64) -> f64 {
 
 // As a standalone function to unit test it.
 /// Get the last block of the chain before the given date by performing a binary search.
-pub fn block_before_date<Fh, Fs>(
+pub fn block_before_date<Fh, Fs, E>(
     target_timestamp: u32,
     chain_tip: BlockChainTip,
     mut get_hash: Fh,
     mut get_stats: Fs,
-) -> Option<BlockChainTip>
+) -> Result<Option<BlockChainTip>, E>
 where
-    Fh: FnMut(i32) -> Option<bitcoin::BlockHash>,
-    Fs: FnMut(bitcoin::BlockHash) -> BlockStats,
+    Fh: FnMut(i32) -> Result<Option<bitcoin::BlockHash>, E>,
+    Fs: FnMut(bitcoin::BlockHash) -> Result<BlockStats, E>,
 {
     log::debug!("Looking for the first block before {}", target_timestamp);
 
     let mut start_height = 0;
     let mut end_height = chain_tip.height;
 
-    let genesis_stats = get_stats(get_hash(0).expect("Genesis hash"));
-    let tip_stats = get_stats(chain_tip.hash);
+    let genesis_stats = get_stats(get_hash(0)?.expect("Genesis hash"))?;
+    let tip_stats = get_stats(chain_tip.hash)?;
     if !(genesis_stats.time..tip_stats.time).contains(&target_timestamp) {
-        return None;
+        return Ok(None);
     }
 
     while start_height < end_height {
@@ -47,7 +59,11 @@ where
         let current_height = start_height + delta.checked_div(2).unwrap();
         // We want the last block with a timestamp below, not the first with a higher one.
         let next_height = current_height.checked_add(1).unwrap();
-        let next_stats = get_stats(get_hash(next_height)?);
+        let next_hash = match get_hash(next_height)? {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+        let next_stats = get_stats(next_hash)?;
         log::debug!("Current next block: {:?}", next_stats);
 
         if target_timestamp > next_stats.time {
@@ -62,36 +78,155 @@ where
     // timestamp above the target a bit down this height. I think we would be safe by scanning the
     // last 12 blocks and checking their timestamp is below the target. Would we?
     log::debug!("Result height: {}", start_height);
-    Some(BlockChainTip {
+    Ok(get_hash(start_height)?.map(|hash| BlockChainTip {
         height: start_height,
-        hash: get_hash(start_height)?,
-    })
+        hash,
+    }))
+}
+
+// A map of a bounded size, dropping the least recently used entry when full.
+struct Lru<K, V> {
+    entries: HashMap<K, (V, u64)>,
+    // The keys by the time they were last used.
+    usage: BTreeMap<u64, K>,
+    counter: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Lru<K, V> {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            usage: BTreeMap::new(),
+            counter: 0,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.usage.remove(last_used);
+        self.counter += 1;
+        *last_used = self.counter;
+        self.usage.insert(self.counter, key.clone());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.counter += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.counter)) {
+            self.usage.remove(&last_used);
+        }
+        self.usage.insert(self.counter, key);
+        if self.entries.len() > BLOCK_CACHE_SIZE {
+            let oldest = self.usage.keys().next().copied();
+            if let Some(lru_key) = oldest.and_then(|last_used| self.usage.remove(&last_used)) {
+                self.entries.remove(&lru_key);
+            }
+        }
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        self.entries.retain(|key, (value, _)| f(key, value));
+        self.usage = self
+            .entries
+            .iter()
+            .map(|(key, (_, last_used))| (*last_used, key.clone()))
+            .collect();
+    }
+}
+
+/// A cache of the hashes and headers of the blocks buried deep enough in the chain, to avoid
+/// querying them again and again from bitcoind.
+pub struct BlockCache {
+    // The height of the last chain tip we got from bitcoind.
+    tip_height: i32,
+    hashes: Lru<i32, bitcoin::BlockHash>,
+    stats: Lru<bitcoin::BlockHash, BlockStats>,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self {
+            tip_height: 0,
+            hashes: Lru::new(),
+            stats: Lru::new(),
+        }
+    }
+}
+
+impl BlockCache {
+    // Whether the block at this height is buried deep enough to be cached.
+    fn is_buried(&self, height: i32) -> bool {
+        self.tip_height - height + 1 > BLOCK_CACHE_MIN_CONFS
+    }
+
+    /// Record the new tip of the chain. If it is lower than the previous one, the blocks which are
+    /// not buried deep enough anymore are dropped.
+    pub fn set_tip(&mut self, tip_height: i32) {
+        let prev_height = self.tip_height;
+        self.tip_height = tip_height;
+        if tip_height < prev_height {
+            let min_buried = tip_height - BLOCK_CACHE_MIN_CONFS;
+            self.hashes.retain(|height, _| *height <= min_buried);
+            self.stats.retain(|_, stats| stats.height <= min_buried);
+        }
+    }
+
+    pub fn block_hash(&mut self, height: i32) -> Option<bitcoin::BlockHash> {
+        self.hashes.get(&height)
+    }
+
+    pub fn insert_block_hash(&mut self, height: i32, hash: bitcoin::BlockHash) {
+        if self.is_buried(height) {
+            self.hashes.insert(height, hash);
+        }
+    }
+
+    /// The cached header of this block, with its number of confirmations as of the last tip.
+    pub fn block_stats(&mut self, hash: &bitcoin::BlockHash) -> Option<BlockStats> {
+        let mut stats = self.stats.get(hash)?;
+        stats.confirmations =
+            std::cmp::max(stats.confirmations, self.tip_height - stats.height + 1);
+        Some(stats)
+    }
+
+    pub fn insert_block_stats(&mut self, stats: BlockStats) {
+        if stats.confirmations > BLOCK_CACHE_MIN_CONFS {
+            self.stats.insert(stats.blockhash, stats);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use miniscript::bitcoin::hashes::Hash;
     use std::str::FromStr;
 
     // The expected number of seconds in average between two blocks.
     const EXPECTED_BLOCK_INTERVAL_SECS: u32 = 600;
 
     // Inefficient dummy implementation of BitcoinD's self.get_block_hash
-    fn get_hash(chain: &[(BlockChainTip, BlockStats)], height: i32) -> Option<bitcoin::BlockHash> {
-        chain
+    fn get_hash(
+        chain: &[(BlockChainTip, BlockStats)],
+        height: i32,
+    ) -> Result<Option<bitcoin::BlockHash>, ()> {
+        Ok(chain
             .iter()
             .find(|(tip, _)| tip.height == height)
-            .map(|(tip, _)| tip.hash)
+            .map(|(tip, _)| tip.hash))
     }
 
     // Inefficient dummy implementation of BitcoinD's self.get_block_stats
-    fn get_stats(chain: &[(BlockChainTip, BlockStats)], hash: bitcoin::BlockHash) -> BlockStats {
-        chain
+    fn get_stats(
+        chain: &[(BlockChainTip, BlockStats)],
+        hash: bitcoin::BlockHash,
+    ) -> Result<BlockStats, ()> {
+        Ok(chain
             .iter()
             .find(|(tip, _)| tip.hash == hash)
             .unwrap()
             .1
-            .clone()
+            .clone())
     }
 
     macro_rules! bh {
@@ -139,6 +274,7 @@ mod tests {
             |h| get_hash(&dummy_chain, h),
             |h| get_stats(&dummy_chain, h),
         )
+        .unwrap()
         .is_none());
 
         // A timestamp before the genesis
@@ -164,6 +300,7 @@ mod tests {
             |h| get_hash(&dummy_chain, h),
             |h| get_stats(&dummy_chain, h),
         )
+        .unwrap()
         .is_none());
 
         // Simulate and detail a full binary search through a dummy chain.
@@ -354,6 +491,7 @@ mod tests {
                 |h| get_hash(&dummy_chain, h),
                 |h| get_stats(&dummy_chain, h),
             )
+            .unwrap()
             .unwrap(),
             // Step 21 above
             BlockChainTip {
@@ -374,4 +512,62 @@ mod tests {
         assert_eq!(roundup_progress(0.9998), 1.0);
         assert_eq!(roundup_progress(0.9991), 1.0);
     }
+
+    #[test]
+    fn block_cache() {
+        let hash = |n: u8| bitcoin::BlockHash::from_inner([n; 32]);
+        let stats = |height: i32, confirmations: i32| BlockStats {
+            confirmations,
+            previous_blockhash: Some(hash(height as u8 - 1)),
+            blockhash: hash(height as u8),
+            height,
+            time: 0,
+            median_time_past: 0,
+        };
+        let mut cache = BlockCache::default();
+
+        // Nothing is cached before we know the tip.
+        cache.insert_block_hash(10, hash(10));
+        assert_eq!(cache.block_hash(10), None);
+
+        // Only the blocks buried deep enough are.
+        cache.set_tip(200);
+        cache.insert_block_hash(100, hash(100));
+        cache.insert_block_hash(150, hash(150));
+        cache.insert_block_stats(stats(100, 101));
+        cache.insert_block_stats(stats(150, 51));
+        assert_eq!(cache.block_hash(100), Some(hash(100)));
+        assert_eq!(cache.block_hash(150), None);
+        assert_eq!(cache.block_stats(&hash(100)).unwrap().confirmations, 101);
+        assert!(cache.block_stats(&hash(150)).is_none());
+
+        // The confirmations of the cached headers follow the tip.
+        cache.set_tip(210);
+        assert_eq!(cache.block_stats(&hash(100)).unwrap().confirmations, 111);
+
+        // A reorg to a lower tip. The block at 150 was reorged out, it is queried again and its
+        // new hash isn't cached. Neither is the block at 100 which isn't buried deep enough
+        // anymore.
+        cache.set_tip(190);
+        cache.insert_block_hash(150, hash(151));
+        assert_eq!(cache.block_hash(150), None);
+        assert_eq!(cache.block_hash(100), None);
+        assert!(cache.block_stats(&hash(100)).is_none());
+        cache.insert_block_hash(90, hash(90));
+        assert_eq!(cache.block_hash(90), Some(hash(90)));
+
+        // The least recently used entries are dropped once the cache is full.
+        for height in 0..=BLOCK_CACHE_SIZE as i32 {
+            cache.set_tip(height + BLOCK_CACHE_MIN_CONFS);
+            if height > 0 {
+                // Keep using the first one.
+                assert_eq!(cache.block_hash(0), Some(hash(0)));
+            }
+            cache.insert_block_hash(height, hash(height as u8));
+        }
+        assert_eq!(cache.hashes.entries.len(), BLOCK_CACHE_SIZE);
+        assert_eq!(cache.block_hash(0), Some(hash(0)));
+        assert_eq!(cache.block_hash(1), None);
+        assert_eq!(cache.block_hash(2), Some(hash(2)));
+    }
 }
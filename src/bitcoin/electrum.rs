@@ -0,0 +1,917 @@
+///! Implementation of the Bitcoin interface using an Electrum server.
+///!
+///! Unlike bitcoind there is no wallet on the server side: coins are found by querying the
+///! history of the scripts derived from our descriptors.
+use crate::{
+    bitcoin::{Block, BlockChainTip, MempoolEntry, UTxO, UtxoSetScan},
+    config, descriptors,
+};
+
+use std::{
+    cell::RefCell,
+    cmp,
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    fmt,
+    sync::{self, atomic},
+    thread, time,
+};
+
+use electrum_client::{
+    raw_client::{ElectrumPlaintextStream, RawClient},
+    ElectrumApi, ScriptStatus,
+};
+use miniscript::bitcoin::{self, secp256k1, util::bip32};
+
+/// Number of scripts queried at once from the server.
+const BATCH_SIZE: u32 = 100;
+
+/// How many blocks to rewind when none of the tips we've seen is part of the best chain anymore.
+const MAX_REORG_DEPTH: i32 = 144;
+
+/// How many of the tips we've seen to keep around to find a common ancestor after a reorg.
+const MAX_SEEN_TIPS: usize = 144;
+
+/// For how long to retry a request after a connection error before giving up.
+const RETRY_LIMIT: time::Duration = time::Duration::from_secs(60);
+
+/// For how long to wait for the server to accept the connection, or to answer a request.
+const TIMEOUT: time::Duration = time::Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum ElectrumError {
+    Client(electrum_client::Error),
+    NetworkMismatch(bitcoin::Network /*config*/),
+}
+
+impl fmt::Display for ElectrumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Client(e) => write!(f, "Electrum client error: {}", e),
+            Self::NetworkMismatch(net) => write!(
+                f,
+                "Network mismatch. We are supposed to run on '{}' but the Electrum server is not.",
+                net
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ElectrumError {}
+
+impl From<electrum_client::Error> for ElectrumError {
+    fn from(e: electrum_client::Error) -> Self {
+        Self::Client(e)
+    }
+}
+
+/// A plain TCP connection to the server. It is dropped after an error, and established again at
+/// the next request.
+struct Connection {
+    addr: String,
+    client: sync::Mutex<Option<RawClient<ElectrumPlaintextStream>>>,
+    /// How many times the connection was established. The subscriptions don't survive a new one.
+    generation: atomic::AtomicU64,
+}
+
+impl Connection {
+    fn new(addr: String) -> Self {
+        Self {
+            addr,
+            client: sync::Mutex::new(None),
+            generation: atomic::AtomicU64::new(0),
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(atomic::Ordering::SeqCst)
+    }
+
+    fn call<T>(
+        &self,
+        f: impl Fn(&RawClient<ElectrumPlaintextStream>) -> Result<T, electrum_client::Error>,
+    ) -> Result<T, electrum_client::Error> {
+        let mut client = self.client.lock().unwrap();
+        if client.is_none() {
+            *client = Some(RawClient::new(self.addr.as_str(), Some(TIMEOUT))?);
+            self.generation.fetch_add(1, atomic::Ordering::SeqCst);
+        }
+        let res = f(client.as_ref().expect("Just set"));
+        // An error returned by the server itself doesn't break the connection.
+        if matches!(res, Err(ref e) if !matches!(e, electrum_client::Error::Protocol(_))) {
+            *client = None;
+        }
+        res
+    }
+}
+
+/// Convert a feerate in BTC/kvB as returned by the server to sats/vb, rounding up.
+fn sats_per_vb(btc_per_kvb: f64) -> Option<u64> {
+    let sats_per_kvb = bitcoin::Amount::from_btc(btc_per_kvb).ok()?.to_sat();
+    Some((sats_per_kvb + 999) / 1_000)
+}
+
+/// Make a request to the server, retrying for some time on connection errors. An error
+/// returned by the server itself (for instance for an unknown transaction) is returned as is,
+/// without retrying.
+fn request<T>(
+    connection: &Connection,
+    f: impl Fn(&RawClient<ElectrumPlaintextStream>) -> Result<T, electrum_client::Error>,
+) -> Result<T, electrum_client::Error> {
+    let start = time::Instant::now();
+    loop {
+        match connection.call(&f) {
+            Err(electrum_client::Error::Protocol(e)) => {
+                return Err(electrum_client::Error::Protocol(e))
+            }
+            Err(e) => {
+                if start.elapsed() > RETRY_LIMIT {
+                    log::error!(
+                        "Failed to make a request to the Electrum server for more than a minute: '{}'.",
+                        e
+                    );
+                    return Err(e);
+                }
+                log::debug!("Error making request to the Electrum server: '{}'.", e);
+                thread::sleep(time::Duration::from_secs(1));
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Make a request to the server for which an error returned by the server itself means the
+/// object queried does not exist.
+fn request_opt<T>(
+    connection: &Connection,
+    f: impl Fn(&RawClient<ElectrumPlaintextStream>) -> Result<T, electrum_client::Error>,
+) -> Result<Option<T>, String> {
+    match request(connection, f) {
+        Ok(res) => Ok(Some(res)),
+        Err(electrum_client::Error::Protocol(_)) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A transaction in the history of a script.
+#[derive(Debug, Clone, Copy)]
+struct HistoryEntry {
+    txid: bitcoin::Txid,
+    /// Confirmation height. 0 if unconfirmed, -1 if some of its inputs are unconfirmed too.
+    height: i32,
+}
+
+/// The scripts we subscribed to, with their status and their history as of this status.
+#[derive(Default)]
+struct ScriptCache {
+    /// The generation of the connection the subscriptions were made on.
+    generation: u64,
+    scripts: HashMap<bitcoin::Script, (Option<ScriptStatus>, Vec<HistoryEntry>)>,
+}
+
+pub struct Electrum {
+    /// Shared with the scans run without holding on to this interface.
+    connection: sync::Arc<Connection>,
+    network: bitcoin::Network,
+    secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    /// Number of consecutive unused addresses after which we stop looking for coins.
+    gap_limit: u32,
+    /// The tips we returned, by height. The server can't give us a block by hash, so this is
+    /// what we compare against the current chain to find a common ancestor after a reorg.
+    seen_tips: RefCell<BTreeMap<i32, bitcoin::BlockHash>>,
+    /// Avoids querying again the history of the scripts which didn't change.
+    script_cache: RefCell<ScriptCache>,
+}
+
+impl Electrum {
+    /// Connect to the Electrum server and check it is on the expected network.
+    pub fn new(
+        config: &config::ElectrumConfig,
+        network: bitcoin::Network,
+        gap_limit: u32,
+    ) -> Result<Self, ElectrumError> {
+        let connection = Connection::new(config.addr.clone());
+
+        let genesis_hash = connection.call(|c| c.block_header(0))?.block_hash();
+        if genesis_hash != bitcoin::blockdata::constants::genesis_block(network).block_hash() {
+            return Err(ElectrumError::NetworkMismatch(network));
+        }
+
+        Ok(Self {
+            connection: sync::Arc::new(connection),
+            network,
+            secp: secp256k1::Secp256k1::verification_only(),
+            gap_limit,
+            seen_tips: RefCell::new(BTreeMap::new()),
+            script_cache: RefCell::new(ScriptCache::default()),
+        })
+    }
+
+    fn request<T>(
+        &self,
+        f: impl Fn(&RawClient<ElectrumPlaintextStream>) -> Result<T, electrum_client::Error>,
+    ) -> Result<T, String> {
+        request(&self.connection, f).map_err(|e| e.to_string())
+    }
+
+    /// The hash of the block at this height, `None` if there is no such block.
+    fn block_hash(&self, height: i32) -> Result<Option<bitcoin::BlockHash>, String> {
+        let height = match height.try_into() {
+            Ok(height) => height,
+            Err(_) => return Ok(None),
+        };
+        Ok(request_opt(&self.connection, |c| c.block_header(height))?
+            .map(|header| header.block_hash()))
+    }
+
+    fn block(&self, height: i32) -> Result<Block, String> {
+        let header = self.request(|c| c.block_header(height as usize))?;
+        Ok(Block {
+            hash: header.block_hash(),
+            height,
+            time: header.time,
+        })
+    }
+
+    fn tip_header(&self) -> Result<(i32, bitcoin::BlockHeader), String> {
+        let notif = self.request(|c| c.block_headers_subscribe())?;
+        let height = notif
+            .height
+            .try_into()
+            .expect("Must fit by Bitcoin consensus");
+        Ok((height, notif.header))
+    }
+
+    fn script_history(&self, script: &bitcoin::Script) -> Result<Vec<HistoryEntry>, String> {
+        Ok(self
+            .histories(&[script.clone()])?
+            .pop()
+            .expect("One history per script"))
+    }
+
+    /// Get the history of these scripts. We subscribe to a script the first time it's queried,
+    /// then its history is only requested again if the server notified us its status changed.
+    fn histories(&self, scripts: &[bitcoin::Script]) -> Result<Vec<Vec<HistoryEntry>>, String> {
+        let mut cache = self.script_cache.borrow_mut();
+        // Make sure we read the notifications sent since the last request.
+        self.request(|c| c.ping())?;
+        let generation = self.connection.generation();
+        if cache.generation != generation {
+            cache.generation = generation;
+            cache.scripts.clear();
+        }
+
+        let mut outdated = Vec::new();
+        for script in scripts {
+            let status = if cache.scripts.contains_key(script) {
+                let mut status = None;
+                // If the connection was lost in the meantime we'll subscribe again next time.
+                while let Some(new_status) = self.request(|c| match c.script_pop(script) {
+                    Err(electrum_client::Error::NotSubscribed(_)) => Ok(None),
+                    res => res,
+                })? {
+                    status = Some(new_status);
+                }
+                match status {
+                    Some(status) if Some(status) != cache.scripts[script].0 => status,
+                    _ => continue,
+                }
+            } else {
+                let status = self.request(|c| match c.script_subscribe(script) {
+                    // The subscription was recorded but the request failed.
+                    Err(electrum_client::Error::AlreadySubscribed(_)) => {
+                        c.script_unsubscribe(script)?;
+                        c.script_subscribe(script)
+                    }
+                    res => res,
+                })?;
+                cache.scripts.insert(script.clone(), (None, Vec::new()));
+                match status {
+                    Some(status) => status,
+                    // The script was never used.
+                    None => continue,
+                }
+            };
+            cache.scripts.get_mut(script).expect("Just checked").0 = Some(status);
+            if !outdated.contains(script) {
+                outdated.push(script.clone());
+            }
+        }
+
+        for chunk in outdated.chunks(BATCH_SIZE as usize) {
+            let histories = self.request(|c| c.batch_script_get_history(chunk.iter()))?;
+            for (script, history) in chunk.iter().zip(histories) {
+                cache.scripts.get_mut(script).expect("Inserted above").1 = history
+                    .into_iter()
+                    .map(|entry| HistoryEntry {
+                        txid: entry.tx_hash,
+                        height: entry.height,
+                    })
+                    .collect();
+            }
+        }
+
+        // The subscriptions made over a previous connection are lost, and the histories
+        // of the scripts we did not query again were dropped with them. Don't return them as
+        // unused: start over at the next poll.
+        if self.connection.generation() != cache.generation {
+            cache.scripts.clear();
+            return Err(
+                "The connection to the Electrum server was reset while querying the scripts history."
+                    .to_string(),
+            );
+        }
+
+        Ok(scripts
+            .iter()
+            .map(|script| {
+                cache
+                    .scripts
+                    .get(script)
+                    .map(|(_, history)| history.clone())
+                    .unwrap_or_default()
+            })
+            .collect())
+    }
+
+    /// Get the scripts of this descriptor which were ever used, with their history. Stops
+    /// after `gap_limit` consecutive unused scripts.
+    fn used_scripts(
+        &self,
+        desc: &descriptors::InheritanceDescriptor,
+    ) -> Result<Vec<(bitcoin::Address, Vec<HistoryEntry>)>, String> {
+        let mut used = Vec::new();
+        let mut last_used = 0;
+        let mut start = 0;
+
+        while start <= last_used + self.gap_limit {
+            let addresses: Vec<bitcoin::Address> = (start..start + BATCH_SIZE)
+                .map(|index| {
+                    let index = bip32::ChildNumber::from_normal_idx(index)
+                        .expect("Can't get above the hardened index by gap limit");
+                    desc.derive(index, &self.secp).address(self.network)
+                })
+                .collect();
+            let scripts: Vec<bitcoin::Script> =
+                addresses.iter().map(|addr| addr.script_pubkey()).collect();
+            let histories = self.histories(&scripts)?;
+
+            for (i, (address, history)) in addresses.into_iter().zip(histories).enumerate() {
+                if !history.is_empty() {
+                    last_used = start + i as u32;
+                    used.push((address, history));
+                }
+            }
+            start += BATCH_SIZE;
+        }
+
+        Ok(used)
+    }
+
+    /// The transactions queried from the server, to avoid making the same requests twice.
+    fn tx_getter(&self) -> TxGetter<'_> {
+        TxGetter {
+            electrum: self,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Get the history of the scripts of these coins at once. `None` for a coin whose transaction
+    /// the server doesn't know about.
+    fn coins_histories<'o>(
+        &self,
+        tx_getter: &mut TxGetter,
+        outpoints: impl Iterator<Item = &'o bitcoin::OutPoint>,
+    ) -> Result<Vec<Option<Vec<HistoryEntry>>>, String> {
+        let mut scripts = Vec::new();
+        for op in outpoints {
+            scripts.push(tx_getter.prevout_script(op)?);
+        }
+        let known: Vec<bitcoin::Script> = scripts.iter().flatten().cloned().collect();
+        let mut histories = self.histories(&known)?.into_iter();
+        Ok(scripts
+            .into_iter()
+            .map(|script| script.map(|_| histories.next().expect("One history per script")))
+            .collect())
+    }
+
+    /// Get the history entry of this transaction from the history of the given script.
+    fn history_entry(
+        &self,
+        script: &bitcoin::Script,
+        txid: &bitcoin::Txid,
+    ) -> Result<Option<HistoryEntry>, String> {
+        Ok(self
+            .script_history(script)?
+            .into_iter()
+            .find(|entry| entry.txid == *txid))
+    }
+}
+
+struct TxGetter<'a> {
+    electrum: &'a Electrum,
+    cache: HashMap<bitcoin::Txid, Option<bitcoin::Transaction>>,
+}
+
+impl<'a> TxGetter<'a> {
+    /// Get a transaction, `None` if the server doesn't know about it.
+    fn get(&mut self, txid: &bitcoin::Txid) -> Result<Option<bitcoin::Transaction>, String> {
+        if let Some(tx) = self.cache.get(txid) {
+            return Ok(tx.clone());
+        }
+        let tx = request_opt(&self.electrum.connection, |c| c.transaction_get(txid))?;
+        self.cache.insert(*txid, tx.clone());
+        Ok(tx)
+    }
+
+    /// Get the script of the output spent by this outpoint.
+    fn prevout_script(
+        &mut self,
+        op: &bitcoin::OutPoint,
+    ) -> Result<Option<bitcoin::Script>, String> {
+        Ok(self.get(&op.txid)?.and_then(|tx| {
+            tx.output
+                .get(op.vout as usize)
+                .map(|txo| txo.script_pubkey.clone())
+        }))
+    }
+
+    /// Whether this transaction spends this coin. False if the server doesn't know about it.
+    fn spends(&mut self, txid: &bitcoin::Txid, op: &bitcoin::OutPoint) -> Result<bool, String> {
+        Ok(self.get(txid)?.map_or(false, |tx| {
+            tx.input.iter().any(|txin| txin.previous_output == *op)
+        }))
+    }
+}
+
+impl super::BitcoinInterface for Electrum {
+    fn genesis_block(&self) -> Result<BlockChainTip, String> {
+        let hash = self
+            .block_hash(0)?
+            .expect("Genesis block hash must always be there");
+        Ok(BlockChainTip { hash, height: 0 })
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        self.connection
+            .call(|c| c.ping())
+            .map_err(|e| e.to_string())
+    }
+
+    fn sync_progress(&self) -> Result<f64, String> {
+        // The server only serves us once it is synced.
+        Ok(1.0)
+    }
+
+    fn chain_tip(&self) -> Result<BlockChainTip, String> {
+        let (height, header) = self.tip_header()?;
+        let hash = header.block_hash();
+
+        let mut seen_tips = self.seen_tips.borrow_mut();
+        seen_tips.insert(height, hash);
+        while seen_tips.len() > MAX_SEEN_TIPS {
+            let lowest = *seen_tips.keys().next().expect("Not empty");
+            seen_tips.remove(&lowest);
+        }
+
+        Ok(BlockChainTip { hash, height })
+    }
+
+    fn tip_time(&self) -> Result<u32, String> {
+        Ok(self.tip_header()?.1.time)
+    }
+
+    fn mempool_min_feerate(&self) -> Result<u64, String> {
+        let btc_per_kvb = self.request(|c| c.relay_fee())?;
+        sats_per_vb(btc_per_kvb)
+            .ok_or_else(|| format!("Invalid relay fee from the server: {}", btc_per_kvb))
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Result<Option<u64>, String> {
+        let btc_per_kvb = self.request(|c| c.estimate_fee(conf_target.into()))?;
+        // The server returns -1 if it does not have enough data.
+        if btc_per_kvb <= 0.0 {
+            return Ok(None);
+        }
+        Ok(sats_per_vb(btc_per_kvb))
+    }
+
+    fn is_in_chain(&self, tip: &BlockChainTip) -> Result<bool, String> {
+        Ok(self.block_hash(tip.height)? == Some(tip.hash))
+    }
+
+    fn received_coins(
+        &self,
+        tip: &BlockChainTip,
+        descs: &[descriptors::InheritanceDescriptor],
+    ) -> Result<Vec<UTxO>, String> {
+        let mut tx_getter = self.tx_getter();
+        let mut received = Vec::new();
+
+        for desc in descs {
+            for (address, history) in self.used_scripts(desc)? {
+                let script = address.script_pubkey();
+                // Unconfirmed transactions have a height of 0, or -1 if they have unconfirmed
+                // parents.
+                for entry in history
+                    .into_iter()
+                    .filter(|entry| entry.height <= 0 || entry.height > tip.height)
+                {
+                    let tx = if let Some(tx) = tx_getter.get(&entry.txid)? {
+                        tx
+                    } else {
+                        log::error!("Server doesn't know transaction '{}'.", entry.txid);
+                        continue;
+                    };
+                    for (vout, txo) in tx.output.iter().enumerate() {
+                        if txo.script_pubkey != script {
+                            continue;
+                        }
+                        received.push(UTxO {
+                            outpoint: bitcoin::OutPoint {
+                                txid: entry.txid,
+                                vout: vout as u32,
+                            },
+                            amount: bitcoin::Amount::from_sat(txo.value),
+                            block_height: if entry.height > 0 {
+                                Some(entry.height)
+                            } else {
+                                None
+                            },
+                            address: address.clone(),
+                            is_coinbase: tx.is_coin_base(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(received)
+    }
+
+    fn confirmed_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Result<(Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>), String> {
+        let mut confirmed = Vec::with_capacity(outpoints.len());
+        let mut expired = Vec::new();
+        let mut tx_getter = self.tx_getter();
+        let histories = self.coins_histories(&mut tx_getter, outpoints.iter())?;
+
+        for (op, history) in outpoints.iter().zip(histories) {
+            // If the server doesn't know the transaction anymore it was dropped from the mempool.
+            let entry =
+                history.and_then(|history| history.into_iter().find(|entry| entry.txid == op.txid));
+            match entry {
+                Some(entry) if entry.height > 0 => {
+                    let block = self.block(entry.height)?;
+                    confirmed.push((*op, block.height, block.time));
+                }
+                Some(_) => {}
+                None => expired.push(*op),
+            }
+        }
+
+        Ok((confirmed, expired))
+    }
+
+    fn spending_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid)>, String> {
+        let mut spending = Vec::with_capacity(outpoints.len());
+        let mut tx_getter = self.tx_getter();
+        let histories = self.coins_histories(&mut tx_getter, outpoints.iter())?;
+
+        for (op, history) in outpoints.iter().zip(histories) {
+            let mut history = if let Some(history) = history {
+                history
+            } else {
+                log::error!("Could not get the transaction of coin '{}'.", op);
+                continue;
+            };
+            // Look at the confirmed spenders first.
+            history.sort_by_key(|entry| cmp::Reverse(entry.height > 0));
+            for entry in history.into_iter().filter(|entry| entry.txid != op.txid) {
+                if tx_getter.spends(&entry.txid, op)? {
+                    spending.push((*op, entry.txid));
+                    break;
+                }
+            }
+        }
+
+        Ok(spending)
+    }
+
+    fn spent_coins(
+        &self,
+        outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>, String> {
+        let mut spent = Vec::with_capacity(outpoints.len());
+        let mut tx_getter = self.tx_getter();
+        let histories = self.coins_histories(&mut tx_getter, outpoints.iter().map(|(op, _)| op))?;
+
+        for ((op, txid), history) in outpoints.iter().zip(histories) {
+            let history = if let Some(history) = history {
+                history
+            } else {
+                log::error!("Could not get the transaction of coin '{}'.", op);
+                continue;
+            };
+
+            // If the spending transaction, or a conflicting one, was confirmed mark it as such.
+            for entry in history
+                .into_iter()
+                .filter(|entry| entry.height > 0 && entry.txid != op.txid)
+            {
+                if entry.txid == *txid || tx_getter.spends(&entry.txid, op)? {
+                    spent.push((*op, entry.txid, self.block(entry.height)?));
+                    break;
+                }
+            }
+        }
+
+        Ok(spent)
+    }
+
+    fn common_ancestor(&self, tip: &BlockChainTip) -> Result<Option<BlockChainTip>, String> {
+        if self.is_in_chain(tip)? {
+            return Ok(Some(*tip));
+        }
+
+        // The server can't give us the parent of a block by hash. Check which of the tips we've
+        // seen are still part of the best chain instead.
+        let seen_tips = self.seen_tips.borrow().clone();
+        for (height, hash) in seen_tips.range(..tip.height).rev() {
+            if self.block_hash(*height)? == Some(*hash) {
+                return Ok(Some(BlockChainTip {
+                    hash: *hash,
+                    height: *height,
+                }));
+            }
+        }
+
+        // None of them is. Rewind far enough for the reorg to not go deeper.
+        let height = cmp::max(tip.height - MAX_REORG_DEPTH, 0);
+        Ok(self
+            .block_hash(height)?
+            .map(|hash| BlockChainTip { hash, height }))
+    }
+
+    fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String> {
+        self.request(|c| c.transaction_broadcast(tx)).map(|_| ())
+    }
+
+    fn mempool_entry(&self, _: &bitcoin::Txid) -> Result<Option<MempoolEntry>, String> {
+        // The Electrum protocol doesn't expose the mempool entries, nor the packages.
+        Ok(None)
+    }
+
+    fn start_rescan(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+        timestamp: u32,
+    ) -> Result<(), String> {
+        // The server indexes the whole history of all scripts, so the rescan is performed by the
+        // poller rewinding our tip to the block before this date and fetching again the history
+        // of our scripts. Make sure this block can be found, and don't trust the histories we
+        // already got.
+        if self.block_before_date(timestamp)?.is_none() {
+            return Err(format!("No block before timestamp {}", timestamp));
+        }
+        self.script_cache.borrow_mut().scripts.clear();
+        Ok(())
+    }
+
+    fn start_rescan_from_height(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        height: i32,
+    ) -> Result<u32, String> {
+        // The median time past of the previous block, below the timestamp of this block and of
+        // all the following ones.
+        let mut times = Vec::new();
+        for h in cmp::max(height - 11, 0)..height {
+            times.push(self.block(h)?.time);
+        }
+        times.sort_unstable();
+        let timestamp = times
+            .get(times.len() / 2)
+            .copied()
+            .ok_or_else(|| format!("Cannot rescan from height {}", height))?;
+        self.start_rescan(desc, timestamp)?;
+        Ok(timestamp)
+    }
+
+    fn rescan_progress(&self) -> Result<Option<f64>, String> {
+        Ok(None)
+    }
+
+    fn block_before_date(&self, timestamp: u32) -> Result<Option<BlockChainTip>, String> {
+        let (tip_height, tip_header) = self.tip_header()?;
+        let genesis_time = self.block(0)?.time;
+        if !(genesis_time..tip_header.time).contains(&timestamp) {
+            return Ok(None);
+        }
+
+        // Find the last block with a timestamp below the target by a binary search.
+        let (mut start, mut end) = (0, tip_height);
+        while start < end {
+            let current = start + (end - start) / 2;
+            if timestamp > self.block(current + 1)?.time {
+                start = current + 1;
+            } else {
+                end = current;
+            }
+        }
+
+        Ok(self.block_hash(start)?.map(|hash| BlockChainTip {
+            hash,
+            height: start,
+        }))
+    }
+
+    fn wallet_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+    ) -> Result<Option<(bitcoin::Transaction, Option<Block>)>, String> {
+        let mut tx_getter = self.tx_getter();
+        let tx = match tx_getter.get(txid)? {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+
+        // Look for the confirmation height in the history of the scripts it pays to, or spends
+        // from.
+        let mut scripts: Vec<bitcoin::Script> = tx
+            .output
+            .iter()
+            .map(|txo| txo.script_pubkey.clone())
+            .collect();
+        for txin in &tx.input {
+            if let Some(script) = tx_getter.prevout_script(&txin.previous_output)? {
+                scripts.push(script);
+            }
+        }
+        let mut block = None;
+        for script in &scripts {
+            if let Some(entry) = self.history_entry(script, txid)? {
+                if entry.height > 0 {
+                    block = Some(self.block(entry.height)?);
+                }
+                break;
+            }
+        }
+
+        Ok(Some((tx, block)))
+    }
+
+    fn utxo_set_scan(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> UtxoSetScan {
+        let mut scripts = Vec::new();
+        for desc in &[desc.receive_descriptor(), desc.change_descriptor()] {
+            for index in 0..range_end {
+                match bip32::ChildNumber::from_normal_idx(index) {
+                    Ok(index) => scripts.push(desc.derive(index, &self.secp).script_pubkey()),
+                    Err(e) => {
+                        let e = e.to_string();
+                        return Box::new(move || Err(e));
+                    }
+                }
+            }
+        }
+
+        let connection = self.connection.clone();
+        Box::new(move || {
+            let mut coins = Vec::new();
+            for chunk in scripts.chunks(BATCH_SIZE as usize) {
+                let unspents = request(&connection, |c| c.batch_script_list_unspent(chunk.iter()))
+                    .map_err(|e| e.to_string())?;
+                coins.extend(
+                    unspents
+                        .into_iter()
+                        .flatten()
+                        .filter(|utxo| utxo.height > 0)
+                        .map(|utxo| {
+                            (
+                                bitcoin::OutPoint {
+                                    txid: utxo.tx_hash,
+                                    vout: utxo.tx_pos as u32,
+                                },
+                                bitcoin::Amount::from_sat(utxo.value),
+                            )
+                        }),
+                );
+            }
+            Ok(coins)
+        })
+    }
+
+    fn watched_range_end(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+    ) -> Result<Option<u32>, String> {
+        // The scripts are queried up to the gap limit past the last used one at every poll.
+        Ok(None)
+    }
+
+    fn extend_watched_range(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+        _: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn address_labels(&self) -> Result<Vec<(bitcoin::Address, String)>, String> {
+        // An Electrum server doesn't keep a wallet for us, there is nothing to label.
+        Ok(Vec::new())
+    }
+
+    fn generate_to_address(
+        &self,
+        _: u32,
+        _: &bitcoin::Address,
+    ) -> Result<Vec<bitcoin::BlockHash>, String> {
+        Err("Mining blocks is not possible through an Electrum server.".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net,
+        str::FromStr,
+    };
+
+    const SCRIPT_STATUS: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const TXID: &str = "617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3";
+
+    // An Electrum server for which every script was used once. The first connection is closed
+    // when the history of a script is requested. Returns its address.
+    fn dummy_server() -> net::SocketAddr {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                let reader = BufReader::new(stream.try_clone().unwrap());
+                for line in reader.lines() {
+                    let req: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                    let result = match req["method"].as_str().unwrap() {
+                        "server.ping" => serde_json::Value::Null,
+                        "blockchain.scripthash.subscribe" => SCRIPT_STATUS.into(),
+                        "blockchain.scripthash.get_history" if i == 0 => break,
+                        "blockchain.scripthash.get_history" => {
+                            serde_json::json!([{ "tx_hash": TXID, "height": 100 }])
+                        }
+                        method => panic!("Unexpected request '{}'", method),
+                    };
+                    let resp =
+                        serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": result});
+                    stream.write_all(format!("{}\n", resp).as_bytes()).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn histories_across_reconnection() {
+        let electrum = Electrum {
+            connection: sync::Arc::new(Connection::new(dummy_server().to_string())),
+            network: bitcoin::Network::Bitcoin,
+            secp: secp256k1::Secp256k1::verification_only(),
+            gap_limit: 20,
+            seen_tips: RefCell::new(BTreeMap::new()),
+            script_cache: RefCell::new(ScriptCache::default()),
+        };
+        let scripts = vec![bitcoin::Script::from(vec![0x51])];
+
+        // The connection is reset after subscribing to the script. The subscription is lost, an
+        // empty history must not be returned in place of the actual one.
+        assert!(electrum.histories(&scripts).is_err());
+
+        // We subscribe again over the new connection.
+        let histories = electrum.histories(&scripts).unwrap();
+        assert_eq!(histories.len(), 1);
+        assert_eq!(histories[0].len(), 1);
+        assert_eq!(histories[0][0].txid, bitcoin::Txid::from_str(TXID).unwrap());
+        assert_eq!(histories[0][0].height, 100);
+    }
+
+    #[test]
+    fn electrum_feerate_conversion() {
+        assert_eq!(sats_per_vb(0.00001), Some(1));
+        assert_eq!(sats_per_vb(0.00001001), Some(2));
+        assert_eq!(sats_per_vb(0.0002), Some(20));
+        assert_eq!(sats_per_vb(-1.0), None);
+    }
+}
@@ -2,6 +2,7 @@
 ///!
 ///! Broadcast transactions, poll for new unspent coins, gather fee estimates.
 pub mod d;
+pub mod electrum;
 pub mod poller;
 
 use crate::{
@@ -9,7 +10,7 @@ use crate::{
     descriptors,
 };
 
-use std::{fmt, sync};
+use std::{collections, fmt, sync};
 
 use miniscript::bitcoin;
 
@@ -21,6 +22,20 @@ pub struct Block {
     pub time: u32,
 }
 
+/// Information about a transaction in the mempool, along with its unconfirmed ancestors and
+/// descendants. The ancestor and descendant figures include the transaction itself.
+#[derive(Debug, Clone, Eq, PartialEq, Copy)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    pub fee: bitcoin::Amount,
+    pub ancestor_count: u64,
+    pub ancestor_vsize: u64,
+    pub ancestor_fees: bitcoin::Amount,
+    pub descendant_count: u64,
+    pub descendant_vsize: u64,
+    pub descendant_fees: bitcoin::Amount,
+}
+
 /// Information about the best block in the chain
 #[derive(Debug, Clone, Eq, PartialEq, Copy)]
 pub struct BlockChainTip {
@@ -34,55 +49,78 @@ impl fmt::Display for BlockChainTip {
     }
 }
 
+/// A scan of the UTxO set for the confirmed unspent coins of a descriptor, run without holding on
+/// to the Bitcoin backend.
+pub type UtxoSetScan =
+    Box<dyn FnOnce() -> Result<Vec<(bitcoin::OutPoint, bitcoin::Amount)>, String> + Send>;
+
 /// Our Bitcoin backend.
+///
+/// The requests to the backend are retried for some time on connection errors. An error is
+/// returned if it still can't be reached, in which case it can be tried again later.
 pub trait BitcoinInterface: Send {
-    fn genesis_block(&self) -> BlockChainTip;
+    fn genesis_block(&self) -> Result<BlockChainTip, String>;
+
+    /// Check whether the Bitcoin backend can be reached, without retrying on failure.
+    fn ping(&self) -> Result<(), String>;
 
     /// Get the progress of the block chain synchronization.
     /// Returns a percentage between 0 and 1.
-    fn sync_progress(&self) -> f64;
+    fn sync_progress(&self) -> Result<f64, String>;
 
     /// Get the best block info.
-    fn chain_tip(&self) -> BlockChainTip;
+    fn chain_tip(&self) -> Result<BlockChainTip, String>;
 
     /// Get the timestamp set in the best block's header.
-    fn tip_time(&self) -> u32;
+    fn tip_time(&self) -> Result<u32, String>;
+
+    /// Get the minimum feerate, in sats/vb, for a transaction to be accepted in the mempool.
+    fn mempool_min_feerate(&self) -> Result<u64, String>;
+
+    /// Estimate the feerate, in sats/vb, for a transaction to confirm within the given number of
+    /// blocks. Returns `None` if the backend does not have enough data to give an estimate.
+    fn estimate_feerate(&self, conf_target: u16) -> Result<Option<u64>, String>;
 
     /// Check whether this former tip is part of the current best chain.
-    fn is_in_chain(&self, tip: &BlockChainTip) -> bool;
+    fn is_in_chain(&self, tip: &BlockChainTip) -> Result<bool, String>;
 
-    /// Get coins received since the specified tip.
+    /// Get coins received since the specified tip. This may fail if the backend does not answer
+    /// in time, for instance with a large wallet, in which case it can be tried again later.
     fn received_coins(
         &self,
         tip: &BlockChainTip,
         descs: &[descriptors::InheritanceDescriptor],
-    ) -> Vec<UTxO>;
+    ) -> Result<Vec<UTxO>, String>;
 
     /// Get all coins that were confirmed, and at what height and time. Along with "expired"
     /// unconfirmed coins (for instance whose creating transaction may have been replaced).
     fn confirmed_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>);
+    ) -> Result<(Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>), String>;
 
     /// Get all coins that are being spent, and the spending txid.
     fn spending_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)>;
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid)>, String>;
 
     /// Get all coins that are spent with the final spend tx txid and blocktime.
     fn spent_coins(
         &self,
         outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>;
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>, String>;
 
     /// Get the common ancestor between the Bitcoin backend's tip and the given tip.
-    fn common_ancestor(&self, tip: &BlockChainTip) -> Option<BlockChainTip>;
+    fn common_ancestor(&self, tip: &BlockChainTip) -> Result<Option<BlockChainTip>, String>;
 
     /// Broadcast this transaction to the Bitcoin P2P network
     fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String>;
 
+    /// Get information about this transaction and its package in the mempool. Returns `None` if
+    /// it is not in the mempool, or if the backend can't tell.
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Result<Option<MempoolEntry>, String>;
+
     /// Trigger a rescan of the block chain for transactions related to this descriptor since
     /// the given date.
     fn start_rescan(
@@ -91,51 +129,100 @@ pub trait BitcoinInterface: Send {
         timestamp: u32,
     ) -> Result<(), String>;
 
+    /// Trigger a rescan of the block chain for transactions related to this descriptor since
+    /// the block at this height. Returns the date the rescan was started from, which is below
+    /// the timestamp of this block and of all the blocks after it.
+    fn start_rescan_from_height(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        height: i32,
+    ) -> Result<u32, String>;
+
     /// Rescan progress percentage. Between 0 and 1.
-    fn rescan_progress(&self) -> Option<f64>;
+    fn rescan_progress(&self) -> Result<Option<f64>, String>;
 
     /// Get the last block chain tip with a timestamp below this. Timestamp must be a valid block
     /// timestamp.
-    fn block_before_date(&self, timestamp: u32) -> Option<BlockChainTip>;
+    fn block_before_date(&self, timestamp: u32) -> Result<Option<BlockChainTip>, String>;
 
     /// Get a transaction related to the wallet along with potential confirmation info.
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
-    ) -> Option<(bitcoin::Transaction, Option<Block>)>;
+    ) -> Result<Option<(bitcoin::Transaction, Option<Block>)>, String>;
+
+    /// Prepare a scan for the confirmed unspent coins of this descriptor, derived up to the given
+    /// index, directly from the UTxO set. This doesn't rely on the coins tracked by the backend's
+    /// wallet. The scan may take minutes: it is run without holding on to the backend.
+    fn utxo_set_scan(&self, desc: &descriptors::MultipathDescriptor, range_end: u32)
+        -> UtxoSetScan;
+
+    /// The last derivation index of this descriptor watched by the backend for incoming coins.
+    /// `None` if the backend doesn't watch a fixed range but follows the gap limit by itself.
+    fn watched_range_end(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+    ) -> Result<Option<u32>, String>;
+
+    /// Watch the addresses of this descriptor for incoming coins up to this derivation index.
+    /// Coins received in the past on the newly watched addresses are not searched for.
+    fn extend_watched_range(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> Result<(), String>;
+
+    /// The labels the backend's wallet has for some addresses, if it supports labeling them.
+    fn address_labels(&self) -> Result<Vec<(bitcoin::Address, String)>, String>;
+
+    /// Mine this number of blocks paying to this address. Only meant for regtest.
+    fn generate_to_address(
+        &self,
+        count: u32,
+        address: &bitcoin::Address,
+    ) -> Result<Vec<bitcoin::BlockHash>, String>;
 }
 
 impl BitcoinInterface for d::BitcoinD {
-    fn genesis_block(&self) -> BlockChainTip {
+    fn genesis_block(&self) -> Result<BlockChainTip, String> {
         let height = 0;
         let hash = self
             .get_block_hash(height)
+            .map_err(|e| e.to_string())?
             .expect("Genesis block hash must always be there");
-        BlockChainTip { hash, height }
+        Ok(BlockChainTip { hash, height })
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        self.ping().map_err(|e| e.to_string())
     }
 
-    fn sync_progress(&self) -> f64 {
-        self.sync_progress()
+    fn sync_progress(&self) -> Result<f64, String> {
+        self.sync_progress().map_err(|e| e.to_string())
     }
 
-    fn chain_tip(&self) -> BlockChainTip {
-        self.chain_tip()
+    fn chain_tip(&self) -> Result<BlockChainTip, String> {
+        self.chain_tip().map_err(|e| e.to_string())
     }
 
-    fn is_in_chain(&self, tip: &BlockChainTip) -> bool {
-        self.get_block_hash(tip.height)
+    fn is_in_chain(&self, tip: &BlockChainTip) -> Result<bool, String> {
+        Ok(self
+            .get_block_hash(tip.height)
+            .map_err(|e| e.to_string())?
             .map(|bh| bh == tip.hash)
-            .unwrap_or(false)
+            .unwrap_or(false))
     }
 
     fn received_coins(
         &self,
         tip: &BlockChainTip,
         descs: &[descriptors::InheritanceDescriptor],
-    ) -> Vec<UTxO> {
-        let lsb_res = self.list_since_block(&tip.hash);
+    ) -> Result<Vec<UTxO>, String> {
+        let lsb_res = self
+            .list_since_block(&tip.hash)
+            .map_err(|e| e.to_string())?;
 
-        lsb_res
+        Ok(lsb_res
             .received_coins
             .into_iter()
             .filter_map(|entry| {
@@ -145,6 +232,7 @@ impl BitcoinInterface for d::BitcoinD {
                     block_height,
                     address,
                     parent_descs,
+                    is_coinbase,
                 } = entry;
                 if parent_descs
                     .iter()
@@ -155,26 +243,33 @@ impl BitcoinInterface for d::BitcoinD {
                         amount,
                         block_height,
                         address,
+                        is_coinbase,
                     })
                 } else {
                     None
                 }
             })
-            .collect()
+            .collect())
     }
 
     fn confirmed_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
+    ) -> Result<(Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>), String> {
         // The confirmed and expired coins to be returned.
         let mut confirmed = Vec::with_capacity(outpoints.len());
         let mut expired = Vec::new();
-        // Cached calls to `gettransaction`.
+        // Cached calls to `gettransaction`, all made at once in batches.
         let mut tx_getter = CachedTxGetter::new(self);
+        tx_getter
+            .cache_transactions(outpoints.iter().map(|op| op.txid))
+            .map_err(|e| e.to_string())?;
 
         for op in outpoints {
-            let res = if let Some(res) = tx_getter.get_transaction(&op.txid) {
+            let res = if let Some(res) = tx_getter
+                .get_transaction(&op.txid)
+                .map_err(|e| e.to_string())?
+            {
                 res
             } else {
                 log::error!("Transaction not in wallet for coin '{}'.", op);
@@ -188,56 +283,58 @@ impl BitcoinInterface for d::BitcoinD {
             }
 
             // If the transaction was dropped from the mempool, discard the coin.
-            if !self.is_in_mempool(&op.txid) {
+            if !self.is_in_mempool(&op.txid).map_err(|e| e.to_string())? {
                 expired.push(*op);
             }
         }
 
-        (confirmed, expired)
+        Ok((confirmed, expired))
     }
 
     fn spending_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid)>, String> {
         let mut spent = Vec::with_capacity(outpoints.len());
 
         for op in outpoints {
-            if self.is_spent(op) {
-                let spending_txid = if let Some(txid) = self.get_spender_txid(op) {
-                    txid
-                } else {
-                    // TODO: better handling of this edge case.
-                    log::error!(
-                        "Could not get spender of '{}'. Not reporting it as spending.",
-                        op
-                    );
-                    continue;
-                };
+            if self.is_spent(op).map_err(|e| e.to_string())? {
+                let spending_txid =
+                    if let Some(txid) = self.get_spender_txid(op).map_err(|e| e.to_string())? {
+                        txid
+                    } else {
+                        // TODO: better handling of this edge case.
+                        log::error!(
+                            "Could not get spender of '{}'. Not reporting it as spending.",
+                            op
+                        );
+                        continue;
+                    };
 
                 spent.push((*op, spending_txid));
             }
         }
 
-        spent
+        Ok(spent)
     }
 
     fn spent_coins(
         &self,
         outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)> {
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>, String> {
         // Spend coins to be returned.
         let mut spent = Vec::with_capacity(outpoints.len());
         // Cached calls to `gettransaction`.
         let mut tx_getter = CachedTxGetter::new(self);
 
         for (op, txid) in outpoints {
-            let res = if let Some(res) = tx_getter.get_transaction(txid) {
-                res
-            } else {
-                log::error!("Could not get tx {} spending coin {}.", txid, op);
-                continue;
-            };
+            let res =
+                if let Some(res) = tx_getter.get_transaction(txid).map_err(|e| e.to_string())? {
+                    res
+                } else {
+                    log::error!("Could not get tx {} spending coin {}.", txid, op);
+                    continue;
+                };
 
             // If the transaction was confirmed, mark it as such.
             if let Some(block) = res.block {
@@ -245,47 +342,80 @@ impl BitcoinInterface for d::BitcoinD {
                 continue;
             }
 
-            // If a conflicting transaction was confirmed instead, replace the txid of the
-            // spender for this coin with it and mark it as confirmed.
-            for txid in &res.conflicting_txs {
-                if let Some(tx) = tx_getter.get_transaction(txid) {
-                    if let Some(block) = tx.block {
-                        spent.push((*op, *txid, block))
-                    }
+            // If a conflicting transaction spending this coin was confirmed instead, replace the
+            // txid of the spender for this coin with it and mark it as confirmed. It may have
+            // replaced a transaction which itself replaced ours, so walk the conflicts until we
+            // find it.
+            let mut visited = collections::HashSet::new();
+            visited.insert(*txid);
+            let mut conflicts: collections::VecDeque<bitcoin::Txid> =
+                res.conflicting_txs.into_iter().collect();
+            while let Some(conflict_txid) = conflicts.pop_front() {
+                if !visited.insert(conflict_txid) {
+                    continue;
+                }
+                let conflict = if let Some(res) = tx_getter
+                    .get_transaction(&conflict_txid)
+                    .map_err(|e| e.to_string())?
+                {
+                    res
+                } else {
+                    continue;
+                };
+                let spends_coin = conflict
+                    .tx
+                    .input
+                    .iter()
+                    .any(|txin| txin.previous_output == *op);
+                if let Some(block) = conflict.block.filter(|_| spends_coin) {
+                    log::debug!(
+                        "Spend '{}' of coin '{}' was replaced by '{}'.",
+                        txid,
+                        op,
+                        conflict_txid
+                    );
+                    spent.push((*op, conflict_txid, block));
+                    break;
                 }
+                conflicts.extend(conflict.conflicting_txs);
             }
         }
 
-        spent
+        Ok(spent)
     }
 
-    fn common_ancestor(&self, tip: &BlockChainTip) -> Option<BlockChainTip> {
-        let mut stats = self.get_block_stats(tip.hash);
+    fn common_ancestor(&self, tip: &BlockChainTip) -> Result<Option<BlockChainTip>, String> {
+        let mut stats = self.get_block_stats(tip.hash).map_err(|e| e.to_string())?;
         let mut ancestor = *tip;
 
         while stats.confirmations == -1 {
-            stats = self.get_block_stats(stats.previous_blockhash?);
+            let prev_hash = match stats.previous_blockhash {
+                Some(hash) => hash,
+                None => return Ok(None),
+            };
+            stats = self.get_block_stats(prev_hash).map_err(|e| e.to_string())?;
             ancestor = BlockChainTip {
                 hash: stats.blockhash,
                 height: stats.height,
             };
         }
 
-        Some(ancestor)
+        Ok(Some(ancestor))
     }
 
     fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String> {
         match self.broadcast_tx(tx) {
             Ok(()) => Ok(()),
             Err(BitcoindError::Server(e)) => Err(e.to_string()),
-            // We assume the Bitcoin backend doesn't fail, so it must be a JSONRPC error.
-            Err(e) => panic!(
-                "Unexpected Bitcoin error when broadcast transaction: '{}'.",
-                e
-            ),
+            // For instance it timed out, the transaction may or may not have been accepted.
+            Err(e) => Err(e.to_string()),
         }
     }
 
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Result<Option<MempoolEntry>, String> {
+        self.mempool_entry(txid).map_err(|e| e.to_string())
+    }
+
     fn start_rescan(
         &self,
         desc: &descriptors::MultipathDescriptor,
@@ -296,42 +426,133 @@ impl BitcoinInterface for d::BitcoinD {
             .map_err(|e| e.to_string())
     }
 
-    fn rescan_progress(&self) -> Option<f64> {
-        self.rescan_progress()
+    fn start_rescan_from_height(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        height: i32,
+    ) -> Result<u32, String> {
+        // The timestamp of a block must be above the median time past of the previous one, which
+        // never decreases. Rescanning from there is therefore sure to include this block.
+        let prev_hash = self
+            .get_block_hash(height - 1)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No block at height {}", height - 1))?;
+        let timestamp = self
+            .get_block_stats(prev_hash)
+            .map_err(|e| e.to_string())?
+            .median_time_past;
+        self.start_rescan(desc, timestamp)
+            .map(|_| timestamp)
+            .map_err(|e| e.to_string())
+    }
+
+    fn rescan_progress(&self) -> Result<Option<f64>, String> {
+        self.rescan_progress().map_err(|e| e.to_string())
     }
 
-    fn block_before_date(&self, timestamp: u32) -> Option<BlockChainTip> {
+    fn block_before_date(&self, timestamp: u32) -> Result<Option<BlockChainTip>, String> {
         self.tip_before_timestamp(timestamp)
+            .map_err(|e| e.to_string())
     }
 
-    fn tip_time(&self) -> u32 {
-        let tip = self.chain_tip();
-        self.get_block_stats(tip.hash).time
+    fn tip_time(&self) -> Result<u32, String> {
+        let tip = self.chain_tip().map_err(|e| e.to_string())?;
+        self.get_block_stats(tip.hash)
+            .map(|stats| stats.time)
+            .map_err(|e| e.to_string())
+    }
+
+    fn mempool_min_feerate(&self) -> Result<u64, String> {
+        self.mempool_min_feerate().map_err(|e| e.to_string())
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Result<Option<u64>, String> {
+        self.estimate_feerate(conf_target)
+            .map_err(|e| e.to_string())
     }
 
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
-    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
-        self.get_transaction(txid).map(|res| (res.tx, res.block))
+    ) -> Result<Option<(bitcoin::Transaction, Option<Block>)>, String> {
+        self.get_transaction(txid)
+            .map(|res| res.map(|res| (res.tx, res.block)))
+            .map_err(|e| e.to_string())
+    }
+
+    fn utxo_set_scan(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> UtxoSetScan {
+        let descs = [
+            desc.receive_descriptor().to_string(),
+            desc.change_descriptor().to_string(),
+        ];
+        let scan = self.scan_tx_out_set(&descs, range_end);
+        Box::new(move || {
+            scan()
+                .map(|entries| {
+                    entries
+                        .into_iter()
+                        .map(|entry| (entry.outpoint, entry.amount))
+                        .collect()
+                })
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    fn watched_range_end(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+    ) -> Result<Option<u32>, String> {
+        self.watched_range_end(desc)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    fn extend_watched_range(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> Result<(), String> {
+        self.extend_watched_range(desc, range_end)
+            .map_err(|e| e.to_string())
+    }
+
+    fn address_labels(&self) -> Result<Vec<(bitcoin::Address, String)>, String> {
+        self.address_labels().map_err(|e| e.to_string())
+    }
+
+    fn generate_to_address(
+        &self,
+        count: u32,
+        address: &bitcoin::Address,
+    ) -> Result<Vec<bitcoin::BlockHash>, String> {
+        self.generate_to_address(count, address)
+            .map_err(|e| e.to_string())
     }
 }
 
 // FIXME: do we need to repeat the entire trait implemenation? Isn't there a nicer way?
 impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>> {
-    fn genesis_block(&self) -> BlockChainTip {
+    fn genesis_block(&self) -> Result<BlockChainTip, String> {
         self.lock().unwrap().genesis_block()
     }
 
-    fn sync_progress(&self) -> f64 {
+    fn ping(&self) -> Result<(), String> {
+        self.lock().unwrap().ping()
+    }
+
+    fn sync_progress(&self) -> Result<f64, String> {
         self.lock().unwrap().sync_progress()
     }
 
-    fn chain_tip(&self) -> BlockChainTip {
+    fn chain_tip(&self) -> Result<BlockChainTip, String> {
         self.lock().unwrap().chain_tip()
     }
 
-    fn is_in_chain(&self, tip: &BlockChainTip) -> bool {
+    fn is_in_chain(&self, tip: &BlockChainTip) -> Result<bool, String> {
         self.lock().unwrap().is_in_chain(tip)
     }
 
@@ -339,32 +560,32 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         &self,
         tip: &BlockChainTip,
         descs: &[descriptors::InheritanceDescriptor],
-    ) -> Vec<UTxO> {
+    ) -> Result<Vec<UTxO>, String> {
         self.lock().unwrap().received_coins(tip, descs)
     }
 
     fn confirmed_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
+    ) -> Result<(Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>), String> {
         self.lock().unwrap().confirmed_coins(outpoints)
     }
 
     fn spending_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid)>, String> {
         self.lock().unwrap().spending_coins(outpoints)
     }
 
     fn spent_coins(
         &self,
         outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)> {
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>, String> {
         self.lock().unwrap().spent_coins(outpoints)
     }
 
-    fn common_ancestor(&self, tip: &BlockChainTip) -> Option<BlockChainTip> {
+    fn common_ancestor(&self, tip: &BlockChainTip) -> Result<Option<BlockChainTip>, String> {
         self.lock().unwrap().common_ancestor(tip)
     }
 
@@ -372,6 +593,10 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         self.lock().unwrap().broadcast_tx(tx)
     }
 
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Result<Option<MempoolEntry>, String> {
+        self.lock().unwrap().mempool_entry(txid)
+    }
+
     fn start_rescan(
         &self,
         desc: &descriptors::MultipathDescriptor,
@@ -380,24 +605,75 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         self.lock().unwrap().start_rescan(desc, timestamp)
     }
 
-    fn rescan_progress(&self) -> Option<f64> {
+    fn start_rescan_from_height(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        height: i32,
+    ) -> Result<u32, String> {
+        self.lock().unwrap().start_rescan_from_height(desc, height)
+    }
+
+    fn rescan_progress(&self) -> Result<Option<f64>, String> {
         self.lock().unwrap().rescan_progress()
     }
 
-    fn block_before_date(&self, timestamp: u32) -> Option<BlockChainTip> {
+    fn block_before_date(&self, timestamp: u32) -> Result<Option<BlockChainTip>, String> {
         self.lock().unwrap().block_before_date(timestamp)
     }
 
-    fn tip_time(&self) -> u32 {
+    fn tip_time(&self) -> Result<u32, String> {
         self.lock().unwrap().tip_time()
     }
 
+    fn mempool_min_feerate(&self) -> Result<u64, String> {
+        self.lock().unwrap().mempool_min_feerate()
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Result<Option<u64>, String> {
+        self.lock().unwrap().estimate_feerate(conf_target)
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
-    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
+    ) -> Result<Option<(bitcoin::Transaction, Option<Block>)>, String> {
         self.lock().unwrap().wallet_transaction(txid)
     }
+
+    fn utxo_set_scan(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> UtxoSetScan {
+        self.lock().unwrap().utxo_set_scan(desc, range_end)
+    }
+
+    fn watched_range_end(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+    ) -> Result<Option<u32>, String> {
+        self.lock().unwrap().watched_range_end(desc)
+    }
+
+    fn extend_watched_range(
+        &self,
+        desc: &descriptors::MultipathDescriptor,
+        range_end: u32,
+    ) -> Result<(), String> {
+        self.lock().unwrap().extend_watched_range(desc, range_end)
+    }
+
+    fn address_labels(&self) -> Result<Vec<(bitcoin::Address, String)>, String> {
+        self.lock().unwrap().address_labels()
+    }
+
+    fn generate_to_address(
+        &self,
+        count: u32,
+        address: &bitcoin::Address,
+    ) -> Result<Vec<bitcoin::BlockHash>, String> {
+        self.lock().unwrap().generate_to_address(count, address)
+    }
 }
 
 // FIXME: We could avoid this type (and all the conversions entailing allocations) if bitcoind
@@ -408,4 +684,5 @@ pub struct UTxO {
     pub amount: bitcoin::Amount,
     pub block_height: Option<i32>,
     pub address: bitcoin::Address,
+    pub is_coinbase: bool,
 }
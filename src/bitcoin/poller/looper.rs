@@ -1,15 +1,35 @@
 use crate::{
-    bitcoin::{BitcoinInterface, BlockChainTip, UTxO},
-    database::{Coin, CoinType, DatabaseConnection, DatabaseInterface},
+    bitcoin::{poller::zmq::BlockNotifier, BitcoinInterface, Block, BlockChainTip, UTxO},
+    database::{
+        obsolete_spends, Coin, CoinType, DatabaseConnection, DatabaseInterface, SpendBlock,
+    },
     descriptors,
 };
 
 use std::{
+    cmp,
+    collections::HashMap,
+    net,
     sync::{self, atomic},
     thread, time,
 };
 
-use miniscript::bitcoin::{self, secp256k1};
+use miniscript::bitcoin::{self, secp256k1, util::bip32};
+
+// The maximum interval between two checks of the block chain synchronization progress, while the
+// Bitcoin backend is still syncing.
+const MAX_SYNC_CHECK_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+// While we are notified of new blocks, the Bitcoin backend is still polled at this interval to
+// catch up with a missed notification and to pick up the unconfirmed transactions.
+const CATCH_UP_POLL_INTERVAL: time::Duration = time::Duration::from_secs(120);
+
+// The maximum interval between two attempts to reach the Bitcoin backend, while it is
+// unreachable. Unless the poll interval itself is longer.
+const MAX_BACKOFF_INTERVAL: time::Duration = time::Duration::from_secs(300);
+
+// The last normal (non-hardened) derivation index.
+const MAX_NORMAL_INDEX: u32 = (1 << 31) - 1;
 
 #[derive(Debug, Clone)]
 struct UpdatedCoins {
@@ -20,6 +40,49 @@ struct UpdatedCoins {
     pub spent: Vec<(bitcoin::OutPoint, bitcoin::Txid, i32, u32)>,
 }
 
+// Whether this spend of one of our coins is already recorded in database. The spends we keep
+// re-checking until they are final would otherwise be written again at every poll. Only those
+// that were replaced or confirmed in a different block since need to be updated.
+fn is_spend_recorded(
+    coins: &HashMap<bitcoin::OutPoint, Coin>,
+    outpoint: &bitcoin::OutPoint,
+    txid: &bitcoin::Txid,
+    block: &Block,
+) -> bool {
+    coins
+        .get(outpoint)
+        .map(|coin| {
+            coin.spend_txid.as_ref() == Some(txid)
+                && coin.spend_block
+                    == Some(SpendBlock {
+                        height: block.height,
+                        time: block.time,
+                    })
+        })
+        .unwrap_or(false)
+}
+
+// Find the derivation index of an address past the look-ahead of the addresses mapping in
+// database, up to the gap limit past the highest derivation index. The Bitcoin backend may watch
+// further than this look-ahead. The descriptors are the receive and change ones, in this order.
+fn derivation_index_past_look_ahead(
+    descs: &[descriptors::InheritanceDescriptor],
+    address: &bitcoin::Address,
+    highest_index: u32,
+    gap_limit: u32,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Option<(bip32::ChildNumber, bool)> {
+    let script_pubkey = address.script_pubkey();
+    let last_index = cmp::min(highest_index.saturating_add(gap_limit), MAX_NORMAL_INDEX);
+    (highest_index.saturating_add(1)..=last_index).find_map(|index| {
+        let index = bip32::ChildNumber::from_normal_idx(index).ok()?;
+        descs
+            .iter()
+            .position(|desc| desc.derive(index, secp).script_pubkey() == script_pubkey)
+            .map(|i| (index, i == 1))
+    })
+}
+
 // Update the state of our coins. There may be new unspent, and existing ones may become confirmed
 // or spent.
 // NOTE: A coin may be updated multiple times at once. That is, a coin may be received, confirmed,
@@ -29,18 +92,31 @@ fn update_coins(
     db_conn: &mut Box<dyn DatabaseConnection>,
     previous_tip: &BlockChainTip,
     descs: &[descriptors::InheritanceDescriptor],
+    final_spend_confs: u32,
+    gap_limit: u32,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
-) -> UpdatedCoins {
+) -> Result<UpdatedCoins, String> {
     let curr_coins = db_conn.coins(CoinType::All);
     log::debug!("Current coins: {:?}", curr_coins);
 
     // Start by fetching newly received coins.
     let mut received = Vec::new();
-    for utxo in bit.received_coins(previous_tip, descs) {
+    for utxo in bit.received_coins(previous_tip, descs)? {
         // We can only really treat them if we know the derivation index that was used.
-        if let Some((derivation_index, is_change)) =
-            db_conn.derivation_index_by_address(&utxo.address)
-        {
+        let derivation_info = db_conn
+            .derivation_index_by_address(&utxo.address)
+            .or_else(|| {
+                let highest_index =
+                    cmp::max(db_conn.receive_index(), db_conn.change_index()).into();
+                derivation_index_past_look_ahead(
+                    descs,
+                    &utxo.address,
+                    highest_index,
+                    gap_limit,
+                    secp,
+                )
+            });
+        if let Some((derivation_index, is_change)) = derivation_info {
             // First of if we are receiving coins that are beyond our next derivation index,
             // adjust it.
             if derivation_index > db_conn.receive_index() {
@@ -53,8 +129,20 @@ fn update_coins(
             // Now record this coin as a newly received one.
             if !curr_coins.contains_key(&utxo.outpoint) {
                 let UTxO {
-                    outpoint, amount, ..
+                    outpoint,
+                    amount,
+                    block_height,
+                    is_coinbase,
+                    ..
                 } = utxo;
+                // Whether the sender may still replace the payment before it confirms. Our own
+                // change outputs are not payments we need to warn about.
+                let is_rbf = !is_change
+                    && block_height.is_none()
+                    && bit
+                        .wallet_transaction(&outpoint.txid)?
+                        .map(|(tx, _)| tx.input.iter().any(|txin| txin.sequence.is_rbf()))
+                        .unwrap_or(false);
                 let coin = Coin {
                     outpoint,
                     amount,
@@ -62,13 +150,15 @@ fn update_coins(
                     is_change,
                     block_height: None,
                     block_time: None,
+                    is_coinbase,
+                    is_rbf,
                     spend_txid: None,
+                    replaced_spend_txid: None,
                     spend_block: None,
                 };
                 received.push(coin);
             }
         } else {
-            // TODO: maybe we could try out something here? Like bruteforcing the next 200 indexes?
             log::error!(
                 "Could not get derivation index for coin '{}' (address: '{}')",
                 &utxo.outpoint,
@@ -92,7 +182,7 @@ fn update_coins(
             }
         })
         .collect();
-    let (confirmed, expired) = bit.confirmed_coins(&to_be_confirmed);
+    let (confirmed, expired) = bit.confirmed_coins(&to_be_confirmed)?;
     log::debug!("Newly confirmed coins: {:?}", confirmed);
     log::debug!("Expired coins: {:?}", expired);
 
@@ -110,33 +200,41 @@ fn update_coins(
             }
         })
         .collect();
-    let spending = bit.spending_coins(&to_be_spent);
+    let spending = bit.spending_coins(&to_be_spent)?;
     log::debug!("Newly spending coins: {:?}", spending);
 
     // Mark coins in a spending state whose Spend transaction was confirmed as such. Note we
     // need to take into account the freshly marked as spending coins as well, as their spend
     // may have been confirmed within the previous tip and the current one, and we may not poll
     // this chunk of the chain anymore.
+    // We also keep checking the coins whose Spend transaction was confirmed but isn't buried deep
+    // enough yet, in case it was replaced by a conflicting one. Once it reached the configured
+    // number of confirmations the spend is considered final and we stop checking it.
     let spending_coins: Vec<(bitcoin::OutPoint, bitcoin::Txid)> = db_conn
         .list_spending_coins()
         .values()
+        .chain(curr_coins.values().filter(|coin| {
+            coin.spend_block.is_some()
+                && !coin.is_spend_final(previous_tip.height, final_spend_confs)
+        }))
         .map(|coin| (coin.outpoint, coin.spend_txid.expect("Coin is spending")))
         .chain(spending.iter().cloned())
         .collect();
     let spent = bit
-        .spent_coins(spending_coins.as_slice())
+        .spent_coins(spending_coins.as_slice())?
         .into_iter()
-        .map(|(oupoint, txid, block)| (oupoint, txid, block.height, block.time))
+        .filter(|(outpoint, txid, block)| !is_spend_recorded(&curr_coins, outpoint, txid, block))
+        .map(|(outpoint, txid, block)| (outpoint, txid, block.height, block.time))
         .collect();
     log::debug!("Newly spent coins: {:?}", spent);
 
-    UpdatedCoins {
+    Ok(UpdatedCoins {
         received,
         confirmed,
         expired,
         spending,
         spent,
-    }
+    })
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -150,19 +248,19 @@ enum TipUpdate {
 }
 
 // Returns the new block chain tip, if it changed.
-fn new_tip(bit: &impl BitcoinInterface, current_tip: &BlockChainTip) -> TipUpdate {
-    let bitcoin_tip = bit.chain_tip();
+fn new_tip(bit: &impl BitcoinInterface, current_tip: &BlockChainTip) -> Result<TipUpdate, String> {
+    let bitcoin_tip = bit.chain_tip()?;
 
     // If the tip didn't change, there is nothing to update.
     if current_tip == &bitcoin_tip {
-        return TipUpdate::Same;
+        return Ok(TipUpdate::Same);
     }
 
     if bitcoin_tip.height > current_tip.height {
         // Make sure we are on the same chain.
-        if bit.is_in_chain(current_tip) {
+        if bit.is_in_chain(current_tip)? {
             // All good, we just moved forward.
-            return TipUpdate::Progress(bitcoin_tip);
+            return Ok(TipUpdate::Progress(bitcoin_tip));
         }
     }
 
@@ -170,13 +268,13 @@ fn new_tip(bit: &impl BitcoinInterface, current_tip: &BlockChainTip) -> TipUpdat
     // block chain re-organisation. Find the common ancestor between our current chain and
     // the new chain and return that. The caller will take care of rewinding our state.
     log::info!("Block chain reorganization detected. Looking for common ancestor.");
-    if let Some(common_ancestor) = bit.common_ancestor(current_tip) {
+    if let Some(common_ancestor) = bit.common_ancestor(current_tip)? {
         log::info!(
             "Common ancestor found: '{}'. Starting rescan from there. Old tip was '{}'.",
             common_ancestor,
             current_tip
         );
-        TipUpdate::Reorged(common_ancestor)
+        Ok(TipUpdate::Reorged(common_ancestor))
     } else {
         log::error!(
             "Failed to get common ancestor for tip '{}'. Starting over.",
@@ -190,13 +288,15 @@ fn updates(
     bit: &impl BitcoinInterface,
     db: &impl DatabaseInterface,
     descs: &[descriptors::InheritanceDescriptor],
+    final_spend_confs: u32,
+    gap_limit: u32,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
-) {
+) -> Result<(), String> {
     let mut db_conn = db.connection();
 
     // Check if there was a new block before updating ourselves.
     let current_tip = db_conn.chain_tip().expect("Always set at first startup");
-    let latest_tip = match new_tip(bit, &current_tip) {
+    let latest_tip = match new_tip(bit, &current_tip)? {
         TipUpdate::Same => current_tip,
         TipUpdate::Progress(new_tip) => new_tip,
         TipUpdate::Reorged(new_tip) => {
@@ -204,18 +304,26 @@ fn updates(
             // between our former chain and the new one, then restart fresh.
             db_conn.rollback_tip(&new_tip);
             log::info!("Tip was rolled back to '{}'.", new_tip);
-            return updates(bit, db, descs, secp);
+            return updates(bit, db, descs, final_spend_confs, gap_limit, secp);
         }
     };
 
     // Then check the state of our coins. Do it even if the tip did not change since last poll, as
     // we may have unconfirmed transactions.
-    let updated_coins = update_coins(bit, &mut db_conn, &current_tip, descs, secp);
+    let updated_coins = update_coins(
+        bit,
+        &mut db_conn,
+        &current_tip,
+        descs,
+        final_spend_confs,
+        gap_limit,
+        secp,
+    )?;
 
     // If the tip changed while we were polling our Bitcoin interface, start over.
-    if bit.chain_tip() != latest_tip {
+    if bit.chain_tip()? != latest_tip {
         log::info!("Chain tip changed while we were updating our state. Starting over.");
-        return updates(bit, db, descs, secp);
+        return updates(bit, db, descs, final_spend_confs, gap_limit, secp);
     }
 
     // The chain tip did not change since we started our updates. Record them and the latest tip.
@@ -232,6 +340,7 @@ fn updates(
     }
 
     log::debug!("Updates done.");
+    Ok(())
 }
 
 // Check if there is any rescan of the backend ongoing or one that just finished.
@@ -239,6 +348,8 @@ fn rescan_check(
     bit: &impl BitcoinInterface,
     db: &impl DatabaseInterface,
     descs: &[descriptors::InheritanceDescriptor],
+    final_spend_confs: u32,
+    gap_limit: u32,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
 ) {
     log::debug!("Checking the state of an ongoing rescan if there is any");
@@ -249,7 +360,14 @@ fn rescan_check(
     // Upon completion of the rescan from the given timestamp on the backend, we rollback our state
     // down to the height before this timestamp to rescan everything that happened since then.
     let rescan_timestamp = db_conn.rescan_timestamp();
-    if let Some(progress) = bit.rescan_progress() {
+    let rescan_progress = match bit.rescan_progress() {
+        Ok(progress) => progress,
+        Err(e) => {
+            log::warn!("Error while checking the rescan progress: '{}'.", e);
+            return;
+        }
+    };
+    if let Some(progress) = rescan_progress {
         log::info!("Rescan progress: {:.2}%.", progress * 100.0);
         if rescan_timestamp.is_none() {
             log::warn!("Backend is rescanning but we didn't ask for it.");
@@ -262,14 +380,21 @@ fn rescan_check(
         // the timestamp of the descriptors in the wallet first (and therefore consider it as
         // rescanned from this height even if it aborts the rescan by being stopped).
         let rescan_tip = match bit.block_before_date(timestamp) {
-            Some(block) => block,
-            None => {
+            Ok(Some(block)) => block,
+            Ok(None) => {
                 log::error!(
                     "Could not retrieve block height for timestamp '{}'",
                     timestamp
                 );
                 return;
             }
+            Err(e) => {
+                log::warn!(
+                    "Error while getting the block before the rescan timestamp: '{}'.",
+                    e
+                );
+                return;
+            }
         };
         db_conn.rollback_tip(&rescan_tip);
         db_conn.complete_rescan();
@@ -277,70 +402,343 @@ fn rescan_check(
             "Rolling back our internal tip to '{}' to update our internal state with past transactions.",
             rescan_tip
         );
-        updates(bit, db, descs, secp)
+        // Our tip was rolled back already, the next poll will pick up from there if this fails.
+        if let Err(e) = updates(bit, db, descs, final_spend_confs, gap_limit, secp) {
+            log::warn!("Error while updating our state after the rescan: '{}'.", e);
+        }
     } else {
         log::debug!("No ongoing rescan.");
     }
 }
 
+// The end of the range to watch, if the addresses up to the gap limit past the highest derivation
+// index aren't all watched. Twice the gap limit is watched for the descriptors not to be imported
+// again every time a new address is used.
+fn extended_range_end(highest_index: u32, range_end: u32, gap_limit: u32) -> Option<u32> {
+    if highest_index.saturating_add(gap_limit) <= range_end {
+        return None;
+    }
+    let new_end = cmp::min(
+        highest_index.saturating_add(gap_limit.saturating_mul(2)),
+        MAX_NORMAL_INDEX,
+    );
+    if new_end > range_end {
+        Some(new_end)
+    } else {
+        None
+    }
+}
+
+// Make sure the Bitcoin backend watches the addresses up to the gap limit past the highest
+// derivation index we gave out or received coins on. A coin paid to an address past the watched
+// range would go unnoticed.
+fn maybe_extend_watched_range(
+    bit: &impl BitcoinInterface,
+    db: &impl DatabaseInterface,
+    desc: &descriptors::MultipathDescriptor,
+    gap_limit: u32,
+) {
+    // Importing the descriptors again would have to wait for an ongoing rescan to complete.
+    if !matches!(bit.rescan_progress(), Ok(None)) {
+        return;
+    }
+    let range_end = match bit.watched_range_end(desc) {
+        Ok(Some(range_end)) => range_end,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Error while getting the watched range: '{}'.", e);
+            return;
+        }
+    };
+    let mut db_conn = db.connection();
+    let highest_index: u32 = cmp::max(db_conn.receive_index(), db_conn.change_index()).into();
+    if let Some(new_end) = extended_range_end(highest_index, range_end, gap_limit) {
+        log::info!(
+            "Highest derivation index is {}, extending the watched range from {} to {}.",
+            highest_index,
+            range_end,
+            new_end
+        );
+        if let Err(e) = bit.extend_watched_range(desc, new_end) {
+            log::error!("Error extending the watched range: '{}'.", e);
+        }
+    }
+}
+
+// Delete the stored Spend transactions which were replaced by a final transaction.
+fn delete_obsolete_spends(db: &impl DatabaseInterface, final_spend_confs: u32) {
+    let mut db_conn = db.connection();
+
+    for txid in obsolete_spends(&mut db_conn, final_spend_confs) {
+        log::info!("Deleting Spend transaction '{}', it was replaced.", txid);
+        db_conn.delete_spend(&txid);
+    }
+}
+
 // If the database chain tip is NULL (first startup), initialize it.
-fn maybe_initialize_tip(bit: &impl BitcoinInterface, db: &impl DatabaseInterface) {
+fn maybe_initialize_tip(
+    bit: &impl BitcoinInterface,
+    db: &impl DatabaseInterface,
+) -> Result<(), String> {
     let mut db_conn = db.connection();
 
     if db_conn.chain_tip().is_none() {
         // TODO: be smarter. We can use the timestamp of the descriptor to get a newer block hash.
-        db_conn.update_tip(&bit.genesis_block());
+        db_conn.update_tip(&bit.genesis_block()?);
     }
+
+    Ok(())
+}
+
+// The interval until the next attempt to reach the Bitcoin backend, after a failed one.
+fn unreachable_backoff(interval: time::Duration, poll_interval: time::Duration) -> time::Duration {
+    cmp::min(interval * 2, cmp::max(MAX_BACKOFF_INTERVAL, poll_interval))
 }
 
 /// Main event loop. Repeatedly polls the Bitcoin interface until told to stop through the
 /// `shutdown` atomic.
+#[allow(clippy::too_many_arguments)]
 pub fn looper(
     bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
     db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
     shutdown: sync::Arc<atomic::AtomicBool>,
     poll_interval: time::Duration,
     desc: descriptors::MultipathDescriptor,
+    final_spend_confs: u32,
+    cleanup_spends: bool,
+    gap_limit: u32,
+    zmq_block_addr: Option<net::SocketAddr>,
 ) {
     let mut last_poll = None;
     let mut synced = false;
+    let mut unreachable = false;
+    // While the Bitcoin backend is syncing or unreachable, back off the interval between two
+    // checks. It is reset to the poll interval once it's synced and reachable.
+    let mut interval = poll_interval;
     let descs = [
         desc.receive_descriptor().clone(),
         desc.change_descriptor().clone(),
     ];
     let secp = secp256k1::Secp256k1::verification_only();
 
-    maybe_initialize_tip(&bit, &db);
+    // Poll as soon as a block is announced, if we can be notified of them. Otherwise, or if the
+    // connection to the notifications is lost, fall back to polling on an interval.
+    let notifier = zmq_block_addr.map(|addr| BlockNotifier::start(addr, shutdown.clone()));
 
     while !shutdown.load(atomic::Ordering::Relaxed) || last_poll.is_none() {
         let now = time::Instant::now();
 
         if let Some(last_poll) = last_poll {
-            if now.duration_since(last_poll) < poll_interval {
+            let (notified, interval) = match &notifier {
+                Some(notifier) if synced && notifier.is_connected() => (
+                    notifier.new_block(),
+                    cmp::max(interval, CATCH_UP_POLL_INTERVAL),
+                ),
+                _ => (false, interval),
+            };
+            if !notified && now.duration_since(last_poll) < interval {
                 thread::sleep(time::Duration::from_millis(500));
                 continue;
             }
         }
         last_poll = Some(now);
 
+        // Don't make requests which would be retried until failure while the Bitcoin backend
+        // can't be reached. Only the transitions are logged, not every failed attempt.
+        match bit.ping() {
+            Err(e) => {
+                if !unreachable {
+                    log::warn!(
+                        "The Bitcoin backend is unreachable: '{}'. Backing off until it recovers.",
+                        e
+                    );
+                    unreachable = true;
+                    interval = poll_interval;
+                }
+                interval = unreachable_backoff(interval, poll_interval);
+                log::debug!(
+                    "The Bitcoin backend is still unreachable, next attempt in {:?}.",
+                    interval
+                );
+                continue;
+            }
+            Ok(()) if unreachable => {
+                log::info!("The Bitcoin backend is reachable again.");
+                unreachable = false;
+                interval = poll_interval;
+            }
+            Ok(()) => {}
+        }
+
         // Don't poll until the Bitcoin backend is fully synced.
         if !synced {
-            let sync_progress = bit.sync_progress();
+            let sync_progress = match bit.sync_progress() {
+                Ok(progress) => progress,
+                Err(e) => {
+                    log::warn!(
+                        "Error while checking the synchronization progress: '{}'.",
+                        e
+                    );
+                    continue;
+                }
+            };
             log::info!(
                 "Block chain synchronization progress: {:.2}%",
                 sync_progress * 100.0
             );
             synced = sync_progress == 1.0;
             if !synced {
-                // Avoid harassing bitcoind..
-                // TODO: be smarter, like in revaultd, but more generic too.
-                #[cfg(not(test))]
-                thread::sleep(time::Duration::from_secs(30));
+                // Avoid harassing bitcoind, it can't give us useful answers until it's synced
+                // anyways.
+                if interval < MAX_SYNC_CHECK_INTERVAL {
+                    interval = std::cmp::min(interval * 2, MAX_SYNC_CHECK_INTERVAL);
+                }
+                log::debug!(
+                    "Waiting for the block chain to be synced, next check in {:?}.",
+                    interval
+                );
                 continue;
             }
+            interval = poll_interval;
+        }
+
+        if let Err(e) = maybe_initialize_tip(&bit, &db) {
+            log::warn!("Error while initializing our chain tip: '{}'.", e);
+            continue;
+        }
+
+        // The Bitcoin backend may not answer in time, for instance if the wallet is large. Our
+        // state was left untouched, just try again at the next poll.
+        if let Err(e) = updates(&bit, &db, &descs, final_spend_confs, gap_limit, &secp) {
+            log::warn!(
+                "Error while polling the Bitcoin backend: '{}'. Retrying at next poll.",
+                e
+            );
+            continue;
         }
+        rescan_check(&bit, &db, &descs, final_spend_confs, gap_limit, &secp);
+        maybe_extend_watched_range(&bit, &db, &desc, gap_limit);
+        if cleanup_spends {
+            delete_obsolete_spends(&db, final_spend_confs);
+        }
+    }
+
+    if let Some(notifier) = notifier {
+        notifier.join();
+    }
+}
 
-        updates(&bit, &db, &descs, &secp);
-        rescan_check(&bit, &db, &descs, &secp);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn watched_range_extension() {
+        // Enough addresses are watched past the highest index.
+        assert_eq!(extended_range_end(0, 999, 200), None);
+        assert_eq!(extended_range_end(799, 999, 200), None);
+
+        // The gap limit past the highest index isn't watched anymore, watch twice as much.
+        assert_eq!(extended_range_end(800, 999, 200), Some(1_200));
+        assert_eq!(extended_range_end(5_000, 999, 1_000), Some(7_000));
+
+        // Never past the normal derivation indexes.
+        assert_eq!(
+            extended_range_end(MAX_NORMAL_INDEX - 100, 0, 200),
+            Some(MAX_NORMAL_INDEX)
+        );
+        assert_eq!(
+            extended_range_end(MAX_NORMAL_INDEX - 100, MAX_NORMAL_INDEX, 200),
+            None
+        );
+    }
+
+    #[test]
+    fn unreachable_backend_backoff() {
+        let secs = time::Duration::from_secs;
+
+        // The interval doubles at each failed attempt.
+        assert_eq!(unreachable_backoff(secs(30), secs(30)), secs(60));
+        assert_eq!(unreachable_backoff(secs(60), secs(30)), secs(120));
+        assert_eq!(unreachable_backoff(secs(120), secs(30)), secs(240));
+
+        // Up to the maximum backoff interval.
+        assert_eq!(
+            unreachable_backoff(secs(240), secs(30)),
+            MAX_BACKOFF_INTERVAL
+        );
+        assert_eq!(
+            unreachable_backoff(MAX_BACKOFF_INTERVAL, secs(30)),
+            MAX_BACKOFF_INTERVAL
+        );
+
+        // Unless the poll interval is longer, in which case we never wait for more than it.
+        assert_eq!(unreachable_backoff(secs(600), secs(600)), secs(600));
+    }
+
+    #[test]
+    fn recorded_spends() {
+        let outpoint = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let txid = bitcoin::Txid::from_str(
+            "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
+        )
+        .unwrap();
+        let other_txid = bitcoin::Txid::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810",
+        )
+        .unwrap();
+        let block = Block {
+            hash: bitcoin::BlockHash::from_str(
+                "00000000000000000006d50e4c9fd269ddf690c94f422dff85e96f1a84b3a615",
+            )
+            .unwrap(),
+            height: 100,
+            time: 1_700_000_000,
+        };
+        let coins: HashMap<_, _> = [(
+            outpoint,
+            Coin {
+                outpoint,
+                block_height: Some(90),
+                block_time: Some(1_699_990_000),
+                amount: bitcoin::Amount::from_sat(100_000),
+                derivation_index: bip32::ChildNumber::from(0),
+                is_change: false,
+                spend_txid: Some(txid),
+                spend_block: Some(SpendBlock {
+                    height: 100,
+                    time: 1_700_000_000,
+                }),
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect();
+
+        // The very same spend is already recorded.
+        assert!(is_spend_recorded(&coins, &outpoint, &txid, &block));
+
+        // The spend was replaced, or confirmed in another block.
+        assert!(!is_spend_recorded(&coins, &outpoint, &other_txid, &block));
+        let other_block = Block {
+            height: 101,
+            time: 1_700_000_600,
+            ..block
+        };
+        assert!(!is_spend_recorded(&coins, &outpoint, &txid, &other_block));
+
+        // A coin we don't know of.
+        let other_outpoint = bitcoin::OutPoint {
+            vout: 1,
+            ..outpoint
+        };
+        assert!(!is_spend_recorded(&coins, &other_outpoint, &txid, &block));
     }
 }
@@ -1,4 +1,5 @@
 mod looper;
+mod zmq;
 
 use crate::{
     bitcoin::{poller::looper::looper, BitcoinInterface},
@@ -7,6 +8,7 @@ use crate::{
 };
 
 use std::{
+    net,
     sync::{self, atomic},
     thread, time,
 };
@@ -18,18 +20,35 @@ pub struct Poller {
 }
 
 impl Poller {
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
         db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
         poll_interval: time::Duration,
         desc: descriptors::MultipathDescriptor,
+        final_spend_confs: u32,
+        cleanup_spends: bool,
+        gap_limit: u32,
+        zmq_block_addr: Option<net::SocketAddr>,
     ) -> Poller {
         let shutdown = sync::Arc::from(atomic::AtomicBool::from(false));
         let handle = thread::Builder::new()
             .name("Bitcoin poller".to_string())
             .spawn({
                 let shutdown = shutdown.clone();
-                move || looper(bit, db, shutdown, poll_interval, desc)
+                move || {
+                    looper(
+                        bit,
+                        db,
+                        shutdown,
+                        poll_interval,
+                        desc,
+                        final_spend_confs,
+                        cleanup_spends,
+                        gap_limit,
+                        zmq_block_addr,
+                    )
+                }
             })
             .expect("Must not fail");
 
@@ -0,0 +1,315 @@
+//! A minimal ZMQ subscriber to bitcoind's block notifications.
+//!
+//! It speaks just enough of the ZMTP 3.0 protocol (NULL security mechanism, SUB socket) to
+//! receive the `hashblock` and `rawblock` messages published by bitcoind, without depending on
+//! libzmq.
+
+use std::{
+    io::{self, Read, Write},
+    net,
+    sync::{self, atomic},
+    thread, time,
+};
+
+/// The topics of the notifications of a new block. Either may be enabled on bitcoind.
+const BLOCK_TOPICS: [&[u8]; 2] = [b"hashblock", b"rawblock"];
+
+/// How long to wait before trying to connect again after the connection was lost.
+const RECONNECT_INTERVAL: time::Duration = time::Duration::from_secs(10);
+
+/// How often the notifier thread checks whether it must shut down while waiting for a message.
+const READ_TIMEOUT: time::Duration = time::Duration::from_millis(500);
+
+// Flags of a ZMTP frame.
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// Listens for notifications of new blocks in a background thread.
+pub struct BlockNotifier {
+    handle: thread::JoinHandle<()>,
+    new_block: sync::Arc<atomic::AtomicBool>,
+    connected: sync::Arc<atomic::AtomicBool>,
+}
+
+impl BlockNotifier {
+    pub fn start(addr: net::SocketAddr, shutdown: sync::Arc<atomic::AtomicBool>) -> BlockNotifier {
+        let new_block = sync::Arc::from(atomic::AtomicBool::from(false));
+        let connected = sync::Arc::from(atomic::AtomicBool::from(false));
+        let handle = thread::Builder::new()
+            .name("Block notifier".to_string())
+            .spawn({
+                let (new_block, connected) = (new_block.clone(), connected.clone());
+                move || notifier(addr, shutdown, new_block, connected)
+            })
+            .expect("Must not fail");
+
+        BlockNotifier {
+            handle,
+            new_block,
+            connected,
+        }
+    }
+
+    /// Whether a block was announced since the last call.
+    pub fn new_block(&self) -> bool {
+        self.new_block.swap(false, atomic::Ordering::Relaxed)
+    }
+
+    /// Whether we are currently subscribed to the notifications.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Wait for the notifier thread to exit, once the shutdown was signaled.
+    pub fn join(self) {
+        self.handle
+            .join()
+            .expect("The block notifier must not fail");
+    }
+}
+
+fn notifier(
+    addr: net::SocketAddr,
+    shutdown: sync::Arc<atomic::AtomicBool>,
+    new_block: sync::Arc<atomic::AtomicBool>,
+    connected: sync::Arc<atomic::AtomicBool>,
+) {
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        match subscribe(addr) {
+            Ok(mut stream) => {
+                log::info!("Subscribed to block notifications at '{}'.", addr);
+                // We may have missed a block while we were not connected.
+                new_block.store(true, atomic::Ordering::Relaxed);
+                connected.store(true, atomic::Ordering::Relaxed);
+                if let Err(e) = listen(&mut stream, &shutdown, &new_block) {
+                    log::warn!(
+                        "Lost connection to the block notifications at '{}': {}. Falling back \
+                         to polling.",
+                        addr,
+                        e
+                    );
+                }
+                connected.store(false, atomic::Ordering::Relaxed);
+            }
+            Err(e) => log::debug!(
+                "Could not subscribe to block notifications at '{}': {}",
+                addr,
+                e
+            ),
+        }
+
+        // Wait before trying again, while still honouring a shutdown request.
+        let now = time::Instant::now();
+        while !shutdown.load(atomic::Ordering::Relaxed) && now.elapsed() < RECONNECT_INTERVAL {
+            thread::sleep(READ_TIMEOUT);
+        }
+    }
+}
+
+/// Connect to the publisher, go through the ZMTP handshake and subscribe to the block topics.
+fn subscribe(addr: net::SocketAddr) -> io::Result<net::TcpStream> {
+    let mut stream = net::TcpStream::connect_timeout(&addr, RECONNECT_INTERVAL)?;
+    stream.set_read_timeout(Some(RECONNECT_INTERVAL))?;
+
+    stream.write_all(&greeting())?;
+    let mut peer_greeting = [0; 64];
+    stream.read_exact(&mut peer_greeting)?;
+    if peer_greeting[0] != 0xff || peer_greeting[9] != 0x7f || peer_greeting[10] < 3 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a ZMTP 3 publisher",
+        ));
+    }
+
+    // The READY command announcing our socket type.
+    let mut ready = vec![5];
+    ready.extend_from_slice(b"READY");
+    ready.push(11);
+    ready.extend_from_slice(b"Socket-Type");
+    ready.extend_from_slice(&3u32.to_be_bytes());
+    ready.extend_from_slice(b"SUB");
+    write_frame(&mut stream, FLAG_COMMAND, &ready)?;
+    let (flags, _) = read_frame(&mut stream)?;
+    if flags & FLAG_COMMAND == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Expected a READY command",
+        ));
+    }
+
+    // With ZMTP 3.0 a subscription is a message starting with 0x01.
+    for topic in BLOCK_TOPICS.iter() {
+        let mut subscription = vec![1];
+        subscription.extend_from_slice(topic);
+        write_frame(&mut stream, 0, &subscription)?;
+    }
+
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Read the published messages until the shutdown or an error.
+fn listen(
+    stream: &mut net::TcpStream,
+    shutdown: &atomic::AtomicBool,
+    new_block: &atomic::AtomicBool,
+) -> io::Result<()> {
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        // Wait for the start of the next frame without blocking the shutdown.
+        let mut flags = [0];
+        match stream.read(&mut flags) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed",
+                ))
+            }
+            Ok(_) => {}
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+
+        // Once a message started, read it entirely. Its first frame is the topic.
+        stream.set_read_timeout(Some(RECONNECT_INTERVAL))?;
+        let (mut flags, topic) = read_frame_body(stream, flags[0])?;
+        while flags & FLAG_MORE != 0 {
+            flags = read_frame(stream)?.0;
+        }
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        if flags & FLAG_COMMAND == 0 && BLOCK_TOPICS.contains(&topic.as_slice()) {
+            log::debug!("Notified of a new block.");
+            new_block.store(true, atomic::Ordering::Relaxed);
+        }
+    }
+
+    Ok(())
+}
+
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    // Version 3.0.
+    greeting[10] = 3;
+    greeting[11] = 0;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+fn write_frame(stream: &mut impl Write, flags: u8, body: &[u8]) -> io::Result<()> {
+    if body.len() > u8::MAX as usize {
+        stream.write_all(&[flags | FLAG_LONG])?;
+        stream.write_all(&(body.len() as u64).to_be_bytes())?;
+    } else {
+        stream.write_all(&[flags, body.len() as u8])?;
+    }
+    stream.write_all(body)
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0];
+    stream.read_exact(&mut flags)?;
+    read_frame_body(stream, flags[0])
+}
+
+fn read_frame_body(stream: &mut impl Read, flags: u8) -> io::Result<(u8, Vec<u8>)> {
+    let size = if flags & FLAG_LONG != 0 {
+        let mut size = [0; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size)
+    } else {
+        let mut size = [0];
+        stream.read_exact(&mut size)?;
+        size[0] as u64
+    };
+    let mut body = Vec::new();
+    stream.by_ref().take(size).read_to_end(&mut body)?;
+    if body.len() as u64 != size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Truncated frame",
+        ));
+    }
+    Ok((flags, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A publisher sending a block notification to the first subscriber, like bitcoind would.
+    fn publisher(listener: net::TcpListener) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut peer_greeting = [0; 64];
+        stream.read_exact(&mut peer_greeting).unwrap();
+        assert_eq!(&peer_greeting[12..16], b"NULL");
+        stream.write_all(&greeting()).unwrap();
+
+        let (flags, ready) = read_frame(&mut stream).unwrap();
+        assert_eq!(flags, FLAG_COMMAND);
+        assert!(ready.ends_with(b"SUB"));
+        let mut ready = vec![5];
+        ready.extend_from_slice(b"READY");
+        ready.push(11);
+        ready.extend_from_slice(b"Socket-Type");
+        ready.extend_from_slice(&3u32.to_be_bytes());
+        ready.extend_from_slice(b"PUB");
+        write_frame(&mut stream, FLAG_COMMAND, &ready).unwrap();
+        for topic in BLOCK_TOPICS.iter() {
+            let (_, subscription) = read_frame(&mut stream).unwrap();
+            assert_eq!(&subscription[1..], *topic);
+        }
+
+        // Another topic is not a block notification.
+        write_frame(&mut stream, FLAG_MORE, b"hashtx").unwrap();
+        write_frame(&mut stream, FLAG_MORE, &[0; 32]).unwrap();
+        write_frame(&mut stream, 0, &0u32.to_le_bytes()).unwrap();
+        thread::sleep(time::Duration::from_secs(1));
+
+        write_frame(&mut stream, FLAG_MORE, b"rawblock").unwrap();
+        write_frame(&mut stream, FLAG_MORE, &[0; 300]).unwrap();
+        write_frame(&mut stream, 0, &1u32.to_le_bytes()).unwrap();
+        thread::sleep(time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn block_notifier() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let publisher = thread::spawn(move || publisher(listener));
+        let shutdown = sync::Arc::from(atomic::AtomicBool::from(false));
+        let notifier = BlockNotifier::start(addr, shutdown.clone());
+
+        // Upon connection a poll is triggered, as blocks may have been missed.
+        while !notifier.is_connected() {
+            thread::sleep(time::Duration::from_millis(50));
+        }
+        assert!(notifier.new_block());
+        thread::sleep(time::Duration::from_millis(500));
+        assert!(!notifier.new_block());
+
+        // Then the block notification.
+        let now = time::Instant::now();
+        while !notifier.new_block() {
+            assert!(now.elapsed() < time::Duration::from_secs(5));
+            thread::sleep(time::Duration::from_millis(50));
+        }
+
+        // The publisher disconnects.
+        publisher.join().unwrap();
+        let now = time::Instant::now();
+        while notifier.is_connected() {
+            assert!(now.elapsed() < time::Duration::from_secs(5));
+            thread::sleep(time::Duration::from_millis(50));
+        }
+
+        shutdown.store(true, atomic::Ordering::Relaxed);
+        notifier.join();
+    }
+}
@@ -4,10 +4,12 @@
 
 mod utils;
 
+pub(crate) use utils::DerivedDescCache;
+
 use crate::{
     bitcoin::BitcoinInterface,
-    database::{Coin, CoinType, DatabaseInterface},
-    descriptors, DaemonControl, VERSION,
+    database::{obsolete_spends, Coin, CoinType, DatabaseConnection, DatabaseInterface},
+    descriptors, random, DaemonControl, VERSION,
 };
 
 use utils::{
@@ -16,6 +18,7 @@ use utils::{
 };
 
 use std::{
+    cmp,
     collections::{hash_map, BTreeMap, HashMap},
     convert::TryInto,
     fmt,
@@ -23,8 +26,11 @@ use std::{
 
 use miniscript::{
     bitcoin::{
-        self,
-        util::psbt::{Input as PsbtIn, Output as PsbtOut, PartiallySignedTransaction as Psbt},
+        self, secp256k1,
+        util::{
+            bip32,
+            psbt::{Input as PsbtIn, Output as PsbtOut, PartiallySignedTransaction as Psbt},
+        },
     },
     psbt::PsbtExt,
 };
@@ -40,16 +46,42 @@ const MAX_FEE: u64 = bitcoin::blockdata::constants::COIN_VALUE;
 // Assume that paying more than 1000sat/vb in feerate is a bug.
 const MAX_FEERATE: u64 = 1_000;
 
+// The minimum feerate increment, in sats/vb, for a replacement transaction to be relayed. Same as
+// bitcoind's default incremental relay fee.
+const INCREMENTAL_RELAY_FEERATE: u64 = 1;
+
 // Timestamp in the header of the genesis block. Used for sanity checks.
 const MAINNET_GENESIS_TIME: u32 = 1231006505;
 
+// How many derivation indexes past the last one we handed out to look for coins when scanning the
+// UTxO set. Same as the range we import the descriptors with in the watchonly wallet.
+const UTXO_SET_SCAN_LOOKAHEAD: u32 = 1_000;
+
+// The largest confirmation target bitcoind gives a feerate estimate for.
+const MAX_CONF_TARGET: u16 = 1_008;
+
+/// Where to send the change of a Spend transaction, if there is any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeDestination {
+    /// A new address derived at the next change index.
+    Auto,
+    /// An address of ours, for instance to reuse the last change address.
+    Address(bitcoin::Address),
+    /// Don't create a change output, leave the excess to the fee.
+    NoChange,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CommandError {
     NoOutpoint,
     NoDestination,
     InvalidFeerate(/* sats/vb */ u64),
+    /// The feerate is below the minimum accepted in the mempool of the Bitcoin backend.
+    FeerateBelowMempoolMin(/* sats/vb */ u64, /* minimum sats/vb */ u64),
     UnknownOutpoint(bitcoin::OutPoint),
     AlreadySpent(bitcoin::OutPoint),
+    /// The coin is a coinbase output that did not reach maturity yet.
+    ImmatureCoinbase(bitcoin::OutPoint),
     AddressNetwork(bitcoin::Address, /* Expected */ bitcoin::Network),
     InvalidOutputValue(bitcoin::Amount),
     InsufficientFunds(
@@ -66,9 +98,46 @@ pub enum CommandError {
     TxBroadcast(String),
     AlreadyRescanning,
     InsaneRescanTimestamp(u32),
+    /// The block height to rescan from must be above 0 and at most the height of the tip.
+    InsaneRescanHeight(i32),
     /// An error that might occur in the racy rescan triggering logic.
     RescanTrigger(String),
     RecoveryNotAvailable,
+    /// The descriptor has no recovery path with this timelock.
+    UnknownRecoveryPath(u16),
+    /// The recovery path with this timelock also requires an absolute timelock, not reached yet.
+    RecoveryLocked(u16, bitcoin::LockTime),
+    /// An error when scanning the UTxO set for our coins.
+    UtxoSetScan(String),
+    /// A confirmation target for a feerate estimate must be between 1 and 1008 blocks.
+    InvalidConfTarget(u16),
+    /// An error when querying a feerate estimate from the Bitcoin backend.
+    FeeEstimation(String),
+    /// The transaction has an input with a relative timelock but its version does not
+    /// enforce it.
+    RelativeTimelockTxVersion(/* tx version */ i32),
+    /// The command is only available on regtest.
+    RegtestOnly(/* our network */ bitcoin::Network),
+    /// An error when mining blocks on regtest.
+    BlockGeneration(String),
+    /// An error when querying the labels of the Bitcoin backend's wallet.
+    BackendLabels(String),
+    /// The transaction to be replaced or bumped is already confirmed.
+    AlreadyConfirmed(bitcoin::Txid),
+    /// The replacement feerate must be higher than the feerate of the replaced transaction plus
+    /// the incremental relay feerate.
+    RbfFeerateTooLow(/* sats/vb */ u64, /* minimum sats/vb */ u64),
+    /// The Spend transaction to be replaced has no change output to deduct the additional fee
+    /// from, or its value would fall below the dust limit.
+    RbfNoChange(bitcoin::Txid),
+    /// The change address is not derived from our descriptor.
+    ChangeAddressNotOurs(bitcoin::Address),
+    /// No coin's recovery path becomes available within this number of blocks.
+    NothingToRefresh(/* threshold */ u32),
+    /// An error when getting randomness to seed the coin selection.
+    Randomness(String),
+    /// The Bitcoin backend could not be queried.
+    BitcoinBackend(String),
 }
 
 impl fmt::Display for CommandError {
@@ -77,7 +146,17 @@ impl fmt::Display for CommandError {
             Self::NoOutpoint => write!(f, "No provided outpoint. Need at least one."),
             Self::NoDestination => write!(f, "No provided destination. Need at least one."),
             Self::InvalidFeerate(sats_vb) => write!(f, "Invalid feerate: {} sats/vb.", sats_vb),
+            Self::FeerateBelowMempoolMin(sats_vb, min) => write!(
+                f,
+                "Feerate {} sats/vb is below the minimum of {} sats/vb currently accepted in the mempool.",
+                sats_vb, min
+            ),
             Self::AlreadySpent(op) => write!(f, "Coin at '{}' is already spent.", op),
+            Self::ImmatureCoinbase(op) => write!(
+                f,
+                "Coin at '{}' is from a coinbase transaction and isn't mature yet.",
+                op
+            ),
             Self::UnknownOutpoint(op) => write!(f, "Unknown outpoint '{}'.", op),
             Self::AddressNetwork(addr, expected) => write!(
                 f,
@@ -121,11 +200,75 @@ impl fmt::Display for CommandError {
                 "There is already a rescan ongoing. Please wait for it to complete first."
             ),
             Self::InsaneRescanTimestamp(t) => write!(f, "Insane timestamp '{}'.", t),
+            Self::InsaneRescanHeight(h) => write!(f, "Insane block height '{}'.", h),
             Self::RescanTrigger(s) => write!(f, "Error while starting rescan: '{}'", s),
             Self::RecoveryNotAvailable => write!(
                 f,
                 "No coin currently available through the timelocked recovery path."
             ),
+            Self::RecoveryLocked(t, locktime) => match locktime {
+                bitcoin::LockTime::Blocks(height) => write!(
+                    f,
+                    "The recovery path with a timelock of {} blocks is only available after block {}.",
+                    t, height
+                ),
+                bitcoin::LockTime::Seconds(time) => write!(
+                    f,
+                    "The recovery path with a timelock of {} blocks is only available after timestamp {}.",
+                    t, time
+                ),
+            },
+            Self::UnknownRecoveryPath(t) => write!(
+                f,
+                "There is no recovery path with a timelock of {} blocks in our descriptor.",
+                t
+            ),
+            Self::UtxoSetScan(e) => write!(f, "Error while scanning the UTxO set: '{}'", e),
+            Self::InvalidConfTarget(target) => write!(
+                f,
+                "Invalid confirmation target: {}. It must be between 1 and {} blocks.",
+                target, MAX_CONF_TARGET
+            ),
+            Self::FeeEstimation(e) => write!(f, "Error while estimating the feerate: '{}'", e),
+            Self::RelativeTimelockTxVersion(version) => write!(
+                f,
+                "Transaction version {} does not enforce relative timelocks but one of its inputs uses one. The version must be at least 2.",
+                version
+            ),
+            Self::RegtestOnly(network) => write!(
+                f,
+                "This command is only available on regtest, but we are running on '{}'.",
+                network
+            ),
+            Self::BlockGeneration(e) => write!(f, "Error while generating blocks: '{}'", e),
+            Self::BackendLabels(e) => {
+                write!(f, "Error while querying the labels of the Bitcoin backend: '{}'", e)
+            }
+            Self::AlreadyConfirmed(txid) => {
+                write!(f, "Transaction '{}' is already confirmed.", txid)
+            }
+            Self::RbfFeerateTooLow(sats_vb, min) => write!(
+                f,
+                "Feerate {} sats/vb is too low to replace the transaction. It must be at least {} sats/vb.",
+                sats_vb, min
+            ),
+            Self::RbfNoChange(txid) => write!(
+                f,
+                "Transaction '{}' has no change output large enough to pay for the additional fee.",
+                txid
+            ),
+            Self::ChangeAddressNotOurs(addr) => {
+                write!(f, "Change address '{}' is not one of ours.", addr)
+            }
+            Self::NothingToRefresh(threshold) => write!(
+                f,
+                "No coin's recovery path becomes available within {} blocks.",
+                threshold
+            ),
+            Self::Randomness(e) => write!(f, "Error while getting randomness: '{}'", e),
+            Self::BitcoinBackend(e) => {
+                write!(f, "Error while querying the Bitcoin backend: '{}'", e)
+            }
         }
     }
 }
@@ -152,11 +295,49 @@ pub enum InsaneFeeInfo {
     TooHighFeerate(u64),
 }
 
+// The relative timelocks set in the nSequence of the inputs are only enforced for transactions
+// of version 2 and above (BIP68). A recovery transaction of a lower version would be invalid.
+fn enforces_relative_timelocks(tx: &bitcoin::Transaction) -> bool {
+    tx.version >= 2
+        || !tx
+            .input
+            .iter()
+            .any(|txin| txin.sequence.is_relative_lock_time())
+}
+
+// Get the size in vbytes of the satisfactions of all the inputs of this transaction. They spend
+// through the primary path unless their nSequence enables a recovery path, in which case the
+// largest satisfaction among the enabled recovery paths is assumed.
+fn tx_sat_vbytes(
+    spent_desc: &descriptors::MultipathDescriptor,
+    tx: &bitcoin::Transaction,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> usize {
+    let sequence = match tx.input.first() {
+        Some(txin) => txin.sequence,
+        None => return 0,
+    };
+    let sat_vb = if sequence.is_height_locked() {
+        spent_desc
+            .info()
+            .recovery_paths()
+            .keys()
+            .filter(|timelock| sequence.0 >= **timelock as u32)
+            .filter_map(|timelock| spent_desc.path_sat_vbytes(Some(*timelock), secp))
+            .max()
+    } else {
+        spent_desc.path_sat_vbytes(None, secp)
+    };
+    // A relative timelock which doesn't enable any recovery path: assume the worst.
+    sat_vb.unwrap_or_else(|| spent_desc.max_sat_vbytes()) * tx.input.len()
+}
+
 // Apply some sanity checks on a created transaction's PSBT.
 // TODO: add more sanity checks from revault_tx
 fn sanity_check_psbt(
     spent_desc: &descriptors::MultipathDescriptor,
     psbt: &Psbt,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
 ) -> Result<(), CommandError> {
     let tx = &psbt.unsigned_tx;
 
@@ -165,11 +346,16 @@ fn sanity_check_psbt(
         return Err(CommandError::SanityCheckFailure(psbt.clone()));
     }
 
+    // The timelock of a recovery input must be enforceable.
+    if !enforces_relative_timelocks(tx) {
+        return Err(CommandError::SanityCheckFailure(psbt.clone()));
+    }
+
     // Compute the transaction input value, checking all PSBT inputs have the derivation
     // index set for signing devices to recognize them as ours.
     let mut value_in = 0;
     for psbtin in psbt.inputs.iter() {
-        if psbtin.bip32_derivation.is_empty() {
+        if psbtin.bip32_derivation.is_empty() && psbtin.tap_key_origins.is_empty() {
             return Err(CommandError::SanityCheckFailure(psbt.clone()));
         }
         value_in += psbtin
@@ -189,7 +375,7 @@ fn sanity_check_psbt(
     }
 
     // Check the feerate isn't insane.
-    let tx_vb = (tx.vsize() + spent_desc.max_sat_vbytes() * tx.input.len()) as u64;
+    let tx_vb = (tx.vsize() + tx_sat_vbytes(spent_desc, tx, secp)) as u64;
     let feerate_sats_vb = abs_fee
         .checked_div(tx_vb)
         .ok_or_else(|| CommandError::InsaneFees(InsaneFeeInfo::InvalidFeerate))?;
@@ -215,14 +401,27 @@ fn serializable_size<T: bitcoin::consensus::Encodable + ?Sized>(t: &T) -> u64 {
 }
 
 impl DaemonControl {
-    // Get the derived descriptor for this coin
-    fn derived_desc(&self, coin: &Coin) -> descriptors::DerivedInheritanceDescriptor {
+    // Get the derived descriptor for this coin, from the cache if it was derived already as of
+    // the current tip.
+    fn derived_desc(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        coin: &Coin,
+    ) -> descriptors::DerivedInheritanceDescriptor {
+        let mut cache = self.derived_descs.lock().unwrap();
+        cache.set_tip(db_conn.chain_tip());
+        if let Some(desc) = cache.get(coin.derivation_index, coin.is_change) {
+            return desc;
+        }
+
         let desc = if coin.is_change {
             self.config.main_descriptor.change_descriptor()
         } else {
             self.config.main_descriptor.receive_descriptor()
         };
-        desc.derive(coin.derivation_index, &self.secp)
+        let derived = desc.derive(coin.derivation_index, &self.secp);
+        cache.insert(coin.derivation_index, coin.is_change, derived.clone());
+        derived
     }
 
     // Check whether this address is valid for the network we are operating on.
@@ -244,23 +443,51 @@ impl DaemonControl {
 
 impl DaemonControl {
     /// Get information about the current state of the daemon
-    pub fn get_info(&self) -> GetInfoResult {
+    pub fn get_info(&self) -> Result<GetInfoResult, CommandError> {
         let mut db_conn = self.db.connection();
 
         let block_height = db_conn.chain_tip().map(|tip| tip.height).unwrap_or(0);
-        let rescan_progress = db_conn
-            .rescan_timestamp()
-            .map(|_| self.bitcoin.rescan_progress().unwrap_or(1.0));
-        GetInfoResult {
+        let rescan_progress = match db_conn.rescan_timestamp() {
+            Some(_) => Some(
+                self.bitcoin
+                    .rescan_progress()
+                    .map_err(CommandError::BitcoinBackend)?
+                    .unwrap_or(1.0),
+            ),
+            None => None,
+        };
+        Ok(GetInfoResult {
             version: VERSION.to_string(),
             network: self.config.bitcoin_config.network,
             block_height,
-            sync: self.bitcoin.sync_progress(),
+            sync: self
+                .bitcoin
+                .sync_progress()
+                .map_err(CommandError::BitcoinBackend)?,
             descriptors: GetInfoDescriptors {
                 main: self.config.main_descriptor.clone(),
             },
             rescan_progress,
-        }
+            mempool_min_feerate: self
+                .bitcoin
+                .mempool_min_feerate()
+                .map_err(CommandError::BitcoinBackend)?,
+            addresses: GetInfoAddresses {
+                gap_limit: self.config.bitcoin_config.gap_limit,
+                receive_index: db_conn.receive_index().into(),
+                change_index: db_conn.change_index().into(),
+                used_receive_index: db_conn
+                    .coins(CoinType::All)
+                    .values()
+                    .filter(|coin| !coin.is_change)
+                    .map(|coin| u32::from(coin.derivation_index))
+                    .max(),
+                watched_range_end: self
+                    .bitcoin
+                    .watched_range_end(&self.config.main_descriptor)
+                    .map_err(CommandError::BitcoinBackend)?,
+            },
+        })
     }
 
     /// Get a new deposit address. This will always generate a new deposit address, regardless of
@@ -272,18 +499,28 @@ impl DaemonControl {
             .increment()
             .expect("Can't get into hardened territory");
         db_conn.set_receive_index(new_index, &self.secp);
-        let address = self
-            .config
-            .main_descriptor
-            .receive_descriptor()
-            .derive(index, &self.secp)
-            .address(self.config.bitcoin_config.network);
-        GetAddressResult { address }
+        // The address was already derived when populating the addresses mapping up to the
+        // look-ahead limit, only derive it again if it's missing.
+        let address = db_conn
+            .address_by_derivation_index(index, false)
+            .unwrap_or_else(|| {
+                self.config
+                    .main_descriptor
+                    .receive_descriptor()
+                    .derive(index, &self.secp)
+                    .address(self.config.bitcoin_config.network)
+            });
+        GetAddressResult {
+            address,
+            derivation_index: index,
+        }
     }
 
     /// Get a list of all known coins.
     pub fn list_coins(&self) -> ListCoinsResult {
         let mut db_conn = self.db.connection();
+        let tip_height = db_conn.chain_tip().map(|tip| tip.height).unwrap_or(0);
+        let final_spend_confs = self.config.bitcoin_config.final_spend_confirmations;
         #[allow(clippy::iter_kv_map)] // Because Rust 1.48
         let coins: Vec<ListCoinsEntry> = db_conn
             .coins(CoinType::All)
@@ -296,48 +533,44 @@ impl DaemonControl {
                     block_height,
                     spend_txid,
                     spend_block,
+                    replaced_spend_txid,
                     ..
                 } = coin;
+                let is_immature = coin.is_immature(tip_height);
+                let is_replaceable = coin.is_replaceable();
+                let settled = coin.is_spend_final(tip_height, final_spend_confs);
                 let spend_info = spend_txid.map(|txid| LCSpendInfo {
                     txid,
                     height: spend_block.map(|b| b.height),
+                    settled,
+                    replaced_txid: replaced_spend_txid,
                 });
                 ListCoinsEntry {
                     amount,
                     outpoint,
                     block_height,
                     spend_info,
+                    is_immature,
+                    is_replaceable,
                 }
             })
             .collect();
         ListCoinsResult { coins }
     }
 
-    pub fn create_spend(
+    // Create the transaction inputs spending these coins, along with the PSBT inputs populated
+    // with the information needed by signers. Also returns the total value of the coins.
+    fn spend_inputs(
         &self,
-        destinations: &HashMap<bitcoin::Address, u64>,
+        db_conn: &mut Box<dyn DatabaseConnection>,
         coins_outpoints: &[bitcoin::OutPoint],
-        feerate_vb: u64,
-    ) -> Result<CreateSpendResult, CommandError> {
-        if coins_outpoints.is_empty() {
-            return Err(CommandError::NoOutpoint);
-        }
-        if destinations.is_empty() {
-            return Err(CommandError::NoDestination);
-        }
-        if feerate_vb < 1 {
-            return Err(CommandError::InvalidFeerate(feerate_vb));
-        }
-        let mut db_conn = self.db.connection();
-
-        // Iterate through given outpoints to fetch the coins (hence checking their existence
-        // at the same time). We checked there is at least one, therefore after this loop the
-        // list of coins is not empty.
-        // While doing so, we record the total input value of the transaction to later compute
-        // fees, and add necessary information to the PSBT inputs.
+    ) -> Result<(Vec<bitcoin::TxIn>, Vec<PsbtIn>, bitcoin::Amount), CommandError> {
+        let current_height = self
+            .bitcoin
+            .chain_tip()
+            .map_err(CommandError::BitcoinBackend)?
+            .height;
         let mut in_value = bitcoin::Amount::from_sat(0);
-        let txin_sat_vb = self.config.main_descriptor.max_sat_vbytes();
-        let mut sat_vb = 0;
         let mut txins = Vec::with_capacity(coins_outpoints.len());
         let mut psbt_ins = Vec::with_capacity(coins_outpoints.len());
         let mut spent_txs = HashMap::with_capacity(coins_outpoints.len());
@@ -348,11 +581,15 @@ impl DaemonControl {
             if coin.is_spent() {
                 return Err(CommandError::AlreadySpent(*op));
             }
+            if coin.is_immature(current_height) {
+                return Err(CommandError::ImmatureCoinbase(*op));
+            }
             // Fetch the transaction that created it if necessary
             if !spent_txs.contains_key(op) {
                 let tx = self
                     .bitcoin
                     .wallet_transaction(&op.txid)
+                    .map_err(CommandError::BitcoinBackend)?
                     .ok_or(CommandError::FetchingTransaction(*op))?;
                 spent_txs.insert(*op, tx.0);
             }
@@ -366,23 +603,253 @@ impl DaemonControl {
             });
 
             // Populate the PSBT input with the information needed by signers.
-            let coin_desc = self.derived_desc(coin);
-            sat_vb += txin_sat_vb;
-            let witness_script = Some(coin_desc.witness_script());
+            let coin_desc = self.derived_desc(db_conn, coin);
             let witness_utxo = Some(bitcoin::TxOut {
                 value: coin.amount.to_sat(),
                 script_pubkey: coin_desc.script_pubkey(),
             });
             let non_witness_utxo = spent_txs.get(op).cloned();
-            let bip32_derivation = coin_desc.bip32_derivations();
-            psbt_ins.push(PsbtIn {
-                witness_script,
+            let mut psbt_in = PsbtIn {
                 witness_utxo,
-                bip32_derivation,
                 non_witness_utxo,
                 ..PsbtIn::default()
+            };
+            coin_desc.update_psbt_in(&mut psbt_in);
+            psbt_ins.push(psbt_in);
+        }
+
+        Ok((txins, psbt_ins, in_value))
+    }
+
+    // The size in vbytes of the satisfaction of an input spent through the primary path.
+    fn primary_sat_vbytes(&self) -> usize {
+        self.config
+            .main_descriptor
+            .path_sat_vbytes(None, &self.secp)
+            .expect("There is always a primary path")
+    }
+
+    // The PSBT output paying to this address. If it is one of ours, the BIP32 derivation paths
+    // are set to signal it as change to signing devices.
+    fn psbt_output(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        address: &bitcoin::Address,
+    ) -> PsbtOut {
+        let mut psbt_out = PsbtOut::default();
+        if let Some((index, is_change)) = db_conn.derivation_index_by_address(address) {
+            let desc = if is_change {
+                self.config.main_descriptor.change_descriptor()
+            } else {
+                self.config.main_descriptor.receive_descriptor()
+            };
+            desc.derive(index, &self.secp)
+                .update_psbt_out(&mut psbt_out);
+        }
+        psbt_out
+    }
+
+    /// Select coins to pay for these destinations at this feerate, using the Random-Improve
+    /// algorithm over our confirmed unspent coins. The outpoints returned can be passed to
+    /// [`DaemonControl::create_spend`].
+    ///
+    /// The selection is randomized. A `seed` can be given to make it reproducible, otherwise it is
+    /// drawn from a secure source of randomness.
+    pub fn select_coins(
+        &self,
+        destinations: &HashMap<bitcoin::Address, u64>,
+        feerate_vb: u64,
+        seed: Option<u64>,
+    ) -> Result<SelectCoinsResult, CommandError> {
+        if destinations.is_empty() {
+            return Err(CommandError::NoDestination);
+        }
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let mut db_conn = self.db.connection();
+        let current_height = self
+            .bitcoin
+            .chain_tip()
+            .map_err(CommandError::BitcoinBackend)?
+            .height;
+
+        // The value to be covered by the coins is the value of the outputs plus the fee for a
+        // transaction without any input but with a change output.
+        let mut out_value = bitcoin::Amount::from_sat(0);
+        let mut txouts = Vec::with_capacity(destinations.len() + 1);
+        for (address, value_sat) in destinations {
+            self.validate_address(address)?;
+            let amount = bitcoin::Amount::from_sat(*value_sat);
+            check_output_value(amount)?;
+            out_value = out_value.checked_add(amount).unwrap();
+            txouts.push(bitcoin::TxOut {
+                value: amount.to_sat(),
+                script_pubkey: address.script_pubkey(),
+            });
+        }
+        let change_desc = self
+            .config
+            .main_descriptor
+            .change_descriptor()
+            .derive(db_conn.change_index(), &self.secp);
+        txouts.push(bitcoin::TxOut {
+            value: std::u64::MAX,
+            script_pubkey: change_desc.script_pubkey(),
+        });
+        let tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: Vec::new(),
+            output: txouts,
+        };
+        let base_fee = feerate_vb.checked_mul(tx.vsize() as u64).unwrap();
+        let target = out_value
+            .checked_add(bitcoin::Amount::from_sat(base_fee))
+            .unwrap();
+        // Each coin adds an input and its satisfaction to the transaction.
+        let input_vb =
+            serializable_size(&bitcoin::TxIn::default()) + self.primary_sat_vbytes() as u64;
+        let input_fee = bitcoin::Amount::from_sat(feerate_vb.checked_mul(input_vb).unwrap());
+
+        // Sort the candidates, as the order of the coins fetched from the database is arbitrary,
+        // for a given seed to always lead to the same selection.
+        #[allow(clippy::iter_kv_map)] // Because Rust 1.48
+        let mut candidates: Vec<(bitcoin::OutPoint, bitcoin::Amount)> = db_conn
+            .coins(CoinType::Unspent)
+            .into_iter()
+            .filter(|(_, coin)| coin.block_height.is_some() && !coin.is_immature(current_height))
+            .map(|(outpoint, coin)| (outpoint, coin.amount))
+            .collect();
+        candidates.sort();
+        let in_value = candidates
+            .iter()
+            .fold(bitcoin::Amount::from_sat(0), |acc, (_, amount)| {
+                acc + *amount
             });
+
+        // Never use a fixed seed unless explicitly asked to.
+        let seed = match seed {
+            Some(seed) => seed,
+            None => {
+                let random =
+                    random::random_bytes().map_err(|e| CommandError::Randomness(e.to_string()))?;
+                let mut seed_bytes = [0; 8];
+                seed_bytes.copy_from_slice(&random[..8]);
+                u64::from_le_bytes(seed_bytes)
+            }
+        };
+        let outpoints = utils::random_improve(
+            &candidates,
+            target,
+            input_fee,
+            &mut utils::SelectionRng::from_seed(seed),
+        )
+        .ok_or(CommandError::InsufficientFunds(
+            in_value, out_value, feerate_vb,
+        ))?;
+
+        Ok(SelectCoinsResult { outpoints })
+    }
+
+    pub fn create_spend(
+        &self,
+        destinations: &HashMap<bitcoin::Address, u64>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+        change: &ChangeDestination,
+    ) -> Result<CreateSpendResult, CommandError> {
+        let psbt = self.spend_psbt(destinations, coins_outpoints, feerate_vb, change, false)?;
+        Ok(CreateSpendResult { psbt })
+    }
+
+    /// Get the fee, feerate and virtual size of the transaction [`DaemonControl::create_spend`]
+    /// would create with these parameters, without creating it. Nothing is persisted, in
+    /// particular the change index isn't updated.
+    pub fn preview_spend(
+        &self,
+        destinations: &HashMap<bitcoin::Address, u64>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+        change: &ChangeDestination,
+    ) -> Result<PreviewSpendResult, CommandError> {
+        let psbt = self.spend_psbt(destinations, coins_outpoints, feerate_vb, change, true)?;
+        let tx = &psbt.unsigned_tx;
+
+        // The satisfaction of the inputs depends on the spending path, which is known from the
+        // nSequence of the inputs.
+        let vsize =
+            (tx.vsize() + tx_sat_vbytes(&self.config.main_descriptor, tx, &self.secp)) as u64;
+        let in_value: u64 = psbt
+            .inputs
+            .iter()
+            .filter_map(|psbtin| psbtin.witness_utxo.as_ref().map(|txo| txo.value))
+            .sum();
+        let out_value: u64 = tx.output.iter().map(|txo| txo.value).sum();
+        let fee = in_value
+            .checked_sub(out_value)
+            .expect("Checked by the sanity checks");
+
+        Ok(PreviewSpendResult {
+            fee: bitcoin::Amount::from_sat(fee),
+            feerate_vb: fee.checked_div(vsize).unwrap(),
+            vsize,
+        })
+    }
+
+    // Create the PSBT for a spend. In a dry run the change index isn't updated when a change
+    // output is added.
+    fn spend_psbt(
+        &self,
+        destinations: &HashMap<bitcoin::Address, u64>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+        change: &ChangeDestination,
+        dry_run: bool,
+    ) -> Result<Psbt, CommandError> {
+        if coins_outpoints.is_empty() {
+            return Err(CommandError::NoOutpoint);
+        }
+        if destinations.is_empty() {
+            return Err(CommandError::NoDestination);
+        }
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                min_feerate_vb,
+            ));
         }
+        let mut db_conn = self.db.connection();
+
+        // Never send the change to an address which isn't ours.
+        let change_address_desc = match change {
+            ChangeDestination::Address(address) => {
+                self.validate_address(address)?;
+                let (index, is_change) = db_conn
+                    .derivation_index_by_address(address)
+                    .ok_or_else(|| CommandError::ChangeAddressNotOurs(address.clone()))?;
+                let desc = if is_change {
+                    self.config.main_descriptor.change_descriptor()
+                } else {
+                    self.config.main_descriptor.receive_descriptor()
+                };
+                Some(desc.derive(index, &self.secp))
+            }
+            ChangeDestination::Auto | ChangeDestination::NoChange => None,
+        };
+
+        // Iterate through given outpoints to fetch the coins (hence checking their existence
+        // at the same time). We checked there is at least one, therefore the list of coins is
+        // not empty. We record the total input value of the transaction to later compute fees.
+        let (txins, psbt_ins, in_value) = self.spend_inputs(&mut db_conn, coins_outpoints)?;
+        let sat_vb = self.primary_sat_vbytes() * txins.len();
 
         // Add the destinations outputs to the transaction and PSBT. At the same time record the
         // total output value to later compute fees, and sanity check each output's value.
@@ -402,21 +869,7 @@ impl DaemonControl {
             });
             // If it's an address of ours, signal it as change to signing devices by adding the
             // BIP32 derivation path to the PSBT output.
-            let bip32_derivation =
-                if let Some((index, is_change)) = db_conn.derivation_index_by_address(address) {
-                    let desc = if is_change {
-                        self.config.main_descriptor.change_descriptor()
-                    } else {
-                        self.config.main_descriptor.receive_descriptor()
-                    };
-                    desc.derive(index, &self.secp).bip32_derivations()
-                } else {
-                    Default::default()
-                };
-            psbt_outs.push(PsbtOut {
-                bip32_derivation,
-                ..PsbtOut::default()
-            });
+            psbt_outs.push(self.psbt_output(&mut db_conn, address));
         }
 
         // Now create the transaction, compute its fees and already sanity check if its feerate
@@ -443,20 +896,27 @@ impl DaemonControl {
 
         // If necessary, add a change output. The computation here is a bit convoluted: we infer
         // the needed change value from the target feerate and the size of the transaction *with
-        // an added output* (for the change).
-        if nochange_feerate_vb > feerate_vb {
+        // an added output* (for the change). Unless asked not to, in which case the excess is
+        // left to the fee.
+        if nochange_feerate_vb > feerate_vb && *change != ChangeDestination::NoChange {
             // Get the change address to create a dummy change txo.
-            let change_index = db_conn.change_index();
-            let change_desc = self
-                .config
-                .main_descriptor
-                .change_descriptor()
-                .derive(change_index, &self.secp);
-            // Don't forget to update our next change index!
-            let next_index = change_index
-                .increment()
-                .expect("Must not get into hardened territory");
-            db_conn.set_change_index(next_index, &self.secp);
+            let change_desc = match change_address_desc {
+                Some(desc) => desc,
+                None => {
+                    let change_index = db_conn.change_index();
+                    // Don't forget to update our next change index!
+                    if !dry_run {
+                        let next_index = change_index
+                            .increment()
+                            .expect("Must not get into hardened territory");
+                        db_conn.set_change_index(next_index, &self.secp);
+                    }
+                    self.config
+                        .main_descriptor
+                        .change_descriptor()
+                        .derive(change_index, &self.secp)
+                }
+            };
             let mut change_txo = bitcoin::TxOut {
                 value: std::u64::MAX,
                 script_pubkey: change_desc.script_pubkey(),
@@ -482,10 +942,9 @@ impl DaemonControl {
                     // TODO: shuffle once we have Taproot
                     change_txo.value = change_amount.to_sat();
                     tx.output.push(change_txo);
-                    psbt_outs.push(PsbtOut {
-                        bip32_derivation: change_desc.bip32_derivations(),
-                        ..PsbtOut::default()
-                    });
+                    let mut psbt_out = PsbtOut::default();
+                    change_desc.update_psbt_out(&mut psbt_out);
+                    psbt_outs.push(psbt_out);
                 }
             }
         }
@@ -499,16 +958,172 @@ impl DaemonControl {
             inputs: psbt_ins,
             outputs: psbt_outs,
         };
-        sanity_check_psbt(&self.config.main_descriptor, &psbt)?;
+        sanity_check_psbt(&self.config.main_descriptor, &psbt, &self.secp)?;
         // TODO: maybe check for common standardness rules (max size, ..)?
 
+        Ok(psbt)
+    }
+
+    /// Create a transaction spending all these coins to a single address, without change. The
+    /// value of the output is the value of the coins minus the fee at the given feerate.
+    pub fn create_sweep(
+        &self,
+        address: bitcoin::Address,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, CommandError> {
+        if coins_outpoints.is_empty() {
+            return Err(CommandError::NoOutpoint);
+        }
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                min_feerate_vb,
+            ));
+        }
+        self.validate_address(&address)?;
+        let mut db_conn = self.db.connection();
+
+        let (txins, psbt_ins, in_value) = self.spend_inputs(&mut db_conn, coins_outpoints)?;
+        let sat_vb = self.primary_sat_vbytes() * txins.len();
+        let mut tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0), // TODO: randomized anti fee sniping
+            input: txins,
+            output: vec![bitcoin::TxOut {
+                value: 0,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+
+        // Compute the value of the single output from the size of the final transaction. The
+        // value of an output does not change its size.
+        let tx_vb = (tx.vsize() + sat_vb) as u64;
+        let absolute_fee = bitcoin::Amount::from_sat(tx_vb.checked_mul(feerate_vb).unwrap());
+        let output_value = in_value
+            .checked_sub(absolute_fee)
+            .filter(|v| v.to_sat() >= DUST_OUTPUT_SATS)
+            .ok_or({
+                CommandError::InsufficientFunds(in_value, bitcoin::Amount::from_sat(0), feerate_vb)
+            })?;
+        check_output_value(output_value)?;
+        tx.output[0].value = output_value.to_sat();
+
+        let psbt = Psbt {
+            unsigned_tx: tx,
+            version: 0,
+            xpub: BTreeMap::new(),
+            proprietary: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            inputs: psbt_ins,
+            outputs: vec![self.psbt_output(&mut db_conn, &address)],
+        };
+        sanity_check_psbt(&self.config.main_descriptor, &psbt, &self.secp)?;
+
         Ok(CreateSpendResult { psbt })
     }
 
+    /// Create a transaction spending back to a fresh change address all the confirmed coins
+    /// whose recovery path with the given timelock (the one available first if None) becomes
+    /// available within `threshold` blocks, in order to restart their timelock. The coins are
+    /// consolidated into a single output to minimize the fee. The transaction spends through the
+    /// primary path.
+    pub fn create_refresh(
+        &self,
+        threshold: u32,
+        feerate_vb: u64,
+        timelock: Option<u16>,
+    ) -> Result<CreateSpendResult, CommandError> {
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let desc_info = self.config.main_descriptor.info();
+        let timelock = match timelock {
+            Some(t) => {
+                if !desc_info.recovery_paths().contains_key(&t) {
+                    return Err(CommandError::UnknownRecoveryPath(t));
+                }
+                t
+            }
+            None => desc_info.recovery_path().0,
+        };
+        let min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                min_feerate_vb,
+            ));
+        }
+        let mut db_conn = self.db.connection();
+
+        // The recovery path of a coin is available from the block `timelock` blocks after the
+        // one it was confirmed in. Unconfirmed coins are left alone, their timelock did not
+        // start yet.
+        let current_height = self
+            .bitcoin
+            .chain_tip()
+            .map_err(CommandError::BitcoinBackend)?
+            .height;
+        let timelock = i32::from(timelock);
+        let outpoints: Vec<bitcoin::OutPoint> = db_conn
+            .coins(CoinType::Unspent)
+            .into_iter()
+            .filter(|(_, c)| {
+                c.block_height
+                    .map(|h| {
+                        let remaining = h + timelock - (current_height + 1);
+                        remaining <= 0 || remaining as u32 <= threshold
+                    })
+                    .unwrap_or(false)
+                    && !c.is_spent()
+                    && !c.is_immature(current_height)
+            })
+            .map(|(op, _)| op)
+            .collect();
+        if outpoints.is_empty() {
+            return Err(CommandError::NothingToRefresh(threshold));
+        }
+
+        let change_index = db_conn.change_index();
+        let next_index = change_index
+            .increment()
+            .expect("Must not get into hardened territory");
+        db_conn.set_change_index(next_index, &self.secp);
+        let change_desc = self
+            .config
+            .main_descriptor
+            .change_descriptor()
+            .derive(change_index, &self.secp);
+        drop(db_conn);
+
+        // The inputs of a sweep never enable the recovery path. Signal the output as change to
+        // signing devices.
+        let address = change_desc.address(self.config.bitcoin_config.network);
+        let mut res = self.create_sweep(address, &outpoints, feerate_vb)?;
+        change_desc.update_psbt_out(&mut res.psbt.outputs[0]);
+        Ok(res)
+    }
+
     pub fn update_spend(&self, mut psbt: Psbt) -> Result<(), CommandError> {
         let mut db_conn = self.db.connection();
         let tx = &psbt.unsigned_tx;
 
+        // Don't store a transaction that could never be valid. Its version can't be fixed here
+        // as it would change its txid and invalidate the signatures.
+        if !enforces_relative_timelocks(tx) {
+            return Err(CommandError::RelativeTimelockTxVersion(tx.version));
+        }
+
         // If the transaction already exists in DB, merge the signatures for each input on a best
         // effort basis.
         // We work on the newly provided PSBT, in case its content was updated.
@@ -535,18 +1150,25 @@ impl DaemonControl {
                 psbtin
                     .partial_sigs
                     .extend(db_psbtin.partial_sigs.clone().into_iter());
+                // Under Taproot the cosigners of a script path may sign separately, and the key
+                // path signature may only be present in the stored PSBT.
+                psbtin
+                    .tap_script_sigs
+                    .extend(db_psbtin.tap_script_sigs.clone().into_iter());
+                if psbtin.tap_key_sig.is_none() {
+                    psbtin.tap_key_sig = db_psbtin.tap_key_sig;
+                }
             }
         } else {
-            // If the transaction doesn't exist in DB already, sanity check its inputs.
-            // FIXME: should we allow for external inputs?
+            // If the transaction doesn't exist in DB already, sanity check its inputs. In a
+            // collaborative transaction some of them may belong to someone else, but it must
+            // spend at least one of our coins.
             let outpoints: Vec<bitcoin::OutPoint> =
                 tx.input.iter().map(|txin| txin.previous_output).collect();
             let coins = db_conn.coins_by_outpoints(&outpoints);
-            if coins.len() != outpoints.len() {
-                for op in outpoints {
-                    if coins.get(&op).is_none() {
-                        return Err(CommandError::UnknownOutpoint(op));
-                    }
+            if coins.is_empty() {
+                if let Some(op) = outpoints.first() {
+                    return Err(CommandError::UnknownOutpoint(*op));
                 }
             }
         }
@@ -572,6 +1194,20 @@ impl DaemonControl {
         db_conn.delete_spend(txid);
     }
 
+    /// Delete the stored Spend transactions which were replaced by a transaction which reached the
+    /// configured number of confirmations, and therefore can never be confirmed.
+    pub fn cleanup_spends(&self) -> CleanupSpendsResult {
+        let mut db_conn = self.db.connection();
+        let deleted = obsolete_spends(
+            &mut db_conn,
+            self.config.bitcoin_config.final_spend_confirmations,
+        );
+        for txid in &deleted {
+            db_conn.delete_spend(txid);
+        }
+        CleanupSpendsResult { deleted }
+    }
+
     /// Finalize and broadcast this stored Spend transaction.
     pub fn broadcast_spend(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
         let mut db_conn = self.db.connection();
@@ -598,16 +1234,316 @@ impl DaemonControl {
             .map_err(CommandError::TxBroadcast)
     }
 
-    /// Trigger a rescan of the block chain for transactions involving our main descriptor between
-    /// the given date and the current tip.
-    /// The date must be after the genesis block time and before the current tip blocktime.
-    pub fn start_rescan(&self, timestamp: u32) -> Result<(), CommandError> {
+    /// Create a transaction replacing the given stored Spend transaction, spending the same coins
+    /// to the same destinations at a higher feerate. The additional fee is deducted from the
+    /// change output. The replacement PSBT is returned, not stored.
+    pub fn rbf_psbt(
+        &self,
+        txid: &bitcoin::Txid,
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, CommandError> {
+        let mut db_conn = self.db.connection();
+        let prev_psbt = db_conn
+            .spend_tx(txid)
+            .ok_or(CommandError::UnknownSpend(*txid))?;
+        let prev_tx = &prev_psbt.unsigned_tx;
+
+        // The coins must still be in the mempool for the transaction to be replaceable.
+        let prev_outpoints: Vec<bitcoin::OutPoint> = prev_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let coins = db_conn.coins_by_outpoints(&prev_outpoints);
+        for op in prev_outpoints.iter() {
+            let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+            if coin.spend_block.is_some() {
+                return Err(CommandError::AlreadyConfirmed(*txid));
+            }
+            if coin.spend_txid.map(|spend_txid| spend_txid != *txid) == Some(true) {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+        }
+
+        // Compute the feerate of the transaction to be replaced. The replacement has the same
+        // inputs and outputs, hence the same size. BIP125 requires it to pay at least the fee of
+        // the replaced transaction plus the incremental relay fee for its own size, so we require
+        // its feerate to be strictly higher than the replaced feerate plus the increment. If the
+        // transaction has descendants in the mempool they would be evicted too, so the replacement
+        // must also pay for their fees.
+        let prev_in_value: u64 = prev_psbt
+            .inputs
+            .iter()
+            .filter_map(|psbtin| psbtin.witness_utxo.as_ref().map(|txo| txo.value))
+            .sum();
+        let prev_out_value: u64 = prev_tx.output.iter().map(|txo| txo.value).sum();
+        let prev_fee = prev_in_value
+            .checked_sub(prev_out_value)
+            .ok_or_else(|| CommandError::SanityCheckFailure(prev_psbt.clone()))?;
+        let tx_vb = (prev_tx.vsize()
+            + tx_sat_vbytes(&self.config.main_descriptor, prev_tx, &self.secp))
+            as u64;
+        let replaced_fee = self
+            .bitcoin
+            .mempool_entry(txid)
+            .map_err(CommandError::BitcoinBackend)?
+            .map(|entry| cmp::max(entry.descendant_fees.to_sat(), prev_fee))
+            .unwrap_or(prev_fee);
+        let replaced_feerate_vb = replaced_fee
+            .checked_add(tx_vb - 1)
+            .and_then(|f| f.checked_div(tx_vb))
+            .ok_or_else(|| CommandError::SanityCheckFailure(prev_psbt.clone()))?;
+        let min_feerate_vb = replaced_feerate_vb + INCREMENTAL_RELAY_FEERATE + 1;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::RbfFeerateTooLow(feerate_vb, min_feerate_vb));
+        }
+        let mempool_min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < mempool_min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                mempool_min_feerate_vb,
+            ));
+        }
+        let additional_fee = feerate_vb
+            .checked_mul(tx_vb)
+            .and_then(|f| f.checked_sub(prev_fee))
+            .ok_or(CommandError::InvalidFeerate(feerate_vb))?;
+
+        // Find the change output, that is an output we know the derivation path of and which
+        // is derived from our change descriptor.
+        let change_desc = self.config.main_descriptor.change_descriptor();
+        let change_index = prev_psbt
+            .outputs
+            .iter()
+            .zip(prev_tx.output.iter())
+            .position(|(psbtout, txo)| {
+                psbtout
+                    .bip32_derivation
+                    .values()
+                    .chain(psbtout.tap_key_origins.values().map(|(_, origin)| origin))
+                    .next()
+                    .and_then(|(_, der_path)| der_path.into_iter().last())
+                    .map(|index| {
+                        change_desc.derive(*index, &self.secp).script_pubkey() == txo.script_pubkey
+                    })
+                    == Some(true)
+            });
+        let change_index = change_index.ok_or(CommandError::RbfNoChange(*txid))?;
+
+        // Create the replacement. Clear any signature from the PSBT inputs as they commit to the
+        // replaced transaction, and make sure every input signals for replacement.
+        let mut psbt = prev_psbt.clone();
+        let change_txo = &mut psbt.unsigned_tx.output[change_index];
+        change_txo.value = change_txo
+            .value
+            .checked_sub(additional_fee)
+            .filter(|v| *v >= DUST_OUTPUT_SATS)
+            .ok_or(CommandError::RbfNoChange(*txid))?;
+        for txin in psbt.unsigned_tx.input.iter_mut() {
+            if !txin.sequence.is_rbf() {
+                txin.sequence = bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME;
+            }
+        }
+        for psbtin in psbt.inputs.iter_mut() {
+            psbtin.partial_sigs.clear();
+            psbtin.tap_key_sig = None;
+            psbtin.tap_script_sigs.clear();
+            psbtin.final_script_sig = None;
+            psbtin.final_script_witness = None;
+        }
+        sanity_check_psbt(&self.config.main_descriptor, &psbt, &self.secp)?;
+
+        Ok(CreateSpendResult { psbt })
+    }
+
+    /// Create a transaction spending this unconfirmed coin back to the wallet, paying enough fee
+    /// for the package made of the transaction which created the coin and the child to reach the
+    /// given feerate. The PSBT is returned, not stored.
+    ///
+    /// If the Bitcoin backend tells us about the mempool package of the parent, the child pays for
+    /// all its unconfirmed ancestors. Otherwise only the parent is accounted for, and its fee only
+    /// if all the coins it spends are known to the Bitcoin backend's wallet. Else it is assumed to
+    /// be null, which may lead to overpay.
+    pub fn create_cpfp(
+        &self,
+        outpoint: &bitcoin::OutPoint,
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, CommandError> {
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                min_feerate_vb,
+            ));
+        }
+        let mut db_conn = self.db.connection();
+
+        // The coin must be ours, unspent, and created by a transaction still in the mempool.
+        let coin = db_conn
+            .coins_by_outpoints(&[*outpoint])
+            .remove(outpoint)
+            .ok_or(CommandError::UnknownOutpoint(*outpoint))?;
+        if coin.is_spent() {
+            return Err(CommandError::AlreadySpent(*outpoint));
+        }
+        let (confirmed, expired) = self
+            .bitcoin
+            .confirmed_coins(&[*outpoint])
+            .map_err(CommandError::BitcoinBackend)?;
+        if coin.block_height.is_some() || !confirmed.is_empty() {
+            return Err(CommandError::AlreadyConfirmed(outpoint.txid));
+        }
+        if !expired.is_empty() {
+            return Err(CommandError::UnknownOutpoint(*outpoint));
+        }
+        let (parent_tx, parent_block) = self
+            .bitcoin
+            .wallet_transaction(&outpoint.txid)
+            .map_err(CommandError::BitcoinBackend)?
+            .ok_or(CommandError::FetchingTransaction(*outpoint))?;
+        if parent_block.is_some() {
+            return Err(CommandError::AlreadyConfirmed(outpoint.txid));
+        }
+
+        // Compute the size of the unconfirmed ancestors of the child and the fee they already pay.
+        // Without the mempool entry of the parent, only account for the parent itself and for its
+        // fee if we can get the value of all its inputs.
+        let (ancestors_vb, ancestors_fee) = match self
+            .bitcoin
+            .mempool_entry(&outpoint.txid)
+            .map_err(CommandError::BitcoinBackend)?
+        {
+            Some(entry) => (entry.ancestor_vsize, entry.ancestor_fees.to_sat()),
+            None => {
+                let parent_in_value: Option<u64> = parent_tx
+                    .input
+                    .iter()
+                    .map(|txin| {
+                        let prevout = &txin.previous_output;
+                        self.bitcoin
+                            .wallet_transaction(&prevout.txid)
+                            .ok()
+                            .flatten()
+                            .and_then(|(tx, _)| {
+                                tx.output.get(prevout.vout as usize).map(|o| o.value)
+                            })
+                    })
+                    .sum();
+                let parent_out_value: u64 = parent_tx.output.iter().map(|o| o.value).sum();
+                let parent_fee = parent_in_value
+                    .and_then(|v| v.checked_sub(parent_out_value))
+                    .unwrap_or(0);
+                (parent_tx.vsize() as u64, parent_fee)
+            }
+        };
+
+        // Spend the coin to a new change address.
+        let change_index = db_conn.change_index();
+        let change_desc = self
+            .config
+            .main_descriptor
+            .change_descriptor()
+            .derive(change_index, &self.secp);
+        let coin_desc = self.derived_desc(&mut db_conn, &coin);
+        let mut tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn {
+                previous_output: *outpoint,
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: coin.amount.to_sat(),
+                script_pubkey: change_desc.script_pubkey(),
+            }],
+        };
+
+        // The child must pay for the whole package at the target feerate, minus what the
+        // ancestors already pay. It must at least pay for itself at this feerate.
+        let child_vb = (tx.vsize() + self.primary_sat_vbytes()) as u64;
+        let package_vb = child_vb + ancestors_vb;
+        let child_fee = cmp::max(
+            feerate_vb
+                .checked_mul(package_vb)
+                .ok_or(CommandError::InvalidFeerate(feerate_vb))?
+                .saturating_sub(ancestors_fee),
+            feerate_vb.checked_mul(child_vb).unwrap(),
+        );
+        let change_amount = coin
+            .amount
+            .to_sat()
+            .checked_sub(child_fee)
+            .filter(|v| *v >= DUST_OUTPUT_SATS)
+            .ok_or(CommandError::InsufficientFunds(
+                coin.amount,
+                bitcoin::Amount::from_sat(0),
+                feerate_vb,
+            ))?;
+        check_output_value(bitcoin::Amount::from_sat(change_amount))?;
+        tx.output[0].value = change_amount;
+        // Don't forget to update our next change index!
+        let next_index = change_index
+            .increment()
+            .expect("Must not get into hardened territory");
+        db_conn.set_change_index(next_index, &self.secp);
+
+        let mut psbt_in = PsbtIn {
+            witness_utxo: Some(bitcoin::TxOut {
+                value: coin.amount.to_sat(),
+                script_pubkey: coin_desc.script_pubkey(),
+            }),
+            non_witness_utxo: Some(parent_tx),
+            ..PsbtIn::default()
+        };
+        coin_desc.update_psbt_in(&mut psbt_in);
+        let mut psbt_out = PsbtOut::default();
+        change_desc.update_psbt_out(&mut psbt_out);
+        let psbt = Psbt {
+            unsigned_tx: tx,
+            version: 0,
+            xpub: BTreeMap::new(),
+            proprietary: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            inputs: vec![psbt_in],
+            outputs: vec![psbt_out],
+        };
+        sanity_check_psbt(&self.config.main_descriptor, &psbt, &self.secp)?;
+
+        Ok(CreateSpendResult { psbt })
+    }
+
+    /// Trigger a rescan of the block chain for transactions involving our main descriptor between
+    /// the given date and the current tip.
+    /// The date must be after the genesis block time and before the current tip blocktime.
+    pub fn start_rescan(&self, timestamp: u32) -> Result<(), CommandError> {
         let mut db_conn = self.db.connection();
 
-        if timestamp < MAINNET_GENESIS_TIME || timestamp >= self.bitcoin.tip_time() {
+        if timestamp < MAINNET_GENESIS_TIME
+            || timestamp
+                >= self
+                    .bitcoin
+                    .tip_time()
+                    .map_err(CommandError::BitcoinBackend)?
+        {
             return Err(CommandError::InsaneRescanTimestamp(timestamp));
         }
-        if db_conn.rescan_timestamp().is_some() || self.bitcoin.rescan_progress().is_some() {
+        if db_conn.rescan_timestamp().is_some()
+            || self
+                .bitcoin
+                .rescan_progress()
+                .map_err(CommandError::BitcoinBackend)?
+                .is_some()
+        {
             return Err(CommandError::AlreadyRescanning);
         }
 
@@ -622,71 +1558,272 @@ impl DaemonControl {
         Ok(())
     }
 
+    /// Trigger a rescan of the block chain for transactions involving our main descriptor between
+    /// the block at the given height and the current tip.
+    /// The height must be above 0 and at most the height of the current tip.
+    pub fn start_rescan_from_height(&self, height: i32) -> Result<StartRescanResult, CommandError> {
+        let mut db_conn = self.db.connection();
+
+        if height <= 0
+            || height
+                > self
+                    .bitcoin
+                    .chain_tip()
+                    .map_err(CommandError::BitcoinBackend)?
+                    .height
+        {
+            return Err(CommandError::InsaneRescanHeight(height));
+        }
+        if db_conn.rescan_timestamp().is_some()
+            || self
+                .bitcoin
+                .rescan_progress()
+                .map_err(CommandError::BitcoinBackend)?
+                .is_some()
+        {
+            return Err(CommandError::AlreadyRescanning);
+        }
+
+        // TODO: same race as in start_rescan.
+        let timestamp = self
+            .bitcoin
+            .start_rescan_from_height(&self.config.main_descriptor, height)
+            .map_err(CommandError::RescanTrigger)?;
+        db_conn.set_rescan(timestamp);
+
+        Ok(StartRescanResult { timestamp })
+    }
+
     /// list_confirmed_transactions retrieves a limited list of transactions which occured between two given dates.
     pub fn list_confirmed_transactions(
         &self,
         start: u32,
         end: u32,
         limit: u64,
-    ) -> ListTransactionsResult {
+    ) -> Result<ListTransactionsResult, CommandError> {
         let mut db_conn = self.db.connection();
         let txids = db_conn.list_txids(start, end, limit);
+        self.list_transactions(&txids)
+    }
+
+    /// list_transactions retrieves the transactions with the given txids.
+    pub fn list_transactions(
+        &self,
+        txids: &[bitcoin::Txid],
+    ) -> Result<ListTransactionsResult, CommandError> {
         let transactions = txids
             .iter()
             .filter_map(|txid| {
                 // TODO: batch those calls to the Bitcoin backend
                 // so it can in turn optimize its queries.
-                self.bitcoin
-                    .wallet_transaction(txid)
-                    .map(|(tx, block)| TransactionInfo {
-                        tx,
-                        height: block.map(|b| b.height),
-                        time: block.map(|b| b.time),
-                    })
+                self.bitcoin.wallet_transaction(txid).transpose()
+            })
+            .map(|res| {
+                res.map(|(tx, block)| TransactionInfo {
+                    tx,
+                    height: block.map(|b| b.height),
+                    time: block.map(|b| b.time),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map_err(CommandError::BitcoinBackend)?;
+        Ok(ListTransactionsResult { transactions })
+    }
+
+    /// Compare the balance of the coins we track with the balance of the coins of our descriptor
+    /// in the UTxO set of the Bitcoin backend. This is read-only and only meant as a diagnostic.
+    ///
+    /// Only confirmed coins are considered, as the UTxO set doesn't contain unconfirmed ones.
+    pub fn check_balance(&self) -> Result<CheckBalanceResult, CommandError> {
+        let mut db_conn = self.db.connection();
+        let range_end = cmp::max(
+            u32::from(db_conn.receive_index()),
+            u32::from(db_conn.change_index()),
+        ) + UTXO_SET_SCAN_LOOKAHEAD;
+        // Don't hold on to the Bitcoin backend for the duration of the scan.
+        let scan = self
+            .bitcoin
+            .utxo_set_scan(&self.config.main_descriptor, range_end);
+        let utxo_set_coins: HashMap<bitcoin::OutPoint, bitcoin::Amount> = scan()
+            .map_err(CommandError::UtxoSetScan)?
+            .into_iter()
+            .collect();
+        // A coin whose spending transaction isn't confirmed yet is still part of the UTxO set.
+        let tracked_coins: HashMap<bitcoin::OutPoint, bitcoin::Amount> = db_conn
+            .coins(CoinType::All)
+            .into_iter()
+            .filter_map(|(op, coin)| {
+                if coin.is_confirmed() && coin.spend_block.is_none() {
+                    Some((op, coin.amount))
+                } else {
+                    None
+                }
             })
             .collect();
-        ListTransactionsResult { transactions }
+
+        let tracked_balance = tracked_coins
+            .values()
+            .fold(bitcoin::Amount::from_sat(0), |sum, amount| sum + *amount);
+        let utxo_set_balance = utxo_set_coins
+            .values()
+            .fold(bitcoin::Amount::from_sat(0), |sum, amount| sum + *amount);
+        let mut untracked: Vec<bitcoin::OutPoint> = utxo_set_coins
+            .keys()
+            .filter(|op| !tracked_coins.contains_key(op))
+            .copied()
+            .collect();
+        untracked.sort();
+        let mut missing: Vec<bitcoin::OutPoint> = tracked_coins
+            .keys()
+            .filter(|op| !utxo_set_coins.contains_key(op))
+            .copied()
+            .collect();
+        missing.sort();
+
+        Ok(CheckBalanceResult {
+            tracked_balance,
+            utxo_set_balance,
+            untracked,
+            missing,
+        })
     }
 
-    /// list_transactions retrieves the transactions with the given txids.
-    pub fn list_transactions(&self, txids: &[bitcoin::Txid]) -> ListTransactionsResult {
-        let transactions = txids
+    /// Estimate the feerate, in sats/vb, for a transaction to confirm within each of the given
+    /// number of blocks. An estimate is missing if the Bitcoin backend doesn't have enough data.
+    pub fn estimate_feerate(
+        &self,
+        conf_targets: &[u16],
+    ) -> Result<EstimateFeerateResult, CommandError> {
+        let estimates = conf_targets
             .iter()
-            .filter_map(|txid| {
-                // TODO: batch those calls to the Bitcoin backend
-                // so it can in turn optimize its queries.
-                self.bitcoin
-                    .wallet_transaction(txid)
-                    .map(|(tx, block)| TransactionInfo {
-                        tx,
-                        height: block.map(|b| b.height),
-                        time: block.map(|b| b.time),
-                    })
+            .map(|&conf_target| {
+                if conf_target == 0 || conf_target > MAX_CONF_TARGET {
+                    return Err(CommandError::InvalidConfTarget(conf_target));
+                }
+                let feerate = self
+                    .bitcoin
+                    .estimate_feerate(conf_target)
+                    .map_err(CommandError::FeeEstimation)?;
+                Ok(FeerateEstimate {
+                    conf_target,
+                    feerate,
+                })
             })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(EstimateFeerateResult { estimates })
+    }
+
+    /// Mine this number of blocks paying to a new receive address of the wallet. Only meant for
+    /// testing on regtest, the coinbase outputs need 100 confirmations to be spendable.
+    pub fn generate_blocks(&self, count: u32) -> Result<GenerateBlocksResult, CommandError> {
+        let network = self.config.bitcoin_config.network;
+        if network != bitcoin::Network::Regtest {
+            return Err(CommandError::RegtestOnly(network));
+        }
+
+        let address = self.get_new_address().address;
+        let blockhashes = self
+            .bitcoin
+            .generate_to_address(count, &address)
+            .map_err(CommandError::BlockGeneration)?;
+
+        Ok(GenerateBlocksResult {
+            address,
+            blockhashes,
+        })
+    }
+
+    /// List the labels the wallet of the Bitcoin backend has for the addresses of our descriptor,
+    /// for instance set by a user who managed the watchonly wallet directly from bitcoind.
+    pub fn list_backend_labels(&self) -> Result<ListBackendLabelsResult, CommandError> {
+        let mut db_conn = self.db.connection();
+        let labels = self
+            .bitcoin
+            .address_labels()
+            .map_err(CommandError::BackendLabels)?
+            .into_iter()
+            .filter(|(address, _)| db_conn.derivation_index_by_address(address).is_some())
+            .map(|(address, label)| AddressLabel { address, label })
             .collect();
-        ListTransactionsResult { transactions }
+        Ok(ListBackendLabelsResult { labels })
     }
 
     /// Create a transaction that sweeps all coins whose timelocked recovery path is currently
     /// available to a provided address with the provided feerate.
     ///
+    /// The recovery path is identified by its timelock. If none is given, the recovery path
+    /// available first is used.
+    ///
     /// Note that not all coins may be spendable through the recovery path at the same time.
     pub fn create_recovery(
         &self,
         address: bitcoin::Address,
         feerate_vb: u64,
+        timelock: Option<u16>,
     ) -> Result<CreateRecoveryResult, CommandError> {
         if feerate_vb < 1 {
             return Err(CommandError::InvalidFeerate(feerate_vb));
         }
+        let desc_info = self.config.main_descriptor.info();
+        let csv_value = match timelock {
+            Some(t) => {
+                if !desc_info.recovery_paths().contains_key(&t) {
+                    return Err(CommandError::UnknownRecoveryPath(t));
+                }
+                t
+            }
+            None => desc_info.recovery_path().0,
+        };
+        let min_feerate_vb = self
+            .bitcoin
+            .mempool_min_feerate()
+            .map_err(CommandError::BitcoinBackend)?;
+        if feerate_vb < min_feerate_vb {
+            return Err(CommandError::FeerateBelowMempoolMin(
+                feerate_vb,
+                min_feerate_vb,
+            ));
+        }
         self.validate_address(&address)?;
         let mut db_conn = self.db.connection();
 
+        // The recovery path may also require an absolute timelock, in which case the nLockTime
+        // of the transaction must be set to its value. It must be reached for the transaction to
+        // be valid in the next block.
+        let current_height = self
+            .bitcoin
+            .chain_tip()
+            .map_err(CommandError::BitcoinBackend)?
+            .height;
+        let locktime = desc_info.recovery_locktime(csv_value);
+        if let Some(locktime) = locktime {
+            let is_reached = match locktime {
+                bitcoin::LockTime::Blocks(height) => {
+                    height.to_consensus_u32() <= current_height as u32
+                }
+                // The median time past isn't available from every backend, approximate it with
+                // the timestamp of the tip. The broadcast may fail until the former catches up.
+                bitcoin::LockTime::Seconds(time) => {
+                    let tip_time = self
+                        .bitcoin
+                        .tip_time()
+                        .map_err(CommandError::BitcoinBackend)?;
+                    time.to_consensus_u32() < tip_time
+                }
+            };
+            if !is_reached {
+                return Err(CommandError::RecoveryLocked(csv_value, locktime));
+            }
+        }
+
         // The transaction template. We'll fill-in the inputs afterward.
         let mut psbt = Psbt {
             unsigned_tx: bitcoin::Transaction {
                 version: 2,
-                lock_time: bitcoin::PackedLockTime(0), // TODO: anti-fee sniping
+                // TODO: anti-fee sniping
+                lock_time: locktime
+                    .map(bitcoin::PackedLockTime::from)
+                    .unwrap_or(bitcoin::PackedLockTime(0)),
                 input: Vec::new(),
                 output: vec![bitcoin::TxOut {
                     script_pubkey: address.script_pubkey(),
@@ -702,11 +1839,7 @@ impl DaemonControl {
         };
 
         // Query the coins that we can spend through the recovery path from the database.
-        let current_height = self.bitcoin.chain_tip().height;
-        let desc_timelock = self.config.main_descriptor.timelock_value();
-        let timelock: i32 = desc_timelock
-            .try_into()
-            .expect("Must fit, it's effectively a u16");
+        let timelock: i32 = csv_value.into();
         let sweepable_coins = db_conn
             .coins(CoinType::Unspent)
             .into_iter()
@@ -715,15 +1848,17 @@ impl DaemonControl {
                 c.block_height
                     .map(|h| current_height + 1 >= h + timelock)
                     .unwrap_or(false)
+                    && !c.is_immature(current_height)
             });
 
         // Fill-in the transaction inputs and PSBT inputs information. Record the value
         // that is fed to the transaction while doing so, to compute the fees afterward.
-        let csv_value: u16 = desc_timelock
-            .try_into()
-            .expect("Must fit, it's effectively a u16");
         let mut in_value = bitcoin::Amount::from_sat(0);
-        let txin_sat_vb = self.config.main_descriptor.max_sat_vbytes();
+        let txin_sat_vb = self
+            .config
+            .main_descriptor
+            .path_sat_vbytes(Some(csv_value), &self.secp)
+            .expect("Checked the recovery path exists above");
         let mut sat_vb = 0;
         let mut spent_txs = HashMap::new();
         for (_, coin) in sweepable_coins {
@@ -740,26 +1875,25 @@ impl DaemonControl {
                 let tx = self
                     .bitcoin
                     .wallet_transaction(&coin.outpoint.txid)
+                    .map_err(CommandError::BitcoinBackend)?
                     .ok_or(CommandError::FetchingTransaction(coin.outpoint))?;
                 e.insert(tx.0);
             }
 
-            let coin_desc = self.derived_desc(&coin);
+            let coin_desc = self.derived_desc(&mut db_conn, &coin);
             sat_vb += txin_sat_vb;
-            let witness_script = Some(coin_desc.witness_script());
             let witness_utxo = Some(bitcoin::TxOut {
                 value: coin.amount.to_sat(),
                 script_pubkey: coin_desc.script_pubkey(),
             });
             let non_witness_utxo = spent_txs.get(&coin.outpoint).cloned();
-            let bip32_derivation = coin_desc.bip32_derivations();
-            psbt.inputs.push(PsbtIn {
-                witness_script,
+            let mut psbt_in = PsbtIn {
                 witness_utxo,
                 non_witness_utxo,
-                bip32_derivation,
                 ..PsbtIn::default()
-            });
+            };
+            coin_desc.update_psbt_in(&mut psbt_in);
+            psbt.inputs.push(psbt_in);
         }
 
         // The sweepable_coins iterator may have been empty.
@@ -775,7 +1909,7 @@ impl DaemonControl {
         })?;
         psbt.unsigned_tx.output[0].value = output_value.to_sat();
 
-        sanity_check_psbt(&self.config.main_descriptor, &psbt)?;
+        sanity_check_psbt(&self.config.main_descriptor, &psbt, &self.secp)?;
 
         Ok(CreateRecoveryResult { psbt })
     }
@@ -786,6 +1920,22 @@ pub struct GetInfoDescriptors {
     pub main: descriptors::MultipathDescriptor,
 }
 
+/// The derivation indexes of the addresses and the range watched for incoming coins.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GetInfoAddresses {
+    /// Number of addresses past the highest derivation index watched for incoming coins.
+    pub gap_limit: u32,
+    /// Derivation index of the next receiving address.
+    pub receive_index: u32,
+    /// Derivation index of the next change address.
+    pub change_index: u32,
+    /// Highest derivation index of the receiving addresses which received coins, if any. The
+    /// addresses up to this index were likely given out already.
+    pub used_receive_index: Option<u32>,
+    /// The last derivation index watched by the Bitcoin backend, if it watches a fixed range.
+    pub watched_range_end: Option<u32>,
+}
+
 /// Information about the daemon
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetInfoResult {
@@ -796,11 +1946,33 @@ pub struct GetInfoResult {
     pub descriptors: GetInfoDescriptors,
     /// The progress as a percentage (between 0 and 1) of an ongoing rescan if there is any
     pub rescan_progress: Option<f64>,
+    /// Minimum feerate in sats/vb for a transaction to be accepted in the mempool of the Bitcoin
+    /// backend.
+    pub mempool_min_feerate: u64,
+    pub addresses: GetInfoAddresses,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetAddressResult {
     pub address: bitcoin::Address,
+    /// Index of the address in the receive keychain of the descriptor.
+    pub derivation_index: bip32::ChildNumber,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressLabel {
+    pub address: bitcoin::Address,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectCoinsResult {
+    pub outpoints: Vec<bitcoin::OutPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListBackendLabelsResult {
+    pub labels: Vec<AddressLabel>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -808,6 +1980,12 @@ pub struct LCSpendInfo {
     pub txid: bitcoin::Txid,
     /// The block height this spending transaction was confirmed at.
     pub height: Option<i32>,
+    /// Whether this spending transaction has enough confirmations to be considered final.
+    pub settled: bool,
+    /// The spending transaction we had seen before, if this one replaced it (for instance
+    /// through RBF).
+    #[serde(default)]
+    pub replaced_txid: Option<bitcoin::Txid>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -821,6 +1999,10 @@ pub struct ListCoinsEntry {
     pub block_height: Option<i32>,
     /// Information about the transaction spending this coin.
     pub spend_info: Option<LCSpendInfo>,
+    /// Whether this coin is a coinbase output that can't be spent yet.
+    pub is_immature: bool,
+    /// Whether this coin is unconfirmed and its creating transaction may be replaced.
+    pub is_replaceable: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -834,6 +2016,20 @@ pub struct CreateSpendResult {
     pub psbt: Psbt,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PreviewSpendResult {
+    /// Absolute fee paid by the transaction.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub fee: bitcoin::Amount,
+    /// Feerate in sats/vb, accounting for the satisfaction of the spending path.
+    pub feerate_vb: u64,
+    /// Virtual size of the transaction once satisfied.
+    pub vsize: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListSpendEntry {
     #[serde(serialize_with = "ser_base64", deserialize_with = "deser_base64")]
@@ -845,6 +2041,18 @@ pub struct ListSpendResult {
     pub spend_txs: Vec<ListSpendEntry>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanupSpendsResult {
+    /// Txids of the deleted Spend transactions.
+    pub deleted: Vec<bitcoin::Txid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateBlocksResult {
+    pub address: bitcoin::Address,
+    pub blockhashes: Vec<bitcoin::BlockHash>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListTransactionsResult {
     pub transactions: Vec<TransactionInfo>,
@@ -864,10 +2072,53 @@ pub struct CreateRecoveryResult {
     pub psbt: Psbt,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StartRescanResult {
+    /// The date the rescan was started from.
+    pub timestamp: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FeerateEstimate {
+    /// Number of blocks within which the transaction should confirm.
+    pub conf_target: u16,
+    /// Estimated feerate in sats/vb, if the Bitcoin backend has enough data.
+    pub feerate: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EstimateFeerateResult {
+    pub estimates: Vec<FeerateEstimate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckBalanceResult {
+    /// Value of the confirmed unspent coins we track.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub tracked_balance: bitcoin::Amount,
+    /// Value of the coins of our descriptor found in the UTxO set.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub utxo_set_balance: bitcoin::Amount,
+    /// Coins found in the UTxO set which we don't track as confirmed and unspent.
+    pub untracked: Vec<bitcoin::OutPoint>,
+    /// Coins we track as confirmed and unspent which aren't in the UTxO set.
+    pub missing: Vec<bitcoin::OutPoint>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{bitcoin::Block, database::SpendBlock, testutils::*};
+    use crate::{
+        bitcoin::{Block, BlockChainTip, MempoolEntry},
+        database::{DatabaseConnection, SpendBlock},
+        testutils::*,
+    };
 
     use bitcoin::{
         blockdata::transaction::{TxIn, TxOut},
@@ -882,7 +2133,11 @@ mod tests {
     fn getinfo() {
         let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
         // We can query getinfo
-        ms.handle.control.get_info();
+        let info = ms.handle.control.get_info().unwrap();
+        assert_eq!(info.addresses.gap_limit, 200);
+        assert_eq!(info.addresses.receive_index, 0);
+        assert!(info.addresses.watched_range_end.is_none());
+        assert!(info.addresses.used_receive_index.is_none());
         ms.shutdown();
     }
 
@@ -892,7 +2147,8 @@ mod tests {
 
         let control = &ms.handle.control;
         // We can get an address
-        let addr = control.get_new_address().address;
+        let res = control.get_new_address();
+        let addr = res.address;
         assert_eq!(
             addr,
             bitcoin::Address::from_str(
@@ -900,9 +2156,11 @@ mod tests {
             )
             .unwrap()
         );
+        assert_eq!(res.derivation_index, bip32::ChildNumber::from(0));
         // We won't get the same twice.
-        let addr2 = control.get_new_address().address;
-        assert_ne!(addr, addr2);
+        let res = control.get_new_address();
+        assert_ne!(addr, res.address);
+        assert_eq!(res.derivation_index, bip32::ChildNumber::from(1));
 
         ms.shutdown();
     }
@@ -938,22 +2196,22 @@ mod tests {
             .cloned()
             .collect();
         assert_eq!(
-            control.create_spend(&destinations, &[], 1),
+            control.create_spend(&destinations, &[], 1, &ChangeDestination::Auto),
             Err(CommandError::NoOutpoint)
         );
         assert_eq!(
-            control.create_spend(&HashMap::new(), &[dummy_op], 1),
+            control.create_spend(&HashMap::new(), &[dummy_op], 1, &ChangeDestination::Auto),
             Err(CommandError::NoDestination)
         );
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 0),
+            control.create_spend(&destinations, &[dummy_op], 0, &ChangeDestination::Auto),
             Err(CommandError::InvalidFeerate(0))
         );
 
         // The coin doesn't exist. If we create a new unspent one at this outpoint with a much
         // higher value, we'll get a Spend transaction with a change output.
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1),
+            control.create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto),
             Err(CommandError::UnknownOutpoint(dummy_op))
         );
         let mut db_conn = control.db().lock().unwrap().connection();
@@ -964,10 +2222,15 @@ mod tests {
             amount: bitcoin::Amount::from_sat(100_000),
             derivation_index: bip32::ChildNumber::from(13),
             is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
             spend_txid: None,
             spend_block: None,
         }]);
-        let res = control.create_spend(&destinations, &[dummy_op], 1).unwrap();
+        let res = control
+            .create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto)
+            .unwrap();
         assert!(res.psbt.inputs[0].non_witness_utxo.is_some());
         let tx = res.psbt.unsigned_tx;
         assert_eq!(tx.input.len(), 1);
@@ -976,22 +2239,54 @@ mod tests {
         assert_eq!(tx.output[0].script_pubkey, dummy_addr.script_pubkey());
         assert_eq!(tx.output[0].value, dummy_value);
 
-        // Transaction is 1 in (P2WSH satisfaction), 2 outs. At 1sat/vb, it's 171 sats fees.
-        // At 2sats/vb, it's twice that.
-        assert_eq!(tx.output[1].value, 89_829);
-        let res = control.create_spend(&destinations, &[dummy_op], 2).unwrap();
+        // Transaction is 1 in (P2WSH satisfaction through the primary path), 2 outs. At 1sat/vb,
+        // it's 162 sats fees. At 2sats/vb, it's twice that.
+        assert_eq!(tx.output[1].value, 89_838);
+        let res = control
+            .create_spend(&destinations, &[dummy_op], 2, &ChangeDestination::Auto)
+            .unwrap();
+        let tx = res.psbt.unsigned_tx;
+        assert_eq!(tx.output[1].value, 89_676);
+
+        // The preview of this spend gives its fee and size, without consuming a change index.
+        let change_index = db_conn.change_index();
+        let preview = control
+            .preview_spend(&destinations, &[dummy_op], 2, &ChangeDestination::Auto)
+            .unwrap();
+        assert_eq!(preview.fee, bitcoin::Amount::from_sat(324));
+        assert_eq!(preview.feerate_vb, 2);
+        assert_eq!(preview.vsize, 162);
+        assert_eq!(db_conn.change_index(), change_index);
+
+        // We can ask not to create a change output, in which case the excess goes to the fee and
+        // no change index is consumed. But we won't send the change to an address not ours.
+        let change_index = db_conn.change_index();
+        let res = control
+            .create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::NoChange)
+            .unwrap();
         let tx = res.psbt.unsigned_tx;
-        assert_eq!(tx.output[1].value, 89_658);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, dummy_value);
+        assert_eq!(db_conn.change_index(), change_index);
+        assert_eq!(
+            control.create_spend(
+                &destinations,
+                &[dummy_op],
+                1,
+                &ChangeDestination::Address(dummy_addr.clone())
+            ),
+            Err(CommandError::ChangeAddressNotOurs(dummy_addr.clone()))
+        );
 
         // A feerate of 555 won't trigger the sanity checks (they were previously not taking the
         // satisfaction size into account and overestimating the feerate).
         control
-            .create_spend(&destinations, &[dummy_op], 555)
+            .create_spend(&destinations, &[dummy_op], 555, &ChangeDestination::Auto)
             .unwrap();
 
         // If we ask for a too high feerate, or a too large/too small output, it'll fail.
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 10_000),
+            control.create_spend(&destinations, &[dummy_op], 10_000, &ChangeDestination::Auto),
             Err(CommandError::InsufficientFunds(
                 bitcoin::Amount::from_sat(100_000),
                 bitcoin::Amount::from_sat(10_000),
@@ -1000,7 +2295,7 @@ mod tests {
         );
         *destinations.get_mut(&dummy_addr).unwrap() = 100_001;
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1),
+            control.create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto),
             Err(CommandError::InsufficientFunds(
                 bitcoin::Amount::from_sat(100_000),
                 bitcoin::Amount::from_sat(100_001),
@@ -1009,7 +2304,7 @@ mod tests {
         );
         *destinations.get_mut(&dummy_addr).unwrap() = 4_500;
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1),
+            control.create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto),
             Err(CommandError::InvalidOutputValue(bitcoin::Amount::from_sat(
                 4_500
             )))
@@ -1026,7 +2321,12 @@ mod tests {
                 .cloned()
                 .collect();
         assert_eq!(
-            control.create_spend(&invalid_destinations, &[dummy_op], 1),
+            control.create_spend(
+                &invalid_destinations,
+                &[dummy_op],
+                1,
+                &ChangeDestination::Auto
+            ),
             Err(CommandError::AddressNetwork(
                 invalid_addr,
                 bitcoin::Network::Bitcoin
@@ -1036,7 +2336,9 @@ mod tests {
         // If we ask for a large, but valid, output we won't get a change output. 95_000 because we
         // won't create an output lower than 5k sats.
         *destinations.get_mut(&dummy_addr).unwrap() = 95_000;
-        let res = control.create_spend(&destinations, &[dummy_op], 1).unwrap();
+        let res = control
+            .create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto)
+            .unwrap();
         let tx = res.psbt.unsigned_tx;
         assert_eq!(tx.input.len(), 1);
         assert_eq!(tx.input[0].previous_output, dummy_op);
@@ -1054,7 +2356,7 @@ mod tests {
             .unwrap(),
         )]);
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op], 1),
+            control.create_spend(&destinations, &[dummy_op], 1, &ChangeDestination::Auto),
             Err(CommandError::AlreadySpent(dummy_op))
         );
 
@@ -1070,16 +2372,58 @@ mod tests {
             amount: bitcoin::Amount::from_sat(400_000),
             derivation_index: bip32::ChildNumber::from(42),
             is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
             spend_txid: None,
             spend_block: None,
         }]);
         assert_eq!(
-            control.create_spend(&destinations, &[dummy_op_dup], 1_001),
+            control.create_spend(
+                &destinations,
+                &[dummy_op_dup],
+                1_001,
+                &ChangeDestination::Auto
+            ),
             Err(CommandError::InsaneFees(InsaneFeeInfo::TooHighFeerate(
                 1001
             )))
         );
 
+        // A coinbase output can't be spent before it reached maturity. The tip of the dummy
+        // bitcoind is at height 100.
+        let coinbase_op = bitcoin::OutPoint {
+            txid: dummy_op.txid,
+            vout: dummy_op.vout + 11,
+        };
+        let mut coinbase_coin = Coin {
+            outpoint: coinbase_op,
+            block_height: Some(2),
+            block_time: Some(1_000),
+            amount: bitcoin::Amount::from_sat(400_000),
+            derivation_index: bip32::ChildNumber::from(43),
+            is_change: false,
+            is_coinbase: true,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        };
+        assert!(coinbase_coin.is_immature(100));
+        db_conn.new_unspent_coins(&[coinbase_coin]);
+        assert_eq!(
+            control.create_spend(&destinations, &[coinbase_op], 1, &ChangeDestination::Auto),
+            Err(CommandError::ImmatureCoinbase(coinbase_op))
+        );
+        // It can be spent in the next block if it was confirmed 100 blocks before.
+        coinbase_coin.block_height = Some(1);
+        assert!(!coinbase_coin.is_immature(100));
+        db_conn.remove_coins(&[coinbase_op]);
+        db_conn.new_unspent_coins(&[coinbase_coin]);
+        control
+            .create_spend(&destinations, &[coinbase_op], 1, &ChangeDestination::Auto)
+            .unwrap();
+
         ms.shutdown();
     }
 
@@ -1117,6 +2461,9 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(100_000),
                 derivation_index: bip32::ChildNumber::from(13),
                 is_change: false,
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
                 spend_block: None,
             },
@@ -1127,6 +2474,9 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(115_680),
                 derivation_index: bip32::ChildNumber::from(34),
                 is_change: false,
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
                 spend_block: None,
             },
@@ -1155,17 +2505,22 @@ mod tests {
                 .cloned()
                 .collect();
         let mut psbt_a = control
-            .create_spend(&destinations_a, &[dummy_op_a], 1)
+            .create_spend(&destinations_a, &[dummy_op_a], 1, &ChangeDestination::Auto)
             .unwrap()
             .psbt;
         let txid_a = psbt_a.unsigned_tx.txid();
         let psbt_b = control
-            .create_spend(&destinations_b, &[dummy_op_b], 10)
+            .create_spend(&destinations_b, &[dummy_op_b], 10, &ChangeDestination::Auto)
             .unwrap()
             .psbt;
         let txid_b = psbt_b.unsigned_tx.txid();
         let psbt_c = control
-            .create_spend(&destinations_c, &[dummy_op_a, dummy_op_b], 100)
+            .create_spend(
+                &destinations_c,
+                &[dummy_op_a, dummy_op_b],
+                100,
+                &ChangeDestination::Auto,
+            )
             .unwrap()
             .psbt;
         let txid_c = psbt_c.unsigned_tx.txid();
@@ -1194,17 +2549,88 @@ mod tests {
         control.update_spend(psbt_c.clone()).unwrap();
         assert_eq!(db_conn.spend_tx(&txid_c).unwrap(), psbt_c);
 
-        // We can't store a PSBT spending an external coin
+        // Two cosigners signing the same Taproot leaf each update the stored PSBT with only their
+        // own signature. Both signatures are kept, as well as the key path signature.
+        let schnorr_sig_a = bitcoin::SchnorrSig::from_slice(&[1; 64]).unwrap();
+        let schnorr_sig_b = bitcoin::SchnorrSig::from_slice(&[2; 64]).unwrap();
+        let xonly_key_a = bitcoin::XOnlyPublicKey::from_str(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let xonly_key_b = bitcoin::XOnlyPublicKey::from_str(
+            "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        )
+        .unwrap();
+        let leaf_hash = bitcoin::util::taproot::TapLeafHash::from_script(
+            &bitcoin::Script::new(),
+            bitcoin::util::taproot::LeafVersion::TapScript,
+        );
+        let mut psbt_tr_a = psbt_b.clone();
+        psbt_tr_a.inputs[0].tap_key_sig = Some(schnorr_sig_a);
+        psbt_tr_a.inputs[0]
+            .tap_script_sigs
+            .insert((xonly_key_a, leaf_hash), schnorr_sig_a);
+        control.update_spend(psbt_tr_a).unwrap();
+        let mut psbt_tr_b = psbt_b.clone();
+        psbt_tr_b.inputs[0]
+            .tap_script_sigs
+            .insert((xonly_key_b, leaf_hash), schnorr_sig_b);
+        control.update_spend(psbt_tr_b).unwrap();
+        let db_psbtin = db_conn.spend_tx(&txid_b).unwrap().inputs[0].clone();
+        assert_eq!(db_psbtin.tap_key_sig, Some(schnorr_sig_a));
+        assert_eq!(db_psbtin.tap_script_sigs.len(), 2);
+        assert_eq!(
+            db_psbtin.tap_script_sigs.get(&(xonly_key_a, leaf_hash)),
+            Some(&schnorr_sig_a)
+        );
+        assert_eq!(
+            db_psbtin.tap_script_sigs.get(&(xonly_key_b, leaf_hash)),
+            Some(&schnorr_sig_b)
+        );
+
+        // A new key path signature replaces the stored one.
+        let mut psbt_tr_c = psbt_b.clone();
+        psbt_tr_c.inputs[0].tap_key_sig = Some(schnorr_sig_b);
+        control.update_spend(psbt_tr_c).unwrap();
+        assert_eq!(
+            db_conn.spend_tx(&txid_b).unwrap().inputs[0].tap_key_sig,
+            Some(schnorr_sig_b)
+        );
+
+        // We can't store a PSBT spending only external coins
         let external_op = bitcoin::OutPoint::from_str(
             "8753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:2",
         )
         .unwrap();
-        psbt_a.unsigned_tx.input[0].previous_output = external_op;
+        let mut psbt_external = psbt_a.clone();
+        psbt_external.unsigned_tx.input[0].previous_output = external_op;
         assert_eq!(
-            control.update_spend(psbt_a),
+            control.update_spend(psbt_external),
             Err(CommandError::UnknownOutpoint(external_op))
         );
 
+        // But we can store a collaborative transaction spending an external coin along with ours.
+        let mut psbt_mixed = psbt_a.clone();
+        psbt_mixed.unsigned_tx.input.push(bitcoin::TxIn {
+            previous_output: external_op,
+            ..bitcoin::TxIn::default()
+        });
+        psbt_mixed.inputs.push(PsbtIn::default());
+        let txid_mixed = psbt_mixed.unsigned_tx.txid();
+        control.update_spend(psbt_mixed.clone()).unwrap();
+        assert_eq!(db_conn.spend_tx(&txid_mixed).unwrap(), psbt_mixed);
+
+        // We can't store a version 1 transaction with an input using a relative timelock, as
+        // the timelock wouldn't be enforced.
+        let mut psbt_v1 = psbt_b.clone();
+        psbt_v1.unsigned_tx.version = 1;
+        control.update_spend(psbt_v1.clone()).unwrap();
+        psbt_v1.unsigned_tx.input[0].sequence = bitcoin::Sequence::from_height(10);
+        assert_eq!(
+            control.update_spend(psbt_v1),
+            Err(CommandError::RelativeTimelockTxVersion(1))
+        );
+
         ms.shutdown();
     }
 
@@ -1299,6 +2725,9 @@ mod tests {
                 spend_block: Some(SpendBlock { time: 3, height: 3 }),
                 derivation_index: ChildNumber::from(0),
                 amount: bitcoin::Amount::from_sat(100_000_000),
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: Some(spend_tx.txid()),
             },
             // Deposit 2
@@ -1313,6 +2742,9 @@ mod tests {
                 spend_block: None,
                 derivation_index: ChildNumber::from(1),
                 amount: bitcoin::Amount::from_sat(2000),
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
             },
             // This coin is a change output.
@@ -1324,6 +2756,9 @@ mod tests {
                 spend_block: None,
                 derivation_index: ChildNumber::from(2),
                 amount: bitcoin::Amount::from_sat(100_000_000 - 4000 - 1000),
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
             },
             // Deposit 3
@@ -1338,6 +2773,9 @@ mod tests {
                 spend_block: None,
                 derivation_index: ChildNumber::from(3),
                 amount: bitcoin::Amount::from_sat(3000),
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
             },
         ]);
@@ -1404,7 +2842,10 @@ mod tests {
 
         let control = &ms.handle.control;
 
-        let transactions = control.list_confirmed_transactions(0, 4, 10).transactions;
+        let transactions = control
+            .list_confirmed_transactions(0, 4, 10)
+            .unwrap()
+            .transactions;
         assert_eq!(transactions.len(), 4);
 
         assert_eq!(transactions[0].time, Some(4));
@@ -1419,14 +2860,20 @@ mod tests {
         assert_eq!(transactions[3].time, Some(1));
         assert_eq!(transactions[3].tx, deposit1);
 
-        let transactions = control.list_confirmed_transactions(2, 3, 10).transactions;
+        let transactions = control
+            .list_confirmed_transactions(2, 3, 10)
+            .unwrap()
+            .transactions;
         assert_eq!(transactions.len(), 2);
 
         assert_eq!(transactions[0].time, Some(3));
         assert_eq!(transactions[1].time, Some(2));
         assert_eq!(transactions[1].tx, deposit2);
 
-        let transactions = control.list_confirmed_transactions(2, 3, 1).transactions;
+        let transactions = control
+            .list_confirmed_transactions(2, 3, 1)
+            .unwrap()
+            .transactions;
         assert_eq!(transactions.len(), 1);
 
         assert_eq!(transactions[0].time, Some(3));
@@ -1536,12 +2983,16 @@ mod tests {
 
         let control = &ms.handle.control;
 
-        let transactions = control.list_transactions(&[tx1.txid()]).transactions;
-        assert_eq!(transactions.len(), 1);
-        assert_eq!(transactions[0].tx, tx1);
+        let transactions = control
+            .list_transactions(&[tx1.txid()])
+            .unwrap()
+            .transactions;
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].tx, tx1);
 
         let transactions = control
             .list_transactions(&[tx1.txid(), tx2.txid(), tx3.txid()])
+            .unwrap()
             .transactions;
         assert_eq!(transactions.len(), 3);
 
@@ -1556,4 +3007,733 @@ mod tests {
 
         ms.shutdown();
     }
+
+    #[test]
+    fn create_spend_mempool_min_feerate() {
+        let dummy_op = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind.mempool_min_feerate = 3;
+        let ms = DummyLiana::new(dummy_bitcoind, DummyDatabase::new());
+        let control = &ms.handle.control;
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        let destinations: HashMap<bitcoin::Address, u64> =
+            [(dummy_addr.clone(), 10_000)].iter().cloned().collect();
+
+        // Below the mempool minimum feerate we won't even look at the coins.
+        assert_eq!(
+            control.create_spend(&destinations, &[dummy_op], 2, &ChangeDestination::Auto),
+            Err(CommandError::FeerateBelowMempoolMin(2, 3))
+        );
+        assert_eq!(
+            control.create_recovery(dummy_addr, 2, None),
+            Err(CommandError::FeerateBelowMempoolMin(2, 3))
+        );
+        // At the minimum the feerate is accepted.
+        assert_eq!(
+            control.create_spend(&destinations, &[dummy_op], 3, &ChangeDestination::Auto),
+            Err(CommandError::UnknownOutpoint(dummy_op))
+        );
+        assert_eq!(control.get_info().unwrap().mempool_min_feerate, 3);
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_recovery_unknown_path() {
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.handle.control;
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+
+        // The dummy descriptor only has a recovery path with a timelock of 10_000 blocks.
+        assert_eq!(
+            control.create_recovery(dummy_addr.clone(), 1, Some(42)),
+            Err(CommandError::UnknownRecoveryPath(42))
+        );
+        // With a known timelock, or none, we fail further since there is no coin to sweep.
+        assert_eq!(
+            control.create_recovery(dummy_addr.clone(), 1, Some(10_000)),
+            Err(CommandError::RecoveryNotAvailable)
+        );
+        assert_eq!(
+            control.create_recovery(dummy_addr, 1, None),
+            Err(CommandError::RecoveryNotAvailable)
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn rbf_psbt() {
+        let dummy_op = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind.txs.insert(
+            dummy_op.txid,
+            (
+                bitcoin::Transaction {
+                    version: 2,
+                    lock_time: bitcoin::PackedLockTime(0),
+                    input: vec![],
+                    output: vec![],
+                },
+                None,
+            ),
+        );
+        let ms = DummyLiana::new(dummy_bitcoind, DummyDatabase::new());
+        let control = &ms.handle.control;
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: dummy_op,
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        }]);
+
+        // Create a Spend transaction with a change output.
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        let destinations: HashMap<bitcoin::Address, u64> =
+            [(dummy_addr, 10_000)].iter().cloned().collect();
+        let psbt = control
+            .create_spend(&destinations, &[dummy_op], 2, &ChangeDestination::Auto)
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+        let txid = psbt.unsigned_tx.txid();
+        assert_eq!(
+            control.rbf_psbt(&txid, 10),
+            Err(CommandError::UnknownSpend(txid))
+        );
+        control.update_spend(psbt.clone()).unwrap();
+
+        // Without a change output we can't deduct the additional fee.
+        let mut nochange_psbt = psbt.clone();
+        nochange_psbt.unsigned_tx.output.pop();
+        nochange_psbt.outputs.pop();
+        let nochange_txid = nochange_psbt.unsigned_tx.txid();
+        db_conn.store_spend(&nochange_psbt);
+        assert_eq!(
+            control.rbf_psbt(&nochange_txid, 10),
+            Err(CommandError::RbfNoChange(nochange_txid))
+        );
+
+        // Sign it, both for a P2WSH and a Taproot descriptor. None of the signatures commit to
+        // the replacement so they must all be dropped from it.
+        let mut signed_psbt = psbt.clone();
+        let ecdsa_sig = bitcoin::EcdsaSig::from_str("304402204004fcdbb9c0d0cbf585f58cee34dccb012efbd8fc2b0d5e97760045ae35803802201a0bd7ec2383e0b93748abc9946c8e17a8312e314dab85982aeba650e738cbf401").unwrap();
+        let schnorr_sig = bitcoin::SchnorrSig::from_slice(&[1; 64]).unwrap();
+        let xonly_key = bitcoin::XOnlyPublicKey::from_str(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let leaf_hash = bitcoin::util::taproot::TapLeafHash::from_script(
+            &bitcoin::Script::new(),
+            bitcoin::util::taproot::LeafVersion::TapScript,
+        );
+        signed_psbt.inputs[0].partial_sigs.insert(
+            bitcoin::PublicKey::from_str(
+                "023a664c5617412f0b292665b1fd9d766456a7a3b1614c7e7c5f411200ff1958ef",
+            )
+            .unwrap(),
+            ecdsa_sig,
+        );
+        signed_psbt.inputs[0].tap_key_sig = Some(schnorr_sig);
+        signed_psbt.inputs[0]
+            .tap_script_sigs
+            .insert((xonly_key, leaf_hash), schnorr_sig);
+        db_conn.store_spend(&signed_psbt);
+
+        // Broadcast it. The feerate must be strictly higher than the previous one plus the
+        // incremental relay feerate.
+        db_conn.spend_coins(&[(dummy_op, txid)]);
+        assert!(matches!(
+            control.rbf_psbt(&txid, 2),
+            Err(CommandError::RbfFeerateTooLow(2, _))
+        ));
+        assert!(matches!(
+            control.rbf_psbt(&txid, 3),
+            Err(CommandError::RbfFeerateTooLow(3, _))
+        ));
+
+        // The replacement spends the same coin to the same destination, deducting the additional
+        // fee from the change output.
+        let rbf_psbt = control.rbf_psbt(&txid, 10).unwrap().psbt;
+        let (tx, rbf_tx) = (&psbt.unsigned_tx, &rbf_psbt.unsigned_tx);
+        assert_ne!(rbf_tx.txid(), txid);
+        assert_eq!(rbf_tx.input.len(), 1);
+        assert_eq!(rbf_tx.input[0].previous_output, dummy_op);
+        assert!(rbf_tx.input[0].sequence.is_rbf());
+        assert_eq!(rbf_tx.output[0], tx.output[0]);
+        assert!(rbf_tx.output[1].value < tx.output[1].value);
+        assert_eq!(rbf_tx.output[1].script_pubkey, tx.output[1].script_pubkey);
+        assert!(rbf_psbt.inputs[0].partial_sigs.is_empty());
+        assert!(rbf_psbt.inputs[0].tap_key_sig.is_none());
+        assert!(rbf_psbt.inputs[0].tap_script_sigs.is_empty());
+
+        // The additional fee may not make the change output dust.
+        assert_eq!(
+            control.rbf_psbt(&txid, 900),
+            Err(CommandError::RbfNoChange(txid))
+        );
+
+        // Once the transaction is confirmed it can't be replaced anymore.
+        db_conn.confirm_spend(&[(dummy_op, txid, 1, 1)]);
+        assert_eq!(
+            control.rbf_psbt(&txid, 10),
+            Err(CommandError::AlreadyConfirmed(txid))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_sweep() {
+        let dummy_op_a = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let dummy_op_b = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:1",
+        )
+        .unwrap();
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind.txs.insert(
+            dummy_op_a.txid,
+            (
+                bitcoin::Transaction {
+                    version: 2,
+                    lock_time: bitcoin::PackedLockTime(0),
+                    input: vec![],
+                    output: vec![],
+                },
+                None,
+            ),
+        );
+        let ms = DummyLiana::new(dummy_bitcoind, DummyDatabase::new());
+        let control = &ms.handle.control;
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+
+        assert_eq!(
+            control.create_sweep(dummy_addr.clone(), &[], 1),
+            Err(CommandError::NoOutpoint)
+        );
+        assert_eq!(
+            control.create_sweep(dummy_addr.clone(), &[dummy_op_a], 0),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        assert_eq!(
+            control.create_sweep(dummy_addr.clone(), &[dummy_op_a], 1),
+            Err(CommandError::UnknownOutpoint(dummy_op_a))
+        );
+        let mut db_conn = control.db().lock().unwrap().connection();
+        let coin = |outpoint, amount| Coin {
+            outpoint,
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(amount),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        };
+        db_conn.new_unspent_coins(&[coin(dummy_op_a, 100_000), coin(dummy_op_b, 50_000)]);
+
+        // All the coins are spent to a single output, paying exactly the requested feerate.
+        let change_index = db_conn.change_index();
+        let psbt = control
+            .create_sweep(dummy_addr.clone(), &[dummy_op_a, dummy_op_b], 2)
+            .unwrap()
+            .psbt;
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].script_pubkey, dummy_addr.script_pubkey());
+        let tx_vb = (tx.vsize() + control.primary_sat_vbytes() * 2) as u64;
+        assert_eq!(tx.output[0].value, 150_000 - tx_vb * 2);
+        assert_eq!(db_conn.change_index(), change_index);
+
+        // The coins must be able to pay for the fee and an output above the dust limit.
+        assert_eq!(
+            control.create_sweep(dummy_addr, &[dummy_op_b], 500),
+            Err(CommandError::InsufficientFunds(
+                bitcoin::Amount::from_sat(50_000),
+                bitcoin::Amount::from_sat(0),
+                500
+            ))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_refresh() {
+        let dummy_op_a = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let dummy_op_b = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:1",
+        )
+        .unwrap();
+        let dummy_op_c = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:2",
+        )
+        .unwrap();
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind.txs.insert(
+            dummy_op_a.txid,
+            (
+                bitcoin::Transaction {
+                    version: 2,
+                    lock_time: bitcoin::PackedLockTime(0),
+                    input: vec![],
+                    output: vec![],
+                },
+                None,
+            ),
+        );
+        let ms = DummyLiana::new(dummy_bitcoind, DummyDatabase::new());
+        let control = &ms.handle.control;
+
+        assert_eq!(
+            control.create_refresh(10_000, 0, None),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        assert_eq!(
+            control.create_refresh(10_000, 1, None),
+            Err(CommandError::NothingToRefresh(10_000))
+        );
+        // The dummy descriptor only has a recovery path with a timelock of 10_000 blocks.
+        assert_eq!(
+            control.create_refresh(10_000, 1, Some(42)),
+            Err(CommandError::UnknownRecoveryPath(42))
+        );
+
+        // The tip is at height 100 and the timelock is 10_000 blocks. The recovery path of the
+        // first coin is available in 9_900 blocks, of the second in 9_949 blocks. The timelock
+        // of the third, unconfirmed, did not start yet.
+        let mut db_conn = control.db().lock().unwrap().connection();
+        let coin = |outpoint| Coin {
+            outpoint,
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        };
+        db_conn.new_unspent_coins(&[coin(dummy_op_a), coin(dummy_op_b), coin(dummy_op_c)]);
+        db_conn.confirm_coins(&[(dummy_op_a, 1, 1_111), (dummy_op_b, 50, 1_111)]);
+        assert_eq!(
+            control.create_refresh(9_899, 1, None),
+            Err(CommandError::NothingToRefresh(9_899))
+        );
+        assert_eq!(
+            control.create_refresh(9_899, 1, Some(10_000)),
+            Err(CommandError::NothingToRefresh(9_899))
+        );
+
+        // The coins approaching their timelock are spent back to a fresh change address, through
+        // the primary path.
+        let change_index = db_conn.change_index();
+        let psbt = control.create_refresh(9_900, 2, None).unwrap().psbt;
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output, dummy_op_a);
+        assert!(!tx.input[0].sequence.is_height_locked());
+        assert_eq!(tx.output.len(), 1);
+        let change_desc = control
+            .config
+            .main_descriptor
+            .change_descriptor()
+            .derive(change_index, &control.secp);
+        assert_eq!(tx.output[0].script_pubkey, change_desc.script_pubkey());
+        assert!(!psbt.outputs[0].bip32_derivation.is_empty());
+        assert_eq!(db_conn.change_index(), change_index.increment().unwrap());
+
+        let psbt = control.create_refresh(10_000, 2, None).unwrap().psbt;
+        let mut spent: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        spent.sort();
+        assert_eq!(spent, vec![dummy_op_a, dummy_op_b]);
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_cpfp() {
+        // A parent transaction, spending a coin of a transaction known to the backend, which
+        // created one of our coins and pays 1_000 sats in fees.
+        let grandparent_tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![bitcoin::TxOut {
+                value: 200_000,
+                script_pubkey: bitcoin::Script::new(),
+            }],
+        };
+        let parent_tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::new(grandparent_tx.txid(), 0),
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![
+                bitcoin::TxOut {
+                    value: 100_000,
+                    script_pubkey: bitcoin::Script::new(),
+                },
+                bitcoin::TxOut {
+                    value: 99_000,
+                    script_pubkey: bitcoin::Script::new(),
+                },
+            ],
+        };
+        let op = bitcoin::OutPoint::new(parent_tx.txid(), 0);
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind
+            .txs
+            .insert(grandparent_tx.txid(), (grandparent_tx.clone(), None));
+        dummy_bitcoind
+            .txs
+            .insert(parent_tx.txid(), (parent_tx.clone(), None));
+        let ms = DummyLiana::new(dummy_bitcoind, DummyDatabase::new());
+        let control = &ms.handle.control;
+
+        assert_eq!(
+            control.create_cpfp(&op, 0),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        assert_eq!(
+            control.create_cpfp(&op, 10),
+            Err(CommandError::UnknownOutpoint(op))
+        );
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: op,
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        }]);
+
+        // The child spends the coin back to us, paying for the package minus the parent's fee.
+        let change_index = db_conn.change_index();
+        let psbt = control.create_cpfp(&op, 10).unwrap().psbt;
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output, op);
+        assert!(tx.input[0].sequence.is_rbf());
+        assert_eq!(tx.output.len(), 1);
+        assert!(!psbt.outputs[0].bip32_derivation.is_empty());
+        assert_eq!(db_conn.change_index(), change_index.increment().unwrap());
+        let child_vb = (tx.vsize() + control.primary_sat_vbytes()) as u64;
+        let package_vb = child_vb + parent_tx.vsize() as u64;
+        assert_eq!(100_000 - tx.output[0].value + 1_000, 10 * package_vb);
+
+        // The coin can't pay for the package at a too high feerate.
+        assert_eq!(
+            control.create_cpfp(&op, 500),
+            Err(CommandError::InsufficientFunds(
+                bitcoin::Amount::from_sat(100_000),
+                bitcoin::Amount::from_sat(0),
+                500
+            ))
+        );
+
+        // Once the parent is confirmed there is no point in bumping it.
+        db_conn.confirm_coins(&[(op, 1, 1)]);
+        assert_eq!(
+            control.create_cpfp(&op, 10),
+            Err(CommandError::AlreadyConfirmed(op.txid))
+        );
+
+        ms.shutdown();
+
+        // If the mempool tells us the parent has itself an unconfirmed parent, the child pays for
+        // both of them.
+        let mut dummy_bitcoind = DummyBitcoind::new();
+        dummy_bitcoind
+            .txs
+            .insert(grandparent_tx.txid(), (grandparent_tx, None));
+        dummy_bitcoind
+            .txs
+            .insert(parent_tx.txid(), (parent_tx.clone(), None));
+        let ancestors_vb = parent_tx.vsize() as u64 + 110;
+        dummy_bitcoind.mempool_entries.insert(
+            parent_tx.txid(),
+            MempoolEntry {
+                vsize: parent_tx.vsize() as u64,
+                fee: bitcoin::Amount::from_sat(1_000),
+                ancestor_count: 2,
+                ancestor_vsize: ancestors_vb,
+                ancestor_fees: bitcoin::Amount::from_sat(1_110),
+                descendant_count: 1,
+                descendant_vsize: parent_tx.vsize() as u64,
+                descendant_fees: bitcoin::Amount::from_sat(1_000),
+            },
+        );
+        let mut db = DummyDatabase::new();
+        db.insert_coins(vec![Coin {
+            outpoint: op,
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        }]);
+        let ms = DummyLiana::new(dummy_bitcoind, db);
+        let control = &ms.handle.control;
+        let tx = control.create_cpfp(&op, 10).unwrap().psbt.unsigned_tx;
+        let child_vb = (tx.vsize() + control.primary_sat_vbytes()) as u64;
+        assert_eq!(
+            100_000 - tx.output[0].value + 1_110,
+            10 * (child_vb + ancestors_vb)
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn check_balance() {
+        let op_a = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        let op_b = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:1",
+        )
+        .unwrap();
+        let op_c = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:2",
+        )
+        .unwrap();
+        let coin = |outpoint: bitcoin::OutPoint, block_height: Option<i32>| Coin {
+            outpoint,
+            block_height,
+            block_time: block_height.map(|h| h as u32),
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        };
+        let mut db = DummyDatabase::new();
+        // A confirmed coin also in the UTxO set, a confirmed one missing from it and an
+        // unconfirmed one which is not expected to be there.
+        db.insert_coins(vec![
+            coin(op_a, Some(10)),
+            coin(op_b, Some(11)),
+            coin(op_c, None),
+        ]);
+        let mut btc = DummyBitcoind::new();
+        let op_d = bitcoin::OutPoint::from_str(
+            "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:3",
+        )
+        .unwrap();
+        btc.utxo_set = vec![
+            (op_a, bitcoin::Amount::from_sat(100_000)),
+            (op_d, bitcoin::Amount::from_sat(50_000)),
+        ];
+        let ms = DummyLiana::new(btc, db);
+        let control = &ms.handle.control;
+
+        let res = control.check_balance().unwrap();
+        assert_eq!(res.tracked_balance, bitcoin::Amount::from_sat(200_000));
+        assert_eq!(res.utxo_set_balance, bitcoin::Amount::from_sat(150_000));
+        assert_eq!(res.untracked, vec![op_d]);
+        assert_eq!(res.missing, vec![op_b]);
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn estimate_feerate() {
+        let mut btc = DummyBitcoind::new();
+        btc.fee_estimates.insert(1, 42);
+        btc.fee_estimates.insert(6, 12);
+        let ms = DummyLiana::new(btc, DummyDatabase::new());
+        let control = &ms.handle.control;
+
+        let res = control.estimate_feerate(&[1, 6, 144]).unwrap();
+        assert_eq!(
+            res.estimates,
+            vec![
+                FeerateEstimate {
+                    conf_target: 1,
+                    feerate: Some(42)
+                },
+                FeerateEstimate {
+                    conf_target: 6,
+                    feerate: Some(12)
+                },
+                FeerateEstimate {
+                    conf_target: 144,
+                    feerate: None
+                },
+            ]
+        );
+        assert_eq!(
+            control.estimate_feerate(&[1, 0]),
+            Err(CommandError::InvalidConfTarget(0))
+        );
+        assert_eq!(
+            control.estimate_feerate(&[1009]),
+            Err(CommandError::InvalidConfTarget(1009))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn cleanup_spends() {
+        let op = |vout: u32| {
+            OutPoint::from_str(&format!(
+                "3753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:{}",
+                vout
+            ))
+            .unwrap()
+        };
+        let spend_psbt = |outpoint: OutPoint| {
+            Psbt::from_unsigned_tx(Transaction {
+                version: 2,
+                lock_time: PackedLockTime(0),
+                input: vec![TxIn {
+                    previous_output: outpoint,
+                    ..TxIn::default()
+                }],
+                output: vec![TxOut {
+                    value: 90_000,
+                    script_pubkey: Script::new(),
+                }],
+            })
+            .unwrap()
+        };
+        let coin = |outpoint: OutPoint, spend: Option<(Txid, i32)>| Coin {
+            outpoint,
+            block_height: Some(1),
+            block_time: Some(1),
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            is_coinbase: false,
+            is_rbf: false,
+            replaced_spend_txid: None,
+            spend_txid: spend.map(|(txid, _)| txid),
+            spend_block: spend.map(|(_, height)| SpendBlock { height, time: 1 }),
+        };
+        let other_txid =
+            Txid::from_str("617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3")
+                .unwrap();
+
+        // With a tip at height 100, a spend confirmed at height 95 has 6 confirmations and is
+        // final, one confirmed at height 99 isn't.
+        let (psbt_a, psbt_b, psbt_c, psbt_d) = (
+            spend_psbt(op(0)),
+            spend_psbt(op(1)),
+            spend_psbt(op(2)),
+            spend_psbt(op(3)),
+        );
+        let mut db = DummyDatabase::new();
+        db.update_tip(&BlockChainTip {
+            hash: bitcoin::BlockHash::from_str(
+                "000000007bc154e0fa7ea32218a72fe2c1bb9f86cf8c9ebf9a715ed27fdb229a",
+            )
+            .unwrap(),
+            height: 100,
+        });
+        db.insert_coins(vec![
+            // Replaced by a final transaction.
+            coin(op(0), Some((other_txid, 95))),
+            // Replaced by a transaction which may still be reorged out.
+            coin(op(1), Some((other_txid, 99))),
+            // Spent by the stored transaction itself.
+            coin(op(2), Some((psbt_c.unsigned_tx.txid(), 95))),
+            // Not spent yet.
+            coin(op(3), None),
+        ]);
+        for psbt in &[&psbt_a, &psbt_b, &psbt_c, &psbt_d] {
+            db.store_spend(psbt);
+        }
+        let ms = DummyLiana::new(DummyBitcoind::new(), db);
+        let control = &ms.handle.control;
+
+        assert_eq!(
+            control.cleanup_spends(),
+            CleanupSpendsResult {
+                deleted: vec![psbt_a.unsigned_tx.txid()]
+            }
+        );
+        let remaining: Vec<Txid> = control
+            .list_spend()
+            .spend_txs
+            .into_iter()
+            .map(|entry| entry.psbt.unsigned_tx.txid())
+            .collect();
+        assert_eq!(remaining.len(), 3);
+        assert!(!remaining.contains(&psbt_a.unsigned_tx.txid()));
+
+        // Nothing left to clean up.
+        assert!(control.cleanup_spends().deleted.is_empty());
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn generate_blocks_regtest_only() {
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.handle.control;
+
+        // The dummy daemon runs on mainnet. No address is handed out.
+        let index = control.db.connection().receive_index();
+        assert_eq!(
+            control.generate_blocks(1).unwrap_err(),
+            CommandError::RegtestOnly(bitcoin::Network::Bitcoin)
+        );
+        assert_eq!(control.db.connection().receive_index(), index);
+
+        ms.shutdown();
+    }
 }
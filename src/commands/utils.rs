@@ -1,6 +1,13 @@
-use miniscript::bitcoin::{self, consensus, hashes::hex::FromHex};
+use crate::{bitcoin::BlockChainTip, descriptors::DerivedInheritanceDescriptor};
+
+use std::collections::{HashMap, VecDeque};
+
+use miniscript::bitcoin::{self, consensus, hashes::hex::FromHex, util::bip32};
 use serde::{de, Deserialize, Deserializer, Serializer};
 
+// The maximum number of derived descriptors kept in the cache.
+const DERIVED_DESC_CACHE_SIZE: usize = 1_000;
+
 /// Serialize an amount as sats
 pub fn ser_amount<S: Serializer>(amount: &bitcoin::Amount, s: S) -> Result<S::Ok, S::Error> {
     s.serialize_u64(amount.to_sat())
@@ -54,3 +61,230 @@ where
     let s = Vec::from_hex(&s).map_err(de::Error::custom)?;
     consensus::deserialize(&s).map_err(de::Error::custom)
 }
+
+/// The descriptors derived for the coins of the wallet, to avoid deriving them again for every
+/// command. Its size is bounded, and it is cleared whenever the chain tip changes as the coins
+/// we track, and spend, change with it.
+#[derive(Debug, Default)]
+pub struct DerivedDescCache {
+    tip: Option<BlockChainTip>,
+    descs: HashMap<(bip32::ChildNumber, bool), DerivedInheritanceDescriptor>,
+    // The keys in the order they were inserted, to drop the oldest entry when full.
+    insertion_order: VecDeque<(bip32::ChildNumber, bool)>,
+}
+
+impl DerivedDescCache {
+    /// Record the current chain tip. The cache is cleared if it changed.
+    pub fn set_tip(&mut self, tip: Option<BlockChainTip>) {
+        if self.tip != tip {
+            self.tip = tip;
+            self.descs.clear();
+            self.insertion_order.clear();
+        }
+    }
+
+    pub fn get(
+        &self,
+        index: bip32::ChildNumber,
+        is_change: bool,
+    ) -> Option<DerivedInheritanceDescriptor> {
+        self.descs.get(&(index, is_change)).cloned()
+    }
+
+    pub fn insert(
+        &mut self,
+        index: bip32::ChildNumber,
+        is_change: bool,
+        desc: DerivedInheritanceDescriptor,
+    ) {
+        if self.descs.insert((index, is_change), desc).is_none() {
+            self.insertion_order.push_back((index, is_change));
+            if self.insertion_order.len() > DERIVED_DESC_CACHE_SIZE {
+                if let Some(oldest) = self.insertion_order.pop_front() {
+                    self.descs.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+/// A small pseudo-random number generator (SplitMix64) used to randomize the coin selection. It
+/// is not cryptographically secure: the seed must come from a secure source of randomness unless
+/// the selection is purposefully made reproducible (for instance for testing).
+#[derive(Debug, Clone)]
+pub struct SelectionRng(u64);
+
+impl SelectionRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in the range `[0, n)`. `n` must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Select coins to pay for `target` using the Random-Improve algorithm. Coins are first picked at
+/// random until they cover the target plus the fee for spending them (`input_fee` per coin). The
+/// selection is then improved by adding more randomly picked coins as long as it gets the total
+/// closer to twice the target (without exceeding thrice the target), to create a change output
+/// of a similar value to the payment.
+///
+/// Returns `None` if the candidates cannot cover the target.
+pub fn random_improve(
+    candidates: &[(bitcoin::OutPoint, bitcoin::Amount)],
+    target: bitcoin::Amount,
+    input_fee: bitcoin::Amount,
+    rng: &mut SelectionRng,
+) -> Option<Vec<bitcoin::OutPoint>> {
+    // Shuffle the candidates (Fisher-Yates) to pick them in a random order.
+    let mut remaining = candidates.to_vec();
+    for i in (1..remaining.len()).rev() {
+        remaining.swap(i, rng.below(i + 1));
+    }
+
+    // The value of a coin once the fee for spending it is deducted.
+    let effective_value =
+        |amount: bitcoin::Amount| amount.to_sat().saturating_sub(input_fee.to_sat());
+
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+    while total < target.to_sat() {
+        let (outpoint, amount) = remaining.pop()?;
+        selected.push(outpoint);
+        total += effective_value(amount);
+    }
+
+    let ideal = target.to_sat().saturating_mul(2);
+    let upper_bound = target.to_sat().saturating_mul(3);
+    while let Some((outpoint, amount)) = remaining.pop() {
+        let new_total = total + effective_value(amount);
+        let distance = |t: u64| if t > ideal { t - ideal } else { ideal - t };
+        if new_total <= upper_bound && distance(new_total) < distance(total) {
+            selected.push(outpoint);
+            total = new_total;
+        }
+    }
+
+    Some(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::{LianaDescKeys, MultipathDescriptor};
+    use miniscript::{bitcoin::secp256k1, descriptor::DescriptorPublicKey};
+    use std::str::FromStr;
+
+    #[test]
+    fn derived_desc_cache() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let owner_key = LianaDescKeys::from_single(DescriptorPublicKey::from_str("[aabbccdd]xpub68JJTXc1MWK8KLW4HGLXZBJknja7kDUJuFHnM424LbziEXsfkh1WQCiEjjHw4zLqSUm4rvhgyGkkuRowE9tCJSgt3TQB5J3SKAbZ2SdcKST/<0;1>/*").unwrap());
+        let heir_key = LianaDescKeys::from_single(DescriptorPublicKey::from_str("[aabbccdd]xpub68JJTXc1MWK8PEQozKsRatrUHXKFNkD1Cb1BuQU9Xr5moCv87anqGyXLyUd4KpnDyZgo3gz4aN1r3NiaoweFW8UutBsBbgKHzaD5HkTkifK/<0;1>/*").unwrap());
+        let desc = MultipathDescriptor::new(owner_key, heir_key, 10_000).unwrap();
+        let derive = |index: u32, is_change: bool| {
+            let desc = if is_change {
+                desc.change_descriptor()
+            } else {
+                desc.receive_descriptor()
+            };
+            desc.derive(index.into(), &secp)
+        };
+        let tip = |height: i32| {
+            Some(BlockChainTip {
+                height,
+                hash: bitcoin::BlockHash::from_str(
+                    "000000007bc154e0fa7ea32218a72fe2c1bb9f86cf8c9ebf9a715ed27fdb229a",
+                )
+                .unwrap(),
+            })
+        };
+
+        let mut cache = DerivedDescCache::default();
+        cache.set_tip(tip(100));
+        cache.insert(0.into(), false, derive(0, false));
+        cache.insert(0.into(), true, derive(0, true));
+        assert_eq!(cache.get(0.into(), false), Some(derive(0, false)));
+        assert_eq!(cache.get(0.into(), true), Some(derive(0, true)));
+        assert_eq!(cache.get(1.into(), false), None);
+
+        // Still the same tip, the cache is kept.
+        cache.set_tip(tip(100));
+        assert_eq!(cache.get(0.into(), false), Some(derive(0, false)));
+
+        // The tip changed, it is cleared.
+        cache.set_tip(tip(101));
+        assert_eq!(cache.get(0.into(), false), None);
+        assert_eq!(cache.get(0.into(), true), None);
+
+        // When full, the oldest entry is dropped.
+        for i in 0..=DERIVED_DESC_CACHE_SIZE as u32 {
+            cache.insert(i.into(), false, derive(0, false));
+        }
+        assert_eq!(cache.get(0.into(), false), None);
+        assert!(cache.get(1.into(), false).is_some());
+        assert!(cache
+            .get((DERIVED_DESC_CACHE_SIZE as u32).into(), false)
+            .is_some());
+    }
+
+    #[test]
+    fn random_improve_seeded() {
+        let txid = bitcoin::Txid::from_str(
+            "f7bd1b2a995b689d326e51eb742eb1088c4a8f110d9cb56128fd553acc9f88e5",
+        )
+        .unwrap();
+        let candidates: Vec<(bitcoin::OutPoint, bitcoin::Amount)> = (0..20)
+            .map(|vout| {
+                (
+                    bitcoin::OutPoint::new(txid, vout),
+                    bitcoin::Amount::from_sat(10_000 * (vout as u64 + 1)),
+                )
+            })
+            .collect();
+        let target = bitcoin::Amount::from_sat(250_000);
+        let input_fee = bitcoin::Amount::from_sat(1_000);
+        let select = |seed: u64| {
+            random_improve(
+                &candidates,
+                target,
+                input_fee,
+                &mut SelectionRng::from_seed(seed),
+            )
+            .unwrap()
+        };
+
+        // The same seed gives the same selection over the same set of coins, and it always
+        // covers the target.
+        let selection = select(42);
+        assert_eq!(selection, select(42));
+        let selected_value: u64 = candidates
+            .iter()
+            .filter(|(op, _)| selection.contains(op))
+            .map(|(_, amount)| amount.to_sat() - input_fee.to_sat())
+            .sum();
+        assert!(selected_value >= target.to_sat());
+        assert!(selected_value <= target.to_sat() * 3);
+
+        // Different seeds lead to different selections.
+        assert!((0..10).any(|seed| select(seed) != selection));
+
+        // Not enough funds.
+        assert!(random_improve(
+            &candidates,
+            bitcoin::Amount::from_sat(10_000_000),
+            input_fee,
+            &mut SelectionRng::from_seed(42)
+        )
+        .is_none());
+    }
+}
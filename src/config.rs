@@ -2,7 +2,7 @@ use crate::descriptors::MultipathDescriptor;
 
 use std::{net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
 
-use miniscript::bitcoin::Network;
+use miniscript::bitcoin::{hashes::hex::FromHex, Network};
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
@@ -43,6 +43,14 @@ fn default_poll_interval() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_final_spend_confirmations() -> u32 {
+    6
+}
+
+fn default_gap_limit() -> u32 {
+    200
+}
+
 #[cfg(unix)]
 fn default_daemon() -> bool {
     false
@@ -55,6 +63,32 @@ pub struct BitcoindConfig {
     pub cookie_path: PathBuf,
     /// The IP:port bitcoind's RPC is listening on
     pub addr: SocketAddr,
+    /// The IP:port bitcoind publishes its ZMQ block notifications on (`zmqpubhashblock` or
+    /// `zmqpubrawblock`). If set, new blocks are processed as soon as they are announced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zmq_block_addr: Option<SocketAddr>,
+    /// How many times to retry a request to bitcoind upon a transient failure (connection
+    /// refused, timeout, bitcoind busy or warming up) before giving up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_retries: Option<usize>,
+    /// How long to wait for bitcoind to answer a request, in seconds. Requests scanning the
+    /// chain are given at least 30 minutes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_timeout_secs: Option<u64>,
+    /// The challenge of the custom signet bitcoind is running on, as a hex-encoded script. Only
+    /// valid on signet, the default signet is assumed if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signet_challenge: Option<String>,
+    /// The IP:port of a SOCKS5 proxy to connect to bitcoind's RPC through, for instance Tor's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_addr: Option<SocketAddr>,
+}
+
+/// Everything we need to know for talking to an Electrum server
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ElectrumConfig {
+    /// The host:port the Electrum server is listening on, for plain TCP connections
+    pub addr: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -68,6 +102,18 @@ pub struct BitcoinConfig {
         default = "default_poll_interval"
     )]
     pub poll_interval_secs: Duration,
+    /// Number of confirmations after which a spending transaction is considered final. Until
+    /// then the poller keeps checking it for conflicts.
+    #[serde(default = "default_final_spend_confirmations")]
+    pub final_spend_confirmations: u32,
+    /// Whether to delete the stored Spend transactions which were replaced by a transaction with
+    /// `final_spend_confirmations` confirmations.
+    #[serde(default)]
+    pub cleanup_spends: bool,
+    /// How many addresses past the highest derivation index used should be watched for incoming
+    /// coins. The range watched by the Bitcoin backend is extended as addresses get used.
+    #[serde(default = "default_gap_limit")]
+    pub gap_limit: u32,
 }
 
 /// Static informations we require to operate
@@ -96,6 +142,9 @@ pub struct Config {
     pub bitcoin_config: BitcoinConfig,
     /// Settings specific to bitcoind as the Bitcoin interface
     pub bitcoind_config: Option<BitcoindConfig>,
+    /// Settings specific to an Electrum server as the Bitcoin interface. If set, it is used
+    /// instead of bitcoind.
+    pub electrum_config: Option<ElectrumConfig>,
 }
 
 impl Config {
@@ -209,6 +258,30 @@ impl Config {
             )));
         }
 
+        if self.bitcoin_config.gap_limit == 0 {
+            return Err(ConfigError::Unexpected(
+                "The gap limit must be at least 1".to_string(),
+            ));
+        }
+
+        if let Some(challenge) = self
+            .bitcoind_config
+            .as_ref()
+            .and_then(|c| c.signet_challenge.as_ref())
+        {
+            if self.bitcoin_config.network != Network::Signet {
+                return Err(ConfigError::Unexpected(
+                    "A signet challenge can only be set on signet".to_string(),
+                ));
+            }
+            if Vec::<u8>::from_hex(challenge).is_err() {
+                return Err(ConfigError::Unexpected(format!(
+                    "Invalid signet challenge '{}', it must be a hex-encoded script",
+                    challenge
+                )));
+            }
+        }
+
         // TODO: check the semantics of the main descriptor
 
         Ok(())
@@ -237,7 +310,15 @@ mod tests {
             cookie_path = "/home/user/.bitcoin/.cookie"
             addr = "127.0.0.1:8332"
             "#.trim_start().replace("            ", "");
-        toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+        let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+        assert_eq!(parsed.bitcoin_config.final_spend_confirmations, 6);
+        assert!(!parsed.bitcoin_config.cleanup_spends);
+        assert_eq!(parsed.bitcoin_config.gap_limit, 200);
+        let bitcoind_config = parsed.bitcoind_config.unwrap();
+        assert!(bitcoind_config.zmq_block_addr.is_none());
+        assert!(bitcoind_config.rpc_retries.is_none());
+        assert!(bitcoind_config.rpc_timeout_secs.is_none());
+        assert!(bitcoind_config.proxy_addr.is_none());
 
         // A valid, round-tripping, config
         let toml_str = r#"
@@ -249,10 +330,17 @@ mod tests {
             [bitcoin_config]
             network = 'bitcoin'
             poll_interval_secs = 18
+            final_spend_confirmations = 3
+            cleanup_spends = true
+            gap_limit = 500
 
             [bitcoind_config]
             cookie_path = '/home/user/.bitcoin/.cookie'
             addr = '127.0.0.1:8332'
+            zmq_block_addr = '127.0.0.1:28332'
+            rpc_retries = 5
+            rpc_timeout_secs = 600
+            proxy_addr = '127.0.0.1:9050'
             "#.trim_start().replace("            ", "");
         let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
         let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
@@ -278,6 +366,23 @@ mod tests {
         let config_res: Result<Config, toml::de::Error> = toml::from_str(toml_str);
         config_res.expect_err("Deserializing an invalid toml_str");
 
+        // An Electrum server instead of bitcoind
+        let toml_str = r#"
+            daemon = false
+            log_level = "trace"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [electrum_config]
+            addr = "127.0.0.1:50001"
+        "#;
+        let parsed = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+        assert!(parsed.bitcoind_config.is_none());
+        let electrum_config = parsed.electrum_config.expect("Electrum config");
+        assert_eq!(electrum_config.addr, "127.0.0.1:50001");
+
         // Not enough parameters: missing the Bitcoin network
         let toml_str = r#"
             daemon = false
@@ -298,6 +403,44 @@ mod tests {
         config_res.expect_err("Deserializing an invalid toml_str");
     }
 
+    #[test]
+    fn signet_challenge() {
+        let config = |network: &str, challenge: &str| {
+            let toml_str = format!(
+                r#"
+                daemon = false
+                log_level = "trace"
+                main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+                [bitcoin_config]
+                network = "{}"
+
+                [bitcoind_config]
+                cookie_path = "/home/user/.bitcoin/signet/.cookie"
+                addr = "127.0.0.1:38332"
+                signet_challenge = "{}"
+                "#,
+                network, challenge
+            );
+            toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str")
+        };
+
+        let parsed = config(
+            "signet",
+            "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be43051ae",
+        );
+        parsed.check().unwrap();
+        assert_eq!(
+            parsed.bitcoind_config.unwrap().signet_challenge.as_deref(),
+            Some("512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be43051ae")
+        );
+
+        // Not a script.
+        config("signet", "not hex").check().unwrap_err();
+        // Not on signet.
+        config("testnet", "51").check().unwrap_err();
+    }
+
     #[test]
     fn config_directory() {
         let filepath = config_file_path().expect("Getting config file path");
@@ -18,6 +18,9 @@ use miniscript::bitcoin::{
     util::{bip32, psbt::PartiallySignedTransaction as Psbt},
 };
 
+/// Number of blocks after which a coinbase output may be spent.
+pub const COINBASE_MATURITY: i32 = 100;
+
 pub trait DatabaseInterface: Send {
     fn connection(&self) -> Box<dyn DatabaseConnection>;
 }
@@ -80,6 +83,14 @@ pub trait DatabaseConnection {
         address: &bitcoin::Address,
     ) -> Option<(bip32::ChildNumber, bool)>;
 
+    /// Get the receive or change address at this derivation index, if it was derived already
+    /// when populating the addresses mapping.
+    fn address_by_derivation_index(
+        &mut self,
+        index: bip32::ChildNumber,
+        is_change: bool,
+    ) -> Option<bitcoin::Address>;
+
     /// Get all our coins, past or present, spent or not.
     fn coins(&mut self, coin_type: CoinType) -> HashMap<bitcoin::OutPoint, Coin>;
 
@@ -223,6 +234,20 @@ impl DatabaseConnection for SqliteConn {
             .map(|db_addr| (db_addr.derivation_index, address == &db_addr.change_address))
     }
 
+    fn address_by_derivation_index(
+        &mut self,
+        index: bip32::ChildNumber,
+        is_change: bool,
+    ) -> Option<bitcoin::Address> {
+        self.db_address_by_index(index).map(|db_addr| {
+            if is_change {
+                db_addr.change_address
+            } else {
+                db_addr.receive_address
+            }
+        })
+    }
+
     fn coins_by_outpoints(
         &mut self,
         outpoints: &[bitcoin::OutPoint],
@@ -286,6 +311,10 @@ pub struct Coin {
     pub is_change: bool,
     pub spend_txid: Option<bitcoin::Txid>,
     pub spend_block: Option<SpendBlock>,
+    pub is_coinbase: bool,
+    pub is_rbf: bool,
+    /// The transaction we had seen spending this coin, if it was replaced by the confirmed one.
+    pub replaced_spend_txid: Option<bitcoin::Txid>,
 }
 
 impl std::convert::From<DbCoin> for Coin {
@@ -299,6 +328,9 @@ impl std::convert::From<DbCoin> for Coin {
             is_change,
             spend_txid,
             spend_block,
+            is_coinbase,
+            is_rbf,
+            replaced_spend_txid,
             ..
         } = db_coin;
         Coin {
@@ -310,6 +342,9 @@ impl std::convert::From<DbCoin> for Coin {
             is_change,
             spend_txid,
             spend_block: spend_block.map(SpendBlock::from),
+            is_coinbase,
+            is_rbf,
+            replaced_spend_txid,
         }
     }
 }
@@ -322,6 +357,31 @@ impl Coin {
     pub fn is_spent(&self) -> bool {
         self.spend_txid.is_some()
     }
+
+    /// Whether this coin is the output of a coinbase transaction that can't be spent yet. A
+    /// coinbase output may only be spent in a block at least `COINBASE_MATURITY` blocks after
+    /// the one it was created in.
+    pub fn is_immature(&self, tip_height: i32) -> bool {
+        self.is_coinbase
+            && self
+                .block_height
+                .map(|height| tip_height + 1 - height < COINBASE_MATURITY)
+                .unwrap_or(true)
+    }
+
+    /// Whether this coin is still unconfirmed and the transaction creating it signals
+    /// replaceability (BIP125). Such a payment may be replaced or cancelled by its sender.
+    pub fn is_replaceable(&self) -> bool {
+        self.is_rbf && !self.is_confirmed()
+    }
+
+    /// Whether the transaction spending this coin is buried deep enough in the chain to be
+    /// considered final. An unconfirmed spend is never final.
+    pub fn is_spend_final(&self, tip_height: i32, final_spend_confs: u32) -> bool {
+        self.spend_block
+            .map(|block| tip_height + 1 - block.height >= final_spend_confs as i32)
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -330,3 +390,47 @@ pub enum CoinType {
     Unspent,
     Spent,
 }
+
+/// Get the txids of the stored Spend transactions which can never be confirmed: one of the coins
+/// they spend was spent by another transaction, which reached `final_spend_confs` confirmations.
+pub fn obsolete_spends(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    final_spend_confs: u32,
+) -> Vec<bitcoin::Txid> {
+    let tip_height = match db_conn.chain_tip() {
+        Some(tip) => tip.height,
+        None => return Vec::new(),
+    };
+    let spends = db_conn.list_spend();
+    let outpoints: Vec<bitcoin::OutPoint> = spends
+        .iter()
+        .flat_map(|psbt| {
+            psbt.unsigned_tx
+                .input
+                .iter()
+                .map(|txin| txin.previous_output)
+        })
+        .collect();
+    let coins = db_conn.coins_by_outpoints(&outpoints);
+
+    spends
+        .into_iter()
+        .filter_map(|psbt| {
+            let txid = psbt.unsigned_tx.txid();
+            let replaced = psbt.unsigned_tx.input.iter().any(|txin| {
+                coins
+                    .get(&txin.previous_output)
+                    .map(|coin| {
+                        coin.spend_txid != Some(txid)
+                            && coin.is_spend_final(tip_height, final_spend_confs)
+                    })
+                    .unwrap_or(false)
+            });
+            if replaced {
+                Some(txid)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
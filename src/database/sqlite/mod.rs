@@ -14,7 +14,10 @@ use crate::{
     database::{
         sqlite::{
             schema::{DbAddress, DbCoin, DbSpendTransaction, DbTip, DbWallet},
-            utils::{create_fresh_db, db_exec, db_query, db_tx_query, LOOK_AHEAD_LIMIT},
+            utils::{
+                create_fresh_db, db_exec, db_query, db_tx_query, maybe_apply_migration,
+                LOOK_AHEAD_LIMIT,
+            },
         },
         Coin, CoinType,
     },
@@ -31,7 +34,7 @@ use miniscript::bitcoin::{
     util::{bip32, psbt::PartiallySignedTransaction as Psbt},
 };
 
-const DB_VERSION: i64 = 0;
+const DB_VERSION: i64 = 3;
 
 #[derive(Debug)]
 pub enum SqliteDbError {
@@ -108,6 +111,9 @@ impl SqliteDb {
             return Err(SqliteDbError::FileNotFound(db_path));
         }
 
+        // Upgrade databases created by a previous version of the software.
+        maybe_apply_migration(&db_path)?;
+
         Ok(SqliteDb { db_path })
     }
 
@@ -126,8 +132,8 @@ impl SqliteDb {
     ) -> Result<(), SqliteDbError> {
         let mut conn = self.connection()?;
 
-        // Check if there database isn't from the future.
-        // NOTE: we'll do migration there eventually. Until then be strict on the check.
+        // Check if there database isn't from the future. Migrations from older versions were
+        // already applied when opening it.
         let db_version = conn.db_version();
         if db_version != DB_VERSION {
             return Err(SqliteDbError::UnsupportedVersion(db_version));
@@ -346,8 +352,8 @@ impl SqliteConn {
             for coin in coins {
                 let deriv_index: u32 = coin.derivation_index.into();
                 db_tx.execute(
-                    "INSERT INTO coins (wallet_id, txid, vout, amount_sat, derivation_index, is_change) \
-                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    "INSERT INTO coins (wallet_id, txid, vout, amount_sat, derivation_index, is_change, is_coinbase, is_rbf) \
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
                     rusqlite::params![
                         WALLET_ID,
                         coin.outpoint.txid.to_vec(),
@@ -355,6 +361,8 @@ impl SqliteConn {
                         coin.amount.to_sat(),
                         deriv_index,
                         coin.is_change,
+                        coin.is_coinbase,
+                        coin.is_rbf,
                     ],
                 )?;
             }
@@ -423,7 +431,9 @@ impl SqliteConn {
         db_exec(&mut self.conn, |db_tx| {
             for (outpoint, spend_txid, height, time) in outpoints {
                 db_tx.execute(
-                    "UPDATE coins SET spend_txid = ?1, spend_block_height = ?2, spend_block_time = ?3 WHERE txid = ?4 AND vout = ?5",
+                    "UPDATE coins SET spend_txid = ?1, spend_block_height = ?2, spend_block_time = ?3, \
+                     replaced_spend_txid = CASE WHEN spend_txid IS NOT NULL AND spend_txid != ?1 THEN spend_txid ELSE replaced_spend_txid END \
+                     WHERE txid = ?4 AND vout = ?5",
                     rusqlite::params![
                         spend_txid.to_vec(),
                         height,
@@ -450,6 +460,19 @@ impl SqliteConn {
         .pop()
     }
 
+    /// Get the addresses at this derivation index, if they were derived already.
+    pub fn db_address_by_index(&mut self, index: bip32::ChildNumber) -> Option<DbAddress> {
+        let index: u32 = index.into();
+        db_query(
+            &mut self.conn,
+            "SELECT * FROM addresses WHERE derivation_index = ?1",
+            rusqlite::params![index],
+            |row| row.try_into(),
+        )
+        .expect("Db must not fail")
+        .pop()
+    }
+
     pub fn db_coins(&mut self, outpoints: &[bitcoin::OutPoint]) -> Vec<DbCoin> {
         // SELECT * FROM coins WHERE (txid, vout) IN ((txidA, voutA), (txidB, voutB));
         let mut query = "SELECT * FROM coins WHERE (txid, vout) IN (VALUES ".to_string();
@@ -665,6 +688,59 @@ mod tests {
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn db_migration_v0() {
+        let (tmp_dir, options, _, db) = dummy_db();
+        let db_path = db.db_path.clone();
+
+        // Turn the fresh database into a version 0 one by removing the coinbase and RBF flags, and
+        // the replaced spends.
+        {
+            let mut conn = rusqlite::Connection::open(&db_path).unwrap();
+            db_exec(&mut conn, |tx| {
+                tx.execute_batch(
+                    "ALTER TABLE coins DROP COLUMN replaced_spend_txid; \
+                     ALTER TABLE coins DROP COLUMN is_rbf; \
+                     ALTER TABLE coins DROP COLUMN is_coinbase; \
+                     UPDATE version SET version = 0;",
+                )
+            })
+            .unwrap();
+        }
+        assert_eq!(db.connection().unwrap().db_version(), 0);
+
+        // Opening it again upgrades it. Coins can be stored and retrieved as usual.
+        let secp = secp256k1::Secp256k1::verification_only();
+        let db = SqliteDb::new(db_path, None, &secp).unwrap();
+        db.sanity_check(bitcoin::Network::Bitcoin, &options.main_descriptor)
+            .unwrap();
+        let mut conn = db.connection().unwrap();
+        assert_eq!(conn.db_version(), DB_VERSION);
+        let coin = Coin {
+            outpoint: bitcoin::OutPoint::from_str(
+                "6f0dc85a369b44458eba3a1f0ea5b5935d563afb6994f70f5b0094e05be1676c:1",
+            )
+            .unwrap(),
+            block_height: None,
+            block_time: None,
+            amount: bitcoin::Amount::from_sat(98765),
+            derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
+            is_change: false,
+            is_coinbase: true,
+            is_rbf: true,
+            replaced_spend_txid: None,
+            spend_txid: None,
+            spend_block: None,
+        };
+        conn.new_unspent_coins(&[coin]);
+        let db_coins = conn.coins(CoinType::All);
+        assert_eq!(db_coins.len(), 1);
+        assert!(db_coins[0].is_coinbase);
+        assert!(db_coins[0].is_rbf);
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn db_tip_update() {
         let (tmp_dir, options, _, db) = dummy_db();
@@ -714,6 +790,9 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(98765),
                 derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
                 is_change: false,
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
                 spend_block: None,
             };
@@ -747,6 +826,9 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(1111),
                 derivation_index: bip32::ChildNumber::from_normal_idx(103).unwrap(),
                 is_change: true,
+                is_coinbase: false,
+                is_rbf: false,
+                replaced_spend_txid: None,
                 spend_txid: None,
                 spend_block: None,
             };
@@ -839,6 +921,20 @@ mod tests {
             assert!(coin.spend_block.is_some());
             assert_eq!(coin.spend_block.as_ref().unwrap().time, time);
             assert_eq!(coin.spend_block.unwrap().height, height);
+            assert!(coin.replaced_spend_txid.is_none());
+
+            // If the spend of the other one is replaced by another transaction, it is recorded.
+            let replaced_txid = bitcoin::Txid::from_slice(&[1; 32][..]).unwrap();
+            let replacement_txid = bitcoin::Txid::from_slice(&[2; 32][..]).unwrap();
+            conn.spend_coins(&[(coin_b.outpoint, replaced_txid)]);
+            conn.confirm_spend(&[(coin_b.outpoint, replacement_txid, height, time)]);
+            let coin = conn.db_coins(&[coin_b.outpoint]).pop().unwrap();
+            assert_eq!(coin.spend_txid, Some(replacement_txid));
+            assert_eq!(coin.replaced_spend_txid, Some(replaced_txid));
+            // And kept if the replacement is confirmed again after a reorg.
+            conn.confirm_spend(&[(coin_b.outpoint, replacement_txid, height + 1, time)]);
+            let coin = conn.db_coins(&[coin_b.outpoint]).pop().unwrap();
+            assert_eq!(coin.replaced_spend_txid, Some(replaced_txid));
         }
 
         fs::remove_dir_all(tmp_dir).unwrap();
@@ -877,6 +973,14 @@ mod tests {
                 .address(options.bitcoind_network);
             let db_addr = conn.db_address(&addr).unwrap();
             assert_eq!(db_addr.derivation_index, 199.into());
+            // It can be queried by index too.
+            assert_eq!(
+                conn.db_address_by_index(199.into())
+                    .unwrap()
+                    .receive_address,
+                addr
+            );
+            assert!(conn.db_address_by_index(200.into()).is_none());
 
             // And not for the 200th one.
             let addr = options
@@ -974,6 +1078,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -987,6 +1094,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(100).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -1000,6 +1110,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(1000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: Some(
                         bitcoin::Txid::from_str(
                             "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
@@ -1021,6 +1134,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(10000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -1034,6 +1150,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(100000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: Some(
                         bitcoin::Txid::from_str(
                             "7477017f992cdc7ba08acafb77cb3b5bc0f42ac340d3e1e1da0785bdda20d5f6",
@@ -1185,6 +1304,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -1198,6 +1320,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(100).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -1211,6 +1336,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(1000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: Some(
                         bitcoin::Txid::from_str(
                             "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
@@ -1232,6 +1360,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(10000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: None,
                     spend_block: None,
                 },
@@ -1245,6 +1376,9 @@ mod tests {
                     amount: bitcoin::Amount::from_sat(98765),
                     derivation_index: bip32::ChildNumber::from_normal_idx(100000).unwrap(),
                     is_change: false,
+                    is_coinbase: false,
+                    is_rbf: false,
+                    replaced_spend_txid: None,
                     spend_txid: Some(
                         bitcoin::Txid::from_str(
                             "7477017f992cdc7ba08acafb77cb3b5bc0f42ac340d3e1e1da0785bdda20d5f6",
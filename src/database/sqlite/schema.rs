@@ -41,6 +41,15 @@ CREATE TABLE wallets (
  *
  * The 'spend_block_height' and 'spend_block.time' are only present if the spending
  * transaction for this coin exists and was confirmed.
+ *
+ * The 'is_coinbase' field is set for outputs of a coinbase transaction, which can only be
+ * spent once they reached maturity.
+ *
+ * The 'is_rbf' field is set for coins whose creating transaction signaled replaceability
+ * (BIP125) when it was first seen unconfirmed.
+ *
+ * The 'replaced_spend_txid' field is set if the transaction we had seen spending this coin was
+ * replaced by the one which got confirmed (for instance through RBF).
  */
 CREATE TABLE coins (
     id INTEGER PRIMARY KEY NOT NULL,
@@ -55,6 +64,9 @@ CREATE TABLE coins (
     spend_txid BLOB,
     spend_block_height INTEGER,
     spend_block_time INTEGER,
+    is_coinbase BOOLEAN NOT NULL DEFAULT 0 CHECK (is_coinbase IN (0,1)),
+    is_rbf BOOLEAN NOT NULL DEFAULT 0 CHECK (is_rbf IN (0,1)),
+    replaced_spend_txid BLOB,
     UNIQUE (txid, vout),
     FOREIGN KEY (wallet_id) REFERENCES wallets (id)
         ON UPDATE RESTRICT
@@ -165,6 +177,9 @@ pub struct DbCoin {
     pub is_change: bool,
     pub spend_txid: Option<bitcoin::Txid>,
     pub spend_block: Option<DbSpendBlock>,
+    pub is_coinbase: bool,
+    pub is_rbf: bool,
+    pub replaced_spend_txid: Option<bitcoin::Txid>,
 }
 
 impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
@@ -197,6 +212,11 @@ impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
             height,
             time: spend_time.expect("Must be there if height is"),
         });
+        let is_coinbase: bool = row.get(12)?;
+        let is_rbf: bool = row.get(13)?;
+        let replaced_spend_txid: Option<Vec<u8>> = row.get(14)?;
+        let replaced_spend_txid = replaced_spend_txid
+            .map(|txid| encode::deserialize(&txid).expect("We only store valid txids"));
 
         Ok(DbCoin {
             id,
@@ -209,6 +229,9 @@ impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
             is_change,
             spend_txid,
             spend_block,
+            is_coinbase,
+            is_rbf,
+            replaced_spend_txid,
         })
     }
 }
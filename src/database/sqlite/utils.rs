@@ -134,3 +134,95 @@ pub fn create_fresh_db(
 
     Ok(())
 }
+
+/// Get the version of the database, whatever the version of the software.
+fn db_version(conn: &mut rusqlite::Connection) -> Result<i64, SqliteDbError> {
+    Ok(db_query(
+        conn,
+        "SELECT version FROM version",
+        rusqlite::params![],
+        |row| {
+            let version: i64 = row.get(0)?;
+            Ok(version)
+        },
+    )?
+    .pop()
+    .expect("There is always a row in the version table"))
+}
+
+// The first version of the database did not record whether a coin is a coinbase output.
+fn migrate_v0_to_v1(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "ALTER TABLE coins ADD COLUMN is_coinbase BOOLEAN NOT NULL DEFAULT 0 CHECK (is_coinbase IN (0,1))",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 1", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+// The second version of the database did not record whether the transaction creating a coin
+// signaled replaceability.
+fn migrate_v1_to_v2(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "ALTER TABLE coins ADD COLUMN is_rbf BOOLEAN NOT NULL DEFAULT 0 CHECK (is_rbf IN (0,1))",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 2", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+// The third version of the database did not record the spending transactions which were replaced
+// by the confirmed one.
+fn migrate_v2_to_v3(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |tx| {
+        tx.execute(
+            "ALTER TABLE coins ADD COLUMN replaced_spend_txid BLOB",
+            rusqlite::params![],
+        )?;
+        tx.execute("UPDATE version SET version = 3", rusqlite::params![])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Check the database version and if necessary apply the migrations to upgrade it to the current
+/// one.
+pub fn maybe_apply_migration(db_path: &path::Path) -> Result<(), SqliteDbError> {
+    let mut conn = rusqlite::Connection::open(db_path)?;
+
+    // Iteratively apply the database migrations necessary.
+    loop {
+        let version = db_version(&mut conn)?;
+        match version {
+            DB_VERSION => {
+                log::info!("Database is up to date.");
+                return Ok(());
+            }
+            0 => {
+                log::warn!("Upgrading database from version 0 to version 1.");
+                migrate_v0_to_v1(&mut conn)?;
+                log::warn!("Migration from database version 0 to version 1 successful.");
+            }
+            1 => {
+                log::warn!("Upgrading database from version 1 to version 2.");
+                migrate_v1_to_v2(&mut conn)?;
+                log::warn!("Migration from database version 1 to version 2 successful.");
+            }
+            2 => {
+                log::warn!("Upgrading database from version 2 to version 3.");
+                migrate_v2_to_v3(&mut conn)?;
+                log::warn!("Migration from database version 2 to version 3 successful.");
+            }
+            _ => return Err(SqliteDbError::UnsupportedVersion(version)),
+        }
+    }
+}
@@ -2,21 +2,23 @@ use miniscript::{
     bitcoin::{
         self,
         blockdata::transaction::Sequence,
-        hashes::{hash160, ripemd160, sha256},
+        hashes::{hash160, ripemd160, sha256, Hash},
         secp256k1,
         util::{
             bip32,
-            psbt::{Input as PsbtIn, Psbt},
+            psbt::{Input as PsbtIn, Output as PsbtOut, Psbt},
+            taproot::{LeafVersion, TapLeafHash},
         },
     },
     descriptor, hash256,
     miniscript::{decode::Terminal, Miniscript},
     policy::{Liftable, Semantic as SemanticPolicy},
-    translate_hash_clone, ForEachKey, MiniscriptKey, ScriptContext, ToPublicKey, TranslatePk,
-    Translator,
+    translate_hash_clone, ForEachKey, MiniscriptKey, Satisfier, ScriptContext, SigType,
+    ToPublicKey, TranslatePk, Translator,
 };
 
 use std::{
+    cmp,
     collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     error, fmt, str, sync,
@@ -36,6 +38,9 @@ fn wu_to_vb(vb: usize) -> usize {
 #[derive(Debug)]
 pub enum LianaDescError {
     InsaneTimelock(u32),
+    /// An absolute timelock (`after()`) may only be used in a recovery path, along with its
+    /// relative timelock (`older()`).
+    AbsoluteTimelock,
     InvalidKey(Box<descriptor::DescriptorPublicKey>),
     DuplicateKey(Box<descriptor::DescriptorPublicKey>),
     Miniscript(miniscript::Error),
@@ -55,6 +60,12 @@ impl std::fmt::Display for LianaDescError {
             Self::InsaneTimelock(tl) => {
                 write!(f, "Timelock value '{}' isn't valid or safe to use", tl)
             }
+            Self::AbsoluteTimelock => {
+                write!(
+                    f,
+                    "An absolute timelock ('after()') may only be used in a recovery path, in addition to its relative timelock ('older()')."
+                )
+            }
             Self::InvalidKey(key) => {
                 write!(
                     f,
@@ -243,6 +254,157 @@ fn key_origin(
     }
 }
 
+// The x-coordinate of the "Nothing Up My Sleeve" point suggested by BIP341, that no one knows the
+// discrete logarithm of.
+const UNSPENDABLE_KEY: &str = "0250929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+// An xpub for the unspendable key, used as the internal key of Taproot descriptors whose primary
+// path isn't a single key. The keys derived from it are unspendable too, since the tweak is known.
+fn unspendable_xpub(network: bitcoin::Network) -> bip32::ExtendedPubKey {
+    bip32::ExtendedPubKey {
+        network,
+        depth: 0,
+        parent_fingerprint: Default::default(),
+        child_number: 0.into(),
+        public_key: UNSPENDABLE_KEY
+            .parse::<secp256k1::PublicKey>()
+            .expect("Valid public key"),
+        chain_code: bip32::ChainCode::from(&[0u8; 32][..]),
+    }
+}
+
+// The unspendable key as a multipath key for the receive and change keychains.
+fn unspendable_desc_key(network: bitcoin::Network) -> descriptor::DescriptorPublicKey {
+    descriptor::DescriptorPublicKey::MultiXPub(descriptor::DescriptorMultiXKey {
+        origin: None,
+        xkey: unspendable_xpub(network),
+        derivation_paths: descriptor::DerivPaths::new(vec![
+            bip32::DerivationPath::from(vec![0.into()]),
+            bip32::DerivationPath::from(vec![1.into()]),
+        ])
+        .expect("Two paths of the same length"),
+        wildcard: descriptor::Wildcard::Unhardened,
+    })
+}
+
+fn is_unspendable_desc_key(key: &descriptor::DescriptorPublicKey) -> bool {
+    match key {
+        descriptor::DescriptorPublicKey::MultiXPub(xpub) => {
+            xpub.xkey.public_key == unspendable_xpub(xpub.xkey.network).public_key
+        }
+        _ => false,
+    }
+}
+
+// Whether this derived key was derived from the unspendable xpub. Its fingerprint doesn't depend
+// on the network.
+fn is_unspendable_derived_key(key: &DerivedPublicKey) -> bool {
+    key.origin.0 == unspendable_xpub(bitcoin::Network::Bitcoin).fingerprint()
+}
+
+// All the keys of a Liana descriptor but the unspendable internal key of a Taproot descriptor.
+fn desc_keys(
+    desc: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+) -> Vec<descriptor::DescriptorPublicKey> {
+    let mut keys = Vec::new();
+    desc.for_each_key(|key| {
+        if !is_unspendable_desc_key(key) {
+            keys.push(key.clone());
+        }
+        true
+    });
+    keys
+}
+
+// The semantic policy of a P2WSH or Taproot Liana descriptor. Under Taproot the key path is
+// one of the spending paths, unless the internal key is unspendable.
+fn liana_policy(
+    desc: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+) -> SemanticPolicy<descriptor::DescriptorPublicKey> {
+    match desc {
+        descriptor::Descriptor::Wsh(wsh) => match wsh.as_inner() {
+            descriptor::WshInner::Ms(ms) => ms.lift().expect("Lifting can't fail on a Miniscript"),
+            descriptor::WshInner::SortedMulti(_) => unreachable!("Checked at parsing time"),
+        },
+        descriptor::Descriptor::Tr(tr) => {
+            let mut subs: Vec<_> = tr
+                .iter_scripts()
+                .map(|(_, ms)| ms.lift().expect("Lifting can't fail on a Miniscript"))
+                .collect();
+            if !is_unspendable_desc_key(tr.internal_key()) {
+                subs.push(SemanticPolicy::Key(tr.internal_key().clone()));
+            }
+            SemanticPolicy::Threshold(1, subs)
+        }
+        _ => unreachable!("Liana descriptors are always P2WSH or Taproot"),
+    }
+    .normalized()
+}
+
+// Check the keys of all the spending paths of a descriptor to be created are valid, and the
+// timelocks of the recovery paths too.
+fn check_paths_keys(
+    owner_keys: &LianaDescKeys,
+    recovery_paths: &BTreeMap<u16, LianaDescKeys>,
+) -> Result<(), LianaDescError> {
+    if recovery_paths.is_empty() {
+        return Err(LianaDescError::IncompatibleDesc);
+    }
+
+    // We require the locktime to:
+    //  - not be disabled
+    //  - be in number of blocks
+    //  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
+    //  - be positive (Miniscript requires it not to be 0)
+    //
+    // All this is achieved through asking for a 16-bit integer.
+    if recovery_paths.contains_key(&0) {
+        return Err(LianaDescError::InsaneTimelock(0));
+    }
+
+    // Check all keys are valid according to our standard (this checks all are multipath keys).
+    let all_keys = owner_keys
+        .keys()
+        .iter()
+        .chain(recovery_paths.values().flat_map(|keys| keys.keys().iter()));
+    if let Some(key) = all_keys.clone().find(|k| !is_valid_desc_key(k)) {
+        return Err(LianaDescError::InvalidKey((*key).clone().into()));
+    }
+
+    // Check for key duplicates. They are invalid in (nonmalleable) miniscripts.
+    let mut key_set = HashSet::new();
+    for key in all_keys {
+        let xpub = match key {
+            descriptor::DescriptorPublicKey::MultiXPub(ref multi_xpub) => multi_xpub.xkey,
+            _ => unreachable!("Just checked it was a multixpub above"),
+        };
+        if key_set.contains(&xpub) {
+            return Err(LianaDescError::DuplicateKey(key.clone().into()));
+        }
+        key_set.insert(xpub);
+    }
+    assert!(!key_set.is_empty());
+
+    Ok(())
+}
+
+// The Miniscript for a recovery path: the keys after the timelock.
+fn recovery_branch<Ctx: ScriptContext>(
+    heir_keys: LianaDescKeys,
+    timelock: u16,
+) -> Miniscript<descriptor::DescriptorPublicKey, Ctx> {
+    let heir_timelock = Terminal::Older(Sequence::from_height(timelock));
+    Miniscript::from_ast(Terminal::AndV(
+        Miniscript::from_ast(Terminal::Verify(heir_keys.into_miniscript(true).into()))
+            .expect("Well typed")
+            .into(),
+        Miniscript::from_ast(heir_timelock)
+            .expect("Well typed")
+            .into(),
+    ))
+    .expect("Well typed")
+}
+
 /// The keys in one of the two spending paths of a Liana descriptor.
 /// May either be a single key, or between 2 and 20 keys along with a threshold (between two and
 /// the number of keys).
@@ -280,14 +442,19 @@ impl LianaDescKeys {
         &self.keys
     }
 
-    pub fn into_miniscript(
+    /// Get the Miniscript for these keys. Under Taproot, a multisig is a `multi_a()`.
+    pub fn into_miniscript<Ctx: ScriptContext>(
         mut self,
         as_hash: bool,
-    ) -> Miniscript<descriptor::DescriptorPublicKey, miniscript::Segwitv0> {
+    ) -> Miniscript<descriptor::DescriptorPublicKey, Ctx> {
         if let Some(thresh) = self.thresh {
             assert!(self.keys.len() >= 2 && self.keys.len() <= 20);
-            Miniscript::from_ast(Terminal::Multi(thresh, self.keys))
-                .expect("multi is a valid Miniscript")
+            Miniscript::from_ast(if Ctx::sig_type() == SigType::Schnorr {
+                Terminal::MultiA(thresh, self.keys)
+            } else {
+                Terminal::Multi(thresh, self.keys)
+            })
+            .expect("multi is a valid Miniscript")
         } else {
             assert_eq!(self.keys.len(), 1);
             let key = self.keys.pop().expect("Length was just asserted");
@@ -304,6 +471,25 @@ impl LianaDescKeys {
     }
 }
 
+/// How the receive and change keys are derived from one of the xpubs of a
+/// [MultipathDescriptor]. The receive and change paths are relative to the xpub and don't include
+/// the final wildcard step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDerivation {
+    pub origin: (bip32::Fingerprint, bip32::DerivationPath),
+    pub receive_path: bip32::DerivationPath,
+    pub change_path: bip32::DerivationPath,
+}
+
+impl KeyDerivation {
+    /// Whether the receive and change keys are derived directly from the xpub, as in
+    /// `xpub/<0;1>/*`. Anything else uses extra derivation steps before the multipath step,
+    /// which other wallets and signing devices may not expect.
+    pub fn is_standard(&self) -> bool {
+        self.receive_path.len() == 1
+    }
+}
+
 /// An [InheritanceDescriptor] that contains multipath keys for (and only for) the receive keychain
 /// and the change keychain.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -328,12 +514,49 @@ impl fmt::Display for MultipathDescriptor {
     }
 }
 
-fn is_single_key_or_multisig(policy: &SemanticPolicy<descriptor::DescriptorPublicKey>) -> bool {
+// A recovery path may require an absolute timelock in addition to its relative timelock. Get it,
+// along with the policy of the path without it. Both timelocks being mandatory, the policy is
+// normalized as `thresh(n, after(x), older(y), ..)` with `n` the number of subs.
+fn split_absolute_timelock(
+    policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
+) -> Result<
+    (
+        Option<bitcoin::PackedLockTime>,
+        SemanticPolicy<descriptor::DescriptorPublicKey>,
+    ),
+    LianaDescError,
+> {
+    if policy.absolute_timelocks().is_empty() {
+        return Ok((None, policy));
+    }
+    let (k, subs) = match policy {
+        SemanticPolicy::Threshold(k, subs) if k == subs.len() => (k, subs),
+        _ => return Err(LianaDescError::AbsoluteTimelock),
+    };
+    let (afters, subs): (Vec<_>, Vec<_>) = subs
+        .into_iter()
+        .partition(|sub| matches!(sub, SemanticPolicy::After(_)));
+    if afters.len() != 1
+        || !subs
+            .iter()
+            .any(|sub| matches!(sub, SemanticPolicy::Older(_)))
+        || subs.iter().any(|sub| !sub.absolute_timelocks().is_empty())
+    {
+        return Err(LianaDescError::AbsoluteTimelock);
+    }
+    let locktime = match afters[0] {
+        SemanticPolicy::After(locktime) => locktime,
+        _ => unreachable!("Only after() subs were partitioned out"),
+    };
+    Ok((Some(locktime), SemanticPolicy::Threshold(k - 1, subs)))
+}
+
+// Whether this policy only involves keys, possibly within (nested) thresholds. That is, whether
+// it's a single key, a multisig or a threshold of those.
+fn is_keys_policy(policy: &SemanticPolicy<descriptor::DescriptorPublicKey>) -> bool {
     match policy {
         SemanticPolicy::Key(..) => true,
-        SemanticPolicy::Threshold(_, subs) => {
-            subs.iter().all(|sub| matches!(sub, SemanticPolicy::Key(_)))
-        }
+        SemanticPolicy::Threshold(_, subs) => subs.iter().all(is_keys_policy),
         _ => false,
     }
 }
@@ -342,69 +565,79 @@ impl str::FromStr for MultipathDescriptor {
     type Err = LianaDescError;
 
     fn from_str(s: &str) -> Result<MultipathDescriptor, Self::Err> {
-        let wsh_desc = descriptor::Wsh::<descriptor::DescriptorPublicKey>::from_str(s)
+        let multi_desc = descriptor::Descriptor::<descriptor::DescriptorPublicKey>::from_str(s)
             .map_err(LianaDescError::Miniscript)?;
-        let ms = match wsh_desc.as_inner() {
-            descriptor::WshInner::Ms(ms) => ms,
+
+        // Must be a P2WSH Miniscript or a Taproot descriptor. The internal key of a Taproot
+        // descriptor may be unspendable, in which case it must be the one we use.
+        let keys: Vec<descriptor::DescriptorPublicKey> = match &multi_desc {
+            descriptor::Descriptor::Wsh(wsh_desc) => match wsh_desc.as_inner() {
+                descriptor::WshInner::Ms(ms) => ms.iter_pk().collect(),
+                _ => return Err(LianaDescError::IncompatibleDesc),
+            },
+            descriptor::Descriptor::Tr(tr_desc) => {
+                let internal_key = tr_desc.internal_key();
+                let mut keys: Vec<_> = tr_desc
+                    .iter_scripts()
+                    .flat_map(|(_, ms)| ms.iter_pk())
+                    .collect();
+                match internal_key {
+                    descriptor::DescriptorPublicKey::MultiXPub(xpub)
+                        if is_unspendable_desc_key(internal_key) =>
+                    {
+                        if internal_key != &unspendable_desc_key(xpub.xkey.network) {
+                            return Err(LianaDescError::InvalidKey(internal_key.clone().into()));
+                        }
+                    }
+                    _ => keys.push(internal_key.clone()),
+                }
+                keys
+            }
             _ => return Err(LianaDescError::IncompatibleDesc),
         };
-        let invalid_key = ms.iter_pk().find_map(|pk| {
-            if is_valid_desc_key(&pk) {
-                None
-            } else {
-                Some(pk)
-            }
-        });
-        if let Some(key) = invalid_key {
+        if let Some(key) = keys.into_iter().find(|pk| !is_valid_desc_key(pk)) {
             return Err(LianaDescError::InvalidKey(key.into()));
         }
 
         // Semantic of the Miniscript must be either the owner now, or the heir after
         // a timelock.
-        let policy = ms
-            .lift()
-            .expect("Lifting can't fail on a Miniscript")
-            .normalized();
+        let policy = liana_policy(&multi_desc);
         let subs = match policy {
             SemanticPolicy::Threshold(1, subs) => Some(subs),
             _ => None,
         }
         .ok_or(LianaDescError::IncompatibleDesc)?;
-        if subs.len() != 2 {
+        if subs.len() < 2 {
             return Err(LianaDescError::IncompatibleDesc);
         }
 
-        // Must always contain a non-timelocked primary spending path and a timelocked recovery
-        // path. The PathInfo constructors perform the checks that each path is well formed.
+        // Must always contain a single non-timelocked primary spending path and at least one
+        // timelocked recovery path. Two recovery paths can't share the same relative timelock. A
+        // recovery path may also require an absolute timelock. The PathInfo constructors perform
+        // the checks that each path is well formed.
+        let mut has_primary_path = false;
+        let mut timelocks = HashSet::with_capacity(subs.len());
         for sub in subs {
-            if is_single_key_or_multisig(&sub) {
+            if is_keys_policy(&sub) {
+                if has_primary_path {
+                    return Err(LianaDescError::IncompatibleDesc);
+                }
                 PathInfo::from_primary_path(sub)?;
+                has_primary_path = true;
             } else {
-                PathInfo::from_recovery_path(sub)?;
+                let (_, sub) = split_absolute_timelock(sub)?;
+                let (timelock, _) = PathInfo::from_recovery_path(sub)?;
+                if !timelocks.insert(timelock) {
+                    return Err(LianaDescError::IncompatibleDesc);
+                }
             }
         }
+        if !has_primary_path {
+            return Err(LianaDescError::IncompatibleDesc);
+        }
 
         // All good, construct the multipath descriptor.
-        let multi_desc = descriptor::Descriptor::Wsh(wsh_desc);
-
-        // Compute the receive and change "sub" descriptors right away. According to our pubkey
-        // check above, there must be only two of those, 0 and 1.
-        // We use /0/* for receiving and /1/* for change.
-        // FIXME: don't rely on into_single_descs()'s ordering.
-        let mut singlepath_descs = multi_desc
-            .clone()
-            .into_single_descriptors()
-            .expect("Can't error, all paths have the same length")
-            .into_iter();
-        assert_eq!(singlepath_descs.len(), 2);
-        let receive_desc = InheritanceDescriptor(singlepath_descs.next().expect("First of 2"));
-        let change_desc = InheritanceDescriptor(singlepath_descs.next().expect("Second of 2"));
-
-        Ok(MultipathDescriptor {
-            multi_desc,
-            receive_desc,
-            change_desc,
-        })
+        Ok(MultipathDescriptor::from_multi_desc(multi_desc))
     }
 }
 
@@ -425,26 +658,35 @@ impl PartialEq<descriptor::Descriptor<descriptor::DescriptorPublicKey>> for Inhe
 pub enum PathInfo {
     Single(descriptor::DescriptorPublicKey),
     Multi(usize, Vec<descriptor::DescriptorPublicKey>),
+    /// A threshold of sub-paths, at least one of which isn't a single key. For instance
+    /// "A and 2 of B, C, D".
+    Nested(usize, Vec<PathInfo>),
 }
 
 impl PathInfo {
     /// Get the information about the primary spending path.
     /// Returns None if the policy does not describe the primary spending path of a Liana
-    /// descriptor (that is, a set of keys).
+    /// descriptor (that is, a set of keys, possibly organized in nested thresholds).
     pub fn from_primary_path(
         policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
     ) -> Result<PathInfo, LianaDescError> {
         match policy {
             SemanticPolicy::Key(key) => Ok(PathInfo::Single(key)),
             SemanticPolicy::Threshold(k, subs) => {
-                let keys: Result<_, LianaDescError> = subs
-                    .into_iter()
-                    .map(|sub| match sub {
-                        SemanticPolicy::Key(key) => Ok(key),
-                        _ => Err(LianaDescError::IncompatibleDesc),
-                    })
-                    .collect();
-                Ok(PathInfo::Multi(k, keys?))
+                if subs.iter().all(|sub| matches!(sub, SemanticPolicy::Key(_))) {
+                    let keys = subs
+                        .into_iter()
+                        .filter_map(|sub| match sub {
+                            SemanticPolicy::Key(key) => Some(key),
+                            _ => None,
+                        })
+                        .collect();
+                    Ok(PathInfo::Multi(k, keys))
+                } else {
+                    let paths: Result<_, LianaDescError> =
+                        subs.into_iter().map(PathInfo::from_primary_path).collect();
+                    Ok(PathInfo::Nested(k, paths?))
+                }
             }
             _ => Err(LianaDescError::IncompatibleDesc),
         }
@@ -476,7 +718,7 @@ impl PathInfo {
                 .ok_or(LianaDescError::IncompatibleDesc)??;
             let keys_sub = subs
                 .into_iter()
-                .find(is_single_key_or_multisig)
+                .find(is_keys_policy)
                 .ok_or(LianaDescError::IncompatibleDesc)?;
             PathInfo::from_primary_path(keys_sub).map(|info| (tl_value, info))
         } else if k == subs.len() && subs.len() > 2 {
@@ -510,6 +752,8 @@ impl PathInfo {
 
     /// Get the required number of keys for spending through this path, and the set of keys
     /// that can be used to provide a signature for this path.
+    /// For a nested threshold, the required number of keys is the minimum number of signatures
+    /// needed to satisfy it.
     pub fn thresh_origins(&self) -> (usize, HashSet<(bip32::Fingerprint, bip32::DerivationPath)>) {
         match self {
             PathInfo::Single(key) => {
@@ -531,6 +775,19 @@ impl PathInfo {
                     })
                     .collect(),
             ),
+            PathInfo::Nested(k, paths) => {
+                let mut thresholds = Vec::with_capacity(paths.len());
+                let mut origins = HashSet::new();
+                for path in paths {
+                    let (threshold, path_origins) = path.thresh_origins();
+                    thresholds.push(threshold);
+                    origins.extend(path_origins);
+                }
+                // The cheapest way to satisfy the threshold is through the sub-paths requiring
+                // the least signatures.
+                thresholds.sort_unstable();
+                (thresholds.into_iter().take(*k).sum(), origins)
+            }
         }
     }
 
@@ -540,6 +797,29 @@ impl PathInfo {
         &self,
         all_pubkeys_signed: impl Iterator<Item = &'a (bip32::Fingerprint, bip32::DerivationPath)>,
     ) -> PathSpendInfo {
+        if let PathInfo::Nested(k, paths) = self {
+            let all_pubkeys_signed: Vec<_> = all_pubkeys_signed.cloned().collect();
+            let mut signed_pubkeys = HashMap::new();
+            let mut missing_sigs = Vec::with_capacity(paths.len());
+            for path in paths {
+                let path_info = path.spend_info(all_pubkeys_signed.iter());
+                missing_sigs.push(path_info.threshold.saturating_sub(path_info.sigs_count));
+                // Keys are never duplicated across sub-paths.
+                signed_pubkeys.extend(path_info.signed_pubkeys);
+            }
+
+            // The signatures still missing are those of the k sub-paths closest to being
+            // satisfied. Report the progress against the minimum number of signatures needed.
+            missing_sigs.sort_unstable();
+            let missing_sigs: usize = missing_sigs.into_iter().take(*k).sum();
+            let threshold = self.thresh_origins().0;
+            return PathSpendInfo {
+                threshold,
+                sigs_count: threshold.saturating_sub(missing_sigs),
+                signed_pubkeys,
+            };
+        }
+
         let mut signed_pubkeys = HashMap::new();
         let mut sigs_count = 0;
         let (threshold, origins) = self.thresh_origins();
@@ -577,18 +857,30 @@ impl PathInfo {
 }
 
 /// Information about the descriptor: how many keys are present in each path, what's the timelock
-/// of the recovery path, what's the threshold if there are multiple keys, etc..
+/// of each recovery path, what's the threshold if there are multiple keys, etc..
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
 pub struct LianaDescInfo {
     primary_path: PathInfo,
-    recovery_path: (u16, PathInfo),
+    /// The recovery paths by timelock. There is always at least one.
+    recovery_paths: BTreeMap<u16, PathInfo>,
+    /// The absolute timelocks required by some of the recovery paths, by relative timelock.
+    recovery_locktimes: BTreeMap<u16, bitcoin::PackedLockTime>,
 }
 
 impl LianaDescInfo {
-    fn new(primary_path: PathInfo, recovery_path: (u16, PathInfo)) -> LianaDescInfo {
+    fn new(
+        primary_path: PathInfo,
+        recovery_paths: BTreeMap<u16, PathInfo>,
+        recovery_locktimes: BTreeMap<u16, bitcoin::PackedLockTime>,
+    ) -> LianaDescInfo {
+        assert!(!recovery_paths.is_empty());
+        assert!(recovery_locktimes
+            .keys()
+            .all(|timelock| recovery_paths.contains_key(timelock)));
         LianaDescInfo {
             primary_path,
-            recovery_path,
+            recovery_paths,
+            recovery_locktimes,
         }
     }
 
@@ -596,18 +888,38 @@ impl LianaDescInfo {
         &self.primary_path
     }
 
-    /// Timelock and path info for the recovery path.
+    /// Timelock and path info for the recovery path available first, the one with the shortest
+    /// timelock.
     pub fn recovery_path(&self) -> (u16, &PathInfo) {
-        (self.recovery_path.0, &self.recovery_path.1)
+        self.recovery_paths
+            .iter()
+            .next()
+            .map(|(timelock, path)| (*timelock, path))
+            .expect("There is always at least one recovery path")
+    }
+
+    /// Path info for all the recovery paths, by timelock.
+    pub fn recovery_paths(&self) -> &BTreeMap<u16, PathInfo> {
+        &self.recovery_paths
+    }
+
+    /// The absolute timelock the recovery path with this relative timelock requires, if any. The
+    /// nLockTime of a transaction spending through this path must be set to it.
+    pub fn recovery_locktime(&self, timelock: u16) -> Option<bitcoin::LockTime> {
+        self.recovery_locktimes
+            .get(&timelock)
+            .map(|locktime| (*locktime).into())
     }
 }
 
 /// Partial spend information for a specific spending path within a descriptor.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct PathSpendInfo {
-    /// The required number of signatures to provide to spend through this path.
+    /// The required number of signatures to provide to spend through this path. For a nested
+    /// threshold, this is the minimum number of signatures needed.
     pub threshold: usize,
-    /// The number of signatures provided.
+    /// The number of signatures provided. For a nested threshold, only the signatures counting
+    /// toward the closest satisfaction of the path are accounted for.
     pub sigs_count: usize,
     /// The keys for which a signature was provided and the number (always >=1) of
     /// signatures provided for this key.
@@ -619,9 +931,9 @@ pub struct PathSpendInfo {
 pub struct PartialSpendInfo {
     /// Number of signatures present for the primary path
     primary_path: PathSpendInfo,
-    /// Number of signatures present for the recovery path, only present if the path is available
-    /// in the first place.
-    recovery_path: Option<PathSpendInfo>,
+    /// Number of signatures present for each recovery path, by timelock. Only the paths
+    /// available in the first place are present.
+    recovery_paths: BTreeMap<u16, PathSpendInfo>,
 }
 
 impl PartialSpendInfo {
@@ -630,10 +942,21 @@ impl PartialSpendInfo {
         &self.primary_path
     }
 
-    /// Get the number of signatures present for the recovery path. Only present if the path is
-    /// available in the first place.
-    pub fn recovery_path(&self) -> &Option<PathSpendInfo> {
-        &self.recovery_path
+    /// Get the number of signatures present for each available recovery path, by timelock.
+    pub fn recovery_paths(&self) -> &BTreeMap<u16, PathSpendInfo> {
+        &self.recovery_paths
+    }
+
+    /// Get the timelock and the number of signatures present for the recovery path the
+    /// signatures are for: the first available path they satisfy, or else the available path
+    /// with the longest timelock, for which the transaction was most likely created. None if no
+    /// recovery path is available.
+    pub fn recovery_path(&self) -> Option<(u16, &PathSpendInfo)> {
+        self.recovery_paths
+            .iter()
+            .find(|(_, path)| path.sigs_count >= path.threshold)
+            .or_else(|| self.recovery_paths.iter().next_back())
+            .map(|(timelock, path)| (*timelock, path))
     }
 }
 
@@ -643,54 +966,37 @@ impl MultipathDescriptor {
         heir_keys: LianaDescKeys,
         timelock: u16,
     ) -> Result<MultipathDescriptor, LianaDescError> {
-        // We require the locktime to:
-        //  - not be disabled
-        //  - be in number of blocks
-        //  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
-        //  - be positive (Miniscript requires it not to be 0)
-        //
-        // All this is achieved through asking for a 16-bit integer.
-        if timelock == 0 {
-            return Err(LianaDescError::InsaneTimelock(timelock as u32));
-        }
-        let timelock = Sequence::from_height(timelock);
-
-        // Check all keys are valid according to our standard (this checks all are multipath keys).
-        let all_keys = owner_keys.keys().iter().chain(heir_keys.keys().iter());
-        if let Some(key) = all_keys.clone().find(|k| !is_valid_desc_key(k)) {
-            return Err(LianaDescError::InvalidKey((*key).clone().into()));
-        }
+        let mut recovery_paths = BTreeMap::new();
+        recovery_paths.insert(timelock, heir_keys);
+        Self::new_with_recovery_paths(owner_keys, recovery_paths)
+    }
 
-        // Check for key duplicates. They are invalid in (nonmalleable) miniscripts.
-        let mut key_set = HashSet::new();
-        for key in all_keys {
-            let xpub = match key {
-                descriptor::DescriptorPublicKey::MultiXPub(ref multi_xpub) => multi_xpub.xkey,
-                _ => unreachable!("Just checked it was a multixpub above"),
-            };
-            if key_set.contains(&xpub) {
-                return Err(LianaDescError::DuplicateKey(key.clone().into()));
-            }
-            key_set.insert(xpub);
-        }
-        assert!(!key_set.is_empty());
+    /// Create a descriptor with several recovery paths, each made of a set of keys spending after
+    /// its own timelock. For instance a single key after 6 months and a 2-of-3 after a year.
+    pub fn new_with_recovery_paths(
+        owner_keys: LianaDescKeys,
+        recovery_paths: BTreeMap<u16, LianaDescKeys>,
+    ) -> Result<MultipathDescriptor, LianaDescError> {
+        check_paths_keys(&owner_keys, &recovery_paths)?;
 
-        // Create the timelocked spending path. If there is a single key we make it a pk_h() in
-        // order to save on the script size (since we assume the timelocked recovery path will
+        // Create the timelocked spending paths. If there is a single key we make it a pk_h() in
+        // order to save on the script size (since we assume the timelocked recovery paths will
         // seldom be used).
-        let heir_timelock = Terminal::Older(timelock);
-        let heir_branch = Miniscript::from_ast(Terminal::AndV(
-            Miniscript::from_ast(Terminal::Verify(heir_keys.into_miniscript(true).into()))
-                .expect("Well typed")
-                .into(),
-            Miniscript::from_ast(heir_timelock)
-                .expect("Well typed")
-                .into(),
-        ))
-        .expect("Well typed");
+        let mut heir_branches = recovery_paths
+            .into_iter()
+            .map(|(timelock, heir_keys)| recovery_branch(heir_keys, timelock))
+            .collect::<Vec<_>>();
+
+        // If there are several recovery paths, the spender selects which one is used. They are
+        // ordered by increasing timelock.
+        let mut heir_branch = heir_branches.pop().expect("Checked not empty above");
+        while let Some(branch) = heir_branches.pop() {
+            heir_branch = Miniscript::from_ast(Terminal::OrI(branch.into(), heir_branch.into()))
+                .expect("Well typed");
+        }
 
-        // Combine the timelocked spending path with the simple "primary" path. For the primary key
-        // we don't use a pkh since it's the one that will likely always be used.
+        // Combine the timelocked spending path(s) with the simple "primary" path. For the primary
+        // key we don't use a pkh since it's the one that will likely always be used.
         let tl_miniscript = Miniscript::from_ast(Terminal::OrD(
             owner_keys.into_miniscript(false).into(),
             heir_branch.into(),
@@ -702,8 +1008,61 @@ impl MultipathDescriptor {
             descriptor::Wsh::new(tl_miniscript).expect("Must pass sanity checks"),
         );
 
-        // Compute the receive and change "sub" descriptors right away. According to our pubkey
-        // check above, there must be only two of those, 0 and 1.
+        Ok(MultipathDescriptor::from_multi_desc(multi_desc))
+    }
+
+    /// Create a Taproot descriptor with the same spending paths as
+    /// [MultipathDescriptor::new_with_recovery_paths]. If the primary path is a single key, it is
+    /// the internal key and spending through the primary path is a key path spend. Otherwise the
+    /// internal key is unspendable and the primary path is a leaf of the tree like the recovery
+    /// paths.
+    pub fn new_taproot(
+        mut owner_keys: LianaDescKeys,
+        recovery_paths: BTreeMap<u16, LianaDescKeys>,
+    ) -> Result<MultipathDescriptor, LianaDescError> {
+        check_paths_keys(&owner_keys, &recovery_paths)?;
+
+        let mut leaves: Vec<Miniscript<descriptor::DescriptorPublicKey, miniscript::Tap>> =
+            Vec::with_capacity(recovery_paths.len() + 1);
+        let internal_key = if owner_keys.thresh.is_none() {
+            owner_keys.keys.pop().expect("A single key")
+        } else {
+            let network = match owner_keys.keys.first() {
+                Some(descriptor::DescriptorPublicKey::MultiXPub(xpub)) => xpub.xkey.network,
+                _ => unreachable!("Checked all keys are multixpubs above"),
+            };
+            leaves.push(owner_keys.into_miniscript(false));
+            unspendable_desc_key(network)
+        };
+        leaves.extend(
+            recovery_paths
+                .into_iter()
+                .map(|(timelock, heir_keys)| recovery_branch(heir_keys, timelock)),
+        );
+
+        // The leaves are ordered by likelihood of being used: the primary path if it's a leaf,
+        // then the recovery paths by increasing timelock. The most likely ones are the
+        // shallowest in the tree, making their spends cheaper.
+        let mut leaves = leaves
+            .into_iter()
+            .rev()
+            .map(|ms| descriptor::TapTree::Leaf(sync::Arc::new(ms)));
+        let mut tree = leaves.next().expect("There is always a recovery path");
+        for leaf in leaves {
+            tree = descriptor::TapTree::Tree(sync::Arc::new(leaf), sync::Arc::new(tree));
+        }
+        let multi_desc = descriptor::Descriptor::Tr(
+            descriptor::Tr::new(internal_key, Some(tree)).map_err(LianaDescError::Miniscript)?,
+        );
+
+        Ok(MultipathDescriptor::from_multi_desc(multi_desc))
+    }
+
+    // Compute the receive and change "sub" descriptors. According to our pubkey checks, there
+    // must be only two of those, 0 and 1.
+    fn from_multi_desc(
+        multi_desc: descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+    ) -> MultipathDescriptor {
         // We use /0/* for receiving and /1/* for change.
         // FIXME: don't rely on into_single_descs()'s ordering.
         let mut singlepath_descs = multi_desc
@@ -715,11 +1074,16 @@ impl MultipathDescriptor {
         let receive_desc = InheritanceDescriptor(singlepath_descs.next().expect("First of 2"));
         let change_desc = InheritanceDescriptor(singlepath_descs.next().expect("Second of 2"));
 
-        Ok(MultipathDescriptor {
+        MultipathDescriptor {
             multi_desc,
             receive_desc,
             change_desc,
-        })
+        }
+    }
+
+    /// Whether this is a Taproot descriptor, as opposed to a P2WSH one.
+    pub fn is_taproot(&self) -> bool {
+        matches!(self.multi_desc, descriptor::Descriptor::Tr(..))
     }
 
     /// Whether all xpubs contained in this descriptor are for the passed expected network.
@@ -733,6 +1097,22 @@ impl MultipathDescriptor {
         })
     }
 
+    /// The master fingerprint of the first xpub contained in this descriptor which is not for
+    /// the passed expected network, if any.
+    pub fn xpub_not_for_net(&self, expected_net: bitcoin::Network) -> Option<bip32::Fingerprint> {
+        let mut mismatch = None;
+        self.multi_desc.for_each_key(|key| {
+            if let descriptor::DescriptorPublicKey::MultiXPub(xpub) = key {
+                if xpub.xkey.network != expected_net {
+                    mismatch = Some(key.master_fingerprint());
+                    return false;
+                }
+            }
+            true
+        });
+        mismatch
+    }
+
     /// Get the descriptor for receiving addresses.
     pub fn receive_descriptor(&self) -> &InheritanceDescriptor {
         &self.receive_desc
@@ -743,74 +1123,99 @@ impl MultipathDescriptor {
         &self.change_desc
     }
 
+    /// Get how the receive and change keys are derived for each xpub of this descriptor, in the
+    /// order the keys appear in the descriptor.
+    pub fn keys_derivation(&self) -> Vec<KeyDerivation> {
+        desc_keys(&self.multi_desc)
+            .into_iter()
+            .map(|key| match key {
+                descriptor::DescriptorPublicKey::MultiXPub(xpub) => {
+                    // Checked at parsing time: always two paths (receive then change), all keys
+                    // have an origin.
+                    let paths = xpub.derivation_paths.paths();
+                    KeyDerivation {
+                        origin: xpub.origin.expect("Checked at parsing time"),
+                        receive_path: paths[0].clone(),
+                        change_path: paths[1].clone(),
+                    }
+                }
+                _ => unreachable!("All keys are multixpubs"),
+            })
+            .collect()
+    }
+
+    /// A short code identifying this descriptor, for instance to check with a cosigner over the
+    /// phone that both have the same wallet without reading the whole descriptor. It is derived
+    /// from the descriptor string: two descriptors get the same code if and only if they have
+    /// the same keys, in the same order, under the same policy.
+    pub fn verification_code(&self) -> String {
+        let hash = sha256::Hash::hash(self.multi_desc.to_string().as_bytes());
+        hash[..6]
+            .chunks(2)
+            .map(|c| format!("{:02X}{:02X}", c[0], c[1]))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
     /// Parse information about this descriptor
     pub fn info(&self) -> LianaDescInfo {
-        // Get the Miniscript
-        let wsh_desc = match &self.multi_desc {
-            descriptor::Descriptor::Wsh(desc) => desc,
-            _ => unreachable!(),
-        };
-        let ms = match wsh_desc.as_inner() {
-            descriptor::WshInner::Ms(ms) => ms,
-            _ => unreachable!(),
-        };
-
-        // Lift the semantic policy from the Miniscript
-        let policy = ms
-            .lift()
-            .expect("Lifting can't fail on a Miniscript")
-            .normalized();
+        // Lift the semantic policy from the Miniscript(s)
+        let policy = liana_policy(&self.multi_desc);
         let subs = match policy {
             SemanticPolicy::Threshold(1, subs) => subs,
-            _ => unreachable!("The policy is always 'one of the primary or the recovery path'"),
+            _ => unreachable!("The policy is always 'one of the primary or the recovery paths'"),
         };
-        // For now we only ever allow a single recovery path.
-        assert_eq!(subs.len(), 2);
 
-        // Fetch the two spending paths' semantic policies. The primary path is identified as the
+        // Parse information about each spending path. The primary path is identified as the
         // only one that isn't timelocked.
-        let (prim_path_sub, reco_path_sub) =
-            subs.into_iter()
-                .fold((None, None), |(mut prim_sub, mut reco_sub), sub| {
-                    if is_single_key_or_multisig(&sub) {
-                        prim_sub = Some(sub);
-                    } else {
-                        reco_sub = Some(sub);
-                    }
-                    (prim_sub, reco_sub)
-                });
-        let (prim_path_sub, reco_path_sub) = (
-            prim_path_sub.expect("Must be present"),
-            reco_path_sub.expect("Must be present"),
-        );
-
-        // Now parse information about each spending path.
-        let primary_path = PathInfo::from_primary_path(prim_path_sub)
-            .expect("Must always be a set of keys without timelock");
-        let reco_path = PathInfo::from_recovery_path(reco_path_sub)
-            .expect("The recovery path policy must always be a timelock along with a set of keys.");
+        let mut primary_path = None;
+        let mut recovery_paths = BTreeMap::new();
+        let mut recovery_locktimes = BTreeMap::new();
+        for sub in subs {
+            if is_keys_policy(&sub) {
+                primary_path = Some(
+                    PathInfo::from_primary_path(sub)
+                        .expect("Must always be a set of keys without timelock"),
+                );
+            } else {
+                let (locktime, sub) = split_absolute_timelock(sub)
+                    .expect("An absolute timelock may only be used along a relative one.");
+                let (timelock, path) = PathInfo::from_recovery_path(sub).expect(
+                    "The recovery path policy must always be a timelock along with a set of keys.",
+                );
+                if let Some(locktime) = locktime {
+                    recovery_locktimes.insert(timelock, locktime);
+                }
+                recovery_paths.insert(timelock, path);
+            }
+        }
 
-        LianaDescInfo::new(primary_path, reco_path)
+        LianaDescInfo::new(
+            primary_path.expect("Must be present"),
+            recovery_paths,
+            recovery_locktimes,
+        )
     }
 
-    /// Get the value (in blocks) of the relative timelock for the heir's spending path.
+    /// Get the value (in blocks) of the relative timelock for the heir's spending path. If there
+    /// are several recovery paths, the one of the path available first.
     pub fn timelock_value(&self) -> u32 {
         // TODO: make it return a u16
-        self.info().recovery_path.0 as u32
+        self.info().recovery_path().0 as u32
     }
 
     /// Get the maximum size in WU of a satisfaction for this descriptor.
     pub fn max_sat_weight(&self) -> usize {
         self.multi_desc
             .max_satisfaction_weight()
-            .expect("Cannot fail for P2WSH")
+            .expect("Cannot fail for P2WSH or Taproot")
     }
 
     /// Get the maximum size in vbytes (rounded up) of a satisfaction for this descriptor.
     pub fn max_sat_vbytes(&self) -> usize {
         self.multi_desc
             .max_satisfaction_weight()
-            .expect("Cannot fail for P2WSH")
+            .expect("Cannot fail for P2WSH or Taproot")
             .checked_add(WITNESS_FACTOR - 1)
             .unwrap()
             .checked_div(WITNESS_FACTOR)
@@ -824,6 +1229,46 @@ impl MultipathDescriptor {
         32 + 4 + 4 + 1 + wu_to_vb(self.max_sat_weight())
     }
 
+    /// Get the size in vbytes (rounded up) of the cheapest satisfaction through the primary path,
+    /// or through the recovery path with the given timelock. Unlike [Self::max_sat_vbytes] it
+    /// depends on the spending path used. Returns None if there is no such recovery path.
+    pub fn path_sat_vbytes(
+        &self,
+        recovery_timelock: Option<u16>,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Option<usize> {
+        let info = self.info();
+        let path = match recovery_timelock {
+            None => info.primary_path(),
+            Some(timelock) => info.recovery_paths().get(&timelock)?,
+        };
+        let (_, origins) = path.thresh_origins();
+
+        // The size of the satisfaction doesn't depend on the derivation index.
+        let der_desc = self
+            .receive_descriptor()
+            .derive(bip32::ChildNumber::from(0), secp);
+        let mut satisfier = PathSatisfier {
+            origins,
+            recovery: recovery_timelock.is_some(),
+            key_spend: false,
+        };
+        if let descriptor::Descriptor::Tr(ref tr) = der_desc.0 {
+            satisfier.key_spend = !satisfier.recovery && satisfier.is_path_key(tr.internal_key());
+        }
+        let (witness, _) = der_desc
+            .0
+            .get_satisfaction(&satisfier)
+            .expect("A spending path can always be satisfied with its keys");
+        let witness_size = bitcoin::VarInt(witness.len() as u64).len()
+            + witness
+                .iter()
+                .map(|item| bitcoin::VarInt(item.len() as u64).len() + item.len())
+                .sum::<usize>();
+
+        Some(wu_to_vb(witness_size))
+    }
+
     /// Get some information about a PSBT input spending Liana coins.
     /// This analysis assumes that:
     /// - The PSBT input actually spend a Liana coin for this descriptor. Otherwise the analysis will be off.
@@ -833,28 +1278,51 @@ impl MultipathDescriptor {
         psbt_in: &PsbtIn,
         txin: &bitcoin::TxIn,
     ) -> PartialSpendInfo {
-        // Get the identifier of all the keys that signed this transaction.
-        let pubkeys_signed = psbt_in
+        // Get the identifier of all the keys that signed this transaction. Under Taproot the
+        // signature for a key path spend is for the internal key.
+        let mut pubkeys_signed: Vec<&(bip32::Fingerprint, bip32::DerivationPath)> = psbt_in
             .partial_sigs
             .iter()
-            .filter_map(|(pk, _)| psbt_in.bip32_derivation.get(&pk.inner));
+            .filter_map(|(pk, _)| psbt_in.bip32_derivation.get(&pk.inner))
+            .chain(
+                psbt_in
+                    .tap_script_sigs
+                    .keys()
+                    .map(|(pk, _)| pk)
+                    .chain(
+                        psbt_in
+                            .tap_internal_key
+                            .as_ref()
+                            .filter(|_| psbt_in.tap_key_sig.is_some()),
+                    )
+                    .filter_map(|pk| psbt_in.tap_key_origins.get(pk))
+                    .map(|(_, origin)| origin),
+            )
+            .collect();
+        // A key present in several leaves may have signed for each of them.
+        pubkeys_signed.sort();
+        pubkeys_signed.dedup();
+        let pubkeys_signed = pubkeys_signed.into_iter();
 
         // Determine the structure of the descriptor. Then compute the spend info for the primary
-        // and recovery paths. Only provide the spend info for the recovery path if it is available
-        // (ie if the nSequence is >= to the chosen CSV value).
+        // and recovery paths. Only provide the spend info for a recovery path if it is available
+        // (ie if the nSequence is >= to its CSV value).
         let desc_info = self.info();
         let primary_path = desc_info.primary_path.spend_info(pubkeys_signed.clone());
-        let recovery_path = if txin.sequence.is_height_locked()
-            && txin.sequence.0 >= desc_info.recovery_path.0 as u32
-        {
-            Some(desc_info.recovery_path.1.spend_info(pubkeys_signed))
+        let recovery_paths = if txin.sequence.is_height_locked() {
+            desc_info
+                .recovery_paths
+                .iter()
+                .filter(|(timelock, _)| txin.sequence.0 >= **timelock as u32)
+                .map(|(timelock, path)| (*timelock, path.spend_info(pubkeys_signed.clone())))
+                .collect()
         } else {
-            None
+            BTreeMap::new()
         };
 
         PartialSpendInfo {
             primary_path,
-            recovery_path,
+            recovery_paths,
         }
     }
 
@@ -863,7 +1331,8 @@ impl MultipathDescriptor {
     // our descriptor too..
     /// Get some information about a PSBT spending Liana coins.
     /// This analysis assumes that:
-    /// - The PSBT only contains input that spends Liana coins. Otherwise the analysis will be off.
+    /// - The inputs spending Liana coins can be told apart from the others (in a collaborative
+    /// transaction) by their BIP32 derivations. Otherwise all inputs are analyzed.
     /// - The PSBT is consistent across inputs (the sequence is the same across inputs, the
     /// signatures are either absent or present for all inputs, ..)
     /// - The provided signatures are valid for this script.
@@ -877,20 +1346,48 @@ impl MultipathDescriptor {
             return Err(LianaDescError::InsanePsbt);
         }
 
+        // Only analyze the inputs spending our coins, the other participants of a collaborative
+        // transaction may sign theirs as they wish.
+        let origins: Vec<(bip32::Fingerprint, bip32::DerivationPath)> = self
+            .keys_derivation()
+            .into_iter()
+            .map(|key| key.origin)
+            .collect();
+        let is_ours = |psbt_in: &PsbtIn| {
+            psbt_in
+                .bip32_derivation
+                .values()
+                .chain(psbt_in.tap_key_origins.values().map(|(_, origin)| origin))
+                .any(|(fg, path)| {
+                    origins.iter().any(|(origin_fg, origin_path)| {
+                        origin_fg == fg && path.as_ref().starts_with(origin_path.as_ref())
+                    })
+                })
+        };
+        let mut inputs: Vec<(&PsbtIn, &bitcoin::TxIn)> = psbt
+            .inputs
+            .iter()
+            .zip(psbt.unsigned_tx.input.iter())
+            .filter(|(psbt_in, _)| is_ours(psbt_in))
+            .collect();
+        if inputs.is_empty() {
+            inputs = psbt
+                .inputs
+                .iter()
+                .zip(psbt.unsigned_tx.input.iter())
+                .collect();
+        }
+
         // We are doing this analysis at a transaction level. We assume that if an input
         // is set to use the recovery path, all are. If one input is signed with a key, all
         // must be.
         // This gets the information needed to analyze the number of signatures from the
         // first input, and checks that this info matches on all inputs.
-        let (mut psbt_ins, mut txins) = (psbt.inputs.iter(), psbt.unsigned_tx.input.iter());
-        let (first_psbt_in, first_txin) = (
-            psbt_ins
-                .next()
-                .expect("We checked at least one is present."),
-            txins.next().expect("We checked at least one is present."),
-        );
-        let spend_info = self.partial_spend_info_txin(first_psbt_in, first_txin);
-        for (psbt_in, txin) in psbt_ins.zip(txins) {
+        let mut inputs = inputs.into_iter();
+        let (first_psbt_in, first_txin) =
+            inputs.next().expect("We checked at least one is present.");
+        let mut spend_info = self.partial_spend_info_txin(first_psbt_in, first_txin);
+        for (psbt_in, txin) in inputs {
             // TODO: maybe it's better to not error if one of the input has more, or different
             // signatures? Instead of erroring we could ignore the superfluous data?
             if txin.sequence != first_txin.sequence
@@ -900,6 +1397,24 @@ impl MultipathDescriptor {
             }
         }
 
+        // A recovery path requiring an absolute timelock is only available if the nLockTime of
+        // the transaction is set to (at least) its value.
+        let desc_info = self.info();
+        let tx_locktime = bitcoin::LockTime::from(psbt.unsigned_tx.lock_time);
+        spend_info.recovery_paths = spend_info
+            .recovery_paths
+            .into_iter()
+            .filter(|(timelock, _)| {
+                desc_info
+                    .recovery_locktime(*timelock)
+                    .map(|locktime| {
+                        locktime.is_same_unit(tx_locktime)
+                            && locktime.to_consensus_u32() <= tx_locktime.to_consensus_u32()
+                    })
+                    .unwrap_or(true)
+            })
+            .collect();
+
         Ok(spend_info)
     }
 }
@@ -963,24 +1478,120 @@ impl InheritanceDescriptor {
     }
 }
 
+// Provides dummy signatures for the keys of a single spending path, in order to compute the
+// size of a satisfaction through this path. The signatures are of the maximum standard size.
+struct PathSatisfier {
+    // The origins of the xpubs of the path.
+    origins: HashSet<(bip32::Fingerprint, bip32::DerivationPath)>,
+    // Whether the path is a recovery path, for which the relative timelock is satisfied. The
+    // keys of each path are distinct, so only the timelock of this path may be used.
+    recovery: bool,
+    // Whether the path is the internal key of a Taproot descriptor.
+    key_spend: bool,
+}
+
+impl PathSatisfier {
+    fn is_path_key(&self, key: &DerivedPublicKey) -> bool {
+        // The keys are derived at /0/* or /1/* from the xpubs.
+        let (fg, der_path) = &key.origin;
+        der_path.len() >= 2
+            && self
+                .origins
+                .contains(&(*fg, der_path[..der_path.len() - 2].into()))
+    }
+
+    fn ecdsa_sig() -> bitcoin::EcdsaSig {
+        // A high R value to get the largest DER encoding.
+        let mut compact = [1; 64];
+        compact[0] = 0x81;
+        bitcoin::EcdsaSig {
+            sig: secp256k1::ecdsa::Signature::from_compact(&compact).expect("Valid signature"),
+            hash_ty: bitcoin::EcdsaSighashType::All,
+        }
+    }
+
+    fn schnorr_sig() -> bitcoin::SchnorrSig {
+        bitcoin::SchnorrSig {
+            sig: secp256k1::schnorr::Signature::from_slice(&[1; 64]).expect("Valid signature"),
+            hash_ty: bitcoin::SchnorrSighashType::Default,
+        }
+    }
+}
+
+impl Satisfier<DerivedPublicKey> for PathSatisfier {
+    fn lookup_ecdsa_sig(&self, key: &DerivedPublicKey) -> Option<bitcoin::EcdsaSig> {
+        if self.is_path_key(key) {
+            Some(Self::ecdsa_sig())
+        } else {
+            None
+        }
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<bitcoin::SchnorrSig> {
+        if self.key_spend {
+            Some(Self::schnorr_sig())
+        } else {
+            None
+        }
+    }
+
+    fn lookup_tap_leaf_script_sig(
+        &self,
+        key: &DerivedPublicKey,
+        _: &TapLeafHash,
+    ) -> Option<bitcoin::SchnorrSig> {
+        if self.is_path_key(key) {
+            Some(Self::schnorr_sig())
+        } else {
+            None
+        }
+    }
+
+    fn check_older(&self, _: Sequence) -> bool {
+        self.recovery
+    }
+
+    fn check_after(&self, _: bitcoin::LockTime) -> bool {
+        self.recovery
+    }
+}
+
 /// Map of a raw public key to the xpub used to derive it and its derivation path
 pub type Bip32Deriv = BTreeMap<secp256k1::PublicKey, (bip32::Fingerprint, bip32::DerivationPath)>;
 
+/// Map of an x-only public key to the Taproot leaves it is used in, the xpub used to derive it and
+/// its derivation path.
+pub type TapKeyOrigins = BTreeMap<
+    secp256k1::XOnlyPublicKey,
+    (
+        Vec<TapLeafHash>,
+        (bip32::Fingerprint, bip32::DerivationPath),
+    ),
+>;
+
 impl DerivedInheritanceDescriptor {
     pub fn address(&self, network: bitcoin::Network) -> bitcoin::Address {
         self.0
             .address(network)
-            .expect("A P2WSH always has an address")
+            .expect("A P2WSH or Taproot descriptor always has an address")
     }
 
     pub fn script_pubkey(&self) -> bitcoin::Script {
         self.0.script_pubkey()
     }
 
+    /// The witness script of a P2WSH descriptor.
+    ///
+    /// # Panics
+    /// - If this is a Taproot descriptor.
     pub fn witness_script(&self) -> bitcoin::Script {
         self.0.explicit_script().expect("Not a Taproot descriptor")
     }
 
+    pub fn is_taproot(&self) -> bool {
+        matches!(self.0, descriptor::Descriptor::Tr(..))
+    }
+
     pub fn bip32_derivations(&self) -> Bip32Deriv {
         let ms = match self.0 {
             descriptor::Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
@@ -989,7 +1600,8 @@ impl DerivedInheritanceDescriptor {
                     unreachable!("None of our descriptors is a sorted multi")
                 }
             },
-            _ => unreachable!("All our descriptors are always P2WSH"),
+            descriptor::Descriptor::Tr(..) => return Bip32Deriv::new(),
+            _ => unreachable!("All our descriptors are always P2WSH or Taproot"),
         };
 
         // For DerivedPublicKey, Pk::Hash == Self.
@@ -997,6 +1609,107 @@ impl DerivedInheritanceDescriptor {
             .map(|k| (k.key.inner, (k.origin.0, k.origin.1)))
             .collect()
     }
+
+    /// The origin of each key of a Taproot descriptor along with the leaves it appears in. The
+    /// internal key is included unless it is unspendable. Empty for a P2WSH descriptor.
+    pub fn tap_key_origins(&self) -> TapKeyOrigins {
+        let tr = match self.0 {
+            descriptor::Descriptor::Tr(ref tr) => tr,
+            _ => return TapKeyOrigins::new(),
+        };
+
+        let mut origins = TapKeyOrigins::new();
+        for (_, ms) in tr.iter_scripts() {
+            let leaf_hash = TapLeafHash::from_script(&ms.encode(), LeafVersion::TapScript);
+            for key in ms.iter_pk() {
+                origins
+                    .entry(key.to_x_only_pubkey())
+                    .or_insert_with(|| (Vec::new(), key.origin.clone()))
+                    .0
+                    .push(leaf_hash);
+            }
+        }
+        let internal_key = tr.internal_key();
+        if !is_unspendable_derived_key(internal_key) {
+            origins
+                .entry(internal_key.to_x_only_pubkey())
+                .or_insert_with(|| (Vec::new(), internal_key.origin.clone()));
+        }
+
+        origins
+    }
+
+    /// Set the information signers need to spend a coin for this descriptor in the PSBT input.
+    pub fn update_psbt_in(&self, psbt_in: &mut PsbtIn) {
+        match self.0 {
+            descriptor::Descriptor::Tr(ref tr) => {
+                let spend_info = tr.spend_info();
+                psbt_in.tap_internal_key = Some(spend_info.internal_key());
+                psbt_in.tap_merkle_root = spend_info.merkle_root();
+                for (_, ms) in tr.iter_scripts() {
+                    let leaf = (ms.encode(), LeafVersion::TapScript);
+                    let control_block = spend_info
+                        .control_block(&leaf)
+                        .expect("The leaf is part of the tree");
+                    psbt_in.tap_scripts.insert(control_block, leaf);
+                }
+                psbt_in.tap_key_origins = self.tap_key_origins();
+            }
+            _ => {
+                psbt_in.witness_script = Some(self.witness_script());
+                psbt_in.bip32_derivation = self.bip32_derivations();
+            }
+        }
+    }
+
+    /// Set the derivation paths of the keys in a PSBT output paying to this descriptor, which
+    /// signals it as change to signing devices.
+    pub fn update_psbt_out(&self, psbt_out: &mut PsbtOut) {
+        match self.0 {
+            descriptor::Descriptor::Tr(ref tr) => {
+                psbt_out.tap_internal_key = Some(tr.internal_key().to_x_only_pubkey());
+                psbt_out.tap_key_origins = self.tap_key_origins();
+            }
+            _ => {
+                psbt_out.bip32_derivation = self.bip32_derivations();
+            }
+        }
+    }
+}
+
+/// The keychain of a descriptor an address was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeychainKind {
+    Receive,
+    Change,
+}
+
+/// Get the keychain and the derivation index of this address if it belongs to the descriptor.
+/// Only the first `gap` indexes of each keychain are derived. The address is matched by its
+/// Script, regardless of its network.
+pub fn address_info(
+    desc: &MultipathDescriptor,
+    addr: &bitcoin::Address,
+    gap: u32,
+) -> Option<(KeychainKind, u32)> {
+    let secp = secp256k1::Secp256k1::verification_only();
+    let script_pubkey = addr.script_pubkey();
+    let keychains = [
+        (KeychainKind::Receive, desc.receive_descriptor()),
+        (KeychainKind::Change, desc.change_descriptor()),
+    ];
+
+    // Hardened indexes can't be derived from an xpub.
+    let gap = cmp::min(gap, 1 << 31);
+    for index in 0..gap {
+        for (kind, keychain) in keychains.iter() {
+            if keychain.derive(index.into(), &secp).script_pubkey() == script_pubkey {
+                return Some((*kind, index));
+            }
+        }
+    }
+
+    None
 }
 
 #[cfg(test)]
@@ -1129,6 +1842,12 @@ mod tests {
     fn inheritance_descriptor_derivation() {
         let secp = secp256k1::Secp256k1::verification_only();
         let desc = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y").unwrap();
+        assert!(desc.all_xpubs_net_is(bitcoin::Network::Testnet));
+        assert_eq!(desc.xpub_not_for_net(bitcoin::Network::Testnet), None);
+        assert_eq!(
+            desc.xpub_not_for_net(bitcoin::Network::Bitcoin),
+            Some(bip32::Fingerprint::from_str("abcdef01").unwrap())
+        );
         let der_desc = desc.receive_descriptor().derive(11.into(), &secp);
         assert_eq!(
             "bc1q26gtczlz03u6juf5cxppapk4sr4fyz53s3g4zs2cgactcahqv6yqc2t8e6",
@@ -1141,6 +1860,36 @@ mod tests {
         assert!(!der_desc.bip32_derivations().is_empty());
     }
 
+    #[test]
+    fn address_info() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let desc = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#2qj59a9y").unwrap();
+
+        let receive_addr = bitcoin::Address::from_str(
+            "bc1q26gtczlz03u6juf5cxppapk4sr4fyz53s3g4zs2cgactcahqv6yqc2t8e6",
+        )
+        .unwrap();
+        assert_eq!(
+            super::address_info(&desc, &receive_addr, 20),
+            Some((KeychainKind::Receive, 11))
+        );
+        // Beyond the gap limit.
+        assert_eq!(super::address_info(&desc, &receive_addr, 11), None);
+
+        let change_addr = desc
+            .change_descriptor()
+            .derive(3.into(), &secp)
+            .address(bitcoin::Network::Testnet);
+        assert_eq!(
+            super::address_info(&desc, &change_addr, 20),
+            Some((KeychainKind::Change, 3))
+        );
+
+        let foreign_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        assert_eq!(super::address_info(&desc, &foreign_addr, 100), None);
+    }
+
     #[test]
     fn inheritance_descriptor_tl_value() {
         let desc = MultipathDescriptor::from_str("wsh(andor(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(1),pk([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))").unwrap();
@@ -1153,6 +1902,53 @@ mod tests {
         assert_eq!(desc.timelock_value(), 0xffff);
     }
 
+    #[test]
+    fn inheritance_descriptor_absolute_timelock() {
+        // Absolute timelock instead of a relative one for the recovery path.
+        let err = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),after(800000))))").unwrap_err();
+        assert!(matches!(err, LianaDescError::AbsoluteTimelock));
+
+        // Both an absolute and a relative timelock in the recovery path. A transaction spending
+        // through it must set its nLockTime to the absolute timelock.
+        let desc = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),and_v(v:after(800000),older(10)))))").unwrap();
+        let info = desc.info();
+        assert_eq!(info.recovery_path().0, 10);
+        assert!(matches!(info.recovery_path().1, PathInfo::Single(_)));
+        assert_eq!(
+            info.recovery_locktime(10),
+            Some(bitcoin::LockTime::from_height(800_000).unwrap())
+        );
+        let secp = secp256k1::Secp256k1::verification_only();
+        assert_eq!(
+            desc.path_sat_vbytes(Some(10), &secp),
+            Some(desc.max_sat_vbytes())
+        );
+
+        // The absolute timelock may be a date, and the recovery path a multisig.
+        let desc = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:multi(2,[abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*,[8a64f2a9]tpubD6NzVbkrYhZ4WmzFjvQrp7sDa4ECUxTi9oby8K4FZkd3XCBtEdKwUiQyYJaxiJo5y42gyDWEczrFpozEjeLxMPxjf2WtkfcbpUdfvNnozWF/<0;1>/*),and_v(v:after(1700000000),older(10)))))").unwrap();
+        let info = desc.info();
+        assert!(matches!(info.recovery_path().1, PathInfo::Multi(2, _)));
+        assert_eq!(
+            info.recovery_locktime(10),
+            Some(bitcoin::LockTime::from_time(1_700_000_000).unwrap())
+        );
+
+        // A recovery path without an absolute timelock doesn't have one.
+        let desc = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),older(10))))").unwrap();
+        assert_eq!(desc.info().recovery_locktime(10), None);
+
+        // Two absolute timelocks in the recovery path.
+        let err = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),and_v(v:after(800000),and_v(v:after(800001),older(10))))))").unwrap_err();
+        assert!(matches!(err, LianaDescError::AbsoluteTimelock));
+
+        // An absolute timelock in the primary path, with a relative one in the recovery path.
+        let err = MultipathDescriptor::from_str("wsh(or_d(and_v(v:pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),after(1700000000)),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),older(10))))").unwrap_err();
+        assert!(matches!(err, LianaDescError::AbsoluteTimelock));
+
+        // Sanity check the same descriptor with only a relative timelock is accepted.
+        MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),older(10))))").unwrap();
+    }
+
     #[test]
     fn inheritance_descriptor_sat_size() {
         let desc = MultipathDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),older(2))))#ravw7jw5").unwrap();
@@ -1177,6 +1973,19 @@ mod tests {
             desc.spender_input_size(),
             32 + 4 + 1 + 4 + wu_to_vb(witness_size),
         );
+
+        // Spending through the recovery path is the most expensive. Through the primary path the
+        // stack is the script and a signature for the owner key.
+        let secp = secp256k1::Secp256k1::verification_only();
+        assert_eq!(
+            desc.path_sat_vbytes(Some(2), &secp),
+            Some(desc.max_sat_vbytes())
+        );
+        assert_eq!(
+            desc.path_sat_vbytes(None, &secp),
+            Some(wu_to_vb(1 + 69 + 73))
+        );
+        assert_eq!(desc.path_sat_vbytes(Some(3), &secp), None);
     }
 
     #[test]
@@ -1213,6 +2022,74 @@ mod tests {
         roundtrip("wsh(or_d(multi(3,[aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*,[aabb0011/10/4893]xpub6Bw79HbNSeS2xXw1sngPE3ehnk1U3iSPCgLYzC9LpN8m9nDuaKLZvkg8QXxL5pDmEmQtYscmUD8B9MkAAZbh6vxPzNXMaLfGQ9Sb3z85qhR/<0;1>/*,[aabbccdd]xpub67zuTXF9Ln4731avKTBSawoVVNRuMfmRvkL7kLUaLBRqma9ZqdHBJg9qx8cPUm3oNQMiXT4TmGovXNoQPuwg17RFcVJ8YrnbcooN7pxVJqC/<0;1>/*),and_v(v:pk([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),older(26352))))#y9l4ldvr");
     }
 
+    #[test]
+    fn keys_derivation() {
+        // The standard form: receive and change keys derived directly from the xpubs.
+        let desc = MultipathDescriptor::from_str("wsh(or_d(pk([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),and_v(v:pkh([aabb0011/10/4893]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*),older(52560))))").unwrap();
+        let keys = desc.keys_derivation();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(
+            keys[0].origin,
+            (
+                bip32::Fingerprint::from_str("aabbccdd").unwrap(),
+                bip32::DerivationPath::master()
+            )
+        );
+        assert_eq!(
+            keys[1].origin,
+            (
+                bip32::Fingerprint::from_str("aabb0011").unwrap(),
+                bip32::DerivationPath::from_str("m/10/4893").unwrap()
+            )
+        );
+        for key in &keys {
+            assert!(key.is_standard());
+            assert_eq!(
+                key.receive_path,
+                bip32::DerivationPath::from_str("m/0").unwrap()
+            );
+            assert_eq!(
+                key.change_path,
+                bip32::DerivationPath::from_str("m/1").unwrap()
+            );
+        }
+
+        // Extra derivation steps before the multipath step are valid, but not standard.
+        let desc = MultipathDescriptor::from_str("wsh(or_d(pk([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),and_v(v:pkh([abcdef01]xpub661MyMwAqRbcFfxf71L4Dx4w5TmyNXrBicTEAM7vLzumxangwATWWgdJPb6xH1JHcJH9S3jNZx3fCnkkB1WyqrqGgavj1rehHcbythmruvZ/24/32/<0;1>/*),older(57600))))").unwrap();
+        let keys = desc.keys_derivation();
+        assert!(keys[0].is_standard());
+        assert!(!keys[1].is_standard());
+        assert_eq!(
+            keys[1].receive_path,
+            bip32::DerivationPath::from_str("m/24/32/0").unwrap()
+        );
+        assert_eq!(
+            keys[1].change_path,
+            bip32::DerivationPath::from_str("m/24/32/1").unwrap()
+        );
+    }
+
+    #[test]
+    fn verification_code() {
+        let desc_str = "wsh(or_d(pk([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),and_v(v:pkh([aabb0011/10/4893]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*),older(52560))))";
+        let desc = MultipathDescriptor::from_str(desc_str).unwrap();
+        let code = desc.verification_code();
+        assert_eq!(code.len(), 14);
+        assert!(code
+            .split('-')
+            .all(|part| part.len() == 4 && part.chars().all(|c| c.is_ascii_hexdigit())));
+
+        // Stable whether or not the descriptor was given with its checksum.
+        let with_checksum = MultipathDescriptor::from_str(&desc.to_string()).unwrap();
+        assert_eq!(with_checksum.verification_code(), code);
+
+        // Another timelock, or the keys swapped, is another wallet.
+        let other = MultipathDescriptor::from_str(&desc_str.replace("52560", "52561")).unwrap();
+        assert_ne!(other.verification_code(), code);
+        let swapped = MultipathDescriptor::from_str("wsh(or_d(pk([aabb0011/10/4893]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*),and_v(v:pkh([aabbccdd]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),older(52560))))").unwrap();
+        assert_ne!(swapped.verification_code(), code);
+    }
+
     fn psbt_from_str(psbt_str: &str) -> Psbt {
         bitcoin::consensus::deserialize(&base64::decode(psbt_str).unwrap()).unwrap()
     }
@@ -1238,29 +2115,45 @@ mod tests {
         assert_eq!(info.primary_path.threshold, 1);
         assert_eq!(info.primary_path.sigs_count, 0);
         assert!(info.primary_path.signed_pubkeys.is_empty());
-        assert!(info.recovery_path.is_none());
+        assert!(info.recovery_path().is_none());
 
         // If we set the sequence too low we still won't have the recovery path info.
         unsigned_single_psbt.unsigned_tx.input[0].sequence =
-            Sequence::from_height(desc_info.recovery_path.0 - 1);
+            Sequence::from_height(desc_info.recovery_path().0 - 1);
         let info = desc.partial_spend_info(&unsigned_single_psbt).unwrap();
-        assert!(info.recovery_path.is_none());
+        assert!(info.recovery_path().is_none());
 
         // Now if we set the sequence at the right value we'll have it.
         unsigned_single_psbt.unsigned_tx.input[0].sequence =
-            Sequence::from_height(desc_info.recovery_path.0);
+            Sequence::from_height(desc_info.recovery_path().0);
         let info = desc.partial_spend_info(&unsigned_single_psbt).unwrap();
-        assert!(info.recovery_path.is_some());
+        assert!(info.recovery_path().is_some());
 
         // Even if it's a bit too high (as long as it's still a block height and activated)
         unsigned_single_psbt.unsigned_tx.input[0].sequence =
-            Sequence::from_height(desc_info.recovery_path.0 + 42);
+            Sequence::from_height(desc_info.recovery_path().0 + 42);
         let info = desc.partial_spend_info(&unsigned_single_psbt).unwrap();
-        let recov_info = info.recovery_path.unwrap();
+        let recov_info = info.recovery_path().unwrap().1;
         assert_eq!(recov_info.threshold, 1);
         assert_eq!(recov_info.sigs_count, 0);
         assert!(recov_info.signed_pubkeys.is_empty());
 
+        // If the recovery path also requires an absolute timelock, it is only available if the
+        // nLockTime of the transaction is set to (at least) its value.
+        let locked_desc = MultipathDescriptor::from_str("wsh(or_d(pk([f5acc2fd]tpubD6NzVbkrYhZ4YgUx2ZLNt2rLYAMTdYysCRzKoLu2BeSHKvzqPaBDvf17GeBPnExUVPkuBpx4kniP964e2MxyzzazcXLptxLXModSVCVEV1T/<0;1>/*),and_v(v:pkh([8a64f2a9]tpubD6NzVbkrYhZ4WmzFjvQrp7sDa4ECUxTi9oby8K4FZkd3XCBtEdKwUiQyYJaxiJo5y42gyDWEczrFpozEjeLxMPxjf2WtkfcbpUdfvNnozWF/<0;1>/*),and_v(v:after(800000),older(10)))))").unwrap();
+        let mut locked_psbt = unsigned_single_psbt.clone();
+        locked_psbt.unsigned_tx.input[0].sequence = Sequence::from_height(10);
+        locked_psbt.unsigned_tx.lock_time = bitcoin::PackedLockTime(799_999);
+        let info = locked_desc.partial_spend_info(&locked_psbt).unwrap();
+        assert!(info.recovery_path().is_none());
+        locked_psbt.unsigned_tx.lock_time = bitcoin::PackedLockTime(800_000);
+        let info = locked_desc.partial_spend_info(&locked_psbt).unwrap();
+        assert!(info.recovery_path().is_some());
+        // A date doesn't satisfy a block height.
+        locked_psbt.unsigned_tx.lock_time = bitcoin::PackedLockTime(1_700_000_000);
+        let info = locked_desc.partial_spend_info(&locked_psbt).unwrap();
+        assert!(info.recovery_path().is_none());
+
         // The same PSBT but with an (invalid) signature for the primary key.
         let mut signed_single_psbt = psbt_from_str("cHNidP8BAHECAAAAAUSHuliRtuCX1S6JxRuDRqDCKkWfKmWL5sV9ukZ/wzvfAAAAAAD9////AogTAAAAAAAAFgAUIxe7UY6LJ6y5mFBoWTOoVispDmdwFwAAAAAAABYAFKqO83TK+t/KdpAt21z2HGC7/Z2FAAAAAAABASsQJwAAAAAAACIAIIIySQjGCTeyx/rKUQx8qobjhJeNCiVCliBJPdyRX6XKIgICNnKlqXPVAFtoGbdlpCo74vCqDArotNKUZS+sBt9rigxIMEUCIQCYZusUL8bdi2PnjWao4bIDDgMQ9Dj2Lcup3/VmkGbYJAIgX/wF5HsqugC5JzvU2cGOmUWtHr2Pg0N4912qogYgDH4BAQVBIQI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDKxzZHapFNYASzIYkEdH9bJz6nnqUG3uBB8kiK1asmgiBgI2cqWpc9UAW2gZt2WkKjvi8KoMCui00pRlL6wG32uKDAz1rML9AAAAAG8AAAAiBgMLcbOxsfLe6+3r1UcjQo77HY0As8OKE4l37yj0/qhIyQyKZPKpAAAAAG8AAAAAAAA=");
         let info = desc.partial_spend_info(&signed_single_psbt).unwrap();
@@ -1274,11 +2167,11 @@ mod tests {
                     .signed_pubkeys
                     .contains_key(&prim_key_origin)
         );
-        assert!(info.recovery_path.is_none());
+        assert!(info.recovery_path().is_none());
 
         // Now enable the recovery path and add a signature for the recovery key.
         signed_single_psbt.unsigned_tx.input[0].sequence =
-            Sequence::from_height(desc_info.recovery_path.0);
+            Sequence::from_height(desc_info.recovery_path().0);
         let recov_pubkey = bitcoin::PublicKey {
             compressed: true,
             inner: *signed_single_psbt.inputs[0]
@@ -1309,7 +2202,7 @@ mod tests {
         assert_eq!(info.primary_path.threshold, 1);
         assert_eq!(info.primary_path.sigs_count, 0);
         assert!(info.primary_path.signed_pubkeys.is_empty());
-        let recov_info = info.recovery_path.unwrap();
+        let recov_info = info.recovery_path().unwrap().1;
         assert_eq!(recov_info.threshold, 1);
         assert_eq!(recov_info.sigs_count, 1);
         assert!(
@@ -1333,12 +2226,12 @@ mod tests {
                     .signed_pubkeys
                     .contains_key(&prim_key_origin)
         );
-        assert!(info.recovery_path.is_none());
+        assert!(info.recovery_path().is_none());
 
         // Enable the recovery path, it should show no recovery sig.
         let mut rec_psbt = psbt.clone();
         for txin in rec_psbt.unsigned_tx.input.iter_mut() {
-            txin.sequence = Sequence::from_height(desc_info.recovery_path.0);
+            txin.sequence = Sequence::from_height(desc_info.recovery_path().0);
         }
         let info = desc.partial_spend_info(&rec_psbt).unwrap();
         assert!(rec_psbt
@@ -1354,7 +2247,7 @@ mod tests {
                     .signed_pubkeys
                     .contains_key(&prim_key_origin)
         );
-        let recov_info = info.recovery_path.unwrap();
+        let recov_info = info.recovery_path().unwrap().1;
         assert_eq!(recov_info.threshold, 1);
         assert_eq!(recov_info.sigs_count, 0);
         assert!(recov_info.signed_pubkeys.is_empty());
@@ -1363,7 +2256,7 @@ mod tests {
         // an error since the analysis is on the whole transaction.
         let mut inconsistent_psbt = psbt.clone();
         inconsistent_psbt.unsigned_tx.input[0].sequence =
-            Sequence::from_height(desc_info.recovery_path.0 + 1);
+            Sequence::from_height(desc_info.recovery_path().0 + 1);
         assert!(desc
             .partial_spend_info(&inconsistent_psbt)
             .unwrap_err()
@@ -1379,6 +2272,33 @@ mod tests {
             .to_string()
             .contains("Analyzed PSBT is inconsistent across inputs."));
 
+        // But an input of another participant in a collaborative transaction, without any of
+        // our keys, is not part of the analysis. Whatever its sequence or signatures.
+        let mut mixed_psbt = psbt.clone();
+        mixed_psbt.unsigned_tx.input.push(bitcoin::TxIn {
+            sequence: Sequence::MAX,
+            ..bitcoin::TxIn::default()
+        });
+        mixed_psbt.inputs.push(PsbtIn {
+            bip32_derivation: vec![(
+                secp256k1::PublicKey::from_str(
+                    "023a664c5617412f0b292665b1fd9d766456a7a3b1614c7e7c5f411200ff1958ef",
+                )
+                .unwrap(),
+                (
+                    bip32::Fingerprint::from_str("00000001").unwrap(),
+                    Vec::new().into(),
+                ),
+            )]
+            .into_iter()
+            .collect(),
+            ..PsbtIn::default()
+        });
+        assert_eq!(
+            desc.partial_spend_info(&mixed_psbt).unwrap(),
+            desc.partial_spend_info(&psbt).unwrap()
+        );
+
         // If we analyze a descriptor with a multisig we'll get the right threshold.
         let desc = MultipathDescriptor::from_str("wsh(or_d(multi(2,[f5acc2fd]tpubD6NzVbkrYhZ4YgUx2ZLNt2rLYAMTdYysCRzKoLu2BeSHKvzqPaBDvf17GeBPnExUVPkuBpx4kniP964e2MxyzzazcXLptxLXModSVCVEV1T/<0;1>/*,[00112233]xpub6FC8vmQGGfSuQGfKG5L73fZ7WjXit8TzfJYDKwTtHkhrbAhU5Kma41oenVq6aMnpgULJRXpQuxnVysyfdpRhVgD6vYe7XLbFDhmvYmDrAVq/<0;1>/*,[aabbccdd]xpub68XtbpvDM19d39wEKdvadHkZ4FGKf4tnryKzAacttp8BLX3uHj7eK8shRnFBhZ2UL83S9dwXe42Qm6eG6BkR1jy8XwUSNBcHKtET7j4V5FB/<0;1>/*),and_v(v:pkh([8a64f2a9]tpubD6NzVbkrYhZ4WmzFjvQrp7sDa4ECUxTi9oby8K4FZkd3XCBtEdKwUiQyYJaxiJo5y42gyDWEczrFpozEjeLxMPxjf2WtkfcbpUdfvNnozWF/<0;1>/*),older(10))))#2kgxuax5").unwrap();
         let info = desc.partial_spend_info(&psbt).unwrap();
@@ -1395,7 +2315,7 @@ mod tests {
                     .signed_pubkeys
                     .contains_key(&prim_key_origin)
         );
-        assert!(info.recovery_path.is_none());
+        assert!(info.recovery_path().is_none());
 
         let desc = MultipathDescriptor::from_str("wsh(or_d(multi(2,[636adf3f/48'/1'/0'/2']tpubDEE9FvWbG4kg4gxDNrALgrWLiHwNMXNs8hk6nXNPw4VHKot16xd2251vwi2M6nsyQTkak5FJNHVHkCcuzmvpSbWHdumX3DxpDm89iTfSBaL/<0;1>/*,[ffd63c8d/48'/1'/0'/2']tpubDExA3EC3iAsPxPhFn4j6gMiVup6V2eH3qKyk69RcTc9TTNRfFYVPad8bJD5FCHVQxyBT4izKsvr7Btd2R4xmQ1hZkvsqGBaeE82J71uTK4N/<0;1>/*),and_v(v:multi(2,[636adf3f/48'/1'/1'/2']tpubDDvF2khuoBBj8vcSjQfa7iKaxsQZE7YjJ7cJL8A8eaneadMPKbHSpoSr4JD1F5LUvWD82HCxdtSppGfrMUmiNbFxrA2EHEVLnrdCFNFe75D/<0;1>/*,[ffd63c8d/48'/1'/1'/2']tpubDFMs44FD4kFt3M7Z317cFh5tdKEGN8tyQRY6Q5gcSha4NtxZfGmTVRMbsD1bWN469LstXU4aVSARDxrvxFCUjHeegfEY2cLSazMBkNCmDPD/<0;1>/*),older(2))))#xcf6jr2r").unwrap();
         let info = desc.info();
@@ -1406,7 +2326,7 @@ mod tests {
                 descriptor::DescriptorPublicKey::from_str("[ffd63c8d/48'/1'/0'/2']tpubDExA3EC3iAsPxPhFn4j6gMiVup6V2eH3qKyk69RcTc9TTNRfFYVPad8bJD5FCHVQxyBT4izKsvr7Btd2R4xmQ1hZkvsqGBaeE82J71uTK4N/<0;1>/*").unwrap(),
             ],
         ));
-        assert_eq!(info.recovery_path, (2, PathInfo::Multi(
+        assert_eq!(info.recovery_path(), (2, &PathInfo::Multi(
             2,
             vec![
                 descriptor::DescriptorPublicKey::from_str("[636adf3f/48'/1'/1'/2']tpubDDvF2khuoBBj8vcSjQfa7iKaxsQZE7YjJ7cJL8A8eaneadMPKbHSpoSr4JD1F5LUvWD82HCxdtSppGfrMUmiNbFxrA2EHEVLnrdCFNFe75D/<0;1>/*").unwrap(),
@@ -1418,7 +2338,221 @@ mod tests {
         assert_eq!(partial_info.primary_path.threshold, 2);
         assert_eq!(partial_info.primary_path.sigs_count, 1);
         assert_eq!(partial_info.primary_path.signed_pubkeys.len(), 1);
-        assert!(partial_info.recovery_path.is_none());
+        assert!(partial_info.recovery_path().is_none());
+    }
+
+    #[test]
+    fn nested_primary_path() {
+        let key_a = descriptor::DescriptorPublicKey::from_str("[636adf3f/48'/1'/0'/2']tpubDEE9FvWbG4kg4gxDNrALgrWLiHwNMXNs8hk6nXNPw4VHKot16xd2251vwi2M6nsyQTkak5FJNHVHkCcuzmvpSbWHdumX3DxpDm89iTfSBaL/<0;1>/*").unwrap();
+        let desc = MultipathDescriptor::from_str(&format!("wsh(or_i(and_v(v:pkh([8a64f2a9]tpubD6NzVbkrYhZ4WmzFjvQrp7sDa4ECUxTi9oby8K4FZkd3XCBtEdKwUiQyYJaxiJo5y42gyDWEczrFpozEjeLxMPxjf2WtkfcbpUdfvNnozWF/<0;1>/*),older(10)),and_v(v:pk({}),multi(2,[ffd63c8d/48'/1'/0'/2']tpubDExA3EC3iAsPxPhFn4j6gMiVup6V2eH3qKyk69RcTc9TTNRfFYVPad8bJD5FCHVQxyBT4izKsvr7Btd2R4xmQ1hZkvsqGBaeE82J71uTK4N/<0;1>/*,[636adf3f/48'/1'/1'/2']tpubDDvF2khuoBBj8vcSjQfa7iKaxsQZE7YjJ7cJL8A8eaneadMPKbHSpoSr4JD1F5LUvWD82HCxdtSppGfrMUmiNbFxrA2EHEVLnrdCFNFe75D/<0;1>/*,[ffd63c8d/48'/1'/1'/2']tpubDFMs44FD4kFt3M7Z317cFh5tdKEGN8tyQRY6Q5gcSha4NtxZfGmTVRMbsD1bWN469LstXU4aVSARDxrvxFCUjHeegfEY2cLSazMBkNCmDPD/<0;1>/*))))", key_a)).unwrap();
+
+        // The primary path is "A and 2 of B, C, D".
+        let info = desc.info();
+        match info.primary_path() {
+            PathInfo::Nested(2, paths) => {
+                assert_eq!(paths.len(), 2);
+                assert!(paths.contains(&PathInfo::Single(key_a.clone())));
+                assert!(paths
+                    .iter()
+                    .any(|path| matches!(path, PathInfo::Multi(2, keys) if keys.len() == 3)));
+            }
+            _ => panic!("Primary path must be a nested threshold"),
+        }
+        assert_eq!(info.recovery_path().0, 10);
+
+        // At least 3 signatures are needed, from any of the 4 keys.
+        let (threshold, origins) = info.primary_path().thresh_origins();
+        assert_eq!(threshold, 3);
+        assert_eq!(origins.len(), 4);
+
+        let signed = |fg: &str, path: &str| {
+            (
+                bip32::Fingerprint::from_str(fg).unwrap(),
+                bip32::DerivationPath::from_str(path).unwrap(),
+            )
+        };
+        let (sig_a, sig_b, sig_c, sig_d) = (
+            signed("636adf3f", "m/48'/1'/0'/2'/0/0"),
+            signed("ffd63c8d", "m/48'/1'/0'/2'/0/0"),
+            signed("636adf3f", "m/48'/1'/1'/2'/0/0"),
+            signed("ffd63c8d", "m/48'/1'/1'/2'/0/0"),
+        );
+
+        // Nothing signed yet.
+        let spend_info = info.primary_path().spend_info(std::iter::empty());
+        assert_eq!(spend_info.threshold, 3);
+        assert_eq!(spend_info.sigs_count, 0);
+
+        // One of the multisig keys signed.
+        let spend_info = info.primary_path().spend_info([sig_b.clone()].iter());
+        assert_eq!(spend_info.sigs_count, 1);
+        assert_eq!(spend_info.signed_pubkeys.len(), 1);
+
+        // The multisig is satisfied but A is still missing.
+        let spend_info = info
+            .primary_path()
+            .spend_info([sig_c, sig_d.clone()].iter());
+        assert_eq!(spend_info.sigs_count, 2);
+        assert!(spend_info.sigs_count < spend_info.threshold);
+
+        // All good with A and 2 of the multisig keys.
+        let spend_info = info.primary_path().spend_info([sig_a, sig_b, sig_d].iter());
+        assert_eq!(spend_info.sigs_count, 3);
+        assert_eq!(spend_info.signed_pubkeys.len(), 3);
+    }
+
+    #[test]
+    fn multiple_recovery_paths() {
+        let owner_key = descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*").unwrap();
+        let heir_key = descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*").unwrap();
+        let heir_keys = vec![
+            descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub69cP4Y7S9TWcbSNxmk6CEDBsoaqr3ZEdjHuZcHxEFFKGh569RsJNr2V27XGhsbH9FXgWUEmKXRN7c5wQfq2VPjt31xP9VsYnVUyU8HcVevm/<0;1>/*").unwrap(),
+            descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub6AA2N8RALRYgLD6jT1iXYCEDkndTeZndMtWPbtNX6sY5dPiLtf2T88ahdxrGXMUPoNadgR86sFhBXWQVgifPzDYbY9ZtwK4gqzx4y5Da1DW/<0;1>/*").unwrap(),
+        ];
+
+        // A single key after 6 months, a 1-of-2 after a year.
+        let mut recovery_paths = BTreeMap::new();
+        recovery_paths.insert(26352, LianaDescKeys::from_single(heir_key.clone()));
+        recovery_paths.insert(
+            52560,
+            LianaDescKeys::from_multi(1, heir_keys.clone()).unwrap(),
+        );
+        let desc = MultipathDescriptor::new_with_recovery_paths(
+            LianaDescKeys::from_single(owner_key.clone()),
+            recovery_paths.clone(),
+        )
+        .unwrap();
+        let desc_str = desc.to_string();
+        assert!(desc_str.starts_with("wsh(or_d(pk([abcdef01]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),or_i(and_v(v:pkh("));
+        assert_eq!(MultipathDescriptor::from_str(&desc_str).unwrap(), desc);
+
+        let info = desc.info();
+        assert_eq!(info.primary_path(), &PathInfo::Single(owner_key.clone()));
+        assert_eq!(info.recovery_paths().len(), 2);
+        assert_eq!(info.recovery_path(), (26352, &PathInfo::Single(heir_key)));
+        assert_eq!(
+            info.recovery_paths().get(&52560),
+            Some(&PathInfo::Multi(1, heir_keys.clone()))
+        );
+        assert_eq!(desc.timelock_value(), 26352);
+
+        // At least one recovery path is required and timelocks can't be null.
+        MultipathDescriptor::new_with_recovery_paths(
+            LianaDescKeys::from_single(owner_key.clone()),
+            BTreeMap::new(),
+        )
+        .unwrap_err();
+        recovery_paths.insert(0, LianaDescKeys::from_single(heir_keys[0].clone()));
+        MultipathDescriptor::new_with_recovery_paths(
+            LianaDescKeys::from_single(owner_key.clone()),
+            recovery_paths,
+        )
+        .unwrap_err();
+
+        // Keys can't be reused across recovery paths.
+        let mut recovery_paths = BTreeMap::new();
+        recovery_paths.insert(26352, LianaDescKeys::from_single(heir_keys[0].clone()));
+        recovery_paths.insert(52560, LianaDescKeys::from_multi(1, heir_keys).unwrap());
+        MultipathDescriptor::new_with_recovery_paths(
+            LianaDescKeys::from_single(owner_key),
+            recovery_paths,
+        )
+        .unwrap_err();
+
+        // Two recovery paths can't have the same timelock.
+        MultipathDescriptor::from_str("wsh(or_d(pk([abcdef01]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*),or_i(and_v(v:pkh([abcdef01]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*),older(26352)),and_v(v:pkh([abcdef01]xpub69cP4Y7S9TWcbSNxmk6CEDBsoaqr3ZEdjHuZcHxEFFKGh569RsJNr2V27XGhsbH9FXgWUEmKXRN7c5wQfq2VPjt31xP9VsYnVUyU8HcVevm/<0;1>/*),older(26352)))))").unwrap_err();
+
+        // The recovery path the signatures are for is the first one they satisfy, or else the
+        // longest available one.
+        let path_info = |threshold, sigs_count| PathSpendInfo {
+            threshold,
+            sigs_count,
+            signed_pubkeys: HashMap::new(),
+        };
+        let mut spend_info = PartialSpendInfo {
+            primary_path: path_info(1, 0),
+            recovery_paths: BTreeMap::new(),
+        };
+        assert!(spend_info.recovery_path().is_none());
+        spend_info.recovery_paths.insert(26352, path_info(1, 0));
+        spend_info.recovery_paths.insert(52560, path_info(2, 0));
+        assert_eq!(spend_info.recovery_path().unwrap().0, 52560);
+        spend_info.recovery_paths.insert(26352, path_info(1, 1));
+        assert_eq!(spend_info.recovery_path().unwrap().0, 26352);
+    }
+
+    #[test]
+    fn taproot_descriptor() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let owner_key = descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*").unwrap();
+        let other_owner_key = descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub69cP4Y7S9TWcbSNxmk6CEDBsoaqr3ZEdjHuZcHxEFFKGh569RsJNr2V27XGhsbH9FXgWUEmKXRN7c5wQfq2VPjt31xP9VsYnVUyU8HcVevm/<0;1>/*").unwrap();
+        let heir_key = descriptor::DescriptorPublicKey::from_str("[abcdef01]xpub688Hn4wScQAAiYJLPg9yH27hUpfZAUnmJejRQBCiwfP5PEDzjWMNW1wChcninxr5gyavFqbbDjdV1aK5USJz8NDVjUy7FRQaaqqXHh5SbXe/<0;1>/*").unwrap();
+        let mut recovery_paths = BTreeMap::new();
+        recovery_paths.insert(26352, LianaDescKeys::from_single(heir_key.clone()));
+
+        // A single owner key is used as the internal key.
+        let desc = MultipathDescriptor::new_taproot(
+            LianaDescKeys::from_single(owner_key.clone()),
+            recovery_paths.clone(),
+        )
+        .unwrap();
+        assert!(desc.is_taproot());
+        let desc_str = desc.to_string();
+        assert!(desc_str.starts_with("tr([abcdef01]xpub6Eze7yAT3Y1wGrnzedCNVYDXUqa9NmHVWck5emBaTbXtURbe1NWZbK9bsz1TiVE7Cz341PMTfYgFw1KdLWdzcM1UMFTcdQfCYhhXZ2HJvTW/<0;1>/*,"));
+        assert_eq!(MultipathDescriptor::from_str(&desc_str).unwrap(), desc);
+        let info = desc.info();
+        assert_eq!(info.primary_path(), &PathInfo::Single(owner_key.clone()));
+        assert_eq!(
+            info.recovery_path(),
+            (26352, &PathInfo::Single(heir_key.clone()))
+        );
+        let der_desc = desc.receive_descriptor().derive(0.into(), &secp);
+        assert!(der_desc.is_taproot());
+        assert!(der_desc.script_pubkey().is_v1_p2tr());
+        assert_eq!(der_desc.tap_key_origins().len(), 2);
+        assert!(der_desc.bip32_derivations().is_empty());
+        // Spending through the primary path is a key path spend, with a single signature.
+        assert_eq!(desc.path_sat_vbytes(None, &secp), Some(wu_to_vb(1 + 65)));
+        assert!(desc.path_sat_vbytes(Some(26352), &secp).unwrap() > wu_to_vb(1 + 65));
+
+        // With a multisig as primary path, the internal key is unspendable and isn't part of the
+        // keys of the descriptor.
+        let owner_keys =
+            LianaDescKeys::from_multi(2, vec![owner_key.clone(), other_owner_key.clone()]).unwrap();
+        let desc = MultipathDescriptor::new_taproot(owner_keys, recovery_paths).unwrap();
+        let desc_str = desc.to_string();
+        assert!(desc_str.starts_with("tr(xpub"));
+        assert!(desc_str.contains("multi_a(2,"));
+        assert_eq!(MultipathDescriptor::from_str(&desc_str).unwrap(), desc);
+        let info = desc.info();
+        assert_eq!(
+            info.primary_path(),
+            &PathInfo::Multi(2, vec![owner_key, other_owner_key])
+        );
+        assert_eq!(info.recovery_path(), (26352, &PathInfo::Single(heir_key)));
+        assert_eq!(desc.keys_derivation().len(), 3);
+        let der_desc = desc.receive_descriptor().derive(0.into(), &secp);
+        assert_eq!(der_desc.tap_key_origins().len(), 3);
+
+        // Any other internal key without an origin is rejected.
+        let internal_key = unspendable_desc_key(bitcoin::Network::Bitcoin).to_string();
+        let tampered_key =
+            descriptor::DescriptorPublicKey::MultiXPub(descriptor::DescriptorMultiXKey {
+                origin: None,
+                xkey: bip32::ExtendedPubKey {
+                    depth: 1,
+                    ..unspendable_xpub(bitcoin::Network::Bitcoin)
+                },
+                derivation_paths: descriptor::DerivPaths::new(vec![
+                    bip32::DerivationPath::from(vec![0.into()]),
+                    bip32::DerivationPath::from(vec![1.into()]),
+                ])
+                .unwrap(),
+                wildcard: descriptor::Wildcard::Unhardened,
+            })
+            .to_string();
+        assert!(desc_str.contains(&internal_key));
+        MultipathDescriptor::from_str(&desc_str.replace(&internal_key, &tampered_key)).unwrap_err();
     }
 
     // TODO: test error conditions of deserialization.
@@ -1,4 +1,5 @@
 use crate::{
+    commands::ChangeDestination,
     jsonrpc::{Error, Params, Request, Response},
     DaemonControl,
 };
@@ -7,8 +8,9 @@ use std::{collections::HashMap, convert::TryInto, str::FromStr};
 
 use miniscript::bitcoin::{self, consensus, util::psbt::PartiallySignedTransaction as Psbt};
 
-fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
-    let destinations = params
+// The map of destination address to amount in sats, as the first parameter.
+fn destinations_param(params: &Params) -> Result<HashMap<bitcoin::Address, u64>, Error> {
+    params
         .get(0, "destinations")
         .ok_or_else(|| Error::invalid_params("Missing 'destinations' parameter."))?
         .as_object()
@@ -21,7 +23,23 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
                 })
                 .collect::<Option<HashMap<bitcoin::Address, u64>>>()
         })
-        .ok_or_else(|| Error::invalid_params("Invalid 'destinations' parameter."))?;
+        .ok_or_else(|| Error::invalid_params("Invalid 'destinations' parameter."))
+}
+
+// The parameters of a spend: destinations, outpoints, feerate and change destination.
+#[allow(clippy::type_complexity)]
+fn spend_params(
+    params: &Params,
+) -> Result<
+    (
+        HashMap<bitcoin::Address, u64>,
+        Vec<bitcoin::OutPoint>,
+        u64,
+        ChangeDestination,
+    ),
+    Error,
+> {
+    let destinations = destinations_param(params)?;
     let outpoints = params
         .get(1, "outpoints")
         .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?
@@ -41,8 +59,49 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
         .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
         .as_u64()
         .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let change = match params.get(3, "change") {
+        None => ChangeDestination::Auto,
+        Some(change) => match change.as_str() {
+            Some("auto") => ChangeDestination::Auto,
+            Some("none") => ChangeDestination::NoChange,
+            Some(addr) => bitcoin::Address::from_str(addr)
+                .map(ChangeDestination::Address)
+                .map_err(|_| Error::invalid_params("Invalid 'change' parameter."))?,
+            None => return Err(Error::invalid_params("Invalid 'change' parameter.")),
+        },
+    };
+
+    Ok((destinations, outpoints, feerate, change))
+}
+
+fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let (destinations, outpoints, feerate, change) = spend_params(&params)?;
+    let res = control.create_spend(&destinations, &outpoints, feerate, &change)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn preview_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let (destinations, outpoints, feerate, change) = spend_params(&params)?;
+    let res = control.preview_spend(&destinations, &outpoints, feerate, &change)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn select_coins(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let destinations = destinations_param(&params)?;
+    let feerate: u64 = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let seed = params
+        .get(2, "seed")
+        .map(|seed| {
+            seed.as_u64()
+                .ok_or_else(|| Error::invalid_params("Invalid 'seed' parameter."))
+        })
+        .transpose()?;
 
-    let res = control.create_spend(&destinations, &outpoints, feerate)?;
+    let res = control.select_coins(&destinations, feerate, seed)?;
     Ok(serde_json::json!(&res))
 }
 
@@ -83,6 +142,23 @@ fn broadcast_spend(control: &DaemonControl, params: Params) -> Result<serde_json
     Ok(serde_json::json!({}))
 }
 
+fn rbf_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    let feerate: u64 = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+
+    let res = control.rbf_psbt(&txid, feerate)?;
+    Ok(serde_json::json!(&res))
+}
+
 fn list_confirmed(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let start: u32 = params
         .get(0, "start")
@@ -106,7 +182,7 @@ fn list_confirmed(control: &DaemonControl, params: Params) -> Result<serde_json:
         .ok_or_else(|| Error::invalid_params("Invalid 'limit' parameter."))?;
 
     Ok(serde_json::json!(
-        &control.list_confirmed_transactions(start, end, limit)
+        &control.list_confirmed_transactions(start, end, limit)?
     ))
 }
 
@@ -121,7 +197,7 @@ fn list_transactions(control: &DaemonControl, params: Params) -> Result<serde_js
                 .collect()
         })
         .ok_or_else(|| Error::invalid_params("Invalid 'txids' parameter."))?;
-    Ok(serde_json::json!(&control.list_transactions(&txids)))
+    Ok(serde_json::json!(&control.list_transactions(&txids)?))
 }
 
 fn start_rescan(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
@@ -136,6 +212,21 @@ fn start_rescan(control: &DaemonControl, params: Params) -> Result<serde_json::V
     Ok(serde_json::json!({}))
 }
 
+fn start_rescan_from_height(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let height: i32 = params
+        .get(0, "height")
+        .ok_or_else(|| Error::invalid_params("Missing 'height' parameter."))?
+        .as_i64()
+        .and_then(|h| h.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'height' parameter."))?;
+    let res = control.start_rescan_from_height(height)?;
+
+    Ok(serde_json::json!(&res))
+}
+
 fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let address = params
         .get(0, "address")
@@ -148,11 +239,119 @@ fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json
         .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
         .as_u64()
         .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let timelock: Option<u16> = params
+        .get(2, "timelock")
+        .map(|t| {
+            t.as_u64()
+                .and_then(|t| t.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'timelock' parameter."))
+        })
+        .transpose()?;
+
+    let res = control.create_recovery(address, feerate, timelock)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn create_refresh(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let threshold: u32 = params
+        .get(0, "threshold")
+        .ok_or_else(|| Error::invalid_params("Missing 'threshold' parameter."))?
+        .as_u64()
+        .and_then(|t| t.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'threshold' parameter."))?;
+    let feerate: u64 = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let timelock: Option<u16> = params
+        .get(2, "timelock")
+        .map(|t| {
+            t.as_u64()
+                .and_then(|t| t.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'timelock' parameter."))
+        })
+        .transpose()?;
+
+    let res = control.create_refresh(threshold, feerate, timelock)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn create_sweep(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let address = params
+        .get(0, "address")
+        .ok_or_else(|| Error::invalid_params("Missing 'address' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Address::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'address' parameter."))?;
+    let outpoints = params
+        .get(1, "outpoints")
+        .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?
+        .as_array()
+        .and_then(|arr| {
+            arr.iter()
+                .map(|entry| {
+                    entry
+                        .as_str()
+                        .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                })
+                .collect::<Option<Vec<bitcoin::OutPoint>>>()
+        })
+        .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))?;
+    let feerate: u64 = params
+        .get(2, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+
+    let res = control.create_sweep(address, &outpoints, feerate)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn create_cpfp(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let outpoint = params
+        .get(0, "outpoint")
+        .ok_or_else(|| Error::invalid_params("Missing 'outpoint' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::OutPoint::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'outpoint' parameter."))?;
+    let feerate: u64 = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
 
-    let res = control.create_recovery(address, feerate)?;
+    let res = control.create_cpfp(&outpoint, feerate)?;
     Ok(serde_json::json!(&res))
 }
 
+fn estimate_feerate(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let conf_targets = params
+        .get(0, "conf_targets")
+        .ok_or_else(|| Error::invalid_params("Missing 'conf_targets' parameter."))?
+        .as_array()
+        .and_then(|arr| {
+            arr.iter()
+                .map(|target| target.as_u64().and_then(|t| t.try_into().ok()))
+                .collect::<Option<Vec<u16>>>()
+        })
+        .ok_or_else(|| Error::invalid_params("Invalid 'conf_targets' parameter."))?;
+
+    let res = control.estimate_feerate(&conf_targets)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn generate_blocks(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let count: u32 = params
+        .get(0, "count")
+        .ok_or_else(|| Error::invalid_params("Missing 'count' parameter."))?
+        .as_u64()
+        .and_then(|c| c.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'count' parameter."))?;
+
+    Ok(serde_json::json!(&control.generate_blocks(count)?))
+}
+
 /// Handle an incoming JSONRPC2 request.
 pub fn handle_request(control: &DaemonControl, req: Request) -> Result<Response, Error> {
     let result = match req.method.as_str() {
@@ -162,12 +361,26 @@ pub fn handle_request(control: &DaemonControl, req: Request) -> Result<Response,
                 .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
             broadcast_spend(control, params)?
         }
+        "checkbalance" => serde_json::json!(&control.check_balance()?),
+        "cleanupspendtxs" => serde_json::json!(&control.cleanup_spends()),
+        "createcpfp" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'outpoint' and 'feerate' parameters.")
+            })?;
+            create_cpfp(control, params)?
+        }
         "createrecovery" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params("Missing 'address' and 'feerate' parameters.")
             })?;
             create_recovery(control, params)?
         }
+        "createrefresh" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'threshold' and 'feerate' parameters.")
+            })?;
+            create_refresh(control, params)?
+        }
         "createspend" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params(
@@ -176,14 +389,33 @@ pub fn handle_request(control: &DaemonControl, req: Request) -> Result<Response,
             })?;
             create_spend(control, params)?
         }
+        "createsweep" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'address', 'outpoints' and 'feerate' parameters.")
+            })?;
+            create_sweep(control, params)?
+        }
         "delspendtx" => {
             let params = req
                 .params
                 .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
             delete_spend(control, params)?
         }
-        "getinfo" => serde_json::json!(&control.get_info()),
+        "estimatefeerate" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'conf_targets' parameter."))?;
+            estimate_feerate(control, params)?
+        }
+        "generateblocks" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'count' parameter."))?;
+            generate_blocks(control, params)?
+        }
+        "getinfo" => serde_json::json!(&control.get_info()?),
         "getnewaddress" => serde_json::json!(&control.get_new_address()),
+        "listbackendlabels" => serde_json::json!(&control.list_backend_labels()?),
         "listcoins" => serde_json::json!(&control.list_coins()),
         "listconfirmed" => {
             let params = req.params.ok_or_else(|| {
@@ -202,12 +434,38 @@ pub fn handle_request(control: &DaemonControl, req: Request) -> Result<Response,
             })?;
             list_transactions(control, params)?
         }
+        "previewspend" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params(
+                    "Missing 'outpoints', 'destinations' and 'feerate' parameters.",
+                )
+            })?;
+            preview_spend(control, params)?
+        }
+        "rbfpsbt" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' and 'feerate' parameters."))?;
+            rbf_psbt(control, params)?
+        }
+        "selectcoins" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'destinations' and 'feerate' parameters.")
+            })?;
+            select_coins(control, params)?
+        }
         "startrescan" => {
             let params = req
                 .params
                 .ok_or_else(|| Error::invalid_params("Missing 'timestamp' parameter."))?;
             start_rescan(control, params)?
         }
+        "startrescanfromheight" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'height' parameter."))?;
+            start_rescan_from_height(control, params)?
+        }
         "stop" => serde_json::json!({}),
         "updatespend" => {
             let params = req
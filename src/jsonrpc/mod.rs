@@ -155,7 +155,9 @@ impl From<commands::CommandError> for Error {
             | commands::CommandError::NoDestination
             | commands::CommandError::UnknownOutpoint(..)
             | commands::CommandError::InvalidFeerate(..)
+            | commands::CommandError::FeerateBelowMempoolMin(..)
             | commands::CommandError::AlreadySpent(..)
+            | commands::CommandError::ImmatureCoinbase(..)
             | commands::CommandError::AddressNetwork(..)
             | commands::CommandError::InvalidOutputValue(..)
             | commands::CommandError::InsufficientFunds(..)
@@ -163,13 +165,30 @@ impl From<commands::CommandError> for Error {
             | commands::CommandError::UnknownSpend(..)
             | commands::CommandError::SpendFinalization(..)
             | commands::CommandError::InsaneRescanTimestamp(..)
+            | commands::CommandError::InsaneRescanHeight(..)
             | commands::CommandError::AlreadyRescanning
-            | commands::CommandError::RecoveryNotAvailable => {
+            | commands::CommandError::RecoveryNotAvailable
+            | commands::CommandError::UnknownRecoveryPath(..)
+            | commands::CommandError::RecoveryLocked(..)
+            | commands::CommandError::RelativeTimelockTxVersion(..)
+            | commands::CommandError::RegtestOnly(..)
+            | commands::CommandError::AlreadyConfirmed(..)
+            | commands::CommandError::RbfFeerateTooLow(..)
+            | commands::CommandError::RbfNoChange(..)
+            | commands::CommandError::ChangeAddressNotOurs(..)
+            | commands::CommandError::NothingToRefresh(..)
+            | commands::CommandError::InvalidConfTarget(..) => {
                 Error::new(ErrorCode::InvalidParams, e.to_string())
             }
             commands::CommandError::FetchingTransaction(..)
             | commands::CommandError::SanityCheckFailure(_)
-            | commands::CommandError::RescanTrigger(..) => {
+            | commands::CommandError::RescanTrigger(..)
+            | commands::CommandError::UtxoSetScan(..)
+            | commands::CommandError::FeeEstimation(..)
+            | commands::CommandError::BlockGeneration(..)
+            | commands::CommandError::BackendLabels(..)
+            | commands::CommandError::BitcoinBackend(..)
+            | commands::CommandError::Randomness(..) => {
                 Error::new(ErrorCode::InternalError, e.to_string())
             }
             commands::CommandError::TxBroadcast(_) => {
@@ -16,11 +16,12 @@ pub use bip39;
 pub use miniscript;
 
 pub use crate::bitcoin::d::{BitcoindError, WalletError};
+pub use crate::bitcoin::electrum::ElectrumError;
 #[cfg(feature = "jsonrpc_server")]
 use crate::jsonrpc::server::{rpcserver_loop, rpcserver_setup};
 use crate::{
-    bitcoin::{d::BitcoinD, poller, BitcoinInterface},
-    config::Config,
+    bitcoin::{d::BitcoinD, electrum::Electrum, poller, BitcoinInterface},
+    config::{Config, ElectrumConfig},
     database::{
         sqlite::{FreshDbOptions, SqliteDb, SqliteDbError},
         DatabaseInterface,
@@ -93,6 +94,7 @@ pub enum StartupError {
     WindowsBitcoindWatchonlyDeletion(path::PathBuf, io::Error),
     Database(SqliteDbError),
     Bitcoind(BitcoindError),
+    Electrum(ElectrumError),
     #[cfg(unix)]
     Daemonization(&'static str),
 }
@@ -124,6 +126,7 @@ impl fmt::Display for StartupError {
             ),
             Self::Database(e) => write!(f, "Error initializing database: '{}'.", e),
             Self::Bitcoind(e) => write!(f, "Error setting up bitcoind interface: '{}'.", e),
+            Self::Electrum(e) => write!(f, "Error setting up Electrum interface: '{}'.", e),
             #[cfg(unix)]
             Self::Daemonization(e) => write!(f, "Error when daemonizing: '{}'.", e),
         }
@@ -150,6 +153,12 @@ impl From<BitcoindError> for StartupError {
     }
 }
 
+impl From<ElectrumError> for StartupError {
+    fn from(e: ElectrumError) -> Self {
+        Self::Electrum(e)
+    }
+}
+
 fn create_datadir(datadir_path: &path::Path) -> Result<(), StartupError> {
     #[cfg(unix)]
     return {
@@ -268,7 +277,10 @@ fn setup_bitcoind(
         bitcoind_config,
         wo_path.to_str().expect("Must be valid unicode").to_string(),
     )?;
-    bitcoind.node_sanity_checks(config.bitcoin_config.network)?;
+    bitcoind.node_sanity_checks(
+        config.bitcoin_config.network,
+        bitcoind_config.signet_challenge.as_deref(),
+    )?;
     if fresh_data_dir {
         // Because of the hack above, the assumption that whenever the data directory is fresh a
         // watchonly wallet doesn't exist doesn't hold for Windows. Make sure it does by removing
@@ -290,6 +302,20 @@ fn setup_bitcoind(
     Ok(bitcoind)
 }
 
+// Connect to the Electrum server and check it is on our network.
+fn setup_electrum(
+    config: &Config,
+    electrum_config: &ElectrumConfig,
+) -> Result<Electrum, StartupError> {
+    let electrum = Electrum::new(
+        electrum_config,
+        config.bitcoin_config.network,
+        config.bitcoin_config.gap_limit,
+    )?;
+    log::info!("Connection to the Electrum server established and checked.");
+    Ok(electrum)
+}
+
 #[derive(Clone)]
 pub struct DaemonControl {
     config: Config,
@@ -297,6 +323,7 @@ pub struct DaemonControl {
     // FIXME: Should we require Sync on DatabaseInterface rather than using a Mutex?
     db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
     secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    derived_descs: sync::Arc<sync::Mutex<commands::DerivedDescCache>>,
 }
 
 impl DaemonControl {
@@ -311,6 +338,7 @@ impl DaemonControl {
             bitcoin,
             db,
             secp,
+            derived_descs: sync::Arc::new(sync::Mutex::new(Default::default())),
         }
     }
 
@@ -368,14 +396,21 @@ impl DaemonHandle {
             )?)) as sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
         };
 
-        // Now, set up the Bitcoin interface.
+        // Now, set up the Bitcoin interface. An Electrum server is used instead of bitcoind if
+        // one is configured.
         let bit = match bitcoin {
             Some(bit) => sync::Arc::from(sync::Mutex::from(bit)),
-            None => sync::Arc::from(sync::Mutex::from(setup_bitcoind(
-                &config,
-                &data_dir,
-                fresh_data_dir,
-            )?)) as sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+            None => match &config.electrum_config {
+                Some(electrum_config) => {
+                    sync::Arc::from(sync::Mutex::from(setup_electrum(&config, electrum_config)?))
+                        as sync::Arc<sync::Mutex<dyn BitcoinInterface>>
+                }
+                None => sync::Arc::from(sync::Mutex::from(setup_bitcoind(
+                    &config,
+                    &data_dir,
+                    fresh_data_dir,
+                )?)) as sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+            },
         };
 
         // If we are on a UNIX system and they told us to daemonize, do it now.
@@ -398,6 +433,15 @@ impl DaemonHandle {
             db.clone(),
             config.bitcoin_config.poll_interval_secs,
             config.main_descriptor.clone(),
+            config.bitcoin_config.final_spend_confirmations,
+            config.bitcoin_config.cleanup_spends,
+            config.bitcoin_config.gap_limit,
+            // Block notifications are only available from bitcoind.
+            config
+                .bitcoind_config
+                .as_ref()
+                .filter(|_| config.electrum_config.is_none())
+                .and_then(|c| c.zmq_block_addr),
         );
 
         // Finally, set up the API.
@@ -678,10 +722,18 @@ mod tests {
         let bitcoin_config = BitcoinConfig {
             network,
             poll_interval_secs: time::Duration::from_secs(2),
+            final_spend_confirmations: 6,
+            cleanup_spends: false,
+            gap_limit: 200,
         };
         let bitcoind_config = BitcoindConfig {
             addr,
             cookie_path: cookie,
+            zmq_block_addr: None,
+            rpc_retries: None,
+            rpc_timeout_secs: None,
+            signet_challenge: None,
+            proxy_addr: None,
         };
 
         // Create a dummy config with this bitcoind
@@ -692,6 +744,7 @@ mod tests {
         let config = Config {
             bitcoin_config,
             bitcoind_config: Some(bitcoind_config),
+            electrum_config: None,
             data_dir: Some(data_dir),
             #[cfg(unix)]
             daemon: false,
@@ -6,13 +6,16 @@
 use crate::random;
 
 use std::{
-    convert::TryInto,
     error, fmt, fs,
     io::{self, Write},
     path,
     str::FromStr,
 };
 
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use miniscript::bitcoin::{
     self,
     hashes::Hash,
@@ -20,8 +23,10 @@ use miniscript::bitcoin::{
     util::{
         bip32::{self, Error as Bip32Error},
         ecdsa,
-        psbt::Psbt,
+        psbt::{self, Psbt},
+        schnorr::{self, TapTweak},
         sighash,
+        taproot::TapLeafHash,
     },
 };
 
@@ -30,8 +35,13 @@ use miniscript::bitcoin::{
 pub enum SignerError {
     Randomness(random::RandomnessError),
     Mnemonic(bip39::Error),
+    UnsupportedWordCount(usize),
     Bip32(Bip32Error),
     MnemonicStorage(io::Error),
+    /// A stored mnemonic is encrypted but no password was given to read it.
+    EncryptedMnemonic,
+    /// A stored mnemonic could not be decrypted: the password is wrong or the file corrupted.
+    MnemonicDecryption,
     InsanePsbt,
     IncompletePsbt,
 }
@@ -41,8 +51,21 @@ impl fmt::Display for SignerError {
         match self {
             Self::Randomness(s) => write!(f, "Error related to getting randomness: {}", s),
             Self::Mnemonic(s) => write!(f, "Error when working with mnemonics: {}", s),
+            Self::UnsupportedWordCount(c) => write!(
+                f,
+                "Unsupported number of mnemonic words: {}, must be 12 or 24",
+                c
+            ),
             Self::Bip32(e) => write!(f, "BIP32 error: {}", e),
             Self::MnemonicStorage(e) => write!(f, "BIP39 mnemonic storage error: {}", e),
+            Self::EncryptedMnemonic => write!(
+                f,
+                "The stored mnemonic is encrypted, a password is needed to read it."
+            ),
+            Self::MnemonicDecryption => write!(
+                f,
+                "Failed to decrypt the stored mnemonic: the password is wrong or the file is corrupted."
+            ),
             Self::InsanePsbt => write!(f, "Information contained in the PSBT is wrong."),
             Self::IncompletePsbt => write!(
                 f,
@@ -56,11 +79,90 @@ impl error::Error for SignerError {}
 
 pub const MNEMONICS_FOLDER_NAME: &str = "mnemonics";
 
-// TODO: zeroize, mlock, etc.. For now we don't even encrypt the seed on disk so that'd be
-// overkill.
+/// The number of words a hot signer mnemonic can have.
+pub const MNEMONIC_WORD_COUNTS: [usize; 2] = [12, 24];
+
+/// The beginning of an encrypted mnemonic file, followed by the version of the format. A file
+/// without it is a plaintext mnemonic.
+const ENCRYPTED_MNEMONIC_MAGIC: &[u8] = b"LIANAENC";
+const ENCRYPTED_MNEMONIC_VERSION: u8 = 1;
+const SCRYPT_SALT_LEN: usize = 16;
+const CHACHA_NONCE_LEN: usize = 12;
+// The scrypt parameters recommended by the scrypt crate. The cost parameter is stored in the file,
+// it's only lowered for the tests to run fast.
+#[cfg(not(test))]
+const SCRYPT_LOG_N: u8 = 15;
+#[cfg(test)]
+const SCRYPT_LOG_N: u8 = 4;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+// The highest cost parameter we accept from a file, for a corrupt one not to make us use more
+// than 1GiB of memory.
+const SCRYPT_MAX_LOG_N: u8 = 20;
+
+// Derive the encryption key from the password using scrypt.
+fn encryption_key(password: &str, salt: &[u8], log_n: u8) -> Result<Key, SignerError> {
+    let params = scrypt::Params::new(log_n, SCRYPT_R, SCRYPT_P)
+        .map_err(|_| SignerError::MnemonicDecryption)?;
+    let mut key = Key::default();
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .expect("The key has a valid length");
+    Ok(key)
+}
+
+// Encrypt the content of a mnemonic file with ChaCha20-Poly1305, under a key derived from the
+// password. The file is made of the magic, the version, the scrypt cost parameter, the salt, the
+// nonce and finally the ciphertext.
+fn encrypt_mnemonic(content: &[u8], password: &str) -> Result<Vec<u8>, SignerError> {
+    let random = random::random_bytes().map_err(SignerError::Randomness)?;
+    let (salt, nonce) = (
+        &random[..SCRYPT_SALT_LEN],
+        &random[SCRYPT_SALT_LEN..SCRYPT_SALT_LEN + CHACHA_NONCE_LEN],
+    );
+    let key = encryption_key(password, salt, SCRYPT_LOG_N)?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(Nonce::from_slice(nonce), content)
+        .expect("Encryption of an in-memory buffer can't fail");
+
+    let mut encrypted = ENCRYPTED_MNEMONIC_MAGIC.to_vec();
+    encrypted.push(ENCRYPTED_MNEMONIC_VERSION);
+    encrypted.push(SCRYPT_LOG_N);
+    encrypted.extend_from_slice(salt);
+    encrypted.extend_from_slice(nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+// Decrypt the content of a file written by `encrypt_mnemonic`, magic included.
+fn decrypt_mnemonic(encrypted: &[u8], password: &str) -> Result<String, SignerError> {
+    let header_len = ENCRYPTED_MNEMONIC_MAGIC.len() + 2;
+    if encrypted.len() < header_len + SCRYPT_SALT_LEN + CHACHA_NONCE_LEN
+        || encrypted[ENCRYPTED_MNEMONIC_MAGIC.len()] != ENCRYPTED_MNEMONIC_VERSION
+    {
+        return Err(SignerError::MnemonicDecryption);
+    }
+    let log_n = encrypted[header_len - 1];
+    if log_n > SCRYPT_MAX_LOG_N {
+        return Err(SignerError::MnemonicDecryption);
+    }
+    let (salt, rest) = encrypted[header_len..].split_at(SCRYPT_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(CHACHA_NONCE_LEN);
+    let key = encryption_key(password, salt, log_n)?;
+    let content = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| SignerError::MnemonicDecryption)?;
+    String::from_utf8(content).map_err(|_| SignerError::MnemonicDecryption)
+}
+
+// TODO: mlock, etc..
 /// A signer that keeps the key on the laptop. Based on BIP39.
+///
+/// The mnemonic, the passphrase and the master key are overwritten in memory when the signer is
+/// dropped.
 pub struct HotSigner {
     mnemonic: bip39::Mnemonic,
+    /// The optional BIP39 passphrase, empty if none.
+    passphrase: String,
     master_xpriv: bip32::ExtendedPrivKey,
 }
 
@@ -105,27 +207,98 @@ impl HotSigner {
     fn from_mnemonic(
         network: bitcoin::Network,
         mnemonic: bip39::Mnemonic,
+        passphrase: &str,
     ) -> Result<Self, SignerError> {
-        let master_xpriv = bip32::ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))
-            .map_err(SignerError::Bip32)?;
+        let master_xpriv =
+            bip32::ExtendedPrivKey::new_master(network, &mnemonic.to_seed(passphrase))
+                .map_err(SignerError::Bip32)?;
         Ok(Self {
             mnemonic,
+            passphrase: passphrase.to_string(),
             master_xpriv,
         })
     }
 
     /// Create a new hot signer from random bytes. Uses a 12-words mnemonics without a passphrase.
     pub fn generate(network: bitcoin::Network) -> Result<Self, SignerError> {
-        // We want a 12-words mnemonic so we only use 16 of the 32 bytes.
+        Self::generate_in(network, bip39::Language::English, 12)
+    }
+
+    /// Create a new hot signer from random bytes, with a mnemonic of the given number of words
+    /// (12 or 24) in the given language and without a passphrase.
+    ///
+    /// Note the seed is derived from the words themselves: the same entropy rendered in two
+    /// different languages results in two different signers.
+    pub fn generate_in(
+        network: bitcoin::Network,
+        language: bip39::Language,
+        word_count: usize,
+    ) -> Result<Self, SignerError> {
+        if !MNEMONIC_WORD_COUNTS.contains(&word_count) {
+            return Err(SignerError::UnsupportedWordCount(word_count));
+        }
+        // Each 3 words encode 4 bytes of entropy: a 12-words mnemonic only uses 16 of the 32
+        // bytes, a 24-words one uses them all.
         let random_32bytes = random::random_bytes().map_err(SignerError::Randomness)?;
         let mnemonic =
-            bip39::Mnemonic::from_entropy(&random_32bytes[..16]).map_err(SignerError::Mnemonic)?;
-        Self::from_mnemonic(network, mnemonic)
+            bip39::Mnemonic::from_entropy_in(language, &random_32bytes[..word_count / 3 * 4])
+                .map_err(SignerError::Mnemonic)?;
+        Self::from_mnemonic(network, mnemonic, "")
     }
 
+    /// The BIP39 wordlists this signer can generate and parse mnemonics in.
+    pub fn supported_languages() -> &'static [bip39::Language] {
+        bip39::Language::all()
+    }
+
+    /// Parse a 12 or 24-words mnemonic, in any of the supported languages.
     pub fn from_str(network: bitcoin::Network, s: &str) -> Result<Self, SignerError> {
+        Self::from_str_with_passphrase(network, s, "")
+    }
+
+    /// Parse a 12 or 24-words mnemonic protected by the given BIP39 passphrase. An empty
+    /// passphrase is the same as none.
+    pub fn from_str_with_passphrase(
+        network: bitcoin::Network,
+        s: &str,
+        passphrase: &str,
+    ) -> Result<Self, SignerError> {
         let mnemonic = bip39::Mnemonic::from_str(s).map_err(SignerError::Mnemonic)?;
-        Self::from_mnemonic(network, mnemonic)
+        Self::from_parsed_mnemonic(network, mnemonic, passphrase)
+    }
+
+    // Parse a mnemonic known to be in the given language. Some words are shared between
+    // wordlists (notably the Chinese ones), the language can't always be guessed from them.
+    fn from_str_in(
+        network: bitcoin::Network,
+        language: bip39::Language,
+        s: &str,
+        passphrase: &str,
+    ) -> Result<Self, SignerError> {
+        let mnemonic = bip39::Mnemonic::parse_in(language, s).map_err(SignerError::Mnemonic)?;
+        Self::from_parsed_mnemonic(network, mnemonic, passphrase)
+    }
+
+    fn from_parsed_mnemonic(
+        network: bitcoin::Network,
+        mnemonic: bip39::Mnemonic,
+        passphrase: &str,
+    ) -> Result<Self, SignerError> {
+        if !MNEMONIC_WORD_COUNTS.contains(&mnemonic.word_count()) {
+            return Err(SignerError::UnsupportedWordCount(mnemonic.word_count()));
+        }
+        Self::from_mnemonic(network, mnemonic, passphrase)
+    }
+
+    /// The signer with the same mnemonic protected by the given passphrase instead. Its master
+    /// key, and therefore its fingerprint, differ unless the passphrase is the same.
+    pub fn with_passphrase(&self, passphrase: &str) -> Result<Self, SignerError> {
+        Self::from_mnemonic(self.master_xpriv.network, self.mnemonic.clone(), passphrase)
+    }
+
+    /// Whether the seed of this signer is derived with a BIP39 passphrase.
+    pub fn has_passphrase(&self) -> bool {
+        !self.passphrase.is_empty()
     }
 
     fn mnemonics_folder(datadir_root: &path::Path, network: bitcoin::Network) -> path::PathBuf {
@@ -138,33 +311,81 @@ impl HotSigner {
         .collect()
     }
 
-    /// Read all the mnemonics from the datadir for the given network.
+    /// Read all the mnemonics from the datadir for the given network. Fails with
+    /// [`SignerError::EncryptedMnemonic`] if one of them is encrypted.
     pub fn from_datadir(
         datadir_root: &path::Path,
         network: bitcoin::Network,
+    ) -> Result<Vec<Self>, SignerError> {
+        Self::read_datadir(datadir_root, network, None)
+    }
+
+    /// Read all the mnemonics from the datadir for the given network, decrypting the encrypted
+    /// ones with the given password. The plaintext ones are read as well.
+    pub fn from_datadir_encrypted(
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+        password: &str,
+    ) -> Result<Vec<Self>, SignerError> {
+        Self::read_datadir(datadir_root, network, Some(password))
+    }
+
+    fn read_datadir(
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+        password: Option<&str>,
     ) -> Result<Vec<Self>, SignerError> {
         let mut signers = Vec::new();
 
         let mnemonic_paths = fs::read_dir(Self::mnemonics_folder(datadir_root, network))
             .map_err(SignerError::MnemonicStorage)?;
         for entry in mnemonic_paths {
-            let mnemonic = fs::read_to_string(entry.map_err(SignerError::MnemonicStorage)?.path())
+            let content = fs::read(entry.map_err(SignerError::MnemonicStorage)?.path())
                 .map_err(SignerError::MnemonicStorage)?;
-            signers.push(Self::from_str(network, &mnemonic)?);
+            let content = if content.starts_with(ENCRYPTED_MNEMONIC_MAGIC) {
+                let password = password.ok_or(SignerError::EncryptedMnemonic)?;
+                decrypt_mnemonic(&content, password)?
+            } else {
+                String::from_utf8(content).map_err(|e| {
+                    SignerError::MnemonicStorage(io::Error::new(io::ErrorKind::InvalidData, e))
+                })?
+            };
+            let mut lines = content.splitn(3, '\n');
+            let mnemonic = lines.next().unwrap_or_default();
+            let passphrase = lines.next().unwrap_or("");
+            signers.push(match lines.next() {
+                Some(language) => {
+                    let language = Self::supported_languages()
+                        .iter()
+                        .find(|l| l.to_string() == language)
+                        .ok_or_else(|| {
+                            SignerError::MnemonicStorage(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("Unknown mnemonic language '{}'", language),
+                            ))
+                        })?;
+                    Self::from_str_in(network, *language, mnemonic, passphrase)?
+                }
+                None => Self::from_str_with_passphrase(network, mnemonic, passphrase)?,
+            });
         }
 
         Ok(signers)
     }
 
     /// The BIP39 mnemonics from which the master key of this signer is derived.
-    pub fn words(&self) -> [&'static str; 12] {
-        let words: Vec<&'static str> = self.mnemonic.word_iter().collect();
-        words.try_into().expect("Always 12 words")
+    pub fn words(&self) -> Vec<&'static str> {
+        self.mnemonic.word_iter().collect()
+    }
+
+    /// The language of the BIP39 wordlist the mnemonic of this signer is rendered in.
+    pub fn language(&self) -> bip39::Language {
+        self.mnemonic.language()
     }
 
     /// The BIP39 mnemonic words as a string.
     pub fn mnemonic_str(&self) -> String {
-        let mut mnemonic_str = String::with_capacity(12 * 7);
+        let mut mnemonic_str = String::with_capacity(self.mnemonic.word_count() * 7);
         let words = self.words();
 
         for (i, word) in words.iter().enumerate() {
@@ -187,13 +408,51 @@ impl HotSigner {
 
     /// Store the mnemonic in a file within the given "data directory".
     /// The file is stored within a "mnemonics" folder, with the filename set to the fingerprint of
-    /// the master xpub corresponding to this mnemonic.
+    /// the master xpub corresponding to this mnemonic. The passphrase, if any, is stored on a
+    /// second line. The language of a mnemonic not in English is stored on a third one.
     pub fn store(
         &self,
         datadir_root: &path::Path,
         network: bitcoin::Network,
         secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
     ) -> Result<(), SignerError> {
+        self.write(datadir_root, network, secp, None)
+    }
+
+    /// Store the mnemonic like [`HotSigner::store`], encrypted with a key derived from the given
+    /// password. It can then only be read with [`HotSigner::from_datadir_encrypted`].
+    pub fn store_encrypted(
+        &self,
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
+        password: &str,
+    ) -> Result<(), SignerError> {
+        self.write(datadir_root, network, secp, Some(password))
+    }
+
+    fn write(
+        &self,
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
+        password: Option<&str>,
+    ) -> Result<(), SignerError> {
+        let mut content = self.mnemonic_str();
+        let language = self.language();
+        if self.has_passphrase() || language != bip39::Language::English {
+            content.push('\n');
+            content.push_str(&self.passphrase);
+        }
+        if language != bip39::Language::English {
+            content.push('\n');
+            content.push_str(&language.to_string());
+        }
+        let content = match password {
+            Some(password) => encrypt_mnemonic(content.as_bytes(), password)?,
+            None => content.into_bytes(),
+        };
+
         let mut mnemonics_folder = Self::mnemonics_folder(datadir_root, network);
         if !mnemonics_folder.exists() {
             create_dir(&mnemonics_folder).map_err(SignerError::MnemonicStorage)?;
@@ -205,7 +464,7 @@ impl HotSigner {
         let mut mnemonic_file =
             create_file(&mnemonic_path).map_err(SignerError::MnemonicStorage)?;
         mnemonic_file
-            .write_all(self.mnemonic_str().as_bytes())
+            .write_all(&content)
             .map_err(SignerError::MnemonicStorage)?;
 
         Ok(())
@@ -233,6 +492,11 @@ impl HotSigner {
 
     /// Sign all inputs of the given PSBT.
     ///
+    /// Inputs are signed with the sighash type set in the PSBT input, or `SIGHASH_ALL` (resp.
+    /// `SIGHASH_DEFAULT` for Taproot inputs) if none is set. Only the standard sighash types are
+    /// supported. Taproot inputs are signed for the key path if our key is the internal key, and
+    /// for each leaf our key appears in.
+    ///
     /// **This does not perform any check. It will blindly sign anything that's passed.**
     pub fn sign_psbt(
         &self,
@@ -244,6 +508,42 @@ impl HotSigner {
 
         // Sign each input in the PSBT.
         for i in 0..psbt.inputs.len() {
+            // Only sign the inputs we were asked a signature for. In a collaborative transaction
+            // the other inputs may belong to someone else, we don't need to know anything about
+            // them.
+            let our_tap_keys: Vec<(
+                secp256k1::XOnlyPublicKey,
+                Vec<TapLeafHash>,
+                bip32::DerivationPath,
+            )> = psbt.inputs[i]
+                .tap_key_origins
+                .iter()
+                .filter(|(_, (_, (fingerprint, _)))| *fingerprint == master_fingerprint)
+                .map(|(pubkey, (leaf_hashes, (_, der_path)))| {
+                    (*pubkey, leaf_hashes.clone(), der_path.clone())
+                })
+                .collect();
+            if !our_tap_keys.is_empty() {
+                self.sign_taproot_input(
+                    &mut psbt.inputs,
+                    i,
+                    our_tap_keys,
+                    &mut sighash_cache,
+                    secp,
+                )?;
+                continue;
+            }
+
+            let our_keys: Vec<(secp256k1::PublicKey, bip32::DerivationPath)> = psbt.inputs[i]
+                .bip32_derivation
+                .iter()
+                .filter(|(_, (fingerprint, _))| *fingerprint == master_fingerprint)
+                .map(|(pubkey, (_, der_path))| (*pubkey, der_path.clone()))
+                .collect();
+            if our_keys.is_empty() {
+                continue;
+            }
+
             // First of all compute the sighash for this input. We assume P2WSH spend: the sighash
             // script code is always the witness script.
             let witscript = psbt.inputs[i]
@@ -255,7 +555,12 @@ impl HotSigner {
                 .as_ref()
                 .ok_or(SignerError::IncompletePsbt)?
                 .value;
-            let sig_type = sighash::EcdsaSighashType::All;
+            let sig_type = match psbt.inputs[i].sighash_type {
+                Some(sig_type) => sig_type
+                    .ecdsa_hash_ty()
+                    .map_err(|_| SignerError::InsanePsbt)?,
+                None => sighash::EcdsaSighashType::All,
+            };
             let sighash = sighash_cache
                 .segwit_signature_hash(i, witscript, value, sig_type)
                 .map_err(|_| SignerError::InsanePsbt)?;
@@ -263,12 +568,7 @@ impl HotSigner {
                 .expect("Sighash is always 32 bytes.");
 
             // Then provide a signature for all the keys they asked for.
-            // FIXME: get rid of this clone somehow.. Can't we just tell the borrow checker it's
-            // fine?
-            for (curr_pubkey, (fingerprint, der_path)) in psbt.inputs[i].bip32_derivation.clone() {
-                if fingerprint != master_fingerprint {
-                    continue;
-                }
+            for (curr_pubkey, der_path) in our_keys {
                 let privkey = self.xpriv_at(&der_path, secp).to_priv();
                 let pubkey = privkey.public_key(secp);
                 if pubkey.inner != curr_pubkey {
@@ -288,12 +588,107 @@ impl HotSigner {
         Ok(psbt)
     }
 
+    // Sign a Taproot input with each of our keys: for the key path if it is the internal key, and
+    // for all the leaves it appears in. The sighash commits to all the spent outputs.
+    fn sign_taproot_input<T: std::ops::Deref<Target = bitcoin::Transaction>>(
+        &self,
+        inputs: &mut [psbt::Input],
+        index: usize,
+        our_keys: Vec<(
+            secp256k1::XOnlyPublicKey,
+            Vec<TapLeafHash>,
+            bip32::DerivationPath,
+        )>,
+        sighash_cache: &mut sighash::SighashCache<T>,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
+    ) -> Result<(), SignerError> {
+        let prevouts = inputs
+            .iter()
+            .map(|psbt_in| psbt_in.witness_utxo.clone())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SignerError::IncompletePsbt)?;
+        let prevouts = sighash::Prevouts::All(&prevouts);
+        let hash_ty = match inputs[index].sighash_type {
+            Some(sig_type) => sig_type
+                .schnorr_hash_ty()
+                .map_err(|_| SignerError::InsanePsbt)?,
+            None => sighash::SchnorrSighashType::Default,
+        };
+
+        for (pubkey, leaf_hashes, der_path) in our_keys {
+            let keypair = secp256k1::KeyPair::from_secret_key(
+                secp,
+                &self.xpriv_at(&der_path, secp).to_priv().inner,
+            );
+            if keypair.x_only_public_key().0 != pubkey {
+                return Err(SignerError::InsanePsbt);
+            }
+
+            // The key path spend is signed with the internal key tweaked by the Merkle root.
+            if inputs[index].tap_internal_key == Some(pubkey) {
+                let sighash = sighash_cache
+                    .taproot_key_spend_signature_hash(index, &prevouts, hash_ty)
+                    .map_err(|_| SignerError::InsanePsbt)?;
+                let sighash = secp256k1::Message::from_slice(sighash.as_hash().as_inner())
+                    .expect("Sighash is always 32 bytes.");
+                let tweaked_keypair = keypair
+                    .tap_tweak(
+                        &secp256k1::Secp256k1::verification_only(),
+                        inputs[index].tap_merkle_root,
+                    )
+                    .to_inner();
+                let sig = secp.sign_schnorr_no_aux_rand(&sighash, &tweaked_keypair);
+                inputs[index].tap_key_sig = Some(schnorr::SchnorrSig { sig, hash_ty });
+            }
+
+            for leaf_hash in leaf_hashes {
+                let sighash = sighash_cache
+                    .taproot_script_spend_signature_hash(index, &prevouts, leaf_hash, hash_ty)
+                    .map_err(|_| SignerError::InsanePsbt)?;
+                let sighash = secp256k1::Message::from_slice(sighash.as_hash().as_inner())
+                    .expect("Sighash is always 32 bytes.");
+                let sig = secp.sign_schnorr_no_aux_rand(&sighash, &keypair);
+                inputs[index]
+                    .tap_script_sigs
+                    .insert((pubkey, leaf_hash), schnorr::SchnorrSig { sig, hash_ty });
+            }
+        }
+
+        Ok(())
+    }
+
     /// Change the network of generated extended keys. Note this value only has to do with the
     /// BIP32 encoding of those keys (xpubs, tpubs, ..) but does not affect any data (whether it is
     /// the keys or the mnemonics).
     pub fn set_network(&mut self, network: bitcoin::Network) {
         self.master_xpriv.network = network;
     }
+
+    /// Overwrite the mnemonic, the passphrase and the master key of this signer in memory. The
+    /// signer is left holding the all-zero entropy mnemonic and must not be used to sign
+    /// afterward.
+    pub fn zeroize(&mut self) {
+        for byte in unsafe { self.passphrase.as_bytes_mut() } {
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+        self.passphrase.clear();
+        let network = self.master_xpriv.network;
+        let mnemonic = bip39::Mnemonic::from_entropy(&[0; 16]).expect("Valid entropy length");
+        let master_xpriv =
+            bip32::ExtendedPrivKey::new_master(network, &[0; 64]).expect("Valid seed length");
+        // Use volatile writes so the compiler can't elide them as dead stores before drop.
+        unsafe {
+            std::ptr::write_volatile(&mut self.mnemonic, mnemonic);
+            std::ptr::write_volatile(&mut self.master_xpriv, master_xpriv);
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl Drop for HotSigner {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
 }
 
 #[cfg(test)]
@@ -303,6 +698,7 @@ mod tests {
     use miniscript::{
         bitcoin::util::psbt::Input as PsbtIn,
         descriptor::{DerivPaths, DescriptorMultiXKey, DescriptorPublicKey, Wildcard},
+        psbt::PsbtExt,
     };
     use std::collections::{BTreeMap, HashSet};
 
@@ -336,6 +732,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hot_signer_languages() {
+        let network = bitcoin::Network::Bitcoin;
+        for language in &[
+            bip39::Language::French,
+            bip39::Language::Spanish,
+            bip39::Language::Japanese,
+        ] {
+            assert!(HotSigner::supported_languages().contains(language));
+            let signer = HotSigner::generate_in(network, *language, 12).unwrap();
+            assert_eq!(signer.language(), *language);
+
+            // Roundtrips, and the language is detected from the words.
+            let signer_parsed = HotSigner::from_str(network, &signer.mnemonic_str()).unwrap();
+            assert_eq!(signer_parsed.language(), *language);
+            assert_eq!(signer_parsed.words(), signer.words());
+        }
+
+        // The default is English.
+        assert_eq!(
+            HotSigner::generate(network).unwrap().language(),
+            bip39::Language::English
+        );
+    }
+
+    #[test]
+    fn hot_signer_word_counts() {
+        let network = bitcoin::Network::Bitcoin;
+        assert_eq!(HotSigner::generate(network).unwrap().words().len(), 12);
+        let signer = HotSigner::generate_in(network, bip39::Language::English, 24).unwrap();
+        assert_eq!(signer.words().len(), 24);
+        let signer_parsed = HotSigner::from_str(network, &signer.mnemonic_str()).unwrap();
+        assert_eq!(signer_parsed.words(), signer.words());
+
+        assert!(matches!(
+            HotSigner::generate_in(network, bip39::Language::English, 18),
+            Err(SignerError::UnsupportedWordCount(18))
+        ));
+        // A valid BIP39 mnemonic of another length is not accepted either.
+        let eighteen_words = bip39::Mnemonic::from_entropy(&[0; 24]).unwrap().to_string();
+        assert!(matches!(
+            HotSigner::from_str(network, &eighteen_words),
+            Err(SignerError::UnsupportedWordCount(18))
+        ));
+    }
+
     #[test]
     fn hot_signer_storage() {
         let secp = secp256k1::Secp256k1::signing_only();
@@ -360,6 +802,166 @@ mod tests {
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn hot_signer_language_storage() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let tmp_dir = tmp_dir();
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let network = bitcoin::Network::Bitcoin;
+
+        // The Chinese wordlists have words in common, the language is read back from the file
+        // rather than guessed from the words.
+        let mut signers = Vec::new();
+        for language in &[
+            bip39::Language::English,
+            bip39::Language::SimplifiedChinese,
+            bip39::Language::TraditionalChinese,
+            bip39::Language::French,
+        ] {
+            let signer = HotSigner::generate_in(network, *language, 12).unwrap();
+            signer.store(&tmp_dir, network, &secp).unwrap();
+            signers.push((signer.fingerprint(&secp), *language));
+            let protected = signer.with_passphrase("passphrase").unwrap();
+            protected.store(&tmp_dir, network, &secp).unwrap();
+            signers.push((protected.fingerprint(&secp), *language));
+        }
+        let signers_read: HashSet<_> = HotSigner::from_datadir(&tmp_dir, network)
+            .unwrap()
+            .into_iter()
+            .map(|signer| (signer.fingerprint(&secp), signer.language()))
+            .collect();
+        assert_eq!(signers_read, signers.into_iter().collect::<HashSet<_>>());
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn hot_signer_passphrase() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let network = bitcoin::Network::Bitcoin;
+        let signer = HotSigner::generate(network).unwrap();
+        assert!(!signer.has_passphrase());
+
+        // An empty passphrase is the same as none.
+        let mnemonic = signer.mnemonic_str();
+        assert_eq!(
+            HotSigner::from_str_with_passphrase(network, &mnemonic, "")
+                .unwrap()
+                .fingerprint(&secp),
+            signer.fingerprint(&secp)
+        );
+
+        // Otherwise it's another master key.
+        let protected = HotSigner::from_str_with_passphrase(network, &mnemonic, "TREZOR").unwrap();
+        assert!(protected.has_passphrase());
+        assert_eq!(protected.words(), signer.words());
+        assert_ne!(protected.fingerprint(&secp), signer.fingerprint(&secp));
+        assert_eq!(
+            signer.with_passphrase("TREZOR").unwrap().fingerprint(&secp),
+            protected.fingerprint(&secp)
+        );
+
+        // BIP39 test vector.
+        let vector = HotSigner::from_str_with_passphrase(
+            network,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "TREZOR",
+        )
+        .unwrap();
+        assert_eq!(
+            vector.master_xpriv.to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+
+        // The passphrase is stored along with the mnemonic.
+        let tmp_dir = tmp_dir();
+        fs::create_dir_all(&tmp_dir).unwrap();
+        protected.store(&tmp_dir, network, &secp).unwrap();
+        signer.store(&tmp_dir, network, &secp).unwrap();
+        let fingerprints: HashSet<_> = HotSigner::from_datadir(&tmp_dir, network)
+            .unwrap()
+            .into_iter()
+            .map(|signer| signer.fingerprint(&secp))
+            .collect();
+        assert_eq!(
+            fingerprints,
+            vec![signer.fingerprint(&secp), protected.fingerprint(&secp)]
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn hot_signer_encrypted_storage() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let tmp_dir = tmp_dir();
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let network = bitcoin::Network::Bitcoin;
+
+        let signer = HotSigner::generate(network)
+            .unwrap()
+            .with_passphrase("passphrase")
+            .unwrap();
+        signer
+            .store_encrypted(&tmp_dir, network, &secp, "password")
+            .unwrap();
+
+        // The mnemonic isn't stored in plaintext.
+        let path = HotSigner::mnemonics_folder(&tmp_dir, network)
+            .join(format!("mnemonic-{:x}.txt", signer.fingerprint(&secp)));
+        let content = fs::read(path).unwrap();
+        assert!(content.starts_with(ENCRYPTED_MNEMONIC_MAGIC));
+        assert!(!String::from_utf8_lossy(&content).contains(&signer.mnemonic_str()));
+
+        // It can't be read without the right password.
+        assert!(matches!(
+            HotSigner::from_datadir(&tmp_dir, network),
+            Err(SignerError::EncryptedMnemonic)
+        ));
+        assert!(matches!(
+            HotSigner::from_datadir_encrypted(&tmp_dir, network, "wrong"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+        let read = HotSigner::from_datadir_encrypted(&tmp_dir, network, "password").unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].fingerprint(&secp), signer.fingerprint(&secp));
+
+        // The plaintext mnemonics are still read along with the encrypted ones.
+        let plaintext = HotSigner::generate(network).unwrap();
+        plaintext.store(&tmp_dir, network, &secp).unwrap();
+        let fingerprints: HashSet<_> =
+            HotSigner::from_datadir_encrypted(&tmp_dir, network, "password")
+                .unwrap()
+                .into_iter()
+                .map(|signer| signer.fingerprint(&secp))
+                .collect();
+        assert_eq!(
+            fingerprints,
+            vec![signer.fingerprint(&secp), plaintext.fingerprint(&secp)]
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+
+        // A truncated file is reported as a decryption failure, not a storage one.
+        let mut truncated = encrypt_mnemonic(b"content", "password").unwrap();
+        truncated.truncate(ENCRYPTED_MNEMONIC_MAGIC.len() + 4);
+        assert!(matches!(
+            decrypt_mnemonic(&truncated, "password"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+
+        // So is a file with a cost parameter too high to be ours.
+        let mut costly = encrypt_mnemonic(b"content", "password").unwrap();
+        costly[ENCRYPTED_MNEMONIC_MAGIC.len() + 1] = SCRYPT_MAX_LOG_N + 1;
+        assert!(matches!(
+            decrypt_mnemonic(&costly, "password"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn hot_signer_sign() {
         let secp = secp256k1::Secp256k1::new();
@@ -575,6 +1177,199 @@ mod tests {
         let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
         assert!(psbt.inputs[0].partial_sigs.is_empty());
         assert_eq!(psbt.inputs[1].partial_sigs.len(), 2);
+
+        // The sighash type set in the PSBT input is used for signing it. It defaults to ALL.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[1].sighash_type =
+            Some(sighash::EcdsaSighashType::SinglePlusAnyoneCanPay.into());
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert!(psbt.inputs[0]
+            .partial_sigs
+            .values()
+            .all(|sig| sig.hash_ty == sighash::EcdsaSighashType::All));
+        assert!(psbt.inputs[1]
+            .partial_sigs
+            .values()
+            .all(|sig| sig.hash_ty == sighash::EcdsaSighashType::SinglePlusAnyoneCanPay));
+
+        // A non-standard sighash type is refused.
+        let mut psbt = dummy_psbt.clone();
+        psbt.inputs[0].sighash_type = Some(sighash::PsbtSighashType::from_u32(0x42));
+        assert!(prim_signer_a
+            .sign_psbt(psbt, &secp)
+            .unwrap_err()
+            .to_string()
+            .contains("Information contained in the PSBT is wrong"));
+
+        // In a collaborative transaction, some inputs may belong to someone else. They are left
+        // untouched, even if they miss the information we'd need to sign them.
+        let foreign_signer = HotSigner::generate(network).unwrap();
+        let foreign_der = bip32::DerivationPath::from_str("m/84'/0'/0'/0/7").unwrap();
+        let foreign_key = foreign_signer.xpub_at(&foreign_der, &secp).public_key;
+        let mut mixed_psbt = dummy_psbt.clone();
+        mixed_psbt.unsigned_tx.input.push(bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint::from_str(
+                "6613e078e4cdbb0fce1bc6e44b028f0e11621a134a1605efdc456c32d155c922:3",
+            )
+            .unwrap(),
+            ..bitcoin::TxIn::default()
+        });
+        mixed_psbt.inputs.push(PsbtIn {
+            bip32_derivation: vec![(
+                foreign_key,
+                (foreign_signer.fingerprint(&secp), foreign_der),
+            )]
+            .into_iter()
+            .collect(),
+            ..PsbtIn::default()
+        });
+        let psbt = prim_signer_a.sign_psbt(mixed_psbt.clone(), &secp).unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+        assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+        assert_eq!(psbt.inputs[2], mixed_psbt.inputs[2]);
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 3);
+        assert_eq!(psbt.inputs[1].partial_sigs.len(), 3);
+        assert!(psbt.inputs[2].partial_sigs.is_empty());
+
+        // The signatures of our inputs commit to the whole transaction, including the foreign
+        // input: they differ from the ones made without it.
+        let alone_psbt = prim_signer_a.sign_psbt(dummy_psbt.clone(), &secp).unwrap();
+        let mixed_psbt = prim_signer_a.sign_psbt(mixed_psbt, &secp).unwrap();
+        assert_ne!(
+            alone_psbt.inputs[0].partial_sigs,
+            mixed_psbt.inputs[0].partial_sigs
+        );
+    }
+
+    #[test]
+    fn hot_signer_sign_taproot() {
+        let secp = secp256k1::Secp256k1::new();
+        let network = bitcoin::Network::Bitcoin;
+        let (prim_signer_a, prim_signer_b, recov_signer) = (
+            HotSigner::generate(network).unwrap(),
+            HotSigner::generate(network).unwrap(),
+            HotSigner::generate(network).unwrap(),
+        );
+        let multi_key = |signer: &HotSigner, origin_der: &str| {
+            let origin_der = bip32::DerivationPath::from_str(origin_der).unwrap();
+            DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+                origin: Some((signer.fingerprint(&secp), origin_der.clone())),
+                xkey: signer.xpub_at(&origin_der, &secp),
+                derivation_paths: DerivPaths::new(vec![
+                    bip32::DerivationPath::from_str("m/0").unwrap(),
+                    bip32::DerivationPath::from_str("m/1").unwrap(),
+                ])
+                .unwrap(),
+                wildcard: Wildcard::Unhardened,
+            })
+        };
+        let mut recovery_paths = BTreeMap::new();
+        recovery_paths.insert(
+            42,
+            descriptors::LianaDescKeys::from_single(multi_key(&recov_signer, "m/48'/0'/0'/2'")),
+        );
+
+        // A PSBT spending a coin from the given descriptor to an external address.
+        let psbt_for = |desc: &descriptors::MultipathDescriptor| {
+            let spent_coin_desc = desc.receive_descriptor().derive(42.into(), &secp);
+            let mut psbt_in = PsbtIn {
+                witness_utxo: Some(bitcoin::TxOut {
+                    value: 19_000,
+                    script_pubkey: spent_coin_desc.script_pubkey(),
+                }),
+                ..PsbtIn::default()
+            };
+            spent_coin_desc.update_psbt_in(&mut psbt_in);
+            Psbt {
+                unsigned_tx: bitcoin::Transaction {
+                    version: 2,
+                    lock_time: bitcoin::PackedLockTime(0),
+                    input: vec![bitcoin::TxIn {
+                        sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                        previous_output: bitcoin::OutPoint::from_str(
+                            "4613e078e4cdbb0fce1bc6e44b028f0e11621a134a1605efdc456c32d155c922:19",
+                        )
+                        .unwrap(),
+                        ..bitcoin::TxIn::default()
+                    }],
+                    output: vec![bitcoin::TxOut {
+                        value: 18_420,
+                        script_pubkey: bitcoin::Address::from_str(
+                            "bc1qvklensptw5lk7d470ds60pcpsr0psdpgyvwepv",
+                        )
+                        .unwrap()
+                        .script_pubkey(),
+                    }],
+                },
+                version: 0,
+                xpub: BTreeMap::new(),
+                proprietary: BTreeMap::new(),
+                unknown: BTreeMap::new(),
+                inputs: vec![psbt_in],
+                outputs: vec![Default::default()],
+            }
+        };
+
+        // With a single key as primary path, it's spent through the key path.
+        let desc = descriptors::MultipathDescriptor::new_taproot(
+            descriptors::LianaDescKeys::from_single(multi_key(&prim_signer_a, "m/48'/0'/0'/2'")),
+            recovery_paths.clone(),
+        )
+        .unwrap();
+        assert!(desc.is_taproot());
+        let psbt = psbt_for(&desc);
+        assert!(psbt.inputs[0].tap_internal_key.is_some());
+        assert_eq!(psbt.inputs[0].tap_scripts.len(), 1);
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert!(psbt.inputs[0].tap_key_sig.is_some());
+        assert!(psbt.inputs[0].tap_script_sigs.is_empty());
+        assert!(psbt.inputs[0].partial_sigs.is_empty());
+        // The recovery signer signs for the leaf of the recovery path.
+        let psbt = recov_signer.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 1);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert_eq!(info.primary_path().sigs_count, 1);
+        let mut final_psbt = psbt.clone();
+        final_psbt.finalize_mut(&secp).unwrap();
+        assert_eq!(
+            final_psbt.inputs[0]
+                .final_script_witness
+                .as_ref()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // With a multisig as primary path, the internal key is unspendable and the primary path
+        // is spent through its leaf.
+        let desc = descriptors::MultipathDescriptor::new_taproot(
+            descriptors::LianaDescKeys::from_multi(
+                2,
+                vec![
+                    multi_key(&prim_signer_a, "m/48'/0'/0'/2'"),
+                    multi_key(&prim_signer_b, "m/48'/0'/0'/2'"),
+                ],
+            )
+            .unwrap(),
+            recovery_paths,
+        )
+        .unwrap();
+        let psbt = psbt_for(&desc);
+        assert_eq!(psbt.inputs[0].tap_scripts.len(), 2);
+        assert_eq!(psbt.inputs[0].tap_key_origins.len(), 3);
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 1);
+        let mut final_psbt = psbt.clone();
+        final_psbt.finalize_mut(&secp).unwrap_err();
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 2);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert_eq!(info.primary_path().sigs_count, 2);
+        assert_eq!(info.primary_path().threshold, 2);
+        let mut final_psbt = psbt;
+        final_psbt.finalize_mut(&secp).unwrap();
     }
 
     #[test]
@@ -602,4 +1397,22 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn signer_zeroize() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let mut signer = HotSigner::from_str(
+            bitcoin::Network::Bitcoin,
+            "burger ball theme dog light account produce chest warrior swarm flip equip",
+        )
+        .unwrap();
+        let fingerprint = signer.fingerprint(&secp);
+
+        signer.zeroize();
+        assert_eq!(
+            signer.mnemonic_str(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_ne!(signer.fingerprint(&secp), fingerprint);
+    }
 }
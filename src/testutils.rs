@@ -1,5 +1,5 @@
 use crate::{
-    bitcoin::{BitcoinInterface, Block, BlockChainTip, UTxO},
+    bitcoin::{BitcoinInterface, Block, BlockChainTip, MempoolEntry, UTxO, UtxoSetScan},
     config::{BitcoinConfig, Config},
     database::{Coin, CoinType, DatabaseConnection, DatabaseInterface, SpendBlock},
     descriptors, DaemonHandle,
@@ -18,6 +18,11 @@ use miniscript::{
 
 pub struct DummyBitcoind {
     pub txs: HashMap<Txid, (Transaction, Option<Block>)>,
+    pub utxo_set: Vec<(bitcoin::OutPoint, bitcoin::Amount)>,
+    pub mempool_min_feerate: u64,
+    /// Feerate estimate in sats/vb per confirmation target.
+    pub fee_estimates: HashMap<u16, u64>,
+    pub mempool_entries: HashMap<Txid, MempoolEntry>,
 }
 
 impl DummyBitcoind {}
@@ -26,64 +31,75 @@ impl DummyBitcoind {
     pub fn new() -> Self {
         Self {
             txs: HashMap::new(),
+            utxo_set: Vec::new(),
+            mempool_min_feerate: 1,
+            fee_estimates: HashMap::new(),
+            mempool_entries: HashMap::new(),
         }
     }
 }
 
 impl BitcoinInterface for DummyBitcoind {
-    fn genesis_block(&self) -> BlockChainTip {
+    fn genesis_block(&self) -> Result<BlockChainTip, String> {
         let hash = bitcoin::BlockHash::from_str(
             "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
         )
         .unwrap();
-        BlockChainTip { hash, height: 0 }
+        Ok(BlockChainTip { hash, height: 0 })
     }
 
-    fn sync_progress(&self) -> f64 {
-        1.0
+    fn ping(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sync_progress(&self) -> Result<f64, String> {
+        Ok(1.0)
     }
 
-    fn chain_tip(&self) -> BlockChainTip {
+    fn chain_tip(&self) -> Result<BlockChainTip, String> {
         let hash = bitcoin::BlockHash::from_str(
             "000000007bc154e0fa7ea32218a72fe2c1bb9f86cf8c9ebf9a715ed27fdb229a",
         )
         .unwrap();
         let height = 100;
-        BlockChainTip { hash, height }
+        Ok(BlockChainTip { hash, height })
     }
 
-    fn is_in_chain(&self, _: &BlockChainTip) -> bool {
+    fn is_in_chain(&self, _: &BlockChainTip) -> Result<bool, String> {
         // No reorg
-        true
+        Ok(true)
     }
 
     fn received_coins(
         &self,
         _: &BlockChainTip,
         _: &[descriptors::InheritanceDescriptor],
-    ) -> Vec<UTxO> {
-        Vec::new()
+    ) -> Result<Vec<UTxO>, String> {
+        Ok(Vec::new())
     }
 
     fn confirmed_coins(
         &self,
         _: &[bitcoin::OutPoint],
-    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
-        (Vec::new(), Vec::new())
+    ) -> Result<(Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>), String> {
+        Ok((Vec::new(), Vec::new()))
     }
 
-    fn spending_coins(&self, _: &[bitcoin::OutPoint]) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
-        Vec::new()
+    fn spending_coins(
+        &self,
+        _: &[bitcoin::OutPoint],
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid)>, String> {
+        Ok(Vec::new())
     }
 
     fn spent_coins(
         &self,
         _: &[(bitcoin::OutPoint, bitcoin::Txid)],
-    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)> {
-        Vec::new()
+    ) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Txid, Block)>, String> {
+        Ok(Vec::new())
     }
 
-    fn common_ancestor(&self, _: &BlockChainTip) -> Option<BlockChainTip> {
+    fn common_ancestor(&self, _: &BlockChainTip) -> Result<Option<BlockChainTip>, String> {
         todo!()
     }
 
@@ -91,27 +107,79 @@ impl BitcoinInterface for DummyBitcoind {
         todo!()
     }
 
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Result<Option<MempoolEntry>, String> {
+        Ok(self.mempool_entries.get(txid).copied())
+    }
+
     fn start_rescan(&self, _: &descriptors::MultipathDescriptor, _: u32) -> Result<(), String> {
         todo!()
     }
 
-    fn rescan_progress(&self) -> Option<f64> {
-        None
+    fn start_rescan_from_height(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+        _: i32,
+    ) -> Result<u32, String> {
+        todo!()
+    }
+
+    fn rescan_progress(&self) -> Result<Option<f64>, String> {
+        Ok(None)
     }
 
-    fn block_before_date(&self, _: u32) -> Option<BlockChainTip> {
+    fn block_before_date(&self, _: u32) -> Result<Option<BlockChainTip>, String> {
         todo!()
     }
 
-    fn tip_time(&self) -> u32 {
+    fn tip_time(&self) -> Result<u32, String> {
         todo!()
     }
 
+    fn mempool_min_feerate(&self) -> Result<u64, String> {
+        Ok(self.mempool_min_feerate)
+    }
+
+    fn estimate_feerate(&self, conf_target: u16) -> Result<Option<u64>, String> {
+        Ok(self.fee_estimates.get(&conf_target).copied())
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
-    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
-        self.txs.get(txid).cloned()
+    ) -> Result<Option<(bitcoin::Transaction, Option<Block>)>, String> {
+        Ok(self.txs.get(txid).cloned())
+    }
+
+    fn utxo_set_scan(&self, _: &descriptors::MultipathDescriptor, _: u32) -> UtxoSetScan {
+        let utxo_set = self.utxo_set.clone();
+        Box::new(move || Ok(utxo_set))
+    }
+
+    fn watched_range_end(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+    ) -> Result<Option<u32>, String> {
+        Ok(None)
+    }
+
+    fn extend_watched_range(
+        &self,
+        _: &descriptors::MultipathDescriptor,
+        _: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn address_labels(&self) -> Result<Vec<(bitcoin::Address, String)>, String> {
+        Ok(Vec::new())
+    }
+
+    fn generate_to_address(
+        &self,
+        _: u32,
+        _: &bitcoin::Address,
+    ) -> Result<Vec<bitcoin::BlockHash>, String> {
+        todo!()
     }
 }
 
@@ -271,6 +339,14 @@ impl DatabaseConnection for DummyDatabase {
         None
     }
 
+    fn address_by_derivation_index(
+        &mut self,
+        _: bip32::ChildNumber,
+        _: bool,
+    ) -> Option<bitcoin::Address> {
+        None
+    }
+
     fn coins_by_outpoints(
         &mut self,
         outpoints: &[bitcoin::OutPoint],
@@ -397,6 +473,9 @@ impl DummyLiana {
         let bitcoin_config = BitcoinConfig {
             network,
             poll_interval_secs: time::Duration::from_secs(2),
+            final_spend_confirmations: 6,
+            cleanup_spends: false,
+            gap_limit: 200,
         };
 
         let owner_key = descriptors::LianaDescKeys::from_single(descriptor::DescriptorPublicKey::from_str("[aabbccdd]xpub68JJTXc1MWK8KLW4HGLXZBJknja7kDUJuFHnM424LbziEXsfkh1WQCiEjjHw4zLqSUm4rvhgyGkkuRowE9tCJSgt3TQB5J3SKAbZ2SdcKST/<0;1>/*").unwrap());
@@ -405,6 +484,7 @@ impl DummyLiana {
         let config = Config {
             bitcoin_config,
             bitcoind_config: None,
+            electrum_config: None,
             data_dir: Some(data_dir),
             #[cfg(unix)]
             daemon: false,
@@ -147,6 +147,44 @@ def lianad(bitcoind, directory):
     lianad.cleanup()
 
 
+@pytest.fixture
+def lianad_locked_recovery(bitcoind, directory):
+    """A Liana daemon whose recovery path also requires an absolute timelock. The height it
+    is available from is set as the 'recovery_locktime' attribute."""
+    datadir = os.path.join(directory, "lianad")
+    os.makedirs(datadir, exist_ok=True)
+    bitcoind_cookie = os.path.join(bitcoind.bitcoin_dir, "regtest", ".cookie")
+
+    signer = SingleSigner()
+    primary_xpub, recovery_xpub = (
+        signer.primary_hd.get_xpub(),
+        signer.recovery_hd.get_xpub(),
+    )
+    csv_value = 10
+    locktime = bitcoind.rpc.getblockcount() + 20
+    main_desc = Descriptor.from_str(
+        f"wsh(or_d(pk([aabbccdd]{primary_xpub}/<0;1>/*),and_v(v:pkh([aabbccdd]{recovery_xpub}/<0;1>/*),and_v(v:after({locktime}),older({csv_value})))))"
+    )
+
+    lianad = Lianad(
+        datadir,
+        signer,
+        main_desc,
+        bitcoind.rpcport,
+        bitcoind_cookie,
+    )
+    lianad.recovery_locktime = locktime
+
+    try:
+        lianad.start()
+        yield lianad
+    except Exception:
+        lianad.cleanup()
+        raise
+
+    lianad.cleanup()
+
+
 def multi_expression(thresh, keys):
     exp = f"multi({thresh},"
     for i, key in enumerate(keys):
@@ -297,3 +297,28 @@ def test_deposit_replacement(lianad, bitcoind):
     addr = lianad.rpc.getnewaddress()["address"]
     bitcoind.rpc.sendtoaddress(addr, 2)
     wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+
+
+def test_watched_range_extension(lianad, bitcoind):
+    """Test the range of addresses watched by bitcoind is extended as we hand out addresses,
+    for the coins paid past the range initially imported to be detected."""
+    addresses_info = lianad.rpc.getinfo()["addresses"]
+    assert addresses_info["gap_limit"] == 200
+    assert addresses_info["watched_range_end"] == 999
+
+    # Once the addresses up to the gap limit past the highest index aren't all watched, the
+    # range is extended.
+    for _ in range(800):
+        lianad.rpc.getnewaddress()
+    lianad.wait_for_log("extending the watched range from 999 to 1200")
+    wait_for(lambda: lianad.rpc.getinfo()["addresses"]["watched_range_end"] == 1200)
+
+    # A coin paid to an address past the initial range is detected.
+    for _ in range(201):
+        res = lianad.rpc.getnewaddress()
+    assert res["derivation_index"] == 1000
+    txid = bitcoind.rpc.sendtoaddress(res["address"], 0.1)
+    wait_for(
+        lambda: any(txid in c["outpoint"] for c in lianad.rpc.listcoins()["coins"])
+    )
+    lianad.wait_for_log("extending the watched range from 1200 to 1401")
@@ -75,14 +75,26 @@ def test_multisig(lianad_multisig, bitcoind):
 
 
 def test_coinbase_deposit(lianad, bitcoind):
-    """Check we detect deposits from (mature) coinbase transactions."""
-    # Create a new deposit in a coinbase transaction.
+    """Check we detect deposits from coinbase transactions, and only spend them once mature."""
+    # Create a new deposit in a coinbase transaction. We detect it right away, as immature.
     addr = lianad.rpc.getnewaddress()["address"]
     bitcoind.rpc.generatetoaddress(1, addr)
-    assert len(lianad.rpc.listcoins()["coins"]) == 0
+    cb_height = bitcoind.rpc.getblockcount()
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    coin = lianad.rpc.listcoins()["coins"][0]
+    assert coin["is_immature"]
 
-    # Generate 100 blocks to make the coinbase mature.
-    bitcoind.generate_block(100)
+    # We can't spend it yet.
+    destinations = {bitcoind.rpc.getnewaddress(): 100_000}
+    with pytest.raises(RpcError, match="isn't mature yet"):
+        lianad.rpc.createspend(destinations, [coin["outpoint"]], 2)
 
-    # We must have detected a new deposit.
-    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    # It can be spent in a block 100 blocks after the one it was created in. It's still immature
+    # if the next block is only 99 blocks after it.
+    bitcoind.generate_block(98)
+    wait_for(lambda: lianad.rpc.getinfo()["block_height"] == cb_height + 98)
+    assert lianad.rpc.listcoins()["coins"][0]["is_immature"]
+    bitcoind.generate_block(1)
+    wait_for(lambda: lianad.rpc.getinfo()["block_height"] == cb_height + 99)
+    assert not lianad.rpc.listcoins()["coins"][0]["is_immature"]
+    lianad.rpc.createspend(destinations, [coin["outpoint"]], 2)
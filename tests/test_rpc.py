@@ -1,8 +1,10 @@
 import pytest
 import random
 import time
+import urllib.parse
 
 from fixtures import *
+from test_framework.bitcoind import BitcoindRpcInterface
 from test_framework.serializations import (
     PSBT,
     PSBT_IN_PARTIAL_SIG,
@@ -27,6 +29,10 @@ def test_getinfo(lianad):
     assert res["sync"] == 1.0
     assert "main" in res["descriptors"]
     assert res["rescan_progress"] is None
+    assert res["mempool_min_feerate"] == 1
+    assert res["addresses"]["gap_limit"] == 200
+    assert res["addresses"]["receive_index"] == 0
+    assert res["addresses"]["used_receive_index"] is None
 
 
 def test_getaddress(lianad):
@@ -52,6 +58,8 @@ def test_listcoins(lianad, bitcoind):
     assert res[0]["amount"] == 1 * COIN
     assert res[0]["block_height"] is None
     assert res[0]["spend_info"] is None
+    # The address it was paid to is now a used one.
+    assert lianad.rpc.getinfo()["addresses"]["used_receive_index"] == 0
 
     # If the coin gets confirmed, it'll be marked as such.
     bitcoind.generate_block(1, wait_for_mempool=txid)
@@ -65,6 +73,7 @@ def test_listcoins(lianad, bitcoind):
     spend_info = lianad.rpc.listcoins()["coins"][0]["spend_info"]
     assert spend_info["txid"] == spend_txid
     assert spend_info["height"] is None
+    assert not spend_info["settled"]
 
     # And if this spending tx gets confirmed.
     bitcoind.generate_block(1, wait_for_mempool=spend_txid)
@@ -73,6 +82,33 @@ def test_listcoins(lianad, bitcoind):
     spend_info = lianad.rpc.listcoins()["coins"][0]["spend_info"]
     assert spend_info["txid"] == spend_txid
     assert spend_info["height"] == curr_height
+    assert not spend_info["settled"]
+
+    # Once it reached the default number of confirmations, the spend is final.
+    bitcoind.generate_block(5)
+    wait_for(lambda: lianad.rpc.listcoins()["coins"][0]["spend_info"]["settled"])
+
+
+def test_listcoins_replaceable(lianad, bitcoind):
+    # An unconfirmed payment signaling RBF is marked as replaceable.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.1, "", "", False, True)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    assert lianad.rpc.listcoins()["coins"][0]["is_replaceable"]
+
+    # Once confirmed, it can't be replaced anymore.
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: lianad.rpc.listcoins()["coins"][0]["block_height"] is not None)
+    assert not lianad.rpc.listcoins()["coins"][0]["is_replaceable"]
+
+    # A payment which doesn't signal RBF isn't marked as replaceable.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.1, "", "", False, False)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 2)
+    coin = next(
+        c for c in lianad.rpc.listcoins()["coins"] if c["outpoint"][:64] == txid
+    )
+    assert not coin["is_replaceable"]
 
 
 def test_jsonrpc_server(lianad, bitcoind):
@@ -133,6 +169,71 @@ def test_create_spend(lianad, bitcoind):
     sign_and_broadcast(lianad, bitcoind, PSBT.from_base64(res["psbt"]))
 
 
+def test_preview_spend(lianad, bitcoind):
+    """We can get the fee and size of a Spend transaction without creating it."""
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in lianad.rpc.listcoins()["coins"]]
+    destinations = {
+        bitcoind.rpc.getnewaddress(): 200_000,
+    }
+
+    # The preview gives the fee of the transaction createspend creates.
+    preview = lianad.rpc.previewspend(destinations, outpoints, 18)
+    res = lianad.rpc.createspend(destinations, outpoints, 18)
+    spend_psbt = PSBT.from_base64(res["psbt"])
+    assert len(spend_psbt.tx.vout) == 2
+    fee = 1_000_000 - sum(o.nValue for o in spend_psbt.tx.vout)
+    assert preview["fee"] == fee
+    assert preview["feerate_vb"] == fee // preview["vsize"]
+
+    # Once signed, the transaction is not larger than previewed.
+    txid = sign_and_broadcast(lianad, bitcoind, spend_psbt)
+    assert bitcoind.rpc.getmempoolentry(txid)["vsize"] <= preview["vsize"]
+
+
+def test_create_spend_change(lianad, bitcoind):
+    """We can choose where the change of a Spend transaction goes."""
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in lianad.rpc.listcoins()["coins"]]
+    destinations = {
+        bitcoind.rpc.getnewaddress(): 200_000,
+    }
+
+    # By default, and with 'auto', the change goes to a new change address.
+    res = lianad.rpc.createspend(destinations, outpoints, 2)
+    change_spk = PSBT.from_base64(res["psbt"]).tx.vout[1].scriptPubKey
+    res = lianad.rpc.createspend(destinations, outpoints, 2, "auto")
+    assert PSBT.from_base64(res["psbt"]).tx.vout[1].scriptPubKey != change_spk
+
+    # It can be sent to an address of ours instead.
+    our_addr = lianad.rpc.getnewaddress()["address"]
+    res = lianad.rpc.createspend(destinations, outpoints, 2, our_addr)
+    spend_psbt = PSBT.from_base64(res["psbt"])
+    assert len(spend_psbt.tx.vout) == 2
+    assert spend_psbt.tx.vout[1].scriptPubKey == bytes.fromhex(
+        bitcoind.rpc.getaddressinfo(our_addr)["scriptPubKey"]
+    )
+
+    # Or not be created at all.
+    res = lianad.rpc.createspend(destinations, outpoints, 2, "none")
+    spend_psbt = PSBT.from_base64(res["psbt"])
+    assert len(spend_psbt.tx.vout) == 1
+
+    # But we won't send it to an address which is not ours.
+    with pytest.raises(RpcError, match="Change address '.*' is not one of ours."):
+        lianad.rpc.createspend(destinations, outpoints, 2, bitcoind.rpc.getnewaddress())
+
+    # We can sign and broadcast the Spend with change to our address.
+    res = lianad.rpc.createspend(destinations, outpoints, 2, our_addr)
+    sign_and_broadcast(lianad, bitcoind, PSBT.from_base64(res["psbt"]))
+
+
 def test_list_spend(lianad, bitcoind):
     # Start by creating two conflicting Spend PSBTs. The first one will have a change
     # output but not the second one.
@@ -367,6 +468,45 @@ def test_start_rescan(lianad, bitcoind):
     assert lianad.rpc.getnewaddress() not in (first_address, second_address)
 
 
+def test_start_rescan_from_height(lianad, bitcoind):
+    """Test we retrieve our transactions since a given block height by rescanning."""
+    list_coins = lambda: lianad.rpc.listcoins()["coins"]
+
+    # We can't rescan from an insane height.
+    tip_height = bitcoind.rpc.getblockcount()
+    for height in (0, -1, tip_height + 1):
+        with pytest.raises(RpcError, match="Insane block height.*"):
+            lianad.rpc.startrescanfromheight(height)
+        assert lianad.rpc.getinfo()["rescan_progress"] is None
+
+    # Receive a coin in the block right after the current tip.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.5)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    rescan_height = tip_height + 1
+    wait_for(lambda: len(list_coins()) == 1)
+    coins_before = list_coins()
+
+    # Move time forward one day as bitcoind will rescan the last 2 hours of block upon
+    # importing a descriptor.
+    bitcoind.rpc.setmocktime(int(time.time()) + 60 * 60 * 24)
+    bitcoind.generate_block(10)
+    lianad.restart_fresh(bitcoind)
+    assert len(list_coins()) == 0
+
+    # Rescanning from the height of the block the coin was received at finds it again.
+    res = lianad.rpc.startrescanfromheight(rescan_height)
+    block_time = bitcoind.rpc.getblockheader(bitcoind.rpc.getblockhash(rescan_height))[
+        "time"
+    ]
+    assert res["timestamp"] < block_time
+    wait_for(lambda: lianad.rpc.getinfo()["rescan_progress"] is None)
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    assert list_coins() == coins_before
+
+
 def test_listtransactions(lianad, bitcoind):
     """Test listing of transactions by txid and timespan"""
 
@@ -558,6 +698,13 @@ def test_create_recovery(lianad, bitcoind):
     ):
         lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 2)
 
+    # We can't sweep through a recovery path that isn't in our descriptor
+    with pytest.raises(
+        RpcError,
+        match="There is no recovery path with a timelock of 42 blocks",
+    ):
+        lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 2, 42)
+
     # Receive another coin, it will be one block after the others
     txid = bitcoind.rpc.sendtoaddress(lianad.rpc.getnewaddress()["address"], 0.4)
 
@@ -569,6 +716,8 @@ def test_create_recovery(lianad, bitcoind):
     reco_psbt = PSBT.from_base64(res["psbt"])
     assert len(reco_psbt.tx.vin) == 3, "The last coin's timelock hasn't matured yet"
     assert len(reco_psbt.tx.vout) == 1
+    # The relative timelock of the inputs is only enforced from version 2.
+    assert reco_psbt.tx.nVersion == 2
     assert int(0.5999 * COIN) < int(reco_psbt.tx.vout[0].nValue) < int(0.6 * COIN)
     txid = sign_and_broadcast(lianad, bitcoind, reco_psbt, recovery=True)
 
@@ -577,9 +726,160 @@ def test_create_recovery(lianad, bitcoind):
     wait_for(
         lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
     )
-    res = lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 1)
+    res = lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 1, 10)
     reco_psbt = PSBT.from_base64(res["psbt"])
     assert len(reco_psbt.tx.vin) == 1
     assert len(reco_psbt.tx.vout) == 1
     assert int(0.39999 * COIN) < int(reco_psbt.tx.vout[0].nValue) < int(0.4 * COIN)
     sign_and_broadcast(lianad, bitcoind, reco_psbt, recovery=True)
+
+
+def test_create_recovery_absolute_timelock(lianad_locked_recovery, bitcoind):
+    """The recovery path may also require an absolute timelock to be reached."""
+    lianad = lianad_locked_recovery
+    locktime = lianad.recovery_locktime
+    txid = bitcoind.rpc.sendtoaddress(lianad.rpc.getnewaddress()["address"], 0.1)
+    bitcoind.generate_block(10, wait_for_mempool=txid)
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+
+    # The relative timelock of the coin is reached, but not the absolute one.
+    assert bitcoind.rpc.getblockcount() < locktime
+    with pytest.raises(
+        RpcError,
+        match=f"only available after block {locktime}",
+    ):
+        lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 2)
+
+    # Once it is, the recovery transaction sets its nLockTime and is valid.
+    bitcoind.generate_block(locktime - bitcoind.rpc.getblockcount())
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    res = lianad.rpc.createrecovery(bitcoind.rpc.getnewaddress(), 2)
+    reco_psbt = PSBT.from_base64(res["psbt"])
+    assert len(reco_psbt.tx.vin) == 1
+    assert reco_psbt.tx.nLockTime == locktime
+    txid = sign_and_broadcast(lianad, bitcoind, reco_psbt, recovery=True)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+
+
+def test_create_refresh(lianad, bitcoind):
+    """Test the refresh of the coins whose recovery path is soon available."""
+    # Get two coins, and wait a few blocks
+    destinations = {
+        lianad.rpc.getnewaddress()["address"]: 0.1,
+        lianad.rpc.getnewaddress()["address"]: 0.2,
+    }
+    txid = bitcoind.rpc.sendmany("", destinations)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    bitcoind.generate_block(5)
+
+    # Receive another coin, confirmed 6 blocks after the others
+    txid = bitcoind.rpc.sendtoaddress(lianad.rpc.getnewaddress()["address"], 0.3)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+
+    # The recovery path of the first two coins is available in 3 blocks (we use a csv of
+    # 10 in the fixture), of the last one in 9 blocks.
+    with pytest.raises(RpcError, match="No coin's recovery path becomes available"):
+        lianad.rpc.createrefresh(2, 2)
+    # The fixture only has a recovery path with a timelock of 10 blocks.
+    with pytest.raises(
+        RpcError, match="There is no recovery path with a timelock of 42 blocks"
+    ):
+        lianad.rpc.createrefresh(3, 2, 42)
+    res = lianad.rpc.createrefresh(3, 2, 10)
+    refresh_psbt = PSBT.from_base64(res["psbt"])
+    assert len(refresh_psbt.tx.vin) == 2
+    assert len(refresh_psbt.tx.vout) == 1
+    assert int(0.2999 * COIN) < int(refresh_psbt.tx.vout[0].nValue) < int(0.3 * COIN)
+    txid = sign_and_broadcast(lianad, bitcoind, refresh_psbt)
+
+    # Once confirmed, the timelock of the refreshed coin restarted.
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    res = lianad.rpc.createrefresh(8, 2)
+    refresh_psbt = PSBT.from_base64(res["psbt"])
+    assert len(refresh_psbt.tx.vin) == 1
+    assert int(0.2999 * COIN) < int(refresh_psbt.tx.vout[0].nValue) < int(0.3 * COIN)
+
+
+def test_check_balance(lianad, bitcoind):
+    # Initially both are empty.
+    res = lianad.rpc.checkbalance()
+    assert res["tracked_balance"] == 0
+    assert res["utxo_set_balance"] == 0
+
+    # An unconfirmed coin isn't part of the UTxO set, and is not considered.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 1)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    res = lianad.rpc.checkbalance()
+    assert res["tracked_balance"] == res["utxo_set_balance"] == 0
+
+    # Once confirmed, both balances match.
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: lianad.rpc.listcoins()["coins"][0]["block_height"] is not None)
+    res = lianad.rpc.checkbalance()
+    assert res["tracked_balance"] == res["utxo_set_balance"] == 1 * COIN
+    assert res["untracked"] == res["missing"] == []
+
+
+def test_estimate_feerate(lianad, bitcoind):
+    # A fresh regtest node does not have enough data to estimate a feerate.
+    res = lianad.rpc.estimatefeerate([1, 6])
+    assert res["estimates"] == [
+        {"conf_target": 1, "feerate": None},
+        {"conf_target": 6, "feerate": None},
+    ]
+
+    # The confirmation targets must be within the range bitcoind estimates for.
+    with pytest.raises(RpcError, match="Invalid confirmation target: 0.*"):
+        lianad.rpc.estimatefeerate([0])
+    with pytest.raises(RpcError, match="Invalid confirmation target: 1009.*"):
+        lianad.rpc.estimatefeerate([1, 1009])
+
+
+def test_generate_blocks(lianad, bitcoind):
+    initial_height = bitcoind.rpc.getblockcount()
+    res = lianad.rpc.generateblocks(2)
+    assert len(res["blockhashes"]) == 2
+    assert bitcoind.rpc.getblockcount() == initial_height + 2
+
+    # The coinbase outputs pay to the wallet, they are immature.
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 2)
+    coins = lianad.rpc.listcoins()["coins"]
+    assert all(c["is_immature"] for c in coins)
+    assert bitcoind.rpc.getaddressinfo(res["address"])["isscript"]
+
+    # The parameter must be provided.
+    with pytest.raises(RpcError, match="Missing 'count' parameter."):
+        lianad.rpc.generateblocks()
+
+
+def test_list_backend_labels(lianad, bitcoind):
+    # No label was set in the watchonly wallet.
+    assert lianad.rpc.listbackendlabels()["labels"] == []
+
+    # Label one of our addresses and a foreign one directly in the watchonly wallet.
+    wallet_path = os.path.join(lianad.datadir, "regtest", "lianad_watchonly_wallet")
+    watchonly_rpc = BitcoindRpcInterface(
+        bitcoind.bitcoin_dir,
+        "regtest",
+        bitcoind.rpcport,
+        wallet=urllib.parse.quote(wallet_path, safe=""),
+    )
+    addr = lianad.rpc.getnewaddress()["address"]
+    watchonly_rpc.setlabel(addr, "Savings")
+    watchonly_rpc.setlabel(bitcoind.rpc.getnewaddress(), "Not ours")
+
+    # Only the label of our address is listed.
+    assert lianad.rpc.listbackendlabels()["labels"] == [
+        {"address": addr, "label": "Savings"}
+    ]
@@ -170,3 +170,22 @@ def test_coin_marked_spent(lianad, bitcoind):
         return True
 
     wait_for(lambda: all(is_spent(c) for c in deposited_coins()))
+
+
+def test_select_coins(lianad, bitcoind):
+    """The coin selection is reproducible given a seed, and funds a Spend."""
+    addrs = [lianad.rpc.getnewaddress()["address"] for _ in range(10)]
+    txid = bitcoind.rpc.sendmany("", {addr: 0.01 for addr in addrs})
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 10)
+
+    destinations = {bitcoind.rpc.getnewaddress(): 2_500_000}
+    outpoints = lianad.rpc.selectcoins(destinations, 2, 42)["outpoints"]
+    assert outpoints == lianad.rpc.selectcoins(destinations, 2, 42)["outpoints"]
+    assert len(outpoints) >= 3
+
+    # Without a seed the selection is random but still covers the destinations.
+    assert len(lianad.rpc.selectcoins(destinations, 2)["outpoints"]) >= 3
+
+    res = lianad.rpc.createspend(destinations, outpoints, 2)
+    assert "psbt" in res